@@ -14,6 +14,212 @@ _____  |  | |__|         _______  ______
 Rust implementation of [ALI](https://github.com/soyart/ali),
 the Aux Linarch Installer.
 
+## Manifest schema version
+
+ali-rs manifests carry a schema version in key `version`
+(currently `1`). Older manifests are upgraded in-memory to the
+current schema when loaded, with warnings about deprecated keys
+(e.g. `arch-chroot` is now `chroot`). Manifests without `version`
+are treated as unversioned (pre-`1`) manifests.
+
+Manifests can also be written in JSON or TOML. The format is
+detected from the manifest file extension (`.json` or `.toml`,
+defaulting to YAML), or can be given explicitly with `--format`.
+
+## Manifest includes
+
+Large manifests can be split into multiple files with key `include`,
+a path (or list of paths) relative to the including manifest:
+
+```yaml
+# laptop.yaml
+include:
+  - base.yaml
+  - hooks.yaml
+
+hostname: laptop
+pacstrap:
+  - iwd
+```
+
+Included manifests are merged in order, and the including manifest
+is merged last, so later files override earlier ones. Mappings
+(e.g. `rootfs` or `vars`) are merged key by key, lists (e.g. `pacstrap`
+or `chroot`) are appended, and other values are replaced.
+Included files may themselves include other files, and the format
+of each file is detected from its extension.
+
+## Remote manifests
+
+The manifest given with `-f` can also be an HTTP(S) URL (or
+`|`-separated mirror URLs), which is downloaded before use.
+Use `--sha256` to pin the manifest checksum, so that ali-rs aborts
+if the downloaded (or local) manifest does not match:
+
+```shell
+ali-rs apply -f https://example.com/manifest.yaml --sha256 <HASH>
+```
+
+Includes in remote manifests are resolved relative to the current
+directory.
+
+## Manifest variables
+
+String values in manifests may reference variables with `{{ name }}`,
+so that one manifest can serve many machines. Variables are looked up
+from `--var name=value` flags first, then from env `ALI_VAR_<name>`,
+then from manifest key `vars`:
+
+```yaml
+vars:
+  disk: /dev/vda
+  hostname: arch-server
+
+# YAML values starting with `{{` must be quoted
+hostname: "{{ hostname }}"
+disks:
+  - device: "{{ disk }}"
+```
+
+```shell
+ali-rs apply -f manifest.yaml --var disk=/dev/nvme0n1 --var hostname=web-01
+```
+
+Referencing an undefined variable is an error.
+
+## Disk selectors
+
+Raw `/dev/sdX` names are unstable across boots of the live ISO, so disks in
+manifest key `disks` may instead be given as a udev link, or as
+comma-separated conditions on `lsblk(8)` properties `wwn`, `serial`,
+`model`, `tran`, and `size` (which also supports `>` and `<`):
+
+```yaml
+disks:
+  # The only NVMe disk larger than 500G
+  - device: tran=nvme,size>500G
+    table: gpt
+    partitions:
+      - label: root
+        type: "83"
+  # Other examples:
+  # - device: /dev/disk/by-id/nvme-Samsung_SSD_980_PRO_1TB_S5GXNF0R123456
+  # - device: wwn=0x5002538e40a1b2c3
+  # - device: model=Samsung SSD 860,serial=S3Z1NB0K111111
+
+# Partitions of selected disks are referred to with `-part<N>` suffix
+rootfs:
+  device: tran=nvme,size>500G-part1
+  fstype: ext4
+```
+
+Selectors are resolved when the manifest is loaded, and each selector
+must match exactly 1 disk.
+
+## Aliases
+
+Some manifest and hook values have aliases, e.g. `fat32` for fstype
+`vfat`, `lvm_on_luks` for `@mkinitcpio` preset `boot_hook=lvm-on-luks`,
+or `systemd-boot` for `@rollback` bootloader `sd-boot`. Aliases are
+resolved to their canonical names when manifests and hooks are parsed,
+and mistyped values get suggestions from all known names.
+
+More aliases can be added in YAML file `/etc/ali-rs/aliases.yaml`,
+or in file given by env `ALI_ALIASES`. Each kind (`boot_hook`, `fstype`,
+or `bootloader`) maps canonical names (or their aliases) to new aliases:
+
+```yaml
+boot_hook:
+  lvm-on-luks: [fde]
+fstype:
+  vfat: [esp]
+  ntfs3: [ntfs] # any mkfs.<FS_TYPE> program
+```
+
+Aliases must not be taken by other names. Only `fstype` accepts names
+unknown to ali-rs.
+
+## Diskless installs (NFS or iSCSI root)
+
+With manifest key `netroot`, ali-rs skips local disk stages and
+installs the new system into a plain directory at the install location,
+producing a netboot-able root tree. Keys `disks`, `dm`, `fs`,
+`mountpoints`, and `swap` are not allowed.
+
+For NFS, `rootfs` describes the NFS export:
+
+```yaml
+rootfs:
+  device: 10.0.0.1:/srv/arch
+  fstype: nfs
+  mntopts: vers=4 # Appended to nfsroot= kernel parameter
+netroot:
+  type: nfs
+  ip: dhcp # Kernel ip= parameter, default is dhcp
+```
+
+For iSCSI, `rootfs` describes the root device as seen by the client:
+
+```yaml
+rootfs:
+  device: /dev/sda1
+  fstype: ext4
+netroot:
+  type: iscsi
+  target: iqn.2024-01.com.example:arch
+  portal: 10.0.0.1:3260 # Port defaults to 3260
+  initiator: iqn.2005-03.org.open-iscsi:client # Defaults to one from hostname
+```
+
+ali-rs then installs `mkinitcpio-nfs-utils` (and `nfs-utils` or `open-iscsi`),
+writes initramfs `HOOKS` with `net` (and a custom `iscsi` hook),
+the kernel command line to `/etc/kernel/cmdline`, a root entry
+in `/etc/fstab`, and regenerates the initramfs in chroot.
+
+The TFTP and NFS export (or iSCSI LUN) layout is printed, and saved to
+`/boot/ali-rs-netroot.txt` in the root tree.
+
+## Portable USB installs
+
+With `--target portable` (or manifest key `target: portable`),
+ali-rs installs to a USB stick that boots on other machines:
+
+- `/etc/fstab` and GRUB refer to filesystems by UUID
+- both `intel-ucode` and `amd-ucode` are installed,
+  instead of assuming the current machine's CPU
+- initramfs `HOOKS` omit `autodetect`, so it includes broad module coverage
+- GRUB is installed for both BIOS (`i386-pc`) and UEFI (`x86_64-efi`,
+  to the removable media path)
+
+The manifest must have exactly 1 GPT disk, with both a BIOS boot partition
+(type `ef02`) and an EFI system partition (type `ef`) mounted via `mountpoints`:
+
+```yaml
+target: portable
+disks:
+  - device: /dev/sdb
+    table: gpt
+    partitions:
+      - label: bios
+        size: 1M
+        type: ef02
+      - label: esp
+        size: 300M
+        type: ef
+      - label: root
+        type: "83"
+rootfs:
+  device: /dev/sdb3
+  fstype: ext4
+fs:
+  - device: /dev/sdb2
+    fstype: vfat
+    fsopts: -F 32
+mountpoints:
+  - device: /dev/sdb2
+    dest: /boot
+```
+
 ## ALI manifest validation
 
 ali-rs is intended to be safe to use, so it validates the manifest
@@ -28,6 +234,68 @@ and it is possible to overwrite existing system devices with
 
 ali-rs also provides [ali-rs hooks](./HOOKS.md) as an extension of ALI.
 
+## Preflight checks
+
+Before touching anything, `ali-rs apply` checks the live system:
+
+- ali-rs is running as root
+- boot mode (UEFI or BIOS) matches the manifest partitions:
+  an EFI system partition (type `ef`) expects UEFI, while a BIOS boot
+  partition (type `ef02`) or MBR-only disks expect BIOS
+- at least one of the first mirrors in `/etc/pacman.d/mirrorlist` is reachable
+- target disks are not mounted or used as swap
+- required commands (e.g. `fdisk`, `mkfs.*`, `cryptsetup`, `pacstrap`) are in `$PATH`
+
+Checks only apply to stages that will run, and all failures are
+reported at once. Preflight checks can be skipped with `--no-preflight`.
+
+No stage needs a running systemd or dbus on the live system: services
+of the new system are enabled with symlinks, configuration is edited
+directly, and `systemctl` is never called outside chroot. Preflight
+warns about optional integrations unavailable without them, e.g.
+`--enable-ssh` then starts sshd directly instead of `sshd.service`.
+
+## Disk wipe confirmation
+
+Before wiping any disk in manifest key `disks`, `ali-rs apply` prints
+its model, size, serial, WWN, and current partition layout (from `lsblk(8)`),
+and asks the operator to type the device name, e.g. `/dev/nvme0n1`,
+to confirm. Any other answer aborts the installation.
+
+For automation, use `-y` or `--yes` to skip confirmation.
+
+## Disk wiping
+
+Stale LVM, RAID, and filesystem signatures on reused disks frequently
+confuse later stages. Each disk may set `wipe` to have it wiped
+before its new partition table is created:
+
+| `wipe`       | Wipe                                                        |
+|--------------|-------------------------------------------------------------|
+| `none`       | Nothing (default)                                           |
+| `signatures` | `wipefs -a` on existing partitions, then the disk           |
+| `full`       | Signatures, then `blkdiscard` if the disk supports discard  |
+|              | (e.g. SSDs), or else zeroes its first and last MiB with `dd`|
+
+`wipe: full` is not a secure erase. Without discard support (e.g. on
+HDDs), only the first and last MiB are zeroed, and old data elsewhere
+on the disk stays readable.
+
+Disks still held by active LVM volume groups or open LUKS mappings
+(e.g. from a previous install) cannot be wiped, and ali-rs fails with
+an error instead. Deactivate them first with `vgchange -an <VG>` and
+`cryptsetup close <NAME>`.
+
+```yaml
+disks:
+  - device: /dev/nvme0n1
+    table: gpt
+    wipe: full
+    partitions:
+      - label: root
+        type: "83"
+```
+
 ## Usage
 
 Run `ali-rs -h` to get the list of all available subcommands,
@@ -36,6 +304,230 @@ or `ali-rs <subcommand> -h` to get available subcommand options.
 Currently, if no subcommand is given, ali-rs defaults to manifest
 validation which is safe to run.
 
+### Shell completions
+
+`ali-rs completions bash|zsh|fish` prints a completion script, which
+also completes hook keys and their keyword arguments inside quoted
+hook strings, e.g. `ali-rs hooks "@uncomment PORT m<TAB>` completes
+to `marker`. Source the script from the shell's startup file:
+
+```shell
+# bash (~/.bashrc)
+source <(ali-rs completions bash)
+
+# zsh (~/.zshrc, after compinit)
+source <(ali-rs completions zsh)
+
+# fish
+ali-rs completions fish > ~/.config/fish/completions/ali-rs.fish
+```
+
+## User config file
+
+Flags repeated on every invocation can be set as defaults in TOML file
+`~/.config/ayi/config.toml` (under `$XDG_CONFIG_HOME` if set),
+or in file given with `--config`. Keys are long flag names, and flags
+given on the command line always win:
+
+```toml
+color = "never"           # --color: auto, always, or never
+verbose = 1               # -v (1 for debug, 2 for trace)
+log-file = "/root/ali-rs.log"
+report-dir = "/root/ali-rs-reports"  # --report-dir
+mirror-country = "DE"     # apply --mirror-country
+```
+
+`apply --yes` cannot be set in the config file, so that disk wipes
+are only left unconfirmed for runs that ask for it.
+
+With `--report-dir DIR`, reports (or error reports) of `ali-rs apply`
+are also written to new files `DIR/report-<UNIX_SECS>.json`.
+
+With `ali-rs apply --mirror-country COUNTRY`, HTTPS mirrors of COUNTRY
+are ranked with `reflector` before pacstrap, which copies the mirrorlist
+to the new system.
+
+## Minimal builds
+
+Subsystems not needed in tiny provisioning environments (e.g. an
+initramfs) can be compiled out with cargo features, all enabled
+by default except `wasm`:
+
+| Feature  | Subsystem                                                       |
+|----------|-----------------------------------------------------------------|
+| `tui`    | Interactive installer `ali-rs tui`                              |
+| `qr`     | QR codes of run summaries (`apply --qr`)                        |
+| `remote` | HTTP(S) downloads, remote manifests, and HTTP report sinks      |
+| `wasm`   | [WASM hook plugins](./HOOKS.md#wasm-plugins)                    |
+
+For example, a minimal static binary for musl:
+
+```shell
+cargo build --release --no-default-features --target x86_64-unknown-linux-musl
+```
+
+Without `tui`, subcommand `tui` does not exist. Using other compiled-out
+subsystems fails with a "not implemented" error.
+
+Static musl builds assume no shared libraries or NSS modules at runtime:
+ali-rs does no user lookups, resolves DNS with musl's own resolver
+(`/etc/resolv.conf`), ships its own TLS roots, creates directories
+without `mkdir`, and falls back to a default `PATH` if none is set,
+so it runs on a bare busybox initramfs.
+
+It still runs external programs for most work. `--capabilities` prints
+enabled features and the programs each subsystem requires, marking
+those missing from `PATH`:
+
+```shell
+ali-rs --capabilities
+```
+
+## Interactive installer
+
+`ali-rs tui` asks for the disk to install to, a partitioning preset
+(`simple` for plain partitions, or `lvm`), root filesystem, swap size,
+hostname, timezone, root password, and an optional `wheel` user.
+It then writes a manifest installing GRUB for the live system's
+boot mode (UEFI or BIOS), to be reviewed before applying:
+
+```shell
+ali-rs tui -o manifest.yaml         # Write manifest only
+ali-rs tui -o manifest.yaml --apply # Write and apply manifest
+```
+
+Passwords are hashed before being written to the manifest.
+
+## Generating manifests from installed systems
+
+`ali-rs generate` inspects an installed system, and prints a best-effort
+manifest that would reproduce it, e.g. to clone a golden machine onto new
+hardware. Use `--root` to inspect a system mounted elsewhere, and `-o` to
+write to a file:
+
+```shell
+ali-rs generate -o golden.yaml
+ali-rs generate --root /mnt -o golden.yaml
+```
+
+The manifest is generated from `/etc/fstab` (rootfs, filesystems,
+mountpoints, and swap), `/etc/hostname`, `/etc/localtime`,
+`/etc/locale.conf` (manifest key `locale`), explicitly installed
+packages, and units enabled in `/etc/systemd/system` (as `chroot`
+commands). Disks, device mappers, and root password are never generated,
+and must be added before applying.
+
+## Verifying installs
+
+`ali-rs verify` checks a completed install, mounted at the install
+location (e.g. reopened with `ali-rs rescue`), against its manifest:
+
+- partitions exist with declared sizes (and types on MBR tables)
+
+- filesystems and swap have declared types, and labels given to mkfs
+  with `-L` or `-n`
+
+- rootfs and mountpoints are mounted where declared
+
+- hostname, timezone, `skel` files, and services enabled by manifest
+  keys `maintenance` and `domain_join`
+
+- files and services managed by hooks in `chroot` and `postinstall`,
+  for hooks that declare them (e.g. `@tailscale`, `@pam`, `@sssd`)
+
+It prints a JSON report of every check, and fails with exit status 4
+if any check fails, so that CI image builds can gate on it:
+
+```shell
+ali-rs -f manifest.yaml rescue && ali-rs -f manifest.yaml verify
+```
+
+```json
+{
+  "location": "/alitarget",
+  "passed": 11,
+  "failed": 1,
+  "checks": [
+    {
+      "check": "partition /dev/vda1 size",
+      "ok": false,
+      "detail": "expected 300M (314572800 bytes), found 209715200 bytes"
+    }
+  ]
+}
+```
+
+## Plans and Terraform/OpenTofu
+
+`ali-rs plan` prints what `ali-rs apply` would do with the manifest,
+without touching any disks: install location, disks to be wiped, stages,
+packages (including those added by ali-rs, e.g. `lvm2`), the manifest
+checksum, and an `apply_command` that applies the exact same manifest,
+pinned with `--sha256`. Block devices are not validated, so that plans
+can be made on machines other than the target. [Disk selectors](#disk-selectors)
+are resolved like `ali-rs apply` does, and `devices` records the disk
+each selector matched, so manifests with selectors are planned on the
+target itself.
+
+The plan also lists `steps`: every action of `ali-rs apply` in order,
+e.g. wiping disks, creating partitions, `luksFormat`, `mkfs`, mounts,
+`pacstrap`, routines, and each chroot and postinstall command or hook.
+Hooks are listed, but not run. Use `--text` to print numbered steps
+instead of JSON.
+
+To apply exactly the reviewed plan, save it with `-o` and pass it to
+`ali-rs apply --plan`. Before touching the system, apply aborts if
+the manifest checksum, the disk of any selector, or any step (of stages
+not skipped) differs from the plan, e.g. because the manifest, install
+location, or `--target` changed:
+
+```shell
+ali-rs -f laptop.yaml plan --text -o laptop.plan.json
+ali-rs -f laptop.yaml apply --plan laptop.plan.json
+```
+
+With `--against-current`, `ali-rs plan` reads partition tables and
+filesystems of current disks, and marks each disk step as `create`
+(nothing there yet), `no-op` (already as planned, e.g. a partition
+with the planned size and type), or `destroy` (existing data is
+overwritten, e.g. `mkfs` on a device with a filesystem). Plans with
+destructive steps are printed, but not saved with `-o`, and ali-rs
+exits with error unless `--allow-destroy` is given.
+
+`ali-rs apply --plan` assesses the steps against current disks again,
+whether or not the plan was made with `--against-current`, and refuses
+destructive steps unless given `--allow-destroy` as well. The
+`apply_command` of plans saved with `--allow-destroy` includes it.
+
+This helps converging partially installed machines: review what
+would be lost before applying. Note that `ali-rs apply` still performs
+every step of stages not skipped, including no-op steps.
+
+With `--external-json`, `ali-rs plan` speaks the
+[external program protocol](https://registry.terraform.io/providers/hashicorp/external/latest/docs/data-sources/external)
+of Terraform and OpenTofu: it reads query keys `manifest`, `sha256`, and
+manifest variables `var.<KEY>` as JSON from stdin, and prints the plan as
+a flat JSON object of strings. The full plan is kept as a JSON string
+in key `plan`. Errors are printed to stderr with non-zero exit status.
+
+```hcl
+data "external" "ali" {
+  program = ["ali-rs", "plan", "--external-json"]
+  query = {
+    manifest   = "./laptop.yaml"
+    "var.disk" = "/dev/nvme0n1"
+  }
+}
+
+resource "null_resource" "install" {
+  triggers = { sha256 = data.external.ali.result.manifest_sha256 }
+
+  provisioner "local-exec" {
+    command = data.external.ali.result.apply_command
+  }
+}
+```
+
 ## ALI manifest application
 
 Once the validation step is done (or skipped), ali-rs applies
@@ -45,6 +537,710 @@ Each stage groups closely related _actions_ together,
 and they are applied in a particular order. If any of the stages
 failed, ali-rs exits.
 
+Stages can be selected with `ali-rs apply` flags, by stage names
+(`mountpoints`, `bootstrap`, `routines`, `chroot-ali`, `chroot-user`,
+and `post-install-user`) or aliases (e.g. `disks` for `mountpoints`,
+or `chroot-hooks` for `chroot-user`):
+
+- `--only <STAGES>..` (or `-s`, `--stages`) runs only the given stages
+
+- `--skip <STAGES>..` skips the given stages
+
+- `--from <STAGE>` skips all stages before `STAGE`, e.g. `--from bootstrap`
+  for machines already partitioned and mounted at the install location
+
+`--skip` can be combined with `--from`. Stages given to both are
+ambiguous, and ali-rs refuses to run with all stages skipped.
+
+ali-rs records how long each stage took, keyed by stage and the
+speed class of the manifest disks (`nvme`, `ssd`, `hdd`, or `unknown`),
+in a local stats file (default `/var/lib/ali-rs/stats.json`, overridable
+with env `ALI_STATS`). On later runs, these historical durations are used
+to print ETAs for each stage and for the whole run.
+
+The report (and error report) also lists resource usage of each stage
+applied in key `resourceUsage`: CPU time of ali-rs and its child
+processes (`cpuSecs`), peak RSS of the largest process so far
+(`peakRssKib`), and bytes written to storage (`bytesWritten`,
+from cgroup v2 `io.stat` if available, or `/proc/self/io`).
+This helps finding pathological stages on constrained hardware.
+
+With `ali-rs apply --qr`, ali-rs prints a terminal QR code at the
+end of the run, encoding a short run summary (or the failure summary),
+plus the live host key fingerprint if `--enable-ssh` was used.
+This is handy for headless machines with a screen, where a phone
+camera is enough to capture the essentials.
+
+## Multi-target sessions
+
+A manifest may install extra, independent systems in the same run,
+e.g. a primary NVMe install plus a minimal rescue system on another
+partition. Each extra target has a name, its own install location,
+and its own manifest, resolved relative to the primary manifest
+(with the same `--var` variables):
+
+```yaml
+extra_targets:
+  - name: rescue
+    location: /rescue
+    manifest: rescue.yaml
+```
+
+Key `sha256` pins the checksum of a target manifest. If the primary
+manifest is pinned with `--sha256`, remote target manifests (including
+those relative to a remote primary manifest) must be pinned too,
+so that pinning covers every manifest of the session.
+
+Targets are applied one after another, starting with the primary
+manifest (named `primary`), so that an extra target may use partitions
+created by earlier targets. Disks of all targets are confirmed before
+anything is applied, while each extra target is validated right before
+it is applied.
+
+Target names must be unique, and install locations must be absolute
+and not nested in one another. Extra targets may not have extra targets
+of their own, nor set keys `secrets`, `reports`, `protected_paths`,
+or `runtime`: those apply to the whole session, and are only read from
+the primary manifest. ali-rs flag `--target` only applies to the primary
+manifest, and `--plan` cannot be used with extra targets.
+
+All targets share the package cache of the live system (`pacstrap -c`),
+so packages downloaded for one target are reused by later ones,
+and mirrors are ranked only once.
+
+Progress lines and JSON events are scoped by target
+(e.g. `rescue/stage-bootstrap` or `"target":"rescue"`), and reports
+of extra targets are listed in key `targets` of the report.
+Stage flags like `--from` apply to every target selected,
+and `--only-target <NAME>` applies only one target, e.g. to resume
+a failed target:
+
+```shell
+ali-rs -f manifest.yaml apply --only-target rescue --from bootstrap
+```
+
+## Rescue mode
+
+`ali-rs rescue` re-opens a system installed from a manifest, without
+creating or formatting anything: it opens LUKS devices, activates LVM VGs,
+and mounts rootfs and other mountpoints under the install location.
+With `--chroot`, it then starts an interactive shell with `arch-chroot(1)`:
+
+```shell
+ali-rs rescue -f manifest.yaml --chroot
+```
+
+With `--fsck`, each filesystem is checked before it is mounted, and a
+per-device summary is printed. The policy for each device is set with
+manifest key `fsck`, and defaults to `check` for unlisted devices:
+
+```yaml
+fsck:
+  /dev/myvg/rootlv: repair # Check and repair automatically
+  /dev/vda1: check         # Report only; mount read-only if errors are found
+  /dev/myvg/datalv: skip   # Do not check
+```
+
+ext\*/vfat use `fsck.<type>`, XFS uses `xfs_repair`, and btrfs is checked
+with `btrfs check --readonly` (repaired online with `btrfs scrub`).
+
+## Storage maintenance
+
+Manifest key `maintenance` installs and enables periodic maintenance units
+on the new system, so that storage is monitored from first boot:
+
+```yaml
+maintenance:
+  scrub: true      # Monthly btrfs-scrub@.timer for each Btrfs filesystem
+  balance: weekly  # Periodic btrfs balance (systemd calendar event)
+  fstrim: true     # Weekly fstrim.timer
+  smartd:          # smartd monitoring of all disks
+    email: admin@example.com        # Alert via mail(1), installs s-nail
+    ntfy: https://ntfy.sh/mytopic   # Alert via ntfy topic
+```
+
+Scrub and balance run once per Btrfs filesystem, at its first mountpoint.
+smartd runs daily short and weekly long self-tests, and only logs to the
+journal if no alert target is set.
+
+## Resource limits of install runs
+
+Manifest key `runtime` limits resources of the install run itself, so that
+a runaway `mkfs` or `pacstrap` cannot exhaust the live environment.
+When running under systemd, `ali-rs apply` moves itself into a transient
+scope `ali-rs-<PID>.scope` with these limits, and all commands it runs
+stay in the scope:
+
+```yaml
+runtime:
+  memory_max: 2G      # Hard limit, commands exceeding it are OOM-killed
+  memory_high: 1536M  # Commands are throttled above this limit
+  io_weight: 50       # IO weight from 1 to 10000 (default 100)
+  cpu_quota: 200      # Percent of one CPU, e.g. 200 for 2 CPUs
+  tasks_max: 512      # Limit on processes and threads
+  slice: ali-rs.slice # Slice to create the scope in
+```
+
+Sizes are in decimal units like elsewhere in manifests (`2G` is
+2,000,000,000 bytes), use e.g. `2GiB` for binary units.
+Without systemd, the limits are ignored with a warning.
+
+## Skeleton files for new users
+
+Manifest key `skel` populates `/etc/skel` of the new system before chroot
+commands run, so that every user created with `useradd -m` gets the same
+defaults. Files have either inline `content`, or a template file `source`
+on the live system, in which `{{ name }}` tokens are replaced by `vars`:
+
+```yaml
+skel:
+  - path: .bashrc
+    content: |
+      alias ll='ls -l'
+  - path: .gitconfig
+    source: ./templates/gitconfig
+    vars:
+      editor: nvim
+    mode: "0600"      # Octal, defaults to 0644 for files
+  - path: .local/bin/ # Paths ending with / are directories (0755)
+```
+
+Inline content is subject to [manifest variables](#manifest-variables)
+like all other manifest strings.
+
+## Time synchronization
+
+Manifest key `time` replaces systemd-timesyncd (which is masked) with
+[chrony](https://chrony-project.org) and/or PTP with
+[linuxptp](https://linuxptp.nwtime.org), for hosts needing authenticated
+or sub-microsecond time. Packages `chrony` and `linuxptp` are installed
+as needed.
+
+- `chrony` writes `/etc/chrony.conf` with Network Time Security (NTS)
+  on all servers, unless `nts: false`, and enables `chronyd.service` and
+  `chrony-wait.service` (so that `time-sync.target` waits for sync)
+
+- `ptp` writes `/etc/linuxptp/ptp4l.conf`, loads kernel module `ptp`
+  (and `modules`, e.g. the NIC driver) at boot, and enables
+  `ptp4l@<INTERFACE>.service`. With hardware time stamping (default),
+  `phc2sys@<INTERFACE>.service` synchronizes the system clock to the
+  NIC's PTP hardware clock. If `chrony` is also set, phc2sys instead
+  feeds chrony as a preferred reference clock, with NTS servers
+  as fallback.
+
+```yaml
+time:
+  chrony:
+    servers:
+      - time.cloudflare.com
+      - nts.netnod.se
+
+  ptp:
+    interface: enp1s0
+    domain: 24              # Default 0
+    transport: L2           # UDPv4 (default), UDPv6, or L2
+    time_stamping: hardware # Or software, which cannot be used with chrony
+    modules: [igb]
+```
+
+## Active Directory domain join
+
+Manifest key `domain_join` (alias `ad`) makes the new system a member of
+an Active Directory domain, with domain users and groups from SSSD.
+Joining needs a reachable domain controller and a clock within Kerberos'
+5 minute skew, which the live environment often lacks. So ali-rs only
+prepares the join at install time:
+
+- writes `/etc/sssd/sssd.conf` (AD provider) and `/etc/krb5.conf`
+
+- adds `sss` to `/etc/nsswitch.conf`, and `pam_sss.so` and
+  `pam_mkhomedir.so` to `/etc/pam.d/system-auth`
+
+- copies the join password from the live system to
+  `/etc/ali-rs/domain-join.secret` (mode 600)
+
+- enables `systemd-time-wait-sync.service`, `sssd.service`, and
+  `ali-rs-domain-join.service`
+
+On first boot, once the network is online and the clock is synchronized,
+`ali-rs-domain-join.service` joins with adcli(8), retrying every minute
+until it succeeds. The password is then removed, and the join status
+is written to `/var/lib/ali-rs/domain-join.json`:
+
+```json
+{"domain":"corp.example.com","joined":true,"exit_code":0,"time":"2026-10-17T09:00:00+07:00"}
+```
+
+The install report records the prepared join as routine `domainJoin`,
+with the path of the status file.
+
+```yaml
+hostname: ws01        # Computer account name, unless computer_name is set
+
+ad:
+  domain: corp.example.com
+  user: joiner        # Account allowed to join computers
+  password_file: /root/ad-join.pass
+  ou: OU=Linux,DC=corp,DC=example,DC=com
+  fully_qualified_names: false  # alice, not alice@corp.example.com
+```
+
+Prestaged computer accounts can join with
+`one_time_password_file` instead of `user` and `password_file`.
+
+## Secrets
+
+Values that should not live in manifests can be given as secret
+references `secret://<NAME>`, resolved only when they are used.
+References are accepted by manifest key `rootpasswd` (the password
+hash), `@replace-token` values, and the inline secrets of hooks
+`@quicknet`, `@tailscale`, `@wireguard`, `@sssd`, and `@backup`.
+
+Resolved secrets, and secrets read from hook files such as `psk_file`,
+are replaced by `***` in reports, report sinks, JSON errors, `-print`
+output, and logs.
+
+Manifest key `secrets` lists providers, tried in order:
+
+| Type   | Secret `<NAME>` is read from                                   |
+|--------|----------------------------------------------------------------|
+| `env`  | Env `<PREFIX><NAME>`, NAME uppercased with `-` and `.` as `_`  |
+| `file` | File `<DIR>/<NAME>`, without trailing newline                  |
+| `sops` | Key `<NAME>` of YAML or JSON file decrypted with `sops -d`     |
+| `age`  | Key `<NAME>` of YAML or JSON file decrypted with `age -d`      |
+
+Encrypted files are decrypted once, on first use. Without key `secrets`,
+secrets are read from env `ALI_RS_SECRET_<NAME>`.
+
+```yaml
+rootpasswd: secret://root-hash
+
+secrets:
+  - type: env               # e.g. ALI_RS_SECRET_ROOT_HASH
+  - type: file
+    dir: /run/secrets
+  - type: sops
+    file: ./secrets.sops.yaml
+  - type: age
+    file: ./secrets.yaml.age
+    identity: /root/age.key
+
+chroot:
+  - "@quicknet wlan0 ssid=office psk=secret://office-psk"
+```
+
+## CA certificates
+
+Manifest key `ca_certs` (alias `ca_certificates`) adds trust anchors for
+internal certificate authorities, so the installed system can reach
+internal HTTPS endpoints. Each certificate comes from exactly one of
+an inline `pem`, a `file` on the live system, or a `url`. Certificates
+fetched from a URL must be pinned with `sha256`.
+
+All certificates are resolved before anything is written. They are then
+saved to `/etc/ca-certificates/trust-source/anchors/<name>.crt`, and
+`update-ca-trust` is run in the chroot. This happens before hooks, so
+hooks downloading from internal endpoints already trust these CAs.
+
+```yaml
+ca_certs:
+  - name: corp-root
+    file: /root/corp-root.pem
+
+  - name: corp-issuing
+    url: https://pki.corp.example.com/issuing.pem
+    sha256: 9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08
+
+  - name: lab-root
+    pem: |
+      -----BEGIN CERTIFICATE-----
+      MIIB...
+      -----END CERTIFICATE-----
+```
+
+## SSH client config and known hosts
+
+Manifest key `ssh` provisions OpenSSH client config and known hosts,
+so that new machines can reach internal infrastructure on first boot:
+
+```yaml
+ssh:
+  config:            # /etc/ssh/ssh_config.d/50-ali-rs.conf
+    - host: "*.internal"
+      options:
+        User: deploy
+        ProxyJump: bastion.internal
+  known_hosts:       # Appended to /etc/ssh/ssh_known_hosts
+    - host: git.internal,10.0.0.5
+      key: ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAA...
+    - host: "*.internal"
+      key: ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAA...
+      cert_authority: true
+  hash_known_hosts: true
+  users:             # Appended to ~/.ssh/config and ~/.ssh/known_hosts
+    alice:
+      known_hosts:
+        - host: git.internal
+          key: ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAA...
+```
+
+With `hash_known_hosts`, host names are hashed like `ssh-keygen -H`,
+one host per line. Patterns (e.g. `*.internal`) are left as-is.
+
+System-wide files are written in `stage-routines`. Per-user files are
+written at the end of `stage-chroot_user`, so users created by commands
+in manifest key `chroot` can be used. Package `openssh` is installed
+automatically.
+
+## Report sinks
+
+Manifest key `reports` declares sinks that receive report events as
+JSON lines while `ali-rs apply` runs, independent of ali-rs stdout:
+
+```yaml
+reports:
+  - type: file     # Appended to file on the live system
+    path: /var/log/ali-rs/report.jsonl
+  - type: http     # POSTed to HTTP(S) endpoint
+    url: https://example.com/ali-rs/report
+  - type: command  # Run with `sh -c`, with the event on stdin
+    command: logger -t ali-rs
+```
+
+Each hook action is sent as it is produced, followed by the final report
+or error:
+
+```json
+{"action":{"QuickNet":"..."},"event":"hook","hook":"@quicknet","stage":"stage-chroot_ali","version":1}
+{"event":"report","report":{"elaspedTime":{},"summary":{},"version":1},"version":1}
+{"error":{"error":"...","version":1},"event":"error","version":1}
+```
+
+Failing sinks are logged as warnings, and do not fail the installation.
+
+## Status file descriptor
+
+With `--status-fd N`, ali-rs writes a compact JSON line to file
+descriptor `N` when done, like `gpg --status-fd`. Orchestrators can
+read it instead of parsing stdout:
+
+```shell
+ali-rs apply -y --status-fd 3 3>status.json
+```
+
+```json
+{"class":"install","code":"cmd-failed","error":"...","hint":"see command output in the log file (--log-file)","log":"/tmp/ali-rs.log","reports":["/var/log/ali-rs/report.jsonl"],"stage":"stage-bootstrap","status":"error","version":1}
+```
+
+Error `class` is the class of the root cause, one of `install`,
+`manifest`, `usage`, `hook`, `aborted`, `io`, or `internal`,
+and is `ok` on success. Key `stage`
+is only present when a stage failed, and `reports` lists paths
+of [file report sinks](#report-sinks).
+
+Key `code` is a stable code of the root cause, e.g. `no-such-device`,
+`preflight`, or `cmd-failed`, and `hint` suggests a fix if ali-rs
+knows one. Both are also in the error JSON ali-rs prints to stderr.
+
+## Exit statuses
+
+ali-rs exits with a distinct status for each kind of failure,
+decided by the root cause (`code`), so that provisioning scripts
+can branch on it:
+
+| Status | Failure                          | Codes                                |
+|--------|----------------------------------|--------------------------------------|
+| 0      | Success                          |                                      |
+| 2      | Bad command-line arguments       | `bad-args`                           |
+| 3      | Bad manifest                     | `bad-manifest`                       |
+| 4      | Validation or preflight failure  | `validation`, `preflight`            |
+| 5      | Disk or block device error       | `no-such-device`                     |
+| 6      | Hook error                       | `bad-hook-cmd`, `hook-error`         |
+| 7      | External command failure         | `cmd-failed`                         |
+| 8      | Aborted, e.g. by user            | `aborted`                            |
+| 9      | File error                       | `no-such-file`, `file-error`         |
+| 70     | Internal ali-rs bug              | `ali-rs-bug`, `not-implemented`      |
+
+`ali-rs plan --external-json` always exits with status 1 on errors,
+as required by the external program protocol.
+
+## Crash files
+
+If ali-rs panics, it writes a JSON crash file `crash-<UNIX_SECS>.json`
+to the directory of the log file (`/var/log/ali-rs/` by default),
+with the panic message and location, backtrace, current stage,
+last command run, and ali-rs, OS, and kernel versions.
+Please attach it to bug reports.
+
+Crash files are only sent anywhere if you opt in with
+`--crash-report-url URL`, which POSTs the crash file to the URL.
+Note that the last command may contain manifest values.
+
+## Exit summary
+
+Whether ali-rs succeeds, fails, crashes, or is cancelled with Ctrl-C,
+it ends by printing to stderr where files of the run are: the run
+directory (directory of the log file), the log file, and any reports.
+On failures, it also tells how to resume failed `ali-rs apply` runs
+with `--from STAGE`, and which files to attach to bug reports:
+
+```
+ali-rs: failed
+  run dir:  /var/log/ali-rs
+  log file: /var/log/ali-rs/ali-rs.log
+  resume:   rerun with `apply --from stage-bootstrap` after fixing
+  support:  attach log file and crash-*.json files in run dir to bug reports
+```
+
+A second Ctrl-C exits immediately, without the summary.
+
+## Remote access to the live installer
+
+With `ali-rs apply --enable-ssh`, ali-rs starts sshd on the live
+installer before applying the manifest, so that operators can attach
+remotely if something goes wrong during a long install.
+
+By default, a random root password is generated and printed to the
+console together with the live host key fingerprint. Use
+`--ssh-password-file <FILE>` (or `-` for stdin) to set the password,
+or `--ssh-key <KEY_OR_FILE>` to authorize a public key instead
+(password login is then disabled).
+
+The console also shows the PID of the running install and its log file,
+so that attached operators can follow it with `tail -f`. `ali-rs apply`
+holds a run lock (`/run/ali-rs.lock`) for its whole run, so that
+operators cannot accidentally start a second install while one runs.
+
+## Fetching sources through a proxy
+
+Machines that can only reach remote artifacts through a bastion
+can route ali-rs downloads (e.g. hook `@download` and remote hook
+templates) through a SOCKS5 proxy, declared in manifest key `proxy`
+or with flag `--proxy` (which takes precedence):
+
+```yaml
+# Existing SOCKS5 proxy
+proxy: socks5://127.0.0.1:1080
+
+# Ephemeral tunnel via `ssh -D`, closed when ali-rs exits
+proxy: ssh://admin@bastion:2222
+```
+
+## Progress output
+
+ali-rs reports the current stage, step (device, package set, or hook),
+and elapsed time to stderr while partitioning disks, creating filesystems,
+running `pacstrap`, and running hooks. Long steps print a "still running"
+line every 15 seconds, so they do not look frozen.
+
+Use global flag `--progress` to choose `plain` (default), `fancy` (colored),
+or `json` (one JSON object per line, for other programs):
+
+```json
+{"elapsedSecs":95,"event":"running","stage":"stage-bootstrap","step":"pacstrap 5 package(s)","stepSecs":30,"version":1}
+```
+
+Output of `pacstrap` is streamed line by line as it runs, and is also
+written to the log file. In JSON mode, each line is an `output` event:
+
+```json
+{"cmd":"pacstrap","event":"output","line":"installing linux...","stage":"stage-bootstrap","stream":"stdout","version":1}
+```
+
+## Hung command watchdog
+
+ali-rs warns when an external command (e.g. `pacstrap` or `mkfs`)
+made no progress for 10 minutes: neither the command nor its child
+processes read or wrote anything, or used any CPU time. Slow commands
+that are still making progress are never flagged. The warning shows
+each process with its state and kernel wait channel from `/proc`.
+
+Use `--watchdog SECS` to change the idle period, or `--watchdog 0`
+to disable the watchdog. With `--watchdog-prompt`, ali-rs also asks
+whether to kill the idle command, if stdin is a terminal.
+
+## Command timeouts and Ctrl-C
+
+With `--cmd-timeout SECS`, external commands still running after
+SECS seconds get SIGTERM, then SIGKILL 5 seconds later, failing the
+current stage. Read-only disk probes like `lsblk` and `blkid` always
+time out after 60 seconds.
+
+During `ali-rs apply`, Ctrl-C forwards SIGINT to running commands and
+their children (killing them if they do not exit within 5 seconds),
+then unmounts everything under the install location and exits with
+status 8. Press Ctrl-C again to exit immediately without cleanup.
+
+## JSON output
+
+All JSON ali-rs produces for other programs (reports, plans, statuses,
+progress lines, hook schema, and hook actions) is an object with
+key `version`, currently `1`, and keys sorted alphabetically.
+Fields may be added without bumping `version`, but never removed
+or renamed. Non-object values are kept in key `data`.
+
+## Mocked commands
+
+In builds with cargo feature `e2e`, ali-rs run with env `ALI_RS_MOCK`
+records external commands in the log instead of running them, so that
+manifests can be exercised without root or real block devices. Release
+builds ignore `ALI_RS_MOCK`. `ALI_RS_MOCK=1` makes every command succeed
+with empty output. Otherwise, `ALI_RS_MOCK` is a path to a JSON file of
+canned results, keyed by command line prefix (the longest matching
+prefix wins):
+
+```json
+{
+  "blkid -o export /dev/vda2": { "stdout": "TYPE=crypto_LUKS\n" },
+  "cryptsetup luksOpen": { "exit": 2 }
+}
+```
+
+Unit tests use the same backend to cover storage code and hooks.
+
+## Loop device tests
+
+Real coverage of the destructive paths comes from an opt-in test suite
+behind cargo feature `e2e`. It attaches loop devices backed by sparse
+files, runs stage mountpoints (disks, LUKS, LVM, mkfs, and mounts)
+against them, and checks the resulting layout with lsblk(8) and
+blkid(8). Loop devices, mappers, and mounts are torn down afterwards,
+even on failures.
+
+The suite needs root, `losetup`, `fdisk`, `cryptsetup`, `lvm2`,
+`e2fsprogs`, and `dosfstools`, e.g. on a CI runner:
+
+```shell
+sudo -E cargo test --features e2e e2e
+```
+
+## Debugging hooks with file snapshots
+
+With `--snapshot-hooks`, ali-rs snapshots metadata (mode, size, mtime,
+and SHA-256 of contents) of all files under `/etc` of the hook root
+before and after each hook, and logs exactly which files each hook
+changed. This is handy for checking that third-party or plugin hooks
+are idempotent, since a second run should change nothing.
+
+Other directories can be given as a comma-separated list:
+
+```shell
+ali-rs apply --snapshot-hooks=/etc,/usr/local
+
+ali-rs --snapshot-hooks hooks run -m /mnt @x-foo
+```
+
+Changes are logged as `+` (created), `~` (modified), `*` (touched:
+only mtime or mode changed), and `-` (deleted), even if the hook failed.
+The same lists are in key `files` of `hook` events of
+[report sinks](#report-sinks).
+
+## Protected paths
+
+Hooks refuse to write some paths, so that a typo in a manifest cannot
+clobber them:
+
+- `/etc/shadow` and `/etc/gshadow`, which ali-rs manages itself
+  (e.g. with manifest key `rootpasswd`)
+
+- `/boot/efi/EFI/Microsoft`, the Windows boot manager of dual-boot machines
+
+- `/run/archiso`, the live ISO
+
+A file is protected if it is under a protected path, either on the live
+system or relative to the mountpoint, after resolving symlinks and `..`.
+More paths can be protected with manifest key `protected_paths`:
+
+```yaml
+protected_paths:
+  - /etc/sudoers
+  - /home
+```
+
+To let one hook write a protected path anyway, wrap it with
+[`@unprotect`](./HOOKS.md#unprotect):
+
+```yaml
+postinstall:
+  - "@unprotect /etc/sudoers @replace-token WHEEL ALL /etc/sudoers"
+```
+
+Protection covers files written by ali-rs hooks themselves. Plugin
+hooks, native or WASM, write files on their own, so files under
+protected paths are compared before and after each plugin runs, and
+the hook fails if any of them was written, created, or removed. This
+catches the write after the fact, but stops the install before later
+stages build on it. Other commands run by hooks are not restricted.
+
+Regardless of protection, paths of files written under the mountpoint
+by hooks and stages are resolved as if the mountpoint were `/`:
+absolute symlinks in the new system point inside it, and paths climbing
+out of the mountpoint with `..` (e.g. `../../etc/passwd`) are rejected.
+
+## Undoing hook edits
+
+File-editing hooks `@uncomment`, `@replace-token`, `@mkinitcpio`, `@pam`
+and `@sssd` take flag `backup`, which copies each file they edit to
+`/var/lib/ali-rs/backups/<PATH>.<UNIX_NANOS>` of the hook root before it
+is written. `--backup-hooks` does the same
+for all such hooks.
+
+```yaml
+postinstall:
+  - "@uncomment PubkeyAuthentication /etc/ssh/sshd_config backup"
+```
+
+`ali-rs hooks undo` restores the latest backup of each file and removes
+it, so repeated runs walk back through older backups. Files created by
+hooks (i.e. with nothing to back up) are left alone.
+
+```shell
+ali-rs hooks undo -m /mnt --dry-run
+
+ali-rs hooks undo -m /mnt
+```
+
+## Hook output limits
+
+Output printed by each hook (e.g. `-print` hooks), and the action it
+reports, is capped at 256KiB by default. Longer output is truncated with
+a notice, and the full output is stored gzip-compressed as
+`hook-output-<UNIX_SECS>-<SEQ>-<HOOK>.gz` in the directory of the log file.
+
+```shell
+# Cap each hook at 64KiB
+ali-rs apply --hook-output-limit 64KiB
+
+# No limit
+ali-rs apply --hook-output-limit 0
+
+zcat hook-output-*-replace-token-print.gz
+```
+
+Output of external commands run by hooks is not captured, and so not
+capped.
+
+## Logging
+
+ali-rs logs to stderr at info level by default. Use `-v` for debug
+(including every command executed) and `-vv` for trace, which also add
+timestamps and log targets to each line.
+
+A debug-level log is always appended to `--log-file`
+(default `/var/log/ali-rs/ali-rs.log` in the live environment).
+After `ali-rs apply`, it is copied to `/var/log/ali-rs/` in the new system,
+even if installation failed after the base system was installed,
+so that failed installs can be debugged after reboot.
+
+## Accessible output
+
+With global flag `--accessible`, ali-rs avoids colored and decorated
+output, announces progress as plain sequential sentences (e.g.
+"Starting stage-bootstrap, step 2 of 6."), and rings the terminal bell
+on completion. If [`espeak-ng`](https://github.com/espeak-ng/espeak-ng)
+is installed on the live system (as on the official Arch ISO),
+the sentences are also spoken.
+
 ## Root password in ali-rs
 
 User `root` password (hashed) is defined in manifest key
@@ -79,6 +1275,31 @@ ali-rs follows ALI steps in this strict order:
    `/etc/locale.conf`, `/etc/hostname`, and populating `/etc/fstab`
    with `genfstab(8)`.
 
+   Generated configs refer to devices by UUID from `blkid(8)`, not device paths:
+   non-root LUKS devices are added to `/etc/crypttab`, and `root=`
+   (and `cryptdevice=` for root on LUKS) are written to `/etc/kernel/cmdline`.
+
+   Kernel parameters from ali-rs (rootfs, LUKS, and network root) and from
+   manifest key `cmdline` are merged into one kernel command line, which
+   is written to `/etc/kernel/cmdline` and to `GRUB_CMDLINE_LINUX` in
+   `/etc/default/grub` (minus `root=`, `rw`, and `ro`, which grub-mkconfig
+   generates). Conflicting parameters, e.g. a second `root=` or both `rw`
+   and `ro`, are rejected during validation:
+
+   ```yaml
+   cmdline:
+     - console=ttyS0,115200
+     - quiet
+   ```
+
+   CPU microcode is loaded early from the first initrd. The initramfs
+   hooks written by ali-rs (portable, netroot, and `@mkinitcpio` presets)
+   include mkinitcpio hook `microcode`, so that microcode from installed
+   `amd-ucode` or `intel-ucode` is embedded in the initramfs (and in UKIs
+   built from it). GRUB entries load `/boot/*-ucode.img` first via
+   grub-mkconfig, and systemd-boot entries generated by `@rollback` list
+   installed microcode images before the initramfs.
+
 4. `stage-chroot_ali`
 
    This stage contains actions that ali-rs will apply on the behalf
@@ -9,6 +9,7 @@ use crate::types::action::{
 use crate::utils::shell;
 
 use super::map_err::*;
+use super::portable;
 
 pub fn chroot_ali(
     manifest: &Manifest,
@@ -16,22 +17,64 @@ pub fn chroot_ali(
 ) -> Result<Vec<ActionChrootAli>, AliError> {
     let mut actions = Vec::new();
 
-    let (action_tz, cmd_tz) = cmd_link_timezone(&manifest.timezone);
-    if let Err(err) = shell::arch_chroot(location, &cmd_tz) {
-        return Err(map_err_chroot_ali(err, action_tz, actions));
+    for action in planned_ali(manifest) {
+        if let Err(err) = run_ali(&action, manifest, location) {
+            return Err(map_err_chroot_ali(err, action, actions));
+        }
+
+        actions.push(action);
     }
 
-    actions.push(action_tz);
+    Ok(actions)
+}
 
-    let cmd_locale_gen = cmd_locale_gen();
-    let action_locale_gen = ActionChrootAli::LocaleGen;
-    if let Err(err) = shell::arch_chroot(location, &cmd_locale_gen) {
-        return Err(map_err_chroot_ali(err, action_locale_gen, actions));
+/// ALI routines in arch-chroot with `manifest`, in apply order.
+/// Also used by `ali-rs plan`, so plans follow the same conditions.
+pub(super) fn planned_ali(manifest: &Manifest) -> Vec<ActionChrootAli> {
+    let tz = manifest.timezone.as_deref().unwrap_or(defaults::TIMEZONE);
+    let mut actions = vec![
+        ActionChrootAli::LinkTimezone(tz.to_string()),
+        ActionChrootAli::LocaleGen,
+    ];
+
+    // Regenerate initramfs with network root or portable hooks
+    if manifest.netroot.is_some() || manifest.is_portable() {
+        actions.push(ActionChrootAli::Mkinitcpio);
     }
 
-    actions.push(action_locale_gen);
+    // Install both BIOS and UEFI boot paths
+    if manifest.is_portable() {
+        actions.push(ActionChrootAli::InstallGrub);
+    }
 
-    Ok(actions)
+    actions
+}
+
+/// Applies a routine from [`planned_ali`] in arch-chroot
+fn run_ali(
+    action: &ActionChrootAli,
+    manifest: &Manifest,
+    location: &str,
+) -> Result<(), AliError> {
+    match action {
+        ActionChrootAli::LinkTimezone(tz) => {
+            shell::arch_chroot(location, &cmd_link_timezone(tz))
+        }
+        ActionChrootAli::LocaleGen => {
+            shell::arch_chroot(location, &cmd_locale_gen(&manifest.locale))
+        }
+        ActionChrootAli::Mkinitcpio => {
+            shell::arch_chroot(location, "mkinitcpio -P")
+        }
+        ActionChrootAli::InstallGrub => {
+            let boot = crate::ali::portable::boot(manifest)?;
+            for cmd in portable::cmds_grub(&boot) {
+                shell::arch_chroot(location, &cmd)?;
+            }
+
+            Ok(())
+        }
+    }
 }
 
 pub fn chroot_user<'a, I>(
@@ -69,17 +112,14 @@ where
     Ok(actions)
 }
 
-fn cmd_link_timezone(tz: &Option<String>) -> (ActionChrootAli, String) {
-    let tz = tz.clone().unwrap_or(defaults::TIMEZONE.to_string());
-    let tz_cmd = format!("ln -s /usr/share/zoneinfo/{} /etc/localtime", tz);
-
-    (ActionChrootAli::LinkTimezone(tz), tz_cmd)
+fn cmd_link_timezone(tz: &str) -> String {
+    format!("ln -s /usr/share/zoneinfo/{} /etc/localtime", tz)
 }
 
-// Appends defaults::DEFAULT_LOCALE_GEN to /etc/locale.gen
-fn cmd_locale_gen() -> String {
-    format!(
-        "echo {} >> /etc/locale.gen && locale-gen",
-        defaults::LOCALE_GEN
-    )
+// Appends `<LANG>.<CHARSET> <CHARSET>` to /etc/locale.gen
+fn cmd_locale_gen(locale: &Option<String>) -> String {
+    let locale = locale.as_deref().unwrap_or(defaults::LOCALE);
+    let charset = locale.split_once('.').map_or("UTF-8", |(_, c)| c);
+
+    format!("echo {locale} {charset} >> /etc/locale.gen && locale-gen")
 }
@@ -1,29 +1,139 @@
 use std::collections::HashSet;
+use std::sync::atomic::{
+    AtomicBool,
+    Ordering,
+};
+use std::sync::OnceLock;
 
 use crate::errors::AliError;
-use crate::utils::shell;
+use crate::utils::{
+    progress,
+    shell,
+};
 
+/// Country of mirrors ranked before pacstrap, if any
+static MIRROR_COUNTRY: OnceLock<String> = OnceLock::new();
+
+/// Whether mirrors were already ranked by an earlier target
+static MIRRORS_RANKED: AtomicBool = AtomicBool::new(false);
+
+/// Whether pacstrap uses package cache of the live system,
+/// shared by all targets of a multi-target session
+static SHARED_CACHE: AtomicBool = AtomicBool::new(false);
+
+/// Ranks mirrors of `country` with reflector before pacstrap
+pub fn set_mirror_country(country: &str) {
+    if MIRROR_COUNTRY.set(country.to_string()).is_err() {
+        log::warn!("mirror country already set, ignoring {country}");
+    }
+}
+
+/// Makes pacstrap download packages to package cache of the live system
+/// instead of each new system, so that later targets reuse them
+pub fn set_shared_cache() {
+    SHARED_CACHE.store(true, Ordering::Relaxed);
+}
+
+/// Executes:
+/// ```shell
+/// reflector --country <COUNTRY> --protocol https --latest 20 \
+///     --sort rate --save /etc/pacman.d/mirrorlist
+/// ```
+fn rank_mirrors(country: &str) -> Result<(), AliError> {
+    let _step = progress::step(format!("ranking mirrors in {country}"));
+
+    shell::exec_stream(
+        "reflector",
+        &[
+            "--country",
+            country,
+            "--protocol",
+            "https",
+            "--latest",
+            "20",
+            "--sort",
+            "rate",
+            "--save",
+            "/etc/pacman.d/mirrorlist",
+        ],
+    )?;
+
+    Ok(())
+}
+
+/// Installs packages to `location`. If `allow_dir` is true,
+/// `location` may be a plain directory instead of a mountpoint.
 pub fn pacstrap_to_location(
     pacstraps: &Option<HashSet<String>>,
     location: &str,
+    allow_dir: bool,
 ) -> Result<(), AliError> {
-    // Collect packages, with base as bare-minimum
+    let packages = packages(pacstraps);
+
+    // pacstrap copies mirrorlist of live system to the new system
+    if let Some(country) = MIRROR_COUNTRY.get() {
+        if !MIRRORS_RANKED.load(Ordering::Relaxed) {
+            rank_mirrors(country)?;
+            MIRRORS_RANKED.store(true, Ordering::Relaxed);
+        }
+    }
+
+    let _step =
+        progress::step(format!("pacstrap {} package(s)", packages.len()));
+
+    let args = pacstrap_args(
+        location,
+        &packages,
+        allow_dir,
+        SHARED_CACHE.load(Ordering::Relaxed),
+    );
+
+    // Stream output, so that users can follow package downloads
+    shell::exec_stream("pacstrap", &args)?;
+
+    Ok(())
+}
+
+/// Packages to install, with base as bare-minimum
+pub(super) fn packages(pacstraps: &Option<HashSet<String>>) -> HashSet<String> {
     let mut packages = HashSet::from(["base".to_string()]);
+    packages.extend(pacstraps.iter().flatten().cloned());
 
-    if let Some(pacstraps) = pacstraps.clone() {
-        packages.extend(pacstraps);
+    packages
+}
+
+fn pacstrap_args<'a>(
+    location: &'a str,
+    packages: &'a HashSet<String>,
+    allow_dir: bool,
+    shared_cache: bool,
+) -> Vec<&'a str> {
+    let mut args = vec!["-K"];
+
+    if allow_dir {
+        args.push("-d");
+    }
+
+    if shared_cache {
+        args.push("-c");
     }
 
-    let cmd_pacstrap = {
-        let mut cmd_parts = vec![
-            "pacstrap".to_string(),
-            "-K".to_string(),
-            location.to_string(),
-        ];
+    args.push(location);
+    args.extend(packages.iter().map(String::as_str));
+
+    args
+}
 
-        cmd_parts.extend(packages);
-        cmd_parts.join(" ")
-    };
+#[test]
+fn test_pacstrap_args() {
+    let packages = HashSet::from(["base".to_string()]);
 
-    shell::sh_c(&cmd_pacstrap)
+    assert_eq!(
+        vec!["-K", "/mnt", "base"],
+        pacstrap_args("/mnt", &packages, false, false),
+    );
+    assert_eq!(
+        vec!["-K", "-d", "-c", "/rescue", "base"],
+        pacstrap_args("/rescue", &packages, true, true),
+    );
 }
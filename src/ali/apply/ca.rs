@@ -0,0 +1,140 @@
+use crate::ali::ManifestCaCert;
+use crate::errors::AliError;
+use crate::hooks;
+use crate::utils::fs::write_under;
+use crate::utils::shell;
+
+/// Trust anchors of p11-kit, picked up by update-ca-trust(8)
+pub const DIR_ANCHORS: &str = "/etc/ca-certificates/trust-source/anchors";
+
+const BEGIN: &str = "-----BEGIN CERTIFICATE-----";
+const END: &str = "-----END CERTIFICATE-----";
+
+/// Writes certificates to anchors directory of new system at `location`,
+/// and rebuilds its trust store
+pub fn install(
+    m_certs: &[ManifestCaCert],
+    location: &str,
+) -> Result<(), AliError> {
+    // Fetch everything first, so that failed downloads change nothing
+    let pems = m_certs
+        .iter()
+        .map(|cert| pem(cert).map(|pem| (cert, pem)))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    for (cert, pem) in pems {
+        write_under(location, &anchor(&cert.name), &pem)?;
+    }
+
+    shell::arch_chroot(location, "update-ca-trust")
+}
+
+/// Path of anchor of certificate `name` in new system
+pub fn anchor(name: &str) -> String {
+    format!("{DIR_ANCHORS}/{name}.crt")
+}
+
+/// Reads PEM of `cert` from its source
+fn pem(cert: &ManifestCaCert) -> Result<String, AliError> {
+    let name = &cert.name;
+    let pem = match (&cert.pem, &cert.file, &cert.url) {
+        (Some(pem), None, None) => pem.clone(),
+        (None, Some(file), None) => {
+            std::fs::read_to_string(file).map_err(|err| {
+                AliError::NoSuchFile(err, format!("ca_certs: {name}: {file}"))
+            })?
+        }
+        (None, None, Some(url)) => {
+            hooks::download_string(url, cert.sha256.as_deref())?
+        }
+        _ => {
+            return Err(AliError::BadManifest(format!(
+                "ca_certs: {name}: expecting exactly one of pem, file, or url"
+            )));
+        }
+    };
+
+    if !is_pem(&pem) {
+        return Err(AliError::BadManifest(format!(
+            "ca_certs: {name}: not a PEM certificate"
+        )));
+    }
+
+    match pem.ends_with('\n') {
+        true => Ok(pem),
+        false => Ok(format!("{pem}\n")),
+    }
+}
+
+/// Returns whether `s` holds at least one PEM certificate
+pub fn is_pem(s: &str) -> bool {
+    s.trim_start().starts_with(BEGIN) && s.trim_end().ends_with(END)
+}
+
+#[test]
+fn test_install_ca() {
+    use std::sync::Arc;
+
+    use crate::utils::mock::MockRunner;
+
+    let pem = format!("{BEGIN}\nMIIBszCCAVmgAwIBAgIU\n{END}");
+    let root = std::env::temp_dir()
+        .join(format!("ali-rs-test-ca-{}", std::process::id()));
+    let location = root.to_str().unwrap();
+
+    let file = root.join("corp.pem");
+    std::fs::create_dir_all(&root).unwrap();
+    std::fs::write(&file, &pem).unwrap();
+
+    let cert = |name: &str| {
+        ManifestCaCert {
+            name: name.to_string(),
+            pem: None,
+            file: None,
+            url: None,
+            sha256: None,
+        }
+    };
+
+    let mock = Arc::new(MockRunner::new());
+    let _guard = shell::set_runner(mock.clone());
+
+    let m_certs = vec![
+        ManifestCaCert {
+            pem: Some(pem.clone()),
+            ..cert("inline")
+        },
+        ManifestCaCert {
+            file: Some(file.to_str().unwrap().to_string()),
+            ..cert("from-file")
+        },
+    ];
+
+    install(&m_certs, location).unwrap();
+    for name in ["inline", "from-file"] {
+        let written =
+            std::fs::read_to_string(format!("{location}{}", anchor(name)))
+                .unwrap();
+        assert_eq!(format!("{pem}\n"), written);
+    }
+    assert_eq!(
+        vec![format!("sh -c arch-chroot {location} update-ca-trust")],
+        mock.calls(),
+    );
+
+    // Bad certificates are rejected before anything is written
+    let m_certs = vec![
+        ManifestCaCert {
+            pem: Some(pem.clone()),
+            ..cert("good")
+        },
+        ManifestCaCert {
+            pem: Some("not a cert".to_string()),
+            ..cert("bad")
+        },
+    ];
+    assert!(install(&m_certs, location).is_err());
+    assert!(!root.join(&anchor("good")[1..]).exists());
+
+    std::fs::remove_dir_all(root).unwrap();
+}
@@ -0,0 +1,128 @@
+use crate::ali::Manifest;
+use crate::errors::AliError;
+use crate::linux::blkid::DeviceMap;
+use crate::types::cmdline::KernelCmdline;
+use crate::utils::fs::write_under;
+use crate::utils::shellconf::{
+    ShellConf,
+    Value,
+};
+
+use super::{
+    netroot,
+    routines,
+};
+
+const KERNEL_CMDLINE: &str = "/etc/kernel/cmdline";
+const DEFAULT_GRUB: &str = "/etc/default/grub";
+const KEY_GRUB_CMDLINE: &str = "GRUB_CMDLINE_LINUX";
+
+/// Merges kernel parameters from rootfs, root LUKS device, network root,
+/// and manifest key `cmdline`, in that order, failing on conflicts.
+/// Devices are referred to by stable identifiers found in `devices`.
+pub fn build(
+    manifest: &Manifest,
+    devices: &DeviceMap,
+) -> Result<KernelCmdline, AliError> {
+    let mut cmdline = KernelCmdline::default();
+
+    match &manifest.netroot {
+        Some(m_netroot) => {
+            for param in netroot::kernel_params(manifest, m_netroot) {
+                cmdline.push("netroot", &param)?;
+            }
+        }
+        None => {
+            if let Some(m_luks) = routines::root_luks(manifest) {
+                cmdline.push(
+                    "luks",
+                    &format!(
+                        "cryptdevice={}:{}",
+                        devices.stable_ref(&m_luks.device),
+                        m_luks.name
+                    ),
+                )?;
+            }
+
+            cmdline.push(
+                "rootfs",
+                &format!(
+                    "root={}",
+                    devices.stable_ref(&manifest.rootfs.device)
+                ),
+            )?;
+            cmdline.push("rootfs", "rw")?;
+        }
+    }
+
+    for param in manifest.cmdline.iter().flatten() {
+        cmdline.push("manifest key cmdline", param)?;
+    }
+
+    Ok(cmdline)
+}
+
+/// Single writer of kernel command line to bootloader configs:
+/// `/etc/kernel/cmdline` (UKIs and kernel-install), and GRUB_CMDLINE_LINUX
+/// in `/etc/default/grub` if GRUB is installed and there are parameters
+/// not generated by grub-mkconfig
+pub fn write(location: &str, cmdline: &KernelCmdline) -> Result<(), AliError> {
+    write_under(location, KERNEL_CMDLINE, &format!("{cmdline}\n"))?;
+
+    let params = cmdline.grub_params();
+    if params.is_empty() {
+        return Ok(());
+    }
+
+    let Ok(mut default_grub) =
+        ShellConf::read(&format!("{location}{DEFAULT_GRUB}"))
+    else {
+        return Ok(());
+    };
+
+    // Leave templates to be filled by hooks, e.g. @replace-token
+    if default_grub.to_string().contains("{{") {
+        log::warn!(
+            "{DEFAULT_GRUB} has template tokens, not setting GRUB_CMDLINE_LINUX=\"{params}\""
+        );
+        return Ok(());
+    }
+
+    set_grub_cmdline(&mut default_grub, &params);
+    default_grub.write_under(location, DEFAULT_GRUB)
+}
+
+/// Replaces GRUB_CMDLINE_LINUX in `default_grub`, or appends it if missing
+fn set_grub_cmdline(default_grub: &mut ShellConf, params: &str) {
+    match default_grub.get(KEY_GRUB_CMDLINE) {
+        Some(Value::String(old)) if !old.is_empty() && old != params => {
+            log::info!(
+                "{DEFAULT_GRUB}: replacing {KEY_GRUB_CMDLINE}=\"{old}\""
+            );
+        }
+        _ => {}
+    }
+
+    default_grub.set_string(KEY_GRUB_CMDLINE, params);
+}
+
+#[test]
+fn test_set_grub_cmdline() {
+    let set = |default_grub: &str, params: &str| {
+        let mut default_grub = ShellConf::parse(default_grub);
+        set_grub_cmdline(&mut default_grub, params);
+
+        default_grub.to_string()
+    };
+
+    let default_grub = "GRUB_DEFAULT=0\nGRUB_CMDLINE_LINUX_DEFAULT=\"loglevel=3 quiet\"\nGRUB_CMDLINE_LINUX=\"\"\n";
+    assert_eq!(
+        "GRUB_DEFAULT=0\nGRUB_CMDLINE_LINUX_DEFAULT=\"loglevel=3 quiet\"\nGRUB_CMDLINE_LINUX=\"cryptdevice=UUID=foo:root\"\n",
+        set(default_grub, "cryptdevice=UUID=foo:root"),
+    );
+
+    assert_eq!(
+        "GRUB_DEFAULT=0\nGRUB_CMDLINE_LINUX=\"quiet\"\n",
+        set("GRUB_DEFAULT=0\n", "quiet"),
+    );
+}
@@ -0,0 +1,295 @@
+use std::fs::Permissions;
+use std::os::unix::fs::PermissionsExt;
+
+use crate::ali::{
+    Manifest,
+    ManifestDomainJoin,
+};
+use crate::errors::AliError;
+use crate::linux::nsswitch::{
+    self,
+    FILENAME_NSSWITCH,
+};
+use crate::linux::pam::{
+    self,
+    PamConf,
+};
+use crate::linux::systemd;
+use crate::utils::fs::{
+    path_under,
+    write_under,
+};
+
+const SSSD_CONF: &str = "/etc/sssd/sssd.conf";
+const KRB5_CONF: &str = "/etc/krb5.conf";
+const PAM_SERVICE: &str = "system-auth";
+
+/// Join password, removed once joined
+const SECRET: &str = "/etc/ali-rs/domain-join.secret";
+const SCRIPT: &str = "/usr/local/bin/ali-rs-domain-join";
+const UNIT: &str = "ali-rs-domain-join.service";
+
+/// Join status written by first boot join, as JSON
+pub const STATUS: &str = "/var/lib/ali-rs/domain-join.json";
+
+/// Databases served by sssd
+const NSS_DATABASES: &[&str] = &["passwd", "group", "shadow", "netgroup"];
+
+/// Units making `time-sync.target` wait for synchronized clock,
+/// since Kerberos rejects clocks skewed by over 5 minutes
+const UNITS_TIME_SYNC: &[(&str, &str)] = &[
+    ("systemd-timesyncd.service", "sysinit.target"),
+    ("systemd-time-wait-sync.service", "sysinit.target"),
+];
+
+/// Writes SSSD, Kerberos, NSS and PAM configs for domain users,
+/// and first boot join unit to new system at `location`
+pub fn write_files(
+    manifest: &Manifest,
+    m_join: &ManifestDomainJoin,
+    location: &str,
+) -> Result<(), AliError> {
+    let filename_pam = format!("{}/{PAM_SERVICE}", pam::DIR_PAM_D);
+    let mut conf_pam = PamConf::read(&format!("{location}{filename_pam}"))?;
+
+    if let Err(err) = conf_pam.add_sss().and_then(|_| conf_pam.check_auth()) {
+        return Err(AliError::BadManifest(format!(
+            "domain_join: refusing to write {filename_pam}: {err}"
+        )));
+    }
+
+    let secret = read_secret(m_join)?;
+
+    write_under(location, SSSD_CONF, &sssd_conf(m_join))?;
+    chmod(location, SSSD_CONF, 0o600)?;
+    write_under(location, KRB5_CONF, &krb5_conf(m_join))?;
+
+    let nss = std::fs::read_to_string(format!("{location}{FILENAME_NSSWITCH}"))
+        .unwrap_or_default();
+    let nss = nsswitch::add_source(&nss, NSS_DATABASES, "sss");
+    write_under(location, FILENAME_NSSWITCH, &nss)?;
+    write_under(location, &filename_pam, &conf_pam.to_string())?;
+
+    write_under(location, SECRET, &format!("{secret}\n"))?;
+    chmod(location, SECRET, 0o600)?;
+    write_under(location, SCRIPT, &script(manifest, m_join))?;
+    chmod(location, SCRIPT, 0o755)?;
+    write_under(location, &format!("/etc/systemd/system/{UNIT}"), UNIT_JOIN)?;
+
+    // Manifest key time replaces systemd-timesyncd
+    if manifest.time.is_none() {
+        for (unit, target) in UNITS_TIME_SYNC {
+            systemd::enable_service(location, unit, target)?;
+        }
+    }
+
+    systemd::enable_service(location, "sssd.service", "multi-user.target")?;
+    systemd::enable_service(location, UNIT, "multi-user.target")
+}
+
+fn chmod(location: &str, path: &str, mode: u32) -> Result<(), AliError> {
+    let path = path_under(location, path)?;
+    std::fs::set_permissions(&path, Permissions::from_mode(mode))
+        .map_err(|err| AliError::FileError(err, format!("chmod {path}")))
+}
+
+/// Reads join password from live system
+fn read_secret(m_join: &ManifestDomainJoin) -> Result<String, AliError> {
+    let path = match (&m_join.password_file, &m_join.one_time_password_file)
+    {
+        (Some(path), None) | (None, Some(path)) => path,
+        _ => {
+            return Err(AliError::BadManifest(
+                "domain_join: expecting either password_file or one_time_password_file"
+                    .to_string(),
+            ));
+        }
+    };
+
+    let secret = std::fs::read_to_string(path)
+        .map_err(|err| {
+            AliError::FileError(err, format!("domain_join: reading {path}"))
+        })?
+        .trim_end_matches(['\n', '\r'])
+        .to_string();
+
+    if secret.is_empty() || secret.contains('\n') {
+        return Err(AliError::BadManifest(format!(
+            "domain_join: bad password in {path}"
+        )));
+    }
+
+    Ok(secret)
+}
+
+fn sssd_conf(m_join: &ManifestDomainJoin) -> String {
+    let domain = &m_join.domain;
+    let fq = m_join.fully_qualified_names.unwrap_or(false);
+
+    format!(
+        "\
+# Generated by ali-rs, used once domain is joined on first boot
+[sssd]
+services = nss, pam
+domains = {domain}
+
+[domain/{domain}]
+id_provider = ad
+access_provider = ad
+ad_domain = {domain}
+krb5_realm = {realm}
+ldap_id_mapping = true
+cache_credentials = true
+use_fully_qualified_names = {fq}
+fallback_homedir = /home/%u
+default_shell = /bin/bash
+",
+        realm = m_join.realm(),
+    )
+}
+
+fn krb5_conf(m_join: &ManifestDomainJoin) -> String {
+    format!(
+        "\
+# Generated by ali-rs
+[libdefaults]
+    default_realm = {}
+    dns_lookup_realm = false
+    dns_lookup_kdc = true
+    rdns = false
+",
+        m_join.realm(),
+    )
+}
+
+/// Join script, which records join status and removes password
+/// once joined
+fn script(manifest: &Manifest, m_join: &ManifestDomainJoin) -> String {
+    let domain = &m_join.domain;
+    let mut args = format!("--domain={domain} --domain-realm={}", m_join.realm());
+
+    let computer_name =
+        m_join.computer_name.as_ref().or(manifest.hostname.as_ref());
+    if let Some(name) = computer_name {
+        args.push_str(&format!(" --computer-name={name}"));
+    }
+    if let Some(ref ou) = m_join.ou {
+        args.push_str(&format!(" --domain-ou='{ou}'"));
+    }
+
+    let join = match m_join.user {
+        Some(ref user) => {
+            format!(
+                "adcli join {args} --login-user='{user}' --stdin-password < {SECRET}"
+            )
+        }
+        None => {
+            format!("adcli join {args} --one-time-password=\"$(cat {SECRET})\"")
+        }
+    };
+
+    format!(
+        "\
+#!/bin/sh
+# Generated by ali-rs, joins domain {domain} on first boot
+set -u
+
+{join}
+code=$?
+
+joined=false
+if [ \"$code\" -eq 0 ]; then
+    joined=true
+    rm -f {SECRET}
+    systemctl restart sssd.service
+fi
+
+mkdir -p /var/lib/ali-rs
+printf '{{\"domain\":\"{domain}\",\"joined\":%s,\"exit_code\":%d,\"time\":\"%s\"}}\\n' \\
+    \"$joined\" \"$code\" \"$(date -Iseconds)\" > {STATUS}
+
+exit \"$code\"
+"
+    )
+}
+
+/// Joins once network is up and clock is synchronized, retrying
+/// until joined, i.e. while password exists
+const UNIT_JOIN: &str = "\
+[Unit]
+Description=Join Active Directory domain on first boot
+Wants=network-online.target time-sync.target
+After=network-online.target time-sync.target
+ConditionPathExists=/etc/ali-rs/domain-join.secret
+
+[Service]
+Type=oneshot
+ExecStart=/usr/local/bin/ali-rs-domain-join
+Restart=on-failure
+RestartSec=1min
+
+[Install]
+WantedBy=multi-user.target
+";
+
+#[test]
+fn test_domain_join() {
+    let yaml = r#"
+version: 1
+hostname: ws01
+rootfs:
+  device: /dev/vda2
+  fstype: ext4
+ad:
+  domain: corp.example.com
+  user: joiner
+  password_file: /root/ad.pass
+  ou: OU=Linux,DC=corp,DC=example,DC=com
+"#;
+
+    let manifest = crate::ali::parse(yaml).unwrap();
+    let m_join = manifest.domain_join.as_ref().unwrap();
+    assert_eq!("CORP.EXAMPLE.COM", m_join.realm());
+    assert!(UNIT_JOIN.contains(SECRET));
+    assert!(UNIT_JOIN.contains(SCRIPT));
+
+    let script = script(&manifest, m_join);
+    assert!(script.contains(
+        "adcli join --domain=corp.example.com --domain-realm=CORP.EXAMPLE.COM --computer-name=ws01 --domain-ou='OU=Linux,DC=corp,DC=example,DC=com' --login-user='joiner' --stdin-password < /etc/ali-rs/domain-join.secret\n"
+    ));
+    assert!(script.contains(&format!("> {STATUS}\n")));
+
+    let root = std::env::temp_dir()
+        .join(format!("ali-rs-test-domain-join-{}", std::process::id()));
+    let location = root.to_str().unwrap();
+    let password_file = root.join("ad.pass");
+    std::fs::create_dir_all(root.join("etc/pam.d")).unwrap();
+    std::fs::write(root.join("etc/pam.d/system-auth"), pam::TEST_SYSTEM_AUTH)
+        .unwrap();
+    std::fs::write(&password_file, "hunter2\n").unwrap();
+
+    let m_join = ManifestDomainJoin {
+        password_file: Some(password_file.to_str().unwrap().to_string()),
+        ..m_join.clone()
+    };
+    write_files(&manifest, &m_join, location).unwrap();
+
+    let secret = root.join(&SECRET[1..]);
+    assert_eq!("hunter2\n", std::fs::read_to_string(&secret).unwrap());
+    let mode = std::fs::metadata(&secret).unwrap().permissions().mode();
+    assert_eq!(0o600, mode & 0o777);
+
+    let sssd = std::fs::read_to_string(root.join(&SSSD_CONF[1..])).unwrap();
+    assert!(sssd.contains("use_fully_qualified_names = false\n"));
+
+    let conf = PamConf::read(&format!("{location}/etc/pam.d/system-auth"))
+        .unwrap();
+    assert_eq!(Some(2), conf.position("auth", "pam_sss.so"));
+    assert!(root
+        .join("etc/systemd/system/multi-user.target.wants")
+        .join(UNIT)
+        .symlink_metadata()
+        .is_ok());
+
+    std::fs::remove_dir_all(root).unwrap();
+}
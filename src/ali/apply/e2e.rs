@@ -0,0 +1,237 @@
+//! End-to-end tests of destructive stage mountpoints against loop
+//! devices backed by sparse files, enabled with feature `e2e`.
+//!
+//! The tests partition, encrypt, and format real block devices,
+//! so they must run as root, e.g. on CI runners:
+//!
+//! ```shell
+//! sudo -E cargo test --features e2e e2e
+//! ```
+
+use std::process::Command;
+
+use serde_json::Value;
+
+use super::stages;
+use crate::ali;
+use crate::types::stage::StageActions;
+use crate::utils::shell;
+
+/// Size of sparse backing files
+const SIZE_BACKING: u64 = 1 << 30;
+
+/// Loop device attached to a sparse backing file,
+/// torn down with everything on top of it when dropped
+struct LoopDev {
+    backing: String,
+    device: String,
+    root_location: String,
+    luks: Vec<String>,
+    vgs: Vec<String>,
+}
+
+impl LoopDev {
+    fn new(name: &str) -> Self {
+        assert!(
+            nix::unistd::geteuid().is_root(),
+            "e2e tests need root to attach loop devices"
+        );
+
+        let tmp = std::env::temp_dir();
+        let backing = tmp.join(format!("{name}.img"));
+        let backing = backing.to_str().unwrap().to_string();
+        let root_location = tmp.join(format!("{name}-root"));
+        let root_location = root_location.to_str().unwrap().to_string();
+
+        std::fs::File::create(&backing)
+            .and_then(|f| f.set_len(SIZE_BACKING))
+            .expect("failed to create sparse backing file");
+
+        let output = shell::exec_with_output(
+            "losetup",
+            &["--find", "--show", "--partscan", &backing],
+        )
+        .expect("losetup failed to attach backing file");
+
+        LoopDev {
+            backing,
+            device: String::from_utf8_lossy(&output).trim().to_string(),
+            root_location,
+            luks: Vec::new(),
+            vgs: Vec::new(),
+        }
+    }
+
+    fn part(&self, n: usize) -> String {
+        format!("{}p{n}", self.device)
+    }
+}
+
+/// Teardown uses [`Command`] directly and ignores failures,
+/// because it also runs after failed or partial applies
+impl Drop for LoopDev {
+    fn drop(&mut self) {
+        let run = |cmd: &str, args: &[&str]| {
+            let _ = Command::new(cmd).args(args).status();
+        };
+
+        run("umount", &["-R", &self.root_location]);
+        for vg in &self.vgs {
+            run("vgchange", &["-an", vg]);
+        }
+        for name in &self.luks {
+            run("cryptsetup", &["close", name]);
+        }
+
+        run("losetup", &["-d", &self.device]);
+        let _ = std::fs::remove_file(&self.backing);
+        let _ = std::fs::remove_dir_all(&self.root_location);
+    }
+}
+
+/// Flat list of block devices under `device` from lsblk(8),
+/// as `(path, type, fstype, mountpoint)`
+fn lsblk(device: &str) -> Vec<(String, String, String, String)> {
+    let output = shell::exec_with_output(
+        "lsblk",
+        &["--json", "--list", "-o", "PATH,TYPE,FSTYPE,MOUNTPOINT", device],
+    )
+    .expect("lsblk failed");
+
+    let lsblk: Value = serde_json::from_slice(&output).unwrap();
+    let field = |dev: &Value, key: &str| {
+        dev[key].as_str().unwrap_or_default().to_string()
+    };
+
+    lsblk["blockdevices"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|dev| {
+            (
+                field(dev, "path"),
+                field(dev, "type"),
+                field(dev, "fstype"),
+                field(dev, "mountpoint"),
+            )
+        })
+        .collect()
+}
+
+/// Filesystem type of `device` from blkid(8)
+fn blkid_type(device: &str) -> String {
+    let output =
+        shell::exec_with_output("blkid", &["-s", "TYPE", "-o", "value", device])
+            .expect("blkid failed");
+
+    String::from_utf8_lossy(&output).trim().to_string()
+}
+
+#[test]
+fn test_e2e_mountpoints_lvm_on_luks() {
+    let name = format!("ali-rs-e2e-{}", std::process::id());
+    let mut dev = LoopDev::new(&name);
+
+    let luks = format!("{name}-crypt");
+    let vg = format!("alie2e{}", std::process::id());
+    dev.luks.push(luks.clone());
+    dev.vgs.push(vg.clone());
+
+    let yaml = format!(
+        r#"
+version: 1
+
+disks:
+  - device: {device}
+    table: mbr-dos
+    partitions:
+      - label: boot
+        size: 100M
+        type: ef
+      - label: root
+        type: 8e
+
+dm:
+  - type: luks
+    device: {p2}
+    name: {luks}
+    key: e2epassphrase
+
+  - type: lvm
+    pvs:
+      - /dev/mapper/{luks}
+    vgs:
+      - name: {vg}
+        pvs:
+          - /dev/mapper/{luks}
+    lvs:
+      - name: swaplv
+        vg: {vg}
+        size: 64M
+      - name: rootlv
+        vg: {vg}
+
+rootfs:
+  device: /dev/{vg}/rootlv
+  fstype: ext4
+
+fs:
+  - device: {p1}
+    fstype: vfat
+    fsopts: -F 32
+
+mountpoints:
+  - device: {p1}
+    dest: /boot
+"#,
+        device = dev.device,
+        p1 = dev.part(1),
+        p2 = dev.part(2),
+    );
+
+    let manifest = ali::parse(&yaml).expect("bad e2e manifest");
+    let mut actions = StageActions::default();
+    stages::mountpoints(&manifest, &dev.root_location, &mut actions)
+        .expect("stage mountpoints failed");
+
+    let devices = lsblk(&dev.device);
+    let find = |path: &str| {
+        devices
+            .iter()
+            .find(|(p, ..)| p == path)
+            .unwrap_or_else(|| panic!("{path} not found in {devices:?}"))
+    };
+
+    let mapper_luks = format!("/dev/mapper/{luks}");
+    let mapper_root = format!("/dev/mapper/{vg}-rootlv");
+    let mapper_swap = format!("/dev/mapper/{vg}-swaplv");
+    let boot = format!("{}/boot", dev.root_location);
+
+    let (_, t, fstype, mnt) = find(&dev.part(1));
+    assert_eq!(
+        ("part", "vfat", boot.as_str()),
+        (t.as_str(), fstype.as_str(), mnt.as_str()),
+    );
+
+    let (_, t, fstype, _) = find(&dev.part(2));
+    assert_eq!(("part", "crypto_LUKS"), (t.as_str(), fstype.as_str()));
+
+    let (_, t, fstype, _) = find(&mapper_luks);
+    assert_eq!(("crypt", "LVM2_member"), (t.as_str(), fstype.as_str()));
+
+    let (_, t, fstype, mnt) = find(&mapper_root);
+    assert_eq!(
+        ("lvm", "ext4", dev.root_location.as_str()),
+        (t.as_str(), fstype.as_str(), mnt.as_str()),
+    );
+
+    let (_, t, _, _) = find(&mapper_swap);
+    assert_eq!("lvm", t);
+
+    assert_eq!("vfat", blkid_type(&dev.part(1)));
+    assert_eq!("crypto_LUKS", blkid_type(&dev.part(2)));
+    assert_eq!("LVM2_member", blkid_type(&mapper_luks));
+    assert_eq!("ext4", blkid_type(&mapper_root));
+
+    assert!(!actions.mountpoints.is_empty());
+}
@@ -0,0 +1,148 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+use crate::ali::Manifest;
+use crate::constants::{
+    self,
+    defaults,
+};
+use crate::types::stage::Stage;
+
+/// Device speed class, used to key historical durations
+/// so that HDD runs do not skew SSD estimates and vice versa
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SpeedClass {
+    Nvme,
+    Ssd,
+    Hdd,
+    Unknown,
+}
+
+/// Historical stage durations, persisted as JSON between runs
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Stats {
+    entries: HashMap<String, Entry>,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+struct Entry {
+    runs: u32,
+    mean_secs: f64,
+}
+
+impl Stats {
+    /// Loads stats from file, or returns empty stats
+    /// if the file is missing or malformed
+    pub fn load(path: &str) -> Self {
+        std::fs::read_to_string(path)
+            .ok()
+            .and_then(|s| serde_json::from_str(&s).ok())
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self, path: &str) -> std::io::Result<()> {
+        if let Some(parent) = std::path::Path::new(path).parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        std::fs::write(path, serde_json::to_string(self)?)
+    }
+
+    /// Returns estimated duration of `stage` and number of runs
+    /// the estimate is based on
+    pub fn estimate(
+        &self,
+        stage: &Stage,
+        class: &SpeedClass,
+    ) -> Option<(Duration, u32)> {
+        self.entries
+            .get(&key(stage, class))
+            .map(|e| (Duration::from_secs_f64(e.mean_secs), e.runs))
+    }
+
+    /// Records a successful stage duration into the running mean
+    pub fn record(
+        &mut self,
+        stage: &Stage,
+        class: &SpeedClass,
+        elapsed: Duration,
+    ) {
+        let entry = self.entries.entry(key(stage, class)).or_default();
+        let total = entry.mean_secs * entry.runs as f64;
+
+        entry.runs += 1;
+        entry.mean_secs = (total + elapsed.as_secs_f64()) / entry.runs as f64;
+    }
+}
+
+/// Returns path to stats file, overridable with env `ALI_STATS`
+pub fn stats_file() -> String {
+    std::env::var(constants::ENV_ALI_STATS)
+        .unwrap_or(defaults::STATS_FILE.to_string())
+}
+
+/// Classifies the manifest disks by their slowest member.
+/// Manifests without `disks` are classified as unknown.
+pub fn speed_class(manifest: &Manifest) -> SpeedClass {
+    let disks = match &manifest.disks {
+        Some(disks) if !disks.is_empty() => disks,
+        _ => return SpeedClass::Unknown,
+    };
+
+    let mut class = SpeedClass::Nvme;
+    for disk in disks {
+        let name = disk.device.trim_start_matches("/dev/");
+        let rotational = std::fs::read_to_string(format!(
+            "/sys/block/{name}/queue/rotational"
+        ));
+
+        match rotational.as_ref().map(|s| s.trim()) {
+            Ok("1") => return SpeedClass::Hdd,
+            Ok("0") if name.starts_with("nvme") => {}
+            Ok("0") => class = SpeedClass::Ssd,
+            _ => return SpeedClass::Unknown,
+        }
+    }
+
+    class
+}
+
+fn key(stage: &Stage, class: &SpeedClass) -> String {
+    format!("{stage}/{class}")
+}
+
+impl std::fmt::Display for SpeedClass {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Nvme => write!(f, "nvme"),
+            Self::Ssd => write!(f, "ssd"),
+            Self::Hdd => write!(f, "hdd"),
+            Self::Unknown => write!(f, "unknown"),
+        }
+    }
+}
+
+#[test]
+fn test_stats_record_estimate() {
+    let mut stats = Stats::default();
+    let class = SpeedClass::Ssd;
+
+    assert!(stats.estimate(&Stage::Bootstrap, &class).is_none());
+
+    stats.record(&Stage::Bootstrap, &class, Duration::from_secs(100));
+    stats.record(&Stage::Bootstrap, &class, Duration::from_secs(200));
+
+    let (eta, runs) = stats.estimate(&Stage::Bootstrap, &class).unwrap();
+    assert_eq!(2, runs);
+    assert_eq!(Duration::from_secs(150), eta);
+
+    assert!(stats
+        .estimate(&Stage::Bootstrap, &SpeedClass::Hdd)
+        .is_none());
+    assert!(stats.estimate(&Stage::Routines, &class).is_none());
+}
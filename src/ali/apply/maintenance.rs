@@ -0,0 +1,228 @@
+use std::fs::Permissions;
+use std::os::unix::fs::PermissionsExt;
+
+use crate::ali::{
+    Manifest,
+    ManifestMaintenance,
+    ManifestSmartd,
+};
+use crate::errors::AliError;
+use crate::linux::systemd;
+use crate::utils::fs::{
+    path_under,
+    write_under,
+};
+
+const DIR_UNITS: &str = "/etc/systemd/system";
+const UNIT_BALANCE: &str = "ali-rs-btrfs-balance@";
+const SMARTD_CONF: &str = "/etc/smartd.conf";
+const SMARTD_NOTIFY: &str = "/usr/local/bin/ali-rs-smartd-notify";
+
+/// Monitor all disks with offline and self tests: short tests daily at 2am,
+/// and long tests on Saturdays at 3am. Skip disks in standby.
+const SMARTD_DIRECTIVES: &str =
+    "DEVICESCAN -a -o on -S on -n standby,q -s (S/../.././02|L/../../6/03)";
+
+/// Writes maintenance units and configs to new system at `location`,
+/// and enables them
+pub fn write_files(
+    manifest: &Manifest,
+    m_maintenance: &ManifestMaintenance,
+    location: &str,
+) -> Result<(), AliError> {
+    let btrfs = btrfs_mountpoints(manifest);
+
+    if m_maintenance.btrfs_scrub.unwrap_or(false) {
+        for mountpoint in &btrfs {
+            let timer = format!(
+                "btrfs-scrub@{}.timer",
+                systemd::escape_path(mountpoint)
+            );
+
+            systemd::enable_service(location, &timer, "timers.target")?;
+        }
+    }
+
+    if let Some(schedule) = &m_maintenance.btrfs_balance {
+        let (service, timer) = units_balance(schedule);
+        write_under(
+            location,
+            &format!("{DIR_UNITS}/{UNIT_BALANCE}.service"),
+            &service,
+        )?;
+        write_under(
+            location,
+            &format!("{DIR_UNITS}/{UNIT_BALANCE}.timer"),
+            &timer,
+        )?;
+
+        for mountpoint in &btrfs {
+            let timer = format!(
+                "{UNIT_BALANCE}{}.timer",
+                systemd::escape_path(mountpoint)
+            );
+
+            systemd::enable_service(location, &timer, "timers.target")?;
+        }
+    }
+
+    if m_maintenance.fstrim.unwrap_or(false) {
+        systemd::enable_service(location, "fstrim.timer", "timers.target")?;
+    }
+
+    if let Some(smartd) = &m_maintenance.smartd {
+        write_under(location, SMARTD_CONF, &smartd_conf(smartd))?;
+
+        if let Some(ntfy) = &smartd.ntfy {
+            write_notify(location, ntfy)?;
+        }
+
+        systemd::enable_service(
+            location,
+            "smartd.service",
+            "multi-user.target",
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Mountpoints in new system of each Btrfs filesystem,
+/// e.g. `/` and `/data`. Only the first mountpoint of each filesystem
+/// is returned, since scrub and balance work on whole filesystems.
+fn btrfs_mountpoints(manifest: &Manifest) -> Vec<String> {
+    let mut devices = Vec::new();
+    let mut mountpoints = Vec::new();
+
+    if manifest.rootfs.fs_type == "btrfs" {
+        devices.push(manifest.rootfs.device.as_str());
+        mountpoints.push("/".to_string());
+    }
+
+    for mnt in manifest.mountpoints.iter().flatten() {
+        let is_btrfs = manifest
+            .filesystems
+            .iter()
+            .flatten()
+            .any(|fs| fs.device == mnt.device && fs.fs_type == "btrfs");
+
+        if is_btrfs && !devices.contains(&mnt.device.as_str()) {
+            devices.push(mnt.device.as_str());
+            mountpoints.push(mnt.dest.clone());
+        }
+    }
+
+    mountpoints
+}
+
+/// Template units of periodic Btrfs balance, with instance
+/// being the escaped mountpoint
+fn units_balance(schedule: &str) -> (String, String) {
+    let service = "\
+[Unit]
+Description=Btrfs balance on %f
+ConditionPathIsMountPoint=%f
+
+[Service]
+Type=oneshot
+Nice=19
+IOSchedulingClass=idle
+ExecStart=/usr/bin/btrfs balance start -dusage=50 -musage=50 %f
+"
+    .to_string();
+
+    let timer = format!(
+        "\
+[Unit]
+Description=Periodic Btrfs balance on %f
+
+[Timer]
+OnCalendar={schedule}
+RandomizedDelaySec=1h
+Persistent=true
+
+[Install]
+WantedBy=timers.target
+"
+    );
+
+    (service, timer)
+}
+
+fn smartd_conf(smartd: &ManifestSmartd) -> String {
+    let mail = match (&smartd.email, &smartd.ntfy) {
+        (Some(email), None) => format!(" -m {email}"),
+        (Some(email), Some(_)) => {
+            format!(" -m {email} -M exec {SMARTD_NOTIFY}")
+        }
+        (None, Some(_)) => format!(" -m <nomailer> -M exec {SMARTD_NOTIFY}"),
+        (None, None) => String::new(),
+    };
+
+    format!("# Generated by ali-rs\n{SMARTD_DIRECTIVES}{mail}\n")
+}
+
+/// Writes smartd alert script, which sends email (if any) and publishes
+/// to ntfy topic
+fn write_notify(location: &str, ntfy: &str) -> Result<(), AliError> {
+    let script = format!(
+        "\
+#!/bin/sh
+# Generated by ali-rs, executed by smartd on disk alerts
+
+if [ -n \"$SMARTD_ADDRESS\" ]; then
+    echo \"$SMARTD_FULLMESSAGE\" | mail -s \"$SMARTD_SUBJECT\" \"$SMARTD_ADDRESS\"
+fi
+
+curl -fsS -H \"Title: $SMARTD_SUBJECT\" -H 'Priority: high' -H 'Tags: warning' \\
+    -d \"$SMARTD_MESSAGE\" '{ntfy}'
+"
+    );
+
+    write_under(location, SMARTD_NOTIFY, &script)?;
+
+    let path = path_under(location, SMARTD_NOTIFY)?;
+    std::fs::set_permissions(&path, Permissions::from_mode(0o755))
+        .map_err(|err| AliError::FileError(err, format!("chmod {path}")))
+}
+
+#[test]
+fn test_maintenance() {
+    let yaml = r#"
+version: 1
+rootfs:
+  device: /dev/myvg/rootlv
+  fstype: btrfs
+fs:
+  - device: /dev/vda1
+    fstype: vfat
+  - device: /dev/vdb1
+    fstype: btrfs
+mountpoints:
+  - device: /dev/myvg/rootlv
+    dest: /home
+    mntopts: subvol=/home
+  - device: /dev/vda1
+    dest: /boot
+  - device: /dev/vdb1
+    dest: /data
+  - device: /dev/vdb1
+    dest: /data/archive
+maintenance:
+  scrub: true
+  smartd:
+    email: admin@example.com
+    ntfy: https://ntfy.sh/mytopic
+"#;
+
+    let manifest = crate::ali::parse(yaml).unwrap();
+    assert_eq!(vec!["/", "/data"], btrfs_mountpoints(&manifest));
+
+    let smartd = manifest.maintenance.unwrap().smartd.unwrap();
+    assert_eq!(
+        format!(
+            "# Generated by ali-rs\n{SMARTD_DIRECTIVES} -m admin@example.com -M exec {SMARTD_NOTIFY}\n"
+        ),
+        smartd_conf(&smartd),
+    );
+}
@@ -1,21 +1,42 @@
 mod archchroot;
 mod bootstrap;
-mod disks;
-mod dm;
-mod fs;
+pub mod ca;
+pub mod cmdline;
+mod domain_join;
+#[cfg(all(test, feature = "e2e"))]
+mod e2e;
+mod eta;
+mod maintenance;
 mod map_err;
+mod mountpoints;
+mod netroot;
+pub mod plan;
+mod portable;
 mod routines;
+mod skel;
+mod ssh;
 mod stages;
+mod time;
 
 use std::collections::HashSet;
 
 use crate::ali::Manifest;
 use crate::errors::AliError;
+use crate::linux::rusage;
 use crate::types::stage::{
     self,
     Stage,
     StageActions,
 };
+use crate::utils::{
+    accessible,
+    progress,
+};
+
+pub use bootstrap::{
+    set_mirror_country,
+    set_shared_cache,
+};
 
 type ApplyFn = fn(&Manifest, &str, &mut StageActions) -> Result<(), AliError>;
 
@@ -29,9 +50,30 @@ pub fn apply_manifest(
 ) -> Result<Box<StageActions>, AliError> {
     let mut progress = Box::default();
 
-    for stage in stage::STAGES {
-        if skip.contains(&stage) {
-            continue;
+    let stats_file = eta::stats_file();
+    let mut stats = eta::Stats::load(&stats_file);
+    let speed_class = eta::speed_class(manifest);
+
+    print_etas(&stats, &speed_class, &skip);
+
+    let total = stage::STAGES.iter().filter(|s| !skip.contains(s)).count();
+
+    for (i, stage) in stage::STAGES
+        .into_iter()
+        .filter(|s| !skip.contains(s))
+        .enumerate()
+    {
+        progress::stage(&stage);
+        accessible::announce(&format!(
+            "Starting {stage}, step {} of {total}.",
+            i + 1
+        ));
+
+        if let Some((eta, runs)) = stats.estimate(&stage, &speed_class) {
+            log::info!(
+                "{stage}: ETA {} (mean of {runs} run(s) on {speed_class})",
+                progress::fmt_duration(eta),
+            );
         }
 
         let f: ApplyFn = match stage {
@@ -43,13 +85,54 @@ pub fn apply_manifest(
             Stage::PostInstallUser => stages::postinstall_user,
         };
 
-        if let Err(err) = f(manifest, install_location, &mut progress) {
+        let start = std::time::Instant::now();
+        let usage_start = rusage::Snapshot::now();
+        let result = f(manifest, install_location, &mut progress);
+        progress.usage.push(usage_start.stage_usage(&stage.to_string()));
+
+        if let Err(err) = result {
+            save_stats(&stats, &stats_file);
+            accessible::announce(&format!("Failed {stage}."));
+
             return Err(AliError::InstallError {
                 error: Box::new(err),
+                stage_failed: stage,
                 stages_performed: progress,
             });
         }
+
+        stats.record(&stage, &speed_class, start.elapsed());
+        accessible::announce(&format!(
+            "Finished {stage} in {}.",
+            progress::fmt_duration(start.elapsed())
+        ));
     }
 
+    save_stats(&stats, &stats_file);
+
     Ok(progress)
 }
+
+/// Prints ETA for the whole run, if all stages to be applied
+/// have historical durations
+fn print_etas(
+    stats: &eta::Stats,
+    speed_class: &eta::SpeedClass,
+    skip: &HashSet<Stage>,
+) {
+    let mut total = std::time::Duration::ZERO;
+    for stage in stage::STAGES.iter().filter(|s| !skip.contains(s)) {
+        match stats.estimate(stage, speed_class) {
+            Some((eta, _)) => total += eta,
+            None => return,
+        }
+    }
+
+    log::info!("ETA for all stages: {}", progress::fmt_duration(total));
+}
+
+fn save_stats(stats: &eta::Stats, stats_file: &str) {
+    if let Err(err) = stats.save(stats_file) {
+        log::warn!("failed to save stage stats to {stats_file}: {err}");
+    }
+}
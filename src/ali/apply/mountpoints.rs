@@ -0,0 +1,327 @@
+use crate::ali::{
+    DiskWipe,
+    Dm,
+    Manifest,
+    ManifestDisk,
+    ManifestFs,
+    ManifestLuks,
+    ManifestLvmLv,
+    ManifestLvmVg,
+    ManifestMountpoint,
+};
+use crate::errors::AliError;
+use crate::linux::mount::prepend_base;
+use crate::linux::{
+    self,
+    fdisk,
+    wipe,
+};
+use crate::types::action::ActionMountpoints;
+use crate::utils::fs::mkdir_p;
+use crate::utils::progress;
+
+/// One operation of stage mountpoints. Both the stage and
+/// `ali-rs plan` work from [`ops`], so plans cannot drift from applies.
+#[derive(Debug)]
+pub enum Op<'a> {
+    Wipe(&'a ManifestDisk, DiskWipe),
+    CreateTable(&'a ManifestDisk),
+
+    /// Partition number starts at 1
+    CreatePartition(&'a ManifestDisk, usize),
+    SetPartitionType(&'a ManifestDisk, usize),
+
+    LuksFormat(&'a ManifestLuks),
+    LuksOpen(&'a ManifestLuks),
+    CreatePv(&'a str),
+    CreateVg(&'a ManifestLvmVg),
+    CreateLv(&'a ManifestLvmLv),
+    CreateFs(ManifestFs),
+    MkdirRootFs,
+
+    /// Directory for mountpoint `dest`, under install location
+    MkdirFs(&'a str),
+    Mount(ManifestMountpoint),
+
+    /// Marks end of a group of operations in reports
+    Done(ActionMountpoints),
+}
+
+/// Operations of stage mountpoints with `manifest`, in apply order
+pub fn ops(manifest: &Manifest) -> Vec<Op<'_>> {
+    // Diskless installs go to a plain directory
+    if manifest.netroot.is_some() {
+        return vec![Op::MkdirRootFs];
+    }
+
+    let mut ops = Vec::new();
+
+    // Format and partition disks
+    if let Some(m_disks) = &manifest.disks {
+        for disk in m_disks {
+            if let Some(disk_wipe) =
+                disk.wipe.filter(|w| *w != DiskWipe::None)
+            {
+                ops.push(Op::Wipe(disk, disk_wipe));
+            }
+
+            ops.push(Op::CreateTable(disk));
+            for n in 1..=disk.partitions.len() {
+                ops.push(Op::CreatePartition(disk, n));
+                ops.push(Op::SetPartitionType(disk, n));
+            }
+
+            ops.push(Op::Done(ActionMountpoints::ApplyDisk {
+                device: disk.device.clone(),
+            }));
+        }
+
+        ops.push(Op::Done(ActionMountpoints::ApplyDisks));
+    }
+
+    // Format and create device mappers.
+    // For each LVM entry, do PV, then VG, then LV.
+    if let Some(m_dms) = &manifest.device_mappers {
+        for dm in m_dms {
+            match dm {
+                Dm::Luks(m_luks) => {
+                    ops.push(Op::LuksFormat(m_luks));
+                    ops.push(Op::LuksOpen(m_luks));
+                }
+                Dm::Lvm(m_lvm) => {
+                    let pvs = m_lvm.pvs.iter().flatten();
+                    ops.extend(pvs.map(|pv| Op::CreatePv(pv)));
+                    ops.extend(m_lvm.vgs.iter().flatten().map(Op::CreateVg));
+                    ops.extend(m_lvm.lvs.iter().flatten().map(Op::CreateLv));
+                }
+            }
+        }
+
+        ops.push(Op::Done(ActionMountpoints::ApplyDms));
+    }
+
+    // Create rootfs, then other filesystems
+    ops.push(Op::CreateFs(manifest.rootfs.clone().into()));
+    let filesystems = manifest.filesystems.iter().flatten();
+    ops.extend(filesystems.map(|fs| Op::CreateFs(fs.clone())));
+
+    // mkdir and mount rootfs, then other filesystems under it
+    ops.push(Op::MkdirRootFs);
+    ops.push(Op::Mount(manifest.rootfs.clone().into()));
+
+    let mounts = manifest.mountpoints.iter().flatten();
+    ops.extend(mounts.clone().map(|m| Op::MkdirFs(&m.dest)));
+    ops.extend(mounts.map(|m| Op::Mount(m.clone())));
+
+    ops
+}
+
+impl Op<'_> {
+    /// Action reported once the operation is performed
+    pub fn action(&self) -> ActionMountpoints {
+        match self {
+            Self::Wipe(disk, wipe) => {
+                ActionMountpoints::WipeDisk {
+                    device: disk.device.clone(),
+                    wipe: *wipe,
+                }
+            }
+            Self::CreateTable(disk) => {
+                ActionMountpoints::CreatePartitionTable {
+                    device: disk.device.clone(),
+                    table: disk.table.clone(),
+                }
+            }
+            Self::CreatePartition(disk, n) => {
+                let part = &disk.partitions[n - 1];
+                ActionMountpoints::CreatePartition {
+                    device: disk.device.clone(),
+                    number: *n,
+                    size: part.size.clone().unwrap_or("100%".into()),
+                }
+            }
+            Self::SetPartitionType(disk, n) => {
+                ActionMountpoints::SetPartitionType {
+                    device: disk.device.clone(),
+                    number: *n,
+                    partition_type: disk.partitions[n - 1].part_type.clone(),
+                }
+            }
+            Self::LuksFormat(m_luks) => {
+                ActionMountpoints::CreateDmLuks {
+                    device: m_luks.device.clone(),
+                }
+            }
+            Self::LuksOpen(m_luks) => {
+                ActionMountpoints::OpenDmLuks {
+                    device: m_luks.device.clone(),
+                    name: m_luks.name.clone(),
+                }
+            }
+            Self::CreatePv(pv) => {
+                ActionMountpoints::CreateDmLvmPv(pv.to_string())
+            }
+            Self::CreateVg(vg) => {
+                ActionMountpoints::CreateDmLvmVg {
+                    pvs: vg.pvs.clone(),
+                    vg: format!("/dev/{}", vg.name),
+                }
+            }
+            Self::CreateLv(lv) => {
+                ActionMountpoints::CreateDmLvmLv {
+                    vg: format!("/dev/{}", lv.vg),
+                    lv: format!("/dev/{}/{}", lv.vg, lv.name),
+                }
+            }
+            Self::CreateFs(fs) => {
+                ActionMountpoints::CreateFs {
+                    device: fs.device.clone(),
+                    fs_type: fs.fs_type.clone(),
+                    fs_opts: fs.fs_opts.clone(),
+                }
+            }
+            Self::MkdirRootFs => ActionMountpoints::MkdirRootFs,
+            Self::MkdirFs(dest) => ActionMountpoints::MkdirFs(dest.to_string()),
+            Self::Mount(mnt) => {
+                ActionMountpoints::MountFs {
+                    src: mnt.device.clone(),
+                    dst: mnt.dest.clone(),
+                    opts: mnt.mnt_opts.clone(),
+                }
+            }
+            Self::Done(action) => action.clone(),
+        }
+    }
+
+    /// Performs the operation, for a new system at `root_location`
+    pub fn run(&self, root_location: &str) -> Result<(), AliError> {
+        match self {
+            Self::Wipe(disk, disk_wipe) => {
+                let _step = progress::step(format!("wiping {}", disk.device));
+                wipe::wipe(&disk.device, *disk_wipe)
+            }
+            Self::CreateTable(disk) => {
+                let _step =
+                    progress::step(format!("partitioning {}", disk.device));
+                let cmd = fdisk::create_table_cmd(&disk.table);
+                fdisk::run_fdisk_cmd(&disk.device, &cmd)
+            }
+            Self::CreatePartition(disk, n) => {
+                let part = &disk.partitions[n - 1];
+                let cmd = fdisk::create_partition_cmd(&disk.table, *n, part);
+                fdisk::run_fdisk_cmd(&disk.device, &cmd)
+            }
+            Self::SetPartitionType(disk, n) => {
+                let part = &disk.partitions[n - 1];
+                let cmd = fdisk::set_partition_type_cmd(*n, part);
+                fdisk::run_fdisk_cmd(&disk.device, &cmd)
+            }
+            Self::LuksFormat(m_luks) => {
+                let passphrase = m_luks.passphrase.as_deref();
+                linux::luks::format(&m_luks.device, passphrase)
+            }
+            Self::LuksOpen(m_luks) => {
+                let passphrase = m_luks.passphrase.as_deref();
+                linux::luks::open(&m_luks.device, passphrase, &m_luks.name)
+            }
+            Self::CreatePv(pv) => linux::lvm::create_pv(pv),
+            Self::CreateVg(vg) => linux::lvm::create_vg(vg),
+            Self::CreateLv(lv) => linux::lvm::create_lv(lv),
+            Self::CreateFs(fs) => {
+                let _step = progress::step(format!(
+                    "mkfs.{} {}",
+                    fs.fs_type, fs.device
+                ));
+                linux::mkfs::create_fs(fs)
+            }
+            Self::MkdirRootFs => mkdir_p(root_location),
+            Self::MkdirFs(dest) => mkdir_p(&prepend_base(root_location, dest)),
+            Self::Mount(mnt) => linux::mount::mount(mnt, root_location),
+            Self::Done(_) => Ok(()),
+        }
+    }
+}
+
+#[test]
+fn test_ops() {
+    use std::sync::Arc;
+
+    use crate::ali;
+    use crate::utils::mock::MockRunner;
+    use crate::utils::shell;
+
+    let disk: ali::ManifestDisk = serde_yaml::from_str(
+        r#"
+device: /dev/vda
+table: gpt
+partitions:
+  - label: efi
+    size: 500M
+    type: ef
+  - label: root
+    type: 8e
+"#,
+    )
+    .unwrap();
+
+    let manifest = ali::Manifest {
+        disks: Some(vec![disk]),
+        ..Default::default()
+    };
+    let disk_ops = |manifest: &ali::Manifest| -> Vec<ActionMountpoints> {
+        ops(manifest)
+            .into_iter()
+            .take_while(|op| !matches!(op, Op::Done(_)))
+            .map(|op| op.action())
+            .collect()
+    };
+
+    let run = |manifest: &ali::Manifest| -> Result<(), AliError> {
+        ops(manifest)
+            .iter()
+            .take_while(|op| !matches!(op, Op::Done(_)))
+            .try_for_each(|op| op.run("/alitarget"))
+    };
+
+    assert_eq!(5, disk_ops(&manifest).len());
+
+    let mock = Arc::new(MockRunner::new());
+    let _guard = shell::set_runner(mock.clone());
+    run(&manifest).unwrap();
+
+    // printf and fdisk for table, and each partition and its type
+    let calls = mock.calls();
+    assert_eq!(10, calls.len());
+    assert!(calls.iter().skip(1).step_by(2).all(|c| c == "fdisk /dev/vda"));
+
+    let mock = Arc::new(MockRunner::new().exit("fdisk", 1));
+    let _guard = shell::set_runner(mock.clone());
+    assert!(run(&manifest).is_err());
+    assert_eq!(2, mock.calls().len());
+
+    // Signatures are wiped before creating table
+    let mut manifest = manifest;
+    manifest.disks.as_mut().unwrap()[0].wipe = Some(DiskWipe::Signatures);
+
+    let actions = disk_ops(&manifest);
+    assert_eq!(6, actions.len());
+    assert!(matches!(actions[0], ActionMountpoints::WipeDisk { .. }));
+
+    let mock = Arc::new(MockRunner::new());
+    let _guard = shell::set_runner(mock.clone());
+    run(&manifest).unwrap();
+    assert_eq!("wipefs -a /dev/vda", mock.calls()[1]);
+
+    // Diskless installs only create install location
+    let manifest = ali::Manifest {
+        netroot: Some(ali::ManifestNetRoot {
+            protocol: ali::NetRootProtocol::Nfs,
+            ip: None,
+            target: None,
+            portal: None,
+            initiator: None,
+        }),
+        ..manifest
+    };
+    assert!(matches!(ops(&manifest)[..], [Op::MkdirRootFs]));
+}
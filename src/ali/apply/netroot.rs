@@ -0,0 +1,253 @@
+use crate::ali::{
+    Manifest,
+    ManifestNetRoot,
+    ManifestRootFs,
+    NetRootProtocol,
+};
+use crate::constants::defaults;
+use crate::errors::AliError;
+use crate::types::cmdline::KernelCmdline;
+use crate::utils::fs::write_under;
+
+/// mkinitcpio hooks for NFS root, `net` is from mkinitcpio-nfs-utils.
+/// autodetect is omitted so that the image boots on any netboot client.
+const HOOKS_NFS: &str =
+    "base udev microcode modconf kms keyboard keymap consolefont net filesystems";
+
+/// mkinitcpio hooks for iSCSI root, see [`INITCPIO_INSTALL_ISCSI`]
+const HOOKS_ISCSI: &str =
+    "base udev microcode modconf kms keyboard keymap consolefont block net iscsi filesystems fsck";
+
+const ISCSI_PORT: &str = "3260";
+
+const MKINITCPIO_CONF: &str = "/etc/mkinitcpio.conf.d/10-ali-rs-netroot.conf";
+const INITCPIO_ISCSI_INSTALL: &str = "/etc/initcpio/install/iscsi";
+const INITCPIO_ISCSI_HOOK: &str = "/etc/initcpio/hooks/iscsi";
+
+/// Netboot layout description, written relative to install location
+pub const LAYOUT_FILE: &str = "/boot/ali-rs-netroot.txt";
+
+const INITCPIO_INSTALL_ISCSI: &str = r#"#!/bin/bash
+
+build() {
+    map add_module iscsi_tcp iscsi_ibft libiscsi libiscsi_tcp scsi_transport_iscsi crc32c
+    add_checked_modules /drivers/net
+    add_binary iscsistart
+    add_runscript
+}
+
+help() {
+    echo "Logs in to iSCSI target from kernel command line before mounting root"
+}
+"#;
+
+const INITCPIO_HOOK_ISCSI: &str = r#"#!/usr/bin/ash
+
+run_hook() {
+    modprobe iscsi_tcp
+    iscsistart -i "$iscsi_initiator" -t "$iscsi_target" -g 1 \
+        -a "$iscsi_address" -p "$iscsi_port" -d 1
+}
+"#;
+
+/// Writes initramfs configuration, fstab,
+/// and netboot layout description to the root tree at `location`.
+/// The initramfs is regenerated later in chroot.
+pub fn write_files(
+    manifest: &Manifest,
+    netroot: &ManifestNetRoot,
+    cmdline: &KernelCmdline,
+    location: &str,
+) -> Result<(), AliError> {
+    let hooks = match netroot.protocol {
+        NetRootProtocol::Nfs => HOOKS_NFS,
+        NetRootProtocol::Iscsi => {
+            write_under(
+                location,
+                INITCPIO_ISCSI_INSTALL,
+                INITCPIO_INSTALL_ISCSI,
+            )?;
+            write_under(location, INITCPIO_ISCSI_HOOK, INITCPIO_HOOK_ISCSI)?;
+            HOOKS_ISCSI
+        }
+    };
+
+    write_under(location, MKINITCPIO_CONF, &format!("HOOKS=({hooks})\n"))?;
+
+    let fstab = format!("{location}/etc/fstab");
+    let entry = fstab_entry(&manifest.rootfs);
+    let mut fstab_content = std::fs::read_to_string(&fstab).unwrap_or_default();
+    fstab_content.push_str(&entry);
+    write_under(location, "/etc/fstab", &fstab_content)?;
+
+    let layout = layout(manifest, netroot, cmdline, location);
+    write_under(location, LAYOUT_FILE, &layout)?;
+    log::info!("{layout}");
+
+    Ok(())
+}
+
+/// Kernel parameters for netboot clients
+pub fn kernel_params(
+    manifest: &Manifest,
+    netroot: &ManifestNetRoot,
+) -> Vec<String> {
+    let ip = netroot.ip.as_deref().unwrap_or("dhcp");
+    let rootfs = &manifest.rootfs;
+
+    match netroot.protocol {
+        NetRootProtocol::Nfs => {
+            let nfsroot = match &rootfs.mnt_opts {
+                Some(opts) => format!("{},{opts}", rootfs.device),
+                None => rootfs.device.clone(),
+            };
+
+            vec![
+                "root=/dev/nfs".to_string(),
+                format!("nfsroot={nfsroot}"),
+                format!("ip={ip}"),
+                "rw".to_string(),
+            ]
+        }
+
+        NetRootProtocol::Iscsi => {
+            let (address, port) = split_portal(netroot);
+            let target = netroot.target.as_deref().unwrap_or_default();
+            let initiator = initiator(manifest, netroot);
+
+            vec![
+                format!("root={}", rootfs.device),
+                format!("ip={ip}"),
+                format!("iscsi_initiator={initiator}"),
+                format!("iscsi_target={target}"),
+                format!("iscsi_address={address}"),
+                format!("iscsi_port={port}"),
+                "rw".to_string(),
+            ]
+        }
+    }
+}
+
+/// Describes how to serve the root tree at `location` to netboot clients
+pub fn layout(
+    manifest: &Manifest,
+    netroot: &ManifestNetRoot,
+    cmdline: &KernelCmdline,
+    location: &str,
+) -> String {
+    let tftp = format!(
+        "TFTP root:\n  \
+        vmlinuz-linux        <- {location}/boot/vmlinuz-linux\n  \
+        initramfs-linux.img  <- {location}/boot/initramfs-linux.img\n  \
+        kernel command line: {cmdline}\n"
+    );
+
+    let export = match netroot.protocol {
+        NetRootProtocol::Nfs => {
+            let (server, path) =
+                manifest.rootfs.device.split_once(':').unwrap_or_default();
+
+            format!(
+                "NFS server {server}:\n  \
+                /etc/exports: {path} *(rw,no_root_squash,no_subtree_check)\n  \
+                copy root tree: rsync -aAXH {location}/ {server}:{path}/\n"
+            )
+        }
+
+        NetRootProtocol::Iscsi => {
+            let (address, port) = split_portal(netroot);
+            let target = netroot.target.as_deref().unwrap_or_default();
+
+            format!(
+                "iSCSI target {target} at {address}:{port}:\n  \
+                allow initiator: {}\n  \
+                create {} filesystem on LUN, seen by client as {}\n  \
+                copy root tree: rsync -aAXH {location}/ <LUN mountpoint>/\n",
+                initiator(manifest, netroot),
+                manifest.rootfs.fs_type,
+                manifest.rootfs.device,
+            )
+        }
+    };
+
+    format!("ali-rs netroot layout for {location}\n\n{tftp}\n{export}")
+}
+
+fn fstab_entry(rootfs: &ManifestRootFs) -> String {
+    let opts = rootfs.mnt_opts.as_deref().unwrap_or("defaults");
+
+    format!(
+        "{} / {} {opts},_netdev 0 0\n",
+        rootfs.device, rootfs.fs_type
+    )
+}
+
+fn split_portal(netroot: &ManifestNetRoot) -> (&str, &str) {
+    let portal = netroot.portal.as_deref().unwrap_or_default();
+
+    match portal.rsplit_once(':') {
+        Some((address, port)) if port.parse::<u16>().is_ok() => (address, port),
+        _ => (portal, ISCSI_PORT),
+    }
+}
+
+fn initiator(manifest: &Manifest, netroot: &ManifestNetRoot) -> String {
+    netroot.initiator.clone().unwrap_or_else(|| {
+        let hostname =
+            manifest.hostname.as_deref().unwrap_or(defaults::HOSTNAME);
+
+        format!("iqn.2005-03.org.open-iscsi:{hostname}")
+    })
+}
+
+#[test]
+fn test_netroot_cmdline() {
+    let yaml = r#"
+version: 1
+hostname: client
+rootfs:
+  device: 10.0.0.1:/srv/arch
+  fstype: nfs
+  mntopts: vers=4
+netroot:
+  type: nfs
+"#;
+
+    let manifest = crate::ali::parse(yaml).unwrap();
+    let netroot = manifest.netroot.as_ref().unwrap();
+    assert_eq!(
+        "root=/dev/nfs nfsroot=10.0.0.1:/srv/arch,vers=4 ip=dhcp rw",
+        kernel_params(&manifest, netroot).join(" "),
+    );
+
+    let cmdline =
+        super::cmdline::build(&manifest, &Default::default()).unwrap();
+    let layout = layout(&manifest, netroot, &cmdline, "/srv/arch");
+    assert!(layout.contains("/etc/exports: /srv/arch *(rw"));
+    assert!(layout.contains("rsync -aAXH /srv/arch/ 10.0.0.1:/srv/arch/"));
+
+    let yaml = r#"
+version: 1
+hostname: client
+rootfs:
+  device: /dev/sda1
+  fstype: ext4
+netroot:
+  type: iscsi
+  ip: 10.0.0.2::10.0.0.254:255.255.255.0::eth0:off
+  target: iqn.2024-01.com.example:arch
+  portal: 10.0.0.1:3261
+"#;
+
+    let manifest = crate::ali::parse(yaml).unwrap();
+    let netroot = manifest.netroot.as_ref().unwrap();
+    assert_eq!(
+        "root=/dev/sda1 ip=10.0.0.2::10.0.0.254:255.255.255.0::eth0:off iscsi_initiator=iqn.2005-03.org.open-iscsi:client iscsi_target=iqn.2024-01.com.example:arch iscsi_address=10.0.0.1 iscsi_port=3261 rw",
+        kernel_params(&manifest, netroot).join(" "),
+    );
+
+    assert_eq!(
+        "/dev/sda1 / ext4 defaults,_netdev 0 0\n",
+        fstab_entry(&manifest.rootfs)
+    );
+}
@@ -0,0 +1,428 @@
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+use crate::ali::{
+    Manifest,
+    ManifestDisk,
+    PartitionTable,
+};
+use crate::constants::defaults;
+use crate::hooks;
+use crate::linux::mount::prepend_base;
+use crate::linux::{
+    partition_name,
+    wipe,
+};
+use crate::types::action::{
+    ActionChrootAli,
+    ActionRoutine,
+};
+use crate::types::stage::Stage;
+
+use super::mountpoints::{
+    self,
+    Op,
+};
+use super::{
+    archchroot,
+    bootstrap,
+    routines,
+};
+
+/// One action to be performed by `ali-rs apply`, in apply order
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Step {
+    pub stage: String,
+    pub action: String,
+
+    /// Change to current disks, if assessed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub change: Option<Change>,
+}
+
+/// Change of a step to current disks, see `ali-rs plan --against-current`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Change {
+    /// Nothing is on the device yet
+    #[serde(rename = "create")]
+    Create,
+
+    /// Device is already as planned, and its data is kept
+    #[serde(rename = "no-op")]
+    NoOp,
+
+    /// Existing data on the device is overwritten
+    #[serde(rename = "destroy")]
+    Destroy,
+}
+
+/// What to look for on current disks to assess a step
+#[derive(Debug, Clone, PartialEq)]
+pub enum Probe {
+    /// Partition table of disk, kept only if all partitions are kept
+    Table {
+        device: String,
+        table: PartitionTable,
+        partitions: Vec<ProbePartition>,
+    },
+
+    /// Partition, kept if it has the planned size and type
+    Partition(ProbePartition),
+
+    /// Device overwritten with a new signature, e.g. by mkfs
+    Signature(String),
+
+    /// Device created by the step, e.g. LUKS mapper
+    Exists(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProbePartition {
+    pub device: String,
+    pub size: Option<String>,
+    pub part_type: String,
+    pub mbr: bool,
+}
+
+impl std::fmt::Display for Step {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[{}] {}", self.stage, self.action)?;
+        match self.change {
+            Some(change) => write!(f, " ({change})"),
+            None => Ok(()),
+        }
+    }
+}
+
+impl std::fmt::Display for Change {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Create => write!(f, "create"),
+            Self::NoOp => write!(f, "no-op"),
+            Self::Destroy => write!(f, "destroy"),
+        }
+    }
+}
+
+/// Ordered steps that `apply_manifest` would perform with `manifest`
+/// at `location`. Steps are described from the same planned actions
+/// the stages perform, without touching the system, so hooks are
+/// listed but not run.
+pub fn steps(manifest: &Manifest, location: &str) -> Vec<Step> {
+    probed_steps(manifest, location)
+        .into_iter()
+        .map(|(step, _)| step)
+        .collect()
+}
+
+/// Like [`steps`], with probes of disk steps for assessing
+/// their changes to current disks
+pub fn probed_steps(
+    manifest: &Manifest,
+    location: &str,
+) -> Vec<(Step, Option<Probe>)> {
+    let mut steps = Vec::new();
+    let mut push = |stage: Stage, actions: Vec<(String, Option<Probe>)>| {
+        let stage = stage.to_string();
+        steps.extend(actions.into_iter().map(|(action, probe)| {
+            let step = Step {
+                stage: stage.clone(),
+                action,
+                change: None,
+            };
+
+            (step, probe)
+        }));
+    };
+
+    let unprobed =
+        |actions: Vec<String>| actions.into_iter().map(|a| (a, None)).collect();
+
+    push(Stage::Mountpoints, mountpoints(manifest, location));
+    push(Stage::Bootstrap, unprobed(bootstrap(manifest, location)));
+    push(Stage::Routines, unprobed(routines(manifest)));
+    push(Stage::ChrootAli, unprobed(chroot_ali(manifest)));
+    push(Stage::ChrootUser, unprobed(chroot_user(manifest)));
+    push(Stage::PostInstallUser, unprobed(postinstall_user(manifest)));
+
+    steps
+}
+
+fn mountpoints(
+    manifest: &Manifest,
+    location: &str,
+) -> Vec<(String, Option<Probe>)> {
+    mountpoints::ops(manifest)
+        .iter()
+        .filter_map(|op| describe_mountpoint(op, location))
+        .collect()
+}
+
+/// Describes `op` with its probe, or None for report markers
+fn describe_mountpoint(
+    op: &Op,
+    location: &str,
+) -> Option<(String, Option<Probe>)> {
+    let signature = |device: &str| Some(Probe::Signature(device.to_string()));
+
+    let described = match op {
+        Op::Wipe(disk, disk_wipe) => {
+            (wipe::describe(&disk.device, *disk_wipe), None)
+        }
+        Op::CreateTable(disk) => {
+            let table = match disk.table {
+                PartitionTable::Gpt => "gpt",
+                PartitionTable::Mbr => "mbr",
+            };
+
+            (
+                format!("create {table} partition table on {}", disk.device),
+                Some(Probe::Table {
+                    device: disk.device.clone(),
+                    table: disk.table.clone(),
+                    partitions: probe_partitions(disk),
+                }),
+            )
+        }
+        Op::CreatePartition(disk, n) => {
+            let partition = probe_partitions(disk).swap_remove(n - 1);
+            let size = partition.size.as_deref().unwrap_or("100%");
+
+            (
+                format!("create partition {n} on {} ({size})", disk.device),
+                Some(Probe::Partition(partition)),
+            )
+        }
+        Op::SetPartitionType(disk, n) => {
+            let part_type = &disk.partitions[n - 1].part_type;
+            let device = &disk.device;
+            (
+                format!("set type of partition {n} on {device} to {part_type}"),
+                None,
+            )
+        }
+        Op::LuksFormat(m_luks) => {
+            let device = &m_luks.device;
+            (format!("luksFormat {device}"), signature(device))
+        }
+        Op::LuksOpen(m_luks) => {
+            (
+                format!("luksOpen {} as {}", m_luks.device, m_luks.name),
+                Some(Probe::Exists(format!("/dev/mapper/{}", m_luks.name))),
+            )
+        }
+        Op::CreatePv(pv) => (format!("pvcreate {pv}"), signature(pv)),
+        Op::CreateVg(vg) => {
+            (
+                format!("vgcreate {} {}", vg.name, vg.pvs.join(" ")),
+                Some(Probe::Exists(format!("/dev/{}", vg.name))),
+            )
+        }
+        Op::CreateLv(lv) => {
+            let size = lv.size.as_deref().unwrap_or("100%FREE");
+            (
+                format!("lvcreate {}/{} ({size})", lv.vg, lv.name),
+                Some(Probe::Exists(format!("/dev/{}/{}", lv.vg, lv.name))),
+            )
+        }
+        Op::CreateFs(fs) => {
+            let mkfs = match &fs.fs_opts {
+                Some(opts) => format!("mkfs.{} {opts}", fs.fs_type),
+                None => format!("mkfs.{}", fs.fs_type),
+            };
+            (format!("{mkfs} {}", fs.device), signature(&fs.device))
+        }
+        Op::MkdirRootFs => (format!("mkdir {location}"), None),
+        Op::MkdirFs(dest) => {
+            (format!("mkdir {}", prepend_base(location, dest)), None)
+        }
+        Op::Mount(mnt) => {
+            let mountpoint = prepend_base(location, &mnt.dest);
+            let mount = match &mnt.mnt_opts {
+                Some(opts) => format!("mount -o {opts}"),
+                None => "mount".to_string(),
+            };
+            (format!("{mount} {} {mountpoint}", mnt.device), None)
+        }
+        Op::Done(_) => return None,
+    };
+
+    Some(described)
+}
+
+fn probe_partitions(disk: &ManifestDisk) -> Vec<ProbePartition> {
+    disk.partitions
+        .iter()
+        .enumerate()
+        .map(|(n, part)| {
+            ProbePartition {
+                device: partition_name(&disk.device, n as u8 + 1),
+                size: part.size.clone(),
+                part_type: part.part_type.clone(),
+                mbr: matches!(disk.table, PartitionTable::Mbr),
+            }
+        })
+        .collect()
+}
+
+fn bootstrap(manifest: &Manifest, location: &str) -> Vec<String> {
+    let mut packages: Vec<String> =
+        bootstrap::packages(&manifest.pacstraps).into_iter().collect();
+    packages.sort();
+
+    vec![format!("pacstrap {location} {}", packages.join(" "))]
+}
+
+fn routines(manifest: &Manifest) -> Vec<String> {
+    routines::planned(manifest)
+        .into_iter()
+        .map(|action| {
+            match action {
+                ActionRoutine::RootPasswd => "set root password".to_string(),
+                ActionRoutine::NetRoot => {
+                    "write network root files".to_string()
+                }
+                ActionRoutine::GenFstab => "genfstab -U".to_string(),
+                ActionRoutine::Crypttab => {
+                    "append /etc/crypttab".to_string()
+                }
+                ActionRoutine::KernelCmdline => {
+                    "write kernel command line".to_string()
+                }
+                ActionRoutine::CaCertificates(names) => {
+                    format!("install CA certificates {}", names.join(" "))
+                }
+                ActionRoutine::Portable => {
+                    "write portable configs".to_string()
+                }
+                ActionRoutine::Maintenance => {
+                    "write maintenance units".to_string()
+                }
+                ActionRoutine::SshClient => {
+                    "write system ssh client configs".to_string()
+                }
+                ActionRoutine::Skel => "write /etc/skel files".to_string(),
+                ActionRoutine::TimeSync(services) => {
+                    format!("write time sync configs ({})", services.join(" "))
+                }
+                ActionRoutine::DomainJoin { domain, .. } => {
+                    format!("defer domain join {domain} to first boot")
+                }
+                ActionRoutine::SetHostname => {
+                    "write /etc/hostname".to_string()
+                }
+                ActionRoutine::LocaleConf => {
+                    "write /etc/locale.conf".to_string()
+                }
+            }
+        })
+        .collect()
+}
+
+fn chroot_ali(manifest: &Manifest) -> Vec<String> {
+    let locale = manifest.locale.as_deref().unwrap_or(defaults::LOCALE);
+
+    archchroot::planned_ali(manifest)
+        .into_iter()
+        .map(|action| {
+            match action {
+                ActionChrootAli::LinkTimezone(tz) => {
+                    format!("link timezone {tz}")
+                }
+                ActionChrootAli::LocaleGen => format!("locale-gen {locale}"),
+                ActionChrootAli::Mkinitcpio => "mkinitcpio -P".to_string(),
+                ActionChrootAli::InstallGrub => {
+                    "install GRUB for BIOS and UEFI".to_string()
+                }
+            }
+        })
+        .collect()
+}
+
+fn chroot_user(manifest: &Manifest) -> Vec<String> {
+    let mut actions: Vec<String> = manifest
+        .chroot
+        .iter()
+        .flatten()
+        .map(|cmd| {
+            match hooks::is_hook(cmd) {
+                true => format!("hook {cmd}"),
+                false => format!("arch-chroot: {cmd}"),
+            }
+        })
+        .collect();
+
+    if manifest.ssh.is_some() {
+        actions.push("write per-user ssh files".to_string());
+    }
+
+    actions
+}
+
+fn postinstall_user(manifest: &Manifest) -> Vec<String> {
+    manifest
+        .postinstall
+        .iter()
+        .flatten()
+        .map(|cmd| {
+            match hooks::is_hook(cmd) {
+                true => format!("hook {cmd}"),
+                false => format!("sh -c: {cmd}"),
+            }
+        })
+        .collect()
+}
+
+#[test]
+fn test_steps() {
+    let manifest = include_str!("../examples/uefi-root-on-lvm-on-luks.yaml");
+    let mut manifest = crate::ali::parse(manifest).unwrap();
+    manifest.pacstraps.get_or_insert_default().insert("lvm2".to_string());
+
+    let steps = steps(&manifest, "/alitarget");
+    let actions: Vec<&str> = steps.iter().map(|s| s.action.as_str()).collect();
+
+    assert_eq!("stage-mountpoints", steps[0].stage);
+    assert_eq!("create mbr partition table on /dev/vda", actions[0]);
+    assert!(actions.contains(&"luksOpen /dev/vda2 as crypty"));
+    assert!(actions.contains(&"lvcreate archvg/swaplv (8G)"));
+    assert!(actions.contains(&"mount /dev/vda1 /alitarget/boot"));
+    assert!(actions.contains(
+        &"mount -o compress:zstd:3 /dev/archvg/rootlv /alitarget/"
+    ));
+    assert!(actions.contains(&"link timezone US/Pacific"));
+
+    // Root LUKS is unlocked by initramfs, not crypttab
+    assert!(!actions.contains(&"append /etc/crypttab"));
+
+    // Wipes are described as performed
+    manifest.disks.as_mut().unwrap()[0].wipe = Some(crate::ali::DiskWipe::Full);
+    let wiped = self::steps(&manifest, "/alitarget");
+    assert!(wiped[0].action.starts_with("wipefs -a /dev/vda and its "));
+    assert!(wiped[0].action.contains("blkdiscard /dev/vda"));
+    assert_eq!(actions[0], wiped[1].action);
+
+    let pacstrap = steps
+        .iter()
+        .find(|s| s.stage == "stage-bootstrap")
+        .unwrap();
+    assert!(pacstrap.action.starts_with("pacstrap /alitarget "));
+    assert!(pacstrap.action.contains(" lvm2"));
+
+    // Stages are in apply order
+    let stages: Vec<&str> = steps.iter().map(|s| s.stage.as_str()).collect();
+    let mut sorted = stages.clone();
+    sorted.sort_by_key(|stage| {
+        crate::types::stage::STAGES
+            .iter()
+            .position(|s| s.to_string() == *stage)
+    });
+    assert_eq!(sorted, stages);
+
+    // Mounts come after mkfs
+    let mkfs = actions.iter().rposition(|a| a.starts_with("mkfs.")).unwrap();
+    let mount = actions.iter().position(|a| a.starts_with("mount ")).unwrap();
+    assert!(mkfs < mount);
+}
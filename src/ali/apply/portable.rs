@@ -0,0 +1,46 @@
+use crate::ali::portable::PortableBoot;
+use crate::errors::AliError;
+use crate::utils::fs::write_under;
+
+/// mkinitcpio hooks without autodetect, so that the initramfs
+/// includes modules for any machine the stick is plugged into
+const HOOKS_PORTABLE: &str =
+    "base udev microcode modconf kms keyboard keymap consolefont block filesystems fsck";
+
+const MKINITCPIO_CONF: &str = "/etc/mkinitcpio.conf.d/10-ali-rs-portable.conf";
+const DEFAULT_GRUB: &str = "/etc/default/grub";
+
+/// Always refer to root by UUID, and never probe the host machine's OSes
+const DEFAULT_GRUB_PORTABLE: &str =
+    "\n# ali-rs portable target\nGRUB_DISABLE_LINUX_UUID=false\nGRUB_DISABLE_OS_PROBER=true\n";
+
+/// Writes initramfs and GRUB configuration to new system at `location`.
+/// The initramfs is regenerated later in chroot.
+pub fn write_files(location: &str) -> Result<(), AliError> {
+    write_under(
+        location,
+        MKINITCPIO_CONF,
+        &format!("HOOKS=({HOOKS_PORTABLE})\n"),
+    )?;
+
+    let default_grub = format!("{location}{DEFAULT_GRUB}");
+    let mut content =
+        std::fs::read_to_string(&default_grub).unwrap_or_default();
+    content.push_str(DEFAULT_GRUB_PORTABLE);
+
+    write_under(location, DEFAULT_GRUB, &content)
+}
+
+/// Commands to install both BIOS and UEFI boot paths, run in chroot.
+/// The UEFI path is installed to the removable media path,
+/// so it boots without NVRAM boot entries on any machine.
+pub fn cmds_grub(boot: &PortableBoot) -> Vec<String> {
+    vec![
+        format!("grub-install --target=i386-pc --recheck {}", boot.disk),
+        format!(
+            "grub-install --target=x86_64-efi --efi-directory={} --boot-directory=/boot --removable --recheck",
+            boot.esp_dest,
+        ),
+        "grub-mkconfig -o /boot/grub/grub.cfg".to_string(),
+    ]
+}
@@ -1,10 +1,32 @@
-use crate::ali::Manifest;
+use crate::ali::{
+    Dm,
+    Manifest,
+    ManifestLuks,
+};
 use crate::constants::defaults;
 use crate::errors::AliError;
+use crate::linux::blkid::DeviceMap;
 use crate::types::action::ActionRoutine;
+use crate::types::cmdline::KernelCmdline;
+use crate::utils::fs::{
+    path_under,
+    write_under,
+};
+use crate::utils::secrets;
 use crate::utils::shell;
 
 use super::map_err::map_err_routine;
+use super::{
+    ca,
+    cmdline,
+    domain_join,
+    maintenance,
+    netroot,
+    portable,
+    skel,
+    ssh,
+    time,
+};
 
 pub fn ali_routines(
     manifest: &Manifest,
@@ -12,31 +34,248 @@ pub fn ali_routines(
 ) -> Result<Vec<ActionRoutine>, AliError> {
     let mut actions = Vec::new();
 
-    let action_rootpasswd = ActionRoutine::RootPasswd;
-    if let Err(err) = root_password(&manifest.rootpasswd, install_location) {
-        return Err(map_err_routine(err, action_rootpasswd, actions));
+    for action in planned(manifest) {
+        if let Err(err) = run(&action, manifest, install_location) {
+            return Err(map_err_routine(err, action, actions));
+        }
+        actions.push(action);
     }
-    actions.push(action_rootpasswd);
 
-    let action_genfstab = ActionRoutine::GenFstab;
-    if let Err(err) = genfstab_uuid(install_location) {
-        return Err(map_err_routine(err, action_genfstab, actions));
+    Ok(actions)
+}
+
+/// Routines to apply with `manifest`, in apply order.
+/// Also used by `ali-rs plan`, so plans follow the same conditions.
+pub(super) fn planned(manifest: &Manifest) -> Vec<ActionRoutine> {
+    let mut actions = vec![ActionRoutine::RootPasswd];
+
+    // Diskless installs have no local mounts to generate fstab from
+    match manifest.netroot {
+        Some(_) => actions.push(ActionRoutine::NetRoot),
+        None => {
+            actions.push(ActionRoutine::GenFstab);
+            if has_crypttab(manifest) {
+                actions.push(ActionRoutine::Crypttab);
+            }
+        }
     }
-    actions.push(action_genfstab);
 
-    let action_set_hostname = ActionRoutine::SetHostname;
-    if let Err(err) = hostname(&manifest.hostname, install_location) {
-        return Err(map_err_routine(err, action_set_hostname, actions));
+    actions.push(ActionRoutine::KernelCmdline);
+
+    // Internal HTTPS endpoints must be trusted before chroot commands
+    if let Some(m_certs) = &manifest.ca_certs {
+        actions.push(ActionRoutine::CaCertificates(
+            m_certs.iter().map(|cert| cert.name.clone()).collect(),
+        ));
     }
-    actions.push(action_set_hostname);
 
-    let action_locale_conf = ActionRoutine::LocaleConf;
-    if let Err(err) = locale_conf(install_location) {
-        return Err(map_err_routine(err, action_locale_conf, actions));
+    if manifest.is_portable() {
+        actions.push(ActionRoutine::Portable);
     }
-    actions.push(action_locale_conf);
 
-    Ok(actions)
+    if manifest.maintenance.is_some() {
+        actions.push(ActionRoutine::Maintenance);
+    }
+
+    if manifest.ssh.is_some() {
+        actions.push(ActionRoutine::SshClient);
+    }
+
+    // Skeleton files must be in place before chroot commands create users
+    if manifest.skel.is_some() {
+        actions.push(ActionRoutine::Skel);
+    }
+
+    if let Some(m_time) = &manifest.time {
+        actions.push(ActionRoutine::TimeSync(m_time.services()));
+    }
+
+    // Domain join itself is deferred to first boot
+    if let Some(m_join) = &manifest.domain_join {
+        actions.push(ActionRoutine::DomainJoin {
+            domain: m_join.domain.clone(),
+            status: domain_join::STATUS.to_string(),
+        });
+    }
+
+    actions.push(ActionRoutine::SetHostname);
+    actions.push(ActionRoutine::LocaleConf);
+
+    actions
+}
+
+/// Applies a routine from [`planned`]
+fn run(
+    action: &ActionRoutine,
+    manifest: &Manifest,
+    install_location: &str,
+) -> Result<(), AliError> {
+    let unplanned = || {
+        AliError::AliRsBug(format!(
+            "routine {action:?} without its manifest key"
+        ))
+    };
+
+    match action {
+        ActionRoutine::RootPasswd => {
+            root_password(&manifest.rootpasswd, install_location)
+        }
+        ActionRoutine::NetRoot => {
+            let m_netroot = manifest.netroot.as_ref().ok_or_else(unplanned)?;
+            let cmdline = kernel_cmdline(manifest)?;
+
+            netroot::write_files(
+                manifest,
+                m_netroot,
+                &cmdline,
+                install_location,
+            )
+        }
+        ActionRoutine::GenFstab => genfstab_uuid(install_location),
+        ActionRoutine::Crypttab => {
+            let devices = DeviceMap::query(config_devices(manifest));
+            let entries = crypttab(manifest, &devices);
+
+            append_file(install_location, "/etc/crypttab", &entries)
+        }
+        ActionRoutine::KernelCmdline => {
+            cmdline::write(install_location, &kernel_cmdline(manifest)?)
+        }
+        ActionRoutine::CaCertificates(_) => {
+            let m_certs = manifest.ca_certs.as_ref().ok_or_else(unplanned)?;
+            ca::install(m_certs, install_location)
+        }
+        ActionRoutine::Portable => portable::write_files(install_location),
+        ActionRoutine::Maintenance => {
+            let m_maintenance =
+                manifest.maintenance.as_ref().ok_or_else(unplanned)?;
+            maintenance::write_files(manifest, m_maintenance, install_location)
+        }
+        ActionRoutine::SshClient => {
+            let m_ssh = manifest.ssh.as_ref().ok_or_else(unplanned)?;
+            ssh::write_system(m_ssh, install_location)
+        }
+        ActionRoutine::Skel => {
+            let m_skel = manifest.skel.as_ref().ok_or_else(unplanned)?;
+            skel::write_files(m_skel, install_location)
+        }
+        ActionRoutine::TimeSync(_) => {
+            let m_time = manifest.time.as_ref().ok_or_else(unplanned)?;
+            time::write_files(m_time, install_location).map(|_| ())
+        }
+        ActionRoutine::DomainJoin { .. } => {
+            let m_join = manifest.domain_join.as_ref().ok_or_else(unplanned)?;
+            domain_join::write_files(manifest, m_join, install_location)
+        }
+        ActionRoutine::SetHostname => {
+            hostname(&manifest.hostname, install_location)
+        }
+        ActionRoutine::LocaleConf => {
+            locale_conf(&manifest.locale, install_location)
+        }
+    }
+}
+
+/// Kernel command line, with stable device identifiers unless
+/// the install is diskless
+fn kernel_cmdline(manifest: &Manifest) -> Result<KernelCmdline, AliError> {
+    match manifest.netroot {
+        Some(_) => cmdline::build(manifest, &DeviceMap::default()),
+        None => {
+            // Identifiers of formatted devices, for generated configs
+            let devices = DeviceMap::query(config_devices(manifest));
+            cmdline::build(manifest, &devices)
+        }
+    }
+}
+
+/// Devices referred to by generated configs
+fn config_devices(manifest: &Manifest) -> Vec<&str> {
+    let mut devices = vec![manifest.rootfs.device.as_str()];
+    for dm in manifest.device_mappers.iter().flatten() {
+        if let Dm::Luks(m_luks) = dm {
+            devices.push(m_luks.device.as_str());
+        }
+    }
+
+    devices
+}
+
+/// LUKS device underlying rootfs, directly or as LVM PV,
+/// which is unlocked by initramfs instead of crypttab
+pub(super) fn root_luks(manifest: &Manifest) -> Option<&ManifestLuks> {
+    let root = &manifest.rootfs.device;
+    let dms = manifest.device_mappers.as_ref()?;
+
+    let root_pvs: Vec<&String> = dms
+        .iter()
+        .filter_map(|dm| {
+            match dm {
+                Dm::Lvm(m_lvm) => m_lvm.vgs.as_ref(),
+                _ => None,
+            }
+        })
+        .flatten()
+        .filter(|vg| {
+            root.starts_with(&format!("/dev/{}/", vg.name))
+                || root.starts_with(&format!("/dev/mapper/{}-", vg.name))
+        })
+        .flat_map(|vg| vg.pvs.iter())
+        .collect();
+
+    dms.iter().find_map(|dm| {
+        match dm {
+            Dm::Luks(m_luks) => {
+                let mapper = format!("/dev/mapper/{}", m_luks.name);
+                (*root == mapper || root_pvs.contains(&&mapper))
+                    .then_some(m_luks)
+            }
+            _ => None,
+        }
+    })
+}
+
+/// Whether any LUKS device is left to crypttab, i.e. is not root LUKS
+fn has_crypttab(manifest: &Manifest) -> bool {
+    let root = root_luks(manifest);
+
+    manifest.device_mappers.iter().flatten().any(|dm| {
+        matches!(dm, Dm::Luks(m_luks) if Some(m_luks) != root)
+    })
+}
+
+/// crypttab entries for non-root LUKS devices, with passphrase prompted at boot
+fn crypttab(manifest: &Manifest, devices: &DeviceMap) -> String {
+    let root = root_luks(manifest);
+
+    manifest
+        .device_mappers
+        .iter()
+        .flatten()
+        .filter_map(|dm| {
+            match dm {
+                Dm::Luks(m_luks) if Some(m_luks) != root => {
+                    Some(format!(
+                        "{} {} none luks\n",
+                        m_luks.name,
+                        devices.stable_ref(&m_luks.device)
+                    ))
+                }
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+fn append_file(
+    install_location: &str,
+    path: &str,
+    content: &str,
+) -> Result<(), AliError> {
+    let existing = std::fs::read_to_string(format!("{install_location}{path}"))
+        .unwrap_or_default();
+
+    write_under(install_location, path, &(existing + content))
 }
 
 fn genfstab_uuid(install_location: &str) -> Result<(), AliError> {
@@ -49,7 +288,7 @@ fn hostname(
 ) -> Result<(), AliError> {
     let hostname = hostname.clone().unwrap_or(defaults::HOSTNAME.to_string());
 
-    let etc_hostname = format!("{install_location}/etc/hostname");
+    let etc_hostname = path_under(install_location, "/etc/hostname")?;
 
     std::fs::write(&etc_hostname, hostname).map_err(|err| {
         AliError::FileError(
@@ -59,10 +298,14 @@ fn hostname(
     })
 }
 
-fn locale_conf(install_location: &str) -> Result<(), AliError> {
-    let dst = format!("{install_location}/etc/locale.conf");
+fn locale_conf(
+    locale: &Option<String>,
+    install_location: &str,
+) -> Result<(), AliError> {
+    let dst = path_under(install_location, "/etc/locale.conf")?;
+    let locale = locale.as_deref().unwrap_or(defaults::LOCALE);
 
-    std::fs::write(&dst, defaults::LOCALE_CONF).map_err(|err| {
+    std::fs::write(&dst, format!("LANG={locale}\n")).map_err(|err| {
         AliError::FileError(
             err,
             format!("failed to create new locale.conf {dst}"),
@@ -74,9 +317,10 @@ fn root_password(
     hashed_root_passwd: &Option<String>,
     install_location: &str,
 ) -> Result<(), AliError> {
-    let password = hashed_root_passwd
-        .clone()
-        .unwrap_or(defaults::hashed_password());
+    let password = match hashed_root_passwd {
+        Some(hashed) => secrets::resolve(hashed)?,
+        None => defaults::hashed_password(),
+    };
 
     let cmd = format!("echo 'username:{password}' | chpasswd -e");
 
@@ -87,3 +331,63 @@ fn root_password(
 fn cmd_genfstab_uuid(install_location: &str) -> String {
     format!("genfstab -U {install_location} >> {install_location}/etc/fstab")
 }
+
+#[test]
+fn test_crypttab_cmdline() {
+    use crate::linux::blkid::DeviceIds;
+
+    let yaml = r#"
+version: 1
+rootfs:
+  device: /dev/archvg/rootlv
+  fstype: ext4
+dm:
+  - type: luks
+    device: /dev/vda2
+    name: cryptroot
+  - type: luks
+    device: /dev/vdb1
+    name: cryptdata
+  - type: lvm
+    pvs:
+      - /dev/mapper/cryptroot
+    vgs:
+      - name: archvg
+        pvs:
+          - /dev/mapper/cryptroot
+    lvs:
+      - name: rootlv
+        vg: archvg
+"#;
+
+    let manifest = crate::ali::parse(yaml).unwrap();
+    let mut devices = DeviceMap::default();
+    for (device, uuid) in [
+        ("/dev/archvg/rootlv", "root-uuid"),
+        ("/dev/vda2", "luks-root-uuid"),
+        ("/dev/vdb1", "luks-data-uuid"),
+    ] {
+        devices.insert(
+            device,
+            DeviceIds {
+                uuid: Some(uuid.to_string()),
+                partuuid: None,
+            },
+        );
+    }
+
+    assert_eq!(
+        Some("cryptroot"),
+        root_luks(&manifest).map(|l| l.name.as_str())
+    );
+    assert_eq!(
+        "cryptdata UUID=luks-data-uuid none luks\n",
+        crypttab(&manifest, &devices)
+    );
+    assert_eq!(
+        "cryptdevice=UUID=luks-root-uuid:cryptroot root=UUID=root-uuid rw",
+        super::cmdline::build(&manifest, &devices)
+            .unwrap()
+            .to_string()
+    );
+}
@@ -0,0 +1,96 @@
+use std::fs::Permissions;
+use std::os::unix::fs::PermissionsExt;
+
+use crate::ali::ManifestSkelFile;
+use crate::errors::AliError;
+use crate::utils::fs::{
+    mkdir_p,
+    path_under,
+    write_under,
+};
+
+const DIR_SKEL: &str = "/etc/skel";
+
+/// Writes files and directories of manifest key `skel`
+/// to `/etc/skel` of new system at `location`
+pub fn write_files(
+    m_skel: &[ManifestSkelFile],
+    location: &str,
+) -> Result<(), AliError> {
+    for file in m_skel {
+        let path = format!("{DIR_SKEL}/{}", file.path.trim_end_matches('/'));
+        let dst = path_under(location, &path)?;
+
+        match file.is_dir() {
+            true => mkdir_p(&dst)?,
+            false => write_under(location, &path, &content(file)?)?,
+        }
+
+        std::fs::set_permissions(&dst, Permissions::from_mode(file.mode()?))
+            .map_err(|err| {
+                AliError::FileError(err, format!("failed to chmod {dst}"))
+            })?;
+    }
+
+    Ok(())
+}
+
+/// Content of skeleton file, read from template `source` if given
+fn content(file: &ManifestSkelFile) -> Result<String, AliError> {
+    let Some(ref source) = file.source else {
+        return Ok(file.content.clone().unwrap_or_default());
+    };
+
+    let mut template = std::fs::read_to_string(source).map_err(|err| {
+        AliError::FileError(err, format!("failed to read template {source}"))
+    })?;
+
+    for (name, value) in file.vars.iter().flatten() {
+        template = template.replace(&format!("{{{{ {name} }}}}"), value);
+    }
+
+    Ok(template)
+}
+
+#[test]
+fn test_write_skel() {
+    let dir = std::env::temp_dir()
+        .join(format!("ali-rs-test-skel-{}", std::process::id()));
+    let location = dir.to_str().unwrap();
+    let template = dir.join("gitconfig.tpl");
+
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(&template, "[user]\n\tname = {{ name }}\n").unwrap();
+
+    let yaml = format!(
+        r#"
+- path: .bashrc
+  content: alias ll='ls -l'
+- path: .gitconfig
+  source: {}
+  vars:
+    name: Foo
+  mode: "0600"
+- path: .local/bin/
+"#,
+        template.display()
+    );
+
+    let m_skel: Vec<ManifestSkelFile> = serde_yaml::from_str(&yaml).unwrap();
+    write_files(&m_skel, location).unwrap();
+
+    let skel = dir.join("etc/skel");
+    let read = |path: &str| std::fs::read_to_string(skel.join(path)).unwrap();
+    let mode = |path: &str| {
+        let metadata = std::fs::metadata(skel.join(path)).unwrap();
+        metadata.permissions().mode() & 0o7777
+    };
+
+    assert_eq!("alias ll='ls -l'", read(".bashrc"));
+    assert_eq!("[user]\n\tname = Foo\n", read(".gitconfig"));
+    assert_eq!(0o644, mode(".bashrc"));
+    assert_eq!(0o600, mode(".gitconfig"));
+    assert_eq!(0o755, mode(".local/bin"));
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
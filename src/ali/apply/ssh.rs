@@ -0,0 +1,283 @@
+use std::io::Read;
+use std::os::unix::fs::{
+    chown,
+    PermissionsExt,
+};
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use hmac::{
+    Hmac,
+    Mac,
+};
+use sha1::Sha1;
+
+use crate::ali::{
+    ManifestKnownHost,
+    ManifestSsh,
+    ManifestSshHost,
+};
+use crate::errors::AliError;
+use crate::utils::fs::{
+    path_under,
+    write_under,
+};
+
+const SSH_CONFIG: &str = "/etc/ssh/ssh_config.d/50-ali-rs.conf";
+const SSH_KNOWN_HOSTS: &str = "/etc/ssh/ssh_known_hosts";
+const PASSWD: &str = "/etc/passwd";
+
+/// Salt length of hashed known hosts, same as OpenSSH
+const SALT_LEN: usize = 20;
+
+/// Writes system-wide ssh_config drop-in and known hosts
+/// to new system at `location`
+pub fn write_system(
+    m_ssh: &ManifestSsh,
+    location: &str,
+) -> Result<(), AliError> {
+    if let Some(hosts) = &m_ssh.config {
+        write_under(location, SSH_CONFIG, &ssh_config(hosts))?;
+    }
+
+    if let Some(entries) = &m_ssh.known_hosts {
+        let hash = m_ssh.hash_known_hosts.unwrap_or(false);
+        append(location, SSH_KNOWN_HOSTS, &known_hosts(entries, hash)?)?;
+    }
+
+    Ok(())
+}
+
+/// Writes per-user ssh config and known hosts under home directories
+/// of users in new system at `location`, returning the users.
+/// Users are looked up in new system's /etc/passwd, and must already exist.
+pub fn write_users(
+    m_ssh: &ManifestSsh,
+    location: &str,
+) -> Result<Vec<String>, AliError> {
+    let Some(users) = &m_ssh.users else {
+        return Ok(Vec::new());
+    };
+
+    let passwd_path = format!("{location}{PASSWD}");
+    let passwd = std::fs::read_to_string(&passwd_path)
+        .map_err(|err| AliError::FileError(err, passwd_path))?;
+
+    let hash = m_ssh.hash_known_hosts.unwrap_or(false);
+    let mut provisioned = Vec::new();
+
+    for (user, m_user) in users {
+        let Some((uid, gid, home)) = passwd_entry(&passwd, user) else {
+            return Err(AliError::BadManifest(format!(
+                "ssh: no such user {user} in new system"
+            )));
+        };
+
+        let dir = format!("{home}/.ssh");
+        let mut files = Vec::new();
+
+        if let Some(hosts) = &m_user.config {
+            let path = format!("{dir}/config");
+            append(location, &path, &ssh_config(hosts))?;
+            files.push((path, 0o600));
+        }
+
+        if let Some(entries) = &m_user.known_hosts {
+            let path = format!("{dir}/known_hosts");
+            append(location, &path, &known_hosts(entries, hash)?)?;
+            files.push((path, 0o644));
+        }
+
+        files.push((dir, 0o700));
+        for (path, mode) in files {
+            let path = path_under(location, &path)?;
+            let perm = std::fs::Permissions::from_mode(mode);
+            std::fs::set_permissions(&path, perm)
+                .and_then(|_| chown(&path, Some(uid), Some(gid)))
+                .map_err(|err| {
+                    AliError::FileError(err, format!("chown/chmod {path}"))
+                })?;
+        }
+
+        provisioned.push(user.clone());
+    }
+
+    Ok(provisioned)
+}
+
+/// Returns uid, gid, and home directory of `user` from passwd(5) content
+fn passwd_entry(passwd: &str, user: &str) -> Option<(u32, u32, String)> {
+    passwd.lines().find_map(|line| {
+        let fields: Vec<&str> = line.split(':').collect();
+        match fields.as_slice() {
+            [name, _, uid, gid, _, home, ..] if *name == user => {
+                Some((uid.parse().ok()?, gid.parse().ok()?, home.to_string()))
+            }
+            _ => None,
+        }
+    })
+}
+
+fn ssh_config(hosts: &[ManifestSshHost]) -> String {
+    let mut config = String::from("# Generated by ali-rs\n");
+    for host in hosts {
+        config.push_str(&format!("\nHost {}\n", host.host));
+        for (keyword, value) in &host.options {
+            config.push_str(&format!("    {keyword} {value}\n"));
+        }
+    }
+
+    config
+}
+
+fn known_hosts(
+    entries: &[ManifestKnownHost],
+    hash: bool,
+) -> Result<String, AliError> {
+    let mut lines = String::new();
+    for entry in entries {
+        let marker = match entry.cert_authority.unwrap_or(false) {
+            true => "@cert-authority ",
+            false => "",
+        };
+
+        if !hash {
+            lines.push_str(&format!("{marker}{} {}\n", entry.host, entry.key));
+            continue;
+        }
+
+        // Hashed entries hold 1 host each, and patterns cannot be hashed
+        for host in entry.host.split(',') {
+            let host = match host.contains(['*', '?', '!']) {
+                true => host.to_string(),
+                false => hash_host(host, &random_salt()?),
+            };
+
+            lines.push_str(&format!("{marker}{host} {}\n", entry.key));
+        }
+    }
+
+    Ok(lines)
+}
+
+/// Hashes host name like `ssh-keygen -H`, i.e. `|1|<SALT>|<HMAC-SHA1>`
+fn hash_host(host: &str, salt: &[u8]) -> String {
+    let mut mac =
+        Hmac::<Sha1>::new_from_slice(salt).expect("HMAC accepts any key size");
+    mac.update(host.as_bytes());
+
+    format!(
+        "|1|{}|{}",
+        BASE64.encode(salt),
+        BASE64.encode(mac.finalize().into_bytes()),
+    )
+}
+
+fn random_salt() -> Result<[u8; SALT_LEN], AliError> {
+    let mut salt = [0u8; SALT_LEN];
+    std::fs::File::open("/dev/urandom")
+        .and_then(|mut f| f.read_exact(&mut salt))
+        .map_err(|err| AliError::FileError(err, "/dev/urandom".to_string()))?;
+
+    Ok(salt)
+}
+
+fn append(location: &str, path: &str, content: &str) -> Result<(), AliError> {
+    let existing = std::fs::read_to_string(format!("{location}{path}"))
+        .unwrap_or_default();
+
+    write_under(location, path, &(existing + content))
+}
+
+#[test]
+fn test_ssh() {
+    let m_ssh: ManifestSsh = serde_yaml::from_str(
+        r#"
+config:
+  - host: "*.internal"
+    options:
+      User: deploy
+      ProxyJump: bastion.internal
+known_hosts:
+  - host: git.internal,10.0.0.5
+    key: ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIFoo
+  - host: "*.internal"
+    key: ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIBar
+    cert_authority: true
+hash: true
+users:
+  alice:
+    known-hosts:
+      - host: git.internal
+        key: ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIFoo
+"#,
+    )
+    .unwrap();
+
+    assert_eq!(
+        "# Generated by ali-rs\n\nHost *.internal\n    ProxyJump bastion.internal\n    User deploy\n",
+        ssh_config(m_ssh.config.as_ref().unwrap()),
+    );
+
+    let entries = m_ssh.known_hosts.as_ref().unwrap();
+    assert_eq!(
+        "git.internal,10.0.0.5 ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIFoo\n@cert-authority *.internal ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIBar\n",
+        known_hosts(entries, false).unwrap(),
+    );
+
+    let hashed = known_hosts(entries, true).unwrap();
+    let lines: Vec<&str> = hashed.lines().collect();
+    assert_eq!(3, lines.len());
+    assert!(lines[0].starts_with("|1|"));
+    assert!(lines[1].starts_with("|1|"));
+    assert_ne!(lines[0], lines[1]);
+    assert!(lines[2].starts_with("@cert-authority *.internal "));
+
+    // HMAC-SHA1 of host name keyed with salt of 20 zero bytes
+    assert_eq!(
+        "|1|AAAAAAAAAAAAAAAAAAAAAAAAAAA=|gpC7xTg1CeKU4E4Di5wukZ5gCn8=",
+        hash_host("git.internal", &[0u8; SALT_LEN]),
+    );
+
+    let passwd = "root:x:0:0::/root:/bin/bash\nalice:x:1000:1000::/home/alice:/bin/bash\n";
+    assert_eq!(
+        Some((1000, 1000, "/home/alice".to_string())),
+        passwd_entry(passwd, "alice"),
+    );
+    assert_eq!(None, passwd_entry(passwd, "bob"));
+
+    let dir = std::env::temp_dir()
+        .join(format!("ali-rs-test-ssh-{}", std::process::id()));
+    let location = dir.to_string_lossy().to_string();
+    let uid = nix::unistd::Uid::current().as_raw();
+    let gid = nix::unistd::Gid::current().as_raw();
+
+    write_under(
+        &location,
+        PASSWD,
+        &format!("alice:x:{uid}:{gid}::/home/alice:/bin/bash\n"),
+    )
+    .unwrap();
+
+    write_system(&m_ssh, &location).unwrap();
+    assert_eq!(vec!["alice"], write_users(&m_ssh, &location).unwrap());
+
+    assert!(dir.join("etc/ssh/ssh_config.d/50-ali-rs.conf").exists());
+    assert_eq!(
+        3,
+        std::fs::read_to_string(dir.join("etc/ssh/ssh_known_hosts"))
+            .unwrap()
+            .lines()
+            .count(),
+    );
+
+    let ssh_dir = dir.join("home/alice/.ssh");
+    let mode = |p: &std::path::Path| {
+        std::fs::metadata(p).unwrap().permissions().mode() & 0o777
+    };
+    assert_eq!(0o700, mode(&ssh_dir));
+    assert_eq!(0o644, mode(&ssh_dir.join("known_hosts")));
+    assert!(!ssh_dir.join("config").exists());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -1,23 +1,17 @@
-use std::collections::HashSet;
-
+use super::map_err::map_err_mountpoints;
 use super::{
     archchroot,
     bootstrap,
-    disks,
-    dm,
-    fs,
+    mountpoints,
     routines,
+    ssh,
 };
-use crate::ali::{
-    Manifest,
-    ManifestFs,
-    ManifestMountpoint,
-};
+use crate::ali::Manifest;
 use crate::errors::AliError;
 use crate::hooks;
 use crate::types::action::{
     ActionBootstrap,
-    ActionMountpoints,
+    ActionChrootUser,
     ActionPostInstallUser,
 };
 use crate::types::stage::StageActions;
@@ -29,58 +23,14 @@ pub fn mountpoints(
     root_location: &str,
     stages: &mut StageActions,
 ) -> Result<(), AliError> {
-    // Format and partition disks
-    if let Some(ref m_disks) = manifest.disks {
-        let actions_disks = disks::apply_disks(m_disks)?;
-        stages.mountpoints.extend(actions_disks);
-    }
-
-    // Format and create device mappers
-    if let Some(ref m_dms) = manifest.device_mappers {
-        let actions_dms = dm::apply_dms(m_dms)?;
-        stages.mountpoints.extend(actions_dms);
-    }
-
-    // Create rootfs
-    let rootfs: ManifestFs = manifest.rootfs.clone().into();
-    let action_create_rootfs = fs::create_filesystem(&rootfs)?;
-    stages.mountpoints.push(action_create_rootfs);
-
-    // Create other filesystems
-    if let Some(filesystems) = &manifest.filesystems {
-        let actions_create_filesystems = fs::create_filesystems(filesystems)?;
-        stages.mountpoints.extend(actions_create_filesystems);
-    }
-
-    // mkdir rootfs chroot mount
-    shell::exec("mkdir", &["-p", root_location])?;
-    stages.mountpoints.push(ActionMountpoints::MkdirRootFs);
-
-    // Mount rootfs
-    let mnt_root: ManifestMountpoint = manifest.rootfs.clone().into();
-    let action_mnt_rootfs = fs::mount_filesystem(&mnt_root, root_location)?;
-    stages.mountpoints.push(action_mnt_rootfs);
-
-    // Mount other filesystems to /{DEFAULT_CHROOT_LOC}
-    if let Some(mounts) = &manifest.mountpoints {
-        // Collect filesystems mountpoints and actions.
-        // The mountpoints will be prepended with default base
-        let mountpoints: Vec<(String, ActionMountpoints)> = mounts
-            .iter()
-            .map(|m| {
-                (m.dest.clone(), ActionMountpoints::MkdirFs(m.dest.clone()))
-            })
-            .collect();
-
-        // mkdir -p /{DEFAULT_CHROOT_LOC}/{mkdir_path}
-        for (dir, action_mkdir) in mountpoints {
-            shell::exec("mkdir", &[&dir])?;
-            stages.mountpoints.push(action_mkdir);
+    for op in mountpoints::ops(manifest) {
+        let action = op.action();
+        if let Err(err) = op.run(root_location) {
+            let performed = stages.mountpoints.clone();
+            return Err(map_err_mountpoints(err, action, performed));
         }
 
-        // Mount other filesystems under /{DEFAULT_CHROOT_LOC}
-        let actions_mnt = fs::mount_filesystems(mounts, root_location)?;
-        stages.mountpoints.extend(actions_mnt);
+        stages.mountpoints.push(action);
     }
 
     Ok(())
@@ -92,15 +42,15 @@ pub fn bootstrap(
     install_location: &str,
     stages: &mut StageActions,
 ) -> Result<(), AliError> {
-    // Collect packages, with base as bare-minimum
-    let mut packages = HashSet::from(["base".to_string()]);
-    if let Some(pacstraps) = manifest.pacstraps.clone() {
-        packages.extend(pacstraps);
-    }
+    let packages = bootstrap::packages(&manifest.pacstraps);
 
     // Install packages (manifest.pacstraps) to install_location
     let action_pacstrap = ActionBootstrap::InstallPackages { packages };
-    bootstrap::pacstrap_to_location(&manifest.pacstraps, install_location)?;
+    bootstrap::pacstrap_to_location(
+        &manifest.pacstraps,
+        install_location,
+        manifest.netroot.is_some(),
+    )?;
     stages.bootstrap.push(action_pacstrap);
 
     Ok(())
@@ -137,15 +87,20 @@ pub fn chroot_user(
     install_location: &str,
     stages: &mut StageActions,
 ) -> Result<(), AliError> {
-    if manifest.chroot.is_none() {
-        return Ok(());
-    }
+    if let Some(commands) = &manifest.chroot {
+        let actions_user_cmds =
+            archchroot::chroot_user(commands.iter(), install_location)?;
 
-    let commands = manifest.chroot.as_ref().unwrap();
-    let actions_user_cmds =
-        archchroot::chroot_user(commands.iter(), install_location)?;
+        stages.chroot_user.extend(actions_user_cmds);
+    }
 
-    stages.chroot_user.extend(actions_user_cmds);
+    // Per-user ssh files, after users are created by chroot commands
+    if let Some(m_ssh) = &manifest.ssh {
+        let users = ssh::write_users(m_ssh, install_location)?;
+        stages
+            .chroot_user
+            .extend(users.into_iter().map(ActionChrootUser::SshClientUser));
+    }
 
     Ok(())
 }
@@ -0,0 +1,222 @@
+use crate::ali::{
+    ManifestChrony,
+    ManifestPtp,
+    ManifestTime,
+};
+use crate::errors::AliError;
+use crate::linux::systemd;
+use crate::utils::fs::write_under;
+
+const CHRONY_CONF: &str = "/etc/chrony.conf";
+const PTP4L_CONF: &str = "/etc/linuxptp/ptp4l.conf";
+const MODULES_LOAD_PTP: &str = "/etc/modules-load.d/ptp.conf";
+
+/// Replaced by chrony or phc2sys, which must be the only ones
+/// steering the system clock
+const UNIT_TIMESYNCD: &str = "systemd-timesyncd.service";
+
+/// ptp4l(8) on interface `%i`
+const UNIT_PTP4L: &str = "[Unit]
+Description=Precision Time Protocol (PTP) service on %I
+Documentation=man:ptp4l
+BindsTo=sys-subsystem-net-devices-%i.device
+After=sys-subsystem-net-devices-%i.device
+
+[Service]
+Type=simple
+ExecStart=/usr/bin/ptp4l -f /etc/linuxptp/ptp4l.conf -i %I
+Restart=on-failure
+
+[Install]
+WantedBy=multi-user.target
+";
+
+/// phc2sys(8) reading PTP hardware clock of interface `%i`,
+/// once ptp4l synchronized it
+const UNIT_PHC2SYS_TPL: &str = "[Unit]
+Description=Synchronize system clock to PTP hardware clock of %I
+Documentation=man:phc2sys
+Requires=ptp4l@%i.service
+After=ptp4l@%i.service
+
+[Service]
+Type=simple
+ExecStart=/usr/bin/phc2sys -s %I -w {{ sink }}
+Restart=on-failure
+
+[Install]
+WantedBy=multi-user.target
+";
+
+/// Shared memory segment of phc2sys for chrony refclock
+const SHM_SEGMENT: u8 = 0;
+
+/// Writes chrony and linuxptp configs and units to new system
+/// at `location`, returning the units enabled
+pub fn write_files(
+    m_time: &ManifestTime,
+    location: &str,
+) -> Result<Vec<String>, AliError> {
+    let phc_refclock = m_time
+        .ptp
+        .as_ref()
+        .is_some_and(|m_ptp| m_time.chrony.is_some() && m_ptp.is_hardware());
+
+    if let Some(ref m_chrony) = m_time.chrony {
+        let conf = chrony_conf(m_chrony, phc_refclock);
+        write_under(location, CHRONY_CONF, &conf)?;
+    }
+
+    if let Some(ref m_ptp) = m_time.ptp {
+        write_under(location, PTP4L_CONF, &ptp4l_conf(m_ptp))?;
+        write_under(location, MODULES_LOAD_PTP, &modules_load(m_ptp))?;
+        write_under(
+            location,
+            "/etc/systemd/system/ptp4l@.service",
+            UNIT_PTP4L,
+        )?;
+
+        if m_ptp.is_hardware() {
+            let sink = match phc_refclock {
+                true => format!("-E ntpshm -M {SHM_SEGMENT}"),
+                false => "-c CLOCK_REALTIME".to_string(),
+            };
+
+            write_under(
+                location,
+                "/etc/systemd/system/phc2sys@.service",
+                &UNIT_PHC2SYS_TPL.replace("{{ sink }}", &sink),
+            )?;
+        }
+    }
+
+    systemd::mask(location, UNIT_TIMESYNCD)?;
+
+    let services = m_time.services();
+    for unit in &services {
+        systemd::enable_service(location, unit, "multi-user.target")?;
+    }
+
+    Ok(services)
+}
+
+fn chrony_conf(m_chrony: &ManifestChrony, phc_refclock: bool) -> String {
+    let nts = m_chrony.nts.unwrap_or(true);
+
+    let mut conf = String::from("# Generated by ali-rs\n");
+    for server in &m_chrony.servers {
+        conf.push_str(&format!("server {server} iburst"));
+        if nts {
+            conf.push_str(" nts");
+        }
+        conf.push('\n');
+    }
+
+    if phc_refclock {
+        conf.push_str(&format!(
+            "refclock SHM {SHM_SEGMENT} refid PTP poll 0 precision 1e-7 prefer\n"
+        ));
+    }
+
+    conf.push_str(
+        "
+driftfile /var/lib/chrony/drift
+ntsdumpdir /var/lib/chrony
+makestep 1.0 3
+rtcsync
+leapsectz right/UTC
+",
+    );
+
+    conf
+}
+
+fn ptp4l_conf(m_ptp: &ManifestPtp) -> String {
+    format!(
+        "# Generated by ali-rs
+[global]
+domainNumber {}
+network_transport {}
+time_stamping {}
+",
+        m_ptp.domain.unwrap_or(0),
+        m_ptp.transport.as_deref().unwrap_or("UDPv4"),
+        m_ptp.time_stamping.as_deref().unwrap_or("hardware"),
+    )
+}
+
+fn modules_load(m_ptp: &ManifestPtp) -> String {
+    std::iter::once("ptp")
+        .chain(m_ptp.modules.iter().flatten().map(|m| m.as_str()))
+        .map(|m| format!("{m}\n"))
+        .collect()
+}
+
+#[test]
+fn test_write_time_files() {
+    let location = std::env::temp_dir().join("ali-rs-test-time");
+    let location = location.to_str().unwrap();
+    let _ = std::fs::remove_dir_all(location);
+
+    let m_time = ManifestTime {
+        chrony: Some(ManifestChrony {
+            servers: vec!["time.cloudflare.com".to_string()],
+            nts: None,
+        }),
+        ptp: Some(ManifestPtp {
+            interface: "eth0".to_string(),
+            domain: Some(24),
+            transport: Some("L2".to_string()),
+            time_stamping: None,
+            modules: Some(vec!["igb".to_string()]),
+        }),
+    };
+
+    let services = write_files(&m_time, location).unwrap();
+    assert_eq!(services, vec![
+        "chronyd.service",
+        "chrony-wait.service",
+        "ptp4l@eth0.service",
+        "phc2sys@eth0.service",
+    ]);
+
+    let read = |path: &str| {
+        std::fs::read_to_string(format!("{location}{path}")).unwrap()
+    };
+
+    let chrony = read(CHRONY_CONF);
+    assert!(chrony.contains("server time.cloudflare.com iburst nts\n"));
+    assert!(chrony.contains("refclock SHM 0 refid PTP"));
+
+    let ptp4l = read(PTP4L_CONF);
+    assert!(ptp4l.contains("domainNumber 24\n"));
+    assert!(ptp4l.contains("network_transport L2\n"));
+
+    assert_eq!(read(MODULES_LOAD_PTP), "ptp\nigb\n");
+    assert!(read("/etc/systemd/system/phc2sys@.service")
+        .contains("ExecStart=/usr/bin/phc2sys -s %I -w -E ntpshm -M 0\n"));
+
+    let timesyncd = format!("{location}/etc/systemd/system/{UNIT_TIMESYNCD}");
+    assert_eq!(
+        std::fs::read_link(timesyncd).unwrap().to_str(),
+        Some("/dev/null")
+    );
+
+    for unit in &services {
+        assert!(systemd::is_enabled(location, unit, Some("multi-user.target")));
+    }
+
+    // Without chrony, phc2sys steers the system clock
+    let m_time = ManifestTime {
+        chrony: None,
+        ..m_time
+    };
+    std::fs::remove_dir_all(location).unwrap();
+    write_files(&m_time, location).unwrap();
+    assert!(read("/etc/systemd/system/phc2sys@.service")
+        .contains("-w -c CLOCK_REALTIME\n"));
+    let chrony = format!("{location}{CHRONY_CONF}");
+    assert!(!std::path::Path::new(&chrony).exists());
+
+    std::fs::remove_dir_all(location).unwrap();
+}
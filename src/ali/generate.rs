@@ -0,0 +1,302 @@
+use std::collections::{
+    BTreeSet,
+    HashSet,
+};
+
+use crate::ali::{
+    migrate,
+    Manifest,
+    ManifestFs,
+    ManifestMountpoint,
+    ManifestRootFs,
+};
+use crate::errors::AliError;
+use crate::utils::shell;
+
+/// Filesystem types of fstab entries which are not backed by block devices
+const FS_PSEUDO: [&str; 8] = [
+    "tmpfs", "proc", "sysfs", "devpts", "efivarfs", "nfs", "nfs4", "cifs",
+];
+
+/// Prefixes of fstab device specs, with their `/dev/disk` directories
+const FSTAB_TAGS: [(&str, &str); 4] = [
+    ("UUID=", "by-uuid"),
+    ("LABEL=", "by-label"),
+    ("PARTUUID=", "by-partuuid"),
+    ("PARTLABEL=", "by-partlabel"),
+];
+
+/// Facts about an installed system, from which manifest is generated
+#[derive(Debug, Default, PartialEq)]
+pub struct System {
+    pub hostname: Option<String>,
+    pub timezone: Option<String>,
+    pub locale: Option<String>,
+    pub fstab: Vec<FstabEntry>,
+    /// Explicitly installed packages
+    pub packages: Vec<String>,
+    /// Units enabled in `/etc/systemd/system/*.wants`
+    pub services: BTreeSet<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct FstabEntry {
+    pub device: String,
+    pub dest: String,
+    pub fs_type: String,
+    pub opts: String,
+}
+
+/// Inspects system installed at `root` (`/` for running system)
+pub fn inspect(root: &str) -> Result<System, AliError> {
+    let root = root.trim_end_matches('/');
+    let read = |path: &str| std::fs::read_to_string(format!("{root}{path}"));
+
+    let fstab = read("/etc/fstab").map_err(|err| {
+        AliError::FileError(err, format!("failed to read {root}/etc/fstab"))
+    })?;
+
+    let timezone = std::fs::read_link(format!("{root}/etc/localtime"))
+        .ok()
+        .and_then(|link| {
+            link.to_string_lossy()
+                .split_once("zoneinfo/")
+                .map(|(_, tz)| tz.to_string())
+        });
+
+    let locale = read("/etc/locale.conf").ok().and_then(|conf| {
+        conf.lines()
+            .find_map(|line| line.trim().strip_prefix("LANG="))
+            .map(|lang| lang.trim_matches('"').to_string())
+    });
+
+    let packages = shell::exec_with_output(
+        "pacman",
+        &["--dbpath", &format!("{root}/var/lib/pacman"), "-Qqe"],
+    )?;
+
+    Ok(System {
+        hostname: read("/etc/hostname").ok().map(|h| h.trim().to_string()),
+        timezone,
+        locale,
+        fstab: parse_fstab(&fstab, resolve_tag),
+        packages: String::from_utf8_lossy(&packages)
+            .lines()
+            .map(String::from)
+            .collect(),
+        services: enabled_services(&format!("{root}/etc/systemd/system")),
+    })
+}
+
+impl System {
+    /// Generates best-effort manifest reproducing this system.
+    /// Disks, device mappers, and root password are never generated.
+    pub fn manifest(&self) -> Result<Manifest, AliError> {
+        let entries = self
+            .fstab
+            .iter()
+            .filter(|e| !FS_PSEUDO.contains(&e.fs_type.as_str()));
+
+        let mut rootfs = None;
+        let mut swap = Vec::new();
+        let mut filesystems = Vec::new();
+        let mut mountpoints = Vec::new();
+        let mut formatted = HashSet::new();
+
+        for entry in entries {
+            let mnt_opts = match entry.opts.as_str() {
+                "defaults" | "" => None,
+                opts => Some(opts.to_string()),
+            };
+
+            if entry.dest == "/" {
+                formatted.insert(entry.device.clone());
+                rootfs = Some(ManifestRootFs {
+                    device: entry.device.clone(),
+                    fs_type: entry.fs_type.clone(),
+                    fs_opts: None,
+                    mnt_opts,
+                });
+
+                continue;
+            }
+
+            if entry.fs_type == "swap" {
+                swap.push(entry.device.clone());
+                continue;
+            }
+
+            // Btrfs subvolumes share one filesystem
+            if formatted.insert(entry.device.clone()) {
+                filesystems.push(ManifestFs {
+                    device: entry.device.clone(),
+                    fs_type: entry.fs_type.clone(),
+                    fs_opts: None,
+                });
+            }
+
+            mountpoints.push(ManifestMountpoint {
+                device: entry.device.clone(),
+                dest: entry.dest.clone(),
+                mnt_opts,
+            });
+        }
+
+        let rootfs = rootfs.ok_or(AliError::NotImplemented(
+            "generating manifest for system without / in fstab".to_string(),
+        ))?;
+
+        // Mountpoints are sorted, so that parents are mounted first
+        mountpoints.sort_by(|a, b| a.dest.cmp(&b.dest));
+        filesystems.retain(|fs| fs.device != rootfs.device);
+
+        let chroot: Vec<String> = self
+            .services
+            .iter()
+            .map(|unit| format!("systemctl enable {unit}"))
+            .collect();
+
+        Ok(Manifest {
+            version: migrate::CURRENT_VERSION,
+            location: None,
+            hostname: self.hostname.clone(),
+            timezone: self.timezone.clone(),
+            locale: self.locale.clone(),
+            cmdline: None,
+            rootfs,
+            disks: None,
+            device_mappers: None,
+            filesystems: non_empty(filesystems),
+            mountpoints: non_empty(mountpoints),
+            swap: non_empty(swap),
+            pacstraps: match self.packages.is_empty() {
+                true => None,
+                false => Some(self.packages.iter().cloned().collect()),
+            },
+            rootpasswd: None,
+            chroot: non_empty(chroot),
+            postinstall: None,
+            proxy: None,
+            netroot: None,
+            target: None,
+            fsck: None,
+            maintenance: None,
+            reports: None,
+            hook_groups: None,
+            ssh: None,
+            runtime: None,
+            skel: None,
+            domain_join: None,
+            ca_certs: None,
+            secrets: None,
+            time: None,
+            protected_paths: None,
+            extra_targets: None,
+        })
+    }
+}
+
+fn non_empty<T>(v: Vec<T>) -> Option<Vec<T>> {
+    (!v.is_empty()).then_some(v)
+}
+
+/// Parses fstab, resolving tagged device specs (e.g. `UUID=`) with `resolve`
+fn parse_fstab<F>(fstab: &str, resolve: F) -> Vec<FstabEntry>
+where
+    F: Fn(&str, &str) -> String,
+{
+    fstab
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let [spec, dest, fs_type, opts, ..] = fields[..] else {
+                return None;
+            };
+
+            let device = FSTAB_TAGS
+                .iter()
+                .find_map(|(prefix, dir)| {
+                    spec.strip_prefix(prefix).map(|tag| resolve(dir, tag))
+                })
+                .unwrap_or(spec.to_string());
+
+            Some(FstabEntry {
+                device,
+                dest: dest.to_string(),
+                fs_type: fs_type.to_string(),
+                opts: opts.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Resolves fstab tag to device node if present on this machine,
+/// or to its `/dev/disk` symlink otherwise
+fn resolve_tag(dir: &str, tag: &str) -> String {
+    let link = format!("/dev/disk/{dir}/{tag}");
+    std::fs::canonicalize(&link)
+        .map(|path| path.to_string_lossy().to_string())
+        .unwrap_or(link)
+}
+
+/// Lists units enabled in `*.wants` directories of `dir`
+fn enabled_services(dir: &str) -> BTreeSet<String> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return BTreeSet::new();
+    };
+
+    entries
+        .flatten()
+        .filter(|e| e.file_name().to_string_lossy().ends_with(".wants"))
+        .filter_map(|e| std::fs::read_dir(e.path()).ok())
+        .flat_map(|units| units.flatten())
+        .map(|unit| unit.file_name().to_string_lossy().to_string())
+        .collect()
+}
+
+#[test]
+fn test_generate_manifest() {
+    let fstab = "# /etc/fstab
+UUID=1111	/	btrfs	rw,noatime,subvol=/@	0 0
+UUID=1111	/home	btrfs	rw,noatime,subvol=/@home	0 0
+/dev/vda1	/boot	vfat	defaults	0 2
+PARTLABEL=swap	none	swap	defaults	0 0
+tmpfs	/tmp	tmpfs	rw,nosuid	0 0
+";
+
+    let system = System {
+        hostname: Some("golden".into()),
+        timezone: Some("Asia/Bangkok".into()),
+        locale: Some("th_TH.UTF-8".into()),
+        fstab: parse_fstab(fstab, |dir, tag| format!("/dev/disk/{dir}/{tag}")),
+        packages: vec!["base".into(), "vim".into()],
+        services: BTreeSet::from(["sshd.service".into()]),
+    };
+
+    assert_eq!(5, system.fstab.len());
+
+    let m = system.manifest().unwrap();
+    assert_eq!("/dev/disk/by-uuid/1111", m.rootfs.device);
+    assert_eq!(Some("rw,noatime,subvol=/@".into()), m.rootfs.mnt_opts);
+    assert_eq!(Some(vec!["/dev/disk/by-partlabel/swap".into()]), m.swap);
+    assert_eq!(Some(vec!["systemctl enable sshd.service".into()]), m.chroot);
+
+    // Root device is not formatted twice for /home subvolume
+    let filesystems = m.filesystems.unwrap();
+    assert_eq!(1, filesystems.len());
+    assert_eq!("/dev/vda1", filesystems[0].device);
+
+    let dests: Vec<&str> = m
+        .mountpoints
+        .as_ref()
+        .unwrap()
+        .iter()
+        .map(|mnt| mnt.dest.as_str())
+        .collect();
+    assert_eq!(vec!["/boot", "/home"], dests);
+    assert_eq!(None, m.mountpoints.unwrap()[0].mnt_opts);
+
+    assert!(System::default().manifest().is_err());
+}
@@ -0,0 +1,137 @@
+use std::collections::HashMap;
+
+use super::Manifest;
+use crate::errors::AliError;
+
+/// Invokes a hook group from manifest key `hook_groups`, e.g.
+/// `@group harden_ssh`
+pub const KEY_GROUP: &str = "@group";
+
+/// Expands `@group <NAME>` in manifest keys `chroot` and `postinstall`
+/// to commands of group NAME in manifest key `hook_groups`
+pub fn expand(manifest: &mut Manifest) -> Result<(), AliError> {
+    let empty = HashMap::new();
+    let groups = manifest.hook_groups.as_ref().unwrap_or(&empty);
+
+    for cmds in [&mut manifest.chroot, &mut manifest.postinstall]
+        .into_iter()
+        .flatten()
+    {
+        *cmds = expand_cmds(cmds, groups)?;
+    }
+
+    Ok(())
+}
+
+/// Returns `cmds` with `@group <NAME>` replaced by commands of group NAME,
+/// which may in turn invoke other groups
+pub fn expand_cmds(
+    cmds: &[String],
+    groups: &HashMap<String, Vec<String>>,
+) -> Result<Vec<String>, AliError> {
+    let mut expanded = Vec::new();
+    for cmd in cmds {
+        expand_cmd(cmd, groups, &mut Vec::new(), &mut expanded)?;
+    }
+
+    Ok(expanded)
+}
+
+/// Returns group name if `cmd` invokes a hook group
+pub fn group_name(cmd: &str) -> Result<Option<&str>, AliError> {
+    let mut parts = cmd.split_whitespace();
+    if parts.next() != Some(KEY_GROUP) {
+        return Ok(None);
+    }
+
+    match (parts.next(), parts.next()) {
+        (Some(name), None) => Ok(Some(name)),
+        _ => {
+            Err(AliError::BadManifest(format!(
+                "{KEY_GROUP}: expecting 1 group name, got `{cmd}`"
+            )))
+        }
+    }
+}
+
+/// Appends expanded `cmd` to `expanded`, with `stack` tracking
+/// groups being expanded to detect cycles
+fn expand_cmd<'a>(
+    cmd: &'a str,
+    groups: &'a HashMap<String, Vec<String>>,
+    stack: &mut Vec<&'a str>,
+    expanded: &mut Vec<String>,
+) -> Result<(), AliError> {
+    let Some(name) = group_name(cmd)? else {
+        expanded.push(cmd.to_string());
+        return Ok(());
+    };
+
+    if stack.contains(&name) {
+        return Err(AliError::BadManifest(format!(
+            "{KEY_GROUP}: cyclic hook groups {} -> {name}",
+            stack.join(" -> "),
+        )));
+    }
+
+    let Some(group) = groups.get(name) else {
+        return Err(AliError::BadManifest(format!(
+            "{KEY_GROUP}: no such hook group {name}"
+        )));
+    };
+
+    stack.push(name);
+    for cmd in group {
+        expand_cmd(cmd, groups, stack, expanded)?;
+    }
+    stack.pop();
+
+    Ok(())
+}
+
+#[test]
+fn test_expand_cmds() {
+    let groups: HashMap<String, Vec<String>> = serde_yaml::from_str(
+        "
+harden_ssh:
+  - '@uncomment PermitRootLogin /etc/ssh/sshd_config'
+  - '@replace-token 22 2222 /etc/ssh/sshd_config'
+base:
+  - systemctl enable sshd
+  - '@group harden_ssh'
+cycle_a: ['@group cycle_b']
+cycle_b: ['@group cycle_a']
+",
+    )
+    .unwrap();
+
+    let cmds = |v: &[&str]| -> Vec<String> {
+        v.iter().map(|s| s.to_string()).collect()
+    };
+
+    assert_eq!(
+        cmds(&[
+            "echo foo",
+            "systemctl enable sshd",
+            "@uncomment PermitRootLogin /etc/ssh/sshd_config",
+            "@replace-token 22 2222 /etc/ssh/sshd_config",
+            "echo bar",
+        ]),
+        expand_cmds(&cmds(&["echo foo", "@group base", "echo bar"]), &groups)
+            .unwrap(),
+    );
+
+    let should_err = [
+        "@group",
+        "@group foo",
+        "@group base harden_ssh",
+        "@group cycle_a",
+    ];
+
+    for cmd in should_err {
+        assert!(
+            expand_cmds(&cmds(&[cmd]), &groups).is_err(),
+            "expecting error for {cmd}"
+        );
+    }
+}
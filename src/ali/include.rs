@@ -0,0 +1,186 @@
+use std::path::{
+    Path,
+    PathBuf,
+};
+
+use serde_yaml::Value;
+
+use super::ManifestFormat;
+use crate::errors::AliError;
+
+const KEY_INCLUDE: &str = "include";
+
+/// Resolves key `include` (a path or list of paths relative to `base_dir`)
+/// in manifest `value`, returning the merged manifest.
+///
+/// Included manifests are merged in order, and the including manifest
+/// is merged last, so later files override earlier ones:
+/// mappings are merged recursively, lists are appended,
+/// and other values are replaced.
+///
+/// `stack` holds the chain of files being included, to detect cycles.
+pub fn resolve(
+    mut value: Value,
+    base_dir: &Path,
+    stack: &mut Vec<PathBuf>,
+) -> Result<Value, AliError> {
+    let includes =
+        match value.as_mapping_mut().and_then(|m| m.remove(KEY_INCLUDE)) {
+            None => return Ok(value),
+            Some(Value::String(path)) => vec![path],
+            Some(Value::Sequence(paths)) => {
+                paths
+                    .into_iter()
+                    .map(|p| {
+                        match p {
+                            Value::String(path) => Ok(path),
+                            other => {
+                                Err(AliError::BadManifest(format!(
+                                    "bad include path {other:?}"
+                                )))
+                            }
+                        }
+                    })
+                    .collect::<Result<Vec<_>, _>>()?
+            }
+            Some(other) => {
+                return Err(AliError::BadManifest(format!(
+                "key `{KEY_INCLUDE}` is not a path or list of paths: {other:?}"
+            )));
+            }
+        };
+
+    let mut merged = Value::Mapping(Default::default());
+    for include in includes {
+        let included = load(&base_dir.join(&include), stack)?;
+        merge(&mut merged, included);
+    }
+
+    merge(&mut merged, value);
+
+    Ok(merged)
+}
+
+/// Loads included manifest file at `path`, resolving its own includes
+fn load(path: &Path, stack: &mut Vec<PathBuf>) -> Result<Value, AliError> {
+    let canonical = canonicalize(path)?;
+    if stack.contains(&canonical) {
+        return Err(AliError::BadManifest(format!(
+            "include cycle: {} includes itself",
+            path.display()
+        )));
+    }
+
+    let manifest = std::fs::read_to_string(path).map_err(|err| {
+        AliError::NoSuchFile(err, path.to_string_lossy().to_string())
+    })?;
+
+    let format = ManifestFormat::from_path(&path.to_string_lossy());
+    let mut value = super::parse_value(&manifest, format)?;
+    super::migrate_warn(&mut value)?;
+
+    stack.push(canonical);
+    let base_dir = path.parent().unwrap_or(Path::new("."));
+    let value = resolve(value, base_dir, stack)?;
+    stack.pop();
+
+    Ok(value)
+}
+
+pub(super) fn canonicalize(path: &Path) -> Result<PathBuf, AliError> {
+    path.canonicalize().map_err(|err| {
+        AliError::NoSuchFile(err, path.to_string_lossy().to_string())
+    })
+}
+
+/// Merges `over` into `base`: mappings are merged recursively,
+/// lists are appended, and other values are replaced
+pub fn merge(base: &mut Value, over: Value) {
+    match (base, over) {
+        (Value::Mapping(base), Value::Mapping(over)) => {
+            for (k, v) in over {
+                match base.get_mut(&k) {
+                    Some(existing) => merge(existing, v),
+                    None => {
+                        base.insert(k, v);
+                    }
+                }
+            }
+        }
+        (Value::Sequence(base), Value::Sequence(over)) => base.extend(over),
+        (base, over) => *base = over,
+    }
+}
+
+#[test]
+fn test_merge() {
+    let mut base: Value = serde_yaml::from_str(
+        r#"
+hostname: base
+rootfs:
+  device: /dev/vda1
+  fstype: ext4
+pacstrap: [base, git]
+"#,
+    )
+    .unwrap();
+
+    let over: Value = serde_yaml::from_str(
+        r#"
+hostname: laptop
+rootfs:
+  fstype: btrfs
+pacstrap: [iwd]
+chroot: ["echo foo"]
+"#,
+    )
+    .unwrap();
+
+    merge(&mut base, over);
+
+    let expected: Value = serde_yaml::from_str(
+        r#"
+hostname: laptop
+rootfs:
+  device: /dev/vda1
+  fstype: btrfs
+pacstrap: [base, git, iwd]
+chroot: ["echo foo"]
+"#,
+    )
+    .unwrap();
+
+    assert_eq!(expected, base);
+}
+
+#[test]
+fn test_resolve() {
+    let dir = std::env::temp_dir()
+        .join(format!("ali-rs-include-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    std::fs::write(
+        dir.join("base.yaml"),
+        "version: 1\nhostname: base\npacstrap: [base]\n",
+    )
+    .unwrap();
+    std::fs::write(dir.join("cycle.yaml"), "include: cycle.yaml\n").unwrap();
+
+    let value: Value = serde_yaml::from_str(
+        "include: base.yaml\nhostname: laptop\npacstrap: [iwd]\n",
+    )
+    .unwrap();
+    let value = resolve(value, &dir, &mut Vec::new()).unwrap();
+
+    assert_eq!(Some("laptop"), value["hostname"].as_str());
+    assert_eq!(2, value["pacstrap"].as_sequence().unwrap().len());
+    assert!(value.get(KEY_INCLUDE).is_none());
+
+    let value: Value = serde_yaml::from_str("include: cycle.yaml\n").unwrap();
+    assert!(resolve(value, &dir, &mut Vec::new()).is_err());
+
+    let value: Value = serde_yaml::from_str("include: missing.yaml\n").unwrap();
+    assert!(resolve(value, &dir, &mut Vec::new()).is_err());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
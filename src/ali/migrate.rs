@@ -0,0 +1,117 @@
+use serde_yaml::{
+    Mapping,
+    Value,
+};
+
+use crate::errors::AliError;
+
+/// Current manifest schema version
+pub const CURRENT_VERSION: u32 = 1;
+
+/// Manifests without `version` predate schema versioning
+const VERSION_UNVERSIONED: u32 = 0;
+
+const KEY_VERSION: &str = "version";
+
+/// Keys deprecated in version 1, and their replacements
+const DEPRECATED_V1: [(&str, &str); 5] = [
+    ("device-mappers", "device_mappers"),
+    ("arch-chroot", "chroot"),
+    ("post-install", "postinstall"),
+    ("root-password", "rootpasswd"),
+    ("root-passwd", "rootpasswd"),
+];
+
+/// Upgrades manifest `value` in-memory to [`CURRENT_VERSION`],
+/// one version at a time, returning warnings about deprecated keys.
+pub fn migrate(value: &mut Value) -> Result<Vec<String>, AliError> {
+    let mapping = value.as_mapping_mut().ok_or(AliError::BadManifest(
+        "manifest is not a mapping".to_string(),
+    ))?;
+
+    let mut warnings = Vec::new();
+    let mut version = match mapping.get(KEY_VERSION) {
+        None => {
+            warnings.push(format!(
+                "manifest has no key `{KEY_VERSION}`, assuming unversioned manifest"
+            ));
+
+            VERSION_UNVERSIONED
+        }
+        Some(v) => {
+            v.as_u64().and_then(|v| u32::try_from(v).ok()).ok_or(
+                AliError::BadManifest(format!("bad manifest version {v:?}")),
+            )?
+        }
+    };
+
+    if version > CURRENT_VERSION {
+        return Err(AliError::BadManifest(format!(
+            "manifest version {version} is newer than supported version {CURRENT_VERSION}"
+        )));
+    }
+
+    while version < CURRENT_VERSION {
+        match version {
+            VERSION_UNVERSIONED => migrate_v0_v1(mapping, &mut warnings)?,
+            _ => unreachable!("no migration from version {version}"),
+        }
+
+        version += 1;
+    }
+
+    mapping.insert(KEY_VERSION.into(), CURRENT_VERSION.into());
+
+    Ok(warnings)
+}
+
+/// Renames deprecated hyphenated keys to their canonical names
+fn migrate_v0_v1(
+    mapping: &mut Mapping,
+    warnings: &mut Vec<String>,
+) -> Result<(), AliError> {
+    for (old, new) in DEPRECATED_V1 {
+        let Some(v) = mapping.remove(old) else {
+            continue;
+        };
+
+        if mapping.contains_key(new) {
+            return Err(AliError::BadManifest(format!(
+                "found both key `{old}` and `{new}`"
+            )));
+        }
+
+        warnings.push(format!("key `{old}` is deprecated, use `{new}`"));
+        mapping.insert(new.into(), v);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_migrate() {
+    let yaml = "arch-chroot: [\"echo foo\"]\nhostname: foo\n";
+    let mut value: Value = serde_yaml::from_str(yaml).unwrap();
+    let warnings = migrate(&mut value).unwrap();
+
+    assert_eq!(2, warnings.len());
+    assert_eq!(Some(1), value.get("version").and_then(Value::as_u64));
+    assert!(value.get("arch-chroot").is_none());
+    assert!(value.get("chroot").is_some());
+
+    let mut value: Value =
+        serde_yaml::from_str("version: 1\nchroot: []\n").unwrap();
+    assert!(migrate(&mut value).unwrap().is_empty());
+
+    let should_err = [
+        "version: 2\n",
+        "version: foo\n",
+        "arch-chroot: []\nchroot: []\n",
+        "- foo\n",
+    ];
+
+    for yaml in should_err {
+        let mut value: Value = serde_yaml::from_str(yaml).unwrap();
+        assert!(migrate(&mut value).is_err(), "expecting error for {yaml}");
+    }
+}
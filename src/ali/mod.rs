@@ -1,17 +1,46 @@
 pub mod apply;
+pub mod generate;
+pub mod groups;
+pub mod include;
+pub mod migrate;
+pub mod portable;
+pub mod preflight;
+#[cfg(feature = "tui")]
+pub mod preset;
 pub mod validation;
+pub mod vars;
 
-use std::collections::HashSet;
+use std::collections::{
+    BTreeMap,
+    HashMap,
+    HashSet,
+};
+use std::path::{
+    Path,
+    PathBuf,
+};
 
+use clap::ValueEnum;
 use serde::{
     Deserialize,
     Serialize,
 };
 
 use crate::errors::AliError;
+use crate::linux::systemd::Property;
+use crate::types::blockdev::parse_human_bytes;
+use crate::utils::aliases::{
+    self,
+    Kind,
+};
+use crate::utils::report_sink::ReportSink;
+use crate::utils::secrets::ProviderConfig;
 
-#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
 pub struct Manifest {
+    /// Manifest schema version, see [`migrate`]
+    pub version: u32,
+
     #[serde(alias = "location", alias = "install_location")]
     pub location: Option<String>,
 
@@ -21,6 +50,15 @@ pub struct Manifest {
     #[serde(alias = "tz")]
     pub timezone: Option<String>,
 
+    /// Locale in `<LANG>.<CHARSET>` form, e.g. `de_DE.UTF-8`
+    #[serde(alias = "lang")]
+    pub locale: Option<String>,
+
+    /// Extra kernel parameters, merged with parameters generated
+    /// by ali-rs, e.g. `console=ttyS0`
+    #[serde(alias = "kernel_params")]
+    pub cmdline: Option<Vec<String>>,
+
     #[serde(alias = "root")]
     pub rootfs: ManifestRootFs,
 
@@ -58,13 +96,534 @@ pub struct Manifest {
 
     #[serde(alias = "post-install")]
     pub postinstall: Option<Vec<String>>,
+
+    /// SOCKS5 proxy or SSH bastion used to fetch remote sources,
+    /// e.g. `socks5://127.0.0.1:1080` or `ssh://user@bastion`
+    #[serde(alias = "socks", alias = "tunnel")]
+    pub proxy: Option<String>,
+
+    /// Root on NFS or iSCSI for diskless installs
+    #[serde(alias = "diskless")]
+    pub netroot: Option<ManifestNetRoot>,
+
+    /// What the new system is for, overridden by `--target`
+    pub target: Option<InstallTarget>,
+
+    /// Per-device fsck policy for `ali-rs rescue --fsck`
+    pub fsck: Option<HashMap<String, FsckPolicy>>,
+
+    /// Periodic storage maintenance and monitoring of the new system
+    pub maintenance: Option<ManifestMaintenance>,
+
+    /// Sinks receiving hook actions and the final report as they are produced
+    #[serde(alias = "report", alias = "report-sinks")]
+    pub reports: Option<Vec<ReportSink>>,
+
+    /// Named lists of commands, invoked with `@group <NAME>`
+    /// from `chroot`, `postinstall`, or `ali-rs hooks`
+    #[serde(alias = "groups", alias = "hook-groups")]
+    pub hook_groups: Option<HashMap<String, Vec<String>>>,
+
+    /// OpenSSH client config and known hosts of the new system
+    pub ssh: Option<ManifestSsh>,
+
+    /// Resource limits of the install run itself
+    pub runtime: Option<ManifestRuntime>,
+
+    /// Files in `/etc/skel` of the new system, copied to home directories
+    /// of users created by chroot commands
+    pub skel: Option<Vec<ManifestSkelFile>>,
+
+    /// Active Directory domain joined on first boot of the new system
+    #[serde(alias = "ad")]
+    pub domain_join: Option<ManifestDomainJoin>,
+
+    /// CA certificates trusted by the new system, installed before
+    /// chroot commands run
+    #[serde(alias = "ca_certificates")]
+    pub ca_certs: Option<Vec<ManifestCaCert>>,
+
+    /// Providers resolving `secret://<NAME>` references, tried in order
+    pub secrets: Option<Vec<ProviderConfig>>,
+
+    /// Time synchronization of the new system with chrony and PTP,
+    /// in place of systemd-timesyncd
+    #[serde(alias = "time_sync")]
+    pub time: Option<ManifestTime>,
+
+    /// Paths hooks may never write, in addition to defaults
+    /// like `/etc/shadow`. Hook `@unprotect` overrides this per hook
+    pub protected_paths: Option<Vec<String>>,
+
+    /// Independent targets installed after this one in the same run,
+    /// e.g. a minimal rescue system on another partition
+    #[serde(alias = "extra-targets")]
+    pub extra_targets: Option<Vec<ManifestExtraTarget>>,
+}
+
+/// Extra install target of a multi-target session
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ManifestExtraTarget {
+    /// Name in progress, reports, and `apply --only-target`
+    pub name: String,
+
+    /// Install location, distinct from (and not nested in)
+    /// other install locations
+    pub location: String,
+
+    /// Manifest of this target, relative to this manifest file.
+    /// It may not have extra targets of its own
+    pub manifest: String,
+
+    /// Pinned checksum of `manifest`, required for remote manifests
+    /// if this manifest is pinned with `--sha256`
+    pub sha256: Option<String>,
+}
+
+/// Kind of machine the new system is installed for
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, ValueEnum,
+)]
+pub enum InstallTarget {
+    /// The machine running the installer
+    #[serde(rename = "fixed")]
+    Fixed,
+
+    /// Portable USB stick, bootable on both BIOS and UEFI machines
+    #[serde(rename = "portable")]
+    Portable,
+}
+
+/// Maintenance units installed and enabled on the new system
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ManifestMaintenance {
+    /// Enables monthly `btrfs-scrub@.timer` for each Btrfs filesystem
+    #[serde(alias = "scrub")]
+    pub btrfs_scrub: Option<bool>,
+
+    /// systemd calendar event of periodic balance for each Btrfs
+    /// filesystem, e.g. `weekly`
+    #[serde(alias = "balance")]
+    pub btrfs_balance: Option<String>,
+
+    /// Enables weekly `fstrim.timer`
+    pub fstrim: Option<bool>,
+
+    /// Enables smartd to monitor all disks
+    #[serde(alias = "smart")]
+    pub smartd: Option<ManifestSmartd>,
+}
+
+/// Alerting of smartd, which only logs to journal if empty
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ManifestSmartd {
+    /// Email address to send alerts to with mail(1)
+    pub email: Option<String>,
+
+    /// ntfy topic URL to publish alerts to, e.g. `https://ntfy.sh/mytopic`
+    pub ntfy: Option<String>,
+}
+
+impl ManifestMaintenance {
+    /// Packages required by maintenance units
+    pub fn packages(&self) -> Vec<&'static str> {
+        let mut packages = Vec::new();
+        if let Some(smartd) = &self.smartd {
+            packages.push("smartmontools");
+            if smartd.email.is_some() {
+                packages.push("s-nail");
+            }
+        }
+
+        packages
+    }
+}
+
+/// File or directory in `/etc/skel`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ManifestSkelFile {
+    /// Path relative to `/etc/skel`, e.g. `.bashrc`.
+    /// Paths ending with `/` are directories.
+    pub path: String,
+
+    /// Content of file
+    pub content: Option<String>,
+
+    /// Template file on the live system, copied with `{{ name }}` tokens
+    /// replaced by `vars`
+    #[serde(alias = "src")]
+    pub source: Option<String>,
+
+    /// Values of tokens in `source`
+    pub vars: Option<BTreeMap<String, String>>,
+
+    /// Octal permissions, e.g. `0600`.
+    /// Defaults to `0644` for files and `0755` for directories.
+    pub mode: Option<String>,
+}
+
+impl ManifestSkelFile {
+    pub fn is_dir(&self) -> bool {
+        self.path.ends_with('/')
+    }
+
+    /// Permission bits from `mode`, or the default
+    pub fn mode(&self) -> Result<u32, AliError> {
+        let Some(ref mode) = self.mode else {
+            return Ok(if self.is_dir() { 0o755 } else { 0o644 });
+        };
+
+        match u32::from_str_radix(mode, 8) {
+            Ok(bits) if bits <= 0o7777 => Ok(bits),
+            _ => {
+                Err(AliError::BadManifest(format!(
+                    "skel: bad mode {mode} of {}",
+                    self.path
+                )))
+            }
+        }
+    }
+}
+
+/// CA certificate installed as trust anchor, from exactly one of
+/// `pem`, `file`, or `url`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ManifestCaCert {
+    /// File name in anchors directory, without `.crt`
+    pub name: String,
+
+    /// Inline PEM certificate
+    pub pem: Option<String>,
+
+    /// PEM file on the live system
+    pub file: Option<String>,
+
+    /// URL of PEM certificate, which must be pinned with `sha256`
+    pub url: Option<String>,
+
+    /// Checksum of certificate downloaded from `url`
+    pub sha256: Option<String>,
+}
+
+/// Active Directory membership with adcli(8), with users and groups
+/// from SSSD. Configs are written at install time, but the join itself
+/// runs on first boot, once network and clock are in sync.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ManifestDomainJoin {
+    /// DNS name of the domain, e.g. `corp.example.com`
+    pub domain: String,
+
+    /// Account allowed to join computers, with password read from
+    /// `password_file` on the live system at install time
+    pub user: Option<String>,
+
+    pub password_file: Option<String>,
+
+    /// File with one-time password of a prestaged computer account,
+    /// used instead of `user`
+    pub one_time_password_file: Option<String>,
+
+    /// Distinguished name of OU to create computer account in,
+    /// e.g. `OU=Linux,DC=corp,DC=example,DC=com`
+    pub ou: Option<String>,
+
+    /// Computer account name, defaults to hostname
+    pub computer_name: Option<String>,
+
+    /// Whether user and group names include the domain,
+    /// e.g. `alice@corp.example.com`
+    pub fully_qualified_names: Option<bool>,
+}
+
+impl ManifestDomainJoin {
+    /// Packages required to join and to resolve domain users
+    pub fn packages(&self) -> [&'static str; 3] {
+        ["adcli", "sssd", "krb5"]
+    }
+
+    /// Kerberos realm, i.e. upper-case domain
+    pub fn realm(&self) -> String {
+        self.domain.to_uppercase()
+    }
+}
+
+/// Time synchronization for hosts needing authenticated or
+/// sub-microsecond time, e.g. lab and trading machines
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ManifestTime {
+    /// chrony(1) with NTS, replacing systemd-timesyncd
+    pub chrony: Option<ManifestChrony>,
+
+    /// PTP (IEEE 1588) with linuxptp
+    pub ptp: Option<ManifestPtp>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ManifestChrony {
+    /// NTP servers, e.g. `time.cloudflare.com`
+    pub servers: Vec<String>,
+
+    /// Whether to authenticate servers with Network Time Security,
+    /// defaults to true
+    pub nts: Option<bool>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ManifestPtp {
+    /// Network interface to run ptp4l(8) on
+    pub interface: String,
+
+    /// PTP domain number, defaults to 0
+    pub domain: Option<u8>,
+
+    /// `UDPv4` (default), `UDPv6`, or `L2`
+    pub transport: Option<String>,
+
+    /// `hardware` (default) or `software`. With hardware time stamping,
+    /// phc2sys(8) synchronizes the system clock to the NIC's PTP hardware
+    /// clock, or feeds it to chrony if `chrony` is also set.
+    pub time_stamping: Option<String>,
+
+    /// Kernel modules loaded at boot besides `ptp`, e.g. NIC driver `igb`
+    pub modules: Option<Vec<String>>,
+}
+
+impl ManifestTime {
+    pub fn packages(&self) -> Vec<&'static str> {
+        let mut packages = Vec::new();
+        if self.chrony.is_some() {
+            packages.push("chrony");
+        }
+        if self.ptp.is_some() {
+            packages.push("linuxptp");
+        }
+
+        packages
+    }
+
+    /// Units enabled in the new system for `multi-user.target`
+    pub fn services(&self) -> Vec<String> {
+        let mut services = Vec::new();
+        if self.chrony.is_some() {
+            services.push("chronyd.service".to_string());
+            services.push("chrony-wait.service".to_string());
+        }
+
+        if let Some(ref m_ptp) = self.ptp {
+            let interface = &m_ptp.interface;
+            services.push(format!("ptp4l@{interface}.service"));
+            if m_ptp.is_hardware() {
+                services.push(format!("phc2sys@{interface}.service"));
+            }
+        }
+
+        services
+    }
+}
+
+impl ManifestPtp {
+    pub fn is_hardware(&self) -> bool {
+        self.time_stamping.as_deref().unwrap_or("hardware") == "hardware"
+    }
+}
+
+/// Resource limits of the install run. ali-rs and all commands it runs
+/// are moved into a transient systemd scope with these limits, so that
+/// runaway commands cannot exhaust the live environment.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ManifestRuntime {
+    /// Hard memory limit, e.g. `2G`. Commands exceeding it are OOM-killed
+    #[serde(alias = "memory")]
+    pub memory_max: Option<String>,
+
+    /// Memory limit above which commands are throttled, e.g. `1536M`
+    pub memory_high: Option<String>,
+
+    /// IO weight from 1 to 10000, relative to the default of 100
+    pub io_weight: Option<u64>,
+
+    /// CPU time limit in percent of one CPU, e.g. `200` for 2 CPUs
+    pub cpu_quota: Option<u64>,
+
+    /// Limit on number of processes and threads
+    pub tasks_max: Option<u64>,
+
+    /// Slice to create the scope in, e.g. `ali-rs.slice`
+    pub slice: Option<String>,
+}
+
+impl ManifestRuntime {
+    /// Resource-control properties of the transient scope
+    pub fn properties(&self) -> Result<Vec<Property>, AliError> {
+        let mut properties = Vec::new();
+
+        let sizes = [
+            ("MemoryMax", &self.memory_max),
+            ("MemoryHigh", &self.memory_high),
+        ];
+        for (name, size) in sizes {
+            if let Some(size) = size {
+                let bytes = parse_human_bytes(size)?.size() as u64;
+                properties.push(Property::U64(name, bytes));
+            }
+        }
+
+        if let Some(weight) = self.io_weight {
+            if !(1..=10000).contains(&weight) {
+                return Err(AliError::BadManifest(format!(
+                    "runtime: io_weight {weight} not in range 1-10000"
+                )));
+            }
+
+            properties.push(Property::U64("IOWeight", weight));
+        }
+
+        if let Some(percent) = self.cpu_quota {
+            if percent == 0 {
+                return Err(AliError::BadManifest(
+                    "runtime: cpu_quota must be positive".to_string(),
+                ));
+            }
+
+            // Quota is in CPU microseconds per second
+            let usecs = percent * 10_000;
+            properties.push(Property::U64("CPUQuotaPerSecUSec", usecs));
+        }
+
+        if let Some(tasks) = self.tasks_max {
+            properties.push(Property::U64("TasksMax", tasks));
+        }
+
+        if let Some(ref slice) = self.slice {
+            if !slice.ends_with(".slice") || slice.contains('/') {
+                return Err(AliError::BadManifest(format!(
+                    "runtime: bad slice name {slice}"
+                )));
+            }
+
+            properties.push(Property::String("Slice", slice.clone()));
+        }
+
+        Ok(properties)
+    }
+}
+
+/// OpenSSH client config and known hosts, system-wide and per user
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ManifestSsh {
+    /// `Host` blocks of system-wide ssh_config
+    #[serde(alias = "ssh_config")]
+    pub config: Option<Vec<ManifestSshHost>>,
+
+    /// Entries of system-wide known hosts file
+    #[serde(alias = "known-hosts")]
+    pub known_hosts: Option<Vec<ManifestKnownHost>>,
+
+    /// Hashes host names in known hosts files, like `ssh-keygen -H`
+    #[serde(alias = "hash", alias = "hash-known-hosts")]
+    pub hash_known_hosts: Option<bool>,
+
+    /// Per-user config and known hosts, keyed by user name.
+    /// Users must exist after commands in manifest key `chroot`.
+    pub users: Option<BTreeMap<String, ManifestSshUser>>,
+}
+
+/// ssh_config `Host` block, e.g. host `*.internal` with option `User deploy`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ManifestSshHost {
+    /// Host patterns, separated by spaces
+    pub host: String,
+
+    /// ssh_config keywords and values
+    pub options: BTreeMap<String, String>,
+}
+
+/// Known hosts entry
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ManifestKnownHost {
+    /// Host names or patterns, separated by commas,
+    /// e.g. `git.internal,10.0.0.5` or `[git.internal]:2222`
+    #[serde(alias = "hosts")]
+    pub host: String,
+
+    /// Public host key, e.g. `ssh-ed25519 AAAA..`
+    pub key: String,
+
+    /// Marks key as CA signing host certificates of `host`
+    #[serde(alias = "cert-authority")]
+    pub cert_authority: Option<bool>,
+}
+
+/// Per-user ssh client config and known hosts
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ManifestSshUser {
+    pub config: Option<Vec<ManifestSshHost>>,
+
+    #[serde(alias = "known-hosts")]
+    pub known_hosts: Option<Vec<ManifestKnownHost>>,
+}
+
+/// What to do with a filesystem before mounting it in rescue mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FsckPolicy {
+    /// Do not check
+    #[serde(rename = "skip")]
+    Skip,
+
+    /// Check and report, without changing anything.
+    /// Filesystems with errors are mounted read-only.
+    #[serde(rename = "check")]
+    Check,
+
+    /// Check and automatically repair
+    #[serde(rename = "repair")]
+    Repair,
+}
+
+/// Manifest file format, detected from file extension if not given
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ManifestFormat {
+    Yaml,
+    Json,
+    Toml,
+}
+
+impl ManifestFormat {
+    /// Detects format from `path` extension, defaulting to YAML
+    pub fn from_path(path: &str) -> Self {
+        let ext = std::path::Path::new(path)
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase());
+
+        match ext.as_deref() {
+            Some("json") => Self::Json,
+            Some("toml") => Self::Toml,
+            _ => Self::Yaml,
+        }
+    }
 }
 
 impl Manifest {
     #[inline]
+    #[allow(unused)]
     pub fn from_yaml(manifest_yaml: &str) -> Result<Self, AliError> {
         parse(manifest_yaml)
     }
+
+    #[inline]
+    pub fn is_portable(&self) -> bool {
+        self.target == Some(InstallTarget::Portable)
+    }
+
+    #[inline]
+    pub fn from_str_at(
+        manifest: &str,
+        path: &str,
+        format: ManifestFormat,
+        vars: &HashMap<String, String>,
+    ) -> Result<Self, AliError> {
+        parse_at(manifest, path, format, vars)
+    }
 }
 
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
@@ -81,6 +640,36 @@ pub struct ManifestDisk {
     pub device: String,
     pub table: PartitionTable,
     pub partitions: Vec<ManifestPartition>,
+
+    /// Wipe before creating partition table, default none
+    pub wipe: Option<DiskWipe>,
+}
+
+/// How disks are wiped before creating partition tables,
+/// since stale LVM, RAID, and filesystem signatures confuse later stages
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiskWipe {
+    /// Signatures, then the whole disk with blkdiscard if supported
+    /// (e.g. SSDs), or else zeroes only first and last MiB.
+    /// Not a secure erase: old data may remain readable on HDDs.
+    Full,
+
+    /// Filesystem, RAID, LVM, and partition table signatures,
+    /// on the disk and its partitions
+    Signatures,
+
+    None,
+}
+
+impl std::fmt::Display for DiskWipe {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Full => write!(f, "full"),
+            Self::Signatures => write!(f, "signatures"),
+            Self::None => write!(f, "none"),
+        }
+    }
 }
 
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
@@ -92,6 +681,22 @@ pub struct ManifestPartition {
     pub part_type: String,
 }
 
+impl ManifestPartition {
+    /// Partition types for EFI system partition
+    const TYPES_ESP: [&'static str; 4] = ["ef", "ef00", "uefi", "esp"];
+
+    /// Partition types for GPT BIOS boot partition
+    const TYPES_BIOS_BOOT: [&'static str; 2] = ["ef02", "bios"];
+
+    pub fn is_esp(&self) -> bool {
+        Self::TYPES_ESP.contains(&self.part_type.to_lowercase().as_str())
+    }
+
+    pub fn is_bios_boot(&self) -> bool {
+        Self::TYPES_BIOS_BOOT.contains(&self.part_type.to_lowercase().as_str())
+    }
+}
+
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct ManifestFs {
     pub device: String,
@@ -114,7 +719,7 @@ pub struct ManifestMountpoint {
     pub mnt_opts: Option<String>,
 }
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ManifestRootFs {
     pub device: String,
 
@@ -128,6 +733,50 @@ pub struct ManifestRootFs {
     pub mnt_opts: Option<String>,
 }
 
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum NetRootProtocol {
+    #[serde(rename = "nfs")]
+    Nfs,
+
+    #[serde(rename = "iscsi")]
+    Iscsi,
+}
+
+/// Network root for diskless installs. With `netroot`, local disk stages
+/// are skipped, and the new system is installed into a plain directory
+/// to be exported to netboot clients.
+///
+/// For NFS, `rootfs.device` is the export, e.g. `10.0.0.1:/srv/arch`.
+/// For iSCSI, `rootfs.device` is the root device as seen by the client.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ManifestNetRoot {
+    #[serde(rename = "type")]
+    pub protocol: NetRootProtocol,
+
+    /// Kernel `ip=` parameter, defaults to `dhcp`
+    pub ip: Option<String>,
+
+    /// iSCSI target IQN
+    #[serde(alias = "iqn")]
+    pub target: Option<String>,
+
+    /// iSCSI portal, e.g. `10.0.0.1:3260`
+    pub portal: Option<String>,
+
+    /// iSCSI initiator IQN, defaults to one derived from hostname
+    pub initiator: Option<String>,
+}
+
+impl ManifestNetRoot {
+    /// Packages needed in the new system to boot from network root
+    pub fn packages(&self) -> [&'static str; 2] {
+        match self.protocol {
+            NetRootProtocol::Nfs => ["mkinitcpio-nfs-utils", "nfs-utils"],
+            NetRootProtocol::Iscsi => ["mkinitcpio-nfs-utils", "open-iscsi"],
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub struct ManifestLuks {
     pub device: String,
@@ -191,14 +840,212 @@ impl From<ManifestRootFs> for ManifestMountpoint {
 
 #[inline]
 pub fn parse(manifest: &str) -> Result<Manifest, AliError> {
-    serde_yaml::from_str(manifest)
-        .map_err(|err| AliError::BadManifest(err.to_string()))
+    parse_format(manifest, ManifestFormat::Yaml, &HashMap::new())
+}
+
+/// Parses manifest in any format. All formats are first read into
+/// YAML value, so that they share the same migration, includes,
+/// variable substitution (with `vars` overriding manifest variables),
+/// and serde structs.
+///
+/// Includes are resolved relative to current directory.
+pub fn parse_format(
+    manifest: &str,
+    format: ManifestFormat,
+    vars: &HashMap<String, String>,
+) -> Result<Manifest, AliError> {
+    let value = parse_value(manifest, format)?;
+    finish(value, Path::new("."), &mut Vec::new(), vars)
+}
+
+/// Serializes `manifest` to YAML, omitting empty keys
+pub fn to_yaml(manifest: &Manifest) -> Result<String, AliError> {
+    let mut value = serde_yaml::to_value(manifest).map_err(|err| {
+        AliError::AliRsBug(format!("failed to serialize manifest: {err}"))
+    })?;
+
+    strip_nulls(&mut value);
+
+    serde_yaml::to_string(&value).map_err(|err| {
+        AliError::AliRsBug(format!("failed to serialize manifest: {err}"))
+    })
+}
+
+fn strip_nulls(value: &mut serde_yaml::Value) {
+    match value {
+        serde_yaml::Value::Mapping(map) => {
+            map.retain(|_, v| !v.is_null());
+            map.values_mut().for_each(strip_nulls);
+        }
+        serde_yaml::Value::Sequence(seq) => {
+            seq.iter_mut().for_each(strip_nulls)
+        }
+        serde_yaml::Value::Tagged(tagged) => strip_nulls(&mut tagged.value),
+        _ => {}
+    }
+}
+
+/// Parses manifest read from file `path`,
+/// resolving includes relative to the file
+pub fn parse_at(
+    manifest: &str,
+    path: &str,
+    format: ManifestFormat,
+    vars: &HashMap<String, String>,
+) -> Result<Manifest, AliError> {
+    let value = parse_value(manifest, format)?;
+    let mut stack = vec![include::canonicalize(Path::new(path))?];
+    let base_dir = Path::new(path).parent().unwrap_or(Path::new("."));
+
+    finish(value, base_dir, &mut stack, vars)
+}
+
+fn finish(
+    mut value: serde_yaml::Value,
+    base_dir: &Path,
+    stack: &mut Vec<PathBuf>,
+    vars: &HashMap<String, String>,
+) -> Result<Manifest, AliError> {
+    migrate_warn(&mut value)?;
+
+    let mut value = include::resolve(value, base_dir, stack)?;
+    vars::substitute(&mut value, vars)?;
+
+    let mut manifest: Manifest = serde_yaml::from_value(value)
+        .map_err(|err| AliError::BadManifest(err.to_string()))?;
+
+    groups::expand(&mut manifest)?;
+    resolve_aliases(&mut manifest);
+
+    Ok(manifest)
+}
+
+/// Replaces aliased filesystem types with canonical ones,
+/// e.g. `fat32` with `vfat`
+fn resolve_aliases(manifest: &mut Manifest) {
+    let fs_types = std::iter::once(&mut manifest.rootfs.fs_type).chain(
+        manifest
+            .filesystems
+            .iter_mut()
+            .flatten()
+            .map(|fs| &mut fs.fs_type),
+    );
+
+    for fs_type in fs_types {
+        *fs_type = aliases::canonical(Kind::FsType, fs_type);
+    }
+}
+
+fn parse_value(
+    manifest: &str,
+    format: ManifestFormat,
+) -> Result<serde_yaml::Value, AliError> {
+    let bad_manifest = |err: String| AliError::BadManifest(err);
+    let value = match format {
+        ManifestFormat::Yaml => {
+            serde_yaml::from_str(manifest)
+                .map_err(|err| bad_manifest(err.to_string()))?
+        }
+        ManifestFormat::Json => {
+            serde_json::from_str(manifest)
+                .map_err(|err| bad_manifest(err.to_string()))?
+        }
+        ManifestFormat::Toml => {
+            toml::from_str(manifest)
+                .map_err(|err| bad_manifest(err.to_string()))?
+        }
+    };
+
+    Ok(value)
+}
+
+/// Migrates `value` to current schema, printing warnings
+fn migrate_warn(value: &mut serde_yaml::Value) -> Result<(), AliError> {
+    for warning in migrate::migrate(value)? {
+        log::warn!("{warning}");
+    }
+
+    Ok(())
 }
 
 #[test]
 fn test_parse() {
     let example_yaml = include_str!("./examples/uefi-root-on-lvm.yaml");
     let manifest: Manifest = parse(example_yaml).unwrap();
+    assert_eq!(migrate::CURRENT_VERSION, manifest.version);
 
     println!("{:?}", manifest);
 }
+
+#[test]
+fn test_parse_format() {
+    let yaml = include_str!("./examples/uefi-root-on-lvm.yaml");
+    let expected = parse(yaml).unwrap();
+
+    let value: serde_yaml::Value = serde_yaml::from_str(yaml).unwrap();
+    let json = serde_json::to_string(&value).unwrap();
+    let manifest =
+        parse_format(&json, ManifestFormat::Json, &HashMap::new()).unwrap();
+    assert_eq!(expected, manifest);
+
+    let toml = r#"
+version = 1
+hostname = "arch-server"
+pacstrap = ["base"]
+
+[rootfs]
+device = "/dev/vda1"
+fstype = "btrfs"
+"#;
+    let manifest =
+        parse_format(toml, ManifestFormat::Toml, &HashMap::new()).unwrap();
+    assert_eq!(Some("arch-server".to_string()), manifest.hostname);
+    assert_eq!("/dev/vda1", manifest.rootfs.device);
+
+    // Aliased filesystem types are resolved
+    let manifest = parse_format(
+        &toml.replace("btrfs", "fat32"),
+        ManifestFormat::Toml,
+        &HashMap::new(),
+    )
+    .unwrap();
+    assert_eq!("vfat", manifest.rootfs.fs_type);
+
+    let tests = [
+        ("manifest.yaml", ManifestFormat::Yaml),
+        ("manifest.yml", ManifestFormat::Yaml),
+        ("manifest", ManifestFormat::Yaml),
+        ("path/to/manifest.JSON", ManifestFormat::Json),
+        ("manifest.toml", ManifestFormat::Toml),
+    ];
+
+    for (path, expected) in tests {
+        assert_eq!(expected, ManifestFormat::from_path(path));
+    }
+}
+
+#[test]
+fn test_runtime_properties() {
+    let yaml = r#"
+memory: 2G
+io_weight: 50
+cpu_quota: 150
+slice: ali-rs.slice
+"#;
+    let m_runtime: ManifestRuntime = serde_yaml::from_str(yaml).unwrap();
+    assert_eq!(
+        vec![
+            Property::U64("MemoryMax", 2_000_000_000),
+            Property::U64("IOWeight", 50),
+            Property::U64("CPUQuotaPerSecUSec", 1_500_000),
+            Property::String("Slice", "ali-rs.slice".to_string()),
+        ],
+        m_runtime.properties().unwrap(),
+    );
+
+    let bad = ManifestRuntime {
+        io_weight: Some(0),
+        ..m_runtime
+    };
+    assert!(bad.properties().is_err());
+}
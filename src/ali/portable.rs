@@ -0,0 +1,138 @@
+use crate::ali::{
+    Manifest,
+    PartitionTable,
+};
+use crate::errors::AliError;
+use crate::linux;
+
+/// Packages installed for portable targets: GRUB for both boot paths,
+/// and microcode for both CPU vendors instead of the current machine's
+pub const PACKAGES: [&str; 3] = ["grub", "intel-ucode", "amd-ucode"];
+
+const MSG: &str = "portable target";
+
+/// Boot layout of portable target, with both BIOS and UEFI boot paths
+#[derive(Debug, PartialEq)]
+pub struct PortableBoot {
+    /// Whole disk, e.g. `/dev/sdb`
+    pub disk: String,
+
+    /// Mountpoint of EFI system partition in new system, e.g. `/boot`
+    pub esp_dest: String,
+}
+
+/// Resolves boot layout of portable target from `manifest`.
+///
+/// Portable targets must have exactly 1 GPT disk, with both an EFI system
+/// partition (type `ef`) and a BIOS boot partition (type `ef02`),
+/// and the EFI system partition must be mounted via `mountpoints`.
+pub fn boot(manifest: &Manifest) -> Result<PortableBoot, AliError> {
+    if manifest.netroot.is_some() {
+        return Err(AliError::BadManifest(format!(
+            "{MSG}: netroot is not allowed"
+        )));
+    }
+
+    let disk = match manifest.disks.as_deref() {
+        Some([disk]) => disk,
+        _ => {
+            return Err(AliError::BadManifest(format!(
+                "{MSG}: expecting exactly 1 disk"
+            )));
+        }
+    };
+
+    if disk.table != PartitionTable::Gpt {
+        return Err(AliError::BadManifest(format!(
+            "{MSG}: expecting GPT partition table on {}",
+            disk.device
+        )));
+    }
+
+    if !disk.partitions.iter().any(|p| p.is_bios_boot()) {
+        return Err(AliError::BadManifest(format!(
+            "{MSG}: missing BIOS boot partition (type ef02) on {}",
+            disk.device
+        )));
+    }
+
+    let esp = match disk.partitions.iter().position(|p| p.is_esp()) {
+        Some(n) => linux::partition_name(&disk.device, n as u8 + 1),
+        None => {
+            return Err(AliError::BadManifest(format!(
+                "{MSG}: missing EFI system partition (type ef) on {}",
+                disk.device
+            )));
+        }
+    };
+
+    let esp_dest = manifest
+        .mountpoints
+        .iter()
+        .flatten()
+        .find(|m| m.device == esp)
+        .map(|m| m.dest.clone())
+        .ok_or(AliError::BadManifest(format!(
+            "{MSG}: EFI system partition {esp} is not mounted, e.g. at /boot"
+        )))?;
+
+    Ok(PortableBoot {
+        disk: disk.device.clone(),
+        esp_dest,
+    })
+}
+
+#[test]
+fn test_portable_boot() {
+    let yaml = r#"
+version: 1
+target: portable
+rootfs:
+  device: /dev/sdb3
+  fstype: ext4
+disks:
+  - device: /dev/sdb
+    table: gpt
+    partitions:
+      - label: bios
+        size: 1M
+        type: ef02
+      - label: esp
+        size: 300M
+        type: ef
+      - label: root
+        type: "83"
+fs:
+  - device: /dev/sdb2
+    fstype: vfat
+mountpoints:
+  - device: /dev/sdb2
+    dest: /boot
+"#;
+
+    let manifest = crate::ali::parse(yaml).unwrap();
+    assert!(manifest.is_portable());
+    assert_eq!(
+        PortableBoot {
+            disk: "/dev/sdb".to_string(),
+            esp_dest: "/boot".to_string(),
+        },
+        boot(&manifest).unwrap(),
+    );
+
+    let should_err = [
+        yaml.replace("table: gpt", "table: mbr"),
+        yaml.replace("type: ef02", "type: \"83\""),
+        yaml.replace("type: ef\n", "type: \"83\"\n"),
+        yaml.replace(
+            "device: /dev/sdb2\n    dest",
+            "device: /dev/sdb3\n    dest",
+        ),
+        format!("{yaml}netroot:\n  type: nfs\n"),
+    ];
+
+    for yaml in should_err {
+        let manifest = crate::ali::parse(&yaml).unwrap();
+        assert!(boot(&manifest).is_err(), "expecting error for {yaml}");
+    }
+}
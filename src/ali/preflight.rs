@@ -0,0 +1,409 @@
+use std::collections::HashSet;
+use std::net::{
+    TcpStream,
+    ToSocketAddrs,
+};
+use std::time::Duration;
+
+use crate::ali::{
+    DiskWipe,
+    Dm,
+    Manifest,
+    ManifestPartition,
+    PartitionTable,
+};
+use crate::errors::AliError;
+use crate::linux;
+use crate::types::stage::Stage;
+use crate::utils::fs::file_exists;
+use crate::utils::shell;
+
+const SYS_EFI: &str = "/sys/firmware/efi";
+const PROC_MOUNTS: &str = "/proc/mounts";
+const PROC_SWAPS: &str = "/proc/swaps";
+const MIRRORLIST: &str = "/etc/pacman.d/mirrorlist";
+const DBUS_SOCKET: &str = "/run/dbus/system_bus_socket";
+
+/// Number of mirrors from mirrorlist to try
+const MIRRORS_TRY: usize = 3;
+const MIRROR_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BootMode {
+    Uefi,
+    Bios,
+}
+
+impl std::fmt::Display for BootMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Uefi => write!(f, "UEFI"),
+            Self::Bios => write!(f, "BIOS"),
+        }
+    }
+}
+
+/// Checks the live system before touching anything, returning
+/// [`AliError::Preflight`] with all failures found.
+///
+/// Checks for stages in `skip` are skipped.
+pub fn preflight(
+    manifest: &Manifest,
+    skip: &HashSet<Stage>,
+) -> Result<(), AliError> {
+    let mut failures = Vec::new();
+
+    if !linux::user::is_root() {
+        failures.push("ali-rs must be run as root".to_string());
+    }
+
+    if !skip.contains(&Stage::Mountpoints) {
+        let firmware = match file_exists(SYS_EFI) {
+            true => BootMode::Uefi,
+            false => BootMode::Bios,
+        };
+
+        // Portable targets boot on both, regardless of live system
+        let expected = match manifest.is_portable() {
+            true => None,
+            false => expected_boot_mode(manifest),
+        };
+
+        if let Some(expected) = expected {
+            if expected != firmware {
+                failures.push(format!(
+                    "manifest partitions expect {expected} boot, but live system booted in {firmware} mode"
+                ));
+            }
+        }
+
+        let mounts = read_proc(PROC_MOUNTS) + &read_proc(PROC_SWAPS);
+        for disk in manifest.disks.iter().flatten() {
+            for dev in mounted_devices(&disk.device, &mounts) {
+                failures.push(format!(
+                    "target disk {} is in use: {dev} is mounted",
+                    disk.device
+                ));
+            }
+        }
+    }
+
+    for cmd in required_binaries(manifest, skip) {
+        if !shell::in_path(&cmd) {
+            failures.push(format!("command {cmd} not in path"));
+        }
+    }
+
+    if !skip.contains(&Stage::Bootstrap) {
+        let mirrorlist = read_proc(MIRRORLIST);
+        let mirrors = parse_mirrorlist(&mirrorlist);
+
+        if mirrors.is_empty() {
+            failures.push(format!("no mirrors found in {MIRRORLIST}"));
+        } else if !mirrors.iter().take(MIRRORS_TRY).any(|m| reachable(m)) {
+            failures.push(format!(
+                "none of the first {MIRRORS_TRY} mirrors in {MIRRORLIST} is reachable"
+            ));
+        }
+    }
+
+    let systemd = linux::systemd::is_booted();
+    let dbus = file_exists(DBUS_SOCKET);
+    for integration in unavailable_integrations(systemd, dbus) {
+        log::warn!("preflight: unavailable on live system: {integration}");
+    }
+
+    if !failures.is_empty() {
+        return Err(AliError::Preflight(failures));
+    }
+
+    Ok(())
+}
+
+/// Returns optional integrations unavailable on live systems without
+/// running systemd or dbus. No stage needs either, and only commands
+/// in manifest key `postinstall` may be affected.
+pub fn unavailable_integrations(
+    systemd: bool,
+    dbus: bool,
+) -> Vec<&'static str> {
+    let mut unavailable = Vec::new();
+
+    if !systemd {
+        unavailable.extend([
+            "sshd.service for --enable-ssh (sshd is started directly)",
+            "systemctl in postinstall commands",
+        ]);
+    }
+
+    if !dbus {
+        unavailable.push(
+            "hostnamectl, timedatectl, and localectl in postinstall commands",
+        );
+    }
+
+    unavailable
+}
+
+/// Infers boot mode from manifest partitions, since ALI has no bootloader key
+pub fn expected_boot_mode(manifest: &Manifest) -> Option<BootMode> {
+    let disks = manifest.disks.as_ref()?;
+    let mut partitions = disks.iter().flat_map(|d| d.partitions.iter());
+
+    if partitions.clone().any(ManifestPartition::is_esp) {
+        return Some(BootMode::Uefi);
+    }
+
+    if partitions.any(ManifestPartition::is_bios_boot)
+        || disks.iter().all(|d| d.table == PartitionTable::Mbr)
+    {
+        return Some(BootMode::Bios);
+    }
+
+    None
+}
+
+/// Returns mounted or swapped-on devices on `disk`,
+/// from contents of /proc/mounts or /proc/swaps
+fn mounted_devices<'a>(disk: &str, mounts: &'a str) -> Vec<&'a str> {
+    mounts
+        .lines()
+        .filter_map(|line| line.split_whitespace().next())
+        .filter(|dev| {
+            let Some(rest) = dev.strip_prefix(disk) else {
+                return false;
+            };
+
+            let rest = rest.strip_prefix('p').unwrap_or(rest);
+            rest.chars().all(|c| c.is_ascii_digit())
+        })
+        .collect()
+}
+
+/// Returns binaries needed by stages not in `skip`
+fn required_binaries(
+    manifest: &Manifest,
+    skip: &HashSet<Stage>,
+) -> Vec<String> {
+    let mut cmds = Vec::new();
+
+    if !skip.contains(&Stage::Mountpoints) {
+        if manifest.disks.is_some() {
+            cmds.push("fdisk".to_string());
+        }
+
+        let wipes: Vec<DiskWipe> =
+            manifest.disks.iter().flatten().filter_map(|d| d.wipe).collect();
+
+        if wipes.contains(&DiskWipe::Full) {
+            cmds.extend(["wipefs", "blkdiscard", "dd"].map(String::from));
+        } else if wipes.contains(&DiskWipe::Signatures) {
+            cmds.push("wipefs".to_string());
+        }
+
+        for dm in manifest.device_mappers.iter().flatten() {
+            match dm {
+                Dm::Luks(_) => cmds.push("cryptsetup".to_string()),
+                Dm::Lvm(_) => {
+                    cmds.extend(
+                        ["pvcreate", "vgcreate", "lvcreate"].map(String::from),
+                    )
+                }
+            }
+        }
+
+        if manifest.netroot.is_none() {
+            cmds.push(format!("mkfs.{}", manifest.rootfs.fs_type));
+        }
+
+        for fs in manifest.filesystems.iter().flatten() {
+            cmds.push(format!("mkfs.{}", fs.fs_type));
+        }
+
+        if manifest.swap.is_some() {
+            cmds.push("mkswap".to_string());
+        }
+    }
+
+    if !skip.contains(&Stage::Bootstrap) {
+        cmds.push("pacstrap".to_string());
+    }
+
+    if !skip.contains(&Stage::Routines) && manifest.netroot.is_none() {
+        cmds.push("genfstab".to_string());
+    }
+
+    if [Stage::Routines, Stage::ChrootAli, Stage::ChrootUser]
+        .iter()
+        .any(|s| !skip.contains(s))
+    {
+        cmds.push("arch-chroot".to_string());
+    }
+
+    for provider in manifest.secrets.iter().flatten() {
+        cmds.extend(provider.program().map(String::from));
+    }
+
+    let mut seen = HashSet::new();
+    cmds.retain(|cmd| seen.insert(cmd.clone()));
+
+    cmds
+}
+
+/// Returns mirror URLs from pacman mirrorlist
+fn parse_mirrorlist(mirrorlist: &str) -> Vec<String> {
+    mirrorlist
+        .lines()
+        .filter_map(|line| {
+            let (k, v) = line.trim().split_once('=')?;
+            match k.trim() {
+                "Server" => Some(v.trim().to_string()),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// Tries TCP connection to mirror host
+fn reachable(mirror: &str) -> bool {
+    let Some((scheme, rest)) = mirror.split_once("://") else {
+        return false;
+    };
+
+    let host = rest.split('/').next().unwrap_or_default();
+    let addr = match (host.contains(':'), scheme) {
+        (true, _) => host.to_string(),
+        (false, "http") => format!("{host}:80"),
+        (false, _) => format!("{host}:443"),
+    };
+
+    let Ok(addrs) = addr.to_socket_addrs() else {
+        return false;
+    };
+
+    addrs
+        .into_iter()
+        .any(|a| TcpStream::connect_timeout(&a, MIRROR_TIMEOUT).is_ok())
+}
+
+fn read_proc(path: &str) -> String {
+    std::fs::read_to_string(path).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MANIFEST: &str = r#"
+version: 1
+rootfs:
+  device: /dev/vda2
+  fstype: btrfs
+disks:
+  - device: /dev/vda
+    table: gpt
+    partitions:
+      - label: boot
+        size: 300M
+        type: ef
+      - label: root
+        type: 8e
+dm:
+  - type: luks
+    device: /dev/vda2
+    name: cryptroot
+fs:
+  - device: /dev/vda1
+    fstype: vfat
+"#;
+
+    #[test]
+    fn test_expected_boot_mode() {
+        let manifest = crate::ali::parse(MANIFEST).unwrap();
+        assert_eq!(Some(BootMode::Uefi), expected_boot_mode(&manifest));
+
+        let manifest = crate::ali::parse(
+            &MANIFEST
+                .replace("gpt", "mbr")
+                .replace("type: ef\n", "type: \"83\"\n"),
+        )
+        .unwrap();
+        assert_eq!(Some(BootMode::Bios), expected_boot_mode(&manifest));
+
+        let manifest =
+            crate::ali::parse(&MANIFEST.replace("type: ef\n", "type: ef02\n"))
+                .unwrap();
+        assert_eq!(Some(BootMode::Bios), expected_boot_mode(&manifest));
+
+        let manifest = crate::ali::parse(
+            &MANIFEST.replace("type: ef\n", "type: \"83\"\n"),
+        )
+        .unwrap();
+        assert_eq!(None, expected_boot_mode(&manifest));
+    }
+
+    #[test]
+    fn test_mounted_devices() {
+        let mounts = "\
+/dev/sda1 /boot vfat rw 0 0
+/dev/sdab2 /mnt ext4 rw 0 0
+/dev/nvme0n1p2 / btrfs rw 0 0
+Filename Type Size Used Priority
+/dev/sda3 partition 8388604 0 -2
+";
+
+        assert_eq!(
+            vec!["/dev/sda1", "/dev/sda3"],
+            mounted_devices("/dev/sda", mounts)
+        );
+        assert_eq!(
+            vec!["/dev/nvme0n1p2"],
+            mounted_devices("/dev/nvme0n1", mounts)
+        );
+        assert!(mounted_devices("/dev/vda", mounts).is_empty());
+    }
+
+    #[test]
+    fn test_required_binaries() {
+        let manifest = crate::ali::parse(MANIFEST).unwrap();
+        let cmds = required_binaries(&manifest, &HashSet::new());
+        for cmd in
+            ["fdisk", "cryptsetup", "mkfs.btrfs", "mkfs.vfat", "pacstrap"]
+        {
+            assert!(cmds.contains(&cmd.to_string()), "missing {cmd}");
+        }
+
+        let skip = HashSet::from([Stage::Mountpoints, Stage::Bootstrap]);
+        let cmds = required_binaries(&manifest, &skip);
+        assert!(!cmds.contains(&"fdisk".to_string()));
+        assert!(!cmds.contains(&"pacstrap".to_string()));
+    }
+
+    #[test]
+    fn test_unavailable_integrations() {
+        assert!(unavailable_integrations(true, true).is_empty());
+        assert_eq!(1, unavailable_integrations(true, false).len());
+        assert!(unavailable_integrations(false, true)
+            .iter()
+            .any(|i| i.contains("sshd")));
+        assert_eq!(3, unavailable_integrations(false, false).len());
+    }
+
+    #[test]
+    fn test_parse_mirrorlist() {
+        let mirrorlist = "\
+## Worldwide
+#Server = https://commented.example.com/$repo/os/$arch
+Server = https://geo.mirror.pkgbuild.com/$repo/os/$arch
+Server=http://mirror.example.com/archlinux/$repo/os/$arch
+";
+
+        assert_eq!(
+            vec![
+                "https://geo.mirror.pkgbuild.com/$repo/os/$arch",
+                "http://mirror.example.com/archlinux/$repo/os/$arch",
+            ],
+            parse_mirrorlist(mirrorlist),
+        );
+    }
+}
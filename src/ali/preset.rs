@@ -0,0 +1,265 @@
+use std::collections::HashSet;
+
+use crate::ali::{
+    migrate,
+    Dm,
+    Manifest,
+    ManifestDisk,
+    ManifestFs,
+    ManifestLvm,
+    ManifestLvmLv,
+    ManifestLvmVg,
+    ManifestMountpoint,
+    ManifestPartition,
+    ManifestRootFs,
+    PartitionTable,
+};
+use crate::linux;
+
+const VG: &str = "archvg";
+const ESP_SIZE: &str = "512M";
+
+/// Partitioning preset of generated manifests
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Layout {
+    /// Root (and swap) on plain partitions
+    Simple,
+
+    /// Root (and swap) on LVM logical volumes
+    Lvm,
+}
+
+/// Answers collected by interactive installer, from which manifest
+/// is generated
+#[derive(Debug, Clone, PartialEq)]
+pub struct Answers {
+    pub disk: String,
+    pub uefi: bool,
+    pub layout: Layout,
+    pub fs_type: String,
+    /// Swap size, e.g. `4G`
+    pub swap: Option<String>,
+    pub hostname: String,
+    pub timezone: String,
+    /// Hashed root password
+    pub rootpasswd: Option<String>,
+    /// Name and hashed password of user in group `wheel`
+    pub user: Option<(String, String)>,
+    pub packages: Vec<String>,
+}
+
+/// Generates manifest installing to single disk from `answers`,
+/// with GRUB as bootloader
+pub fn manifest(answers: &Answers) -> Manifest {
+    let disk = &answers.disk;
+    let part_type_root = match answers.layout {
+        Layout::Simple => "83",
+        Layout::Lvm => "8e",
+    };
+
+    let mut partitions = Vec::new();
+    if answers.uefi {
+        partitions.push(partition("esp", Some(ESP_SIZE), "ef"));
+    }
+
+    // Swap partition before root, which takes the rest of the disk
+    let swap_size = answers.swap.as_deref();
+    if let (Layout::Simple, Some(size)) = (answers.layout, swap_size) {
+        partitions.push(partition("swap", Some(size), "82"));
+    }
+
+    partitions.push(partition("root", None, part_type_root));
+
+    let part = |n: usize| linux::partition_name(disk, n as u8);
+    let part_root = part(partitions.len());
+
+    let (root, swap, device_mappers) = match answers.layout {
+        Layout::Simple => {
+            let swap = swap_size.map(|_| part(partitions.len() - 1));
+            (part_root, swap, None)
+        }
+        Layout::Lvm => {
+            let mut lvs = Vec::new();
+            if let Some(size) = swap_size {
+                lvs.push(lv("swaplv", Some(size)));
+            }
+            lvs.push(lv("rootlv", None));
+
+            let lvm = ManifestLvm {
+                pvs: Some(vec![part_root.clone()]),
+                vgs: Some(vec![ManifestLvmVg {
+                    name: VG.to_string(),
+                    pvs: vec![part_root],
+                }]),
+                lvs: Some(lvs),
+            };
+
+            (
+                format!("/dev/{VG}/rootlv"),
+                swap_size.map(|_| format!("/dev/{VG}/swaplv")),
+                Some(vec![Dm::Lvm(lvm)]),
+            )
+        }
+    };
+
+    let (filesystems, mountpoints) = match answers.uefi {
+        true => {
+            let esp = part(1);
+            (
+                Some(vec![ManifestFs {
+                    device: esp.clone(),
+                    fs_type: "vfat".to_string(),
+                    fs_opts: Some("-F 32".to_string()),
+                }]),
+                Some(vec![ManifestMountpoint {
+                    device: esp,
+                    dest: "/boot".to_string(),
+                    mnt_opts: None,
+                }]),
+            )
+        }
+        false => (None, None),
+    };
+
+    let mut packages: HashSet<String> =
+        ["linux", "linux-firmware", "grub"].map(String::from).into();
+    if answers.uefi {
+        packages.insert("efibootmgr".to_string());
+    }
+    packages.extend(answers.packages.iter().cloned());
+
+    Manifest {
+        version: migrate::CURRENT_VERSION,
+        location: None,
+        hostname: Some(answers.hostname.clone()),
+        timezone: Some(answers.timezone.clone()),
+        rootfs: ManifestRootFs {
+            device: root,
+            fs_type: answers.fs_type.clone(),
+            fs_opts: None,
+            mnt_opts: None,
+        },
+        disks: Some(vec![ManifestDisk {
+            device: disk.clone(),
+            table: match answers.uefi {
+                true => PartitionTable::Gpt,
+                false => PartitionTable::Mbr,
+            },
+            partitions,
+            wipe: None,
+        }]),
+        device_mappers,
+        filesystems,
+        mountpoints,
+        swap: swap.map(|s| vec![s]),
+        pacstraps: Some(packages),
+        rootpasswd: answers.rootpasswd.clone(),
+        chroot: Some(chroot(answers)),
+        postinstall: None,
+        proxy: None,
+        netroot: None,
+        target: None,
+        fsck: None,
+        maintenance: None,
+        reports: None,
+        hook_groups: None,
+        ssh: None,
+        runtime: None,
+        skel: None,
+        domain_join: None,
+        ca_certs: None,
+        secrets: None,
+        time: None,
+        protected_paths: None,
+        extra_targets: None,
+        locale: None,
+        cmdline: None,
+    }
+}
+
+/// Commands run in chroot: initramfs for LVM root, GRUB, and user
+fn chroot(answers: &Answers) -> Vec<String> {
+    let mut cmds = Vec::new();
+    if answers.layout == Layout::Lvm {
+        // Also regenerates initramfs
+        cmds.push("@mkinitcpio boot_hook=root-lvm".to_string());
+    }
+
+    cmds.push(match answers.uefi {
+        true => {
+            "grub-install --target=x86_64-efi --efi-directory=/boot --bootloader-id=GRUB".to_string()
+        }
+        false => format!("grub-install --target=i386-pc {}", answers.disk),
+    });
+    cmds.push("grub-mkconfig -o /boot/grub/grub.cfg".to_string());
+
+    if let Some((name, hashed)) = &answers.user {
+        cmds.push(format!("useradd -m -G wheel -p '{hashed}' {name}"));
+    }
+
+    cmds
+}
+
+fn partition(
+    label: &str,
+    size: Option<&str>,
+    part_type: &str,
+) -> ManifestPartition {
+    ManifestPartition {
+        label: label.to_string(),
+        size: size.map(String::from),
+        part_type: part_type.to_string(),
+    }
+}
+
+fn lv(name: &str, size: Option<&str>) -> ManifestLvmLv {
+    ManifestLvmLv {
+        name: name.to_string(),
+        vg: VG.to_string(),
+        size: size.map(String::from),
+    }
+}
+
+#[test]
+fn test_preset_manifest() {
+    let answers = Answers {
+        disk: "/dev/nvme0n1".to_string(),
+        uefi: true,
+        layout: Layout::Lvm,
+        fs_type: "ext4".to_string(),
+        swap: Some("4G".to_string()),
+        hostname: "myarch".to_string(),
+        timezone: "Asia/Bangkok".to_string(),
+        rootpasswd: None,
+        user: Some(("alice".to_string(), "$2b$hash".to_string())),
+        packages: vec!["vim".to_string()],
+    };
+
+    let m = manifest(&answers);
+    assert_eq!("/dev/archvg/rootlv", m.rootfs.device);
+    assert_eq!(Some(vec!["/dev/archvg/swaplv".to_string()]), m.swap);
+    assert_eq!("/dev/nvme0n1p1", m.mountpoints.as_ref().unwrap()[0].device);
+
+    // Generated YAML is parsed back to the same manifest
+    let yaml = crate::ali::to_yaml(&m).unwrap();
+    assert!(!yaml.contains("null"));
+    assert_eq!(m, crate::ali::parse(&yaml).unwrap());
+
+    let answers = Answers {
+        disk: "/dev/sda".to_string(),
+        uefi: false,
+        layout: Layout::Simple,
+        ..answers
+    };
+
+    let m = manifest(&answers);
+    let disk = &m.disks.as_ref().unwrap()[0];
+    assert_eq!(PartitionTable::Mbr, disk.table);
+    assert_eq!(2, disk.partitions.len());
+    assert_eq!("/dev/sda2", m.rootfs.device);
+    assert_eq!(Some(vec!["/dev/sda1".to_string()]), m.swap);
+    assert!(m
+        .chroot
+        .unwrap()
+        .contains(&"grub-install --target=i386-pc /dev/sda".to_string()));
+}
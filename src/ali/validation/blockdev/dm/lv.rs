@@ -201,7 +201,7 @@ mod tests {
     fn test_validate_size() {
         use crate::ali::ManifestLvmVg;
 
-        let should_ok = vec![
+        let should_ok = [
             TestValidateSize {
                 dms: vec![Dm::Lvm(ali::ManifestLvm {
                     pvs: None,
@@ -292,7 +292,7 @@ mod tests {
             },
         ];
 
-        let should_err = vec![
+        let should_err = [
             TestValidateSize {
                 dms: vec![Dm::Lvm(ali::ManifestLvm {
                     pvs: None,
@@ -410,12 +410,12 @@ mod tests {
             },
         ];
 
-        for (_i, t) in should_ok.iter().enumerate() {
+        for t in should_ok.iter() {
             let result = validate_size(&t.dms);
             assert!(result.is_ok());
         }
 
-        for (_i, t) in should_err.iter().enumerate() {
+        for t in should_err.iter() {
             let result = validate_size(&t.dms);
             assert!(result.is_err());
         }
@@ -423,7 +423,7 @@ mod tests {
 
     #[test]
     fn test_collect_from_sys() {
-        let should_ok = vec![
+        let should_ok = [
             // 1
             TestCollectFromSys {
                 vg: BlockDev {
@@ -635,7 +635,7 @@ mod tests {
             },
         ];
 
-        for (_i, t) in should_ok.iter().enumerate() {
+        for t in should_ok.iter() {
             let result = collect_from_sys(&t.vg, &t.lv, &t.sys_lvms);
 
             let mut count = 0;
@@ -654,7 +654,7 @@ mod tests {
 
     #[test]
     fn test_collect_valid() {
-        let should_ok = vec![
+        let should_ok = [
             // 1
             TestCollectFromValidLv {
                 vg: BlockDev {
@@ -782,7 +782,7 @@ mod tests {
             },
         ];
 
-        for (_i, t) in should_ok.iter().enumerate() {
+        for t in should_ok.iter() {
             let result = collect_from_valids(&t.vg, &t.lv, &t.valids);
 
             let mut count = 0;
@@ -801,7 +801,7 @@ mod tests {
 
     #[test]
     fn test_collect_lv() {
-        let mut should_ok = vec![
+        let mut should_ok = [
             // 1
             TestCollectValidLv {
                 lv: ManifestLvmLv {
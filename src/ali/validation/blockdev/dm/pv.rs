@@ -115,7 +115,7 @@ mod tests {
 
     #[test]
     fn test_collect_valid() {
-        let mut should_ok = vec![
+        let mut should_ok = [
             //
             TestCollectValidPv {
                 pv: "/dev/fda2".into(),
@@ -302,7 +302,7 @@ mod tests {
             },
         ];
 
-        let mut should_err = vec![
+        let mut should_err = [
             //
             TestCollectValidPv {
                 pv: "/dev/fda2".into(),
@@ -120,7 +120,7 @@ mod tests {
 
     #[test]
     fn test_collect_valid() {
-        let mut should_ok = vec![
+        let mut should_ok = [
             //
             TestCollectValidVg {
                 vg: ManifestLvmVg {
@@ -433,7 +433,7 @@ mod tests {
             },
         ];
 
-        let mut should_err = vec![
+        let mut should_err = [
             //
             TestCollectValidVg {
                 vg: ManifestLvmVg {
@@ -623,7 +623,7 @@ mod tests {
             },
         ];
 
-        for (_i, t) in should_ok.iter_mut().enumerate() {
+        for t in should_ok.iter_mut() {
             let result = collect_valid(
                 &t.vg,
                 &t.sys_fs_devs,
@@ -651,7 +651,7 @@ mod tests {
             assert_eq!(0, diff.len());
         }
 
-        for (_i, t) in should_err.iter_mut().enumerate() {
+        for t in should_err.iter_mut() {
             let result = collect_valid(
                 &t.vg,
                 &t.sys_fs_devs,
@@ -267,6 +267,7 @@ mod tests {
                 ManifestDisk {
                     device: "./test_assets/mock_devs/sda".into(),
                     table: PartitionTable::Gpt,
+                    wipe: None,
                     partitions: vec![
                         //
                         ManifestPartition {
@@ -312,7 +313,7 @@ mod tests {
             let result = collect_valids(
                 &Some(t.manifest_disks),
                 &Some(t.manifest_dms),
-                &mut t.sys_fs_devs.clone(),
+                &t.sys_fs_devs.clone(),
                 &mut t.sys_fs_ready_devs.clone(),
                 &mut t.sys_lvms.clone(),
             );
@@ -361,24 +362,15 @@ mod tests {
                 sys_lvms: None,
 
                 manifest: Manifest {
-                    location: None,
-                    disks: None,
-                    device_mappers: None,
+                    version: 1,
                     rootfs: ManifestRootFs{
                         device: "/dev/fda1".into(),
                         fs_type: "btrfs".into(),
                         fs_opts: None,
                         mnt_opts: None,
                     },
-                    filesystems: None,
-                    mountpoints: None,
                     swap: Some(vec!["/dev/fake1p2".into()]),
-                    pacstraps: None,
-                    chroot: None,
-                    postinstall: None,
-                    hostname: None,
-                    timezone: None,
-                    rootpasswd: None,
+                    ..Default::default()
                 },
             },
 
@@ -394,24 +386,14 @@ mod tests {
                 sys_lvms: None,
 
                 manifest: Manifest {
-                    location: None,
-                    disks: None,
-                    device_mappers: None,
+                    version: 1,
                     rootfs: ManifestRootFs{
                         device: "/dev/fake1p1".into(),
                         fs_type: "btrfs".into(),
                         fs_opts: None,
                         mnt_opts: None,
                     },
-                    filesystems: None,
-                    mountpoints: None,
-                    swap: None,
-                    pacstraps: None,
-                    chroot: None,
-                    postinstall: None,
-                    hostname: None,
-                    timezone: None,
-                    rootpasswd: None,
+                    ..Default::default()
                 },
             },
 
@@ -442,24 +424,15 @@ mod tests {
                 )])),
 
                 manifest: Manifest {
-                    location: None,
-                    disks: None,
-                    device_mappers: None,
+                    version: 1,
                     rootfs: ManifestRootFs{
                         device: "/dev/myvg/mylv".into(),
                         fs_type: "btrfs".into(),
                         fs_opts: None,
                         mnt_opts: None,
                     },
-                    filesystems: None,
-                    mountpoints: None,
                     swap: Some(vec!["/dev/fake1p2".into()]),
-                    pacstraps: None,
-                    chroot: None,
-                    postinstall: None,
-                    hostname: None,
-                    timezone: None,
-                    rootpasswd: None,
+                    ..Default::default()
                 },
             },
 
@@ -490,8 +463,7 @@ mod tests {
                 )])),
 
                 manifest: Manifest {
-                    location: None,
-                    disks: None,
+                    version: 1,
                     device_mappers: Some(vec![
                         Dm::Luks(ManifestLuks {
                             device: "/dev/fake1p2".into(),
@@ -505,15 +477,8 @@ mod tests {
                         fs_opts: None,
                         mnt_opts: None,
                     },
-                    filesystems: None,
-                    mountpoints: None,
                     swap: Some(vec!["/dev/myvg/mylv".into()]),
-                    pacstraps: None,
-                    chroot: None,
-                    postinstall: None,
-                    hostname: None,
-                    timezone: None,
-                    rootpasswd: None,
+                    ..Default::default()
                 },
             },
 
@@ -544,8 +509,7 @@ mod tests {
                 )])),
 
                 manifest: Manifest {
-                    location: None,
-                    disks: None,
+                    version: 1,
                     device_mappers: Some(vec![
                         Dm::Luks(ManifestLuks {
                             device: "/dev/fake1p2".into(),
@@ -570,15 +534,8 @@ mod tests {
                         fs_opts: None,
                         mnt_opts: None,
                     },
-                    filesystems: None,
-                    mountpoints: None,
                     swap: Some(vec!["/dev/myvg/mylv".into()]),
-                    pacstraps: None,
-                    chroot: None,
-                    postinstall: None,
-                    hostname: None,
-                    timezone: None,
-                    rootpasswd: None,
+                    ..Default::default()
                 },
             },
 
@@ -609,8 +566,7 @@ mod tests {
                 )])),
 
                 manifest: Manifest {
-                    location: None,
-                    disks: None,
+                    version: 1,
                     device_mappers: Some(vec![
                         Dm::Luks(ManifestLuks {
                             device: "/dev/fake1p2".into(),
@@ -638,13 +594,7 @@ mod tests {
                             mnt_opts: None,
                         },
                     ]),
-                    swap: None,
-                    pacstraps: None,
-                    chroot: None,
-                    postinstall: None,
-                    hostname: None,
-                    timezone: None,
-                    rootpasswd: None,
+                    ..Default::default()
                 },
             },
 
@@ -691,8 +641,7 @@ mod tests {
                 )])),
 
                 manifest: Manifest {
-                    location: None,
-                    disks: None,
+                    version: 1,
                     device_mappers: Some(vec![
                         Dm::Luks(ManifestLuks {
                             device: "/dev/myvg/mylv".into(),
@@ -711,15 +660,8 @@ mod tests {
                         fs_opts: None,
                         mnt_opts: None,
                     },
-                    filesystems: None,
-                    mountpoints: None,
                     swap: Some(vec!["/dev/mapper/cryptswap".into()]),
-                    pacstraps: None,
-                    chroot: None,
-                    postinstall: None,
-                    hostname: None,
-                    timezone: None,
-                    rootpasswd: None,
+                    ..Default::default()
                 },
             },
 
@@ -748,8 +690,7 @@ mod tests {
                 )])),
 
                 manifest: Manifest {
-                    location: None,
-                    disks: None,
+                    version: 1,
                     device_mappers: Some(vec![
                         Dm::Lvm(ManifestLvm {
                             pvs: Some(vec![
@@ -785,15 +726,8 @@ mod tests {
                         fs_opts: None,
                         mnt_opts: None,
                     },
-                    filesystems: None,
-                    mountpoints: None,
                     swap: Some(vec!["/dev/mapper/cryptswap".into()]),
-                    pacstraps: None,
-                    chroot: None,
-                    postinstall: None,
-                    hostname: None,
-                    timezone: None,
-                    rootpasswd: None,
+                    ..Default::default()
                 },
             },
 
@@ -824,24 +758,15 @@ mod tests {
                 )])),
 
                 manifest: Manifest {
-                    location: None,
-                    disks: None,
-                    device_mappers: None,
+                    version: 1,
                     rootfs: ManifestRootFs{
                         device: "/dev/myvg/mylv".into(),
                         fs_type: "btrfs".into(),
                         fs_opts: None,
                         mnt_opts: None,
                     },
-                    filesystems: None,
-                    mountpoints: None,
                     swap: Some(vec!["/dev/fake1p2".into()]),
-                    pacstraps: None,
-                    chroot: None,
-                    postinstall: None,
-                    hostname: None,
-                    timezone: None,
-                    rootpasswd: None,
+                    ..Default::default()
                 },
             },
 
@@ -868,8 +793,7 @@ mod tests {
                 )])),
 
                 manifest: Manifest {
-                    location: None,
-                    disks: None,
+                    version: 1,
                     device_mappers: Some(vec![Dm::Lvm(ManifestLvm {
                         pvs: None,
                         vgs: None,
@@ -885,15 +809,8 @@ mod tests {
                         fs_opts: None,
                         mnt_opts: None,
                     },
-                    filesystems: None,
-                    mountpoints: None,
                     swap: Some(vec!["/dev/fake1p2".into()]),
-                    pacstraps: None,
-                    chroot: None,
-                    postinstall: None,
-                    hostname: None,
-                    timezone: None,
-                    rootpasswd: None,
+                    ..Default::default()
                 },
             },
 
@@ -908,8 +825,7 @@ mod tests {
                 sys_lvms: None,
 
                 manifest: Manifest {
-                    location: None,
-                    disks: None,
+                    version: 1,
                     device_mappers: Some(vec![Dm::Lvm(ManifestLvm {
                         pvs: Some(vec!["/dev/fda1".into()]),
                         vgs: Some(vec![ManifestLvmVg {
@@ -928,15 +844,8 @@ mod tests {
                         fs_opts: None,
                         mnt_opts: None,
                     },
-                    filesystems: None,
-                    mountpoints: None,
                     swap: Some(vec!["/dev/fake1p2".into()]),
-                    pacstraps: None,
-                    chroot: None,
-                    postinstall: None,
-                    hostname: None,
-                    timezone: None,
-                    rootpasswd: None,
+                    ..Default::default()
                 },
             },
 
@@ -950,10 +859,11 @@ mod tests {
                 sys_lvms: None,
 
                 manifest: Manifest {
-                    location: None,
+                    version: 1,
                     disks: Some(vec![ManifestDisk {
                         device: "./test_assets/mock_devs/sda".into(),
                         table: PartitionTable::Gpt,
+                        wipe: None,
                         partitions: vec![
                             ManifestPartition {
                                 label: "PART_EFI".into(),
@@ -985,15 +895,8 @@ mod tests {
                         fs_opts: None,
                         mnt_opts: None,
                     },
-                    filesystems: None,
-                    mountpoints: None,
                     swap: Some(vec!["/dev/fake1p2".into()]),
-                    pacstraps: None,
-                    chroot: None,
-                    postinstall: None,
-                    hostname: None,
-                    timezone: None,
-                    rootpasswd: None,
+                    ..Default::default()
                 },
             },
 
@@ -1008,11 +911,12 @@ mod tests {
                 sys_lvms: None,
 
                 manifest: Manifest {
-                    location: None,
+                    version: 1,
                     disks: Some(vec![
                         ManifestDisk {
                             device: "./test_assets/mock_devs/sda".into(),
                             table: PartitionTable::Gpt,
+                            wipe: None,
                             partitions: vec![
                                 ManifestPartition {
                                     label: "PART_EFI".into(),
@@ -1051,15 +955,8 @@ mod tests {
                         fs_opts: None,
                         mnt_opts:None,
                     },
-                    filesystems: None,
-                    mountpoints: None,
                     swap: Some(vec!["/dev/fake1p2".into()]),
-                    pacstraps: None,
-                    chroot: None,
-                    postinstall: None,
-                    hostname: None,
-                    timezone: None,
-                    rootpasswd: None,
+                    ..Default::default()
                 },
             },
 
@@ -1074,11 +971,12 @@ mod tests {
                 sys_lvms: None,
 
                 manifest: Manifest {
-                    location: None,
+                    version: 1,
                     disks: Some(vec![
                         ManifestDisk {
                             device: "./test_assets/mock_devs/sda".into(),
                             table: PartitionTable::Gpt,
+                            wipe: None,
                             partitions: vec![
                                 ManifestPartition {
                                     label: "PART_EFI".into(),
@@ -1095,6 +993,7 @@ mod tests {
                         ManifestDisk {
                             device: "./test_assets/mock_devs/sdb".into(),
                             table: PartitionTable::Mbr,
+                            wipe: None,
                             partitions: vec![
                                 ManifestPartition {
                                     label: "PART_PV2".into(),
@@ -1130,15 +1029,8 @@ mod tests {
                         fs_opts: None,
                         mnt_opts: None,
                     },
-                    filesystems: None,
-                    mountpoints: None,
                     swap: Some(vec!["/dev/fake1p1".into()]),
-                    pacstraps: None,
-                    chroot: None,
-                    postinstall: None,
-                    hostname: None,
-                    timezone: None,
-                    rootpasswd: None,
+                    ..Default::default()
                 },
             },
 
@@ -1153,11 +1045,12 @@ mod tests {
                 sys_lvms: None,
 
                 manifest: Manifest {
-                    location: None,
+                    version: 1,
                     disks: Some(vec![
                         ManifestDisk {
                             device: "./test_assets/mock_devs/sda".into(),
                             table: PartitionTable::Gpt,
+                            wipe: None,
                             partitions: vec![
                                 ManifestPartition {
                                     label: "PART_EFI".into(),
@@ -1174,6 +1067,7 @@ mod tests {
                         ManifestDisk {
                             device: "./test_assets/mock_devs/sdb".into(),
                             table: PartitionTable::Mbr,
+                            wipe: None,
                             partitions: vec![
                                 ManifestPartition {
                                     label: "PART_PV2".into(),
@@ -1216,15 +1110,8 @@ mod tests {
                         fs_opts: None,
                         mnt_opts: None,
                     },
-                    filesystems: None,
-                    mountpoints: None,
                     swap: Some(vec!["/dev/myvg/myswap".into()]),
-                    pacstraps: None,
-                    chroot: None,
-                    postinstall: None,
-                    hostname: None,
-                    timezone: None,
-                    rootpasswd: None,
+                    ..Default::default()
                 },
             },
 
@@ -1239,11 +1126,12 @@ mod tests {
                 sys_lvms: None,
 
                 manifest: Manifest {
-                    location: None,
+                    version: 1,
                     disks: Some(vec![
                         ManifestDisk {
                             device: "./test_assets/mock_devs/sda".into(),
                             table: PartitionTable::Gpt,
+                            wipe: None,
                             partitions: vec![
                                 ManifestPartition {
                                     label: "PART_EFI".into(),
@@ -1260,6 +1148,7 @@ mod tests {
                         ManifestDisk {
                             device: "./test_assets/mock_devs/sdb".into(),
                             table: PartitionTable::Mbr,
+                            wipe: None,
                             partitions: vec![
                                 ManifestPartition {
                                     label: "PART_PV2".into(),
@@ -1307,15 +1196,8 @@ mod tests {
                         fs_opts: None,
                         mnt_opts: None,
                     },
-                    filesystems: None,
-                    mountpoints: None,
                     swap: Some(vec!["/dev/myvg/myswap".into()]),
-                    pacstraps: None,
-                    chroot: None,
-                    postinstall: None,
-                    hostname: None,
-                    timezone: None,
-                    rootpasswd: None,
+                    ..Default::default()
                 },
             },
 
@@ -1330,11 +1212,12 @@ mod tests {
                 sys_lvms: None,
 
                 manifest: Manifest {
-                    location: None,
+                    version: 1,
                     disks: Some(vec![
                         ManifestDisk {
                             device: "./test_assets/mock_devs/sda".into(),
                             table: PartitionTable::Gpt,
+                            wipe: None,
                             partitions: vec![
                                 ManifestPartition {
                                     label: "PART_EFI".into(),
@@ -1351,6 +1234,7 @@ mod tests {
                         ManifestDisk {
                             device: "./test_assets/mock_devs/sdb".into(),
                             table: PartitionTable::Mbr,
+                            wipe: None,
                             partitions: vec![
                                 ManifestPartition {
                                     label: "PART_PV2".into(),
@@ -1413,12 +1297,7 @@ mod tests {
                         },
                     ]),
                     swap: Some(vec!["/dev/myvg/myswap".into()]),
-                    pacstraps: None,
-                    chroot: None,
-                    postinstall: None,
-                    hostname: None,
-                    timezone: None,
-                    rootpasswd: None,
+                    ..Default::default()
                 },
             },
 
@@ -1433,11 +1312,12 @@ mod tests {
                 sys_lvms: None,
 
                 manifest: Manifest {
-                    location: None,
+                    version: 1,
                     disks: Some(vec![
                         ManifestDisk {
                             device: "./test_assets/mock_devs/sda".into(),
                             table: PartitionTable::Gpt,
+                            wipe: None,
                             partitions: vec![
                                 ManifestPartition {
                                     label: "PART_EFI".into(),
@@ -1454,6 +1334,7 @@ mod tests {
                         ManifestDisk {
                             device: "./test_assets/mock_devs/sdb".into(),
                             table: PartitionTable::Mbr,
+                            wipe: None,
                             partitions: vec![
                                 ManifestPartition {
                                     label: "PART_PV2".into(),
@@ -1526,12 +1407,7 @@ mod tests {
                         },
                     ]),
                     swap: Some(vec!["/dev/myvg/myswap".into()]),
-                    pacstraps: None,
-                    chroot: None,
-                    postinstall: None,
-                    hostname: None,
-                    timezone: None,
-                    rootpasswd: None,
+                    ..Default::default()
                 },
             },
 
@@ -1546,11 +1422,12 @@ mod tests {
                 sys_lvms: None,
 
                 manifest: Manifest {
-                    location: None,
+                    version: 1,
                     disks: Some(vec![
                         ManifestDisk {
                             device: "./test_assets/mock_devs/sda".into(),
                             table: PartitionTable::Gpt,
+                            wipe: None,
                             partitions: vec![
                                 ManifestPartition {
                                     label: "PART_EFI".into(),
@@ -1567,6 +1444,7 @@ mod tests {
                         ManifestDisk {
                             device: "./test_assets/mock_devs/sdb".into(),
                             table: PartitionTable::Mbr,
+                            wipe: None,
                             partitions: vec![
                                 ManifestPartition {
                                     label: "PART_PV2".into(),
@@ -1653,12 +1531,7 @@ mod tests {
                         },
                     ]),
                     swap: Some(vec!["/dev/sysvg/swaplv".into()]),
-                    pacstraps: None,
-                    chroot: None,
-                    postinstall: None,
-                    hostname: None,
-                    timezone: None,
-                    rootpasswd: None,
+                    ..Default::default()
                 },
             },
 
@@ -1679,11 +1552,12 @@ mod tests {
                 ])),
 
                 manifest: Manifest {
-                    location: None,
+                    version: 1,
                     disks: Some(vec![
                         ManifestDisk {
                             device: "./test_assets/mock_devs/sda".into(),
                             table: PartitionTable::Gpt,
+                            wipe: None,
                             partitions: vec![
                                 ManifestPartition {
                                     label: "PART_EFI".into(),
@@ -1700,6 +1574,7 @@ mod tests {
                         ManifestDisk {
                             device: "./test_assets/mock_devs/sdb".into(),
                             table: PartitionTable::Mbr,
+                            wipe: None,
                             partitions: vec![
                                 ManifestPartition {
                                     label: "PART_PV2".into(),
@@ -1743,15 +1618,8 @@ mod tests {
                         fs_opts: None,
                         mnt_opts: None,
                     },
-                    filesystems: None,
-                    mountpoints: None,
                     swap: Some(vec!["/dev/myvg/myswap".into()]),
-                    pacstraps: None,
-                    chroot: None,
-                    postinstall: None,
-                    hostname: None,
-                    timezone: None,
-                    rootpasswd: None,
+                    ..Default::default()
                 },
             },
 
@@ -1772,11 +1640,12 @@ mod tests {
                 )])),
 
                 manifest: Manifest {
-                    location: None,
+                    version: 1,
                     disks: Some(vec![
                         ManifestDisk {
                             device: "./test_assets/mock_devs/sda".into(),
                             table: PartitionTable::Gpt,
+                            wipe: None,
                             partitions: vec![
                                 ManifestPartition {
                                     label: "PART_EFI".into(),
@@ -1793,6 +1662,7 @@ mod tests {
                         ManifestDisk {
                             device: "./test_assets/mock_devs/sdb".into(),
                             table: PartitionTable::Mbr,
+                            wipe: None,
                             partitions: vec![ManifestPartition {
                                 label: "PART_PV2".into(),
                                 size: None,
@@ -1857,12 +1727,7 @@ mod tests {
                 swap: Some(vec![
                     "/dev/mynvmevg/myswap".into(),
                 ]),
-                pacstraps: None,
-                chroot: None,
-                postinstall: None,
-                hostname: None,
-                timezone: None,
-                rootpasswd: None,
+                ..Default::default()
             },
         }];
 
@@ -1875,24 +1740,15 @@ mod tests {
                 sys_lvms: None,
 
                 manifest: Manifest {
-                    location: None,
-                    disks: None,
-                    device_mappers: None,
+                    version: 1,
                     rootfs: ManifestRootFs {
                         device: "/dev/fda1".into(),
                         fs_type: "btrfs".into(),
                         fs_opts: None,
                         mnt_opts: None,
                     },
-                    filesystems: None,
-                    mountpoints: None,
                     swap: Some(vec!["/dev/fake1p2".into()]),
-                    pacstraps: None,
-                    chroot: None,
-                    postinstall: None,
-                    hostname: None,
-                    timezone: None,
-                    rootpasswd: None,
+                    ..Default::default()
                 },
             },
 
@@ -1907,24 +1763,15 @@ mod tests {
                 sys_lvms: None,
 
                 manifest: Manifest {
-                    location: None,
-                    disks: None,
-                    device_mappers: None,
+                    version: 1,
                     rootfs: ManifestRootFs{
                         device: "/dev/fake1p1".into(),
                         fs_type: "btrfs".into(),
                         fs_opts: None,
                         mnt_opts: None,
                     },
-                    filesystems: None,
-                    mountpoints: None,
                     swap: Some(vec!["/dev/fake1p2".into()]),
-                    pacstraps: None,
-                    chroot: None,
-                    postinstall: None,
-                    hostname: None,
-                    timezone: None,
-                    rootpasswd: None,
+                    ..Default::default()
                 },
             },
 
@@ -1941,24 +1788,15 @@ mod tests {
                 sys_lvms: None,
 
                 manifest: Manifest {
-                    location: None,
-                    disks: None,
-                    device_mappers: None,
+                    version: 1,
                     rootfs: ManifestRootFs{
                         device: "/dev/fake1p1".into(),
                         fs_type: "btrfs".into(),
                         fs_opts: None,
                         mnt_opts: None,
                     },
-                    filesystems: None,
-                    mountpoints: None,
                     swap: Some(vec!["/dev/fake1p3".into()]),
-                    pacstraps: None,
-                    chroot: None,
-                    postinstall: None,
-                    hostname: None,
-                    timezone: None,
-                    rootpasswd: None,
+                    ..Default::default()
                 },
             },
 
@@ -1975,9 +1813,7 @@ mod tests {
                 sys_lvms: None,
 
                 manifest: Manifest {
-                    location: None,
-                    disks: None,
-                    device_mappers: None,
+                    version: 1,
                     rootfs: ManifestRootFs{
                         device: "/dev/fake1p1".into(),
                         fs_type: "btrfs".into(),
@@ -1991,14 +1827,8 @@ mod tests {
                             fs_opts: None,
                         }
                     ]),
-                    mountpoints: None,
                     swap: Some(vec!["/dev/fake1p2".into()]),
-                    pacstraps: None,
-                    chroot: None,
-                    postinstall: None,
-                    hostname: None,
-                    timezone: None,
-                    rootpasswd: None,
+                    ..Default::default()
                 },
             },
 
@@ -2015,9 +1845,7 @@ mod tests {
                 sys_lvms: None,
 
                 manifest: Manifest {
-                    location: None,
-                    disks: None,
-                    device_mappers: None,
+                    version: 1,
                     rootfs: ManifestRootFs{
                         device: "/dev/fake1p1".into(),
                         fs_type: "btrfs".into(),
@@ -2031,16 +1859,10 @@ mod tests {
                             fs_opts: None,
                         },
                     ]),
-                    mountpoints: None,
                     swap: Some(vec![
                         "/dev/fake1p2".into(),
                     ]),
-                    pacstraps: None,
-                    chroot: None,
-                    postinstall: None,
-                    hostname: None,
-                    timezone: None,
-                    rootpasswd: None,
+                    ..Default::default()
                 },
             },
 
@@ -2057,9 +1879,7 @@ mod tests {
                 sys_lvms: None,
 
                 manifest: Manifest {
-                    location: None,
-                    disks: None,
-                    device_mappers: None,
+                    version: 1,
                     rootfs: ManifestRootFs{
                         device: "/dev/fake1p1".into(),
                         fs_type: "btrfs".into(),
@@ -2078,14 +1898,7 @@ mod tests {
                             fs_opts: None,
                         }
                     ]),
-                    mountpoints: None,
-                    swap: None,
-                    pacstraps: None,
-                    chroot: None,
-                    postinstall: None,
-                    hostname: None,
-                    timezone: None,
-                    rootpasswd: None,
+                    ..Default::default()
                 },
             },
 
@@ -2115,8 +1928,7 @@ mod tests {
                 )])),
 
                 manifest: Manifest {
-                    location: None,
-                    disks: None,
+                    version: 1,
                     device_mappers: Some(vec![
                         Dm::Luks(ManifestLuks {
                             device: "/dev/fake1p2".into(),
@@ -2130,15 +1942,8 @@ mod tests {
                         fs_opts: None,
                         mnt_opts: None,
                     },
-                    filesystems: None,
-                    mountpoints: None,
                     swap: Some(vec!["/dev/fake1p2".into()]),
-                    pacstraps: None,
-                    chroot: None,
-                    postinstall: None,
-                    hostname: None,
-                    timezone: None,
-                    rootpasswd: None,
+                    ..Default::default()
                 },
             },
 
@@ -2184,8 +1989,7 @@ mod tests {
                 )])),
 
                 manifest: Manifest {
-                    location: None,
-                    disks: None,
+                    version: 1,
                     device_mappers: Some(vec![
                         Dm::Luks(ManifestLuks {
                             device: "/dev/myvg/mylv".into(),
@@ -2199,15 +2003,8 @@ mod tests {
                         fs_opts: None,
                         mnt_opts: None,
                     },
-                    filesystems: None,
-                    mountpoints: None,
                     swap: Some(vec!["/dev/myvg/mylv".into()]),
-                    pacstraps: None,
-                    chroot: None,
-                    postinstall: None,
-                    hostname: None,
-                    timezone: None,
-                    rootpasswd: None,
+                    ..Default::default()
                 },
             },
 
@@ -2238,8 +2035,7 @@ mod tests {
                 )])),
 
                 manifest: Manifest {
-                    location: None,
-                    disks: None,
+                    version: 1,
                     device_mappers: Some(vec![
                         Dm::Luks(ManifestLuks {
                             device: "/dev/fake1p2".into(),
@@ -2260,14 +2056,7 @@ mod tests {
                             fs_opts: None,
                         },
                     ]),
-                    mountpoints: None,
-                    swap: None,
-                    pacstraps: None,
-                    chroot: None,
-                    postinstall: None,
-                    hostname: None,
-                    timezone: None,
-                    rootpasswd: None,
+                    ..Default::default()
                 },
             },
 
@@ -2298,8 +2087,7 @@ mod tests {
                 )])),
 
                 manifest: Manifest {
-                    location: None,
-                    disks: None,
+                    version: 1,
                     device_mappers: Some(vec![
                         Dm::Luks(ManifestLuks {
                             device: "/dev/fake1p2".into(),
@@ -2327,13 +2115,7 @@ mod tests {
                             mnt_opts: None,
                         },
                     ]),
-                    swap: None,
-                    pacstraps: None,
-                    chroot: None,
-                    postinstall: None,
-                    hostname: None,
-                    timezone: None,
-                    rootpasswd: None,
+                    ..Default::default()
                 },
             },
 
@@ -2347,11 +2129,12 @@ mod tests {
                 sys_lvms: None,
 
                 manifest: Manifest {
-                    location: None,
+                    version: 1,
                     disks: Some(vec![
                         ManifestDisk {
                             device: "./test_assets/mock_devs/sda".into(),
                             table: PartitionTable::Gpt,
+                            wipe: None,
                             partitions: vec![
                                 ManifestPartition {
                                     label: "PART_EFI".into(),
@@ -2396,14 +2179,8 @@ mod tests {
                             fs_opts: None,
                         },
                     ]),
-                    mountpoints: None,
                     swap: Some(vec!["/dev/fake1p2".into()]),
-                    pacstraps: None,
-                    chroot: None,
-                    postinstall: None,
-                    hostname: None,
-                    timezone: None,
-                    rootpasswd: None,
+                    ..Default::default()
                 },
             },
 
@@ -2417,11 +2194,12 @@ mod tests {
                 sys_lvms: None,
 
                 manifest: Manifest {
-                    location: None,
+                    version: 1,
                     disks: Some(vec![
                         ManifestDisk {
                             device: "./test_assets/mock_devs/sda".into(),
                             table: PartitionTable::Gpt,
+                            wipe: None,
                             partitions: vec![
                                 ManifestPartition {
                                     label: "PART_EFI".into(),
@@ -2459,7 +2237,6 @@ mod tests {
                         fs_opts: None,
                         mnt_opts: None,
                     },
-                    filesystems: None,
                     mountpoints: Some(vec![
                         ManifestMountpoint {
                             device: "/dev/myvg/mylv".into(),
@@ -2468,12 +2245,7 @@ mod tests {
                         },
                     ]),
                     swap: Some(vec!["/dev/fake1p2".into()]),
-                    pacstraps: None,
-                    chroot: None,
-                    postinstall: None,
-                    hostname: None,
-                    timezone: None,
-                    rootpasswd: None,
+                    ..Default::default()
                 },
             },
 
@@ -2487,11 +2259,12 @@ mod tests {
                 sys_lvms: None,
 
                 manifest: Manifest {
-                    location: None,
+                    version: 1,
                     disks: Some(vec![
                         ManifestDisk {
                             device: "./test_assets/mock_devs/sda".into(),
                             table: PartitionTable::Gpt,
+                            wipe: None,
                             partitions: vec![
                                 ManifestPartition {
                                     label: "PART_EFI".into(),
@@ -2521,15 +2294,8 @@ mod tests {
                         fs_opts: None,
                         mnt_opts: None,
                     },
-                    filesystems: None,
-                    mountpoints: None,
                     swap: Some(vec!["/dev/fake1p2".into()]),
-                    pacstraps: None,
-                    chroot: None,
-                    postinstall: None,
-                    hostname: None,
-                    timezone: None,
-                    rootpasswd: None,
+                    ..Default::default()
                 },
             },
 
@@ -2543,11 +2309,12 @@ mod tests {
                 sys_lvms: None,
 
                 manifest: Manifest {
-                    location: None,
+                    version: 1,
                     disks: Some(vec![
                         ManifestDisk {
                             device: "./test_assets/mock_devs/sda".into(),
                             table: PartitionTable::Gpt,
+                            wipe: None,
                             partitions: vec![
                                 ManifestPartition {
                                     label: "PART_EFI".into(),
@@ -2588,15 +2355,8 @@ mod tests {
                         fs_opts: None,
                         mnt_opts: None,
                     },
-                    filesystems: None,
-                    mountpoints: None,
                     swap: Some(vec!["/dev/fake1p2".into()]),
-                    pacstraps: None,
-                    chroot: None,
-                    postinstall: None,
-                    hostname: None,
-                    timezone: None,
-                    rootpasswd: None,
+                    ..Default::default()
                 },
             },
 
@@ -2610,11 +2370,12 @@ mod tests {
                 sys_lvms: None,
 
                 manifest: Manifest {
-                    location: None,
+                    version: 1,
                     disks: Some(vec![
                         ManifestDisk {
                             device: "./test_assets/mock_devs/sda".into(),
                             table: PartitionTable::Gpt,
+                            wipe: None,
                             partitions: vec![
                                 ManifestPartition {
                                     label: "PART_EFI".into(),
@@ -2655,15 +2416,8 @@ mod tests {
                         fs_opts: None,
                         mnt_opts: None,
                     },
-                    filesystems: None,
-                    mountpoints: None,
                     swap: Some(vec!["/dev/fake1p2".into()]),
-                    pacstraps: None,
-                    chroot: None,
-                    postinstall: None,
-                    hostname: None,
-                    timezone: None,
-                    rootpasswd: None,
+                    ..Default::default()
                 },
             },
 
@@ -2677,11 +2431,12 @@ mod tests {
                 sys_lvms: None,
 
                 manifest: Manifest {
-                    location: None,
+                    version: 1,
                     disks: Some(vec![
                         ManifestDisk {
                             device: "./test_assets/mock_devs/sda".into(),
                             table: PartitionTable::Gpt,
+                            wipe: None,
                             partitions: vec![
                                 ManifestPartition {
                                     label: "PART_EFI".into(),
@@ -2722,15 +2477,8 @@ mod tests {
                         fs_opts: None,
                         mnt_opts: None,
                     },
-                    filesystems: None,
-                    mountpoints: None,
                     swap: Some(vec!["/dev/fake1p2".into()]),
-                    pacstraps: None,
-                    chroot: None,
-                    postinstall: None,
-                    hostname: None,
-                    timezone: None,
-                    rootpasswd: None,
+                    ..Default::default()
                 },
             },
 
@@ -2744,11 +2492,12 @@ mod tests {
                 sys_lvms: None,
 
                 manifest: Manifest {
-                    location: None,
+                    version: 1,
                     disks: Some(vec![
                         ManifestDisk {
                             device: "./test_assets/mock_devs/sda".into(),
                             table: PartitionTable::Gpt,
+                            wipe: None,
                             partitions: vec![
                                 ManifestPartition {
                                     label: "PART_EFI".into(),
@@ -2789,15 +2538,8 @@ mod tests {
                         fs_opts: None,
                         mnt_opts: None,
                     },
-                    filesystems: None,
-                    mountpoints: None,
                     swap: Some(vec!["/dev/fake1p2".into()]),
-                    pacstraps: None,
-                    chroot: None,
-                    postinstall: None,
-                    hostname: None,
-                    timezone: None,
-                    rootpasswd: None,
+                    ..Default::default()
                 },
             },
 
@@ -2811,11 +2553,12 @@ mod tests {
                 sys_lvms: None,
 
                 manifest: Manifest {
-                    location: None,
+                    version: 1,
                     disks: Some(vec![
                         ManifestDisk {
                             device: "./test_assets/mock_devs/sda".into(),
                             table: PartitionTable::Gpt,
+                            wipe: None,
                             partitions: vec![
                                 ManifestPartition {
                                     label: "PART_EFI".into(),
@@ -2856,15 +2599,8 @@ mod tests {
                         fs_opts: None,
                         mnt_opts: None,
                     },
-                    filesystems: None,
-                    mountpoints: None,
                     swap: Some(vec!["/dev/fake1p2".into()]),
-                    pacstraps: None,
-                    chroot: None,
-                    postinstall: None,
-                    hostname: None,
-                    timezone: None,
-                    rootpasswd: None,
+                    ..Default::default()
                 },
             },
 
@@ -2878,11 +2614,12 @@ mod tests {
                 sys_lvms: None,
 
                 manifest: Manifest {
-                    location: None,
+                    version: 1,
                     disks: Some(vec![
                         ManifestDisk {
                             device: "./test_assets/mock_devs/sda".into(),
                             table: PartitionTable::Gpt,
+                            wipe: None,
                             partitions: vec![
                                 ManifestPartition {
                                     label: "PART_EFI".into(),
@@ -2923,15 +2660,8 @@ mod tests {
                         fs_opts: None,
                         mnt_opts: None,
                     },
-                    filesystems: None,
-                    mountpoints: None,
                     swap: Some(vec!["/dev/fake1p2".into()]),
-                    pacstraps: None,
-                    chroot: None,
-                    postinstall: None,
-                    hostname: None,
-                    timezone: None,
-                    rootpasswd: None,
+                    ..Default::default()
                 },
             },
 
@@ -2945,11 +2675,12 @@ mod tests {
                 sys_lvms: None,
 
                 manifest: Manifest {
-                    location: None,
+                    version: 1,
                     disks: Some(vec![
                         ManifestDisk {
                             device: "./test_assets/mock_devs/sda".into(),
                             table: PartitionTable::Gpt,
+                            wipe: None,
                             partitions: vec![
                                 ManifestPartition {
                                     label: "PART_EFI".into(),
@@ -2983,15 +2714,8 @@ mod tests {
                         fs_opts: None,
                         mnt_opts: None,
                     },
-                    filesystems: None,
-                    mountpoints: None,
                     swap: Some(vec!["/dev/fake1p2".into()]),
-                    pacstraps: None,
-                    chroot: None,
-                    postinstall: None,
-                    hostname: None,
-                    timezone: None,
-                    rootpasswd: None,
+                    ..Default::default()
                 },
             },
 
@@ -3005,11 +2729,12 @@ mod tests {
                 sys_lvms: None,
 
                 manifest: Manifest {
-                    location: None,
+                    version: 1,
                     disks: Some(vec![
                         ManifestDisk {
                             device: "./test_assets/mock_devs/sda".into(),
                             table: PartitionTable::Gpt,
+                            wipe: None,
                             partitions: vec![
                                 ManifestPartition {
                                     label: "PART_EFI".into(),
@@ -3059,12 +2784,7 @@ mod tests {
                         }
                     ]),
                     swap: Some(vec!["/dev/fake1p2".into()]),
-                    pacstraps: None,
-                    chroot: None,
-                    postinstall: None,
-                    hostname: None,
-                    timezone: None,
-                    rootpasswd: None,
+                    ..Default::default()
                 },
             },
 
@@ -3078,11 +2798,12 @@ mod tests {
                 sys_lvms: None,
 
                 manifest: Manifest {
-                    location: None,
+                    version: 1,
                     disks: Some(vec![
                         ManifestDisk {
                             device: "./test_assets/mock_devs/sda".into(),
                             table: PartitionTable::Gpt,
+                            wipe: None,
                             partitions: vec![
                                 ManifestPartition {
                                     label: "PART_EFI".into(),
@@ -3099,6 +2820,7 @@ mod tests {
                         ManifestDisk {
                             device: "./test_assets/mock_devs/sdb".into(),
                             table: PartitionTable::Mbr,
+                            wipe: None,
                             partitions: vec![
                                 ManifestPartition {
                                     label: "PART_PV2".into(),
@@ -3133,15 +2855,8 @@ mod tests {
                         fs_opts: None,
                         mnt_opts: None,
                     },
-                    filesystems: None,
-                    mountpoints: None,
                     swap: Some(vec!["/dev/fake1p2".into()]), // Was already used as manifest PV
-                    pacstraps: None,
-                    chroot: None,
-                    postinstall: None,
-                    hostname: None,
-                    timezone: None,
-                    rootpasswd: None,
+                    ..Default::default()
                 },
             },
 
@@ -3154,11 +2869,12 @@ mod tests {
                 sys_fs_devs: None,
                 sys_lvms: None,
                 manifest: Manifest {
-                    location: None,
+                    version: 1,
                     disks: Some(vec![
                         ManifestDisk {
                             device: "./test_assets/mock_devs/sda".into(),
                             table: PartitionTable::Gpt,
+                            wipe: None,
                             partitions: vec![
                                 ManifestPartition {
                                     label: "PART_EFI".into(),
@@ -3175,6 +2891,7 @@ mod tests {
                         ManifestDisk {
                             device: "./test_assets/mock_devs/sdb".into(),
                             table: PartitionTable::Mbr,
+                            wipe: None,
                             partitions: vec![
                                 ManifestPartition {
                                     label: "PART_PV2".into(),
@@ -3210,15 +2927,8 @@ mod tests {
                         fs_opts: None,
                         mnt_opts: None,
                     },
-                    filesystems: None,
-                    mountpoints: None,
                     swap: Some(vec!["/dev/fake1p1".into()]),
-                    pacstraps: None,
-                    chroot: None,
-                    postinstall: None,
-                    hostname: None,
-                    timezone: None,
-                    rootpasswd: None,
+                    ..Default::default()
                 },
             },
 
@@ -3235,11 +2945,12 @@ mod tests {
                 sys_lvms: None,
 
                 manifest: Manifest {
-                    location: None,
+                    version: 1,
                     disks: Some(vec![
                         ManifestDisk {
                             device: "./test_assets/mock_devs/sda".into(),
                             table: PartitionTable::Gpt,
+                            wipe: None,
                             partitions: vec![
                                 ManifestPartition {
                                     label: "PART_EFI".into(),
@@ -3256,6 +2967,7 @@ mod tests {
                         ManifestDisk {
                             device: "./test_assets/mock_devs/sdb".into(),
                             table: PartitionTable::Mbr,
+                            wipe: None,
                             partitions: vec![
                                 ManifestPartition {
                                     label: "PART_PV2".into(),
@@ -3298,15 +3010,8 @@ mod tests {
                         fs_opts: None,
                         mnt_opts: None,
                     },
-                    filesystems: None,
-                    mountpoints: None,
                     swap: Some(vec!["/dev/myvg/myswap".into()]),
-                    pacstraps: None,
-                    chroot: None,
-                    postinstall: None,
-                    hostname: None,
-                    timezone: None,
-                    rootpasswd: None,
+                    ..Default::default()
                 },
             },
 
@@ -3330,11 +3035,12 @@ mod tests {
                 ])),
 
                 manifest: Manifest {
-                    location: None,
+                    version: 1,
                     disks: Some(vec![
                         ManifestDisk {
                             device: "./test_assets/mock_devs/sda".into(),
                             table: PartitionTable::Gpt,
+                            wipe: None,
                             partitions: vec![
                                 ManifestPartition {
                                     label: "PART_EFI".into(),
@@ -3351,6 +3057,7 @@ mod tests {
                         ManifestDisk {
                             device: "./test_assets/mock_devs/sdb".into(),
                             table: PartitionTable::Mbr,
+                            wipe: None,
                             partitions: vec![
                                 ManifestPartition {
                                     label: "PART_PV2".into(),
@@ -3393,15 +3100,8 @@ mod tests {
                         fs_opts: None,
                         mnt_opts: None,
                     },
-                    filesystems: None,
-                    mountpoints: None,
                     swap: Some(vec!["/dev/myvg/myswap".into()]),
-                    pacstraps: None,
-                    chroot: None,
-                    postinstall: None,
-                    hostname: None,
-                    timezone: None,
-                    rootpasswd: None,
+                    ..Default::default()
                 },
             },
 
@@ -3416,11 +3116,12 @@ mod tests {
                 sys_lvms: None,
 
                 manifest: Manifest {
-                    location: None,
+                    version: 1,
                     disks: Some(vec![
                         ManifestDisk {
                             device: "./test_assets/mock_devs/sda".into(),
                             table: PartitionTable::Gpt,
+                            wipe: None,
                             partitions: vec![
                                 ManifestPartition {
                                     label: "PART_EFI".into(),
@@ -3437,6 +3138,7 @@ mod tests {
                         ManifestDisk {
                             device: "./test_assets/mock_devs/sdb".into(),
                             table: PartitionTable::Mbr,
+                            wipe: None,
                             partitions: vec![
                                 ManifestPartition {
                                     label: "PART_PV2".into(),
@@ -3504,12 +3206,7 @@ mod tests {
                         },
                     ]),
                     swap: Some(vec!["/dev/myvg/myswap".into()]),
-                    pacstraps: None,
-                    chroot: None,
-                    postinstall: None,
-                    hostname: None,
-                    timezone: None,
-                    rootpasswd: None,
+                    ..Default::default()
                 },
             },
 
@@ -3524,11 +3221,12 @@ mod tests {
                 sys_lvms: None,
 
                 manifest: Manifest {
-                    location: None,
+                    version: 1,
                     disks: Some(vec![
                         ManifestDisk {
                             device: "./test_assets/mock_devs/sda".into(),
                             table: PartitionTable::Gpt,
+                            wipe: None,
                             partitions: vec![
                                 ManifestPartition {
                                     label: "PART_EFI".into(),
@@ -3545,6 +3243,7 @@ mod tests {
                         ManifestDisk {
                             device: "./test_assets/mock_devs/sdb".into(),
                             table: PartitionTable::Mbr,
+                            wipe: None,
                             partitions: vec![
                                 ManifestPartition {
                                     label: "PART_PV2".into(),
@@ -3612,12 +3311,7 @@ mod tests {
                         },
                     ]),
                     swap: Some(vec!["/dev/myvg/myswap".into()]),
-                    pacstraps: None,
-                    chroot: None,
-                    postinstall: None,
-                    hostname: None,
-                    timezone: None,
-                    rootpasswd: None,
+                    ..Default::default()
                 },
             },
 
@@ -3632,11 +3326,12 @@ mod tests {
                 sys_lvms: None,
 
                 manifest: Manifest {
-                    location: None,
+                    version: 1,
                     disks: Some(vec![
                         ManifestDisk {
                             device: "./test_assets/mock_devs/sda".into(),
                             table: PartitionTable::Gpt,
+                            wipe: None,
                             partitions: vec![
                                 ManifestPartition {
                                     label: "PART_EFI".into(),
@@ -3653,6 +3348,7 @@ mod tests {
                         ManifestDisk {
                             device: "./test_assets/mock_devs/sdb".into(),
                             table: PartitionTable::Mbr,
+                            wipe: None,
                             partitions: vec![
                                 ManifestPartition {
                                     label: "PART_PV2".into(),
@@ -3734,12 +3430,7 @@ mod tests {
                         },
                     ]),
                     swap: Some(vec!["/dev/sysvg/swaplv".into()]),
-                    pacstraps: None,
-                    chroot: None,
-                    postinstall: None,
-                    hostname: None,
-                    timezone: None,
-                    rootpasswd: None,
+                    ..Default::default()
                 },
             },
 
@@ -3754,11 +3445,12 @@ mod tests {
                 sys_lvms: None,
 
                 manifest: Manifest {
-                    location: None,
+                    version: 1,
                     disks: Some(vec![
                         ManifestDisk {
                             device: "./test_assets/mock_devs/sda".into(),
                             table: PartitionTable::Gpt,
+                            wipe: None,
                             partitions: vec![
                                 ManifestPartition {
                                     label: "PART_EFI".into(),
@@ -3775,6 +3467,7 @@ mod tests {
                         ManifestDisk {
                             device: "./test_assets/mock_devs/sdb".into(),
                             table: PartitionTable::Mbr,
+                            wipe: None,
                             partitions: vec![
                                 ManifestPartition {
                                     label: "PART_PV2".into(),
@@ -3861,12 +3554,7 @@ mod tests {
                         },
                     ]),
                     swap: Some(vec!["/dev/sysvg/swaplv".into()]),
-                    pacstraps: None,
-                    chroot: None,
-                    postinstall: None,
-                    hostname: None,
-                    timezone: None,
-                    rootpasswd: None,
+                    ..Default::default()
                 },
             },
         ];
@@ -3905,7 +3593,7 @@ mod tests {
                 test.sys_lvms.clone().unwrap_or_default(),
             );
 
-            if result.is_ok() {
+            if let Ok(paths) = result {
                 eprintln!(
                     "Unexpected ok result from test case {}: {}",
                     i + 1,
@@ -3916,7 +3604,6 @@ mod tests {
                     eprintln!("\nCONTEXT: {ctx}\n");
                 }
 
-                let paths = result.unwrap();
                 let paths_json = serde_json::to_string(&paths).unwrap();
 
                 eprintln!("Test structure: {test:?}");
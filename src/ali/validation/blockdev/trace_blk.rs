@@ -410,14 +410,14 @@ fn test_trace_existing_lvms() {
         };
 
         for (i, list) in v.into_iter().enumerate() {
-            let expected = expecteds
-                .get_mut(i)
-                .expect(&format!("no such expected list {i} for key {k}"));
+            let expected = expecteds.get_mut(i).unwrap_or_else(|| {
+                panic!("no such expected list {i} for key {k}")
+            });
 
             for (j, item) in list.into_iter().enumerate() {
-                let expected_item = expected.pop_front().expect(&format!(
-                    "no such expected item {j} on list {i} for key {k}",
-                ));
+                let expected_item = expected.pop_front().unwrap_or_else(|| {
+                    panic!("no such expected item {j} on list {i} for key {k}")
+                });
 
                 assert_eq!(expected_item, item);
             }
@@ -0,0 +1,155 @@
+use std::collections::HashSet;
+
+use crate::ali::apply::ca;
+use crate::ali::ManifestCaCert;
+use crate::errors::AliError;
+use crate::hooks;
+use crate::utils::checksum;
+use crate::utils::fs::file_exists;
+
+const MSG: &str = "ca_certs validation failed";
+
+/// Validates manifest key `ca_certs`
+pub fn validate(m_certs: &[ManifestCaCert]) -> Result<(), AliError> {
+    let mut names = HashSet::new();
+
+    for cert in m_certs {
+        let name = &cert.name;
+        let valid = |c: char| c.is_ascii_alphanumeric() || "._-".contains(c);
+        if name.is_empty() || name.starts_with('.') || !name.chars().all(valid)
+        {
+            return Err(AliError::BadManifest(format!(
+                "{MSG}: bad name {name}"
+            )));
+        }
+
+        if !names.insert(name) {
+            return Err(AliError::BadManifest(format!(
+                "{MSG}: duplicate name {name}"
+            )));
+        }
+
+        match (&cert.pem, &cert.file, &cert.url) {
+            (Some(pem), None, None) => {
+                if !ca::is_pem(pem) {
+                    return Err(AliError::BadManifest(format!(
+                        "{MSG}: {name}: pem is not a PEM certificate"
+                    )));
+                }
+            }
+            (None, Some(file), None) => {
+                if !file_exists(file) {
+                    return Err(AliError::BadManifest(format!(
+                        "{MSG}: {name}: no such file {file}"
+                    )));
+                }
+            }
+            (None, None, Some(url)) => {
+                if !hooks::is_remote(url) {
+                    return Err(AliError::BadManifest(format!(
+                        "{MSG}: {name}: bad url {url}"
+                    )));
+                }
+            }
+            _ => {
+                return Err(AliError::BadManifest(format!(
+                    "{MSG}: {name}: expecting exactly one of pem, file, or url"
+                )));
+            }
+        }
+
+        // Trust anchors must not change under our feet
+        match (&cert.url, &cert.sha256) {
+            (Some(_), None) => {
+                return Err(AliError::BadManifest(format!(
+                    "{MSG}: {name}: url requires sha256"
+                )));
+            }
+            (None, Some(_)) => {
+                return Err(AliError::BadManifest(format!(
+                    "{MSG}: {name}: sha256 is only for url"
+                )));
+            }
+            (Some(_), Some(sum)) if !checksum::is_sha256_hex(sum) => {
+                return Err(AliError::BadManifest(format!(
+                    "{MSG}: {name}: bad sha256 {sum}"
+                )));
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_validate_ca() {
+    let sum = "a".repeat(64);
+    let cert = |name: &str| {
+        ManifestCaCert {
+            name: name.to_string(),
+            pem: None,
+            file: None,
+            url: None,
+            sha256: None,
+        }
+    };
+
+    let pem = "-----BEGIN CERTIFICATE-----\nMIIB\n-----END CERTIFICATE-----\n";
+    let inline = ManifestCaCert {
+        pem: Some(pem.to_string()),
+        ..cert("corp-root")
+    };
+    let file = ManifestCaCert {
+        file: Some("./test_assets/foo.conf".to_string()),
+        ..cert("corp.intermediate")
+    };
+
+    assert!(validate(&[inline.clone(), file.clone()]).is_ok());
+
+    let should_err = vec![
+        vec![cert("empty")],
+        vec![ManifestCaCert {
+            pem: Some("garbage".to_string()),
+            ..cert("garbage")
+        }],
+        vec![ManifestCaCert {
+            name: "../etc".to_string(),
+            ..inline.clone()
+        }],
+        vec![inline.clone(), inline.clone()],
+        vec![ManifestCaCert {
+            file: Some("./test_assets/missing.pem".to_string()),
+            ..cert("missing")
+        }],
+        vec![ManifestCaCert {
+            url: Some("https://pki.example.com/root.pem".to_string()),
+            ..cert("unpinned")
+        }],
+        vec![ManifestCaCert {
+            url: Some("https://pki.example.com/root.pem".to_string()),
+            sha256: Some("abc".to_string()),
+            ..cert("bad-pin")
+        }],
+        vec![ManifestCaCert {
+            sha256: Some(sum.clone()),
+            ..inline.clone()
+        }],
+        vec![ManifestCaCert {
+            file: Some("./test_assets/foo.conf".to_string()),
+            ..inline.clone()
+        }],
+    ];
+
+    for m_certs in should_err {
+        assert!(validate(&m_certs).is_err(), "unexpected ok for {m_certs:?}");
+    }
+
+    #[cfg(feature = "remote")]
+    assert!(validate(&[ManifestCaCert {
+        url: Some("https://pki.example.com/root.pem".to_string()),
+        sha256: Some(sum),
+        ..cert("pinned")
+    }])
+    .is_ok());
+}
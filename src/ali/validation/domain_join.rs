@@ -0,0 +1,168 @@
+use crate::ali::{
+    Manifest,
+    ManifestDomainJoin,
+};
+use crate::errors::AliError;
+use crate::utils::fs::file_exists;
+
+const MSG: &str = "domain_join validation failed";
+
+/// Longest computer account name, limited by NetBIOS names
+const MAX_COMPUTER_NAME: usize = 15;
+
+/// Validates manifest key `domain_join`
+pub fn validate(
+    manifest: &Manifest,
+    m_join: &ManifestDomainJoin,
+) -> Result<(), AliError> {
+    let domain = &m_join.domain;
+    let is_domain = domain.contains('.')
+        && !domain.starts_with(['-', '.'])
+        && domain
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-.".contains(c));
+
+    if !is_domain {
+        return Err(AliError::BadManifest(format!(
+            "{MSG}: bad domain {domain}"
+        )));
+    }
+
+    let password_file = match (
+        &m_join.user,
+        &m_join.password_file,
+        &m_join.one_time_password_file,
+    ) {
+        (Some(_), Some(file), None) => file,
+        (None, None, Some(file)) => file,
+        (Some(_), None, _) => {
+            return Err(AliError::BadManifest(format!(
+                "{MSG}: user requires password_file"
+            )));
+        }
+        _ => {
+            return Err(AliError::BadManifest(format!(
+                "{MSG}: expecting either user with password_file, or one_time_password_file"
+            )));
+        }
+    };
+
+    if !file_exists(password_file) {
+        return Err(AliError::BadManifest(format!(
+            "{MSG}: no such password file {password_file}"
+        )));
+    }
+
+    // Values end up single-quoted in join script
+    for (key, value) in [("user", &m_join.user), ("ou", &m_join.ou)] {
+        if let Some(value) = value {
+            if value.is_empty() || value.contains(['\'', '\n']) {
+                return Err(AliError::BadManifest(format!(
+                    "{MSG}: bad {key} {value}"
+                )));
+            }
+        }
+    }
+
+    let computer_name = m_join
+        .computer_name
+        .as_ref()
+        .or(manifest.hostname.as_ref())
+        .ok_or_else(|| {
+            AliError::BadManifest(format!(
+                "{MSG}: computer_name is required without hostname"
+            ))
+        })?;
+
+    let is_name = computer_name.len() <= MAX_COMPUTER_NAME
+        && !computer_name.starts_with('-')
+        && computer_name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-');
+
+    if computer_name.is_empty() || !is_name {
+        return Err(AliError::BadManifest(format!(
+            "{MSG}: bad computer name {computer_name}, expecting at most {MAX_COMPUTER_NAME} letters, digits, or hyphens"
+        )));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_validate_domain_join() {
+    let base = "
+version: 1
+rootfs:
+  device: /dev/vda2
+  fstype: ext4
+";
+
+    let should_pass = vec![
+        "hostname: ws01
+ad:
+  domain: corp.example.com
+  user: joiner
+  password_file: ./test_assets/foo.conf",
+        "ad:
+  domain: corp.example.com
+  one_time_password_file: ./test_assets/foo.conf
+  computer_name: ws-0001",
+    ];
+
+    let should_err = vec![
+        // No computer name
+        "ad:
+  domain: corp.example.com
+  one_time_password_file: ./test_assets/foo.conf",
+        // Computer name too long
+        "hostname: workstation-00001
+ad:
+  domain: corp.example.com
+  one_time_password_file: ./test_assets/foo.conf",
+        // No password
+        "hostname: ws01
+ad:
+  domain: corp.example.com
+  user: joiner",
+        // Both passwords
+        "hostname: ws01
+ad:
+  domain: corp.example.com
+  user: joiner
+  password_file: ./test_assets/foo.conf
+  one_time_password_file: ./test_assets/foo.conf",
+        // Missing password file
+        "hostname: ws01
+ad:
+  domain: corp.example.com
+  one_time_password_file: ./test_assets/missing.pass",
+        "hostname: ws01
+ad:
+  domain: corp
+  one_time_password_file: ./test_assets/foo.conf",
+        "hostname: ws01
+ad:
+  domain: corp.example.com
+  user: joiner
+  password_file: ./test_assets/foo.conf
+  ou: OU='Linux'",
+    ];
+
+    for yaml in should_pass {
+        let manifest = crate::ali::parse(&format!("{base}{yaml}")).unwrap();
+        let m_join = manifest.domain_join.as_ref().unwrap();
+        if let Err(err) = validate(&manifest, m_join) {
+            panic!("unexpected error from {yaml}: {err:?}");
+        }
+    }
+
+    for yaml in should_err {
+        let manifest = crate::ali::parse(&format!("{base}{yaml}")).unwrap();
+        let m_join = manifest.domain_join.as_ref().unwrap();
+        assert!(
+            validate(&manifest, m_join).is_err(),
+            "unexpected ok result from {yaml}"
+        );
+    }
+}
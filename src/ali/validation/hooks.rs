@@ -2,7 +2,17 @@ use crate::ali::Manifest;
 use crate::errors::AliError;
 use crate::hooks;
 
+const MSG: &str = "hooks validation failed";
+
 pub fn validate(manifest: &Manifest, mountpoint: &str) -> Result<(), AliError> {
+    for path in manifest.protected_paths.iter().flatten() {
+        if !path.starts_with('/') || path.split('/').any(|c| c == "..") {
+            return Err(AliError::BadManifest(format!(
+                "{MSG}: protected path {path} is not an absolute path"
+            )));
+        }
+    }
+
     if let Some(cmds) = &manifest.chroot {
         validate_hooks(cmds, &hooks::Caller::ManifestChroot, mountpoint)?;
     }
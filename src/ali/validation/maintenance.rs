@@ -0,0 +1,90 @@
+use crate::ali::{
+    Manifest,
+    ManifestMaintenance,
+};
+use crate::errors::AliError;
+
+const MSG: &str = "maintenance validation failed";
+
+/// Validates manifest key `maintenance`
+pub fn validate(
+    manifest: &Manifest,
+    m_maintenance: &ManifestMaintenance,
+) -> Result<(), AliError> {
+    let has_btrfs = manifest.rootfs.fs_type == "btrfs"
+        || manifest
+            .filesystems
+            .iter()
+            .flatten()
+            .any(|fs| fs.fs_type == "btrfs");
+
+    let btrfs_keys = [
+        ("scrub", m_maintenance.btrfs_scrub.unwrap_or(false)),
+        ("balance", m_maintenance.btrfs_balance.is_some()),
+    ];
+
+    for (key, enabled) in btrfs_keys {
+        if enabled && !has_btrfs {
+            return Err(AliError::BadManifest(format!(
+                "{MSG}: key `{key}` requires a Btrfs filesystem"
+            )));
+        }
+    }
+
+    if manifest.netroot.is_some()
+        && (m_maintenance.fstrim.unwrap_or(false)
+            || m_maintenance.smartd.is_some())
+    {
+        return Err(AliError::BadManifest(format!(
+            "{MSG}: fstrim and smartd are not allowed for diskless installs"
+        )));
+    }
+
+    let ntfy = m_maintenance.smartd.as_ref().and_then(|s| s.ntfy.as_ref());
+    if let Some(ntfy) = ntfy {
+        let is_url =
+            ntfy.starts_with("https://") || ntfy.starts_with("http://");
+
+        if !is_url || ntfy.contains(['\'', ' ']) {
+            return Err(AliError::BadManifest(format!(
+                "{MSG}: bad ntfy topic URL {ntfy}"
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_validate_maintenance() {
+    let yaml = r#"
+version: 1
+rootfs:
+  device: /dev/vda2
+  fstype: ext4
+maintenance:
+  fstrim: true
+  smartd:
+    ntfy: https://ntfy.sh/mytopic
+"#;
+
+    let manifest = crate::ali::parse(yaml).unwrap();
+    let m_maintenance = manifest.maintenance.as_ref().unwrap();
+    assert!(validate(&manifest, m_maintenance).is_ok());
+
+    let should_err = [
+        format!("{yaml}  scrub: true\n"),
+        format!("{yaml}  balance: weekly\n"),
+        yaml.replace("https://ntfy.sh/mytopic", "ntfy.sh/mytopic"),
+        yaml.replace("mytopic", "my'topic"),
+    ];
+
+    for yaml in should_err {
+        let manifest = crate::ali::parse(&yaml).unwrap();
+        let m_maintenance = manifest.maintenance.as_ref().unwrap();
+        assert!(
+            validate(&manifest, m_maintenance).is_err(),
+            "expecting error for {yaml}"
+        );
+    }
+}
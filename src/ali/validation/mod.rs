@@ -1,23 +1,90 @@
 mod blockdev;
+mod ca;
+mod domain_join;
 mod hooks;
+mod maintenance;
+mod netroot;
+mod secrets;
+mod skel;
+mod ssh;
+mod time;
 
-use crate::ali::Manifest;
+use crate::ali::{
+    portable,
+    Manifest,
+};
 use crate::constants::{
     self,
     defaults,
 };
 use crate::errors::AliError;
+use crate::linux::blkid::DeviceMap;
 use crate::types::report::ValidationReport;
+use crate::utils::aliases::{
+    self,
+    Kind,
+};
 use crate::utils::fs::file_exists;
 use crate::utils::shell;
+use crate::utils::tunnel::Proxy;
 
 pub fn validate(
     manifest: &Manifest,
     install_location: &str,
     overwrite: bool,
 ) -> Result<ValidationReport, AliError> {
-    // Validate block devices in manifest
-    let block_devs = blockdev::validate(manifest, overwrite)?;
+    // Validate block devices in manifest,
+    // or network root for diskless installs
+    let block_devs = match &manifest.netroot {
+        Some(m_netroot) => {
+            netroot::validate(manifest, m_netroot)?;
+            vec![]
+        }
+        None => blockdev::validate(manifest, overwrite)?,
+    };
+
+    // Validate maintenance units
+    if let Some(m_maintenance) = &manifest.maintenance {
+        maintenance::validate(manifest, m_maintenance)?;
+    }
+
+    // Validate ssh client config and known hosts
+    if let Some(m_ssh) = &manifest.ssh {
+        ssh::validate(m_ssh)?;
+    }
+
+    // Validate skeleton files
+    if let Some(m_skel) = &manifest.skel {
+        skel::validate(m_skel)?;
+    }
+
+    // Validate secrets providers and references
+    secrets::validate(manifest, manifest.secrets.as_deref().unwrap_or(&[]))?;
+
+    // Validate CA certificates
+    if let Some(m_certs) = &manifest.ca_certs {
+        ca::validate(m_certs)?;
+    }
+
+    // Validate time synchronization
+    if let Some(m_time) = &manifest.time {
+        time::validate(m_time)?;
+    }
+
+    // Validate domain join
+    if let Some(m_join) = &manifest.domain_join {
+        domain_join::validate(manifest, m_join)?;
+    }
+
+    // Validate resource limits of install run
+    if let Some(m_runtime) = &manifest.runtime {
+        m_runtime.properties()?;
+    }
+
+    // Validate boot layout for portable target
+    if manifest.is_portable() {
+        portable::boot(manifest)?;
+    }
 
     // Check all commands used by ALI before ch-root
     for cmd in constants::REQUIRED_COMMANDS {
@@ -28,11 +95,12 @@ pub fn validate(
         }
     }
 
-    // Check mkfs for rootfs
+    // Check mkfs for rootfs, which is not created for diskless installs
     let mkfs_rootfs = &format!("mkfs.{}", manifest.rootfs.fs_type);
-    if !shell::in_path(mkfs_rootfs) {
+    if manifest.netroot.is_none() && !shell::in_path(mkfs_rootfs) {
         return Err(AliError::BadManifest(format!(
-            "no such program to create rootfs: {mkfs_rootfs}"
+            "no such program to create rootfs: {mkfs_rootfs}{}",
+            aliases::hint(Kind::FsType, &manifest.rootfs.fs_type),
         )));
     }
 
@@ -44,12 +112,24 @@ pub fn validate(
                 let device = &fs.device;
 
                 return Err(AliError::BadManifest(format!(
-                    "no such program to create filesystem for device {device}: {mkfs_cmd}"
+                    "no such program to create filesystem for device {device}: {mkfs_cmd}{}",
+                    aliases::hint(Kind::FsType, &fs.fs_type),
                 )));
             }
         }
     }
 
+    // Validate proxy, and that we can open SSH tunnels
+    if let Some(proxy) = &manifest.proxy {
+        if let Proxy::Ssh { .. } = Proxy::try_from(proxy.as_str())? {
+            if !shell::in_path("ssh") {
+                return Err(AliError::Validation(
+                    "command ssh not in path (required by proxy)".to_string(),
+                ));
+            }
+        }
+    }
+
     // Validate ali-rs hooks
     hooks::validate(manifest, install_location)?;
 
@@ -68,5 +148,18 @@ pub fn validate(
         )));
     }
 
+    if let Some(ref locale) = manifest.locale {
+        let valid = |c: char| c.is_ascii_alphanumeric() || "_-.@".contains(c);
+        if !locale.contains('.') || !locale.chars().all(valid) {
+            return Err(AliError::BadManifest(format!(
+                "bad locale {locale}, expecting <LANG>.<CHARSET>, e.g. de_DE.UTF-8"
+            )));
+        }
+    }
+
+    // Detect conflicting kernel parameters early, with device paths
+    // standing in for identifiers of yet-to-be formatted devices
+    crate::ali::apply::cmdline::build(manifest, &DeviceMap::default())?;
+
     Ok(ValidationReport { block_devs })
 }
@@ -0,0 +1,139 @@
+use crate::ali::{
+    Manifest,
+    ManifestNetRoot,
+    NetRootProtocol,
+};
+use crate::errors::AliError;
+
+const MSG: &str = "netroot validation failed";
+
+/// Validates diskless manifest. Local disk stages are skipped for netroot,
+/// so local block device keys are not allowed.
+pub fn validate(
+    manifest: &Manifest,
+    netroot: &ManifestNetRoot,
+) -> Result<(), AliError> {
+    let local_keys = [
+        ("disks", manifest.disks.is_some()),
+        ("dm", manifest.device_mappers.is_some()),
+        ("fs", manifest.filesystems.is_some()),
+        ("mountpoints", manifest.mountpoints.is_some()),
+        ("swap", manifest.swap.is_some()),
+    ];
+
+    for (key, found) in local_keys {
+        if found {
+            return Err(AliError::BadManifest(format!(
+                "{MSG}: key `{key}` is not allowed for diskless installs"
+            )));
+        }
+    }
+
+    let rootfs = &manifest.rootfs;
+    match netroot.protocol {
+        NetRootProtocol::Nfs => {
+            if !matches!(rootfs.fs_type.as_str(), "nfs" | "nfs4") {
+                return Err(AliError::BadManifest(format!(
+                    "{MSG}: expecting rootfs fstype nfs, got {}",
+                    rootfs.fs_type
+                )));
+            }
+
+            match rootfs.device.split_once(':') {
+                Some((server, export))
+                    if !server.is_empty() && export.starts_with('/') => {}
+                _ => {
+                    return Err(AliError::BadManifest(format!(
+                        "{MSG}: expecting rootfs device <SERVER>:<EXPORT>, got {}",
+                        rootfs.device
+                    )));
+                }
+            }
+
+            let iscsi_keys = [
+                ("target", netroot.target.is_some()),
+                ("portal", netroot.portal.is_some()),
+                ("initiator", netroot.initiator.is_some()),
+            ];
+
+            for (key, found) in iscsi_keys {
+                if found {
+                    return Err(AliError::BadManifest(format!(
+                        "{MSG}: key `{key}` is only valid for iscsi"
+                    )));
+                }
+            }
+        }
+
+        NetRootProtocol::Iscsi => {
+            if rootfs.fs_type.starts_with("nfs") {
+                return Err(AliError::BadManifest(format!(
+                    "{MSG}: rootfs fstype {} is not valid for iscsi",
+                    rootfs.fs_type
+                )));
+            }
+
+            if netroot.target.is_none() || netroot.portal.is_none() {
+                return Err(AliError::BadManifest(format!(
+                    "{MSG}: iscsi requires both `target` and `portal`"
+                )));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_validate_netroot() {
+    let nfs = r#"
+version: 1
+rootfs:
+  device: 10.0.0.1:/srv/arch
+  fstype: nfs
+netroot:
+  type: nfs
+"#;
+
+    let iscsi = r#"
+version: 1
+rootfs:
+  device: /dev/sda1
+  fstype: ext4
+netroot:
+  type: iscsi
+  target: iqn.2024-01.com.example:arch
+  portal: 10.0.0.1
+"#;
+
+    for yaml in [nfs, iscsi] {
+        let manifest = crate::ali::parse(yaml).unwrap();
+        let netroot = manifest.netroot.as_ref().unwrap();
+        validate(&manifest, netroot).expect("expecting valid netroot");
+    }
+
+    let should_err = [
+        // Local disks
+        format!("{nfs}disks: []\n"),
+        // Bad NFS export
+        nfs.replace("10.0.0.1:/srv/arch", "/dev/sda1"),
+        nfs.replace("10.0.0.1:/srv/arch", "10.0.0.1:srv"),
+        // Not NFS
+        nfs.replace("fstype: nfs", "fstype: ext4"),
+        // iSCSI keys with NFS
+        format!("{nfs}  portal: 10.0.0.1\n"),
+        // Missing portal
+        iscsi.replace("  portal: 10.0.0.1\n", ""),
+        // NFS with iSCSI
+        iscsi.replace("fstype: ext4", "fstype: nfs"),
+    ];
+
+    for yaml in should_err {
+        let manifest = crate::ali::parse(&yaml).unwrap();
+        let netroot = manifest.netroot.as_ref().unwrap();
+        assert!(
+            validate(&manifest, netroot).is_err(),
+            "expecting error for {yaml}"
+        );
+    }
+}
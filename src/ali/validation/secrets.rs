@@ -0,0 +1,100 @@
+use crate::ali::Manifest;
+use crate::errors::AliError;
+use crate::utils::fs::file_exists;
+use crate::utils::secrets::{
+    self,
+    ProviderConfig,
+};
+
+const MSG: &str = "secrets validation failed";
+
+/// Validates manifest key `secrets`, and secret references
+/// in manifest keys other than hooks
+pub fn validate(
+    manifest: &Manifest,
+    m_providers: &[ProviderConfig],
+) -> Result<(), AliError> {
+    for provider in m_providers {
+        if let ProviderConfig::Env {
+            prefix: Some(prefix),
+        } = provider
+        {
+            if prefix.is_empty() {
+                return Err(AliError::BadManifest(format!(
+                    "{MSG}: empty env prefix would expose all variables"
+                )));
+            }
+        }
+
+        for file in provider.files() {
+            if !file_exists(file) {
+                return Err(AliError::BadManifest(format!(
+                    "{MSG}: no such file {file}"
+                )));
+            }
+        }
+    }
+
+    let refs = [("rootpasswd", &manifest.rootpasswd)];
+    for (key, value) in refs {
+        let Some(name) = value.as_deref().and_then(secrets::name) else {
+            continue;
+        };
+
+        if !secrets::is_valid_name(name) {
+            return Err(AliError::BadManifest(format!(
+                "{MSG}: bad secret name {name} in {key}"
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_validate_secrets() {
+    let yaml = r#"
+rootfs:
+  device: /dev/sda1
+  fs_type: ext4
+rootpasswd: secret://root-hash
+"#;
+    let mut manifest = crate::ali::parse(yaml).unwrap();
+
+    let should_pass = vec![
+        vec![],
+        vec![
+            ProviderConfig::Env { prefix: None },
+            ProviderConfig::File {
+                dir: "./test_assets".to_string(),
+            },
+        ],
+    ];
+
+    let should_err = vec![
+        vec![ProviderConfig::Env {
+            prefix: Some(String::new()),
+        }],
+        vec![ProviderConfig::Sops {
+            file: "./test_assets/missing.sops.yaml".to_string(),
+        }],
+        vec![ProviderConfig::Age {
+            file: "./test_assets/foo.conf".to_string(),
+            identity: "./test_assets/missing.key".to_string(),
+        }],
+    ];
+
+    for m_providers in should_pass {
+        validate(&manifest, &m_providers).unwrap();
+    }
+
+    for m_providers in should_err {
+        assert!(
+            validate(&manifest, &m_providers).is_err(),
+            "unexpected ok for {m_providers:?}"
+        );
+    }
+
+    manifest.rootpasswd = Some("secret://../shadow".to_string());
+    assert!(validate(&manifest, &[]).is_err());
+}
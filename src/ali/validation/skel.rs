@@ -0,0 +1,91 @@
+use std::path::{
+    Component,
+    Path,
+};
+
+use crate::ali::ManifestSkelFile;
+use crate::errors::AliError;
+use crate::utils::fs::file_exists;
+
+const MSG: &str = "skel validation failed";
+
+/// Validates manifest key `skel`
+pub fn validate(m_skel: &[ManifestSkelFile]) -> Result<(), AliError> {
+    for file in m_skel {
+        let path = &file.path;
+        let relative = Path::new(path)
+            .components()
+            .all(|c| matches!(c, Component::Normal(_) | Component::CurDir));
+
+        if path.trim_matches('/').is_empty() || !relative {
+            return Err(AliError::BadManifest(format!(
+                "{MSG}: path {path} is not relative to /etc/skel"
+            )));
+        }
+
+        file.mode()?;
+
+        match (file.is_dir(), &file.content, &file.source) {
+            (true, None, None) => {}
+            (true, _, _) => {
+                return Err(AliError::BadManifest(format!(
+                    "{MSG}: directory {path} cannot have content or source"
+                )));
+            }
+            (false, Some(_), None) | (false, None, Some(_)) => {}
+            (false, _, _) => {
+                return Err(AliError::BadManifest(format!(
+                    "{MSG}: file {path} needs either content or source"
+                )));
+            }
+        }
+
+        if file.vars.is_some() && file.source.is_none() {
+            return Err(AliError::BadManifest(format!(
+                "{MSG}: vars of file {path} requires source"
+            )));
+        }
+
+        if let Some(ref source) = file.source {
+            if !file_exists(source) {
+                return Err(AliError::BadManifest(format!(
+                    "{MSG}: no such template {source} for file {path}"
+                )));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_validate_skel() {
+    let valid = r#"
+- path: .bashrc
+  content: alias ll='ls -l'
+- path: .config/
+  mode: "0700"
+- path: .profile
+  source: /etc/hostname
+"#;
+
+    let m_skel: Vec<ManifestSkelFile> = serde_yaml::from_str(valid).unwrap();
+    validate(&m_skel).unwrap();
+
+    let should_err = [
+        "[{path: /etc/passwd, content: foo}]",
+        "[{path: ../.bashrc, content: foo}]",
+        "[{path: /, content: foo}]",
+        "[{path: .bashrc}]",
+        "[{path: .bashrc, content: foo, source: /etc/hostname}]",
+        "[{path: .config/, content: foo}]",
+        "[{path: .bashrc, content: foo, mode: '0999'}]",
+        "[{path: .bashrc, content: foo, vars: {foo: bar}}]",
+        "[{path: .bashrc, source: /ali-rs/no/such/file}]",
+    ];
+
+    for yaml in should_err {
+        let m_skel: Vec<ManifestSkelFile> = serde_yaml::from_str(yaml).unwrap();
+        assert!(validate(&m_skel).is_err(), "{yaml}");
+    }
+}
@@ -0,0 +1,131 @@
+use crate::ali::{
+    ManifestKnownHost,
+    ManifestSsh,
+    ManifestSshHost,
+};
+use crate::errors::AliError;
+
+const MSG: &str = "ssh validation failed";
+
+/// Validates manifest key `ssh`
+pub fn validate(m_ssh: &ManifestSsh) -> Result<(), AliError> {
+    validate_files(m_ssh.config.as_deref(), m_ssh.known_hosts.as_deref())?;
+
+    for (user, m_user) in m_ssh.users.iter().flatten() {
+        let valid = |c: char| {
+            c.is_ascii_lowercase() || c.is_ascii_digit() || "_-".contains(c)
+        };
+
+        if user.is_empty()
+            || user.starts_with('-')
+            || !user.chars().all(valid)
+        {
+            return Err(AliError::BadManifest(format!(
+                "{MSG}: bad user name {user}"
+            )));
+        }
+
+        validate_files(
+            m_user.config.as_deref(),
+            m_user.known_hosts.as_deref(),
+        )?;
+    }
+
+    Ok(())
+}
+
+fn validate_files(
+    config: Option<&[ManifestSshHost]>,
+    known_hosts: Option<&[ManifestKnownHost]>,
+) -> Result<(), AliError> {
+    for host in config.into_iter().flatten() {
+        if host.host.trim().is_empty() || host.host.contains('\n') {
+            return Err(AliError::BadManifest(format!(
+                "{MSG}: bad Host pattern `{}`",
+                host.host
+            )));
+        }
+
+        for (keyword, value) in &host.options {
+            let valid = !keyword.is_empty()
+                && keyword.chars().all(|c| c.is_ascii_alphanumeric())
+                && !value.trim().is_empty()
+                && !value.contains('\n');
+
+            if !valid {
+                return Err(AliError::BadManifest(format!(
+                    "{MSG}: bad option `{keyword} {value}` for Host {}",
+                    host.host
+                )));
+            }
+        }
+    }
+
+    for entry in known_hosts.into_iter().flatten() {
+        if entry.host.is_empty()
+            || entry.host.contains(char::is_whitespace)
+            || entry.host.split(',').any(str::is_empty)
+        {
+            return Err(AliError::BadManifest(format!(
+                "{MSG}: bad known host `{}`",
+                entry.host
+            )));
+        }
+
+        let parts: Vec<&str> = entry.key.split_whitespace().collect();
+        let key_type = ["ssh-", "ecdsa-", "sk-"];
+        let valid = matches!(
+            parts.as_slice(),
+            [t, _, ..] if key_type.iter().any(|prefix| t.starts_with(prefix))
+        );
+
+        if !valid || entry.key.contains('\n') {
+            return Err(AliError::BadManifest(format!(
+                "{MSG}: bad public key for known host {}: {}",
+                entry.host, entry.key
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_validate_ssh() {
+    let valid = r#"
+config:
+  - host: "*.internal bastion"
+    options:
+      User: deploy
+known_hosts:
+  - host: git.internal,[git.internal]:2222
+    key: ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIFoo comment
+users:
+  alice_1:
+    config:
+      - host: git.internal
+        options:
+          IdentityFile: ~/.ssh/id_git
+"#;
+
+    let m_ssh: ManifestSsh = serde_yaml::from_str(valid).unwrap();
+    validate(&m_ssh).unwrap();
+
+    let should_err = [
+        "config: [{host: '', options: {User: deploy}}]",
+        "config: [{host: foo, options: {'User Name': deploy}}]",
+        "config: [{host: foo, options: {User: ''}}]",
+        "known_hosts: [{host: 'foo bar', key: ssh-ed25519 AAAA}]",
+        "known_hosts: [{host: 'foo,', key: ssh-ed25519 AAAA}]",
+        "known_hosts: [{host: foo, key: ssh-ed25519}]",
+        "known_hosts: [{host: foo, key: rsa AAAA}]",
+        "users: {Alice: {}}",
+        "users: {-alice: {}}",
+        "users: {alice: {known_hosts: [{host: foo, key: AAAA}]}}",
+    ];
+
+    for yaml in should_err {
+        let m_ssh: ManifestSsh = serde_yaml::from_str(yaml).unwrap();
+        assert!(validate(&m_ssh).is_err(), "expecting error for {yaml}");
+    }
+}
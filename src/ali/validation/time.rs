@@ -0,0 +1,139 @@
+use crate::ali::ManifestTime;
+use crate::errors::AliError;
+use crate::utils::suggest;
+
+const MSG: &str = "time validation failed";
+
+const TRANSPORTS: &[&str] = &["UDPv4", "UDPv6", "L2"];
+const TIME_STAMPINGS: &[&str] = &["hardware", "software"];
+
+/// Validates manifest key `time`
+pub fn validate(m_time: &ManifestTime) -> Result<(), AliError> {
+    if m_time.chrony.is_none() && m_time.ptp.is_none() {
+        return Err(AliError::BadManifest(format!(
+            "{MSG}: expecting chrony, ptp, or both"
+        )));
+    }
+
+    if let Some(ref m_chrony) = m_time.chrony {
+        if m_chrony.servers.is_empty() {
+            return Err(AliError::BadManifest(format!(
+                "{MSG}: chrony needs at least 1 server"
+            )));
+        }
+
+        for server in &m_chrony.servers {
+            let is_host = !server.is_empty()
+                && !server.starts_with(['-', '.'])
+                && server
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "-.:".contains(c));
+
+            if !is_host {
+                return Err(AliError::BadManifest(format!(
+                    "{MSG}: bad chrony server {server}"
+                )));
+            }
+        }
+    }
+
+    if let Some(ref m_ptp) = m_time.ptp {
+        let interface = &m_ptp.interface;
+        let is_interface = !interface.is_empty()
+            && interface.len() < 16
+            && !interface.contains(['/', ' ']);
+
+        if !is_interface {
+            return Err(AliError::BadManifest(format!(
+                "{MSG}: bad ptp interface {interface}"
+            )));
+        }
+
+        if let Some(ref transport) = m_ptp.transport {
+            if !TRANSPORTS.contains(&transport.as_str()) {
+                return Err(AliError::BadManifest(format!(
+                    "{MSG}: bad ptp transport {transport}{}, expecting one of {}",
+                    suggest::hint(transport, TRANSPORTS.iter().copied()),
+                    TRANSPORTS.join(", "),
+                )));
+            }
+        }
+
+        if let Some(ref time_stamping) = m_ptp.time_stamping {
+            if !TIME_STAMPINGS.contains(&time_stamping.as_str()) {
+                return Err(AliError::BadManifest(format!(
+                    "{MSG}: bad ptp time_stamping {time_stamping}{}, expecting one of {}",
+                    suggest::hint(
+                        time_stamping,
+                        TIME_STAMPINGS.iter().copied(),
+                    ),
+                    TIME_STAMPINGS.join(", "),
+                )));
+            }
+        }
+
+        // Without PTP hardware clock, ptp4l steers the system clock itself
+        if !m_ptp.is_hardware() && m_time.chrony.is_some() {
+            return Err(AliError::BadManifest(format!(
+                "{MSG}: ptp with software time stamping conflicts with chrony"
+            )));
+        }
+
+        for module in m_ptp.modules.iter().flatten() {
+            let is_module = !module.is_empty()
+                && module
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "_-".contains(c));
+
+            if !is_module {
+                return Err(AliError::BadManifest(format!(
+                    "{MSG}: bad kernel module {module}"
+                )));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_validate_time() {
+    let parse = |yaml: &str| -> ManifestTime {
+        serde_yaml::from_str(yaml).unwrap()
+    };
+
+    let should_pass = vec![
+        "chrony: { servers: [time.cloudflare.com, nts.netnod.se] }",
+        "chrony: { servers: [ntp.example.com], nts: false }",
+        "ptp: { interface: eth0 }",
+        "ptp: { interface: eth0, time_stamping: software, transport: L2 }",
+        r#"
+chrony: { servers: [time.cloudflare.com] }
+ptp: { interface: enp1s0, domain: 24, modules: [igb] }
+"#,
+    ];
+
+    let should_err = vec![
+        "{}",
+        "chrony: { servers: [] }",
+        "chrony: { servers: ['time.example.com; rm -rf /'] }",
+        "ptp: { interface: '' }",
+        "ptp: { interface: eth0, transport: UDP }",
+        "ptp: { interface: eth0, time_stamping: onestep }",
+        "ptp: { interface: eth0, modules: ['igb ixgbe'] }",
+        r#"
+chrony: { servers: [time.cloudflare.com] }
+ptp: { interface: eth0, time_stamping: software }
+"#,
+    ];
+
+    for yaml in should_pass {
+        if let Err(err) = validate(&parse(yaml)) {
+            panic!("unexpected error for {yaml}: {err}");
+        }
+    }
+
+    for yaml in should_err {
+        assert!(validate(&parse(yaml)).is_err(), "unexpected ok for {yaml}");
+    }
+}
@@ -0,0 +1,174 @@
+use std::collections::HashMap;
+
+use serde_yaml::Value;
+
+use crate::errors::AliError;
+
+const KEY_VARS: &str = "vars";
+
+/// Prefix of environment variables used as manifest variables,
+/// e.g. `ALI_VAR_disk=/dev/vda` sets variable `disk`
+pub const ENV_PREFIX: &str = "ALI_VAR_";
+
+const OPEN: &str = "{{";
+const CLOSE: &str = "}}";
+
+/// Removes `vars` block from manifest `value`, and substitutes
+/// `{{ name }}` in all string values.
+///
+/// Variables are looked up from `overrides` (i.e. `--var`),
+/// then environment `ALI_VAR_<name>`, then the `vars` block.
+pub fn substitute(
+    value: &mut Value,
+    overrides: &HashMap<String, String>,
+) -> Result<(), AliError> {
+    let vars = match value.as_mapping_mut().and_then(|m| m.remove(KEY_VARS)) {
+        None => HashMap::new(),
+        Some(vars) => parse_vars(vars)?,
+    };
+
+    let lookup = |name: &str| {
+        overrides
+            .get(name)
+            .cloned()
+            .or_else(|| std::env::var(format!("{ENV_PREFIX}{name}")).ok())
+            .or_else(|| vars.get(name).cloned())
+    };
+
+    walk(value, &lookup)
+}
+
+fn parse_vars(vars: Value) -> Result<HashMap<String, String>, AliError> {
+    let Value::Mapping(mapping) = vars else {
+        return Err(AliError::BadManifest(format!(
+            "key `{KEY_VARS}` is not a mapping"
+        )));
+    };
+
+    let mut result = HashMap::new();
+    for (k, v) in mapping {
+        let (Some(k), Some(v)) = (scalar_string(&k), scalar_string(&v)) else {
+            return Err(AliError::BadManifest(format!(
+                "bad variable {k:?}: only scalar variables are supported"
+            )));
+        };
+
+        result.insert(k, v);
+    }
+
+    Ok(result)
+}
+
+fn scalar_string(v: &Value) -> Option<String> {
+    match v {
+        Value::String(s) => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        Value::Bool(b) => Some(b.to_string()),
+        _ => None,
+    }
+}
+
+fn walk<F>(value: &mut Value, lookup: &F) -> Result<(), AliError>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    match value {
+        Value::String(s) => *s = expand(s, lookup)?,
+        Value::Sequence(seq) => {
+            for v in seq {
+                walk(v, lookup)?;
+            }
+        }
+        Value::Mapping(mapping) => {
+            for (_, v) in mapping.iter_mut() {
+                walk(v, lookup)?;
+            }
+        }
+        Value::Tagged(tagged) => walk(&mut tagged.value, lookup)?,
+        Value::Null | Value::Bool(_) | Value::Number(_) => {}
+    }
+
+    Ok(())
+}
+
+/// Expands all `{{ name }}` in `s`
+fn expand<F>(s: &str, lookup: &F) -> Result<String, AliError>
+where
+    F: Fn(&str) -> Option<String>,
+{
+    let mut result = String::with_capacity(s.len());
+    let mut rest = s;
+
+    while let Some(start) = rest.find(OPEN) {
+        let Some(len) = rest[start..].find(CLOSE) else {
+            break;
+        };
+
+        let name = rest[start + OPEN.len()..start + len].trim();
+        let value = lookup(name).ok_or(AliError::BadManifest(format!(
+            "undefined variable `{name}` in \"{s}\""
+        )))?;
+
+        result.push_str(&rest[..start]);
+        result.push_str(&value);
+        rest = &rest[start + len + CLOSE.len()..];
+    }
+
+    result.push_str(rest);
+
+    Ok(result)
+}
+
+#[test]
+fn test_substitute() {
+    let yaml = r#"
+vars:
+  hostname: foo
+  disk: /dev/vda
+  size: 8
+hostname: "{{ hostname }}"
+disks:
+  - device: "{{disk}}"
+    partitions:
+      - size: "{{ size }}G"
+chroot:
+  - "echo {{ hostname }} > /etc/hostname-{{ disk_suffix }}"
+"#;
+
+    let mut value: Value = serde_yaml::from_str(yaml).unwrap();
+    let overrides = HashMap::from([
+        ("hostname".to_string(), "bar".to_string()),
+        ("disk_suffix".to_string(), "a".to_string()),
+    ]);
+
+    substitute(&mut value, &overrides).unwrap();
+
+    let expected: Value = serde_yaml::from_str(
+        r#"
+hostname: bar
+disks:
+  - device: /dev/vda
+    partitions:
+      - size: 8G
+chroot:
+  - "echo bar > /etc/hostname-a"
+"#,
+    )
+    .unwrap();
+
+    assert_eq!(expected, value);
+
+    let should_err = [
+        "hostname: \"{{ undefined_ali_var }}\"",
+        "vars: [foo]",
+        "vars:\n  foo: [bar]",
+    ];
+
+    for yaml in should_err {
+        let mut value: Value = serde_yaml::from_str(yaml).unwrap();
+        assert!(
+            substitute(&mut value, &HashMap::new()).is_err(),
+            "expecting error for {yaml}"
+        );
+    }
+}
@@ -1,11 +1,27 @@
 use clap::{
+    ArgAction,
     Args,
+    CommandFactory,
+    FromArgMatches,
     Parser,
     Subcommand,
+    ValueEnum,
 };
+use serde::Deserialize;
 
+use crate::ali::{
+    InstallTarget,
+    ManifestFormat,
+};
+use crate::config;
+use crate::constants::defaults;
 use crate::errors::AliError;
+use crate::types::blockdev::parse_human_bytes;
 use crate::types::stage;
+use crate::utils::checksum;
+use crate::utils::progress::ProgressMode;
+use crate::utils::snapshot;
+use crate::utils::watchdog;
 
 #[derive(Debug, Parser)]
 #[clap(
@@ -17,7 +33,12 @@ pub struct Cli {
     #[command(subcommand)]
     pub commands: Option<Commands>,
 
-    /// Path to manifest file
+    /// User config file of default flags, overridden by flags given
+    /// on the command line (default ~/.config/ayi/config.toml)
+    #[arg(global = true, long = "config", value_name = "FILE")]
+    pub config: Option<String>,
+
+    /// Path to manifest file, or HTTP(S) URL to download manifest from
     #[arg(
         global = true,
         short = 'f',
@@ -26,6 +47,125 @@ pub struct Cli {
         value_parser = validate_filename,
     )]
     pub manifest: String,
+
+    /// Manifest file format, detected from file extension if not given
+    #[arg(global = true, long = "format", value_enum)]
+    pub format: Option<ManifestFormat>,
+
+    /// Sets manifest variable, overriding manifest key `vars`
+    /// and env `ALI_VAR_<KEY>`, e.g. --var disk=/dev/vda
+    #[arg(global = true, long = "var", value_parser = parse_var)]
+    pub vars: Vec<(String, String)>,
+
+    /// Pins manifest to hex-encoded SHA-256 checksum.
+    /// ali-rs aborts if the manifest does not match
+    #[arg(global = true, long = "sha256", value_parser = parse_sha256)]
+    pub sha256: Option<String>,
+
+    /// Debugs hooks by snapshotting files under DIRS (default /etc)
+    /// of the target before and after each hook, and logging which files
+    /// each hook created, modified, touched, or deleted
+    #[arg(
+        global = true,
+        long = "snapshot-hooks",
+        value_name = "DIRS",
+        num_args(0..=1),
+        require_equals = true,
+        value_delimiter = ',',
+        value_parser = parse_snapshot_dir,
+    )]
+    pub snapshot_hooks: Option<Vec<String>>,
+
+    /// Backs up files before file-editing hooks (e.g. @uncomment)
+    /// write them, as if all such hooks were given flag `backup`.
+    /// Backups are restored with `ali-rs hooks undo`
+    #[arg(global = true, long = "backup-hooks")]
+    pub backup_hooks: bool,
+
+    /// Limits output of each hook to SIZE, e.g. 64KiB or 1MB (0 for
+    /// no limit). Longer output is truncated with a notice, and the full
+    /// output is stored gzip-compressed next to the log file
+    #[arg(
+        global = true,
+        long = "hook-output-limit",
+        value_name = "SIZE",
+        default_value = "256KiB",
+        value_parser = parse_output_limit,
+    )]
+    pub hook_output_limit: usize,
+
+    /// Limits download bandwidth, e.g. 500K or 2MiB (bytes per second)
+    #[arg(global = true, long = "limit-rate", value_parser = parse_limit_rate)]
+    pub limit_rate: Option<u64>,
+
+    /// Fetches remote sources through a SOCKS5 proxy or SSH bastion,
+    /// e.g. socks5://127.0.0.1:1080 or ssh://user@bastion.
+    /// Overrides manifest key `proxy`
+    #[arg(global = true, long = "proxy")]
+    pub proxy: Option<String>,
+
+    /// Screen-reader friendly output: no colors, progress as plain
+    /// sentences, and beeps on prompts and completion.
+    /// Sentences are also spoken with espeak-ng if installed
+    #[arg(global = true, long = "accessible")]
+    pub accessible: bool,
+
+    /// Colored output: auto (default), always, or never
+    #[arg(
+        global = true,
+        long = "color",
+        value_enum,
+        default_value_t = ColorMode::Auto
+    )]
+    pub color: ColorMode,
+
+    /// Also writes report (or error report) of `ali-rs apply`
+    /// to new file DIR/report-<UNIX_SECS>.json
+    #[arg(global = true, long = "report-dir", value_name = "DIR")]
+    pub report_dir: Option<String>,
+
+    /// Prints enabled features and external programs required
+    /// by each subsystem at runtime, then exits
+    #[arg(long = "capabilities")]
+    pub capabilities: bool,
+
+    /// Progress output of current stage, step, and elapsed time
+    #[arg(global = true, long = "progress", value_enum, default_value_t = ProgressMode::Plain)]
+    pub progress: ProgressMode,
+
+    /// Increases log verbosity on stderr (-v for debug, -vv for trace)
+    #[arg(global = true, short = 'v', long = "verbose", action = ArgAction::Count)]
+    pub verbose: u8,
+
+    /// Debug log file, which is always written, and is copied
+    /// to /var/log/ali-rs/ in the new system after apply
+    #[arg(global = true, long = "log-file", default_value_t = String::from(defaults::LOG_FILE))]
+    pub log_file: String,
+
+    /// Writes compact JSON status (error class, failed stage,
+    /// and report and log paths) to file descriptor N when done
+    #[arg(global = true, long = "status-fd", value_name = "N")]
+    pub status_fd: Option<u32>,
+
+    /// Opt-in: POSTs crash file to HTTP(S) endpoint URL if ali-rs panics.
+    /// Crash files are always written to the directory of the log file
+    #[arg(global = true, long = "crash-report-url", value_name = "URL")]
+    pub crash_report_url: Option<String>,
+
+    /// Warns when an external command made no progress (no I/O and
+    /// no CPU time) for SECS seconds. 0 disables the watchdog
+    #[arg(global = true, long = "watchdog", value_name = "SECS", default_value_t = watchdog::DEFAULT_IDLE_SECS)]
+    pub watchdog: u64,
+
+    /// Asks whether to kill commands flagged by the watchdog,
+    /// if stdin is a terminal
+    #[arg(global = true, long = "watchdog-prompt")]
+    pub watchdog_prompt: bool,
+
+    /// Kills external commands still running after SECS seconds,
+    /// failing the current stage. 0 disables the timeout
+    #[arg(global = true, long = "cmd-timeout", value_name = "SECS", default_value_t = 0)]
+    pub cmd_timeout: u64,
 }
 
 #[derive(Debug, Subcommand)]
@@ -38,6 +178,34 @@ pub enum Commands {
 
     /// Runs ali-rs hooks
     Hooks(ArgsHooks),
+
+    /// Re-opens an existing installation described by manifest:
+    /// opens LUKS devices, activates LVM VGs, and mounts filesystems
+    Rescue(ArgsRescue),
+
+    /// Interactive installer: asks for disk, partitioning preset,
+    /// hostname, timezone, and users, then writes (and optionally
+    /// applies) a manifest
+    #[cfg(feature = "tui")]
+    Tui(ArgsTui),
+
+    /// Generates best-effort manifest reproducing an installed system
+    /// (mounts, enabled services, locale, and explicit packages)
+    Generate(ArgsGenerate),
+
+    /// Prints shell completion script, with completion of hook keys
+    /// and their keyword arguments
+    Completions(ArgsCompletions),
+
+    /// Prints what `apply` would do with the manifest, without touching
+    /// any disks: install location, disks to be wiped, stages, packages,
+    /// and the command to apply the exact same manifest
+    Plan(ArgsPlan),
+
+    /// Checks a completed install mounted at the install location against
+    /// the manifest: partitions, filesystems, mounts, files, and services.
+    /// Prints a pass/fail report, and fails if any check fails
+    Verify,
 }
 
 #[derive(Debug, Args)]
@@ -46,28 +214,235 @@ pub struct ArgsApply {
     #[arg(long = "no-validate")]
     pub no_validate: bool,
 
+    /// Do not check the live system (root, boot mode, mirrors,
+    /// mounted target disks, and required commands) before applying
+    #[arg(long = "no-preflight")]
+    pub no_preflight: bool,
+
+    /// Kind of machine to install for, overriding manifest key `target`.
+    /// `portable` installs a USB stick bootable on both BIOS and UEFI machines
+    #[arg(long = "target", value_enum)]
+    pub target: Option<InstallTarget>,
+
+    /// Wipe manifest disks without asking to type each device name
+    #[arg(short = 'y', long = "yes")]
+    pub yes: bool,
+
     /// Overwrite existing system block devices (not recommended).
     /// All disks to be used must be declared in manifests,
     /// and existing system devices will not be considered
     #[arg(short = 'o', long = "overwrite", default_value_t = false)]
     pub overwrite: bool,
 
-    /// Explicit stages to run
-    #[arg(short = 's', long = "stages", num_args(0..))]
+    /// Explicit stages to run, e.g. --only chroot-hooks
+    #[arg(
+        short = 's',
+        long = "stages",
+        visible_alias = "only",
+        num_args(0..)
+    )]
     pub stages: Option<Vec<stage::Stage>>,
 
-    /// ALI stages to skip
+    /// ALI stages to skip, e.g. --skip disks
     #[arg(long = "skip", num_args(0..))]
     pub skip_stages: Vec<stage::Stage>,
 
+    /// Runs stages from STAGE onwards, e.g. --from bootstrap
+    /// on an already partitioned and mounted machine
+    #[arg(long = "from", value_name = "STAGE", conflicts_with = "stages")]
+    pub from_stage: Option<stage::Stage>,
+
+    /// Applies only target NAME of a multi-target session (`primary`
+    /// for the manifest itself), e.g. to resume it with --from
+    #[arg(long = "only-target", value_name = "NAME")]
+    pub only_target: Option<String>,
+
     /// Dry-run, ali-rs will not commit any changes to disks,
     /// and will just print steps to be performed
     #[arg(global = true, short = 'n', default_value_t = false)]
     pub dry_run: bool,
+
+    /// Starts sshd on the live installer before applying, so that
+    /// operators can attach remotely. Root password is random
+    /// (printed to console) unless --ssh-password-file or --ssh-key is given
+    #[arg(long = "enable-ssh")]
+    pub enable_ssh: bool,
+
+    /// File with root password for --enable-ssh, or `-` for stdin,
+    /// so that the password is not in the process list
+    #[arg(
+        long = "ssh-password-file",
+        value_name = "FILE",
+        requires = "enable_ssh"
+    )]
+    pub ssh_password_file: Option<String>,
+
+    /// Public key (or path to public key file) for --enable-ssh,
+    /// disables password login
+    #[arg(
+        long = "ssh-key",
+        requires = "enable_ssh",
+        conflicts_with = "ssh_password_file"
+    )]
+    pub ssh_key: Option<String>,
+
+    /// Prints a terminal QR code of the run summary (or failure)
+    /// at the end of the run, for capture with a phone camera
+    #[arg(long = "qr")]
+    pub qr: bool,
+
+    /// Ranks HTTPS mirrors of COUNTRY (e.g. DE) with reflector
+    /// before pacstrap, which copies the mirrorlist to the new system
+    #[arg(long = "mirror-country", value_name = "COUNTRY")]
+    pub mirror_country: Option<String>,
+
+    /// Reviewed plan from `ali-rs plan -o`. Aborts before touching
+    /// the system if the manifest or steps differ from the plan
+    #[arg(long = "plan")]
+    pub plan: Option<String>,
+
+    /// Allows --plan to destroy existing data on current disks,
+    /// which is assessed again before applying
+    #[arg(long = "allow-destroy", requires = "plan")]
+    pub allow_destroy: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct ArgsRescue {
+    /// Starts an interactive shell with arch-chroot after mounting
+    #[arg(long = "chroot")]
+    pub chroot: bool,
+
+    /// Checks filesystems before mounting, with per-device policies
+    /// from manifest key `fsck` (default `check`)
+    #[arg(long = "fsck")]
+    pub fsck: bool,
+}
+
+#[cfg(feature = "tui")]
+#[derive(Debug, Args)]
+pub struct ArgsTui {
+    /// Path to write generated manifest to
+    #[arg(short = 'o', long = "output", default_value_t = String::from("./manifest.yaml"))]
+    pub output: String,
+
+    /// Applies generated manifest after writing it
+    #[arg(long = "apply")]
+    pub apply: bool,
+
+    /// Dry-run for --apply
+    #[arg(short = 'n', requires = "apply")]
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct ArgsPlan {
+    /// Speaks Terraform/OpenTofu external program protocol: reads query
+    /// JSON object (`manifest`, `sha256`, and `var.<KEY>`) from stdin,
+    /// and prints the plan as flat JSON object of strings
+    #[arg(long = "external-json")]
+    pub external_json: bool,
+
+    /// Prints the plan as numbered steps instead of JSON
+    #[arg(long = "text", conflicts_with = "external_json")]
+    pub text: bool,
+
+    /// Saves JSON plan to file, to be applied with `ali-rs apply --plan`
+    #[arg(short = 'o', long = "out", conflicts_with = "external_json")]
+    pub out: Option<String>,
+
+    /// Reads partition tables and filesystems of current disks,
+    /// and marks each disk step as create, no-op, or destroy
+    #[arg(long = "against-current", conflicts_with = "external_json")]
+    pub against_current: bool,
+
+    /// Allows plans against current disks to destroy existing data
+    #[arg(long = "allow-destroy", requires = "against_current")]
+    pub allow_destroy: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct ArgsGenerate {
+    /// Root of installed system to inspect
+    #[arg(long = "root", default_value_t = String::from("/"))]
+    pub root: String,
+
+    /// Path to write generated manifest to, instead of stdout
+    #[arg(short = 'o', long = "output")]
+    pub output: Option<String>,
 }
 
 #[derive(Debug, Args)]
+#[command(
+    args_conflicts_with_subcommands = true,
+    disable_help_subcommand = true
+)]
 pub struct ArgsHooks {
+    #[command(subcommand)]
+    pub commands: Option<CommandsHooks>,
+
+    /// Without subcommand, runs hooks like `hooks run`
+    #[command(flatten)]
+    pub run: ArgsHooksRun,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum CommandsHooks {
+    /// Lists all hook keys with one-line usage
+    List,
+
+    /// Prints detailed usage and examples of a hook
+    Help(ArgsHooksHelp),
+
+    /// Runs ali-rs hooks
+    Run(ArgsHooksRun),
+
+    /// Prints machine-readable specs of all hooks: keys, argument
+    /// grammar, modes, preferred callers, and chroot requirements
+    Schema(ArgsHooksSchema),
+
+    /// Restores latest backups of files edited by hooks with flag `backup`
+    /// (or with --backup-hooks), one backup per file
+    Undo(ArgsHooksUndo),
+
+    /// Prints completions of partial hook command, one per line
+    #[command(hide = true)]
+    Complete(ArgsHooksComplete),
+}
+
+#[derive(Debug, Args)]
+pub struct ArgsHooksHelp {
+    /// Hook key, e.g. @quicknet
+    pub key: String,
+}
+
+#[derive(Debug, Args)]
+pub struct ArgsHooksSchema {
+    /// Prints JSON instead of YAML
+    #[arg(long = "json")]
+    pub json: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct ArgsHooksUndo {
+    /// Mountpoint of new system, whose backups are restored
+    #[arg(short = 'm', long = "mountpoint")]
+    pub mountpoint: Option<String>,
+
+    /// Prints files to be restored without restoring them
+    #[arg(short = 'n', long = "dry-run", default_value_t = false)]
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Args)]
+pub struct ArgsHooksComplete {
+    /// Partial hook command, e.g. `@uncomment PORT m`
+    #[arg(default_value_t = String::new())]
+    pub partial: String,
+}
+
+#[derive(Debug, Args)]
+pub struct ArgsHooksRun {
     /// ali-rs hooks to run
     #[arg(num_args(0..))]
     pub hooks: Vec<String>,
@@ -82,15 +457,45 @@ pub struct ArgsHooks {
 
     /// Dry-run, ali-rs will not commit any changes to disks,
     /// and will just print steps to be performed
-    #[arg(
-        global = true,
-        short = 'n',
-        long = "dry-run",
-        default_value_t = false
-    )]
+    #[arg(short = 'n', long = "dry-run", default_value_t = false)]
     pub dry_run: bool,
 }
 
+#[derive(Debug, Args)]
+pub struct ArgsCompletions {
+    /// Shell to print completion script for
+    #[arg(value_enum)]
+    pub shell: CompletionShell,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorMode {
+    Auto,
+    Always,
+    Never,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum CompletionShell {
+    Bash,
+    Zsh,
+    Fish,
+}
+
+/// Parses command line, with defaults from user config file.
+/// Exits on bad command line, like [`Parser::parse`].
+pub fn parse() -> Result<Cli, AliError> {
+    let matches = Cli::command().get_matches();
+    let mut cli = Cli::from_arg_matches(&matches).unwrap_or_else(|err| {
+        err.exit();
+    });
+
+    config::load(cli.config.as_deref())?.merge(&mut cli, &matches);
+
+    Ok(cli)
+}
+
 fn validate_filename(name: &str) -> Result<String, AliError> {
     if name.is_empty() {
         return Err(AliError::BadArgs(String::from("empty filename")));
@@ -98,3 +503,189 @@ fn validate_filename(name: &str) -> Result<String, AliError> {
 
     Ok(name.to_string())
 }
+
+fn parse_var(var: &str) -> Result<(String, String), AliError> {
+    match var.split_once('=') {
+        Some((k, v)) if !k.is_empty() => Ok((k.to_string(), v.to_string())),
+        _ => {
+            Err(AliError::BadArgs(format!(
+                "bad variable {var}, expecting KEY=VALUE"
+            )))
+        }
+    }
+}
+
+fn parse_sha256(sum: &str) -> Result<String, AliError> {
+    if !checksum::is_sha256_hex(sum) {
+        return Err(AliError::BadArgs(format!("bad sha256 checksum {sum}")));
+    }
+
+    Ok(sum.to_string())
+}
+
+fn parse_snapshot_dir(dir: &str) -> Result<String, AliError> {
+    match snapshot::is_valid_dir(dir) {
+        true => Ok(dir.trim_end_matches('/').to_string()),
+        false => {
+            Err(AliError::BadArgs(format!(
+                "snapshot directory {dir} is not an absolute path"
+            )))
+        }
+    }
+}
+
+fn parse_limit_rate(rate: &str) -> Result<u64, AliError> {
+    parse_human_bytes(rate)
+        .map(|bytes| bytes.size() as u64)
+        .map_err(|err| AliError::BadArgs(format!("bad limit rate: {err}")))
+}
+
+fn parse_output_limit(limit: &str) -> Result<usize, AliError> {
+    if limit == "0" {
+        return Ok(0);
+    }
+
+    parse_human_bytes(limit)
+        .map(|bytes| bytes.size())
+        .map_err(|err| AliError::BadArgs(format!("bad output limit: {err}")))
+}
+
+#[test]
+fn test_cli_hooks() {
+    use clap::CommandFactory;
+
+    Cli::command().debug_assert();
+
+    let parse = |args: &[&str]| {
+        let cli = Cli::try_parse_from(args).unwrap();
+        match cli.commands {
+            Some(Commands::Hooks(args)) => args,
+            _ => panic!("not hooks subcommand: {args:?}"),
+        }
+    };
+
+    let args = parse(&["ali-rs", "hooks", "list"]);
+    assert!(matches!(args.commands, Some(CommandsHooks::List)));
+
+    let args = parse(&["ali-rs", "hooks", "help", "@quicknet"]);
+    assert!(
+        matches!(args.commands, Some(CommandsHooks::Help(help)) if help.key == "@quicknet")
+    );
+
+    let args = parse(&["ali-rs", "hooks", "run", "-m", "/mnt", "@quicknet"]);
+    let Some(CommandsHooks::Run(run)) = args.commands else {
+        panic!("not hooks run");
+    };
+    assert_eq!(vec!["@quicknet"], run.hooks);
+    assert_eq!(Some("/mnt".to_string()), run.mountpoint);
+
+    // Hooks without subcommand are still run
+    let args = parse(&["ali-rs", "hooks", "-n", "@quicknet", "@mnt /mnt"]);
+    assert!(args.commands.is_none());
+    assert!(args.run.dry_run);
+    assert_eq!(vec!["@quicknet", "@mnt /mnt"], args.run.hooks);
+
+    let args = parse(&["ali-rs", "hooks", "schema", "--json"]);
+    assert!(matches!(
+        args.commands,
+        Some(CommandsHooks::Schema(schema)) if schema.json
+    ));
+
+    let args = parse(&["ali-rs", "hooks", "undo", "-n", "-m", "/mnt"]);
+    assert!(matches!(
+        args.commands,
+        Some(CommandsHooks::Undo(undo))
+            if undo.dry_run && undo.mountpoint.as_deref() == Some("/mnt")
+    ));
+
+    let args = parse(&["ali-rs", "hooks", "complete", "--", "@uncomment X m"]);
+    assert!(matches!(
+        args.commands,
+        Some(CommandsHooks::Complete(complete)) if complete.partial == "@uncomment X m"
+    ));
+
+    let cli = Cli::try_parse_from(["ali-rs", "completions", "fish"]).unwrap();
+    assert!(matches!(
+        cli.commands,
+        Some(Commands::Completions(args)) if args.shell == CompletionShell::Fish
+    ));
+
+    let cli = Cli::try_parse_from(["ali-rs", "apply", "--status-fd", "3"]);
+    assert_eq!(Some(3), cli.unwrap().status_fd);
+
+    let cli = Cli::try_parse_from(["ali-rs", "plan", "--external-json"]);
+    assert!(matches!(
+        cli.unwrap().commands,
+        Some(Commands::Plan(args)) if args.external_json
+    ));
+}
+
+#[test]
+fn test_cli_snapshot_hooks() {
+    let parse = |args: &[&str]| {
+        Cli::try_parse_from(args).map(|cli| cli.snapshot_hooks)
+    };
+
+    assert_eq!(parse(&["ali-rs", "apply"]).unwrap(), None);
+    assert_eq!(
+        parse(&["ali-rs", "apply", "--snapshot-hooks"]).unwrap(),
+        Some(vec![])
+    );
+    assert_eq!(
+        parse(&["ali-rs", "--snapshot-hooks=/etc,/usr/local/", "hooks", "list"])
+            .unwrap(),
+        Some(vec!["/etc".to_string(), "/usr/local".to_string()])
+    );
+    assert!(parse(&["ali-rs", "apply", "--snapshot-hooks=etc"]).is_err());
+}
+
+#[test]
+fn test_cli_hook_output_limit() {
+    let parse = |args: &[&str]| {
+        Cli::try_parse_from(args).map(|cli| cli.hook_output_limit)
+    };
+
+    assert_eq!(parse(&["ali-rs", "apply"]).unwrap(), 256 << 10);
+    assert_eq!(
+        parse(&["ali-rs", "apply", "--hook-output-limit", "0"]).unwrap(),
+        0
+    );
+    assert_eq!(
+        parse(&["ali-rs", "--hook-output-limit=64KiB", "hooks", "list"]).unwrap(),
+        64 << 10
+    );
+    assert!(parse(&["ali-rs", "apply", "--hook-output-limit=x"]).is_err());
+}
+
+#[test]
+fn test_cli_stages() {
+    let parse = |args: &[&str]| {
+        Cli::try_parse_from(args).map(|cli| {
+            match cli.commands {
+                Some(Commands::Apply(args)) => {
+                    (args.stages, args.skip_stages, args.from_stage)
+                }
+                _ => panic!("not apply"),
+            }
+        })
+    };
+
+    assert_eq!(
+        parse(&["ali-rs", "apply", "--only", "chroot-hooks"]).unwrap(),
+        (Some(vec![stage::Stage::ChrootUser]), vec![], None),
+    );
+    assert_eq!(
+        parse(&["ali-rs", "apply", "--skip", "disks", "--from", "bootstrap"])
+            .unwrap(),
+        (
+            None,
+            vec![stage::Stage::Mountpoints],
+            Some(stage::Stage::Bootstrap)
+        ),
+    );
+    assert!(parse(&[
+        "ali-rs", "apply", "--only", "routines", "--from", "routines"
+    ])
+    .is_err());
+    assert!(parse(&["ali-rs", "apply", "--from", "foo"]).is_err());
+}
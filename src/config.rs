@@ -0,0 +1,188 @@
+//! User config file of default CLI flags, by default
+//! `~/.config/ayi/config.toml`. Keys are long flag names, and flags
+//! given on the command line always win over the config file:
+//!
+//! ```toml
+//! color = "never"
+//! verbose = 1
+//! log-file = "/root/ali-rs.log"
+//! report-dir = "/root/ali-rs-reports"
+//! mirror-country = "DE"
+//! ```
+//!
+//! `apply --yes` is deliberately not accepted, so that the confirmation
+//! before wiping disks cannot be turned off for every future run.
+
+use std::path::PathBuf;
+
+use clap::parser::ValueSource;
+use clap::ArgMatches;
+use serde::Deserialize;
+
+use crate::cli::{
+    Cli,
+    ColorMode,
+    Commands,
+};
+use crate::errors::AliError;
+
+/// Path of config file under `$XDG_CONFIG_HOME` (or `~/.config`)
+const CONFIG_PATH: &str = "ayi/config.toml";
+
+#[derive(Debug, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Config {
+    color: Option<ColorMode>,
+    verbose: Option<u8>,
+    log_file: Option<String>,
+    report_dir: Option<String>,
+    /// Default of `ali-rs apply --mirror-country`
+    mirror_country: Option<String>,
+}
+
+/// Default config file path, if home is known
+pub fn default_path() -> Option<PathBuf> {
+    let config_home = std::env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| {
+            std::env::var_os("HOME")
+                .map(|home| PathBuf::from(home).join(".config"))
+        })?;
+
+    Some(config_home.join(CONFIG_PATH))
+}
+
+/// Loads config from `path`, or from default path if it exists
+pub fn load(path: Option<&str>) -> Result<Config, AliError> {
+    let path = match path {
+        Some(path) => PathBuf::from(path),
+        None => {
+            match default_path() {
+                Some(path) if path.exists() => path,
+                _ => return Ok(Config::default()),
+            }
+        }
+    };
+
+    let toml = std::fs::read_to_string(&path).map_err(|err| {
+        AliError::FileError(
+            err,
+            format!("failed to read config {}", path.display()),
+        )
+    })?;
+
+    parse(&toml).map_err(|err| {
+        AliError::BadArgs(format!("bad config {}: {err}", path.display()))
+    })
+}
+
+fn parse(toml: &str) -> Result<Config, toml::de::Error> {
+    toml::from_str(toml)
+}
+
+impl Config {
+    /// Sets flags of `cli` not given on the command line
+    /// (as recorded in `matches`) from config
+    pub fn merge(self, cli: &mut Cli, matches: &ArgMatches) {
+        if let (Some(color), false) = (self.color, given(matches, "color")) {
+            cli.color = color;
+        }
+
+        if let (Some(v), false) = (self.verbose, given(matches, "verbose")) {
+            cli.verbose = v;
+        }
+
+        if let (Some(f), false) = (self.log_file, given(matches, "log_file")) {
+            cli.log_file = f;
+        }
+
+        if !given(matches, "report_dir") {
+            cli.report_dir = cli.report_dir.take().or(self.report_dir);
+        }
+
+        if let Some(Commands::Apply(ref mut args)) = cli.commands {
+            if !given(matches, "mirror_country") {
+                args.mirror_country =
+                    args.mirror_country.take().or(self.mirror_country);
+            }
+        }
+    }
+}
+
+/// Whether argument `id` was given on the command line,
+/// at top level or in any subcommand
+fn given(matches: &ArgMatches, id: &str) -> bool {
+    let here = matches.try_contains_id(id).unwrap_or(false)
+        && matches.value_source(id) == Some(ValueSource::CommandLine);
+
+    here || matches
+        .subcommand()
+        .is_some_and(|(_, sub_matches)| given(sub_matches, id))
+}
+
+#[test]
+fn test_config() {
+    use clap::{
+        CommandFactory,
+        FromArgMatches,
+    };
+
+    let config = || {
+        parse(
+            r#"
+color = "never"
+verbose = 1
+report-dir = "/var/lib/reports"
+mirror-country = "DE"
+"#,
+        )
+        .unwrap()
+    };
+
+    let merged = |args: &[&str]| {
+        let matches = Cli::command().try_get_matches_from(args).unwrap();
+        let mut cli = Cli::from_arg_matches(&matches).unwrap();
+        config().merge(&mut cli, &matches);
+
+        cli
+    };
+
+    let cli = merged(&["ali-rs", "apply"]);
+    assert_eq!(ColorMode::Never, cli.color);
+    assert_eq!(1, cli.verbose);
+    assert_eq!(Some("/var/lib/reports".to_string()), cli.report_dir);
+    let Some(Commands::Apply(args)) = cli.commands else {
+        panic!("not apply");
+    };
+    assert!(!args.yes);
+    assert_eq!(Some("DE".to_string()), args.mirror_country);
+
+    // Command line wins, including global flags after subcommands
+    let cli = merged(&[
+        "ali-rs",
+        "--color=always",
+        "apply",
+        "-vv",
+        "--report-dir",
+        "/tmp",
+        "--mirror-country",
+        "TH",
+    ]);
+    assert_eq!(ColorMode::Always, cli.color);
+    assert_eq!(2, cli.verbose);
+    assert_eq!(Some("/tmp".to_string()), cli.report_dir);
+    let Some(Commands::Apply(args)) = cli.commands else {
+        panic!("not apply");
+    };
+    assert_eq!(Some("TH".to_string()), args.mirror_country);
+
+    // Apply defaults do not leak into other subcommands
+    let cli = merged(&["ali-rs", "validate"]);
+    assert_eq!(ColorMode::Never, cli.color);
+
+    assert!(parse("colour = \"never\"").is_err());
+    assert!(parse("verbose = \"debug\"").is_err());
+    assert!(parse("yes = true").is_err());
+    assert_eq!(Config::default(), parse("").unwrap());
+}
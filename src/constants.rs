@@ -3,8 +3,14 @@ pub mod defaults {
     pub const TIMEZONE: &str = "America/Los_Angeles";
     pub const INSTALL_LOCATION: &str = "/alitarget";
     pub const HOSTNAME: &str = "arch-ali";
-    pub const LOCALE_GEN: &str = "en_US.UTF-8 UTF-8";
-    pub const LOCALE_CONF: &str = "LANG=en_US.UTF-8";
+    pub const LOCALE: &str = "en_US.UTF-8";
+    pub const STATS_FILE: &str = "/var/lib/ali-rs/stats.json";
+    pub const LOG_FILE: &str = "/var/log/ali-rs/ali-rs.log";
+    pub const RUN_LOCK: &str = "/run/ali-rs.lock";
+    pub const HOOKS_DIR: &str = "/usr/lib/ali-rs/hooks";
+    pub const SNAPSHOT_DIR: &str = "/etc";
+    pub const BACKUPS_DIR: &str = "/var/lib/ali-rs/backups";
+    pub const ALIASES_FILE: &str = "/etc/ali-rs/aliases.yaml";
 
     const ROOT_PASSWD: &str = "archalirs";
 
@@ -28,6 +34,8 @@ pub mod defaults {
 }
 
 pub const ENV_ALI_LOC: &str = "ALI_LOC";
+pub const ENV_ALI_STATS: &str = "ALI_STATS";
+pub const ENV_ALI_HOOKS_DIR: &str = "ALI_HOOKS_DIR";
 
 // Use programs instead of bindings to avoid API dependencies
 pub const REQUIRED_COMMANDS: [&str; 15] = [
@@ -5,6 +5,8 @@ use crate::types::{
     action,
     stage,
 };
+use crate::utils::json;
+use crate::utils::secrets;
 use crate::utils::shell;
 
 /// App-wide ali-rs error
@@ -14,10 +16,11 @@ pub enum AliError {
     /// after ApplyError.
     ///
     /// It keeps a state of stages successfully applied,
-    /// and the actual ApplyError
+    /// the stage that failed, and the actual ApplyError
     #[error("ali-rs installation error")]
     InstallError {
         error: Box<AliError>,
+        stage_failed: stage::Stage,
         stages_performed: Box<stage::StageActions>,
     },
 
@@ -48,6 +51,10 @@ pub enum AliError {
     #[error("validation error: {0}")]
     Validation(String),
 
+    /// Preflight collects all failures instead of stopping at the first one
+    #[error("preflight failed:\n  - {}", .0.join("\n  - "))]
+    Preflight(Vec<String>),
+
     #[error(
         "shell command (context: \"{context}\"), embeddedError: {error:?}"
     )]
@@ -65,6 +72,9 @@ pub enum AliError {
     #[error("hook error: {0}")]
     HookError(String),
 
+    #[error("aborted: {0}")]
+    Aborted(String),
+
     #[error("not implemented: {0}")]
     NotImplemented(String),
 
@@ -74,13 +84,15 @@ pub enum AliError {
 
 impl AliError {
     pub fn to_json_string(&self) -> String {
-        let json_value = match self {
+        let mut json_value = match self {
             Self::InstallError {
                 error,
+                stage_failed,
                 stages_performed,
             } => {
                 json!({
                     "error": error.to_json_string(),
+                    "stageFailed": stage_failed.to_string(),
                     "stagesPerformed": stages_performed,
                 })
             }
@@ -102,7 +114,113 @@ impl AliError {
             }
         };
 
-        json_value.to_string()
+        secrets::redact_json(&mut json_value);
+        json_value["code"] = json!(self.code());
+        json_value["class"] = json!(self.class());
+        if let Some(hint) = self.hint() {
+            json_value["hint"] = json!(hint);
+        }
+
+        json::to_string(&json_value)
+    }
+
+    /// Innermost error wrapped by InstallError and ApplyError
+    pub fn root_cause(&self) -> &AliError {
+        match self {
+            Self::InstallError { error, .. }
+            | Self::ApplyError { error, .. } => error.root_cause(),
+            err => err,
+        }
+    }
+
+    /// Stable error code of root cause, e.g. `no-such-device`
+    pub fn code(&self) -> &'static str {
+        match self.root_cause() {
+            Self::InstallError { .. } | Self::ApplyError { .. } => {
+                unreachable!("root cause is never wrapper error")
+            }
+            Self::NoSuchFile(..) => "no-such-file",
+            Self::FileError(..) => "file-error",
+            Self::NoSuchDevice(_) => "no-such-device",
+            Self::BadManifest(_) => "bad-manifest",
+            Self::Validation(_) => "validation",
+            Self::Preflight(_) => "preflight",
+            Self::CmdFailed { .. } => "cmd-failed",
+            Self::BadArgs(_) => "bad-args",
+            Self::BadHookCmd(_) => "bad-hook-cmd",
+            Self::HookError(_) => "hook-error",
+            Self::Aborted(_) => "aborted",
+            Self::NotImplemented(_) => "not-implemented",
+            Self::AliRsBug(_) => "ali-rs-bug",
+        }
+    }
+
+    /// Coarse class of root cause, for orchestrators wrapping ali-rs
+    pub fn class(&self) -> &'static str {
+        match self.root_cause() {
+            Self::InstallError { .. } | Self::ApplyError { .. } => {
+                unreachable!("root cause is never wrapper error")
+            }
+            Self::CmdFailed { .. } => "install",
+            Self::NoSuchFile(..)
+            | Self::BadManifest(_)
+            | Self::Validation(_)
+            | Self::Preflight(_)
+            | Self::NoSuchDevice(_) => "manifest",
+            Self::BadArgs(_) => "usage",
+            Self::BadHookCmd(_) | Self::HookError(_) => "hook",
+            Self::Aborted(_) => "aborted",
+            Self::FileError(..) => "io",
+            Self::NotImplemented(_) | Self::AliRsBug(_) => "internal",
+        }
+    }
+
+    /// Documented process exit status for kind of root cause,
+    /// so that scripts wrapping ali-rs can branch on it
+    pub fn exit_code(&self) -> i32 {
+        match self.root_cause() {
+            Self::BadArgs(_) => 2,
+            Self::BadManifest(_) => 3,
+            Self::Validation(_) | Self::Preflight(_) => 4,
+            Self::NoSuchDevice(_) => 5,
+            Self::BadHookCmd(_) | Self::HookError(_) => 6,
+            Self::CmdFailed { .. } => 7,
+            Self::Aborted(_) => 8,
+            Self::NoSuchFile(..) | Self::FileError(..) => 9,
+            _ => 70,
+        }
+    }
+
+    /// Remediation hint for root cause, if any
+    pub fn hint(&self) -> Option<&'static str> {
+        let hint = match self.root_cause() {
+            Self::NoSuchFile(..) => {
+                "check that the file exists and the path is correct"
+            }
+            Self::NoSuchDevice(_) => {
+                "check device paths with lsblk, or use disk selectors in manifest"
+            }
+            Self::BadManifest(_) => {
+                "check manifest against examples in src/ali/examples"
+            }
+            Self::Validation(_) => {
+                "fix the manifest, or skip validation with `apply --no-validate`"
+            }
+            Self::Preflight(_) => {
+                "fix the listed problems, or skip checks with `apply --no-preflight`"
+            }
+            Self::CmdFailed { .. } => {
+                "see command output in the log file (--log-file)"
+            }
+            Self::BadArgs(_) => "see `ali-rs --help`",
+            Self::BadHookCmd(_) => "see `ali-rs hooks help <KEY>`",
+            Self::AliRsBug(_) => {
+                "please report a bug, with the log file and crash file if any"
+            }
+            _ => return None,
+        };
+
+        Some(hint)
     }
 }
 
@@ -172,9 +290,44 @@ fn test_json_error() {
 
     let err_install = AliError::InstallError {
         error: Box::new(err_pkg),
+        stage_failed: stage::Stage::Bootstrap,
         stages_performed: Box::new(actions_mountpoints.into()),
     };
 
     println!("InstallError:");
     println!("{}", err_install.to_json_string());
+    assert_eq!("install", err_install.class());
+    assert_eq!("cmd-failed", err_install.code());
+    assert_eq!(7, err_install.exit_code());
+
+    // Class, code, and exit status all come from root cause
+    let err_hook = AliError::InstallError {
+        error: Box::new(AliError::HookError("foo".into())),
+        stage_failed: stage::Stage::ChrootUser,
+        stages_performed: Box::default(),
+    };
+    assert_eq!("hook", err_hook.class());
+    assert_eq!("hook-error", err_hook.code());
+    assert_eq!(6, err_hook.exit_code());
+
+    let tests = [
+        (AliError::BadArgs("foo".into()), 2),
+        (AliError::BadManifest("foo".into()), 3),
+        (AliError::Preflight(vec!["foo".into()]), 4),
+        (AliError::NoSuchDevice("/dev/foo".into()), 5),
+        (AliError::HookError("foo".into()), 6),
+        (AliError::Aborted("foo".into()), 8),
+        (AliError::AliRsBug("foo".into()), 70),
+    ];
+
+    for (err, expected) in tests {
+        assert_eq!(expected, err.exit_code(), "{err}");
+    }
+    assert!(err_install.hint().is_some());
+    assert!(err_install
+        .to_json_string()
+        .contains(r#""code":"cmd-failed""#));
+    assert!(err_install
+        .to_json_string()
+        .contains(r#""stageFailed":"stage-bootstrap""#));
 }
@@ -0,0 +1,581 @@
+//! Argument specs shared by hooks
+//!
+//! A hook declares its arguments as a `&'static [Arg]`, and gets parsing,
+//! error messages, and usage string from this module:
+//!
+//! ```ignore
+//! const ARGS: &[Arg] = &[
+//!     Arg::choice("tool", &["borg", "restic"]),
+//!     Arg::key_required("repo", "REPO"),
+//!     Arg::key_default("schedule", "CALENDAR", "daily"),
+//!     Arg::flag("firstboot"),
+//! ];
+//!
+//! static USAGE: LazyLock<String> = LazyLock::new(|| args::usage(ARGS));
+//!
+//! let args = args::parse(&hook_key, &parts[1..], ARGS)?;
+//! let repo = args.required("repo")?;
+//! let firstboot = args.flag("firstboot");
+//! ```
+//!
+//! Positional arguments are filled in order of declaration, so optional
+//! positional arguments should come last. Keywords are only recognized
+//! after positional arguments declared before them. Arguments with `=`
+//! are only treated as keys if the spec declares any keys, so that hooks
+//! taking only positional arguments can take values with `=`.
+
+use std::collections::{
+    HashMap,
+    HashSet,
+};
+
+use crate::errors::AliError;
+use crate::utils::suggest;
+
+/// Argument declaration of a hook
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) enum Arg {
+    /// Positional argument `<VALUE>`, or one of `choices` if not empty
+    Positional {
+        name: &'static str,
+        value: &'static str,
+        choices: &'static [&'static str],
+        required: bool,
+    },
+
+    /// Key-value argument `key=<VALUE>`
+    Key {
+        key: &'static str,
+        value: &'static str,
+        required: bool,
+        default: Option<&'static str>,
+    },
+
+    /// Mutually exclusive key-value arguments, as `(key, value)` pairs
+    OneOf {
+        keys: &'static [(&'static str, &'static str)],
+        required: bool,
+    },
+
+    /// Keyword taking the next argument as its value, e.g. `marker <VALUE>`
+    Keyword {
+        keyword: &'static str,
+        value: &'static str,
+        default: Option<&'static str>,
+    },
+
+    /// Bare flag, e.g. `firstboot`
+    Flag(&'static str),
+}
+
+impl Arg {
+    pub(super) const fn positional(
+        name: &'static str,
+        value: &'static str,
+    ) -> Self {
+        Self::Positional {
+            name,
+            value,
+            choices: &[],
+            required: true,
+        }
+    }
+
+    pub(super) const fn positional_optional(
+        name: &'static str,
+        value: &'static str,
+    ) -> Self {
+        Self::Positional {
+            name,
+            value,
+            choices: &[],
+            required: false,
+        }
+    }
+
+    pub(super) const fn choice(
+        name: &'static str,
+        choices: &'static [&'static str],
+    ) -> Self {
+        Self::Positional {
+            name,
+            value: name,
+            choices,
+            required: true,
+        }
+    }
+
+    pub(super) const fn key(key: &'static str, value: &'static str) -> Self {
+        Self::Key {
+            key,
+            value,
+            required: false,
+            default: None,
+        }
+    }
+
+    pub(super) const fn key_required(
+        key: &'static str,
+        value: &'static str,
+    ) -> Self {
+        Self::Key {
+            key,
+            value,
+            required: true,
+            default: None,
+        }
+    }
+
+    pub(super) const fn key_default(
+        key: &'static str,
+        value: &'static str,
+        default: &'static str,
+    ) -> Self {
+        Self::Key {
+            key,
+            value,
+            required: false,
+            default: Some(default),
+        }
+    }
+
+    pub(super) const fn one_of(
+        keys: &'static [(&'static str, &'static str)],
+    ) -> Self {
+        Self::OneOf {
+            keys,
+            required: false,
+        }
+    }
+
+    pub(super) const fn one_of_required(
+        keys: &'static [(&'static str, &'static str)],
+    ) -> Self {
+        Self::OneOf {
+            keys,
+            required: true,
+        }
+    }
+
+    pub(super) const fn keyword(
+        keyword: &'static str,
+        value: &'static str,
+        default: Option<&'static str>,
+    ) -> Self {
+        Self::Keyword {
+            keyword,
+            value,
+            default,
+        }
+    }
+
+    pub(super) const fn flag(flag: &'static str) -> Self {
+        Self::Flag(flag)
+    }
+
+    /// Formats the argument for usage string
+    fn usage(&self) -> String {
+        let with_default = |value: &str, default: Option<&str>| {
+            match default {
+                Some(default) => format!("<{value}=\"{default}\">"),
+                None => format!("<{value}>"),
+            }
+        };
+
+        match *self {
+            Self::Positional {
+                value,
+                choices,
+                required,
+                ..
+            } => {
+                let value = match choices.is_empty() {
+                    true => value.to_string(),
+                    false => choices.join("|"),
+                };
+
+                match required {
+                    true => format!("<{value}>"),
+                    false => format!("[{value}]"),
+                }
+            }
+
+            Self::Key {
+                key,
+                value,
+                required,
+                default,
+            } => {
+                let arg = format!("{key}={}", with_default(value, default));
+                match required {
+                    true => arg,
+                    false => format!("[{arg}]"),
+                }
+            }
+
+            Self::OneOf { keys, required } => {
+                let keys = keys
+                    .iter()
+                    .map(|(key, value)| format!("{key}=<{value}>"))
+                    .collect::<Vec<_>>()
+                    .join(" | ");
+
+                match required {
+                    true => format!("<{keys}>"),
+                    false => format!("[{keys}]"),
+                }
+            }
+
+            Self::Keyword {
+                keyword,
+                value,
+                default,
+            } => format!("[{keyword} {}]", with_default(value, default)),
+
+            Self::Flag(flag) => format!("[{flag}]"),
+        }
+    }
+}
+
+/// Arguments parsed with [`parse`]
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct Args {
+    hook_key: String,
+    values: HashMap<&'static str, String>,
+    flags: HashSet<&'static str>,
+}
+
+impl Args {
+    /// Returns value of positional argument, key, or keyword `name`,
+    /// or its default value if not given
+    pub(super) fn get(&self, name: &str) -> Option<&str> {
+        self.values.get(name).map(String::as_str)
+    }
+
+    /// Returns value of argument `name` guaranteed by spec to be present
+    pub(super) fn required(&self, name: &str) -> Result<&str, AliError> {
+        self.get(name).ok_or(AliError::AliRsBug(format!(
+            "{}: missing required argument {name} after parsing",
+            self.hook_key,
+        )))
+    }
+
+    /// Returns whether flag `name` was given
+    pub(super) fn flag(&self, name: &str) -> bool {
+        self.flags.contains(name)
+    }
+}
+
+/// Returns usage string generated from `spec`
+pub(super) fn usage(spec: &[Arg]) -> String {
+    spec.iter().map(Arg::usage).collect::<Vec<_>>().join(" ")
+}
+
+/// Parses hook arguments `args` (without hook key) according to `spec`
+pub(super) fn parse(
+    hook_key: &str,
+    args: &[String],
+    spec: &'static [Arg],
+) -> Result<Args, AliError> {
+    let bad = |msg: String| AliError::BadHookCmd(format!("{hook_key}: {msg}"));
+
+    let takes_keys = spec
+        .iter()
+        .any(|arg| matches!(arg, Arg::Key { .. } | Arg::OneOf { .. }));
+
+    let positionals = spec
+        .iter()
+        .filter(|arg| matches!(arg, Arg::Positional { .. }))
+        .collect::<Vec<_>>();
+
+    let mut values = HashMap::new();
+    let mut flags = HashSet::new();
+    let mut next_positional = positionals.iter();
+    let mut filled = 0;
+
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        if let Some((k, v)) = arg.split_once('=') {
+            if takes_keys && is_key_like(k) {
+                let keys = keys(spec);
+                let Some(key) = keys.iter().find(|key| **key == k).copied()
+                else {
+                    return Err(bad(format!(
+                        "unknown key {k}{}, expecting one of: {}",
+                        suggest::hint(k, keys.iter().copied()),
+                        keys.join(", "),
+                    )));
+                };
+
+                if v.is_empty() {
+                    return Err(bad(format!("empty value for key {k}")));
+                }
+
+                if values.insert(key, v.to_string()).is_some() {
+                    return Err(bad(format!("duplicate key {k}")));
+                }
+
+                continue;
+            }
+        }
+
+        if let Some(flag) = find_flag(spec, arg) {
+            if !flags.insert(flag) {
+                return Err(bad(format!("duplicate flag {flag}")));
+            }
+
+            continue;
+        }
+
+        if let Some(keyword) = find_keyword(spec, arg, filled) {
+            let value = iter
+                .next()
+                .ok_or_else(|| bad(format!("got only keyword `{keyword}`")))?;
+
+            if values.insert(keyword, value.clone()).is_some() {
+                return Err(bad(format!("duplicate keyword `{keyword}`")));
+            }
+
+            continue;
+        }
+
+        match next_positional.next() {
+            Some(Arg::Positional { name, choices, .. }) => {
+                if !choices.is_empty() && !choices.contains(&arg.as_str()) {
+                    return Err(bad(format!(
+                        "unexpected argument {arg}{}, expecting {}",
+                        suggest::hint(arg, choices.iter().copied()),
+                        choices.join("|"),
+                    )));
+                }
+
+                values.insert(name, arg.clone());
+                filled += 1;
+            }
+
+            _ => return Err(bad(format!("unexpected argument {arg}"))),
+        }
+    }
+
+    for arg in spec {
+        match *arg {
+            Arg::Positional { name, required, .. } => {
+                if required && !values.contains_key(name) {
+                    let usage = arg.usage();
+                    return Err(bad(format!("missing argument {usage}")));
+                }
+            }
+
+            Arg::Key {
+                key,
+                required,
+                default,
+                ..
+            } => {
+                if required && !values.contains_key(key) {
+                    return Err(bad(format!("missing key {key}")));
+                }
+
+                if let Some(default) = default {
+                    values.entry(key).or_insert(default.to_string());
+                }
+            }
+
+            Arg::OneOf { keys, required } => {
+                let keys = keys.iter().map(|(key, _)| *key);
+                let found = keys
+                    .clone()
+                    .filter(|key| values.contains_key(key))
+                    .collect::<Vec<_>>();
+
+                if found.len() > 1 {
+                    return Err(bad(format!(
+                        "{} are mutually exclusive, but found both",
+                        found.join(" and "),
+                    )));
+                }
+
+                if required && found.is_empty() {
+                    return Err(bad(format!(
+                        "missing {}",
+                        keys.collect::<Vec<_>>().join(" or "),
+                    )));
+                }
+            }
+
+            Arg::Keyword {
+                keyword, default, ..
+            } => {
+                if let Some(default) = default {
+                    values.entry(keyword).or_insert(default.to_string());
+                }
+            }
+
+            Arg::Flag(_) => continue,
+        }
+    }
+
+    Ok(Args {
+        hook_key: hook_key.to_string(),
+        values,
+        flags,
+    })
+}
+
+/// Whether `k` looks like a key, so that values with `=`
+/// such as URLs are not mistaken for keys
+fn is_key_like(k: &str) -> bool {
+    !k.is_empty()
+        && k.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+/// Returns all keys accepted by `spec`, in order of declaration
+fn keys(spec: &'static [Arg]) -> Vec<&'static str> {
+    spec.iter()
+        .flat_map(|arg| {
+            match arg {
+                Arg::Key { key, .. } => vec![*key],
+                Arg::OneOf { keys, .. } => {
+                    keys.iter().map(|(key, _)| *key).collect()
+                }
+                _ => vec![],
+            }
+        })
+        .collect()
+}
+
+fn find_flag(spec: &'static [Arg], s: &str) -> Option<&'static str> {
+    spec.iter().find_map(|arg| {
+        match arg {
+            Arg::Flag(flag) if *flag == s => Some(*flag),
+            _ => None,
+        }
+    })
+}
+
+/// Finds keyword `s`, if positional arguments declared before it
+/// are already `filled`
+fn find_keyword(
+    spec: &'static [Arg],
+    s: &str,
+    filled: usize,
+) -> Option<&'static str> {
+    let mut positionals = 0;
+
+    for arg in spec {
+        match arg {
+            Arg::Positional { .. } => positionals += 1,
+            Arg::Keyword { keyword, .. } if *keyword == s => {
+                return (filled >= positionals).then_some(*keyword);
+            }
+            _ => continue,
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SPEC: &[Arg] = &[
+        Arg::choice("tool", &["borg", "restic"]),
+        Arg::positional("src", "SRC"),
+        Arg::keyword("marker", "MARKER", Some("#")),
+        Arg::positional_optional("dst", "DST"),
+        Arg::key_required("repo", "REPO"),
+        Arg::one_of_required(&[("pass", "PASS"), ("pass_file", "FILE")]),
+        Arg::key_default("schedule", "CALENDAR", "daily"),
+        Arg::key("env_file", "FILE"),
+        Arg::flag("firstboot"),
+    ];
+
+    fn parse_str(s: &str) -> Result<Args, AliError> {
+        let parts = shlex::split(s).unwrap();
+        parse("@test", &parts, SPEC)
+    }
+
+    #[test]
+    fn test_parse_args() {
+        let args = parse_str("borg /src repo=/repo pass=foo").unwrap();
+        assert_eq!(args.get("tool"), Some("borg"));
+        assert_eq!(args.get("src"), Some("/src"));
+        assert_eq!(args.get("dst"), None);
+        assert_eq!(args.get("repo"), Some("/repo"));
+        assert_eq!(args.get("pass"), Some("foo"));
+        assert_eq!(args.get("pass_file"), None);
+        assert_eq!(args.get("schedule"), Some("daily"));
+        assert_eq!(args.get("marker"), Some("#"));
+        assert!(!args.flag("firstboot"));
+
+        let args = parse_str(
+            "repo=/repo restic firstboot /src marker // pass_file=/f schedule=weekly /dst",
+        )
+        .unwrap();
+        assert_eq!(args.get("tool"), Some("restic"));
+        assert_eq!(args.get("src"), Some("/src"));
+        assert_eq!(args.get("dst"), Some("/dst"));
+        assert_eq!(args.get("pass_file"), Some("/f"));
+        assert_eq!(args.get("schedule"), Some("weekly"));
+        assert_eq!(args.get("marker"), Some("//"));
+        assert!(args.flag("firstboot"));
+        assert!(args.required("env_file").is_err());
+
+        let should_err = vec![
+            "borg repo=/repo pass=foo",                  // Missing src
+            "/src borg repo=/repo pass=foo",             // Bad choice
+            "borg /src pass=foo",                        // Missing repo
+            "borg /src repo=/repo",                      // Missing pass
+            "borg /src repo=/repo pass=foo pass_file=f", // Exclusive
+            "borg /src repo=/repo repo=/r pass=foo",     // Duplicate key
+            "borg /src repo=/repo pass=foo foo=bar",     // Unknown key
+            "borg /src repo= pass=foo",                  // Empty value
+            "borg /src /dst /foo repo=/repo pass=foo",   // Extra argument
+            "borg /src repo=/repo pass=foo firstboot firstboot",
+            "borg /src repo=/repo pass=foo marker",
+            "borg /src repo=/repo pass=foo marker ; marker //",
+            "borg marker // /src repo=/repo pass=foo", // Keyword before src
+        ];
+
+        for s in should_err {
+            assert!(parse_str(s).is_err(), "unexpected ok result from {s}");
+        }
+
+        let err = parse_str("borg /src repo=/repo pass=foo foo=bar");
+        assert!(matches!(
+            err,
+            Err(AliError::BadHookCmd(ref msg)) if msg == "@test: unknown key foo, expecting one of: repo, pass, pass_file, schedule, env_file"
+        ));
+    }
+
+    #[test]
+    fn test_parse_args_no_keys() {
+        const SPEC_NO_KEYS: &[Arg] = &[
+            Arg::positional("token", "TOKEN"),
+            Arg::positional("value", "VALUE"),
+        ];
+
+        let parts = vec!["FOO".to_string(), "bar=baz".to_string()];
+        let args = parse("@test", &parts, SPEC_NO_KEYS).unwrap();
+        assert_eq!(args.get("value"), Some("bar=baz"));
+
+        // Values with `=` are not keys
+        let parts = vec!["https://example.com/?a=b".to_string()];
+        const SPEC_URL: &[Arg] = &[
+            Arg::positional("url", "URL"),
+            Arg::key("sha256", "CHECKSUM"),
+        ];
+
+        let args = parse("@test", &parts, SPEC_URL).unwrap();
+        assert_eq!(args.get("url"), Some("https://example.com/?a=b"));
+    }
+
+    #[test]
+    fn test_usage() {
+        assert_eq!(
+            usage(SPEC),
+            "<borg|restic> <SRC> [marker <MARKER=\"#\">] [DST] repo=<REPO> <pass=<PASS> | pass_file=<FILE>> [schedule=<CALENDAR=\"daily\">] [env_file=<FILE>] [firstboot]",
+        );
+    }
+}
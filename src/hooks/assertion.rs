@@ -0,0 +1,417 @@
+use std::sync::LazyLock;
+
+use serde_json::json;
+
+use super::args::{
+    self,
+    Arg,
+};
+use super::{
+    extract_key_and_parts_shlex,
+    wrap_bad_hook_cmd,
+    ActionHook,
+    Caller,
+    Hook,
+    ModeHook,
+    ParseError,
+    KEY_ASSERT_FILE_CONTAINS,
+    KEY_ASSERT_MOUNTED,
+    KEY_ASSERT_SERVICE_ENABLED,
+};
+use crate::errors::AliError;
+use crate::linux::systemd;
+use crate::utils::fs::path_under;
+use crate::utils::json;
+
+const ARGS_FILE_CONTAINS: &[Arg] = &[
+    Arg::positional("path", "PATH"),
+    Arg::positional("text", "TEXT"),
+];
+
+const ARGS_SERVICE_ENABLED: &[Arg] = &[
+    Arg::positional("unit", "UNIT"),
+    Arg::key("target", "TARGET"),
+];
+
+const ARGS_MOUNTED: &[Arg] = &[Arg::positional("path", "PATH")];
+
+static USAGE_FILE_CONTAINS: LazyLock<String> =
+    LazyLock::new(|| args::usage(ARGS_FILE_CONTAINS));
+
+static USAGE_SERVICE_ENABLED: LazyLock<String> =
+    LazyLock::new(|| args::usage(ARGS_SERVICE_ENABLED));
+
+static USAGE_MOUNTED: LazyLock<String> =
+    LazyLock::new(|| args::usage(ARGS_MOUNTED));
+
+/// Example arguments, for `ali-rs hooks schema`
+pub(super) const EXAMPLE_FILE_CONTAINS: &str =
+    "/etc/locale.conf LANG=en_US.UTF-8";
+pub(super) const EXAMPLE_SERVICE_ENABLED: &str = "sshd.service";
+pub(super) const EXAMPLE_MOUNTED: &str = "/boot";
+
+/// Keyword arguments of `@assert-service-enabled`, for shell completion
+pub(super) const KEYWORDS_SERVICE_ENABLED: &[&str] = &["target="];
+
+const PROC_MOUNTS: &str = "/proc/mounts";
+
+/// Condition on the new system, checked without modifying anything
+#[derive(Debug, Clone, PartialEq)]
+enum Assertion {
+    /// File at `path` (relative to mountpoint) contains `text`
+    FileContains { path: String, text: String },
+
+    /// systemd unit enabled, for `target` if given
+    ServiceEnabled {
+        unit: String,
+        target: Option<String>,
+    },
+
+    /// Something is mounted at `path` (relative to mountpoint)
+    Mounted { path: String },
+}
+
+/// Hooks `@assert-*`, which fail the run if their assertions
+/// do not hold. They are always run read-only, and have no print variants.
+struct HookAssert {
+    key: &'static str,
+    assertion: Assertion,
+}
+
+pub(super) fn parse(k: &str, cmd: &str) -> Result<Box<dyn Hook>, ParseError> {
+    match HookAssert::try_from(cmd) {
+        Ok(hook) => Ok(Box::new(hook)),
+        Err(err) => Err(wrap_bad_hook_cmd(err, usage(k))),
+    }
+}
+
+fn usage(k: &str) -> &'static str {
+    match k {
+        KEY_ASSERT_FILE_CONTAINS => &USAGE_FILE_CONTAINS,
+        KEY_ASSERT_SERVICE_ENABLED => &USAGE_SERVICE_ENABLED,
+        KEY_ASSERT_MOUNTED => &USAGE_MOUNTED,
+        key => panic!("unknown key {key}"),
+    }
+}
+
+impl Hook for HookAssert {
+    fn base_key(&self) -> &'static str {
+        self.key
+    }
+
+    /// Assertion hooks are read-only, but have no `-print` suffix
+    fn hook_key(&self) -> String {
+        self.key.to_string()
+    }
+
+    /// `@assert-file-contains <PATH> <TEXT>`
+    ///
+    /// `@assert-service-enabled <UNIT> [target=<TARGET>]`
+    ///
+    /// `@assert-mounted <PATH>`
+    ///
+    /// Examples:
+    ///
+    /// ```txt
+    /// @assert-file-contains /etc/ssh/sshd_config 'PermitRootLogin no'
+    /// ```
+    fn usage(&self) -> &'static str {
+        usage(self.key)
+    }
+
+    fn mode(&self) -> ModeHook {
+        ModeHook::Print
+    }
+
+    fn should_chroot(&self) -> bool {
+        true
+    }
+
+    fn prefer_caller(&self, _caller: &Caller) -> bool {
+        true
+    }
+
+    fn abort_if_no_mount(&self) -> bool {
+        false
+    }
+
+    fn run_hook(
+        &self,
+        _caller: &Caller,
+        root_location: &str,
+    ) -> Result<ActionHook, AliError> {
+        if let Err(msg) = self.assertion.check(root_location) {
+            return Err(self.hook_error(&format!(
+                "assertion failed: {}: {msg}",
+                self.assertion,
+            )));
+        }
+
+        log::info!("{}: assertion holds: {}", self.key, self.assertion);
+
+        Ok(ActionHook::Assert(json::to_string(&json!({
+            "holds": self.assertion.to_string(),
+        }))))
+    }
+}
+
+impl TryFrom<&str> for HookAssert {
+    type Error = AliError;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        let (hook_key, parts) = extract_key_and_parts_shlex(s)?;
+        let (key, spec) = match hook_key.as_str() {
+            KEY_ASSERT_FILE_CONTAINS => {
+                (KEY_ASSERT_FILE_CONTAINS, ARGS_FILE_CONTAINS)
+            }
+            KEY_ASSERT_SERVICE_ENABLED => {
+                (KEY_ASSERT_SERVICE_ENABLED, ARGS_SERVICE_ENABLED)
+            }
+            KEY_ASSERT_MOUNTED => (KEY_ASSERT_MOUNTED, ARGS_MOUNTED),
+            key => panic!("unexpected key {key}"),
+        };
+
+        let args = args::parse(&hook_key, &parts[1..], spec)?;
+        let absolute = |path: &str| {
+            match path.starts_with('/') {
+                true => Ok(path.to_string()),
+                false => {
+                    Err(AliError::BadHookCmd(format!(
+                        "{hook_key}: path must be absolute, got {path}"
+                    )))
+                }
+            }
+        };
+
+        let assertion = match key {
+            KEY_ASSERT_FILE_CONTAINS => {
+                let text = args.required("text")?;
+                if text.is_empty() {
+                    return Err(AliError::BadHookCmd(format!(
+                        "{hook_key}: empty text"
+                    )));
+                }
+
+                Assertion::FileContains {
+                    path: absolute(args.required("path")?)?,
+                    text: text.to_string(),
+                }
+            }
+            KEY_ASSERT_SERVICE_ENABLED => {
+                let unit = args.required("unit")?;
+                if unit.is_empty() || unit.contains('/') {
+                    return Err(AliError::BadHookCmd(format!(
+                        "{hook_key}: bad unit {unit}"
+                    )));
+                }
+
+                // Like systemctl, units without suffixes are services
+                let unit = match unit.contains('.') {
+                    true => unit.to_string(),
+                    false => format!("{unit}.service"),
+                };
+
+                Assertion::ServiceEnabled {
+                    unit,
+                    target: args.get("target").map(String::from),
+                }
+            }
+            _ => {
+                Assertion::Mounted {
+                    path: absolute(args.required("path")?)?,
+                }
+            }
+        };
+
+        Ok(Self { key, assertion })
+    }
+}
+
+impl Assertion {
+    /// Checks assertion under `root_location`,
+    /// returning why it does not hold if so
+    fn check(&self, root_location: &str) -> Result<(), String> {
+        let root = root_location.trim_end_matches('/');
+
+        match self {
+            Self::FileContains { path, text } => {
+                let file = path_under(root_location, path)
+                    .map_err(|err| err.to_string())?;
+
+                let content = std::fs::read_to_string(&file)
+                    .map_err(|err| format!("cannot read {file}: {err}"))?;
+
+                match content.contains(text.as_str()) {
+                    true => Ok(()),
+                    false => Err(format!("{text:?} not found in {file}")),
+                }
+            }
+            Self::ServiceEnabled { unit, target } => {
+                match systemd::is_enabled(root, unit, target.as_deref()) {
+                    true => Ok(()),
+                    false => Err(format!("{unit} not enabled under {root}/")),
+                }
+            }
+            Self::Mounted { path } => {
+                let target = format!("{root}{}", path.trim_end_matches('/'));
+                let target = match target.is_empty() {
+                    true => "/".to_string(),
+                    false => target,
+                };
+
+                let mounts =
+                    std::fs::read_to_string(PROC_MOUNTS).map_err(|err| {
+                        format!("cannot read {PROC_MOUNTS}: {err}")
+                    })?;
+
+                match is_mounted(&mounts, &target) {
+                    true => Ok(()),
+                    false => Err(format!("nothing mounted at {target}")),
+                }
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for Assertion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::FileContains { path, text } => {
+                write!(f, "file {path} contains {text:?}")
+            }
+            Self::ServiceEnabled {
+                unit,
+                target: Some(target),
+            } => write!(f, "service {unit} enabled for {target}"),
+            Self::ServiceEnabled { unit, target: None } => {
+                write!(f, "service {unit} enabled")
+            }
+            Self::Mounted { path } => write!(f, "{path} mounted"),
+        }
+    }
+}
+
+/// Whether `target` is a mountpoint in `mounts`,
+/// in format of /proc/mounts with octal-escaped whitespace
+fn is_mounted(mounts: &str, target: &str) -> bool {
+    mounts
+        .lines()
+        .filter_map(|line| line.split_whitespace().nth(1))
+        .map(|mountpoint| {
+            mountpoint
+                .replace("\\040", " ")
+                .replace("\\011", "\t")
+                .replace("\\012", "\n")
+                .replace("\\134", "\\")
+        })
+        .any(|mountpoint| mountpoint == target)
+}
+
+#[test]
+fn test_parse_assert() {
+    let tests = [
+        (
+            "@assert-file-contains /etc/ssh/sshd_config 'PermitRootLogin no'",
+            Assertion::FileContains {
+                path: "/etc/ssh/sshd_config".into(),
+                text: "PermitRootLogin no".into(),
+            },
+        ),
+        (
+            "@assert-file-contains /etc/locale.conf LANG=en_US.UTF-8",
+            Assertion::FileContains {
+                path: "/etc/locale.conf".into(),
+                text: "LANG=en_US.UTF-8".into(),
+            },
+        ),
+        (
+            "@assert-service-enabled sshd",
+            Assertion::ServiceEnabled {
+                unit: "sshd.service".into(),
+                target: None,
+            },
+        ),
+        (
+            "@assert-service-enabled fstrim.timer target=timers.target",
+            Assertion::ServiceEnabled {
+                unit: "fstrim.timer".into(),
+                target: Some("timers.target".into()),
+            },
+        ),
+        (
+            "@assert-mounted /boot",
+            Assertion::Mounted {
+                path: "/boot".into(),
+            },
+        ),
+    ];
+
+    for (cmd, expected) in tests {
+        let hook = HookAssert::try_from(cmd).unwrap();
+        assert_eq!(expected, hook.assertion, "{cmd}");
+        assert_eq!(ModeHook::Print, hook.mode());
+        assert_eq!(cmd.split_whitespace().next().unwrap(), hook.hook_key());
+    }
+
+    let should_err = [
+        "@assert-file-contains /etc/locale.conf",
+        "@assert-file-contains etc/locale.conf LANG",
+        "@assert-file-contains /etc/locale.conf ''",
+        "@assert-service-enabled",
+        "@assert-service-enabled ../sshd.service",
+        "@assert-service-enabled sshd target=",
+        "@assert-service-enabled sshd wanted=multi-user.target",
+        "@assert-mounted",
+        "@assert-mounted boot",
+        "@assert-mounted /boot /efi",
+    ];
+
+    for cmd in should_err {
+        assert!(HookAssert::try_from(cmd).is_err(), "expecting error: {cmd}");
+    }
+}
+
+#[test]
+fn test_assert() {
+    let dir = std::env::temp_dir()
+        .join(format!("ali-rs-test-assert-{}", std::process::id()));
+    let wants = dir.join("etc/systemd/system/multi-user.target.wants");
+    std::fs::create_dir_all(&wants).unwrap();
+    std::fs::write(dir.join("etc/locale.conf"), "LANG=en_US.UTF-8\n")
+        .unwrap();
+    std::os::unix::fs::symlink(
+        "/usr/lib/systemd/system/sshd.service",
+        wants.join("sshd.service"),
+    )
+    .unwrap();
+
+    let root = dir.to_string_lossy().to_string();
+    let tests = [
+        ("@assert-file-contains /etc/locale.conf LANG=en_US", true),
+        ("@assert-file-contains /etc/locale.conf LANG=th_TH", false),
+        ("@assert-file-contains /etc/hostname foo", false),
+        ("@assert-service-enabled sshd", true),
+        ("@assert-service-enabled sshd target=multi-user.target", true),
+        ("@assert-service-enabled sshd target=graphical.target", false),
+        ("@assert-service-enabled systemd-networkd", false),
+    ];
+
+    for (cmd, holds) in tests {
+        let hook = HookAssert::try_from(cmd).unwrap();
+        let result = hook.run_hook(&Caller::Cli, &root);
+        assert_eq!(holds, result.is_ok(), "{cmd}: {result:?}");
+    }
+
+    let mounts = "\
+/dev/vda2 /mnt ext4 rw,relatime 0 0
+/dev/vda1 /mnt/boot vfat rw,relatime 0 0
+/dev/vdb1 /mnt/srv/my\\040data xfs rw 0 0
+";
+    assert!(is_mounted(mounts, "/mnt"));
+    assert!(is_mounted(mounts, "/mnt/boot"));
+    assert!(is_mounted(mounts, "/mnt/srv/my data"));
+    assert!(!is_mounted(mounts, "/mnt/home"));
+    assert!(!is_mounted(mounts, "/dev/vda1"));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
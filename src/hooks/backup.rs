@@ -0,0 +1,638 @@
+use std::sync::LazyLock;
+
+use serde_json::json;
+
+use super::args::{
+    self,
+    Arg,
+};
+use super::constants::backup::*;
+use super::utils::{
+    output,
+    protected,
+};
+use super::{
+    extract_key_and_parts_shlex,
+    wrap_bad_hook_cmd,
+    ActionHook,
+    Caller,
+    Hook,
+    ModeHook,
+    ParseError,
+    KEY_BACKUP,
+    KEY_BACKUP_PRINT,
+};
+use crate::errors::AliError;
+use crate::linux::systemd;
+use crate::utils::json;
+use crate::utils::secrets;
+use crate::utils::shell;
+
+const ARGS: &[Arg] = &[
+    Arg::choice("tool", &["borg", "restic"]),
+    Arg::key_required("repo", "REPO"),
+    Arg::one_of_required(&[
+        ("passphrase", "PASSPHRASE"),
+        ("passphrase_file", "FILE"),
+    ]),
+    Arg::key("env_file", "FILE"),
+    Arg::key("paths", "PATH[,PATH..]"),
+    Arg::key("exclude", "PATTERN[,PATTERN..]"),
+    Arg::key_default("schedule", "CALENDAR", DEFAULT_SCHEDULE),
+    Arg::key("keep", "DAILY,WEEKLY,MONTHLY"),
+    Arg::flag("firstboot"),
+];
+
+static USAGE: LazyLock<String> = LazyLock::new(|| args::usage(ARGS));
+
+/// Example arguments, for `ali-rs hooks schema`
+pub(super) const EXAMPLE: &str =
+    "restic repo=/srv/restic passphrase_file=/root/.restic";
+
+/// Keyword arguments, for shell completion
+pub(super) const KEYWORDS: &[&str] = &[
+    "borg",
+    "restic",
+    "repo=",
+    "passphrase=",
+    "passphrase_file=",
+    "env_file=",
+    "paths=",
+    "exclude=",
+    "schedule=",
+    "keep=",
+    "firstboot",
+];
+
+const DEFAULT_PATHS: [&str; 3] = ["/etc", "/home", "/root"];
+const DEFAULT_SCHEDULE: &str = "daily";
+const DEFAULT_KEEP: [u32; 3] = [7, 4, 6];
+
+#[derive(Debug, Clone, PartialEq)]
+struct Backup {
+    tool: Tool,
+    repo: String,
+    passphrase: Passphrase,
+    /// File on live system with extra environment variables,
+    /// e.g. cloud storage credentials
+    env_file: Option<String>,
+    paths: Vec<String>,
+    excludes: Vec<String>,
+    /// systemd calendar event of backup timer
+    schedule: String,
+    /// Number of daily, weekly, and monthly archives to keep
+    keep: [u32; 3],
+    /// Initializes repository and runs first backup on first boot
+    firstboot: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Tool {
+    Borg,
+    Restic,
+}
+
+/// Repository passphrase, either inline or read from file when the hook
+/// runs, so that the passphrase does not have to live in the manifest
+#[derive(Debug, Clone, PartialEq)]
+enum Passphrase {
+    Inline(String),
+    File(String),
+}
+
+/// File to be written by backup, with path relative to the new root
+#[derive(Debug, Clone, PartialEq)]
+struct BackupFile {
+    path: String,
+    content: String,
+    mode: u32,
+}
+
+struct HookBackup {
+    backup: Backup,
+    mode_hook: ModeHook,
+}
+
+pub(super) fn parse(k: &str, cmd: &str) -> Result<Box<dyn Hook>, ParseError> {
+    match k {
+        KEY_BACKUP | KEY_BACKUP_PRINT => {
+            match HookBackup::try_from(cmd) {
+                Err(err) => Err(wrap_bad_hook_cmd(err, &USAGE)),
+                Ok(hook) => Ok(Box::new(hook)),
+            }
+        }
+
+        key => panic!("unknown key {key}"),
+    }
+}
+
+impl super::Hook for HookBackup {
+    fn base_key(&self) -> &'static str {
+        KEY_BACKUP
+    }
+
+    /// `@backup <borg|restic> repo=<REPO> <passphrase=<PASSPHRASE> | passphrase_file=<FILE>>`
+    ///
+    /// Examples:
+    ///
+    /// 1. Daily restic backup of default paths to SFTP repository
+    ///
+    /// ```txt
+    /// @backup restic repo=sftp:backup@nas:/srv/restic passphrase_file=/root/restic.pass
+    /// ```
+    ///
+    /// 2. Hourly borg backup of /home, with first backup on first boot
+    ///
+    /// ```txt
+    /// @backup borg repo=ssh://backup@nas/./borg passphrase=hunter22 paths=/home schedule=hourly firstboot
+    /// ```
+    fn usage(&self) -> &'static str {
+        &USAGE
+    }
+
+    fn mode(&self) -> ModeHook {
+        self.mode_hook.clone()
+    }
+
+    fn should_chroot(&self) -> bool {
+        true
+    }
+
+    fn prefer_caller(&self, caller: &Caller) -> bool {
+        matches!(caller, Caller::ManifestChroot | Caller::Cli)
+    }
+
+    fn abort_if_no_mount(&self) -> bool {
+        true
+    }
+
+    fn run_hook(
+        &self,
+        _caller: &Caller,
+        root_location: &str,
+    ) -> Result<ActionHook, AliError> {
+        apply_backup(
+            &self.hook_key(),
+            &self.mode_hook,
+            &self.backup,
+            root_location,
+        )
+    }
+}
+
+impl TryFrom<&str> for HookBackup {
+    type Error = AliError;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        let (hook_key, parts) = extract_key_and_parts_shlex(s)?;
+        let mode_hook = match hook_key.as_str() {
+            KEY_BACKUP => ModeHook::Normal,
+            KEY_BACKUP_PRINT => ModeHook::Print,
+            key => panic!("unexpected key {key}"),
+        };
+
+        let args = args::parse(&hook_key, &parts[1..], ARGS)?;
+
+        let tool = match args.required("tool")? {
+            "borg" => Tool::Borg,
+            _ => Tool::Restic,
+        };
+
+        let repo = args.required("repo")?.to_string();
+
+        let passphrase = match args.get("passphrase") {
+            Some(p) => Passphrase::Inline(p.to_string()),
+            None => {
+                Passphrase::File(args.required("passphrase_file")?.to_string())
+            }
+        };
+
+        let paths = match args.get("paths") {
+            None => DEFAULT_PATHS.map(String::from).to_vec(),
+            Some(paths) => parse_list(&hook_key, "paths", paths)?,
+        };
+
+        if let Some(path) = paths.iter().find(|p| !p.starts_with('/')) {
+            return Err(AliError::BadHookCmd(format!(
+                "{hook_key}: path {path} is not absolute"
+            )));
+        }
+
+        let excludes = match args.get("exclude") {
+            None => vec![],
+            Some(excludes) => parse_list(&hook_key, "exclude", excludes)?,
+        };
+
+        let keep = match args.get("keep") {
+            None => DEFAULT_KEEP,
+            Some(keep) => parse_keep(&hook_key, keep)?,
+        };
+
+        let schedule = args.required("schedule")?.to_string();
+        if schedule.contains('\n') {
+            return Err(AliError::BadHookCmd(format!(
+                "{hook_key}: bad schedule {schedule}"
+            )));
+        }
+
+        let env_file = args.get("env_file").map(String::from);
+
+        Ok(HookBackup {
+            backup: Backup {
+                tool,
+                repo,
+                passphrase,
+                env_file,
+                paths,
+                excludes,
+                schedule,
+                keep,
+                firstboot: args.flag("firstboot"),
+            },
+            mode_hook,
+        })
+    }
+}
+
+/// Parses comma-separated list, which is later single-quoted in script
+fn parse_list(
+    hook_key: &str,
+    key: &str,
+    s: &str,
+) -> Result<Vec<String>, AliError> {
+    let list: Vec<String> = s.split(',').map(String::from).collect();
+    if list
+        .iter()
+        .any(|item| item.is_empty() || item.contains('\''))
+    {
+        return Err(AliError::BadHookCmd(format!("{hook_key}: bad {key} {s}")));
+    }
+
+    Ok(list)
+}
+
+/// Parses `<DAILY>,<WEEKLY>,<MONTHLY>`
+fn parse_keep(hook_key: &str, s: &str) -> Result<[u32; 3], AliError> {
+    let keep: Vec<u32> = s
+        .split(',')
+        .map(|n| n.parse::<u32>())
+        .collect::<Result<_, _>>()
+        .map_err(|err| {
+            AliError::BadHookCmd(format!("{hook_key}: bad keep {s}: {err}"))
+        })?;
+
+    keep.try_into().map_err(|_| {
+        AliError::BadHookCmd(format!(
+            "{hook_key}: bad keep {s}, expecting <DAILY>,<WEEKLY>,<MONTHLY>"
+        ))
+    })
+}
+
+/// Installs backup tool, writes config, script, and units, and enables
+/// backup timer (and first boot backup) in the new system
+fn apply_backup(
+    hook_key: &str,
+    mode_hook: &ModeHook,
+    backup: &Backup,
+    root_location: &str,
+) -> Result<ActionHook, AliError> {
+    let cmd_install = format!("pacman -S --needed --noconfirm {}", backup.tool);
+    let services = backup.services();
+
+    if matches!(mode_hook, ModeHook::Print) {
+        output::println(&format!("# {cmd_install}"));
+        for f in backup.encode_files(false)? {
+            output::println(&format!("# {}\n{}", f.path, f.content));
+        }
+        for (service, _) in services {
+            output::println(&format!("# enable {service}"));
+        }
+
+        return Ok(ActionHook::Backup(backup.to_string()));
+    }
+
+    match root_location {
+        "/" => shell::sh_c(&cmd_install)?,
+        _ => shell::arch_chroot(root_location, &cmd_install)?,
+    }
+
+    for f in backup.encode_files(true)? {
+        protected::write_mode_under(
+            hook_key,
+            root_location,
+            &f.path,
+            &f.content,
+            f.mode,
+        )?;
+    }
+
+    for (service, target) in services {
+        systemd::enable_service(root_location, service, target)?;
+    }
+
+    Ok(ActionHook::Backup(backup.to_string()))
+}
+
+impl Backup {
+    /// Units to enable, with their install targets
+    fn services(&self) -> Vec<(&'static str, &'static str)> {
+        let mut services = vec![("ali-rs-backup.timer", "timers.target")];
+        if self.firstboot {
+            services
+                .push(("ali-rs-backup-firstboot.service", "multi-user.target"));
+        }
+
+        services
+    }
+
+    /// Returns all files to be written, with paths relative to the new root.
+    /// Secrets are only read if `with_secrets`, and are otherwise redacted.
+    fn encode_files(
+        &self,
+        with_secrets: bool,
+    ) -> Result<Vec<BackupFile>, AliError> {
+        let tool = self.tool.to_string();
+        let mut files = vec![
+            BackupFile {
+                path: FILENAME_ENV.to_string(),
+                content: self.encode_env(with_secrets)?,
+                mode: 0o600,
+            },
+            BackupFile {
+                path: FILENAME_SCRIPT.to_string(),
+                content: self.encode_script(),
+                mode: 0o755,
+            },
+            BackupFile {
+                path: FILENAME_SERVICE.to_string(),
+                content: SERVICE.replace(TOKEN_TOOL, &tool),
+                mode: 0o644,
+            },
+            BackupFile {
+                path: FILENAME_TIMER.to_string(),
+                content: TIMER
+                    .replace(TOKEN_TOOL, &tool)
+                    .replace(TOKEN_SCHEDULE, &self.schedule),
+                mode: 0o644,
+            },
+        ];
+
+        if self.firstboot {
+            files.push(BackupFile {
+                path: FILENAME_FIRSTBOOT.to_string(),
+                content: FIRSTBOOT.replace(TOKEN_TOOL, &tool),
+                mode: 0o644,
+            });
+        }
+
+        Ok(files)
+    }
+
+    /// Encodes systemd environment file with repository and passphrase
+    fn encode_env(&self, with_secrets: bool) -> Result<String, AliError> {
+        let (key_repo, key_passphrase) = match self.tool {
+            Tool::Borg => ("BORG_REPO", "BORG_PASSPHRASE"),
+            Tool::Restic => ("RESTIC_REPOSITORY", "RESTIC_PASSWORD"),
+        };
+
+        let passphrase = match with_secrets {
+            true => self.passphrase.resolve()?,
+            false => "<redacted>".to_string(),
+        };
+
+        let mut env = format!(
+            "# Installed by ali-rs hook @backup\n{key_repo}=\"{}\"\n{key_passphrase}=\"{}\"\n",
+            quote_env(&self.repo),
+            quote_env(&passphrase),
+        );
+
+        match (&self.env_file, with_secrets) {
+            (Some(path), true) => {
+                let extra = std::fs::read_to_string(path).map_err(|err| {
+                    AliError::FileError(
+                        err,
+                        format!("{KEY_BACKUP}: reading env file {path}"),
+                    )
+                })?;
+
+                env.push_str(&extra);
+            }
+            (Some(path), false) => env.push_str(&format!("# <{path}>\n")),
+            (None, _) => {}
+        }
+
+        Ok(env)
+    }
+
+    /// Encodes backup script, which also initializes repository
+    /// if called with `--init`
+    fn encode_script(&self) -> String {
+        let quote = |items: &[String]| -> String {
+            items.iter().map(|s| format!(" '{s}'")).collect()
+        };
+
+        let excludes: String = self
+            .excludes
+            .iter()
+            .map(|e| format!(" --exclude '{e}'"))
+            .collect();
+
+        let paths = quote(&self.paths);
+        let [daily, weekly, monthly] = self.keep;
+        let keep = format!(
+            "--keep-daily {daily} --keep-weekly {weekly} --keep-monthly {monthly}"
+        );
+
+        let (init, create, prune) = match self.tool {
+            Tool::Borg => {
+                (
+                    "borg info >/dev/null 2>&1 || borg init --encryption=repokey",
+                    format!(
+                        "borg create --stats --one-file-system --exclude-caches{excludes} '::{{hostname}}-{{now}}'{paths}"
+                    ),
+                    format!("borg prune {keep}"),
+                )
+            }
+            Tool::Restic => {
+                (
+                    "restic cat config >/dev/null 2>&1 || restic init",
+                    format!(
+                        "restic backup --one-file-system --exclude-caches{excludes}{paths}"
+                    ),
+                    format!("restic forget --prune {keep}"),
+                )
+            }
+        };
+
+        format!(
+            "#!/bin/sh
+# Installed by ali-rs hook @backup
+set -eu
+
+if [ \"${{1:-}}\" = --init ]; then
+    {init}
+fi
+
+{create}
+{prune}
+"
+        )
+    }
+}
+
+impl Passphrase {
+    fn resolve(&self) -> Result<String, AliError> {
+        let passphrase = match self {
+            Passphrase::Inline(p) => secrets::resolve(p)?,
+            Passphrase::File(path) => {
+                let p = std::fs::read_to_string(path).map_err(|err| {
+                    AliError::FileError(
+                        err,
+                        format!("{KEY_BACKUP}: reading passphrase file {path}"),
+                    )
+                })?;
+
+                let p = p.trim();
+                secrets::register(p);
+
+                p.to_string()
+            }
+        };
+
+        Ok(passphrase)
+    }
+}
+
+/// Escapes `s` for double-quoted value in systemd environment file
+fn quote_env(s: &str) -> String {
+    s.chars()
+        .flat_map(|c| {
+            match c {
+                '\\' | '"' | '$' | '`' => vec!['\\', c],
+                c => vec![c],
+            }
+        })
+        .collect()
+}
+
+impl std::fmt::Display for Backup {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Passphrase is omitted from reports
+        let j = json!({
+            "tool": self.tool.to_string(),
+            "repo": self.repo,
+            "paths": self.paths,
+            "exclude": self.excludes,
+            "schedule": self.schedule,
+            "keep": self.keep,
+            "firstboot": self.firstboot,
+        });
+
+        write!(f, "{}", json::to_string(&j))
+    }
+}
+
+impl std::fmt::Display for Tool {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Borg => write!(f, "borg"),
+            Self::Restic => write!(f, "restic"),
+        }
+    }
+}
+
+#[test]
+fn test_parse_backup() {
+    let should_pass = vec![
+        (
+            "@backup restic repo=/srv/restic passphrase=foo",
+            Backup {
+                tool: Tool::Restic,
+                repo: "/srv/restic".into(),
+                passphrase: Passphrase::Inline("foo".into()),
+                env_file: None,
+                paths: DEFAULT_PATHS.map(String::from).to_vec(),
+                excludes: vec![],
+                schedule: DEFAULT_SCHEDULE.into(),
+                keep: DEFAULT_KEEP,
+                firstboot: false,
+            },
+        ),
+        (
+            "@backup-print borg firstboot repo=ssh://nas/./borg passphrase_file=/root/pass paths=/home,/srv 'exclude=*.cache' 'schedule=Mon *-*-* 03:00' keep=3,2,1 env_file=/root/env",
+            Backup {
+                tool: Tool::Borg,
+                repo: "ssh://nas/./borg".into(),
+                passphrase: Passphrase::File("/root/pass".into()),
+                env_file: Some("/root/env".into()),
+                paths: vec!["/home".into(), "/srv".into()],
+                excludes: vec!["*.cache".into()],
+                schedule: "Mon *-*-* 03:00".into(),
+                keep: [3, 2, 1],
+                firstboot: true,
+            },
+        ),
+    ];
+
+    for (cmd, expected) in should_pass {
+        let hook = HookBackup::try_from(cmd).unwrap();
+        assert_eq!(expected, hook.backup);
+    }
+
+    let should_err = vec![
+        "@backup repo=/srv/restic passphrase=foo",
+        "@backup rsync repo=/srv/restic passphrase=foo",
+        "@backup restic passphrase=foo",
+        "@backup restic repo=/srv/restic",
+        "@backup restic repo=/srv/restic passphrase=foo passphrase_file=/root/pass",
+        "@backup restic repo=/srv/restic passphrase=foo paths=home",
+        "@backup restic repo=/srv/restic passphrase=foo paths=/home,",
+        "@backup restic repo=/srv/restic passphrase=foo keep=7,4",
+        "@backup restic repo=/srv/restic passphrase=foo foo=bar",
+        "@backup restic borg repo=/srv/restic passphrase=foo",
+    ];
+
+    for cmd in should_err {
+        assert!(
+            HookBackup::try_from(cmd).is_err(),
+            "expecting error for {cmd}"
+        );
+    }
+}
+
+#[test]
+fn test_encode_backup() {
+    let hook = HookBackup::try_from(
+        "@backup restic repo=/srv/restic 'passphrase=fo\"o$' paths=/home exclude=/home/*/.cache firstboot",
+    )
+    .unwrap();
+
+    let files = hook.backup.encode_files(true).unwrap();
+    let paths: Vec<&str> = files.iter().map(|f| f.path.as_str()).collect();
+    assert_eq!(
+        vec![
+            FILENAME_ENV,
+            FILENAME_SCRIPT,
+            FILENAME_SERVICE,
+            FILENAME_TIMER,
+            FILENAME_FIRSTBOOT,
+        ],
+        paths,
+    );
+
+    assert_eq!(
+        "# Installed by ali-rs hook @backup\nRESTIC_REPOSITORY=\"/srv/restic\"\nRESTIC_PASSWORD=\"fo\\\"o\\$\"\n",
+        files[0].content,
+    );
+    assert!(files[1].content.contains(
+        "restic backup --one-file-system --exclude-caches --exclude '/home/*/.cache' '/home'"
+    ));
+    assert!(files[1].content.contains(
+        "restic forget --prune --keep-daily 7 --keep-weekly 4 --keep-monthly 6"
+    ));
+    assert!(files[3].content.contains("OnCalendar=daily"));
+
+    // Secrets are redacted in print mode
+    let files = hook.backup.encode_files(false).unwrap();
+    assert!(!files[0].content.contains("fo\\\"o"));
+}
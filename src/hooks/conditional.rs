@@ -0,0 +1,372 @@
+use std::process::Command;
+
+use serde_json::json;
+
+use super::{
+    wrap_bad_hook_cmd,
+    ActionHook,
+    Caller,
+    Hook,
+    HookSpec,
+    ModeHook,
+    ParseError,
+    KEY_IF,
+};
+use crate::errors::AliError;
+use crate::hooks;
+use crate::utils::fs::file_exists;
+use crate::utils::json;
+
+const USAGE: &str = "<PREDICATE> [PREDICATE..] <HOOK_CMD>, PREDICATE: exists=<PATH> | root-exists=<PATH> | boot=uefi|bios | arch=<ARCH> | virt=<VIRT>|none|any, or with != to negate";
+
+/// Example arguments, for `ali-rs hooks schema`
+pub(super) const EXAMPLE: &str = "boot=uefi @quicknet eth0";
+
+/// Keyword arguments, for shell completion
+pub(super) const KEYWORDS: &[&str] = &[
+    "exists=",
+    "root-exists=",
+    "boot=uefi",
+    "boot=bios",
+    "arch=",
+    "virt=",
+];
+
+const SYS_EFI: &str = "/sys/firmware/efi";
+
+/// Hook `@if`, which runs the wrapped hook only if all predicates hold
+struct HookIf {
+    predicates: Vec<Predicate>,
+    inner: Box<dyn Hook>,
+}
+
+/// Fact about the live system or the new system, compared with `=` or `!=`
+#[derive(Debug, Clone, PartialEq)]
+struct Predicate {
+    fact: Fact,
+    value: String,
+    negate: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Fact {
+    /// Path exists on the live system
+    Exists,
+    /// Path exists under root location
+    RootExists,
+    /// Boot mode of the live system, `uefi` or `bios`
+    Boot,
+    /// CPU architecture, e.g. `x86_64` or `aarch64`
+    Arch,
+    /// Virtualization from systemd-detect-virt, e.g. `kvm`,
+    /// `none` (bare metal), or `any` (any virtualization)
+    Virt,
+}
+
+pub(super) fn parse(_k: &str, cmd: &str) -> Result<Box<dyn Hook>, ParseError> {
+    match HookIf::try_from(cmd) {
+        Ok(hook) => Ok(Box::new(hook)),
+        Err(err) => Err(wrap_bad_hook_cmd(err, USAGE)),
+    }
+}
+
+impl Hook for HookIf {
+    fn base_key(&self) -> &'static str {
+        KEY_IF
+    }
+
+    fn usage(&self) -> &'static str {
+        USAGE
+    }
+
+    fn mode(&self) -> ModeHook {
+        self.inner.mode()
+    }
+
+    fn should_chroot(&self) -> bool {
+        self.inner.should_chroot()
+    }
+
+    fn prefer_caller(&self, caller: &Caller) -> bool {
+        self.inner.prefer_caller(caller)
+    }
+
+    fn abort_if_no_mount(&self) -> bool {
+        self.inner.abort_if_no_mount()
+    }
+
+    fn spec(&self) -> HookSpec {
+        HookSpec {
+            key: KEY_IF.to_string(),
+            mode: None,
+            callers: None,
+            chroot: None,
+            abort_if_no_mount: None,
+            ..HookSpec::new(self)
+        }
+    }
+
+    fn run_hook(
+        &self,
+        caller: &Caller,
+        root_location: &str,
+    ) -> Result<ActionHook, AliError> {
+        for predicate in &self.predicates {
+            if !predicate.eval(root_location)? {
+                log::info!(
+                    "{}: skipping {}: {predicate} does not hold",
+                    self.base_key(),
+                    self.inner.hook_key(),
+                );
+
+                return Ok(ActionHook::If(json::to_string(&json!({
+                    "skipped": self.inner.hook_key(),
+                    "unmet": predicate.to_string(),
+                }))));
+            }
+        }
+
+        self.inner.run_hook(caller, root_location)
+    }
+}
+
+impl TryFrom<&str> for HookIf {
+    type Error = AliError;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        let (hook_key, parts) = hooks::extract_key_and_parts(s)?;
+        if hook_key != KEY_IF {
+            return Err(AliError::AliRsBug(format!(
+                "{KEY_IF}: bad key {hook_key}",
+            )));
+        }
+
+        let Some(inner_at) = parts.iter().skip(1).position(|p| hooks::is_hook(p))
+        else {
+            return Err(AliError::BadHookCmd(format!(
+                "{hook_key}: missing inner hook",
+            )));
+        };
+
+        // Skip hook key
+        let inner_at = inner_at + 1;
+        if inner_at == 1 {
+            return Err(AliError::BadHookCmd(format!(
+                "{hook_key}: missing predicate",
+            )));
+        }
+
+        let predicates = parts[1..inner_at]
+            .iter()
+            .map(|p| Predicate::try_from(p.as_str()))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let inner_cmd = parts[inner_at..].join(" ");
+        let inner = hooks::parse_hook(&parts[inner_at], &inner_cmd)?;
+
+        Ok(Self { predicates, inner })
+    }
+}
+
+impl Predicate {
+    fn eval(&self, root_location: &str) -> Result<bool, AliError> {
+        let value = self.value.as_str();
+        let holds = match self.fact {
+            Fact::Exists => file_exists(value),
+            Fact::RootExists => {
+                file_exists(format!(
+                    "{}/{}",
+                    root_location.trim_end_matches('/'),
+                    value.trim_start_matches('/'),
+                ))
+            }
+            Fact::Boot => {
+                let boot = match file_exists(SYS_EFI) {
+                    true => "uefi",
+                    false => "bios",
+                };
+
+                boot == value
+            }
+            Fact::Arch => std::env::consts::ARCH == value,
+            Fact::Virt => {
+                let virt = detect_virt()?;
+                match value {
+                    "any" => virt != "none",
+                    value => virt == value,
+                }
+            }
+        };
+
+        Ok(holds != self.negate)
+    }
+}
+
+impl TryFrom<&str> for Predicate {
+    type Error = AliError;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        let (fact, value, negate) = match s.split_once("!=") {
+            Some((fact, value)) => (fact, value, true),
+            None => {
+                let Some((fact, value)) = s.split_once('=') else {
+                    return Err(AliError::BadHookCmd(format!(
+                        "{KEY_IF}: bad predicate {s}, expecting <FACT>=<VALUE> or <FACT>!=<VALUE>"
+                    )));
+                };
+
+                (fact, value, false)
+            }
+        };
+
+        let fact = match fact {
+            "exists" => Fact::Exists,
+            "root-exists" => Fact::RootExists,
+            "boot" => Fact::Boot,
+            "arch" => Fact::Arch,
+            "virt" => Fact::Virt,
+            fact => {
+                return Err(AliError::BadHookCmd(format!(
+                    "{KEY_IF}: unknown fact {fact} in predicate {s}"
+                )))
+            }
+        };
+
+        if value.is_empty() {
+            return Err(AliError::BadHookCmd(format!(
+                "{KEY_IF}: empty value in predicate {s}"
+            )));
+        }
+
+        let path = matches!(fact, Fact::Exists | Fact::RootExists);
+        if path && !value.starts_with('/') {
+            return Err(AliError::BadHookCmd(format!(
+                "{KEY_IF}: path must be absolute, got {value}"
+            )));
+        }
+
+        if fact == Fact::Boot && !matches!(value, "uefi" | "bios") {
+            return Err(AliError::BadHookCmd(format!(
+                "{KEY_IF}: boot mode must be uefi or bios, got {value}"
+            )));
+        }
+
+        Ok(Self {
+            fact,
+            value: value.to_string(),
+            negate,
+        })
+    }
+}
+
+impl std::fmt::Display for Predicate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let fact = match self.fact {
+            Fact::Exists => "exists",
+            Fact::RootExists => "root-exists",
+            Fact::Boot => "boot",
+            Fact::Arch => "arch",
+            Fact::Virt => "virt",
+        };
+
+        let op = match self.negate {
+            true => "!=",
+            false => "=",
+        };
+
+        write!(f, "{fact}{op}{}", self.value)
+    }
+}
+
+/// Virtualization of the live system from systemd-detect-virt,
+/// which prints `none` and exits with 1 on bare metal
+fn detect_virt() -> Result<String, AliError> {
+    let output = Command::new("systemd-detect-virt").output().map_err(|err| {
+        AliError::HookError(format!(
+            "{KEY_IF}: failed to run systemd-detect-virt: {err}"
+        ))
+    })?;
+
+    let virt = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    match virt.is_empty() {
+        true => Ok("none".to_string()),
+        false => Ok(virt),
+    }
+}
+
+#[test]
+fn test_parse_if() {
+    let hook = HookIf::try_from(
+        "@if boot=uefi arch!=aarch64 @uncomment-print PORT /etc/ssh/sshd_config",
+    )
+    .unwrap();
+
+    assert_eq!(
+        vec![
+            Predicate {
+                fact: Fact::Boot,
+                value: "uefi".into(),
+                negate: false,
+            },
+            Predicate {
+                fact: Fact::Arch,
+                value: "aarch64".into(),
+                negate: true,
+            },
+        ],
+        hook.predicates,
+    );
+    assert_eq!("@uncomment-print", hook.inner.hook_key());
+    assert_eq!(ModeHook::Print, hook.mode());
+
+    let should_err = vec![
+        "@if",                                      // Missing predicate and hook
+        "@if boot=uefi",                            // Missing hook
+        "@if @uncomment PORT /etc/ssh/sshd_config", // Missing predicate
+        "@if boot @uncomment PORT /etc/ssh/sshd_config",
+        "@if boot=efi @uncomment PORT /etc/ssh/sshd_config",
+        "@if exists=foo @uncomment PORT /etc/ssh/sshd_config",
+        "@if os=linux @uncomment PORT /etc/ssh/sshd_config",
+        "@if arch= @uncomment PORT /etc/ssh/sshd_config",
+        "@if arch=x86_64 @uncomment /etc/ssh/sshd_config", // Bad inner hook
+    ];
+
+    for cmd in should_err {
+        assert!(HookIf::try_from(cmd).is_err(), "expecting error for {cmd}");
+    }
+}
+
+#[test]
+fn test_eval_predicates() {
+    let dir = std::env::temp_dir()
+        .join(format!("ali-rs-test-if-{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("etc")).unwrap();
+    let root = dir.to_string_lossy().to_string();
+
+    let arch = std::env::consts::ARCH;
+    let boot = match file_exists(SYS_EFI) {
+        true => "uefi",
+        false => "bios",
+    };
+
+    let tests = [
+        (format!("exists={root}/etc"), true),
+        (format!("exists={root}/foo"), false),
+        (format!("exists!={root}/foo"), true),
+        ("root-exists=/etc".to_string(), true),
+        ("root-exists=/foo".to_string(), false),
+        (format!("arch={arch}"), true),
+        (format!("arch!={arch}"), false),
+        ("arch=pdp11".to_string(), false),
+        (format!("boot={boot}"), true),
+        (format!("boot!={boot}"), false),
+    ];
+
+    for (predicate, expected) in tests {
+        let p = Predicate::try_from(predicate.as_str()).unwrap();
+        assert_eq!(predicate, p.to_string());
+        assert_eq!(expected, p.eval(&root).unwrap(), "{predicate}");
+    }
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
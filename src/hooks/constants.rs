@@ -1,6 +1,10 @@
 pub mod hook_keys {
     pub const KEY_WRAPPER_MNT: &str = "@mnt";
     pub const KEY_WRAPPER_NO_MNT: &str = "@no-mnt";
+    /// Runs inner hook only if all predicates hold
+    pub const KEY_IF: &str = "@if";
+    /// Lets inner hook write protected paths
+    pub const KEY_UNPROTECT: &str = "@unprotect";
     pub const KEY_QUICKNET: &str = "@quicknet";
     pub const KEY_QUICKNET_PRINT: &str = "@quicknet-print";
     pub const KEY_MKINITCPIO: &str = "@mkinitcpio";
@@ -13,6 +17,24 @@ pub mod hook_keys {
     pub const KEY_REPLACE_TOKEN_PRINT: &str = "@replace-token-print";
     pub const KEY_DOWNLOAD: &str = "@download";
     pub const KEY_DOWNLOAD_PRINT: &str = "@download-print";
+    pub const KEY_BACKUP: &str = "@backup";
+    pub const KEY_BACKUP_PRINT: &str = "@backup-print";
+    pub const KEY_ROLLBACK: &str = "@rollback";
+    pub const KEY_ROLLBACK_PRINT: &str = "@rollback-print";
+    pub const KEY_WIREGUARD: &str = "@wireguard";
+    pub const KEY_WIREGUARD_PRINT: &str = "@wireguard-print";
+    pub const KEY_TAILSCALE: &str = "@tailscale";
+    pub const KEY_TAILSCALE_PRINT: &str = "@tailscale-print";
+    pub const KEY_PAM: &str = "@pam";
+    pub const KEY_PAM_PRINT: &str = "@pam-print";
+    pub const KEY_SSSD: &str = "@sssd";
+    pub const KEY_SSSD_PRINT: &str = "@sssd-print";
+    /// Assertion hooks, which are read-only and have no print variants
+    pub const KEY_ASSERT_FILE_CONTAINS: &str = "@assert-file-contains";
+    pub const KEY_ASSERT_SERVICE_ENABLED: &str = "@assert-service-enabled";
+    pub const KEY_ASSERT_MOUNTED: &str = "@assert-mounted";
+    /// Prefix of plugin hooks, run by external executables
+    pub const KEY_PREFIX_PLUGIN: &str = "@x-";
 }
 
 pub mod quicknet {
@@ -20,7 +42,23 @@ pub mod quicknet {
 
     pub const TOKEN_DNS: &str = "{{ dns_upstream }}";
 
-    pub const FILENAME_TPL: &str = "00-dhcp_{{ inf }}-quicknet.conf";
+    pub const TOKEN_SSID: &str = "{{ ssid }}";
+
+    pub const TOKEN_PSK: &str = "{{ psk }}";
+
+    pub const FILENAME_TPL: &str = "00-dhcp_{{ inf }}-quicknet.network";
+
+    pub const DIR_NETWORKD: &str = "/etc/systemd/network";
+
+    pub const DIR_IWD: &str = "/var/lib/iwd";
+
+    pub const FILENAME_NM_TPL: &str =
+        "/etc/NetworkManager/system-connections/quicknet-{{ inf }}.nmconnection";
+
+    pub const FILENAME_IWD_TPL: &str = "{{ ssid }}.psk";
+
+    pub const FILENAME_WPA_SUPPLICANT_TPL: &str =
+        "/etc/wpa_supplicant/wpa_supplicant-{{ inf }}.conf";
 
     pub const NETWORKD_DHCP: &str = r#"# Installed by ali-rs hook @quicknet
 [Match]
@@ -30,8 +68,116 @@ Name={{ inf }}
 DHCP=yes
 "#;
 
+    pub const TOKEN_KIND: &str = "{{ kind }}";
+
+    pub const TOKEN_MASTER: &str = "{{ master }}";
+
+    pub const TOKEN_VLAN_ID: &str = "{{ vlan_id }}";
+
+    pub const TOKEN_BOND_MODE: &str = "{{ bond_mode }}";
+
+    pub const FILENAME_NETDEV_TPL: &str = "00-{{ inf }}-quicknet.netdev";
+
+    pub const FILENAME_MEMBER_TPL: &str =
+        "10-member_{{ inf }}-quicknet.network";
+
+    pub const NETWORKD_NETDEV: &str = r#"# Installed by ali-rs hook @quicknet
+[NetDev]
+Name={{ inf }}
+Kind={{ kind }}
+"#;
+
+    pub const NETWORKD_NETDEV_VLAN: &str = r#"
+[VLAN]
+Id={{ vlan_id }}
+"#;
+
+    pub const NETWORKD_NETDEV_BOND: &str = r#"
+[Bond]
+Mode={{ bond_mode }}
+"#;
+
+    /// Member (lower) interface of VLAN, bridge, or bond,
+    /// with `{{ kind }}` being networkd key `VLAN`, `Bridge`, or `Bond`
+    pub const NETWORKD_MEMBER: &str = r#"# Installed by ali-rs hook @quicknet
+[Match]
+Name={{ inf }}
+
+[Network]
+{{ kind }}={{ master }}
+"#;
+
+    pub const TOKEN_ADDRESS: &str = "{{ address }}";
+
+    pub const NETWORKD_IPV6_AUTO: &str = "IPv6AcceptRA=yes\n";
+
+    pub const NETWORKD_IPV6_STATIC: &str =
+        "DHCP=ipv4\nIPv6AcceptRA=no\nAddress={{ address }}\n";
+
+    pub const NETWORKD_IPV6_OFF: &str =
+        "DHCP=ipv4\nIPv6AcceptRA=no\nLinkLocalAddressing=ipv4\n";
+
+    pub const FILENAME_SYSCTL_IPV6_TPL: &str =
+        "/etc/sysctl.d/40-quicknet-ipv6_{{ inf }}.conf";
+
+    /// `{{ inf }}` must have `.` replaced with `/` (e.g. VLAN `eth0/10`)
+    pub const SYSCTL_IPV6_OFF: &str = r#"# Installed by ali-rs hook @quicknet
+net.ipv6.conf.{{ inf }}.disable_ipv6 = 1
+"#;
+
+    pub const NM_IPV6_AUTO: &str = "[ipv6]\nmethod=auto\n";
+
+    pub const NM_IPV6_STATIC: &str =
+        "[ipv6]\nmethod=manual\naddress1={{ address }}\n";
+
+    pub const NM_IPV6_OFF: &str = "[ipv6]\nmethod=disabled\n";
+
     pub const NETWORKD_DNS: &str = r#"# Installed by ali-rs hook @quicknet
 DNS={{ dns_upstream }}
+"#;
+
+    pub const NM_CONNECTION: &str = r#"# Installed by ali-rs hook @quicknet
+[connection]
+id=quicknet-{{ inf }}
+type={{ type }}
+interface-name={{ inf }}
+"#;
+
+    pub const NM_WIFI: &str = r#"
+[wifi]
+mode=infrastructure
+ssid={{ ssid }}
+
+[wifi-security]
+key-mgmt=wpa-psk
+psk={{ psk }}
+"#;
+
+    pub const NM_IP: &str = r#"
+[ipv4]
+method=auto
+
+[ipv6]
+method=auto
+"#;
+
+    pub const NM_DNS: &str = "dns={{ dns_upstream }};\n";
+
+    pub const TOKEN_TYPE: &str = "{{ type }}";
+
+    pub const IWD_PSK: &str = r#"# Installed by ali-rs hook @quicknet
+[Security]
+Passphrase={{ psk }}
+"#;
+
+    pub const WPA_SUPPLICANT: &str = r#"# Installed by ali-rs hook @quicknet
+ctrl_interface=/run/wpa_supplicant
+update_config=1
+
+network={
+    ssid={{ ssid }}
+    psk="{{ psk }}"
+}
 "#;
 
     #[test]
@@ -39,13 +185,226 @@ DNS={{ dns_upstream }}
         assert!(FILENAME_TPL.contains(TOKEN_INTERFACE));
         assert!(NETWORKD_DHCP.contains(TOKEN_INTERFACE));
         assert!(NETWORKD_DNS.contains(TOKEN_DNS));
+        assert!(FILENAME_IWD_TPL.contains(TOKEN_SSID));
+        assert!(FILENAME_WPA_SUPPLICANT_TPL.contains(TOKEN_INTERFACE));
+        assert!(IWD_PSK.contains(TOKEN_PSK));
+        assert!(FILENAME_NM_TPL.contains(TOKEN_INTERFACE));
+        assert!(NM_CONNECTION.contains(TOKEN_INTERFACE));
+        assert!(NM_CONNECTION.contains(TOKEN_TYPE));
+        assert!(NM_WIFI.contains(TOKEN_SSID));
+        assert!(NM_WIFI.contains(TOKEN_PSK));
+        assert!(NM_DNS.contains(TOKEN_DNS));
+        assert!(WPA_SUPPLICANT.contains(TOKEN_SSID));
+        assert!(WPA_SUPPLICANT.contains(TOKEN_PSK));
     }
 }
 
 pub mod mkinitcpio {
+    pub const FILENAME_MKINITCPIO_CONF: &str = "/etc/mkinitcpio.conf";
+
+    pub const TOKEN_KERNEL: &str = "{{ kernel }}";
+
+    pub const FILENAME_PRESET_TPL: &str =
+        "/etc/mkinitcpio.d/{{ kernel }}.preset";
+
+    pub const PRESET_TPL: &str = r#"# mkinitcpio preset file for the '{{ kernel }}' package
+# Installed by ali-rs hook @mkinitcpio
+
+#ALL_config="/etc/mkinitcpio.conf"
+ALL_kver="/boot/vmlinuz-{{ kernel }}"
+
+PRESETS=('default' 'fallback')
+
+#default_config="/etc/mkinitcpio.conf"
+default_image="/boot/initramfs-{{ kernel }}.img"
+
+#fallback_config="/etc/mkinitcpio.conf"
+fallback_image="/boot/initramfs-{{ kernel }}-fallback.img"
+fallback_options="-S autodetect"
+"#;
+
     pub const MKINITCPIO_PRESET_LVM_ROOT: &str =
-        "base udev autodetect modconf kms keyboard keymap consolefont block lvm2 filesystems fsck";
-    pub const MKINITCPIO_PRESET_LUKS_ROOT: &str = "@TODO-luks";
-    pub const MKINITCPIO_PRESET_LVM_ON_LUKS_ROOT: &str = "@TODO-lvm-on-luks";
-    pub const MKINITCPIO_PRESET_LUKS_ON_LVM_ROOT: &str = "@TODO-luks-on-lvm";
+        "base udev autodetect microcode modconf kms keyboard keymap consolefont block lvm2 filesystems fsck";
+    pub const MKINITCPIO_PRESET_LUKS_ROOT: &str =
+        "base udev autodetect microcode modconf kms keyboard keymap consolefont block encrypt filesystems fsck";
+    pub const MKINITCPIO_PRESET_LVM_ON_LUKS_ROOT: &str =
+        "base udev autodetect microcode modconf kms keyboard keymap consolefont block encrypt lvm2 filesystems fsck";
+    pub const MKINITCPIO_PRESET_LUKS_ON_LVM_ROOT: &str =
+        "base udev autodetect microcode modconf kms keyboard keymap consolefont block lvm2 encrypt filesystems fsck";
+}
+
+pub mod backup {
+    pub const TOKEN_TOOL: &str = "{{ tool }}";
+
+    pub const TOKEN_SCHEDULE: &str = "{{ schedule }}";
+
+    pub const FILENAME_ENV: &str = "/etc/ali-rs/backup.env";
+
+    pub const FILENAME_SCRIPT: &str = "/usr/local/bin/ali-rs-backup";
+
+    pub const FILENAME_SERVICE: &str =
+        "/etc/systemd/system/ali-rs-backup.service";
+
+    pub const FILENAME_TIMER: &str = "/etc/systemd/system/ali-rs-backup.timer";
+
+    pub const FILENAME_FIRSTBOOT: &str =
+        "/etc/systemd/system/ali-rs-backup-firstboot.service";
+
+    pub const SERVICE: &str = r#"# Installed by ali-rs hook @backup
+[Unit]
+Description=Backup with {{ tool }}
+Wants=network-online.target
+After=network-online.target
+
+[Service]
+Type=oneshot
+EnvironmentFile=/etc/ali-rs/backup.env
+ExecStart=/usr/local/bin/ali-rs-backup
+Nice=19
+IOSchedulingClass=idle
+"#;
+
+    pub const TIMER: &str = r#"# Installed by ali-rs hook @backup
+[Unit]
+Description=Periodic backup with {{ tool }}
+
+[Timer]
+OnCalendar={{ schedule }}
+RandomizedDelaySec=30m
+Persistent=true
+
+[Install]
+WantedBy=timers.target
+"#;
+
+    /// Initializes repository (if needed) and runs first backup once
+    pub const FIRSTBOOT: &str = r#"# Installed by ali-rs hook @backup
+[Unit]
+Description=First backup with {{ tool }}
+Wants=network-online.target
+After=network-online.target
+ConditionPathExists=!/var/lib/ali-rs/backup-firstboot
+
+[Service]
+Type=oneshot
+EnvironmentFile=/etc/ali-rs/backup.env
+ExecStart=/usr/local/bin/ali-rs-backup --init
+ExecStartPost=/usr/bin/mkdir -p /var/lib/ali-rs
+ExecStartPost=/usr/bin/touch /var/lib/ali-rs/backup-firstboot
+
+[Install]
+WantedBy=multi-user.target
+"#;
+
+    #[test]
+    fn test_tokens() {
+        assert!(SERVICE.contains(TOKEN_TOOL));
+        assert!(TIMER.contains(TOKEN_TOOL));
+        assert!(TIMER.contains(TOKEN_SCHEDULE));
+        assert!(FIRSTBOOT.contains(TOKEN_TOOL));
+        assert!(SERVICE.contains(FILENAME_ENV));
+        assert!(SERVICE.contains(FILENAME_SCRIPT));
+        assert!(FIRSTBOOT.contains(FILENAME_SCRIPT));
+    }
+}
+
+pub mod rollback {
+    pub const FILENAME_DEFAULT_GRUB: &str = "/etc/default/grub";
+
+    /// Shows fallback initramfs and kernel entries in GRUB top-level menu
+    pub const DEFAULT_GRUB_FALLBACK: &str =
+        "\n# Installed by ali-rs hook @rollback\nGRUB_DISABLE_SUBMENU=y\n";
+
+    /// Appended to HOOKS, so that read-only snapshots boot with
+    /// writable overlay
+    pub const FILENAME_MKINITCPIO: &str =
+        "/etc/mkinitcpio.conf.d/20-ali-rs-rollback.conf";
+
+    pub const MKINITCPIO_OVERLAYFS: &str =
+        "# Installed by ali-rs hook @rollback\nHOOKS+=(grub-btrfs-overlayfs)\n";
+
+    pub use crate::linux::loader::DIR_ENTRIES as DIR_LOADER_ENTRIES;
+
+    pub const SNAPPER_CONFIG: &str = "/etc/snapper/configs/root";
+
+    pub const PACKAGES_SNAPPER: [&str; 4] =
+        ["snapper", "snap-pac", "grub-btrfs", "inotify-tools"];
+}
+
+pub mod wireguard {
+    pub const DIR_WIREGUARD: &str = "/etc/wireguard";
+
+    pub const FILENAME_HOSTS: &str = "/etc/hosts";
+
+    pub const PACKAGE: &str = "wireguard-tools";
+
+    /// Keepalive for peers behind NAT, as recommended by wg(8)
+    pub const DEFAULT_KEEPALIVE: u16 = 25;
+}
+
+pub mod tailscale {
+    pub const PACKAGE: &str = "tailscale";
+
+    /// Pre-auth key, removed after enrollment
+    pub const FILENAME_AUTHKEY: &str = "/etc/ali-rs/tailscale.authkey";
+
+    pub const FILENAME_SCRIPT: &str = "/usr/local/bin/ali-rs-tailscale-up";
+
+    pub const FILENAME_SERVICE: &str =
+        "/etc/systemd/system/ali-rs-tailscale-up.service";
+
+    /// Output of `tailscale status --json` after enrollment
+    pub const FILENAME_STATUS: &str = "/var/lib/ali-rs/tailscale-status.json";
+
+    pub const SERVICE_TAILSCALED: &str = "tailscaled.service";
+
+    /// Enrolls once on first boot, i.e. while pre-auth key exists
+    pub const SERVICE: &str = r#"# Installed by ali-rs hook @tailscale
+[Unit]
+Description=Enroll in tailnet on first boot
+Wants=network-online.target
+After=network-online.target tailscaled.service
+Requires=tailscaled.service
+ConditionPathExists=/etc/ali-rs/tailscale.authkey
+
+[Service]
+Type=oneshot
+ExecStart=/usr/local/bin/ali-rs-tailscale-up
+
+[Install]
+WantedBy=multi-user.target
+"#;
+
+    #[test]
+    fn test_paths() {
+        assert!(SERVICE.contains(FILENAME_AUTHKEY));
+        assert!(SERVICE.contains(FILENAME_SCRIPT));
+        assert!(SERVICE.contains(SERVICE_TAILSCALED));
+    }
+}
+
+pub mod pam {
+    pub const FILENAME_FAILLOCK_CONF: &str = "/etc/security/faillock.conf";
+
+    pub const FILENAME_PWQUALITY_CONF: &str = "/etc/security/pwquality.conf";
+
+    pub const PACKAGE_U2F: &str = "pam-u2f";
+
+    pub const PACKAGE_PWQUALITY: &str = "libpwquality";
+
+    /// Minimum password length accepted by pam_pwquality
+    pub const MIN_MINLEN: u32 = 6;
+}
+
+pub mod sssd {
+    pub const PACKAGE: &str = "sssd";
+
+    pub const SERVICE: &str = "sssd.service";
+
+    /// Must be owned by root with mode 600, or sssd refuses to start
+    pub const FILENAME_CONF: &str = "/etc/sssd/sssd.conf";
+
+    /// Databases served by sssd
+    pub const NSS_DATABASES: &[&str] =
+        &["passwd", "group", "shadow", "netgroup"];
 }
@@ -1,4 +1,16 @@
-use super::utils::download;
+use std::sync::LazyLock;
+
+use serde_json::json;
+
+use super::args::{
+    self,
+    Arg,
+};
+use super::utils::{
+    download,
+    output,
+    protected,
+};
 use super::{
     wrap_bad_hook_cmd,
     ActionHook,
@@ -9,12 +21,32 @@ use super::{
     KEY_DOWNLOAD_PRINT,
 };
 use crate::errors::AliError;
+use crate::types::blockdev::parse_human_bytes;
+use crate::utils::checksum;
+use crate::utils::fs::path_under;
+use crate::utils::json;
+
+const ARGS: &[Arg] = &[
+    Arg::positional("url", "URL[|MIRROR..]"),
+    Arg::positional("outfile", "OUTFILE"),
+    Arg::key("limit_rate", "RATE"),
+    Arg::key("sha256", "CHECKSUM"),
+];
+
+static USAGE: LazyLock<String> = LazyLock::new(|| args::usage(ARGS));
+
+/// Example arguments, for `ali-rs hooks schema`
+pub(super) const EXAMPLE: &str =
+    "https://example.com/archlinux.iso /root/archlinux.iso";
 
-const USAGE: &str = "<url> <outfile>";
+/// Keyword arguments, for shell completion
+pub(super) const KEYWORDS: &[&str] = &["limit_rate=", "sha256="];
 
 struct HookDownload {
     url: String,
     outfile: String,
+    limit_rate: Option<u64>,
+    sha256: Option<String>,
     mode_hook: ModeHook,
 }
 
@@ -22,7 +54,7 @@ pub(super) fn parse(k: &str, cmd: &str) -> Result<Box<dyn Hook>, ParseError> {
     match k {
         KEY_DOWNLOAD | KEY_DOWNLOAD_PRINT => {
             match HookDownload::try_from(cmd) {
-                Err(err) => Err(wrap_bad_hook_cmd(err, USAGE)),
+                Err(err) => Err(wrap_bad_hook_cmd(err, &USAGE)),
                 Ok(hook) => Ok(Box::new(hook)),
             }
         }
@@ -35,24 +67,43 @@ impl TryFrom<&str> for HookDownload {
     type Error = AliError;
 
     fn try_from(cmd: &str) -> Result<Self, Self::Error> {
-        let parts: Vec<_> = cmd.split_whitespace().collect();
-
-        let l = parts.len();
-        if l != 3 {
-            return Err(AliError::BadHookCmd(format!(
-                "expecting 3 arguments, got {l}"
-            )));
+        let (hook_key, parts) = super::extract_key_and_parts(cmd)?;
+        let mode_hook = match hook_key.as_str() {
+            KEY_DOWNLOAD => ModeHook::Normal,
+            KEY_DOWNLOAD_PRINT => ModeHook::Print,
+            key => panic!("unexpected key {key}"),
+        };
+
+        let args = args::parse(&hook_key, &parts[1..], ARGS)?;
+
+        let limit_rate = match args.get("limit_rate") {
+            None => None,
+            Some(rate) => {
+                let rate = parse_human_bytes(rate).map_err(|err| {
+                    AliError::BadHookCmd(format!(
+                        "{hook_key}: bad limit_rate: {err}"
+                    ))
+                })?;
+
+                Some(rate.size() as u64)
+            }
+        };
+
+        let sha256 = args.get("sha256").map(String::from);
+        if let Some(ref sum) = sha256 {
+            if !checksum::is_sha256_hex(sum) {
+                return Err(AliError::BadHookCmd(format!(
+                    "{hook_key}: bad sha256 checksum {sum}"
+                )));
+            }
         }
 
         Ok(Self {
-            mode_hook: match parts[0] {
-                KEY_DOWNLOAD => ModeHook::Normal,
-                KEY_DOWNLOAD_PRINT => ModeHook::Print,
-                key => panic!("unexpected key {key}"),
-            },
-
-            url: parts[1].to_string(),
-            outfile: parts[2].to_string(),
+            limit_rate,
+            sha256,
+            mode_hook,
+            url: args.required("url")?.to_string(),
+            outfile: args.required("outfile")?.to_string(),
         })
     }
 }
@@ -63,7 +114,7 @@ impl Hook for HookDownload {
     }
 
     fn usage(&self) -> &'static str {
-        USAGE
+        &USAGE
     }
 
     fn mode(&self) -> super::ModeHook {
@@ -82,25 +133,119 @@ impl Hook for HookDownload {
         false
     }
 
-    // @TODO: Use param caller and root_location
+    /// Downloads to `outfile` under `root_location`
     fn run_hook(
         &self,
         _caller: &super::Caller,
-        _root_location: &str,
+        root_location: &str,
     ) -> Result<super::ActionHook, AliError> {
-        let downloader = download::Downloader::new_from_url(&self.url)?;
+        let outfile = path_under(root_location, &self.outfile)?;
+        if matches!(self.mode_hook, ModeHook::Normal) {
+            protected::check(&self.hook_key(), root_location, &outfile)?;
+        }
+
+        let mut downloader = download::Downloader::new_from_url(&self.url)?;
+        if let Some(rate) = self.limit_rate {
+            downloader = downloader.with_limit_rate(rate);
+        }
+        if let Some(ref sum) = self.sha256 {
+            downloader = downloader.with_sha256(sum);
+        }
+
         let bytes = downloader.get_bytes()?;
 
-        if let Err(err) = std::fs::write(&self.outfile, bytes) {
-            return Err(AliError::FileError(
-                err,
-                format!("failed to write downloaded file to {}", self.outfile),
-            ));
+        // Print what would be written instead of writing outfile
+        if matches!(self.mode_hook, ModeHook::Print) {
+            output::print(&summary(&self.url, &outfile, &bytes));
+
+            return Ok(ActionHook::Download(json::to_string(&json!({
+                "url": self.url,
+                "outfile": self.outfile,
+            }))));
         }
 
-        Ok(ActionHook::Download(format!(
-            "{} -> {}",
-            self.url, self.outfile
-        )))
+        protected::write_bytes_under(
+            &self.hook_key(),
+            root_location,
+            &self.outfile,
+            &bytes,
+        )?;
+
+        Ok(ActionHook::Download(json::to_string(&json!({
+            "url": self.url,
+            "outfile": self.outfile,
+        }))))
+    }
+}
+
+/// Describes download of `bytes` from `url` to `outfile`,
+/// without the downloaded content
+fn summary(url: &str, outfile: &str, bytes: &[u8]) -> String {
+    format!(
+        "url: {url}\noutfile: {outfile}\nsize: {} bytes\nsha256: {}\n",
+        bytes.len(),
+        checksum::sha256_hex(bytes),
+    )
+}
+
+#[test]
+fn test_summary() {
+    assert_eq!(
+        "url: https://example.com/foo\noutfile: /alitarget/tmp/foo\n\
+        size: 5 bytes\nsha256: \
+        2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824\n",
+        summary("https://example.com/foo", "/alitarget/tmp/foo", b"hello"),
+    );
+}
+
+#[test]
+fn test_parse_download() {
+    let sum =
+        "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+    let should_pass = vec![
+        ("@download https://example.com/foo /tmp/foo".to_string(), None, None),
+        (
+            "@download https://example.com/foo /tmp/foo limit_rate=1K"
+                .to_string(),
+            Some(1000),
+            None,
+        ),
+        (
+            "@download-print https://example.com/foo /tmp/foo limit_rate=2MiB"
+                .to_string(),
+            Some(2 * 1024 * 1024),
+            None,
+        ),
+        (
+            format!("@download https://a.example/foo|https://b.example/foo /tmp/foo sha256={sum}"),
+            None,
+            Some(sum.to_string()),
+        ),
+        (
+            format!("@download https://example.com/foo /tmp/foo sha256={sum} limit_rate=1K"),
+            Some(1000),
+            Some(sum.to_string()),
+        ),
+    ];
+
+    let should_err = vec![
+        "@download https://example.com/foo",
+        "@download https://example.com/foo /tmp/foo 1K",
+        "@download https://example.com/foo /tmp/foo limit_rate=fast",
+        "@download https://example.com/foo /tmp/foo limit_rate=1K bar",
+        "@download https://example.com/foo /tmp/foo limit_rate=1K limit_rate=2K",
+        "@download https://example.com/foo /tmp/foo sha256=abc",
+    ];
+
+    for (cmd, expected_rate, expected_sum) in should_pass {
+        let hook = HookDownload::try_from(cmd.as_str()).unwrap();
+        assert_eq!(expected_rate, hook.limit_rate);
+        assert_eq!(expected_sum, hook.sha256);
+    }
+
+    for cmd in should_err {
+        if HookDownload::try_from(cmd).is_ok() {
+            panic!("unexpected ok result from {cmd}");
+        }
     }
 }
@@ -0,0 +1,239 @@
+use super::*;
+
+/// Hook manuals, one `### ` section per hook
+const HOOKS_MD: &str = include_str!("../../HOOKS.md");
+
+/// Registered hook keys, sorted
+pub fn hook_keys() -> Vec<&'static str> {
+    registry::global()
+        .read()
+        .expect("hook registry lock poisoned")
+        .keys()
+}
+
+/// Returns one-line usage of hook `k`
+pub fn hook_usage(k: &str) -> Result<String, AliError> {
+    let registry = registry::global()
+        .read()
+        .expect("hook registry lock poisoned");
+
+    let Some(constructor) = registry.get(k) else {
+        return Err(unknown_key(&registry, k).error);
+    };
+
+    // Bare hook keys fail to parse for most hooks, with usage as help
+    match constructor(k, k) {
+        Ok(hook) => Ok(hook.usage().to_string()),
+        Err(err) => Ok(err.help_msg),
+    }
+}
+
+/// Returns specs of all registered hooks, parsed from example commands.
+/// Hooks without examples are parsed from bare hook keys, and are
+/// described by usage and keywords only if that fails.
+pub fn hook_specs() -> Vec<HookSpec> {
+    let registry = registry::global()
+        .read()
+        .expect("hook registry lock poisoned");
+
+    let spec = |k: &'static str| {
+        let constructor = registry.get(k).expect("registered hook key");
+        let example = registry.example(k);
+
+        let mut spec = match constructor(k, example.as_deref().unwrap_or(k)) {
+            Ok(hook) => hook.spec(),
+            Err(err) => {
+                HookSpec {
+                    key: k.to_string(),
+                    usage: err.help_msg,
+                    keywords: Vec::new(),
+                    example: None,
+                    mode: None,
+                    callers: None,
+                    chroot: None,
+                    abort_if_no_mount: None,
+                }
+            }
+        };
+
+        spec.keywords = registry.keywords(k).to_vec();
+        spec.example = example;
+
+        spec
+    };
+
+    registry.keys().into_iter().map(spec).collect()
+}
+
+/// Returns keyword arguments of hook `k`, e.g. `marker` for `@uncomment`
+pub fn hook_keywords(k: &str) -> &'static [&'static str] {
+    registry::global()
+        .read()
+        .expect("hook registry lock poisoned")
+        .keywords(k)
+}
+
+/// Returns completions of partial hook command `partial`, each being
+/// `partial` completed with a hook key (e.g. for inner hooks of `@if`)
+/// or a keyword argument of the hook not yet given
+pub fn hook_completions(partial: &str) -> Vec<String> {
+    let (prefix, current) = match partial.rfind(char::is_whitespace) {
+        Some(i) => partial.split_at(i + 1),
+        None => ("", partial),
+    };
+
+    let candidates: Vec<&str> = match prefix.is_empty() || is_hook(current) {
+        true => hook_keys(),
+        false => {
+            let given: Vec<&str> = prefix
+                .split_whitespace()
+                .map(|word| word.split_once('=').map_or(word, |(k, _)| k))
+                .collect();
+
+            // Innermost hook, e.g. @uncomment in `@mnt /mnt @uncomment X`
+            let Some(key) =
+                prefix.split_whitespace().rev().find(|word| is_hook(word))
+            else {
+                return Vec::new();
+            };
+
+            hook_keywords(key)
+                .iter()
+                .filter(|kw| {
+                    let name = kw.split_once('=').map_or(**kw, |(k, _)| k);
+                    !given.contains(&name)
+                })
+                .copied()
+                .collect()
+        }
+    };
+
+    candidates
+        .into_iter()
+        .filter(|candidate| candidate.starts_with(current))
+        .map(|candidate| format!("{prefix}{candidate}"))
+        .collect()
+}
+
+/// Returns manual section of hook `k` in HOOKS.md, if any
+pub fn hook_manual(k: &str) -> Option<&'static str> {
+    manual(HOOKS_MD, k)
+}
+
+/// Returns `### ` section of `md` whose heading names base key of `k`
+fn manual<'a>(md: &'a str, k: &str) -> Option<&'a str> {
+    let base_key = k.strip_suffix("-print").unwrap_or(k);
+    let heading_key = format!("`{base_key}`");
+
+    let start = md.match_indices("\n### ").find_map(|(i, _)| {
+        let heading = md[i + 1..].lines().next()?;
+        heading.contains(&heading_key).then_some(i + 1)
+    })?;
+
+    let end = md[start + 1..]
+        .match_indices("\n#")
+        .map(|(i, _)| start + 1 + i)
+        .find(|&i| md[i + 1..].starts_with("## ") || md[i + 1..].starts_with("### "))
+        .unwrap_or(md.len());
+
+    Some(md[start..end].trim())
+}
+
+#[test]
+fn test_hook_help() {
+    let md = "# Hooks
+## Hook manuals
+### `@foo` and `@foo-all`
+foo manual
+#### Examples
+`@foo bar`
+### `@bar`
+bar manual
+## Other
+";
+
+    assert_eq!(
+        Some("### `@foo` and `@foo-all`\nfoo manual\n#### Examples\n`@foo bar`"),
+        manual(md, "@foo-print"),
+    );
+    assert_eq!(
+        Some("### `@foo` and `@foo-all`\nfoo manual\n#### Examples\n`@foo bar`"),
+        manual(md, "@foo-all"),
+    );
+    assert_eq!(Some("### `@bar`\nbar manual"), manual(md, "@bar"));
+    assert_eq!(None, manual(md, "@baz"));
+
+    assert!(hook_manual(KEY_QUICKNET).is_some());
+    assert!(hook_manual(KEY_UNCOMMENT_ALL_PRINT).is_some());
+
+    for k in hook_keys() {
+        assert!(!hook_usage(k).unwrap().is_empty(), "empty usage for {k}");
+    }
+
+    assert!(hook_usage("@uncoment").is_err());
+
+    // Examples of all ali-rs hooks can be parsed
+    for spec in hook_specs() {
+        assert!(spec.example.is_some(), "no example for {}", spec.key);
+        assert!(
+            spec.callers.is_some()
+                || spec.key == KEY_IF
+                || spec.key == KEY_UNPROTECT,
+            "{spec:?}"
+        );
+    }
+
+    let specs = hook_specs();
+    let spec = |k: &str| specs.iter().find(|spec| spec.key == k).unwrap();
+
+    assert_eq!(Some(ModeHook::Print), spec(KEY_QUICKNET_PRINT).mode);
+    assert_eq!(Some(true), spec(KEY_QUICKNET).chroot);
+    assert_eq!(
+        Some(vec!["manifest-chroot", "cli"]),
+        spec(KEY_QUICKNET).callers,
+    );
+    assert_eq!(None, spec(KEY_WRAPPER_MNT).chroot);
+    assert_eq!(
+        &["marker", "backup"],
+        spec(KEY_UNCOMMENT).keywords.as_slice(),
+    );
+
+    let completions = |partial: &str| hook_completions(partial);
+    assert_eq!(
+        vec![
+            "@uncomment",
+            "@uncomment-all",
+            "@uncomment-all-print",
+            "@uncomment-print",
+        ],
+        completions("@unc"),
+    );
+    assert_eq!(
+        vec!["@uncomment PORT marker"],
+        completions("@uncomment PORT m"),
+    );
+    assert_eq!(
+        vec!["@download url out limit_rate=1M sha256="],
+        completions("@download url out limit_rate=1M s"),
+    );
+    assert!(completions("@download url out sha256=abc s").is_empty());
+    assert_eq!(
+        vec![
+            "@quicknet eth0 backend=networkd bridge=",
+            "@quicknet eth0 backend=networkd bond=",
+        ],
+        completions("@quicknet eth0 backend=networkd b"),
+    );
+    assert_eq!(
+        vec!["@if boot=uefi @mkinitcpio", "@if boot=uefi @mkinitcpio-print"],
+        completions("@if boot=uefi @mkin"),
+    );
+    assert_eq!(
+        vec![
+            "@mnt /mnt @uncomment PORT marker",
+            "@mnt /mnt @uncomment PORT backup",
+        ],
+        completions("@mnt /mnt @uncomment PORT "),
+    );
+    assert!(completions("foo b").is_empty());
+}
@@ -0,0 +1,220 @@
+use super::*;
+
+/// Keys of ali-rs hooks, as typed counterparts of `KEY_*` constants.
+/// Plugin hooks (`@x-*`) and hooks registered by embedders have no keys
+/// here, see [`hook_keys`] for all registered keys.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum HookKey {
+    WrapperMnt,
+    WrapperNoMnt,
+    If,
+    Unprotect,
+    QuickNet,
+    QuickNetPrint,
+    Mkinitcpio,
+    MkinitcpioPrint,
+    Uncomment,
+    UncommentPrint,
+    UncommentAll,
+    UncommentAllPrint,
+    ReplaceToken,
+    ReplaceTokenPrint,
+    Download,
+    DownloadPrint,
+    Backup,
+    BackupPrint,
+    Rollback,
+    RollbackPrint,
+    WireGuard,
+    WireGuardPrint,
+    Tailscale,
+    TailscalePrint,
+    Pam,
+    PamPrint,
+    Sssd,
+    SssdPrint,
+    AssertFileContains,
+    AssertServiceEnabled,
+    AssertMounted,
+}
+
+impl HookKey {
+    /// All ali-rs hook keys
+    pub const ALL: &'static [HookKey] = &[
+        Self::WrapperMnt,
+        Self::WrapperNoMnt,
+        Self::If,
+        Self::Unprotect,
+        Self::QuickNet,
+        Self::QuickNetPrint,
+        Self::Mkinitcpio,
+        Self::MkinitcpioPrint,
+        Self::Uncomment,
+        Self::UncommentPrint,
+        Self::UncommentAll,
+        Self::UncommentAllPrint,
+        Self::ReplaceToken,
+        Self::ReplaceTokenPrint,
+        Self::Download,
+        Self::DownloadPrint,
+        Self::Backup,
+        Self::BackupPrint,
+        Self::Rollback,
+        Self::RollbackPrint,
+        Self::WireGuard,
+        Self::WireGuardPrint,
+        Self::Tailscale,
+        Self::TailscalePrint,
+        Self::Pam,
+        Self::PamPrint,
+        Self::Sssd,
+        Self::SssdPrint,
+        Self::AssertFileContains,
+        Self::AssertServiceEnabled,
+        Self::AssertMounted,
+    ];
+
+    /// Hook key string, e.g. `@uncomment-print`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::WrapperMnt => KEY_WRAPPER_MNT,
+            Self::WrapperNoMnt => KEY_WRAPPER_NO_MNT,
+            Self::If => KEY_IF,
+            Self::Unprotect => KEY_UNPROTECT,
+            Self::QuickNet => KEY_QUICKNET,
+            Self::QuickNetPrint => KEY_QUICKNET_PRINT,
+            Self::Mkinitcpio => KEY_MKINITCPIO,
+            Self::MkinitcpioPrint => KEY_MKINITCPIO_PRINT,
+            Self::Uncomment => KEY_UNCOMMENT,
+            Self::UncommentPrint => KEY_UNCOMMENT_PRINT,
+            Self::UncommentAll => KEY_UNCOMMENT_ALL,
+            Self::UncommentAllPrint => KEY_UNCOMMENT_ALL_PRINT,
+            Self::ReplaceToken => KEY_REPLACE_TOKEN,
+            Self::ReplaceTokenPrint => KEY_REPLACE_TOKEN_PRINT,
+            Self::Download => KEY_DOWNLOAD,
+            Self::DownloadPrint => KEY_DOWNLOAD_PRINT,
+            Self::Backup => KEY_BACKUP,
+            Self::BackupPrint => KEY_BACKUP_PRINT,
+            Self::Rollback => KEY_ROLLBACK,
+            Self::RollbackPrint => KEY_ROLLBACK_PRINT,
+            Self::WireGuard => KEY_WIREGUARD,
+            Self::WireGuardPrint => KEY_WIREGUARD_PRINT,
+            Self::Tailscale => KEY_TAILSCALE,
+            Self::TailscalePrint => KEY_TAILSCALE_PRINT,
+            Self::Pam => KEY_PAM,
+            Self::PamPrint => KEY_PAM_PRINT,
+            Self::Sssd => KEY_SSSD,
+            Self::SssdPrint => KEY_SSSD_PRINT,
+            Self::AssertFileContains => KEY_ASSERT_FILE_CONTAINS,
+            Self::AssertServiceEnabled => KEY_ASSERT_SERVICE_ENABLED,
+            Self::AssertMounted => KEY_ASSERT_MOUNTED,
+        }
+    }
+
+    /// Normal (non-print) variant of the key,
+    /// e.g. `@uncomment` for `@uncomment-print`
+    pub fn base(&self) -> HookKey {
+        match self {
+            Self::QuickNetPrint => Self::QuickNet,
+            Self::MkinitcpioPrint => Self::Mkinitcpio,
+            Self::UncommentPrint => Self::Uncomment,
+            Self::UncommentAllPrint => Self::UncommentAll,
+            Self::ReplaceTokenPrint => Self::ReplaceToken,
+            Self::DownloadPrint => Self::Download,
+            Self::BackupPrint => Self::Backup,
+            Self::RollbackPrint => Self::Rollback,
+            Self::WireGuardPrint => Self::WireGuard,
+            Self::TailscalePrint => Self::Tailscale,
+            Self::PamPrint => Self::Pam,
+            Self::SssdPrint => Self::Sssd,
+            key => *key,
+        }
+    }
+
+    /// Print variant of the key, e.g. `@uncomment-print` for `@uncomment`.
+    /// Wrapper hooks (e.g. `@if`) have no print variants, and take
+    /// the mode of the hooks they wrap. Assertion hooks have none
+    /// either, since they are always read-only.
+    pub fn print(&self) -> Option<HookKey> {
+        match self.base() {
+            Self::WrapperMnt
+            | Self::WrapperNoMnt
+            | Self::If
+            | Self::Unprotect
+            | Self::AssertFileContains
+            | Self::AssertServiceEnabled
+            | Self::AssertMounted => None,
+            Self::QuickNet => Some(Self::QuickNetPrint),
+            Self::Mkinitcpio => Some(Self::MkinitcpioPrint),
+            Self::Uncomment => Some(Self::UncommentPrint),
+            Self::UncommentAll => Some(Self::UncommentAllPrint),
+            Self::ReplaceToken => Some(Self::ReplaceTokenPrint),
+            Self::Download => Some(Self::DownloadPrint),
+            Self::Backup => Some(Self::BackupPrint),
+            Self::Rollback => Some(Self::RollbackPrint),
+            Self::WireGuard => Some(Self::WireGuardPrint),
+            Self::Tailscale => Some(Self::TailscalePrint),
+            Self::Pam => Some(Self::PamPrint),
+            Self::Sssd => Some(Self::SssdPrint),
+            key => unreachable!("{key} is not a base key"),
+        }
+    }
+
+    /// Mode of hooks with this key.
+    /// Wrapper hooks are reported as normal, and assertion hooks as print.
+    pub fn mode(&self) -> ModeHook {
+        match self {
+            Self::AssertFileContains
+            | Self::AssertServiceEnabled
+            | Self::AssertMounted => ModeHook::Print,
+            key if key.base() == *key => ModeHook::Normal,
+            _ => ModeHook::Print,
+        }
+    }
+}
+
+impl std::str::FromStr for HookKey {
+    type Err = AliError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .iter()
+            .find(|key| key.as_str() == s)
+            .copied()
+            .ok_or_else(|| {
+                AliError::BadHookCmd(format!("unknown hook key {s}"))
+            })
+    }
+}
+
+impl std::fmt::Display for HookKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+#[test]
+fn test_hook_key() {
+    for key in HookKey::ALL {
+        assert_eq!(*key, key.to_string().parse::<HookKey>().unwrap());
+
+        let base = key.base();
+        assert_eq!(base, base.base());
+        if let Some(print) = key.print() {
+            assert_eq!(ModeHook::Print, print.mode());
+            assert_eq!(format!("{base}-print"), print.as_str());
+            assert_eq!(base, print.base());
+        }
+    }
+
+    assert_eq!(
+        Some(HookKey::UncommentAllPrint),
+        "@uncomment-all".parse::<HookKey>().unwrap().print()
+    );
+    assert_eq!(ModeHook::Normal, HookKey::If.mode());
+    assert!(HookKey::If.print().is_none());
+    assert_eq!(ModeHook::Print, HookKey::AssertMounted.mode());
+    assert!(HookKey::AssertMounted.print().is_none());
+    assert!("@x-foo".parse::<HookKey>().is_err());
+    assert!("uncomment".parse::<HookKey>().is_err());
+}
@@ -1,9 +1,20 @@
+use std::sync::LazyLock;
+
 use serde::{
     Deserialize,
     Serialize,
 };
 
+use super::args::{
+    self,
+    Arg,
+};
 use super::constants::mkinitcpio::*;
+use super::utils::{
+    file_backup,
+    output,
+    protected,
+};
 use super::{
     wrap_bad_hook_cmd,
     ActionHook,
@@ -15,15 +26,51 @@ use super::{
     KEY_MKINITCPIO_PRINT,
 };
 use crate::errors::AliError;
+use crate::utils::aliases::{
+    self,
+    Kind,
+};
+use crate::utils::fs::path_under;
+use crate::utils::shellconf::{
+    self,
+    ShellConf,
+    Value,
+};
+use crate::utils::{
+    json,
+    shell,
+};
+
+const ARGS: &[Arg] = &[
+    Arg::one_of(&[("boot_hook", "BOOT_HOOK_PRESET"), ("hooks", "HOOKS")]),
+    Arg::key("modules", "MODULES"),
+    Arg::key("binaries", "BINARIES"),
+    Arg::key("files", "FILES"),
+    Arg::key("presets", "KERNEL[,KERNEL..]"),
+    Arg::flag("backup"),
+];
+
+static USAGE: LazyLock<String> = LazyLock::new(|| args::usage(ARGS));
 
-const USAGE: &str =
-    "[boot_hook=<BOOT_HOOK_PRESET>] [hooks=<HOOKS>] [binaries=BINARIES]";
+/// Example arguments, for `ali-rs hooks schema`
+pub(super) const EXAMPLE: &str = "boot_hook=lvm-on-luks binaries=btrfs";
+
+/// Keyword arguments, for shell completion
+pub(super) const KEYWORDS: &[&str] = &[
+    "boot_hook=",
+    "hooks=",
+    "modules=",
+    "binaries=",
+    "files=",
+    "presets=",
+    "backup",
+];
 
 pub(super) fn parse(k: &str, cmd: &str) -> Result<Box<dyn Hook>, ParseError> {
     match k {
         KEY_MKINITCPIO | KEY_MKINITCPIO_PRINT => {
             match HookMkinitcpio::try_from(cmd) {
-                Err(err) => Err(wrap_bad_hook_cmd(err, USAGE)),
+                Err(err) => Err(wrap_bad_hook_cmd(err, &USAGE)),
                 Ok(hook) => Ok(Box::new(hook)),
             }
         }
@@ -35,13 +82,18 @@ pub(super) fn parse(k: &str, cmd: &str) -> Result<Box<dyn Hook>, ParseError> {
 #[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 struct Mkinitcpio {
     boot_hook: Option<BootHooksRoot>,
+    modules: Option<Vec<String>>,
     binaries: Option<Vec<String>>,
+    files: Option<Vec<String>>,
     hooks: Option<Vec<String>>,
+    /// Kernels to write preset files for, e.g. `linux-lts`
+    presets: Option<Vec<String>>,
 }
 
 struct HookMkinitcpio {
     conf: Mkinitcpio,
     mode_hook: ModeHook,
+    backup: bool,
 }
 
 impl Hook for HookMkinitcpio {
@@ -50,7 +102,7 @@ impl Hook for HookMkinitcpio {
     }
 
     fn usage(&self) -> &'static str {
-        USAGE
+        &USAGE
     }
 
     fn mode(&self) -> ModeHook {
@@ -78,6 +130,7 @@ impl Hook for HookMkinitcpio {
             &self.hook_key(),
             &self.mode_hook,
             self.conf.clone(),
+            self.backup,
             caller,
             root_location,
         )
@@ -105,53 +158,27 @@ impl TryFrom<&str> for HookMkinitcpio {
             )));
         }
 
-        let args = &parts[1..];
-        let keys_vals = args
-            .iter()
-            .filter_map(|arg| arg.split_once('='))
-            .collect::<Vec<_>>();
-
-        let mut mkinitcpio = Mkinitcpio::default();
-        let mut dups = std::collections::HashSet::new();
-
-        for (k, v) in keys_vals {
-            let duplicate_key = !dups.insert(k);
-            if duplicate_key {
-                return Err(AliError::AliRsBug(format!(
-                    "{hook_key}: duplicate key {k}"
-                )));
-            }
-
-            match k {
-                "boot_hook" => {
-                    let boot_hook = decide_boot_hooks(&hook_key, v)?;
-                    mkinitcpio.boot_hook = Some(boot_hook);
-
-                    continue;
-                }
-                "binaries" => {
-                    let binaries = split_whitespace_to_strings(v);
-                    mkinitcpio.binaries = Some(binaries);
-
-                    continue;
-                }
-                "hooks" => {
-                    let hooks = split_whitespace_to_strings(v);
-                    mkinitcpio.hooks = Some(hooks);
-                }
-                _ => continue,
-            }
-        }
-
-        if mkinitcpio.boot_hook.is_some() && mkinitcpio.hooks.is_some() {
-            return Err(AliError::BadHookCmd(format!(
-                "{hook_key}: boot_hook and hooks are mutually exclusive, but found both"
-            )));
-        }
+        let args = args::parse(&hook_key, &parts[1..], ARGS)?;
+
+        let mkinitcpio = Mkinitcpio {
+            boot_hook: args
+                .get("boot_hook")
+                .map(|v| decide_boot_hooks(&hook_key, v))
+                .transpose()?,
+            modules: args.get("modules").map(split_whitespace_to_strings),
+            binaries: args.get("binaries").map(split_whitespace_to_strings),
+            files: args.get("files").map(split_whitespace_to_strings),
+            hooks: args.get("hooks").map(split_whitespace_to_strings),
+            presets: args
+                .get("presets")
+                .map(|v| parse_kernels(&hook_key, v))
+                .transpose()?,
+        };
 
         Ok(HookMkinitcpio {
             conf: mkinitcpio,
             mode_hook,
+            backup: args.flag("backup"),
         })
     }
 }
@@ -160,6 +187,7 @@ fn apply_mkinitcpio(
     hook_key: &str,
     mode_hook: &ModeHook,
     mut m: Mkinitcpio,
+    backup: bool,
     _caller: &Caller,
     root_location: &str,
 ) -> Result<ActionHook, AliError> {
@@ -170,33 +198,77 @@ fn apply_mkinitcpio(
         m.hooks = Some(hooks);
     }
 
-    let (mut hooks_mkinitcpio, mut binaries_mkinitcpio) = (None, None);
-
-    if let Some(binaries) = &m.binaries {
-        binaries_mkinitcpio =
-            Some(fmt_shell_array("BINARIES", binaries.clone()));
-    }
-    if let Some(hooks) = &m.hooks {
-        hooks_mkinitcpio = Some(fmt_shell_array("HOOKS", hooks.clone()));
-    }
+    // In order of appearance in default mkinitcpio.conf
+    let arrays = [
+        ("MODULES", &m.modules),
+        ("BINARIES", &m.binaries),
+        ("FILES", &m.files),
+        ("HOOKS", &m.hooks),
+    ];
+
+    let presets: Vec<(String, String)> = m
+        .presets
+        .iter()
+        .flatten()
+        .map(|kernel| {
+            (
+                FILENAME_PRESET_TPL.replace(TOKEN_KERNEL, kernel),
+                PRESET_TPL.replace(TOKEN_KERNEL, kernel),
+            )
+        })
+        .collect();
 
-    let s = serde_json::to_string(&m).unwrap();
+    let s = json::to_string(&m);
     if matches!(mode_hook, ModeHook::Print) {
-        if let Some(s) = binaries_mkinitcpio {
-            println!("{s}");
+        for (arr_name, arr_elems) in arrays {
+            if let Some(arr_elems) = arr_elems {
+                let value = Value::Array(arr_elems.clone());
+                output::println(&shellconf::assignment(arr_name, &value));
+            }
         }
-        if let Some(s) = hooks_mkinitcpio {
-            println!("{s}");
+        for (filename, preset) in presets {
+            output::println(&format!("# {filename}"));
+            output::print(&preset);
         }
 
         return Ok(ActionHook::Mkinitcpio(s));
     }
 
-    let _mkinitcpio_conf = format!("/{root_location}/mkinitcpio.conf");
+    let filename = path_under(root_location, FILENAME_MKINITCPIO_CONF)?;
+    let conf = std::fs::read_to_string(&filename).map_err(|err| {
+        AliError::FileError(err, format!("{hook_key}: reading {filename}"))
+    })?;
 
-    Err(AliError::NotImplemented(
-        format!("{hook_key}: write files",),
-    ))
+    let mut conf = ShellConf::parse(&conf);
+
+    for (arr_name, arr_elems) in arrays {
+        if let Some(arr_elems) = arr_elems {
+            conf.set_array(arr_name, arr_elems);
+        }
+    }
+
+    protected::check(hook_key, root_location, &filename)?;
+    if file_backup::enabled(backup) {
+        file_backup::save(hook_key, root_location, &filename)?;
+    }
+
+    conf.write_under(root_location, FILENAME_MKINITCPIO_CONF)?;
+
+    for (filename, preset) in presets {
+        if file_backup::enabled(backup) {
+            let target = path_under(root_location, &filename)?;
+            file_backup::save(hook_key, root_location, &target)?;
+        }
+
+        protected::write_under(hook_key, root_location, &filename, &preset)?;
+    }
+
+    match root_location {
+        "/" => shell::sh_c("mkinitcpio -P")?,
+        _ => shell::arch_chroot(root_location, "mkinitcpio -P")?,
+    }
+
+    Ok(ActionHook::Mkinitcpio(s))
 }
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -229,25 +301,44 @@ fn decide_boot_hooks(
     hook_key: &str,
     v: &str,
 ) -> Result<BootHooksRoot, AliError> {
-    if ALIASES_ROOT_LVM.contains(&v) {
-        return Ok(BootHooksRoot::Lvm);
-    }
-
-    if ALIASES_ROOT_LUKS.contains(&v) {
-        return Ok(BootHooksRoot::Luks);
-    }
-
-    if ALIASES_ROOT_LVM_ON_LUKS.contains(&v) {
-        return Ok(BootHooksRoot::LvmOnLuks);
-    }
-
-    if ALIASES_ROOT_LUKS_ON_LVM.contains(&v) {
-        return Ok(BootHooksRoot::LuksOnLvm);
+    match aliases::resolve(Kind::BootHook, v) {
+        Some("lvm") => Ok(BootHooksRoot::Lvm),
+        Some("luks") => Ok(BootHooksRoot::Luks),
+        Some("lvm-on-luks") => Ok(BootHooksRoot::LvmOnLuks),
+        Some("luks-on-lvm") => Ok(BootHooksRoot::LuksOnLvm),
+        Some(preset) => {
+            Err(AliError::AliRsBug(format!(
+                "{hook_key}: unexpected boot_hook preset {preset}"
+            )))
+        }
+        None => {
+            Err(AliError::BadHookCmd(format!(
+                "{hook_key}: no such boot_hook preset: {v}{}",
+                aliases::hint(Kind::BootHook, v),
+            )))
+        }
     }
+}
 
-    Err(AliError::BadHookCmd(format!(
-        "{hook_key}: no such boot_hook preset: {v}"
-    )))
+/// Parses comma-separated kernel package names, e.g. `linux-lts,linux-zen`
+fn parse_kernels(hook_key: &str, v: &str) -> Result<Vec<String>, AliError> {
+    v.split(',')
+        .map(|kernel| {
+            let valid = !kernel.is_empty()
+                && kernel
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+
+            match valid {
+                true => Ok(kernel.to_string()),
+                false => {
+                    Err(AliError::BadHookCmd(format!(
+                        "{hook_key}: bad kernel name '{kernel}' in presets"
+                    )))
+                }
+            }
+        })
+        .collect()
 }
 
 fn split_whitespace_to_strings(s: &str) -> Vec<String> {
@@ -256,50 +347,102 @@ fn split_whitespace_to_strings(s: &str) -> Vec<String> {
         .collect::<Vec<_>>()
 }
 
-fn fmt_shell_array(arr_name: &str, arr_elems: Vec<String>) -> String {
-    let s = arr_elems.join(" ");
+#[test]
+fn test_parse_mkinitcpio() {
+    let should_pass = vec![
+        "@mkinitcpio boot_hook=lvm-on-luks",
+        "@mkinitcpio boot_hook=lvm-on-luks binaries=btrfs",
+        "@mkinitcpio-print hooks='base udev block filesystems'",
+        "@mkinitcpio modules='nvme ext4' files=/etc/crypttab.initramfs",
+        "@mkinitcpio boot_hook=lvm presets=linux-lts,linux-zen",
+        "@mkinitcpio boot_hook=lvm backup",
+    ];
+
+    let should_err = vec![
+        "@mkinitcpio",
+        "@mkinitcpio boot_hoook=lvm-on-luks",
+        "@mkinitcpio boot_hook=lvm-on-luks hooks=base",
+        "@mkinitcpio binaries=btrfs binaries=xfs",
+        "@mkinitcpio boot_hook=lvm-on-luks btrfs",
+        "@mkinitcpio presets=linux-lts,",
+        "@mkinitcpio presets='linux lts'",
+        "@mkinitcpio presets=../linux",
+    ];
+
+    for s in should_pass {
+        if let Err(err) = HookMkinitcpio::try_from(s) {
+            panic!("unexpected error from {s}: {err:?}");
+        }
+    }
 
-    format!("{arr_name}=({s})")
-}
+    for s in should_err {
+        assert!(
+            HookMkinitcpio::try_from(s).is_err(),
+            "unexpected ok result from {s}",
+        );
+    }
 
-const ALIASES_ROOT_LVM: [&str; 7] = [
-    "root-on-lvm",
-    "root_on_lvm",
-    "root-lvm",
-    "root_lvm",
-    "lvm-root",
-    "lvm_root",
-    "lvm",
-];
+    let err = HookMkinitcpio::try_from("@mkinitcpio hoooks='base udev'")
+        .err()
+        .unwrap();
 
-const ALIASES_ROOT_LUKS: [&str; 7] = [
-    "root-on-luks",
-    "root_on_luks",
-    "root-luks",
-    "root_luks",
-    "luks-root",
-    "luks_root",
-    "luks",
-];
+    assert!(matches!(
+        err,
+        AliError::BadHookCmd(ref msg) if msg == "@mkinitcpio: unknown key hoooks (did you mean hooks?), expecting one of: boot_hook, hooks, modules, binaries, files, presets"
+    ));
 
-const ALIASES_ROOT_LVM_ON_LUKS: [&str; 8] = [
-    "root-on-lvm-on-luks",
-    "root_on_lvm_on_luks",
-    "lvm-on-luks-root",
-    "lvm_on_luks_root",
-    "root-lvm-on-luks",
-    "root_lvm_on_luks",
-    "lvm-on-luks",
-    "lvm_on_luks",
-];
+    let err = HookMkinitcpio::try_from("@mkinitcpio boot_hook=lvm-on-lusk")
+        .err()
+        .unwrap();
 
-const ALIASES_ROOT_LUKS_ON_LVM: [&str; 8] = [
-    "root-on-luks-on-lvm",
-    "root_on_luks_on_lvm",
-    "luks-on-lvm-root",
-    "luks_on_lvm_root",
-    "root-luks-on-lvm",
-    "root_luks_on_lvm",
-    "luks-on-lvm",
-    "luks_on_lvm",
-];
+    assert!(matches!(
+        err,
+        AliError::BadHookCmd(ref msg) if msg.ends_with("lvm-on-lusk (did you mean lvm-on-luks?)")
+    ));
+}
+
+#[test]
+fn test_apply_mkinitcpio() {
+    use std::sync::Arc;
+
+    use crate::utils::mock::MockRunner;
+
+    let root = std::env::temp_dir()
+        .join(format!("ali-rs-test-mkinitcpio-{}", std::process::id()));
+    let root_location = root.to_str().unwrap();
+    let conf = format!("{root_location}{FILENAME_MKINITCPIO_CONF}");
+    std::fs::create_dir_all(root.join("etc")).unwrap();
+    std::fs::write(&conf, "MODULES=()\nHOOKS=(base udev)\n").unwrap();
+
+    let mock = Arc::new(MockRunner::new());
+    let _guard = shell::set_runner(mock.clone());
+
+    super::apply_hook(
+        "@mkinitcpio boot_hook=lvm binaries=btrfs",
+        Caller::ManifestChroot,
+        root_location,
+    )
+    .unwrap();
+
+    let conf = std::fs::read_to_string(&conf).unwrap();
+    assert!(conf.contains("BINARIES=(btrfs)"), "{conf}");
+    assert!(conf.contains(" lvm2 "), "{conf}");
+    assert_eq!(
+        Some(&format!("sh -c arch-chroot {root_location} mkinitcpio -P")),
+        mock.calls().last(),
+    );
+
+    // Flag backup saves mkinitcpio.conf before rewriting it
+    super::apply_hook(
+        "@mkinitcpio hooks='base udev' backup",
+        Caller::ManifestChroot,
+        root_location,
+    )
+    .unwrap();
+
+    let backups = root.join("var/lib/ali-rs/backups/etc");
+    let backups = std::fs::read_dir(backups).unwrap().collect::<Vec<_>>();
+    assert_eq!(1, backups.len());
+
+    std::fs::remove_dir_all(root).unwrap();
+}
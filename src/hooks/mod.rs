@@ -1,21 +1,62 @@
+mod args;
+mod assertion;
+mod backup;
+mod conditional;
 mod constants;
 mod download;
+mod help;
+mod key;
 mod mkinitcpio;
+mod pam;
+mod plugin;
 mod quicknet;
+mod registry;
 mod replace_token;
+mod rollback;
+mod sssd;
+mod tailscale;
 mod uncomment;
+mod unprotect;
 mod utils;
+#[cfg(feature = "wasm")]
+mod wasm;
+mod wireguard;
 mod wrappers;
 
 pub use self::constants::hook_keys::*;
+pub use self::help::{
+    hook_completions,
+    hook_keys,
+    hook_keywords,
+    hook_manual,
+    hook_specs,
+    hook_usage,
+};
+pub use self::key::HookKey;
+pub use self::registry::HookConstructor;
 
 use colored::Colorize;
 use serde::{
     Deserialize,
     Serialize,
 };
+use serde_json::json;
+
+use std::sync::OnceLock;
 
 use crate::errors::AliError;
+use crate::utils::snapshot::Snapshot;
+use self::utils::output;
+use crate::utils::{
+    progress,
+    readonly,
+    report_sink,
+    suggest,
+};
+
+/// Directories snapshotted before and after each hook,
+/// see [`set_snapshot_dirs`]
+static SNAPSHOT_DIRS: OnceLock<Vec<String>> = OnceLock::new();
 
 /// All hook actions stores JSON string representation of the hook.
 /// The reason being we want to hide hook implementation from outside code.
@@ -28,6 +69,37 @@ pub enum ActionHook {
     Uncomment(String),
     Mkinitcpio(String),
     Download(String),
+    Backup(String),
+    Rollback(String),
+    WireGuard(String),
+    Tailscale(String),
+    Pam(String),
+    Sssd(String),
+    Plugin(String),
+    If(String),
+    Assert(String),
+}
+
+impl ActionHook {
+    /// Serialized report of any kind of action
+    fn inner_mut(&mut self) -> &mut String {
+        match self {
+            Self::QuickNet(s)
+            | Self::ReplaceToken(s)
+            | Self::Uncomment(s)
+            | Self::Mkinitcpio(s)
+            | Self::Download(s)
+            | Self::Backup(s)
+            | Self::Rollback(s)
+            | Self::WireGuard(s)
+            | Self::Tailscale(s)
+            | Self::Pam(s)
+            | Self::Sssd(s)
+            | Self::Plugin(s)
+            | Self::If(s)
+            | Self::Assert(s) => s,
+        }
+    }
 }
 
 /// Entrypoint for hooks.
@@ -39,36 +111,108 @@ pub enum Caller {
     Cli,
 }
 
+impl Caller {
+    /// All callers, in order of manifest application
+    pub const ALL: [Caller; 3] =
+        [Caller::ManifestChroot, Caller::ManifestPostInstall, Caller::Cli];
+
+    /// Caller name, e.g. `manifest-chroot`
+    pub fn name(&self) -> &'static str {
+        match self {
+            Caller::ManifestChroot => "manifest-chroot",
+            Caller::ManifestPostInstall => "manifest-postinstall",
+            Caller::Cli => "cli",
+        }
+    }
+}
+
 /// ModeHook represents whether this hook command is print-only
-#[derive(Clone, PartialEq)]
-enum ModeHook {
+#[derive(Clone, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ModeHook {
     /// May write changes to disk
     Normal,
     /// Print-only, i.e. idempotent
     Print,
 }
 
+/// Error from parsing hook commands, with usage help for the hook
 #[derive(Debug)]
-struct ParseError {
+pub struct ParseError {
     error: AliError,
     help_msg: String,
 }
 
+/// Machine-readable description of a hook, exported with
+/// `ali-rs hooks schema`. Properties that depend on inner hooks
+/// (e.g. of `@mnt`), or on hooks that cannot be parsed, are `None`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HookSpec {
+    /// Full hook key, e.g. `@quicknet-print`
+    pub key: String,
+    /// Argument grammar, without hook key
+    pub usage: String,
+    /// Keyword arguments, with `=` suffix if taking values
+    pub keywords: Vec<&'static str>,
+    /// Example hook command, if registered
+    pub example: Option<String>,
+    pub mode: Option<ModeHook>,
+    /// Names of preferred callers, see [`Caller::name`]
+    pub callers: Option<Vec<&'static str>>,
+    /// Whether the hook should run in chroot to the new system
+    pub chroot: Option<bool>,
+    /// Whether the hook aborts if mountpoint is `/`
+    pub abort_if_no_mount: Option<bool>,
+}
+
+impl HookSpec {
+    /// Spec from properties of parsed `hook`
+    pub fn new<H: Hook + ?Sized>(hook: &H) -> Self {
+        let callers = Caller::ALL
+            .iter()
+            .filter(|caller| hook.prefer_caller(caller))
+            .map(Caller::name)
+            .collect();
+
+        Self {
+            key: hook.hook_key(),
+            usage: hook.usage().to_string(),
+            keywords: Vec::new(),
+            example: None,
+            mode: Some(hook.mode()),
+            callers: Some(callers),
+            chroot: Some(hook.should_chroot()),
+            abort_if_no_mount: Some(hook.abort_if_no_mount()),
+        }
+    }
+}
+
+/// Piece of state a hook leaves in the new system
+#[derive(Debug, Clone, PartialEq)]
+pub enum HookState {
+    /// File at `path` (relative to new root) containing each of `contains`
+    File { path: String, contains: Vec<String> },
+
+    /// systemd unit enabled for `target`
+    Enabled { unit: String, target: String },
+}
+
 /// Hook represents a parsed, ready to use hook.
 ///
 /// Other than [`run_hook`](Self::run_hook), which
 /// actually executes the hook, this trait also defines
 /// many methods for validating user calls to hooks.
-trait Hook {
+///
+/// Downstream crates implement this trait for their own hooks,
+/// and register them with [`register_hook`].
+pub trait Hook {
     /// (Default) Prints yellow warning text to output
     fn eprintln_warn(&self, msg: &str) {
-        eprintln!(
-            "### {} ###",
-            format!("{} WARN: {msg}", self.base_key()).yellow()
-        );
+        log::warn!("{}: {msg}", self.base_key());
     }
 
     /// (Default) Wraps error in hook with some string prefix
+    #[allow(unused)]
     fn hook_error(&self, msg: &str) -> AliError {
         AliError::HookError(format!("{}: {msg}", self.hook_key()))
     }
@@ -85,6 +229,7 @@ trait Hook {
     fn base_key(&self) -> &'static str;
 
     /// Returns usage string for Self.help
+    #[allow(unused)]
     fn usage(&self) -> &'static str;
 
     /// Returns ModeHook parsed
@@ -101,6 +246,18 @@ trait Hook {
     /// (i.e. root_location or mountpoint == /)
     fn abort_if_no_mount(&self) -> bool;
 
+    /// (Default) Structured description of the hook.
+    /// Hooks delegating to inner hooks should override this
+    fn spec(&self) -> HookSpec {
+        HookSpec::new(self)
+    }
+
+    /// (Default) State this hook leaves in the new system, checked by
+    /// `ali-rs verify`. Hooks with no predictable state return nothing
+    fn state(&self) -> Vec<HookState> {
+        Vec::new()
+    }
+
     /// Executes hook once parsed
     fn run_hook(
         &self,
@@ -115,7 +272,81 @@ pub fn apply_hook(
     root_location: &str,
 ) -> Result<ActionHook, AliError> {
     let h = parse_validate_caller(cmd, &caller, root_location)?;
-    h.run_hook(&caller, root_location)
+
+    // Only report hook key, since hook arguments may contain secrets
+    let _step = progress::step(format!("hook {}", h.hook_key()));
+    let snapshot = SNAPSHOT_DIRS
+        .get()
+        .map(|dirs| (dirs, Snapshot::take(root_location, dirs)));
+
+    // Print hooks may not touch disks, whatever they do on their own
+    let read_only = matches!(h.mode(), ModeHook::Print)
+        .then(|| readonly::enter(&h.hook_key()));
+
+    output::begin(&h.hook_key());
+    let result = h.run_hook(&caller, root_location);
+    drop(read_only);
+
+    if let Some(truncated) = output::end() {
+        log_truncated(&h.hook_key(), "output", &truncated);
+    }
+
+    // Files are reported even if the hook failed
+    let diff = snapshot.map(|(dirs, before)| {
+        let diff = before.diff(&Snapshot::take(root_location, dirs));
+        log::info!(
+            "hook {} changed {} file(s) under {}{}",
+            h.hook_key(),
+            diff.len(),
+            dirs.join(", "),
+            match diff.is_empty() {
+                true => String::new(),
+                false => format!(":\n{diff}"),
+            },
+        );
+
+        diff
+    });
+
+    // Reports of huge actions are truncated too
+    let mut action = result?;
+    if let Some(truncated) = output::truncate(&h.hook_key(), action.inner_mut())
+    {
+        log_truncated(&h.hook_key(), "report", &truncated);
+    }
+
+    let mut event = json!({
+        "stage": progress::current_stage(),
+        "hook": h.hook_key(),
+        "action": action,
+    });
+    if let Some(diff) = diff {
+        event["files"] = json!(diff);
+    }
+    report_sink::emit("hook", event);
+
+    Ok(action)
+}
+
+fn log_truncated(hook_key: &str, what: &str, truncated: &output::Truncated) {
+    let stored = match truncated.path {
+        Some(ref path) => format!(", full {what} in {}", path.display()),
+        None => String::new(),
+    };
+
+    log::warn!(
+        "hook {hook_key}: truncated {what} of {} bytes{stored}",
+        truncated.size,
+    );
+}
+
+/// Registers `constructor` for hook `keys` (e.g. `@foo` and `@foo-print`),
+/// replacing built-in hooks with the same keys
+pub fn register_hook(keys: &[&'static str], constructor: HookConstructor) {
+    registry::global()
+        .write()
+        .expect("hook registry lock poisoned")
+        .register(keys, constructor);
 }
 
 /// Validates if hook_cmd is valid for its caller and mountpoint
@@ -129,6 +360,83 @@ pub fn validate_hook(
     Ok(())
 }
 
+/// Snapshots files under `dirs` of the hook root before and after
+/// each hook, and reports files created, modified, touched, or deleted
+/// by each hook. Only the first call has effect.
+pub fn set_snapshot_dirs(dirs: Vec<String>) {
+    if SNAPSHOT_DIRS.set(dirs).is_err() {
+        log::warn!("hook snapshot directories already set");
+    }
+}
+
+/// Makes file-editing hooks back up files before writing,
+/// as if flag `backup` was given to all of them
+pub fn set_backup_files(backup: bool) {
+    utils::file_backup::set_always(backup);
+}
+
+/// Restores latest backups of files edited by hooks under `root_location`,
+/// and returns restored files with their backups
+pub fn undo_backups(
+    root_location: &str,
+    dry_run: bool,
+) -> Result<Vec<(String, std::path::PathBuf)>, AliError> {
+    utils::file_backup::undo(root_location, dry_run)
+}
+
+/// Protects `paths` from being written by hooks,
+/// in addition to default protected paths like `/etc/shadow`
+pub fn set_protected_paths(paths: Vec<String>) {
+    utils::protected::set_extra(paths);
+}
+
+/// Limits output and reports of each hook to `limit` bytes (0 for
+/// no limit), storing full output compressed in run directory `dir`
+pub fn set_output_limit(limit: usize, dir: &std::path::Path) {
+    utils::output::set_limit(limit, dir);
+}
+
+/// Sets global rate limit (bytes per second) for all hook downloads
+pub fn set_download_limit_rate(bytes_per_sec: u64) {
+    utils::download::set_limit_rate(bytes_per_sec);
+}
+
+/// Sets global SOCKS5 proxy URL for all hook downloads
+pub fn set_download_proxy(url: &str) {
+    utils::download::set_proxy(url);
+}
+
+/// Downloads remote text file (e.g. manifest) from URL or `|`-separated
+/// mirrors, verifying hex-encoded SHA-256 `checksum` if given
+pub fn download_string(
+    url: &str,
+    checksum: Option<&str>,
+) -> Result<String, AliError> {
+    let mut downloader = utils::download::Downloader::new_from_url(url)?;
+    if let Some(sum) = checksum {
+        downloader = downloader.with_sha256(sum);
+    }
+
+    downloader.get_string()
+}
+
+/// Returns whether `s` is a remote URL supported by hook downloads
+pub fn is_remote(s: &str) -> bool {
+    utils::download::Downloader::new_from_url(s).is_ok()
+}
+
+/// State left in the new system by hook command `cmd`.
+/// Print hooks leave no state.
+pub fn hook_state(cmd: &str) -> Result<Vec<HookState>, AliError> {
+    let (key, _) = extract_key_and_parts(cmd)?;
+    let hook = parse_hook(&key, cmd).map_err(|err| err.error)?;
+
+    match hook.mode() {
+        ModeHook::Print => Ok(Vec::new()),
+        ModeHook::Normal => Ok(hook.state()),
+    }
+}
+
 pub fn is_hook(cmd: &str) -> bool {
     cmd.starts_with('@')
 }
@@ -137,7 +445,7 @@ pub fn extract_key_and_parts(
     cmd: &str,
 ) -> Result<(String, Vec<String>), AliError> {
     let parts = cmd.split_whitespace().collect::<Vec<_>>();
-    if parts.first().is_none() {
+    if parts.is_empty() {
         return Err(AliError::AliRsBug("@mnt: got 0 part".to_string()));
     }
 
@@ -160,51 +468,50 @@ pub fn extract_key_and_parts_shlex(
     Ok((key, parts.unwrap()))
 }
 
-fn wrap_bad_hook_cmd(err: AliError, help_msg: &str) -> ParseError {
-    ParseError {
-        error: err,
-        help_msg: help_msg.to_string(),
+impl ParseError {
+    /// Wraps parse error `error` with hook usage `help_msg`,
+    /// which is printed when the hook fails to parse
+    pub fn new(error: AliError, help_msg: &str) -> Self {
+        Self {
+            error,
+            help_msg: help_msg.to_string(),
+        }
     }
 }
 
+fn wrap_bad_hook_cmd(err: AliError, help_msg: &str) -> ParseError {
+    ParseError::new(err, help_msg)
+}
+
 /// (Default) Prints help to output
 fn print_help(hook_key: &str, usage: &str) {
     println!("{}", format!("{}: {}", hook_key, usage).green());
 }
 
 fn parse_hook(k: &str, cmd: &str) -> Result<Box<dyn Hook>, ParseError> {
-    match k {
-        KEY_WRAPPER_MNT | KEY_WRAPPER_NO_MNT => {
-            wrappers::parse(k, cmd) //
-        }
+    let registry = registry::global()
+        .read()
+        .expect("hook registry lock poisoned");
 
-        KEY_QUICKNET | KEY_QUICKNET_PRINT => {
-            quicknet::parse(k, cmd) //
-        }
-
-        KEY_MKINITCPIO | KEY_MKINITCPIO_PRINT => {
-            mkinitcpio::parse(k, cmd) //
-        }
-
-        KEY_REPLACE_TOKEN | KEY_REPLACE_TOKEN_PRINT => {
-            replace_token::parse(k, cmd)
-        }
-
-        KEY_DOWNLOAD | KEY_DOWNLOAD_PRINT => download::parse(k, cmd),
+    match registry.get(k) {
+        Some(constructor) => constructor(k, cmd),
+        None => Err(unknown_key(&registry, k)),
+    }
+}
 
-        KEY_UNCOMMENT
-        | KEY_UNCOMMENT_PRINT
-        | KEY_UNCOMMENT_ALL
-        | KEY_UNCOMMENT_ALL_PRINT => {
-            uncomment::parse(k, cmd) //
-        }
+/// Error for hook key `k` not in `registry`, suggesting close keys
+fn unknown_key(registry: &registry::HookRegistry, k: &str) -> ParseError {
+    let keys = registry.keys();
 
-        key => {
-            Err(ParseError {
-                error: AliError::BadHookCmd(format!("unknown hook key {key}")),
-                help_msg: "Use `--help` to see help".to_string(),
-            })
-        }
+    ParseError {
+        error: AliError::BadHookCmd(format!(
+            "unknown hook key {k}{}",
+            suggest::hint(k, keys.iter().copied()),
+        )),
+        help_msg: format!(
+            "Known hook keys: {}, {KEY_PREFIX_PLUGIN}<NAME>",
+            keys.join(", "),
+        ),
     }
 }
 
@@ -0,0 +1,712 @@
+use std::sync::LazyLock;
+
+use serde_json::json;
+
+use super::args::{
+    self,
+    Arg,
+};
+use super::constants::pam::*;
+use super::utils::protected::write_under;
+use super::utils::{
+    file_backup,
+    output,
+};
+use super::{
+    extract_key_and_parts_shlex,
+    wrap_bad_hook_cmd,
+    ActionHook,
+    Caller,
+    Hook,
+    HookState,
+    ModeHook,
+    ParseError,
+    KEY_PAM,
+    KEY_PAM_PRINT,
+};
+use crate::errors::AliError;
+use crate::linux::pam::{
+    self,
+    PamConf,
+    Stanza,
+};
+use crate::utils::fs::path_under;
+use crate::utils::json;
+use crate::utils::shell;
+
+const ARGS: &[Arg] = &[
+    Arg::key_default("service", "NAME", "system-auth"),
+    Arg::key("faillock", "DENY[:UNLOCK_SECS]"),
+    Arg::flag("deny_root"),
+    Arg::key("u2f", "required|sufficient"),
+    Arg::key("u2f_authfile", "FILE"),
+    Arg::key("pwquality", "MINLEN"),
+    Arg::flag("backup"),
+];
+
+static USAGE: LazyLock<String> = LazyLock::new(|| args::usage(ARGS));
+
+/// Example arguments, for `ali-rs hooks schema`
+pub(super) const EXAMPLE: &str = "faillock=5:600 pwquality=12";
+
+/// Keyword arguments, for shell completion
+pub(super) const KEYWORDS: &[&str] = &[
+    "service=",
+    "faillock=",
+    "deny_root",
+    "u2f=",
+    "u2f_authfile=",
+    "pwquality=",
+    "backup",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+struct Pam {
+    /// Service file in `/etc/pam.d`
+    service: String,
+    faillock: Option<Faillock>,
+    u2f: Option<U2f>,
+    /// Minimum password length
+    pwquality: Option<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Faillock {
+    /// Failed attempts before lock
+    deny: u32,
+    unlock_time: Option<u32>,
+    /// Also locks root
+    deny_root: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct U2f {
+    /// Whether the security key is a second factor (`required`),
+    /// or replaces the password (`sufficient`)
+    required: bool,
+    /// Central key mapping file, instead of `~/.config/Yubico/u2f_keys`
+    authfile: Option<String>,
+}
+
+struct HookPam {
+    pam: Pam,
+    mode_hook: ModeHook,
+    backup: bool,
+}
+
+pub(super) fn parse(k: &str, cmd: &str) -> Result<Box<dyn Hook>, ParseError> {
+    match k {
+        KEY_PAM | KEY_PAM_PRINT => {
+            match HookPam::try_from(cmd) {
+                Err(err) => Err(wrap_bad_hook_cmd(err, &USAGE)),
+                Ok(hook) => Ok(Box::new(hook)),
+            }
+        }
+
+        key => panic!("unknown key {key}"),
+    }
+}
+
+impl Hook for HookPam {
+    fn base_key(&self) -> &'static str {
+        KEY_PAM
+    }
+
+    /// `@pam [service=<NAME="system-auth">] [faillock=<DENY[:UNLOCK_SECS]>] [u2f=<required|sufficient>] [pwquality=<MINLEN>] [backup]`
+    ///
+    /// With flag `backup`, the service file and module configs are backed
+    /// up before they are written, and can be restored with
+    /// `ali-rs hooks undo`.
+    ///
+    /// Examples:
+    ///
+    /// 1. Lock accounts for 10 minutes after 5 failures,
+    ///    and require passwords of at least 12 characters
+    ///
+    /// ```txt
+    /// @pam faillock=5:600 pwquality=12
+    /// ```
+    ///
+    /// 2. Require security keys registered in `/etc/u2f_mappings`
+    ///    as second factor
+    ///
+    /// ```txt
+    /// @pam u2f=required u2f_authfile=/etc/u2f_mappings
+    /// ```
+    fn usage(&self) -> &'static str {
+        &USAGE
+    }
+
+    fn mode(&self) -> ModeHook {
+        self.mode_hook.clone()
+    }
+
+    fn should_chroot(&self) -> bool {
+        true
+    }
+
+    fn prefer_caller(&self, caller: &Caller) -> bool {
+        matches!(caller, Caller::ManifestChroot | Caller::Cli)
+    }
+
+    fn abort_if_no_mount(&self) -> bool {
+        true
+    }
+
+    /// Module options, and modules in service
+    fn state(&self) -> Vec<HookState> {
+        let mut state: Vec<HookState> = self
+            .pam
+            .options()
+            .into_iter()
+            .map(|(path, key, value)| {
+                let option = match value {
+                    Some(value) => format!("{key} = {value}"),
+                    None => key.to_string(),
+                };
+
+                HookState::File {
+                    path: path.to_string(),
+                    contains: vec![option],
+                }
+            })
+            .collect();
+
+        let modules: Vec<String> =
+            self.pam.stanzas().into_iter().map(|s| s.module).collect();
+
+        if !modules.is_empty() {
+            state.push(HookState::File {
+                path: format!("{}/{}", pam::DIR_PAM_D, self.pam.service),
+                contains: modules,
+            });
+        }
+
+        state
+    }
+
+    fn run_hook(
+        &self,
+        _caller: &Caller,
+        root_location: &str,
+    ) -> Result<ActionHook, AliError> {
+        apply_pam(
+            &self.hook_key(),
+            &self.mode_hook,
+            &self.pam,
+            self.backup,
+            root_location,
+        )
+    }
+}
+
+impl TryFrom<&str> for HookPam {
+    type Error = AliError;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        let (hook_key, parts) = extract_key_and_parts_shlex(s)?;
+        let mode_hook = match hook_key.as_str() {
+            KEY_PAM => ModeHook::Normal,
+            KEY_PAM_PRINT => ModeHook::Print,
+            key => panic!("unexpected key {key}"),
+        };
+
+        let bad = |msg: String| AliError::BadHookCmd(format!("{hook_key}: {msg}"));
+        let args = args::parse(&hook_key, &parts[1..], ARGS)?;
+
+        let service = args.required("service")?.to_string();
+        let is_service = |c: char| c.is_ascii_alphanumeric() || "-_.".contains(c);
+        if service.starts_with('.') || !service.chars().all(is_service) {
+            return Err(bad(format!("bad service {service}")));
+        }
+
+        let faillock = match args.get("faillock") {
+            None if args.flag("deny_root") => {
+                return Err(bad("deny_root requires faillock".to_string()));
+            }
+            None => None,
+            Some(faillock) => {
+                let (deny, unlock_time) = match faillock.split_once(':') {
+                    Some((deny, secs)) => (deny, Some(secs)),
+                    None => (faillock, None),
+                };
+
+                let deny = deny
+                    .parse::<u32>()
+                    .ok()
+                    .filter(|deny| *deny > 0)
+                    .ok_or_else(|| bad(format!("bad faillock deny {deny}")))?;
+
+                let unlock_time = unlock_time
+                    .map(|secs| {
+                        secs.parse::<u32>().map_err(|_| {
+                            bad(format!("bad faillock unlock time {secs}"))
+                        })
+                    })
+                    .transpose()?;
+
+                // Root locked forever would leave no way to log in
+                // once all users are locked out
+                let deny_root = args.flag("deny_root");
+                if deny_root && unlock_time == Some(0) {
+                    return Err(bad(
+                        "deny_root with unlock time 0 can lock out root forever"
+                            .to_string(),
+                    ));
+                }
+
+                Some(Faillock {
+                    deny,
+                    unlock_time,
+                    deny_root,
+                })
+            }
+        };
+
+        let u2f = match (args.get("u2f"), args.get("u2f_authfile")) {
+            (None, Some(_)) => {
+                return Err(bad("u2f_authfile requires u2f".to_string()));
+            }
+            (None, None) => None,
+            (Some(control), authfile) => {
+                let required = match control {
+                    "required" => true,
+                    "sufficient" => false,
+                    _ => return Err(bad(format!("bad u2f control {control}"))),
+                };
+
+                if let Some(path) = authfile {
+                    let is_bad = !path.starts_with('/')
+                        || path.contains(char::is_whitespace);
+
+                    if is_bad {
+                        return Err(bad(format!("bad u2f_authfile {path}")));
+                    }
+                }
+
+                Some(U2f {
+                    required,
+                    authfile: authfile.map(String::from),
+                })
+            }
+        };
+
+        let pwquality = match args.get("pwquality") {
+            None => None,
+            Some(minlen) => {
+                match minlen.parse::<u32>() {
+                    Ok(minlen) if minlen >= MIN_MINLEN => Some(minlen),
+                    _ => {
+                        return Err(bad(format!(
+                            "bad pwquality minimum length {minlen}, expecting at least {MIN_MINLEN}"
+                        )));
+                    }
+                }
+            }
+        };
+
+        if faillock.is_none() && u2f.is_none() && pwquality.is_none() {
+            return Err(bad(
+                "expecting at least one of faillock, u2f, or pwquality"
+                    .to_string(),
+            ));
+        }
+
+        Ok(HookPam {
+            pam: Pam {
+                service,
+                faillock,
+                u2f,
+                pwquality,
+            },
+            mode_hook,
+            backup: args.flag("backup"),
+        })
+    }
+}
+
+/// Edits PAM service and module configs in the new system, refusing
+/// changes that would leave users with no way to authenticate
+fn apply_pam(
+    hook_key: &str,
+    mode_hook: &ModeHook,
+    pam: &Pam,
+    backup: bool,
+    root_location: &str,
+) -> Result<ActionHook, AliError> {
+    let s = pam.to_string();
+
+    if matches!(mode_hook, ModeHook::Print) {
+        for stanza in pam.stanzas() {
+            output::println(&stanza.to_string());
+        }
+        for (filename, key, value) in pam.options() {
+            let value = value.map(|v| format!(" = {v}")).unwrap_or_default();
+            output::println(&format!("# {filename}: {key}{value}"));
+        }
+
+        return Ok(ActionHook::Pam(s));
+    }
+
+    let filename = format!("{}/{}", pam::DIR_PAM_D, pam.service);
+    let path = path_under(root_location, &filename)?;
+    let mut conf = PamConf::read(&path)?;
+
+    pam.edit(hook_key, &mut conf, root_location)?;
+    if let Err(err) = conf.check_auth() {
+        return Err(AliError::HookError(format!(
+            "{hook_key}: refusing to write {filename}: {err}"
+        )));
+    }
+
+    let packages = pam.packages();
+    if !packages.is_empty() {
+        let cmd_install =
+            format!("pacman -S --needed --noconfirm {}", packages.join(" "));
+
+        match root_location {
+            "/" => shell::sh_c(&cmd_install)?,
+            _ => shell::arch_chroot(root_location, &cmd_install)?,
+        }
+    }
+
+    for (filename, key, value) in pam.options() {
+        let path = path_under(root_location, filename)?;
+        let options = match std::fs::read_to_string(&path) {
+            Ok(options) => options,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                String::new()
+            }
+            Err(err) => {
+                return Err(AliError::FileError(
+                    err,
+                    format!("{hook_key}: reading file {path}"),
+                ));
+            }
+        };
+
+        let options = pam::set_conf_option(&options, key, value.as_deref());
+        if file_backup::enabled(backup) {
+            file_backup::save(hook_key, root_location, &path)?;
+        }
+
+        write_under(hook_key, root_location, filename, &options)?;
+    }
+
+    if file_backup::enabled(backup) {
+        file_backup::save(hook_key, root_location, &path)?;
+    }
+
+    write_under(hook_key, root_location, &filename, &conf.to_string())?;
+
+    Ok(ActionHook::Pam(s))
+}
+
+impl Pam {
+    /// Edits service `conf` in place
+    fn edit(
+        &self,
+        hook_key: &str,
+        conf: &mut PamConf,
+        root_location: &str,
+    ) -> Result<(), AliError> {
+        let missing = |module: &str| {
+            AliError::HookError(format!(
+                "{hook_key}: service {} has no {module} auth stanza",
+                self.service
+            ))
+        };
+
+        if self.faillock.is_some()
+            && conf.position("auth", "pam_faillock.so").is_none()
+        {
+            return Err(missing("pam_faillock.so"));
+        }
+
+        if let Some(ref u2f) = self.u2f {
+            u2f.check_keys(hook_key, root_location)?;
+
+            let stanza = u2f.stanza();
+            match conf.find_mut("auth", "pam_u2f.so") {
+                Some(existing) => *existing = stanza,
+                None => {
+                    let unix = conf
+                        .position("auth", "pam_unix.so")
+                        .ok_or_else(|| missing("pam_unix.so"))?;
+
+                    // Second factor runs where pam_unix.so continues
+                    // on success, alternative to password runs before it
+                    let k = match u2f.required {
+                        true => conf.after_success("auth", unix),
+                        false => unix,
+                    };
+
+                    conf.insert(k, stanza);
+                }
+            }
+        }
+
+        if self.pwquality.is_some() {
+            let stanza = pwquality_stanza();
+            match conf.find_mut("password", "pam_pwquality.so") {
+                Some(existing) => *existing = stanza,
+                None => {
+                    let unix = conf
+                        .position("password", "pam_unix.so")
+                        .ok_or_else(|| missing("pam_unix.so password"))?;
+
+                    conf.insert(unix, stanza);
+                }
+            }
+
+            // pam_unix.so must use password checked by pam_pwquality.so
+            if let Some(unix) = conf.find_mut("password", "pam_unix.so") {
+                if !unix.args.iter().any(|arg| arg == "use_authtok") {
+                    unix.args.push("use_authtok".to_string());
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Stanzas added to service, for print hook
+    fn stanzas(&self) -> Vec<Stanza> {
+        let mut stanzas = Vec::new();
+        if let Some(ref u2f) = self.u2f {
+            stanzas.push(u2f.stanza());
+        }
+        if self.pwquality.is_some() {
+            stanzas.push(pwquality_stanza());
+        }
+
+        stanzas
+    }
+
+    /// Module options as `(file, key, value)`
+    fn options(&self) -> Vec<(&'static str, &'static str, Option<String>)> {
+        let mut options = Vec::new();
+        if let Some(ref faillock) = self.faillock {
+            let file = FILENAME_FAILLOCK_CONF;
+            options.push((file, "deny", Some(faillock.deny.to_string())));
+
+            if let Some(secs) = faillock.unlock_time {
+                options.push((file, "unlock_time", Some(secs.to_string())));
+            }
+            if faillock.deny_root {
+                options.push((file, "even_deny_root", None));
+            }
+        }
+
+        if let Some(minlen) = self.pwquality {
+            options.push((
+                FILENAME_PWQUALITY_CONF,
+                "minlen",
+                Some(minlen.to_string()),
+            ));
+        }
+
+        options
+    }
+
+    fn packages(&self) -> Vec<&'static str> {
+        let mut packages = Vec::new();
+        if self.u2f.is_some() {
+            packages.push(PACKAGE_U2F);
+        }
+        if self.pwquality.is_some() {
+            packages.push(PACKAGE_PWQUALITY);
+        }
+
+        packages
+    }
+}
+
+impl U2f {
+    fn stanza(&self) -> Stanza {
+        let control = match self.required {
+            true => "required",
+            false => "sufficient",
+        };
+
+        let authfile = self.authfile.as_ref().map(|f| format!("authfile={f}"));
+        let mut args: Vec<&str> = authfile.iter().map(String::as_str).collect();
+        args.push("cue");
+
+        Stanza::new("auth", control, "pam_u2f.so", &args)
+    }
+
+    /// Refuses required security keys if no keys are registered,
+    /// since pam_u2f.so would then fail for every user
+    fn check_keys(
+        &self,
+        hook_key: &str,
+        root_location: &str,
+    ) -> Result<(), AliError> {
+        if !self.required {
+            return Ok(());
+        }
+
+        let Some(ref authfile) = self.authfile else {
+            return Err(AliError::HookError(format!(
+                "{hook_key}: u2f=required needs u2f_authfile with registered keys, or users would be locked out"
+            )));
+        };
+
+        let path = path_under(root_location, authfile)?;
+        let registered = std::fs::read_to_string(&path)
+            .map(|keys| keys.lines().any(|l| l.contains(':')))
+            .unwrap_or(false);
+
+        if !registered {
+            return Err(AliError::HookError(format!(
+                "{hook_key}: no keys registered in {authfile}, users would be locked out"
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+fn pwquality_stanza() -> Stanza {
+    Stanza::new("password", "required", "pam_pwquality.so", &["retry=3"])
+}
+
+impl std::fmt::Display for Pam {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let j = json!({
+            "service": self.service,
+            "faillock": self.faillock.as_ref().map(|faillock| json!({
+                "deny": faillock.deny,
+                "unlock_time": faillock.unlock_time,
+                "deny_root": faillock.deny_root,
+            })),
+            "u2f": self.u2f.as_ref().map(|u2f| json!({
+                "required": u2f.required,
+                "authfile": u2f.authfile,
+            })),
+            "pwquality": self.pwquality,
+        });
+
+        write!(f, "{}", json::to_string(&j))
+    }
+}
+
+#[test]
+fn test_parse_pam() {
+    let should_pass = vec![
+        "@pam faillock=5",
+        "@pam faillock=5:600 deny_root",
+        "@pam-print u2f=sufficient",
+        "@pam u2f=required u2f_authfile=/etc/u2f_mappings pwquality=12",
+        "@pam service=sudo u2f=sufficient",
+        "@pam faillock=5 backup",
+    ];
+
+    let should_err = vec![
+        "@pam",
+        "@pam service=sudo",
+        "@pam faillock=0",
+        "@pam faillock=5:never",
+        "@pam faillock=5:0 deny_root",
+        "@pam deny_root",
+        "@pam u2f=optional",
+        "@pam u2f_authfile=/etc/u2f_mappings",
+        "@pam u2f=required u2f_authfile=u2f_mappings",
+        "@pam pwquality=4",
+        "@pam service=../shadow faillock=5",
+    ];
+
+    for s in should_pass {
+        if let Err(err) = HookPam::try_from(s) {
+            panic!("unexpected error from {s}: {err:?}");
+        }
+    }
+
+    for s in should_err {
+        assert!(HookPam::try_from(s).is_err(), "unexpected ok result from {s}");
+    }
+}
+
+#[test]
+fn test_apply_pam() {
+    use std::sync::Arc;
+
+    use crate::utils::mock::MockRunner;
+
+    let root = std::env::temp_dir()
+        .join(format!("ali-rs-test-pam-{}", std::process::id()));
+    let root_location = root.to_str().unwrap();
+    let service = root.join("etc/pam.d/system-auth");
+    std::fs::create_dir_all(service.parent().unwrap()).unwrap();
+    std::fs::write(&service, pam::TEST_SYSTEM_AUTH).unwrap();
+
+    let mock = Arc::new(MockRunner::new());
+    let _guard = shell::set_runner(mock.clone());
+
+    // No registered keys
+    let cmd = "@pam u2f=required u2f_authfile=/etc/u2f_mappings";
+    assert!(super::apply_hook(cmd, Caller::ManifestChroot, root_location)
+        .is_err());
+    assert_eq!(
+        pam::TEST_SYSTEM_AUTH,
+        std::fs::read_to_string(&service).unwrap(),
+    );
+
+    std::fs::write(root.join("etc/u2f_mappings"), "alice:KEY\n").unwrap();
+    super::apply_hook(
+        &format!("{cmd} faillock=5:600 pwquality=12"),
+        Caller::ManifestChroot,
+        root_location,
+    )
+    .unwrap();
+
+    let conf = PamConf::parse(&std::fs::read_to_string(&service).unwrap());
+    let auth = conf.stack("auth");
+    assert_eq!("pam_u2f.so", auth[4].module);
+    assert_eq!("[success=2 default=ignore]", auth[1].control);
+
+    let password = conf.stack("password");
+    assert_eq!("[success=2 default=ignore]", password[0].control);
+    assert_eq!("pam_pwquality.so", password[1].module);
+    assert!(password[2].args.contains(&"use_authtok".to_string()));
+
+    let faillock =
+        std::fs::read_to_string(root.join("etc/security/faillock.conf"))
+            .unwrap();
+    assert_eq!("deny = 5\nunlock_time = 600\n", faillock);
+    assert_eq!(
+        Some(&format!(
+            "sh -c arch-chroot {root_location} pacman -S --needed --noconfirm pam-u2f libpwquality"
+        )),
+        mock.calls().last(),
+    );
+
+    // Flag backup saves the service file and module configs
+    super::apply_hook(
+        "@pam faillock=3 backup",
+        Caller::ManifestChroot,
+        root_location,
+    )
+    .unwrap();
+
+    let backups = root.join("var/lib/ali-rs/backups/etc");
+    let count =
+        |dir: &str| std::fs::read_dir(backups.join(dir)).unwrap().count();
+    assert_eq!(1, count("pam.d"));
+    assert_eq!(1, count("security"));
+
+    // Unreadable module configs are not replaced with only new options
+    let pwquality = root.join("etc/security/pwquality.conf");
+    let _ = std::fs::remove_file(&pwquality);
+    std::fs::create_dir_all(&pwquality).unwrap();
+    assert!(super::apply_hook(
+        "@pam pwquality=12",
+        Caller::ManifestChroot,
+        root_location
+    )
+    .is_err());
+    assert!(pwquality.is_dir());
+
+    std::fs::remove_dir_all(root).unwrap();
+}
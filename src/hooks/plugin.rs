@@ -0,0 +1,401 @@
+use std::collections::HashSet;
+use std::sync::{
+    Mutex,
+    OnceLock,
+};
+
+use super::utils::protected;
+use super::{
+    extract_key_and_parts_shlex,
+    wrap_bad_hook_cmd,
+    ActionHook,
+    Caller,
+    Hook,
+    ModeHook,
+    ParseError,
+    KEY_PREFIX_PLUGIN,
+};
+use crate::constants::{
+    self,
+    defaults,
+};
+use crate::errors::AliError;
+use crate::utils::shell;
+
+const USAGE: &str = "[ARGS..], see plugin documentation";
+
+/// Plugin keys seen so far, as hook keys are &'static
+static KEYS: OnceLock<Mutex<HashSet<&'static str>>> = OnceLock::new();
+
+/// Hook `@x-<NAME>`, dispatched to executable `<HOOKS_DIR>/x-<NAME>`
+/// or WASM module `<HOOKS_DIR>/x-<NAME>.wasm`, with `-print` suffix
+/// stripped and passed as `ALI_HOOK_MODE=print`
+#[derive(Debug, Clone, PartialEq)]
+struct HookPlugin {
+    key: &'static str,
+    /// Full hook command string
+    cmd: String,
+    executable: String,
+    /// Whether `executable` is a WASM module
+    wasm: bool,
+    args: Vec<String>,
+    mode_hook: ModeHook,
+}
+
+pub(super) fn parse(k: &str, cmd: &str) -> Result<Box<dyn Hook>, ParseError> {
+    match HookPlugin::new(&hooks_dir(), k, cmd) {
+        Err(err) => Err(wrap_bad_hook_cmd(err, USAGE)),
+        Ok(hook) => Ok(Box::new(hook)),
+    }
+}
+
+/// Returns `key` as &'static str, leaking it only the first time
+/// a plugin with this key is parsed
+fn intern(key: &str) -> &'static str {
+    let mut keys = KEYS
+        .get_or_init(|| Mutex::new(HashSet::new()))
+        .lock()
+        .expect("plugin keys lock poisoned");
+
+    match keys.get(key) {
+        Some(interned) => interned,
+        None => {
+            let interned: &'static str = Box::leak(key.into());
+            keys.insert(interned);
+            interned
+        }
+    }
+}
+
+/// Plugin directory, overridable with env `ALI_HOOKS_DIR`
+fn hooks_dir() -> String {
+    std::env::var(constants::ENV_ALI_HOOKS_DIR)
+        .unwrap_or(defaults::HOOKS_DIR.to_string())
+}
+
+impl HookPlugin {
+    fn new(dir: &str, k: &str, cmd: &str) -> Result<Self, AliError> {
+        let (hook_key, parts) = extract_key_and_parts_shlex(cmd)?;
+        if hook_key != k {
+            return Err(AliError::AliRsBug(format!(
+                "plugin key mismatch: {k} != {hook_key}"
+            )));
+        }
+
+        let (name, mode_hook) = match k.strip_suffix("-print") {
+            Some(name) => (name, ModeHook::Print),
+            None => (k, ModeHook::Normal),
+        };
+
+        let name = name.trim_start_matches('@');
+        let valid = |c: char| c.is_ascii_alphanumeric() || "-_.".contains(c);
+        if name.len() < KEY_PREFIX_PLUGIN.len() || !name.chars().all(valid) {
+            return Err(AliError::BadHookCmd(format!(
+                "{k}: bad plugin name {name}"
+            )));
+        }
+
+        // Native executables take precedence over WASM modules
+        let (executable, wasm) = match format!("{dir}/{name}") {
+            executable if is_executable(&executable) => (executable, false),
+            executable if is_file(&format!("{executable}.wasm")) => {
+                (format!("{executable}.wasm"), true)
+            }
+            executable => {
+                return Err(AliError::BadHookCmd(format!(
+                    "{k}: no executable plugin {executable} or {executable}.wasm"
+                )));
+            }
+        };
+
+        Ok(Self {
+            key: intern(&format!("@{name}")),
+            cmd: cmd.to_string(),
+            executable,
+            wasm,
+            args: parts.into_iter().skip(1).collect(),
+            mode_hook,
+        })
+    }
+
+    /// Runs plugin with arguments and environment, and parses its stdout
+    /// as JSON [`ActionHook`]. Plugin stderr is kept in errors.
+    fn exec(
+        &self,
+        caller: &Caller,
+        root_location: &str,
+    ) -> Result<ActionHook, AliError> {
+        let mode = match self.mode_hook {
+            ModeHook::Normal => "normal",
+            ModeHook::Print => "print",
+        };
+
+        log::debug!("exec plugin: {} {}", self.executable, self.args.join(" "));
+
+        // WASM plugins only see root_location, preopened as their `/`
+        let root = match self.wasm {
+            true => "/",
+            false => root_location,
+        };
+
+        let envs = [
+            ("ALI_HOOK_CMD", self.cmd.as_str()),
+            ("ALI_HOOK_CALLER", caller.name()),
+            ("ALI_HOOK_ROOT", root),
+            ("ALI_HOOK_MODE", mode),
+        ];
+
+        // Plugins write files themselves, so protected paths
+        // can only be checked after they ran
+        let watch = protected::watch(root_location);
+        let stdout = match self.wasm {
+            true => self.exec_wasm(&envs, root_location)?,
+            false => self.exec_native(&envs)?,
+        };
+
+        watch.check(self.key)?;
+
+        serde_json::from_slice(&stdout).map_err(|err| {
+            AliError::HookError(format!(
+                "{}: bad ActionHook JSON from plugin {}: {err}",
+                self.key, self.executable
+            ))
+        })
+    }
+
+    fn exec_native(&self, envs: &[(&str, &str)]) -> Result<Vec<u8>, AliError> {
+        let args: Vec<&str> = self.args.iter().map(|s| s.as_str()).collect();
+
+        shell::exec_with_output_envs(&self.executable, &args, envs).map_err(
+            |err| {
+                AliError::HookError(format!(
+                    "{}: plugin {} failed: {err}",
+                    self.key, self.executable
+                ))
+            },
+        )
+    }
+
+    #[cfg(feature = "wasm")]
+    fn exec_wasm(
+        &self,
+        envs: &[(&str, &str)],
+        root_location: &str,
+    ) -> Result<Vec<u8>, AliError> {
+        super::wasm::exec(
+            self.key,
+            &self.executable,
+            &self.args,
+            envs,
+            root_location,
+        )
+    }
+
+    #[cfg(not(feature = "wasm"))]
+    fn exec_wasm(
+        &self,
+        _envs: &[(&str, &str)],
+        _root_location: &str,
+    ) -> Result<Vec<u8>, AliError> {
+        Err(AliError::HookError(format!(
+            "{}: wasm plugin {} requires ali-rs built with feature wasm",
+            self.key, self.executable
+        )))
+    }
+}
+
+impl super::Hook for HookPlugin {
+    fn base_key(&self) -> &'static str {
+        self.key
+    }
+
+    /// `@x-<NAME> [ARGS..]`
+    ///
+    /// Runs executable `x-<NAME>` in plugin directory (default
+    /// `/usr/lib/ali-rs/hooks`) with ARGS as arguments, and with env
+    /// `ALI_HOOK_CMD`, `ALI_HOOK_CALLER`, `ALI_HOOK_ROOT`, and `ALI_HOOK_MODE`.
+    ///
+    /// Without executable `x-<NAME>`, WASM module `x-<NAME>.wasm` is run
+    /// with WASI instead, with access only to the root location as `/`.
+    ///
+    /// The plugin prints JSON ActionHook to stdout, e.g.
+    ///
+    /// ```txt
+    /// {"Plugin": "{\"foo\": \"bar\"}"}
+    /// ```
+    fn usage(&self) -> &'static str {
+        USAGE
+    }
+
+    fn mode(&self) -> ModeHook {
+        self.mode_hook.clone()
+    }
+
+    /// Plugins decide where to write themselves with `ALI_HOOK_ROOT`
+    fn should_chroot(&self) -> bool {
+        false
+    }
+
+    fn prefer_caller(&self, _caller: &Caller) -> bool {
+        true
+    }
+
+    fn abort_if_no_mount(&self) -> bool {
+        false
+    }
+
+    fn run_hook(
+        &self,
+        caller: &Caller,
+        root_location: &str,
+    ) -> Result<ActionHook, AliError> {
+        self.exec(caller, root_location)
+    }
+}
+
+fn is_file(path: &str) -> bool {
+    std::fs::metadata(path)
+        .map(|meta| meta.is_file())
+        .unwrap_or(false)
+}
+
+fn is_executable(path: &str) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    std::fs::metadata(path)
+        .map(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+#[test]
+fn test_plugin() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = std::env::temp_dir()
+        .join(format!("ali-rs-test-plugin-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let dir = dir.to_string_lossy().to_string();
+
+    // Echoes its arguments and environment back as ActionHook
+    let script = format!("{dir}/x-echo");
+    std::fs::write(
+        &script,
+        r#"#!/bin/sh
+printf '{"Plugin":"%s %s %s %s"}' "$*" "$ALI_HOOK_CALLER" "$ALI_HOOK_ROOT" "$ALI_HOOK_MODE"
+"#,
+    )
+    .unwrap();
+    std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755))
+        .unwrap();
+
+    let hook =
+        HookPlugin::new(&dir, "@x-echo-print", "@x-echo-print foo 'bar baz'")
+            .unwrap();
+    assert_eq!(ModeHook::Print, hook.mode_hook);
+    assert_eq!(vec!["foo", "bar baz"], hook.args);
+    assert_eq!("@x-echo-print", hook.hook_key());
+
+    // Keys are leaked once per plugin, not once per parse
+    let again = HookPlugin::new(&dir, "@x-echo", "@x-echo").unwrap();
+    assert!(std::ptr::eq(hook.key, again.key));
+
+    let action = hook.exec(&Caller::ManifestChroot, "/alitarget").unwrap();
+    match action {
+        ActionHook::Plugin(s) => {
+            assert_eq!("foo bar baz manifest-chroot /alitarget print", s)
+        }
+        action => panic!("unexpected action {action:?}"),
+    }
+
+    // Plugins writing protected paths fail
+    let script = format!("{dir}/x-shadow");
+    std::fs::write(
+        &script,
+        r#"#!/bin/sh
+mkdir -p "$ALI_HOOK_ROOT/etc"
+echo 'root::1::::::' >"$ALI_HOOK_ROOT/etc/shadow"
+printf '{"Plugin":"shadow"}'
+"#,
+    )
+    .unwrap();
+    std::fs::set_permissions(&script, std::fs::Permissions::from_mode(0o755))
+        .unwrap();
+
+    let root = format!("{dir}/root");
+    let hook = HookPlugin::new(&dir, "@x-shadow", "@x-shadow").unwrap();
+    let err = hook.exec(&Caller::Cli, &root).unwrap_err();
+    assert!(err.to_string().contains("protected path /etc/shadow"), "{err}");
+
+    // WASM modules are used if there's no native executable
+    std::fs::write(format!("{dir}/x-mod.wasm"), "").unwrap();
+    let hook = HookPlugin::new(&dir, "@x-mod", "@x-mod").unwrap();
+    assert!(hook.wasm);
+    assert_eq!(format!("{dir}/x-mod.wasm"), hook.executable);
+
+    let should_err = vec![
+        ("@x-", "@x-"),
+        ("@x-missing", "@x-missing foo"),
+        ("@x-ec/ho", "@x-ec/ho"),
+    ];
+
+    for (k, cmd) in should_err {
+        assert!(
+            HookPlugin::new(&dir, k, cmd).is_err(),
+            "expecting error for {cmd}"
+        );
+    }
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(feature = "wasm")]
+#[test]
+fn test_plugin_wasm() {
+    let dir = std::env::temp_dir()
+        .join(format!("ali-rs-test-plugin-wasm-{}", std::process::id()));
+    let root = dir.join("root");
+    std::fs::create_dir_all(&root).unwrap();
+    let dir = dir.to_string_lossy().to_string();
+    let root = root.to_string_lossy().to_string();
+
+    // Prints ActionHook JSON, and writes it to `/hello` under guest root.
+    // Text format is accepted by wasmtime in place of binary modules.
+    std::fs::write(
+        format!("{dir}/x-hello.wasm"),
+        r#"(module
+  (import "wasi_snapshot_preview1" "fd_write"
+    (func $fd_write (param i32 i32 i32 i32) (result i32)))
+  (import "wasi_snapshot_preview1" "path_open"
+    (func $path_open (param i32 i32 i32 i32 i32 i64 i64 i32 i32) (result i32)))
+  (memory (export "memory") 1)
+  (data (i32.const 16) "{\"Plugin\":\"wasm\"}")
+  (data (i32.const 64) "hello")
+  (func (export "_start")
+    (i32.store (i32.const 0) (i32.const 16))
+    (i32.store (i32.const 4) (i32.const 17))
+    (drop (call $path_open (i32.const 3) (i32.const 0) (i32.const 64)
+      (i32.const 5) (i32.const 1) (i64.const 0x1fffffff) (i64.const 0x1fffffff)
+      (i32.const 0) (i32.const 128)))
+    (drop (call $fd_write (i32.const 1) (i32.const 0) (i32.const 1) (i32.const 8)))
+    (drop (call $fd_write (i32.load (i32.const 128)) (i32.const 0)
+      (i32.const 1) (i32.const 8)))))
+"#,
+    )
+    .unwrap();
+
+    let hook = HookPlugin::new(&dir, "@x-hello", "@x-hello").unwrap();
+    assert!(hook.wasm);
+
+    let action = hook.exec(&Caller::Cli, &root).unwrap();
+    match action {
+        ActionHook::Plugin(s) => assert_eq!("wasm", s),
+        action => panic!("unexpected action {action:?}"),
+    }
+
+    assert_eq!(
+        r#"{"Plugin":"wasm"}"#,
+        std::fs::read_to_string(format!("{root}/hello")).unwrap(),
+    );
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -1,8 +1,12 @@
 use serde_json::json;
 
 use super::constants::quicknet::*;
+use super::utils::{
+    output,
+    protected,
+};
 use super::{
-    extract_key_and_parts,
+    extract_key_and_parts_shlex,
     wrap_bad_hook_cmd,
     ActionHook,
     Caller,
@@ -13,14 +17,120 @@ use super::{
     KEY_QUICKNET_PRINT,
 };
 use crate::errors::AliError;
-use crate::utils::shell;
+use crate::linux::systemd;
+use crate::utils::fs::{
+    mkdir_p,
+    path_under,
+    write_private,
+};
+use crate::utils::json;
+use crate::utils::secrets;
+
+const USAGE: &str = "<INTERFACE[,INTERFACE..]> [dns <DNS_UPSTREAM>] [backend=networkd|networkmanager] [ssid=<SSID> psk=<PSK> | psk_file=<FILE>] [wireless=iwd|wpa_supplicant] [vlan=<ID> | bridge=<BRIDGE> | bond=<BOND> [mode=<BOND_MODE>]] [ipv6=auto|off|static <ADDRESS/PREFIX>]";
 
-const USAGE: &str = "interface [dns <DNS_STREAM>]";
+/// Example arguments, for `ali-rs hooks schema`
+pub(super) const EXAMPLE: &str = "eth0 dns 1.1.1.1";
+
+/// Keyword arguments, for shell completion
+pub(super) const KEYWORDS: &[&str] = &[
+    "dns",
+    "backend=networkd",
+    "backend=networkmanager",
+    "ssid=",
+    "psk=",
+    "psk_file=",
+    "wireless=iwd",
+    "wireless=wpa_supplicant",
+    "vlan=",
+    "bridge=",
+    "bond=",
+    "mode=",
+    "ipv6=auto",
+    "ipv6=off",
+    "ipv6=static",
+];
+
+const BOND_MODES: [&str; 7] = [
+    "balance-rr",
+    "active-backup",
+    "balance-xor",
+    "broadcast",
+    "802.3ad",
+    "balance-tlb",
+    "balance-alb",
+];
 
 #[derive(Debug, Clone, PartialEq)]
 struct QuickNet {
     interface: String,
     dns_upstream: Option<String>,
+    backend: Backend,
+    wireless: Option<Wireless>,
+    topology: Option<Topology>,
+    ipv6: Option<Ipv6>,
+}
+
+/// IPv6 intent. If not given, the backend defaults are used.
+#[derive(Debug, Clone, PartialEq)]
+enum Ipv6 {
+    /// SLAAC via router advertisements
+    Auto,
+    /// Static address with prefix length, e.g. `2001:db8::10/64`
+    Static(String),
+    /// IPv6 disabled on the interface
+    Off,
+}
+
+/// Virtual network device created on top of the interface(s).
+/// DHCP and DNS are then configured on the virtual device.
+#[derive(Debug, Clone, PartialEq)]
+enum Topology {
+    /// VLAN `<INTERFACE>.<ID>` on top of the interface
+    Vlan(u16),
+
+    /// Bridge enslaving the interface
+    Bridge(String),
+
+    /// Bond enslaving all comma-separated interfaces
+    Bond { name: String, mode: Option<String> },
+}
+
+/// Network configuration backend
+#[derive(Debug, Clone, PartialEq)]
+enum Backend {
+    Networkd,
+    NetworkManager,
+}
+
+/// File to be written by quicknet, with path relative to the new root
+#[derive(Debug, Clone, PartialEq)]
+struct QuickNetFile {
+    path: String,
+    content: String,
+    /// Files with secrets are only readable by owner
+    secret: bool,
+}
+
+/// Wi-Fi profile for wireless interfaces
+#[derive(Debug, Clone, PartialEq)]
+struct Wireless {
+    ssid: String,
+    psk: Psk,
+    backend: WirelessBackend,
+}
+
+/// Pre-shared key, either inline or read from file when the hook runs,
+/// so that the passphrase does not have to live in the manifest
+#[derive(Debug, Clone, PartialEq)]
+enum Psk {
+    Inline(String),
+    File(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum WirelessBackend {
+    Iwd,
+    WpaSupplicant,
 }
 
 struct HookQuickNet {
@@ -46,7 +156,7 @@ impl super::Hook for HookQuickNet {
         KEY_QUICKNET
     }
 
-    /// `@quicknet [dns <DNS_UPSTREAM>] <INTERFACE>`
+    /// `@quicknet [dns <DNS_UPSTREAM>] <INTERFACE> [ssid=<SSID> psk=<PSK>]`
     ///
     /// Examples:
     ///
@@ -61,6 +171,20 @@ impl super::Hook for HookQuickNet {
     /// ```txt
     /// @quicknet dns 1.1.1.1 ens3
     /// ```
+    ///
+    /// 3. Setup DHCP for wlan0, connecting to SSID `home` with iwd
+    ///
+    /// ```txt
+    /// @quicknet wlan0 ssid=home psk_file=/root/home.psk
+    /// ```
+    ///
+    /// PSK can also be a secret reference, e.g. `psk=secret://home-psk`
+    ///
+    /// 4. Setup DHCP for bond0, enslaving eth0 and eth1
+    ///
+    /// ```txt
+    /// @quicknet eth0,eth1 bond=bond0 mode=active-backup
+    /// ```
     fn usage(&self) -> &'static str {
         USAGE
     }
@@ -99,122 +223,462 @@ impl TryFrom<&str> for HookQuickNet {
     type Error = AliError;
 
     fn try_from(s: &str) -> Result<Self, Self::Error> {
-        let (hook_key, parts) = extract_key_and_parts(s)?;
+        let (hook_key, parts) = extract_key_and_parts_shlex(s)?;
         let mode_hook = match hook_key.as_str() {
             KEY_QUICKNET => ModeHook::Normal,
             KEY_QUICKNET_PRINT => ModeHook::Print,
             key => panic!("unexpected key {key}"),
         };
 
-        let qn = match parts.len() {
-            2 => {
-                let interface = parts.get(1).unwrap();
-                if interface == "dns" {
+        let mut interface = None;
+        let mut dns_upstream = None;
+        let mut opts = std::collections::HashMap::new();
+        let mut ipv6_static = None;
+
+        let mut args = parts.iter().skip(1);
+        while let Some(arg) = args.next() {
+            if arg == "dns" {
+                let upstream = args.next().ok_or(AliError::BadHookCmd(
+                    format!("{hook_key}: got only keyword `dns`"),
+                ))?;
+
+                if dns_upstream.replace(upstream.to_string()).is_some() {
                     return Err(AliError::BadHookCmd(format!(
-                        "{hook_key}: got only keyword `dns`"
+                        "{hook_key}: duplicate keyword `dns`"
                     )));
                 }
 
-                QuickNet {
-                    interface: interface.to_string(),
-                    dns_upstream: None,
-                }
+                continue;
             }
 
-            4 => {
-                let mut dns_keyword_idx = None;
-                for (i, word) in parts.iter().enumerate() {
-                    if *word == "dns" {
-                        dns_keyword_idx = Some(i);
+            if let Some((k, v)) = arg.split_once('=') {
+                // `ipv6=static <ADDRESS>` takes the next argument
+                if k == "ipv6" && v == "static" {
+                    let addr = args.next().ok_or(AliError::BadHookCmd(
+                        format!("{hook_key}: ipv6=static requires address"),
+                    ))?;
 
-                        break;
+                    if ipv6_static.replace(addr.as_str()).is_some() {
+                        return Err(AliError::BadHookCmd(format!(
+                            "{hook_key}: duplicate key {k}"
+                        )));
                     }
+
+                    continue;
                 }
 
-                if dns_keyword_idx.is_none() {
+                if opts.insert(k, v).is_some() {
                     return Err(AliError::BadHookCmd(format!(
-                        "{hook_key}: missing argument keyword \"dns\""
+                        "{hook_key}: duplicate key {k}"
                     )));
                 }
-                // #cmd dns upstream inf  1
-                // #cmd inf dns upstream  2
-                let dns_keyword_idx = dns_keyword_idx.unwrap();
-                let interface_idx = {
-                    if dns_keyword_idx == 1 {
-                        3
-                    } else if dns_keyword_idx == 2 {
-                        1
-                    } else {
-                        return Err(AliError::BadHookCmd(format!(
-                        "{hook_key}: \"dns\" keyword in bad position: {dns_keyword_idx}"
-                    )));
-                    }
-                };
 
-                QuickNet {
-                    interface: parts[interface_idx].to_string(),
-                    dns_upstream: Some(parts[dns_keyword_idx + 1].to_string()),
-                }
+                continue;
             }
 
-            l => {
+            if interface.replace(arg.to_string()).is_some() {
                 return Err(AliError::BadHookCmd(format!(
-                    "{hook_key}: unexpected cmd parts length: {l}"
+                    "{hook_key}: unexpected argument {arg}"
+                )));
+            }
+        }
+
+        let interface = interface.ok_or(AliError::BadHookCmd(format!(
+            "{hook_key}: missing interface"
+        )))?;
+
+        let backend = match opts.remove("backend") {
+            None | Some("networkd") | Some("systemd-networkd") => {
+                Backend::Networkd
+            }
+            Some("networkmanager") | Some("NetworkManager") | Some("nm") => {
+                Backend::NetworkManager
+            }
+            Some(other) => {
+                return Err(AliError::BadHookCmd(format!(
+                    "{hook_key}: unknown backend {other}"
                 )));
             }
         };
 
-        Ok(HookQuickNet { qn, mode_hook })
+        if backend == Backend::NetworkManager && opts.contains_key("wireless") {
+            return Err(AliError::BadHookCmd(format!(
+                "{hook_key}: key wireless is not used with backend networkmanager"
+            )));
+        }
+
+        let wireless = parse_wireless(&hook_key, &mut opts)?;
+        let topology = parse_topology(&hook_key, &mut opts)?;
+        let ipv6 = parse_ipv6(&hook_key, opts.remove("ipv6"), ipv6_static)?;
+
+        if topology.is_some() {
+            if backend == Backend::NetworkManager {
+                return Err(AliError::NotImplemented(format!(
+                    "{hook_key}: vlan, bridge, and bond with backend networkmanager"
+                )));
+            }
+
+            if wireless.is_some() {
+                return Err(AliError::BadHookCmd(format!(
+                    "{hook_key}: vlan, bridge, and bond are not used with wireless"
+                )));
+            }
+        }
+
+        let is_bond = matches!(topology, Some(Topology::Bond { .. }));
+        if !is_bond && interface.contains(',') {
+            return Err(AliError::BadHookCmd(format!(
+                "{hook_key}: multiple interfaces are only used with bond"
+            )));
+        }
+
+        if interface.split(',').any(str::is_empty) {
+            return Err(AliError::BadHookCmd(format!(
+                "{hook_key}: empty interface in {interface}"
+            )));
+        }
+
+        if let Some(k) = opts.keys().next() {
+            return Err(AliError::BadHookCmd(format!(
+                "{hook_key}: unknown key {k}"
+            )));
+        }
+
+        Ok(HookQuickNet {
+            qn: QuickNet {
+                interface,
+                dns_upstream,
+                backend,
+                wireless,
+                topology,
+                ipv6,
+            },
+            mode_hook,
+        })
+    }
+}
+
+/// Consumes wireless options from `opts`
+fn parse_wireless(
+    hook_key: &str,
+    opts: &mut std::collections::HashMap<&str, &str>,
+) -> Result<Option<Wireless>, AliError> {
+    let ssid = opts.remove("ssid");
+    let psk = opts.remove("psk");
+    let psk_file = opts.remove("psk_file");
+    let backend = opts.remove("wireless");
+
+    let ssid = match ssid {
+        Some(ssid) => ssid,
+        None => {
+            if psk.is_some() || psk_file.is_some() || backend.is_some() {
+                return Err(AliError::BadHookCmd(format!(
+                    "{hook_key}: wireless options require ssid"
+                )));
+            }
+
+            return Ok(None);
+        }
+    };
+
+    if ssid.is_empty() {
+        return Err(AliError::BadHookCmd(format!("{hook_key}: empty ssid")));
+    }
+
+    let psk = match (psk, psk_file) {
+        (Some(psk), None) => Psk::Inline(psk.to_string()),
+        (None, Some(file)) => Psk::File(file.to_string()),
+        (None, None) => {
+            return Err(AliError::BadHookCmd(format!(
+                "{hook_key}: ssid requires psk or psk_file"
+            )));
+        }
+        (Some(_), Some(_)) => {
+            return Err(AliError::BadHookCmd(format!(
+                "{hook_key}: psk and psk_file are mutually exclusive, but found both"
+            )));
+        }
+    };
+
+    let backend = match backend {
+        None | Some("iwd") => WirelessBackend::Iwd,
+        Some("wpa_supplicant") | Some("wpa-supplicant") => {
+            WirelessBackend::WpaSupplicant
+        }
+        Some(other) => {
+            return Err(AliError::BadHookCmd(format!(
+                "{hook_key}: unknown wireless backend {other}"
+            )));
+        }
+    };
+
+    Ok(Some(Wireless {
+        ssid: ssid.to_string(),
+        psk,
+        backend,
+    }))
+}
+
+/// Parses `ipv6=auto|off`, or address from `ipv6=static <ADDRESS>`
+fn parse_ipv6(
+    hook_key: &str,
+    opt: Option<&str>,
+    static_addr: Option<&str>,
+) -> Result<Option<Ipv6>, AliError> {
+    // Also accept quoted `'ipv6=static <ADDRESS>'`
+    let (opt, static_addr) = match opt.and_then(|o| o.strip_prefix("static ")) {
+        Some(addr) if static_addr.is_none() => (None, Some(addr.trim())),
+        _ => (opt, static_addr),
+    };
+
+    match (opt, static_addr) {
+        (None, None) => Ok(None),
+        (Some("auto"), None) => Ok(Some(Ipv6::Auto)),
+        (Some("off"), None) => Ok(Some(Ipv6::Off)),
+        (None, Some(addr)) => {
+            let valid = addr.split_once('/').is_some_and(|(ip, prefix)| {
+                ip.parse::<std::net::Ipv6Addr>().is_ok()
+                    && prefix.parse::<u8>().is_ok_and(|p| p <= 128)
+            });
+
+            if !valid {
+                return Err(AliError::BadHookCmd(format!(
+                    "{hook_key}: bad ipv6 address {addr}, expecting <ADDRESS>/<PREFIX>"
+                )));
+            }
+
+            Ok(Some(Ipv6::Static(addr.to_string())))
+        }
+        (Some(other), None) => {
+            Err(AliError::BadHookCmd(format!(
+                "{hook_key}: unknown ipv6 mode {other}"
+            )))
+        }
+        (Some(_), Some(_)) => {
+            Err(AliError::BadHookCmd(format!(
+                "{hook_key}: duplicate key ipv6"
+            )))
+        }
+    }
+}
+
+/// Consumes VLAN, bridge, and bond options from `opts`.
+/// The 3 topologies are mutually exclusive.
+fn parse_topology(
+    hook_key: &str,
+    opts: &mut std::collections::HashMap<&str, &str>,
+) -> Result<Option<Topology>, AliError> {
+    let vlan = opts.remove("vlan");
+    let bridge = opts.remove("bridge");
+    let bond = opts.remove("bond");
+    let mode = opts.remove("mode");
+
+    if mode.is_some() && bond.is_none() {
+        return Err(AliError::BadHookCmd(format!(
+            "{hook_key}: key mode is only used with bond"
+        )));
+    }
+
+    let topology = match (vlan, bridge, bond) {
+        (None, None, None) => return Ok(None),
+
+        (Some(id), None, None) => {
+            let id = id.parse::<u16>().map_err(|err| {
+                AliError::BadHookCmd(format!(
+                    "{hook_key}: bad vlan {id}: {err}"
+                ))
+            })?;
+
+            // 0 and 4095 are reserved
+            if !(1..=4094).contains(&id) {
+                return Err(AliError::BadHookCmd(format!(
+                    "{hook_key}: vlan {id} out of range 1-4094"
+                )));
+            }
+
+            Topology::Vlan(id)
+        }
+
+        (None, Some(bridge), None) => Topology::Bridge(bridge.to_string()),
+
+        (None, None, Some(bond)) => {
+            if let Some(mode) = mode {
+                if !BOND_MODES.contains(&mode) {
+                    return Err(AliError::BadHookCmd(format!(
+                        "{hook_key}: unknown bond mode {mode}"
+                    )));
+                }
+            }
+
+            Topology::Bond {
+                name: bond.to_string(),
+                mode: mode.map(|m| m.to_string()),
+            }
+        }
+
+        _ => {
+            return Err(AliError::BadHookCmd(format!(
+                "{hook_key}: vlan, bridge, and bond are mutually exclusive"
+            )));
+        }
+    };
+
+    match topology {
+        Topology::Bridge(ref name) | Topology::Bond { ref name, .. }
+            if name.is_empty() =>
+        {
+            Err(AliError::BadHookCmd(format!(
+                "{hook_key}: empty device name"
+            )))
+        }
+        topology => Ok(Some(topology)),
     }
 }
 
 /// Creates directory "{root_location}/etc/systemd/network/"
-/// and write networkd quicknet config file for it
+/// and write networkd quicknet config file for it.
+/// If the interface is wireless, the Wi-Fi profile is written too.
 fn apply_quicknet(
     hook_key: &str,
     mode_hook: &ModeHook,
     qn: &QuickNet,
     root_location: &str,
 ) -> Result<ActionHook, AliError> {
-    // Formats filename and string output
-    let filename = FILENAME_TPL.replace(TOKEN_INTERFACE, &qn.interface);
-    let filename = format!("{root_location}/{filename}");
-    let conf_str = qn.encode_to_string();
-
-    match mode_hook {
-        ModeHook::Print => {
-            println!("{conf_str}");
-        }
-        ModeHook::Normal => {
-            // Extends to include systemd path
-            let root_location = format!("{root_location}/etc/systemd/network");
-            shell::exec("mkdir", &["-p", &root_location])?;
-
-            std::fs::write(&filename, conf_str).map_err(|err| {
-                AliError::FileError(
-                    err,
-                    format!("{hook_key}: writing file {filename}"),
-                )
-            })?;
+    let files = qn.encode_files()?;
+    let services = qn.services();
+
+    if matches!(mode_hook, ModeHook::Print) {
+        for f in files {
+            output::println(&format!(
+                "# {}\n{}",
+                f.path,
+                secrets::redact(&f.content),
+            ));
         }
+        for service in services {
+            output::println(&format!("# enable {service}"));
+        }
+
+        return Ok(ActionHook::QuickNet(qn.to_string()));
+    }
+
+    for f in files {
+        let filename = path_under(root_location, &f.path)?;
+        let parent = std::path::Path::new(&filename)
+            .parent()
+            .map(|p| p.to_string_lossy().to_string())
+            .unwrap_or(root_location.to_string());
+
+        mkdir_p(&parent)?;
+
+        protected::check(hook_key, root_location, &filename)?;
+        if f.secret {
+            write_private(&filename, f.content.as_bytes())?;
+            continue;
+        }
+
+        std::fs::write(&filename, f.content).map_err(|err| {
+            AliError::FileError(
+                err,
+                format!("{hook_key}: writing file {filename}"),
+            )
+        })?;
+    }
+
+    for service in services {
+        systemd::enable_service(root_location, &service, "multi-user.target")?;
     }
 
     Ok(ActionHook::QuickNet(qn.to_string()))
 }
 
-impl ToString for QuickNet {
-    fn to_string(&self) -> String {
-        json!({
+impl std::fmt::Display for QuickNet {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // PSK is omitted from reports
+        let j = json!({
             "interface": self.interface,
             "dns_upstream": self.dns_upstream,
-        })
-        .to_string()
+            "backend": self.backend.to_string(),
+            "ssid": self.wireless.as_ref().map(|w| &w.ssid),
+            "wireless": self.wireless.as_ref().map(|w| w.backend.to_string()),
+            "topology": self.topology.as_ref().map(|t| t.to_string()),
+            "ipv6": self.ipv6.as_ref().map(|v| v.to_string()),
+        });
+
+        write!(f, "{}", json::to_string(&j))
+    }
+}
+
+impl std::fmt::Display for Backend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Networkd => write!(f, "networkd"),
+            Self::NetworkManager => write!(f, "networkmanager"),
+        }
+    }
+}
+
+impl std::fmt::Display for Ipv6 {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Auto => write!(f, "auto"),
+            Self::Static(addr) => write!(f, "static {addr}"),
+            Self::Off => write!(f, "off"),
+        }
+    }
+}
+
+impl std::fmt::Display for Topology {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Vlan(id) => write!(f, "vlan={id}"),
+            Self::Bridge(name) => write!(f, "bridge={name}"),
+            Self::Bond {
+                name,
+                mode: Some(mode),
+            } => write!(f, "bond={name} mode={mode}"),
+            Self::Bond { name, mode: None } => write!(f, "bond={name}"),
+        }
+    }
+}
+
+impl std::fmt::Display for WirelessBackend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Iwd => write!(f, "iwd"),
+            Self::WpaSupplicant => write!(f, "wpa_supplicant"),
+        }
     }
 }
 
 impl QuickNet {
+    /// Returns name of the interface on which DHCP is configured,
+    /// i.e. the virtual device if any
+    fn dhcp_interface(&self) -> String {
+        match self.topology {
+            None => self.interface.clone(),
+            Some(Topology::Vlan(id)) => format!("{}.{id}", self.interface),
+            Some(Topology::Bridge(ref name)) => name.clone(),
+            Some(Topology::Bond { ref name, .. }) => name.clone(),
+        }
+    }
+
     fn encode_to_string(&self) -> String {
-        let mut s = NETWORKD_DHCP.replace(TOKEN_INTERFACE, &self.interface);
+        let mut s =
+            NETWORKD_DHCP.replace(TOKEN_INTERFACE, &self.dhcp_interface());
+
+        let ipv6 = match self.ipv6 {
+            None => None,
+            Some(Ipv6::Auto) => Some(format!("DHCP=yes\n{NETWORKD_IPV6_AUTO}")),
+            Some(Ipv6::Static(ref addr)) => {
+                Some(NETWORKD_IPV6_STATIC.replace(TOKEN_ADDRESS, addr))
+            }
+            Some(Ipv6::Off) => Some(NETWORKD_IPV6_OFF.to_string()),
+        };
+
+        if let Some(ipv6) = ipv6 {
+            s = s.replacen("DHCP=yes\n", &ipv6, 1);
+        }
         if let Some(ref upstream) = self.dns_upstream {
             let dns_conf = NETWORKD_DNS.replace(TOKEN_DNS, upstream);
 
@@ -223,6 +687,295 @@ impl QuickNet {
 
         s
     }
+
+    /// Returns all files to be written, with paths relative to the new root
+    fn encode_files(&self) -> Result<Vec<QuickNetFile>, AliError> {
+        if self.backend == Backend::NetworkManager {
+            return Ok(vec![self.encode_nm_connection()?]);
+        }
+
+        let filename =
+            FILENAME_TPL.replace(TOKEN_INTERFACE, &self.dhcp_interface());
+        let mut files = vec![QuickNetFile {
+            path: format!("{DIR_NETWORKD}/{filename}"),
+            content: self.encode_to_string(),
+            secret: false,
+        }];
+
+        if let Some(ref topology) = self.topology {
+            files.extend(self.encode_topology(topology));
+        }
+
+        if let Some(Ipv6::Off) = self.ipv6 {
+            files.push(self.encode_sysctl_ipv6_off());
+        }
+
+        if let Some(ref wireless) = self.wireless {
+            files.push(wireless.encode_file(&self.interface)?);
+        }
+
+        Ok(files)
+    }
+
+    /// Encodes sysctl drop-in disabling IPv6 on the DHCP interface,
+    /// so that the kernel does not autoconfigure link-local addresses
+    fn encode_sysctl_ipv6_off(&self) -> QuickNetFile {
+        let inf = self.dhcp_interface();
+
+        QuickNetFile {
+            path: FILENAME_SYSCTL_IPV6_TPL.replace(TOKEN_INTERFACE, &inf),
+            content: SYSCTL_IPV6_OFF
+                .replace(TOKEN_INTERFACE, &inf.replace('.', "/")),
+            secret: false,
+        }
+    }
+
+    /// Encodes .netdev file for the virtual device, and .network files
+    /// attaching member interfaces to it
+    fn encode_topology(&self, topology: &Topology) -> Vec<QuickNetFile> {
+        let dev = self.dhcp_interface();
+        let (kind, member_key) = match topology {
+            Topology::Vlan(_) => ("vlan", "VLAN"),
+            Topology::Bridge(_) => ("bridge", "Bridge"),
+            Topology::Bond { .. } => ("bond", "Bond"),
+        };
+
+        let mut netdev = NETWORKD_NETDEV
+            .replace(TOKEN_INTERFACE, &dev)
+            .replace(TOKEN_KIND, kind);
+
+        match topology {
+            Topology::Vlan(id) => {
+                netdev.push_str(
+                    &NETWORKD_NETDEV_VLAN
+                        .replace(TOKEN_VLAN_ID, &id.to_string()),
+                );
+            }
+            Topology::Bond {
+                mode: Some(mode), ..
+            } => {
+                netdev.push_str(
+                    &NETWORKD_NETDEV_BOND.replace(TOKEN_BOND_MODE, mode),
+                );
+            }
+            _ => {}
+        }
+
+        let mut files = vec![QuickNetFile {
+            path: format!(
+                "{DIR_NETWORKD}/{}",
+                FILENAME_NETDEV_TPL.replace(TOKEN_INTERFACE, &dev)
+            ),
+            content: netdev,
+            secret: false,
+        }];
+
+        for member in self.interface.split(',') {
+            files.push(QuickNetFile {
+                path: format!(
+                    "{DIR_NETWORKD}/{}",
+                    FILENAME_MEMBER_TPL.replace(TOKEN_INTERFACE, member)
+                ),
+                content: NETWORKD_MEMBER
+                    .replace(TOKEN_INTERFACE, member)
+                    .replace(TOKEN_KIND, member_key)
+                    .replace(TOKEN_MASTER, &dev),
+                secret: false,
+            });
+        }
+
+        files
+    }
+
+    /// Encodes NetworkManager keyfile connection
+    fn encode_nm_connection(&self) -> Result<QuickNetFile, AliError> {
+        let conn_type = match self.wireless {
+            Some(_) => "wifi",
+            None => "ethernet",
+        };
+
+        let mut s = NM_CONNECTION
+            .replace(TOKEN_INTERFACE, &self.interface)
+            .replace(TOKEN_TYPE, conn_type);
+
+        if let Some(ref wireless) = self.wireless {
+            let psk = wireless.psk.resolve()?;
+            s.push_str(
+                &NM_WIFI
+                    .replace(TOKEN_SSID, &keyfile_escape(&wireless.ssid))
+                    .replace(TOKEN_PSK, &keyfile_escape(&psk)),
+            );
+        }
+
+        s.push_str(NM_IP);
+        match self.ipv6 {
+            None | Some(Ipv6::Auto) => {}
+            Some(Ipv6::Static(ref addr)) => {
+                s = s.replacen(
+                    NM_IPV6_AUTO,
+                    &NM_IPV6_STATIC.replace(TOKEN_ADDRESS, addr),
+                    1,
+                );
+            }
+            Some(Ipv6::Off) => s = s.replacen(NM_IPV6_AUTO, NM_IPV6_OFF, 1),
+        }
+
+        if let Some(ref upstream) = self.dns_upstream {
+            // Append DNS to [ipv4] section
+            let dns = NM_DNS.replace(TOKEN_DNS, upstream);
+            s = s.replacen(
+                "[ipv4]\nmethod=auto\n",
+                &format!("[ipv4]\nmethod=auto\n{dns}"),
+                1,
+            );
+        }
+
+        Ok(QuickNetFile {
+            path: FILENAME_NM_TPL.replace(TOKEN_INTERFACE, &self.interface),
+            content: s,
+            // NetworkManager ignores keyfiles readable by others
+            secret: true,
+        })
+    }
+
+    /// Returns systemd services to be enabled for the configuration
+    fn services(&self) -> Vec<String> {
+        let mut services = match self.backend {
+            Backend::Networkd => vec!["systemd-networkd.service".to_string()],
+            Backend::NetworkManager => {
+                return vec!["NetworkManager.service".to_string()];
+            }
+        };
+
+        if let Some(ref wireless) = self.wireless {
+            services.push(match wireless.backend {
+                WirelessBackend::Iwd => "iwd.service".to_string(),
+                WirelessBackend::WpaSupplicant => {
+                    format!("wpa_supplicant@{}.service", self.interface)
+                }
+            });
+        }
+
+        services
+    }
+}
+
+impl Psk {
+    fn resolve(&self) -> Result<String, AliError> {
+        let psk = match self {
+            Psk::Inline(psk) => secrets::resolve(psk)?,
+            Psk::File(path) => {
+                std::fs::read_to_string(path)
+                    .map_err(|err| {
+                        AliError::FileError(
+                            err,
+                            format!("{KEY_QUICKNET}: reading psk file {path}"),
+                        )
+                    })?
+                    .trim()
+                    .to_string()
+            }
+        };
+
+        // Inline PSKs are secrets too, and are redacted from print mode
+        secrets::register(&psk);
+
+        // WPA passphrases are printable ASCII, and control characters
+        // would start new lines in the profiles
+        if psk.chars().any(char::is_control) {
+            return Err(AliError::BadHookCmd(format!(
+                "{KEY_QUICKNET}: psk has control characters"
+            )));
+        }
+
+        Ok(psk)
+    }
+}
+
+impl Wireless {
+    fn encode_file(&self, interface: &str) -> Result<QuickNetFile, AliError> {
+        let psk = self.psk.resolve()?;
+        match self.backend {
+            WirelessBackend::Iwd => {
+                let filename =
+                    FILENAME_IWD_TPL.replace(TOKEN_SSID, &iwd_ssid(&self.ssid));
+
+                Ok(QuickNetFile {
+                    path: format!("{DIR_IWD}/{filename}"),
+                    content: IWD_PSK.replace(TOKEN_PSK, &psk),
+                    secret: true,
+                })
+            }
+            WirelessBackend::WpaSupplicant => {
+                Ok(QuickNetFile {
+                    path: FILENAME_WPA_SUPPLICANT_TPL
+                        .replace(TOKEN_INTERFACE, interface),
+                    // Quoted psk is read up to its last quote,
+                    // and has no escapes
+                    content: WPA_SUPPLICANT
+                        .replace(TOKEN_SSID, &wpa_ssid(&self.ssid))
+                        .replace(TOKEN_PSK, &psk),
+                    secret: true,
+                })
+            }
+        }
+    }
+}
+
+/// Returns wpa_supplicant `ssid` value: quoted, or printf-escaped
+/// in `P"<SSID>"` form if it has `"`, `\`, or ASCII control characters
+fn wpa_ssid(ssid: &str) -> String {
+    let plain = !ssid
+        .chars()
+        .any(|c| matches!(c, '"' | '\\') || c.is_ascii_control());
+
+    if plain {
+        return format!("\"{ssid}\"");
+    }
+
+    let escaped: String = ssid
+        .chars()
+        .map(|c| {
+            match c {
+                '"' => "\\\"".to_string(),
+                '\\' => "\\\\".to_string(),
+                c if c.is_ascii_control() => format!("\\x{:02x}", c as u8),
+                c => c.to_string(),
+            }
+        })
+        .collect();
+
+    format!("P\"{escaped}\"")
+}
+
+/// Returns `value` escaped for GLib keyfiles of NetworkManager
+fn keyfile_escape(value: &str) -> String {
+    let escaped = value
+        .replace('\\', "\\\\")
+        .replace('\n', "\\n")
+        .replace('\t', "\\t")
+        .replace('\r', "\\r");
+
+    // Leading spaces are otherwise trimmed
+    match escaped.strip_prefix(' ') {
+        Some(rest) => format!("\\s{rest}"),
+        None => escaped,
+    }
+}
+
+/// Returns iwd profile name for `ssid`. SSIDs with characters other than
+/// alphanumerics, ' ', '_' and '-' are hex-encoded and prefixed with '='.
+fn iwd_ssid(ssid: &str) -> String {
+    let plain = ssid
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || matches!(c, ' ' | '_' | '-'));
+
+    if plain {
+        return ssid.to_string();
+    }
+
+    let hex: String = ssid.bytes().map(|b| format!("{b:02x}")).collect();
+    format!("={hex}")
 }
 
 #[test]
@@ -233,6 +986,10 @@ fn test_parse_quicknet() {
             QuickNet {
                 interface: "eth0".into(),
                 dns_upstream: None,
+                backend: Backend::Networkd,
+                wireless: None,
+                topology: None,
+                ipv6: None,
             },
         ),
         (
@@ -240,6 +997,10 @@ fn test_parse_quicknet() {
             QuickNet {
                 interface: "inf".into(),
                 dns_upstream: None,
+                backend: Backend::Networkd,
+                wireless: None,
+                topology: None,
+                ipv6: None,
             },
         ),
         (
@@ -247,6 +1008,10 @@ fn test_parse_quicknet() {
             QuickNet {
                 interface: "eth0".into(),
                 dns_upstream: Some("1.1.1.1".into()),
+                backend: Backend::Networkd,
+                wireless: None,
+                topology: None,
+                ipv6: None,
             },
         ),
         (
@@ -254,12 +1019,108 @@ fn test_parse_quicknet() {
             QuickNet {
                 interface: "eth0".into(),
                 dns_upstream: Some("1.1.1.1".into()),
+                backend: Backend::Networkd,
+                wireless: None,
+                topology: None,
+                ipv6: None,
+            },
+        ),
+        (
+            "@quicknet wlan0 ssid=home psk=secret",
+            QuickNet {
+                interface: "wlan0".into(),
+                dns_upstream: None,
+                backend: Backend::Networkd,
+                wireless: Some(Wireless {
+                    ssid: "home".into(),
+                    psk: Psk::Inline("secret".into()),
+                    backend: WirelessBackend::Iwd,
+                }),
+                topology: None,
+                ipv6: None,
+            },
+        ),
+        (
+            "@quicknet wlan0 'ssid=my home' psk_file=/root/psk wireless=wpa_supplicant dns 1.1.1.1",
+            QuickNet {
+                interface: "wlan0".into(),
+                dns_upstream: Some("1.1.1.1".into()),
+                backend: Backend::Networkd,
+                wireless: Some(Wireless {
+                    ssid: "my home".into(),
+                    psk: Psk::File("/root/psk".into()),
+                    backend: WirelessBackend::WpaSupplicant,
+                }),
+                topology: None,
+                ipv6: None,
+            },
+        ),
+        (
+            "@quicknet eth0 vlan=10",
+            QuickNet {
+                interface: "eth0".into(),
+                dns_upstream: None,
+                backend: Backend::Networkd,
+                wireless: None,
+                topology: Some(Topology::Vlan(10)),
+                ipv6: None,
+            },
+        ),
+        (
+            "@quicknet eth0 bridge=br0 dns 1.1.1.1",
+            QuickNet {
+                interface: "eth0".into(),
+                dns_upstream: Some("1.1.1.1".into()),
+                backend: Backend::Networkd,
+                wireless: None,
+                topology: Some(Topology::Bridge("br0".into())),
+                ipv6: None,
+            },
+        ),
+        (
+            "@quicknet eth0,eth1 bond=bond0 mode=active-backup",
+            QuickNet {
+                interface: "eth0,eth1".into(),
+                dns_upstream: None,
+                backend: Backend::Networkd,
+                wireless: None,
+                topology: Some(Topology::Bond {
+                    name: "bond0".into(),
+                    mode: Some("active-backup".into()),
+                }),
+                ipv6: None,
             },
         ),
     ];
 
-    let should_err =
-        vec!["@quicknet", "@quicknet dns", "@quicknet eth0 1.1.1.1 dns"];
+    let should_err = vec![
+        "@quicknet",
+        "@quicknet dns",
+        "@quicknet eth0 1.1.1.1 dns",
+        "@quicknet wlan0 ssid=home",
+        "@quicknet wlan0 psk=secret",
+        "@quicknet wlan0 ssid=home psk=secret psk_file=/root/psk",
+        "@quicknet wlan0 ssid=home psk=secret wireless=connman",
+        "@quicknet wlan0 ssid=home psk=secret foo=bar",
+        "@quicknet eth0 backend=connman",
+        "@quicknet wlan0 backend=nm ssid=home psk=secret wireless=iwd",
+        "@quicknet eth0 vlan=0",
+        "@quicknet eth0 vlan=4095",
+        "@quicknet eth0 vlan=foo",
+        "@quicknet eth0 vlan=10 bridge=br0",
+        "@quicknet eth0 mode=active-backup",
+        "@quicknet eth0 bond=bond0 mode=foo",
+        "@quicknet eth0,eth1 bridge=br0",
+        "@quicknet eth0, bond=bond0",
+        "@quicknet eth0 backend=nm vlan=10",
+        "@quicknet wlan0 ssid=home psk=secret bridge=br0",
+        "@quicknet eth0 ipv6=on",
+        "@quicknet eth0 ipv6=static",
+        "@quicknet eth0 ipv6=static 10.0.0.1/24",
+        "@quicknet eth0 ipv6=static 2001:db8::10",
+        "@quicknet eth0 ipv6=static 2001:db8::10/129",
+        "@quicknet eth0 ipv6=off ipv6=static 2001:db8::10/64",
+    ];
 
     for (cmd, expected_qn) in should_pass {
         let hook_result = HookQuickNet::try_from(cmd);
@@ -274,10 +1135,7 @@ fn test_parse_quicknet() {
     for cmd in &should_err {
         let hook_result = HookQuickNet::try_from(*cmd);
         if let Ok(hook) = hook_result {
-            panic!(
-                "unexpected ok result from bad arg {cmd}: {}",
-                hook.qn.to_string()
-            );
+            panic!("unexpected ok result from bad arg {cmd}: {}", hook.qn);
         }
     }
 }
@@ -332,3 +1190,245 @@ DNS=8.8.8.8
         assert_eq!(expected, s);
     }
 }
+
+#[test]
+fn test_quicknet_encode_wireless() {
+    let hook =
+        HookQuickNet::try_from("@quicknet wlan0 ssid=home psk=secret").unwrap();
+    let files = hook.qn.encode_files().unwrap();
+
+    assert_eq!(2, files.len());
+    assert_eq!(
+        "/etc/systemd/network/00-dhcp_wlan0-quicknet.network",
+        files[0].path
+    );
+    assert_eq!("/var/lib/iwd/home.psk", files[1].path);
+    assert!(files[1].secret);
+    assert_eq!(
+        r#"# Installed by ali-rs hook @quicknet
+[Security]
+Passphrase=secret
+"#,
+        files[1].content
+    );
+
+    let hook = HookQuickNet::try_from(
+        "@quicknet wlan0 ssid=home psk=secret wireless=wpa_supplicant",
+    )
+    .unwrap();
+    let files = hook.qn.encode_files().unwrap();
+
+    assert_eq!(
+        "/etc/wpa_supplicant/wpa_supplicant-wlan0.conf",
+        files[1].path
+    );
+    assert!(files[1].content.contains("ssid=\"home\""));
+    assert!(files[1].content.contains("psk=\"secret\""));
+    assert!(!hook.qn.to_string().contains("secret"));
+    assert_eq!(
+        vec![
+            "systemd-networkd.service".to_string(),
+            "wpa_supplicant@wlan0.service".to_string(),
+        ],
+        hook.qn.services(),
+    );
+}
+
+#[test]
+fn test_quicknet_encode_networkmanager() {
+    let hook = HookQuickNet::try_from(
+        "@quicknet eth0 backend=networkmanager dns 1.1.1.1",
+    )
+    .unwrap();
+    let files = hook.qn.encode_files().unwrap();
+
+    assert_eq!(1, files.len());
+    assert_eq!(
+        "/etc/NetworkManager/system-connections/quicknet-eth0.nmconnection",
+        files[0].path
+    );
+    assert_eq!(
+        r#"# Installed by ali-rs hook @quicknet
+[connection]
+id=quicknet-eth0
+type=ethernet
+interface-name=eth0
+
+[ipv4]
+method=auto
+dns=1.1.1.1;
+
+[ipv6]
+method=auto
+"#,
+        files[0].content
+    );
+    assert_eq!(
+        vec!["NetworkManager.service".to_string()],
+        hook.qn.services()
+    );
+
+    let hook = HookQuickNet::try_from(
+        "@quicknet wlan0 backend=nm ssid=home psk=secret",
+    )
+    .unwrap();
+    let files = hook.qn.encode_files().unwrap();
+
+    assert_eq!(1, files.len());
+    assert!(files[0].secret);
+    assert!(files[0].content.contains("type=wifi"));
+    assert!(files[0].content.contains("ssid=home"));
+    assert!(files[0].content.contains("psk=secret"));
+}
+
+#[test]
+fn test_iwd_ssid() {
+    assert_eq!("home", iwd_ssid("home"));
+    assert_eq!("my home-5G_x", iwd_ssid("my home-5G_x"));
+    assert_eq!("=6361666521", iwd_ssid("cafe!"));
+}
+
+#[test]
+fn test_quicknet_encode_topology() {
+    let hook = HookQuickNet::try_from("@quicknet eth0 vlan=10").unwrap();
+    let files = hook.qn.encode_files().unwrap();
+
+    assert_eq!(3, files.len());
+    assert_eq!(
+        "/etc/systemd/network/00-dhcp_eth0.10-quicknet.network",
+        files[0].path
+    );
+    assert!(files[0].content.contains("Name=eth0.10\n"));
+    assert_eq!(
+        "/etc/systemd/network/00-eth0.10-quicknet.netdev",
+        files[1].path
+    );
+    assert_eq!(
+        r#"# Installed by ali-rs hook @quicknet
+[NetDev]
+Name=eth0.10
+Kind=vlan
+
+[VLAN]
+Id=10
+"#,
+        files[1].content
+    );
+    assert_eq!(
+        "/etc/systemd/network/10-member_eth0-quicknet.network",
+        files[2].path
+    );
+    assert_eq!(
+        r#"# Installed by ali-rs hook @quicknet
+[Match]
+Name=eth0
+
+[Network]
+VLAN=eth0.10
+"#,
+        files[2].content
+    );
+
+    let hook = HookQuickNet::try_from(
+        "@quicknet eth0,eth1 bond=bond0 mode=active-backup",
+    )
+    .unwrap();
+    let files = hook.qn.encode_files().unwrap();
+
+    assert_eq!(4, files.len());
+    assert!(files[0].content.contains("Name=bond0\n"));
+    assert!(files[1].content.contains("Kind=bond\n"));
+    assert!(files[1].content.contains("Mode=active-backup\n"));
+    assert!(files[2].content.contains("Name=eth0\n"));
+    assert!(files[2].content.contains("Bond=bond0\n"));
+    assert!(files[3].content.contains("Name=eth1\n"));
+    assert!(files[3].content.contains("Bond=bond0\n"));
+
+    let hook = HookQuickNet::try_from("@quicknet eth0 bridge=br0").unwrap();
+    let files = hook.qn.encode_files().unwrap();
+
+    assert_eq!(3, files.len());
+    assert!(files[1].content.contains("Kind=bridge\n"));
+    assert!(!files[1].content.contains("[Bond]"));
+    assert!(files[2].content.contains("Bridge=br0\n"));
+}
+
+#[test]
+fn test_quicknet_ipv6() {
+    let tests = [
+        ("@quicknet eth0 ipv6=auto", Ipv6::Auto),
+        ("@quicknet eth0 ipv6=off", Ipv6::Off),
+        (
+            "@quicknet eth0 ipv6=static 2001:db8::10/64 dns 1.1.1.1",
+            Ipv6::Static("2001:db8::10/64".into()),
+        ),
+        (
+            "@quicknet eth0 'ipv6=static 2001:db8::10/64'",
+            Ipv6::Static("2001:db8::10/64".into()),
+        ),
+        (
+            "@quicknet eth0 'ipv6=static' 2001:db8::10/64",
+            Ipv6::Static("2001:db8::10/64".into()),
+        ),
+    ];
+
+    for (cmd, expected) in tests {
+        let hook = HookQuickNet::try_from(cmd).unwrap();
+        assert_eq!(Some(expected), hook.qn.ipv6);
+    }
+
+    let hook =
+        HookQuickNet::try_from("@quicknet eth0 ipv6=static 2001:db8::10/64")
+            .unwrap();
+    assert_eq!(
+        r#"# Installed by ali-rs hook @quicknet
+[Match]
+Name=eth0
+
+[Network]
+DHCP=ipv4
+IPv6AcceptRA=no
+Address=2001:db8::10/64
+"#,
+        hook.qn.encode_to_string()
+    );
+
+    let hook =
+        HookQuickNet::try_from("@quicknet eth0 vlan=10 ipv6=off").unwrap();
+    let files = hook.qn.encode_files().unwrap();
+    let sysctl = files.last().unwrap();
+
+    assert!(files[0].content.contains("LinkLocalAddressing=ipv4\n"));
+    assert_eq!("/etc/sysctl.d/40-quicknet-ipv6_eth0.10.conf", sysctl.path);
+    assert!(sysctl
+        .content
+        .contains("net.ipv6.conf.eth0/10.disable_ipv6 = 1\n"));
+
+    let hook =
+        HookQuickNet::try_from("@quicknet eth0 backend=nm ipv6=off").unwrap();
+    let files = hook.qn.encode_files().unwrap();
+    assert!(files[0].content.contains("[ipv6]\nmethod=disabled\n"));
+}
+
+#[test]
+fn test_quicknet_escape() {
+    assert_eq!("\"my home\"", wpa_ssid("my home"));
+    assert_eq!(r#"P"say \"hi\" \\ \x0a""#, wpa_ssid("say \"hi\" \\ \n"));
+
+    assert_eq!(r"a\\b\n", keyfile_escape("a\\b\n"));
+    assert_eq!(r"\s home", keyfile_escape("  home"));
+
+    let hook = HookQuickNet::try_from(
+        r#"@quicknet wlan0 'ssid=a"b' psk=quicknet-test-psk wireless=wpa_supplicant"#,
+    )
+    .unwrap();
+    let files = hook.qn.encode_files().unwrap();
+    assert!(files[1].content.contains(r#"ssid=P"a\"b""#));
+
+    // Inline PSKs are redacted in print mode
+    assert!(files[1].content.contains("quicknet-test-psk"));
+    assert!(!secrets::redact(&files[1].content).contains("quicknet-test-psk"));
+
+    // Line breaks would inject profile lines
+    assert!(Psk::Inline("secret\n[Settings]".into()).resolve().is_err());
+}
@@ -0,0 +1,253 @@
+use std::collections::HashMap;
+use std::sync::{
+    OnceLock,
+    RwLock,
+};
+
+use super::*;
+
+/// Parses hook command `cmd` with hook key `k` into a [`Hook`]
+pub type HookConstructor =
+    fn(k: &str, cmd: &str) -> Result<Box<dyn Hook>, ParseError>;
+
+static REGISTRY: OnceLock<RwLock<HookRegistry>> = OnceLock::new();
+
+/// Maps hook keys, and hook key prefixes, to hook constructors,
+/// and hook keys to keyword arguments and example arguments of the hooks
+pub(crate) struct HookRegistry {
+    keys: HashMap<&'static str, HookConstructor>,
+    prefixes: Vec<(&'static str, HookConstructor)>,
+    keywords: HashMap<&'static str, &'static [&'static str]>,
+    examples: HashMap<&'static str, &'static str>,
+}
+
+impl HookRegistry {
+    /// Registry with no hooks
+    fn empty() -> Self {
+        Self {
+            keys: HashMap::new(),
+            prefixes: Vec::new(),
+            keywords: HashMap::new(),
+            examples: HashMap::new(),
+        }
+    }
+
+    /// Registers `keys` to `constructor`, replacing existing registrations
+    pub(crate) fn register(
+        &mut self,
+        keys: &[&'static str],
+        constructor: HookConstructor,
+    ) {
+        for key in keys {
+            self.keys.insert(key, constructor);
+        }
+    }
+
+    /// Registers all hook keys starting with `prefix` to `constructor`.
+    /// Exact keys take precedence over prefixes.
+    pub(crate) fn register_prefix(
+        &mut self,
+        prefix: &'static str,
+        constructor: HookConstructor,
+    ) {
+        self.prefixes.retain(|(p, _)| *p != prefix);
+        self.prefixes.push((prefix, constructor));
+    }
+
+    /// Registers keyword arguments of hook `keys`,
+    /// e.g. `marker` or `boot_hook=`, for shell completion
+    pub(crate) fn register_keywords(
+        &mut self,
+        keys: &[&'static str],
+        keywords: &'static [&'static str],
+    ) {
+        for key in keys {
+            self.keywords.insert(key, keywords);
+        }
+    }
+
+    /// Returns keyword arguments of hook key `k`
+    pub(crate) fn keywords(&self, k: &str) -> &'static [&'static str] {
+        self.keywords.get(k).copied().unwrap_or_default()
+    }
+
+    /// Registers example arguments of hook `keys`, e.g. `eth0`
+    /// for `@quicknet eth0`
+    pub(crate) fn register_example(
+        &mut self,
+        keys: &[&'static str],
+        args: &'static str,
+    ) {
+        for key in keys {
+            self.examples.insert(key, args);
+        }
+    }
+
+    /// Returns example hook command of hook key `k`
+    pub(crate) fn example(&self, k: &str) -> Option<String> {
+        self.examples.get(k).map(|args| format!("{k} {args}"))
+    }
+
+    /// Returns constructor for hook key `k`
+    pub(crate) fn get(&self, k: &str) -> Option<HookConstructor> {
+        self.keys.get(k).copied().or_else(|| {
+            self.prefixes
+                .iter()
+                .find(|(prefix, _)| k.starts_with(prefix))
+                .map(|(_, constructor)| *constructor)
+        })
+    }
+
+    /// Registered hook keys (without prefixes), sorted
+    pub(crate) fn keys(&self) -> Vec<&'static str> {
+        let mut keys: Vec<_> = self.keys.keys().copied().collect();
+        keys.sort();
+
+        keys
+    }
+}
+
+impl Default for HookRegistry {
+    /// Registry with all ali-rs hooks
+    fn default() -> Self {
+        let mut registry = Self::empty();
+
+        for key in HookKey::ALL {
+            let (constructor, keywords, example) = builtin(key);
+            let keys = &[key.as_str()];
+
+            registry.register(keys, constructor);
+            registry.register_keywords(keys, keywords);
+            registry.register_example(keys, example);
+        }
+
+        registry.register_prefix(KEY_PREFIX_PLUGIN, plugin::parse);
+
+        registry
+    }
+}
+
+/// Returns constructor, keyword arguments,
+/// and example arguments of ali-rs hook `key`
+fn builtin(
+    key: &HookKey,
+) -> (HookConstructor, &'static [&'static str], &'static str) {
+    match key {
+        HookKey::WrapperMnt => (wrappers::parse, &[], wrappers::EXAMPLE_MNT),
+        HookKey::WrapperNoMnt => {
+            (wrappers::parse, &[], wrappers::EXAMPLE_NO_MNT)
+        }
+        HookKey::If => {
+            (conditional::parse, conditional::KEYWORDS, conditional::EXAMPLE)
+        }
+        HookKey::Unprotect => (unprotect::parse, &[], unprotect::EXAMPLE),
+        HookKey::QuickNet | HookKey::QuickNetPrint => {
+            (quicknet::parse, quicknet::KEYWORDS, quicknet::EXAMPLE)
+        }
+        HookKey::Mkinitcpio | HookKey::MkinitcpioPrint => {
+            (mkinitcpio::parse, mkinitcpio::KEYWORDS, mkinitcpio::EXAMPLE)
+        }
+        HookKey::Uncomment
+        | HookKey::UncommentPrint
+        | HookKey::UncommentAll
+        | HookKey::UncommentAllPrint => {
+            (uncomment::parse, uncomment::KEYWORDS, uncomment::EXAMPLE)
+        }
+        HookKey::ReplaceToken | HookKey::ReplaceTokenPrint => {
+            (
+                replace_token::parse,
+                replace_token::KEYWORDS,
+                replace_token::EXAMPLE,
+            )
+        }
+        HookKey::Download | HookKey::DownloadPrint => {
+            (download::parse, download::KEYWORDS, download::EXAMPLE)
+        }
+        HookKey::Backup | HookKey::BackupPrint => {
+            (backup::parse, backup::KEYWORDS, backup::EXAMPLE)
+        }
+        HookKey::Rollback | HookKey::RollbackPrint => {
+            (rollback::parse, rollback::KEYWORDS, rollback::EXAMPLE)
+        }
+        HookKey::WireGuard | HookKey::WireGuardPrint => {
+            (wireguard::parse, wireguard::KEYWORDS, wireguard::EXAMPLE)
+        }
+        HookKey::Tailscale | HookKey::TailscalePrint => {
+            (tailscale::parse, tailscale::KEYWORDS, tailscale::EXAMPLE)
+        }
+        HookKey::Pam | HookKey::PamPrint => {
+            (pam::parse, pam::KEYWORDS, pam::EXAMPLE)
+        }
+        HookKey::Sssd | HookKey::SssdPrint => {
+            (sssd::parse, sssd::KEYWORDS, sssd::EXAMPLE)
+        }
+        HookKey::AssertFileContains => {
+            (assertion::parse, &[], assertion::EXAMPLE_FILE_CONTAINS)
+        }
+        HookKey::AssertServiceEnabled => {
+            (
+                assertion::parse,
+                assertion::KEYWORDS_SERVICE_ENABLED,
+                assertion::EXAMPLE_SERVICE_ENABLED,
+            )
+        }
+        HookKey::AssertMounted => {
+            (assertion::parse, &[], assertion::EXAMPLE_MOUNTED)
+        }
+    }
+}
+
+/// Global registry, initialized with all ali-rs hooks
+pub(crate) fn global() -> &'static RwLock<HookRegistry> {
+    REGISTRY.get_or_init(|| RwLock::new(HookRegistry::default()))
+}
+
+#[test]
+fn test_registry() {
+    let mut registry = HookRegistry::default();
+
+    assert!(registry.get(KEY_QUICKNET).is_some());
+    assert!(registry.get(KEY_UNCOMMENT_ALL_PRINT).is_some());
+    assert!(registry.get("@x-foo").is_some());
+    assert!(registry.get("@foo").is_none());
+    assert!(registry.keys().contains(&KEY_MKINITCPIO));
+    assert!(!registry.keys().contains(&KEY_PREFIX_PLUGIN));
+    assert!(HookKey::ALL
+        .iter()
+        .all(|key| registry.keys().contains(&key.as_str())));
+    assert_eq!(
+        &["marker", "backup"],
+        registry.keywords(KEY_UNCOMMENT_ALL_PRINT),
+    );
+    assert_eq!(
+        &["env_file=", "backup"],
+        registry.keywords(KEY_REPLACE_TOKEN),
+    );
+    assert!(registry.keywords(KEY_WRAPPER_MNT).is_empty());
+    assert_eq!(
+        Some("@rollback-print grub snapper".to_string()),
+        registry.example(KEY_ROLLBACK_PRINT),
+    );
+
+    fn parse_foo(_k: &str, _cmd: &str) -> Result<Box<dyn Hook>, ParseError> {
+        Err(wrap_bad_hook_cmd(
+            AliError::BadHookCmd("foo".to_string()),
+            "foo usage",
+        ))
+    }
+
+    fn help_msg(registry: &HookRegistry, k: &str) -> String {
+        match registry.get(k).unwrap()(k, k) {
+            Err(err) => err.help_msg,
+            Ok(_) => panic!("unexpected ok for {k}"),
+        }
+    }
+
+    registry.register(&["@foo", "@foo-print"], parse_foo);
+    assert_eq!("foo usage", help_msg(&registry, "@foo-print"));
+
+    // Exact keys take precedence over prefixes
+    registry.register(&["@x-foo"], parse_foo);
+    assert_eq!("foo usage", help_msg(&registry, "@x-foo"));
+    assert!(registry.get("@x-bar").is_some());
+}
@@ -1,6 +1,17 @@
+use std::sync::LazyLock;
+
+use serde_json::json;
+
+use super::args::{
+    self,
+    Arg,
+};
 use super::utils::{
     self,
     download,
+    file_backup,
+    output,
+    protected,
 };
 use super::{
     wrap_bad_hook_cmd,
@@ -13,22 +24,57 @@ use super::{
     KEY_REPLACE_TOKEN_PRINT,
 };
 use crate::errors::AliError;
+use crate::utils::fs::path_under;
+use crate::utils::{
+    json,
+    secrets,
+};
+
+const ARGS: &[Arg] = &[
+    Arg::positional("token", "TOKEN"),
+    Arg::positional("value", "VALUE"),
+    Arg::positional("template", "TEMPLATE"),
+    Arg::positional_optional("output", "OUTPUT"),
+    Arg::flag("backup"),
+];
 
-const USAGE: &str = "<TOKEN> <VALUE> <TEMPLATE> [OUTPUT]";
+/// Multi-pair form, see [`parse_pairs`]
+const USAGE_PAIRS: &str =
+    "<TOKEN=VALUE | env_file=FILE> [TOKEN=VALUE..] <TEMPLATE> [OUTPUT] [backup]";
+
+/// Key of mapping file in multi-pair form
+const KEY_ENV_FILE: &str = "env_file";
+
+static USAGE: LazyLock<String> =
+    LazyLock::new(|| format!("{} | {USAGE_PAIRS}", args::usage(ARGS)));
+
+/// Example arguments, for `ali-rs hooks schema`
+pub(super) const EXAMPLE: &str = "PORT 2222 /etc/ssh/sshd_config";
+
+/// Keyword arguments, for shell completion
+pub(super) const KEYWORDS: &[&str] = &["env_file=", "backup"];
 
 #[derive(Debug, PartialEq)]
 struct HookReplaceToken {
     mode_hook: ModeHook,
     output: String,
-    rp: utils::ReplaceToken,
+    /// Tokens that must all be in template
+    rps: Vec<utils::ReplaceToken>,
+    /// `.env`-style file of tokens replaced if found in template
+    env_file: Option<String>,
     template: String,
+    /// Whether output is template itself, which is then read
+    /// from the root location
+    in_place: bool,
+    /// Backs up output before writing
+    backup: bool,
 }
 
 pub(super) fn parse(k: &str, cmd: &str) -> Result<Box<dyn Hook>, ParseError> {
     match k {
         KEY_REPLACE_TOKEN | KEY_REPLACE_TOKEN_PRINT => {
             match HookReplaceToken::try_from(cmd) {
-                Err(err) => Err(wrap_bad_hook_cmd(err, USAGE)),
+                Err(err) => Err(wrap_bad_hook_cmd(err, &USAGE)),
                 Ok(hook) => Ok(Box::new(hook)),
             }
         }
@@ -43,7 +89,7 @@ impl Hook for HookReplaceToken {
     }
 
     fn usage(&self) -> &'static str {
-        "<TOKEN> <VALUE> <TEMPLATE> [OUTPUT]"
+        "<TOKEN> <VALUE> <TEMPLATE> [OUTPUT] [backup] | <TOKEN=VALUE | env_file=FILE> [TOKEN=VALUE..] <TEMPLATE> [OUTPUT] [backup]"
     }
 
     fn mode(&self) -> ModeHook {
@@ -67,27 +113,34 @@ impl Hook for HookReplaceToken {
         _caller: &Caller,
         root_location: &str,
     ) -> Result<ActionHook, AliError> {
-        apply_replace_token(
-            &self.hook_key(),
-            &self.mode_hook,
-            &self.rp,
-            root_location,
-            &self.template,
-            &self.output,
-        )
+        apply_replace_token(&self.hook_key(), self, root_location)
     }
 }
 
 /// Synopsis
 ///
 /// ```txt
-/// @replace-token <TOKEN> <VALUE> <TEMPLATE> [OUTPUT]`
+/// @replace-token <TOKEN> <VALUE> <TEMPLATE> [OUTPUT] [backup]`
+///
+/// @replace-token <TOKEN=VALUE | env_file=FILE> [TOKEN=VALUE..] <TEMPLATE> [OUTPUT] [backup]`
 /// ```
 ///
 /// TOKEN must exist in TEMPLATE file, as {{ TOKEN }},
 /// e.g. TOKEN=foo, then there exists {{ foo }} in TEMPLATE file
 ///
-/// If OUTPUT is not given, output is written to TEMPLATE file
+/// The second form replaces multiple tokens in one go, and is used when
+/// the first argument is `TOKEN=VALUE`. Tokens from `.env`-style FILE
+/// on the live system are only replaced if found in TEMPLATE, and are
+/// overridden by pairs given on the command line.
+///
+/// If OUTPUT is not given, TEMPLATE in the root location
+/// is templated in place
+///
+/// With flag `backup`, OUTPUT is backed up before it is written,
+/// and can be restored with `ali-rs hooks undo`
+///
+/// VALUE can be a secret reference `secret://<NAME>`, resolved when
+/// the hook runs
 ///
 /// Examples:
 /// ```txt
@@ -110,73 +163,233 @@ impl TryFrom<&str> for HookReplaceToken {
             }
         };
 
-        if parts.len() < 3 {
-            return Err(AliError::BadHookCmd(format!(
-                "{hook_key}: expect at least 2 arguments"
-            )));
-        }
+        let is_pair = |arg: &String| {
+            arg.split_once('=').is_some_and(|(token, _)| is_token(token))
+        };
 
-        let l = parts.len();
-        if l != 4 && l != 5 {
-            return Err(AliError::BadHookCmd(format!(
-                "{hook_key}: bad cmd parts (expecting 3-4): {l}"
-            )));
+        if parts.get(1).is_some_and(is_pair) {
+            return parse_pairs(&hook_key, mode_hook, &parts[1..]);
         }
 
-        let (token, value, template) =
-            (parts[1].clone(), parts[2].clone(), parts[3].clone());
+        let args = args::parse(&hook_key, &parts[1..], ARGS)?;
+
+        let token = args.required("token")?.to_string();
+        let value = args.required("value")?.to_string();
+        let template = args.required("template")?.to_string();
 
         // If not given, then use template as output
-        let output = parts
-            .last()
-            .map(|s| s.to_owned())
-            .unwrap_or(template.clone());
+        let output = args.get("output").unwrap_or(&template).to_string();
 
         Ok(HookReplaceToken {
             mode_hook,
+            in_place: args.get("output").is_none(),
             template,
             output,
-            rp: utils::ReplaceToken { token, value },
+            rps: vec![utils::ReplaceToken { token, value }],
+            env_file: None,
+            backup: args.flag("backup"),
         })
     }
 }
 
+/// Parses multi-pair form `<TOKEN=VALUE | env_file=FILE>.. <TEMPLATE> [OUTPUT]`
+fn parse_pairs(
+    hook_key: &str,
+    mode_hook: ModeHook,
+    args: &[String],
+) -> Result<HookReplaceToken, AliError> {
+    let bad = |msg: String| AliError::BadHookCmd(format!("{hook_key}: {msg}"));
+
+    let mut rps: Vec<utils::ReplaceToken> = Vec::new();
+    let mut env_file = None;
+    let mut backup = false;
+    let mut rest = Vec::new();
+
+    for arg in args {
+        if arg == "backup" {
+            if backup {
+                return Err(bad("duplicate flag backup".to_string()));
+            }
+
+            backup = true;
+            continue;
+        }
+
+        let pair = arg
+            .split_once('=')
+            .filter(|(token, _)| rest.is_empty() && is_token(token));
+
+        let Some((token, value)) = pair else {
+            rest.push(arg.clone());
+            continue;
+        };
+
+        if token == KEY_ENV_FILE {
+            if env_file.replace(value.to_string()).is_some() {
+                return Err(bad(format!("duplicate {KEY_ENV_FILE}")));
+            }
+
+            continue;
+        }
+
+        if rps.iter().any(|rp| rp.token == token) {
+            return Err(bad(format!("duplicate token {token}")));
+        }
+
+        rps.push(utils::ReplaceToken {
+            token: token.to_string(),
+            value: value.to_string(),
+        });
+    }
+
+    let (template, output) = match rest.as_slice() {
+        [template] => (template.clone(), None),
+        [template, output] => (template.clone(), Some(output.clone())),
+        [] => return Err(bad("missing template".to_string())),
+        _ => {
+            return Err(bad(format!(
+                "expecting <TEMPLATE> [OUTPUT] after pairs, got {}",
+                rest.join(" ")
+            )));
+        }
+    };
+
+    Ok(HookReplaceToken {
+        mode_hook,
+        in_place: output.is_none(),
+        output: output.unwrap_or(template.clone()),
+        template,
+        rps,
+        env_file,
+        backup,
+    })
+}
+
+/// Returns whether `s` can be a token in multi-pair form
+fn is_token(s: &str) -> bool {
+    !s.is_empty()
+        && s
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "_-.".contains(c))
+}
+
+/// Parses `.env`-style `s` into pairs, skipping blank lines and comments.
+/// Values may be quoted, and lines may start with `export`.
+fn parse_env(s: &str) -> Result<Vec<utils::ReplaceToken>, String> {
+    let mut rps = Vec::new();
+
+    for (i, line) in s.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        let Some((token, value)) = line.split_once('=') else {
+            return Err(format!("line {}: expecting TOKEN=VALUE", i + 1));
+        };
+
+        let token = token.trim();
+        if !is_token(token) {
+            return Err(format!("line {}: bad token {token}", i + 1));
+        }
+
+        let value = value.trim();
+        let value = ['"', '\'']
+            .iter()
+            .find_map(|q| {
+                value
+                    .strip_prefix(*q)
+                    .and_then(|v| v.strip_suffix(*q))
+            })
+            .unwrap_or(value);
+
+        rps.push(utils::ReplaceToken {
+            token: token.to_string(),
+            value: value.to_string(),
+        });
+    }
+
+    Ok(rps)
+}
+
 fn apply_replace_token(
     hook_key: &str,
-    mode_hook: &ModeHook,
-    r: &utils::ReplaceToken,
+    hook: &HookReplaceToken,
     root_location: &str,
-    template: &str,
-    output: &str,
 ) -> Result<ActionHook, AliError> {
+    let template = &hook.template;
     let template_string =
         // If the template is a valid remote URL, download it
         if let Ok(downloader) = download::Downloader::new_from_url(template) {
             downloader.get_string()
 
-        // Otherwise read from file
+        // Otherwise read from file, which is in root location if in-place
         } else {
-            std::fs::read_to_string(template).map_err(|err| {
+            let path = match (hook.in_place, root_location) {
+                (false, _) | (true, "/") => template.to_string(),
+                (true, _) => path_under(root_location, template)?,
+            };
+
+            std::fs::read_to_string(&path).map_err(|err| {
                 AliError::HookError(format!(
-                    "{hook_key}: read template {}: {err}",
-                    template
+                    "{hook_key}: read template {path}: {err}",
                 ))
             })
         }?;
 
-    let replaced = r.replace(&template_string)?;
+    let mut replaced = template_string;
+
+    // Pairs from command line override those from env file
+    if let Some(ref env_file) = hook.env_file {
+        let env = std::fs::read_to_string(env_file).map_err(|err| {
+            AliError::FileError(err, format!("{hook_key}: read {env_file}"))
+        })?;
+        let env = parse_env(&env).map_err(|err| {
+            AliError::HookError(format!("{hook_key}: {env_file}: {err}"))
+        })?;
+
+        let overridden = |rp: &&utils::ReplaceToken| {
+            hook.rps.iter().any(|r| r.token == rp.token)
+        };
+        for rp in env.iter().filter(|rp| !overridden(rp)) {
+            let resolved = utils::ReplaceToken {
+                token: rp.token.clone(),
+                value: secrets::resolve(&rp.value)?,
+            };
+
+            if let Ok(s) = resolved.replace(&replaced) {
+                replaced = s;
+            }
+        }
+    }
+
+    // Secret values are only resolved here, so they never show in reports
+    for rp in &hook.rps {
+        let resolved = utils::ReplaceToken {
+            token: rp.token.clone(),
+            value: secrets::resolve(&rp.value)?,
+        };
+        replaced = resolved.replace(&replaced)?;
+    }
 
-    match mode_hook {
+    match hook.mode_hook {
         ModeHook::Print => {
-            println!("{replaced}")
+            output::println(&secrets::redact(&replaced))
         }
 
         ModeHook::Normal => {
+            let output = &hook.output;
             let output_location = match root_location {
                 "/" => output.to_string(),
-                _ => format!("/{root_location}/{output}"),
+                _ => path_under(root_location, output)?,
             };
 
+            protected::check(hook_key, root_location, &output_location)?;
+            if file_backup::enabled(hook.backup) {
+                file_backup::save(hook_key, root_location, &output_location)?;
+            }
+
             std::fs::write(output_location, replaced).map_err(|err| {
                 AliError::HookError(format!(
                     "{hook_key}: failed to write to output to {output}: {err}",
@@ -185,7 +398,29 @@ fn apply_replace_token(
         }
     }
 
-    Ok(ActionHook::ReplaceToken(r.to_string()))
+    Ok(ActionHook::ReplaceToken(hook.to_string()))
+}
+
+impl std::fmt::Display for HookReplaceToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Single pair is reported as before multi-pair form existed
+        if let ([rp], None) = (self.rps.as_slice(), &self.env_file) {
+            return write!(f, "{rp}");
+        }
+
+        let tokens: Vec<_> = self
+            .rps
+            .iter()
+            .map(|rp| json!({ "token": rp.token, "value": rp.value }))
+            .collect();
+
+        let j = json!({
+            "tokens": tokens,
+            "env_file": self.env_file,
+        });
+
+        write!(f, "{}", json::to_string(&j))
+    }
 }
 
 #[test]
@@ -197,6 +432,11 @@ fn test_parse_replace_token() {
         "@replace-token foo bar https://example.com/template /some/file",
         "@replace-token linux_boot \"loglevel=3 quiet root=/dev/archvg/archlv ro\" /etc/default/grub",
         "@replace-token \"linux boot\" \"loglevel=3 quiet root=/dev/archvg/archlv ro\" /some/template /etc/default/grub",
+        "@replace-token PORT=2222 ADDR=0.0.0.0 /etc/ssh/sshd_config",
+        "@replace-token env_file=/root/app.env /etc/app.conf",
+        "@replace-token env_file=/root/app.env PORT=80 /some/template /etc/app.conf",
+        "@replace-token PORT 3322 /etc/ssh/sshd backup",
+        "@replace-token PORT=2222 /etc/ssh/sshd_config backup",
     ];
 
     let should_err = vec![
@@ -206,6 +446,11 @@ fn test_parse_replace_token() {
         "@replace-token PORT \"3322\"",
         "@replace-token PORT \"3322 /some/template",
         "@replace-token PORT \"3322 /some/template /some/output",
+        "@replace-token PORT=2222",
+        "@replace-token PORT=2222 PORT=22 /etc/ssh/sshd_config",
+        "@replace-token env_file=/a env_file=/b /etc/app.conf",
+        "@replace-token PORT=2222 /some/template /some/output /extra",
+        "@replace-token PORT=2222 backup /etc/ssh/sshd_config backup",
     ];
 
     for cmd in should_pass {
@@ -217,8 +462,8 @@ fn test_parse_replace_token() {
 
     for cmd in should_err {
         let result = HookReplaceToken::try_from(cmd);
-        if let Ok(HookReplaceToken { rp: qn, .. }) = result {
-            panic!("got ok result from bad arg {cmd}: {}", qn.to_string());
+        if let Ok(hook) = result {
+            panic!("got ok result from bad arg {cmd}: {hook}");
         }
     }
 
@@ -227,36 +472,66 @@ fn test_parse_replace_token() {
             "@replace-token-print PORT 3322 /etc/ssh/sshd",
             HookReplaceToken {
                 mode_hook: ModeHook::Print,
+                in_place: true,
                 template: "/etc/ssh/sshd".to_string(),
                 output: "/etc/ssh/sshd".to_string(),
-                rp: utils::ReplaceToken {
+                rps: vec![utils::ReplaceToken {
                     token: "PORT".to_string(),
                     value: "3322".to_string(),
-                },
+                }],
+                env_file: None,
+                backup: false,
             }
         ),
         (
             "@replace-token linux_boot \"loglevel=3 quiet root=/dev/archvg/archlv ro\" /etc/default/grub",
             HookReplaceToken {
                 mode_hook: ModeHook::Normal,
+                in_place: true,
                 template: "/etc/default/grub".to_string(),
                 output: "/etc/default/grub".to_string(),
-                rp: utils::ReplaceToken {
+                rps: vec![utils::ReplaceToken {
                     token: "linux_boot".to_string(),
                     value: "loglevel=3 quiet root=/dev/archvg/archlv ro".to_string(),
-                },
+                }],
+                env_file: None,
+                backup: false,
             }
         ),
         (
             "@replace-token-print \"linux_boot\" \"loglevel=3 quiet root=/dev/archvg/archlv ro\" /some/template /etc/default/grub",
             HookReplaceToken {
                 mode_hook: ModeHook::Print,
+                in_place: false,
                 template: "/some/template".to_string(),
                 output: "/etc/default/grub".to_string(),
-                rp: utils::ReplaceToken {
+                rps: vec![utils::ReplaceToken {
                     token: "linux_boot".to_string(),
                     value: "loglevel=3 quiet root=/dev/archvg/archlv ro".to_string(),
-                },
+                }],
+                env_file: None,
+                backup: false,
+            }
+        ),
+        (
+            "@replace-token env_file=/root/app.env PORT=80 ADDR=\"0.0.0.0\" /etc/app.conf",
+            HookReplaceToken {
+                mode_hook: ModeHook::Normal,
+                in_place: true,
+                template: "/etc/app.conf".to_string(),
+                output: "/etc/app.conf".to_string(),
+                rps: vec![
+                    utils::ReplaceToken {
+                        token: "PORT".to_string(),
+                        value: "80".to_string(),
+                    },
+                    utils::ReplaceToken {
+                        token: "ADDR".to_string(),
+                        value: "0.0.0.0".to_string(),
+                    },
+                ],
+                env_file: Some("/root/app.env".to_string()),
+                backup: false,
             }
         ),
     ]);
@@ -266,3 +541,46 @@ fn test_parse_replace_token() {
         assert_eq!(expected, actual);
     }
 }
+
+#[test]
+fn test_apply_replace_token_pairs() {
+    let dir = std::env::temp_dir().join("ali-rs-test-replace-token");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(dir.join("etc")).unwrap();
+
+    let env_file = dir.join("app.env");
+    std::fs::write(
+        &env_file,
+        "# comment\nexport PORT=8080\nNAME='app'\nUNUSED=1\n",
+    )
+    .unwrap();
+    std::fs::write(
+        dir.join("etc/app.conf"),
+        "name={{ NAME }}\nlisten={{ ADDR }}:{{ PORT }}\n",
+    )
+    .unwrap();
+
+    let cmd = format!(
+        "@replace-token env_file={} PORT=80 ADDR=0.0.0.0 /etc/app.conf",
+        env_file.to_string_lossy(),
+    );
+    let hook = HookReplaceToken::try_from(cmd.as_str()).unwrap();
+    let root = dir.to_string_lossy().to_string();
+    apply_replace_token(KEY_REPLACE_TOKEN, &hook, &root).unwrap();
+
+    // Template is read from root location, and pairs override env file
+    assert_eq!(
+        std::fs::read_to_string(dir.join("etc/app.conf")).unwrap(),
+        "name=app\nlisten=0.0.0.0:80\n",
+    );
+
+    // Explicit pairs must exist in template
+    let cmd = "@replace-token MISSING=1 /etc/app.conf";
+    let hook = HookReplaceToken::try_from(cmd).unwrap();
+    assert!(apply_replace_token(KEY_REPLACE_TOKEN, &hook, &root).is_err());
+
+    assert!(parse_env("FOO").is_err());
+    assert!(parse_env("bad token=1").is_err());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,645 @@
+use std::sync::LazyLock;
+
+use serde_json::json;
+
+use super::args::{
+    self,
+    Arg,
+};
+use super::constants::rollback::*;
+use super::utils::output;
+use super::utils::protected::write_under;
+use super::{
+    extract_key_and_parts_shlex,
+    wrap_bad_hook_cmd,
+    ActionHook,
+    Caller,
+    Hook,
+    ModeHook,
+    ParseError,
+    KEY_ROLLBACK,
+    KEY_ROLLBACK_PRINT,
+};
+use crate::errors::AliError;
+use crate::linux::loader;
+use crate::linux::microcode::{
+    self,
+    Microcode,
+};
+use crate::linux::systemd;
+use crate::utils::aliases::{
+    self,
+    Kind,
+};
+use crate::utils::fs::{
+    file_exists,
+    path_under,
+};
+use crate::utils::json;
+use crate::utils::shell;
+
+const ARGS: &[Arg] = &[
+    Arg::positional("bootloader", "grub|sd-boot"),
+    Arg::key("fallback", "KERNEL"),
+    Arg::key("entry", "ENTRY_FILE"),
+    Arg::flag("snapper"),
+];
+
+static USAGE: LazyLock<String> = LazyLock::new(|| args::usage(ARGS));
+
+/// Example arguments, for `ali-rs hooks schema`
+pub(super) const EXAMPLE: &str = "grub snapper";
+
+/// Keyword arguments, for shell completion
+pub(super) const KEYWORDS: &[&str] = &[
+    "grub",
+    "sd-boot",
+    "fallback=",
+    "entry=",
+    "snapper",
+];
+
+const DEFAULT_ENTRY: &str = "arch.conf";
+
+#[derive(Debug, Clone, PartialEq)]
+struct Rollback {
+    bootloader: Bootloader,
+    /// Extra kernel package (e.g. `linux-lts`) booted by fallback entry.
+    /// If not set, fallback entry boots fallback initramfs
+    fallback: Option<String>,
+    /// systemd-boot loader entry from which fallback entry is derived
+    entry: String,
+    /// Sets up snapper snapshots bootable from GRUB (btrfs root only)
+    snapper: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Bootloader {
+    Grub,
+    SdBoot,
+}
+
+struct HookRollback {
+    rollback: Rollback,
+    mode_hook: ModeHook,
+}
+
+pub(super) fn parse(k: &str, cmd: &str) -> Result<Box<dyn Hook>, ParseError> {
+    match k {
+        KEY_ROLLBACK | KEY_ROLLBACK_PRINT => {
+            match HookRollback::try_from(cmd) {
+                Err(err) => Err(wrap_bad_hook_cmd(err, &USAGE)),
+                Ok(hook) => Ok(Box::new(hook)),
+            }
+        }
+
+        key => panic!("unknown key {key}"),
+    }
+}
+
+impl super::Hook for HookRollback {
+    fn base_key(&self) -> &'static str {
+        KEY_ROLLBACK
+    }
+
+    /// `@rollback <grub|sd-boot> [fallback=<KERNEL>] [entry=<ENTRY_FILE>] [snapper]`
+    ///
+    /// Examples:
+    ///
+    /// 1. GRUB with LTS kernel as fallback, and bootable snapper snapshots
+    ///
+    /// ```txt
+    /// @rollback grub fallback=linux-lts snapper
+    /// ```
+    ///
+    /// 2. systemd-boot fallback entry booting fallback initramfs
+    ///
+    /// ```txt
+    /// @rollback sd-boot entry=arch.conf
+    /// ```
+    fn usage(&self) -> &'static str {
+        &USAGE
+    }
+
+    fn mode(&self) -> ModeHook {
+        self.mode_hook.clone()
+    }
+
+    fn should_chroot(&self) -> bool {
+        true
+    }
+
+    fn prefer_caller(&self, caller: &Caller) -> bool {
+        matches!(caller, Caller::ManifestChroot | Caller::Cli)
+    }
+
+    fn abort_if_no_mount(&self) -> bool {
+        true
+    }
+
+    fn run_hook(
+        &self,
+        _caller: &Caller,
+        root_location: &str,
+    ) -> Result<ActionHook, AliError> {
+        apply_rollback(&self.mode_hook, &self.rollback, root_location)
+    }
+}
+
+impl TryFrom<&str> for HookRollback {
+    type Error = AliError;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        let (hook_key, parts) = extract_key_and_parts_shlex(s)?;
+        let mode_hook = match hook_key.as_str() {
+            KEY_ROLLBACK => ModeHook::Normal,
+            KEY_ROLLBACK_PRINT => ModeHook::Print,
+            key => panic!("unexpected key {key}"),
+        };
+
+        let args = args::parse(&hook_key, &parts[1..], ARGS)?;
+
+        let bootloader = args.required("bootloader")?;
+        let bootloader = match aliases::resolve(Kind::Bootloader, bootloader) {
+            Some("grub") => Bootloader::Grub,
+            Some("sd-boot") => Bootloader::SdBoot,
+            Some(other) => {
+                return Err(AliError::AliRsBug(format!(
+                    "{hook_key}: unexpected bootloader {other}"
+                )));
+            }
+            None => {
+                return Err(AliError::BadHookCmd(format!(
+                    "{hook_key}: unknown bootloader {bootloader}{}",
+                    aliases::hint(Kind::Bootloader, bootloader),
+                )));
+            }
+        };
+
+        let snapper = args.flag("snapper");
+
+        let fallback = args.get("fallback").map(String::from);
+        if let Some(ref kernel) = fallback {
+            let valid =
+                |c: char| c.is_ascii_alphanumeric() || c == '-' || c == '_';
+            if kernel.is_empty() || !kernel.chars().all(valid) {
+                return Err(AliError::BadHookCmd(format!(
+                    "{hook_key}: bad fallback kernel {kernel}"
+                )));
+            }
+        }
+
+        let entry = match (bootloader, args.get("entry")) {
+            (_, None) => DEFAULT_ENTRY.to_string(),
+            (Bootloader::SdBoot, Some(entry))
+                if entry.ends_with(".conf") && !entry.contains('/') =>
+            {
+                entry.to_string()
+            }
+            (Bootloader::SdBoot, Some(entry)) => {
+                return Err(AliError::BadHookCmd(format!(
+                    "{hook_key}: bad entry {entry}, expecting file name in {DIR_LOADER_ENTRIES}"
+                )));
+            }
+            (Bootloader::Grub, Some(_)) => {
+                return Err(AliError::BadHookCmd(format!(
+                    "{hook_key}: key entry is only valid for sd-boot"
+                )));
+            }
+        };
+
+        // Booting into snapshots relies on grub-btrfs
+        if snapper && bootloader == Bootloader::SdBoot {
+            return Err(AliError::BadHookCmd(format!(
+                "{hook_key}: snapper rollback is only supported with grub"
+            )));
+        }
+
+        Ok(HookRollback {
+            rollback: Rollback {
+                bootloader,
+                fallback,
+                entry,
+                snapper,
+            },
+            mode_hook,
+        })
+    }
+}
+
+/// Installs fallback kernel and snapper packages, then sets up
+/// bootloader, snapshots, and initramfs in the new system
+fn apply_rollback(
+    mode_hook: &ModeHook,
+    rollback: &Rollback,
+    root_location: &str,
+) -> Result<ActionHook, AliError> {
+    let packages = rollback.packages();
+    let cmd_install = match packages.is_empty() {
+        true => None,
+        false => {
+            Some(format!(
+                "pacman -S --needed --noconfirm {}",
+                packages.join(" ")
+            ))
+        }
+    };
+
+    let files = rollback.encode_files(root_location)?;
+    let cmds = rollback.cmds(root_location);
+    let services = rollback.services();
+
+    if matches!(mode_hook, ModeHook::Print) {
+        if let Some(cmd) = cmd_install {
+            output::println(&format!("# {cmd}"));
+        }
+        for (path, content) in files {
+            output::println(&format!("# {path}\n{content}"));
+        }
+        for cmd in cmds {
+            output::println(&format!("# {cmd}"));
+        }
+        for (service, _) in services {
+            output::println(&format!("# enable {service}"));
+        }
+
+        return Ok(ActionHook::Rollback(rollback.to_string()));
+    }
+
+    if rollback.snapper {
+        check_btrfs(root_location)?;
+    }
+
+    if rollback.bootloader == Bootloader::SdBoot {
+        for (path, _) in &files {
+            let name = path.rsplit('/').next().unwrap_or(path);
+            loader::check_owned(root_location, name)?;
+        }
+    }
+
+    let run = |cmd: &str| {
+        match root_location {
+            "/" => shell::sh_c(cmd),
+            _ => shell::arch_chroot(root_location, cmd),
+        }
+    };
+
+    if let Some(cmd) = cmd_install {
+        run(&cmd)?;
+    }
+
+    for (path, content) in files {
+        write_under(KEY_ROLLBACK, root_location, &path, &content)?;
+    }
+
+    for (service, target) in services {
+        systemd::enable_service(root_location, service, target)?;
+    }
+
+    for cmd in cmds {
+        run(&cmd)?;
+    }
+
+    if rollback.bootloader == Bootloader::SdBoot {
+        for (path, owned) in loader::layout(root_location) {
+            let owner = if owned { "ali-rs" } else { "other" };
+            log::info!("{KEY_ROLLBACK}: ESP has {path} ({owner})");
+        }
+    }
+
+    Ok(ActionHook::Rollback(rollback.to_string()))
+}
+
+/// Snapper snapshots are btrfs subvolumes
+fn check_btrfs(root_location: &str) -> Result<(), AliError> {
+    let output = shell::exec_with_output(
+        "findmnt",
+        &["-n", "-o", "FSTYPE", "--target", root_location],
+    )?;
+
+    match String::from_utf8_lossy(&output).trim() {
+        "btrfs" => Ok(()),
+        fs_type => {
+            Err(AliError::BadHookCmd(format!(
+                "{KEY_ROLLBACK}: snapper requires btrfs root, found {fs_type}"
+            )))
+        }
+    }
+}
+
+impl Rollback {
+    fn packages(&self) -> Vec<&str> {
+        let mut packages: Vec<&str> =
+            self.fallback.iter().map(String::as_str).collect();
+        if self.snapper {
+            packages.extend(PACKAGES_SNAPPER);
+        }
+
+        packages
+    }
+
+    /// Units to enable, with their install targets
+    fn services(&self) -> Vec<(&'static str, &'static str)> {
+        match self.snapper {
+            false => vec![],
+            true => {
+                vec![
+                    ("snapper-timeline.timer", "timers.target"),
+                    ("snapper-cleanup.timer", "timers.target"),
+                    ("grub-btrfsd.service", "multi-user.target"),
+                ]
+            }
+        }
+    }
+
+    /// Returns files to be written, with paths relative to the new root.
+    /// Existing bootloader configuration is read from `root_location`.
+    fn encode_files(
+        &self,
+        root_location: &str,
+    ) -> Result<Vec<(String, String)>, AliError> {
+        match self.bootloader {
+            Bootloader::Grub => {
+                let default_grub =
+                    path_under(root_location, FILENAME_DEFAULT_GRUB)?;
+                let mut content =
+                    std::fs::read_to_string(default_grub).unwrap_or_default();
+                content.push_str(DEFAULT_GRUB_FALLBACK);
+
+                let mut files =
+                    vec![(FILENAME_DEFAULT_GRUB.to_string(), content)];
+                if self.snapper {
+                    files.push((
+                        FILENAME_MKINITCPIO.to_string(),
+                        MKINITCPIO_OVERLAYFS.to_string(),
+                    ));
+                }
+
+                Ok(files)
+            }
+
+            Bootloader::SdBoot => {
+                let path = format!("{DIR_LOADER_ENTRIES}/{}", self.entry);
+                let filename = path_under(root_location, &path)?;
+                let entry = std::fs::read_to_string(&filename).map_err(|err| {
+                    AliError::FileError(
+                        err,
+                        format!("{KEY_ROLLBACK}: reading loader entry {filename}"),
+                    )
+                })?;
+
+                // ESP may be shared with other installs
+                let stem = self.entry.trim_end_matches(".conf");
+                let name = loader::namespaced(
+                    &loader::entry_token(root_location),
+                    &format!("{stem}-fallback.conf"),
+                );
+
+                Ok(vec![(
+                    format!("{DIR_LOADER_ENTRIES}/{name}"),
+                    fallback_entry(
+                        &entry,
+                        self.fallback.as_deref(),
+                        &microcode::installed(root_location),
+                    )?,
+                )])
+            }
+        }
+    }
+
+    /// Commands run after files are written: snapper config creation,
+    /// initramfs regeneration, and GRUB menu generation
+    fn cmds(&self, root_location: &str) -> Vec<String> {
+        if self.bootloader == Bootloader::SdBoot {
+            return vec![];
+        }
+
+        let mut cmds = Vec::new();
+        if self.snapper {
+            // create-config also creates /.snapshots subvolume
+            let snapper_config = path_under(root_location, SNAPPER_CONFIG);
+            if !snapper_config.is_ok_and(file_exists) {
+                cmds.push(
+                    "snapper --no-dbus -c root create-config /".to_string(),
+                );
+            }
+
+            cmds.push("mkinitcpio -P".to_string());
+        }
+
+        cmds.push("grub-mkconfig -o /boot/grub/grub.cfg".to_string());
+        cmds
+    }
+}
+
+/// Derives systemd-boot fallback entry from `entry`, booting `kernel` if
+/// given, or the fallback initramfs of the entry's kernel otherwise.
+/// Images of installed `microcode` are loaded before the initramfs.
+fn fallback_entry(
+    entry: &str,
+    kernel: Option<&str>,
+    microcode: &[Microcode],
+) -> Result<String, AliError> {
+    let base = entry
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("linux"))
+        .find_map(|image| image.trim().rsplit_once("vmlinuz-"))
+        .map(|(_, base)| base.to_string())
+        .ok_or(AliError::BadHookCmd(format!(
+            "{KEY_ROLLBACK}: loader entry has no linux /vmlinuz-<KERNEL> line"
+        )))?;
+
+    let initramfs = format!("initramfs-{base}.img");
+    let initramfs_fallback = match kernel {
+        Some(kernel) => format!("initramfs-{kernel}.img"),
+        None => format!("initramfs-{base}-fallback.img"),
+    };
+
+    let mut lines = Vec::new();
+    let mut initrds = Vec::new();
+    let mut initrd_at = None;
+
+    for line in entry.lines() {
+        let mut fields = line.split_whitespace();
+        let key = fields.next().unwrap_or_default();
+        match (key, kernel) {
+            ("title", _) => lines.push(format!("{line} (fallback)")),
+            ("linux", Some(kernel)) => {
+                lines.push(line.replace(
+                    &format!("vmlinuz-{base}"),
+                    &format!("vmlinuz-{kernel}"),
+                ));
+            }
+            // All initrds are written together where the first one was
+            ("initrd", _) => {
+                initrd_at.get_or_insert(lines.len());
+                initrds.extend(
+                    fields.map(|f| f.replace(&initramfs, &initramfs_fallback)),
+                );
+            }
+            _ => lines.push(line.to_string()),
+        }
+    }
+
+    let initrd_at = initrd_at.unwrap_or_else(|| {
+        lines
+            .iter()
+            .position(|l| l.trim_start().starts_with("linux"))
+            .map_or(lines.len(), |i| i + 1)
+    });
+
+    let initrds = microcode::order_initrds(microcode, &initrds)
+        .into_iter()
+        .map(|initrd| format!("initrd {initrd}"));
+
+    lines.splice(initrd_at..initrd_at, initrds);
+
+    let mut fallback = String::from("# Installed by ali-rs hook @rollback\n");
+    for line in lines {
+        fallback.push_str(&line);
+        fallback.push('\n');
+    }
+
+    Ok(fallback)
+}
+
+impl std::fmt::Display for Rollback {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let j = json!({
+            "bootloader": self.bootloader.to_string(),
+            "fallback": self.fallback,
+            "entry": self.entry,
+            "snapper": self.snapper,
+        });
+
+        write!(f, "{}", json::to_string(&j))
+    }
+}
+
+impl std::fmt::Display for Bootloader {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Grub => write!(f, "grub"),
+            Self::SdBoot => write!(f, "sd-boot"),
+        }
+    }
+}
+
+#[test]
+fn test_parse_rollback() {
+    let should_pass = vec![
+        (
+            "@rollback grub",
+            Rollback {
+                bootloader: Bootloader::Grub,
+                fallback: None,
+                entry: DEFAULT_ENTRY.into(),
+                snapper: false,
+            },
+        ),
+        (
+            "@rollback-print snapper grub fallback=linux-lts",
+            Rollback {
+                bootloader: Bootloader::Grub,
+                fallback: Some("linux-lts".into()),
+                entry: DEFAULT_ENTRY.into(),
+                snapper: true,
+            },
+        ),
+        (
+            "@rollback gummiboot",
+            Rollback {
+                bootloader: Bootloader::SdBoot,
+                fallback: None,
+                entry: DEFAULT_ENTRY.into(),
+                snapper: false,
+            },
+        ),
+        (
+            "@rollback systemd-boot entry=linux-zen.conf",
+            Rollback {
+                bootloader: Bootloader::SdBoot,
+                fallback: None,
+                entry: "linux-zen.conf".into(),
+                snapper: false,
+            },
+        ),
+    ];
+
+    for (cmd, expected) in should_pass {
+        let hook = HookRollback::try_from(cmd).unwrap();
+        assert_eq!(expected, hook.rollback);
+    }
+
+    let should_err = vec![
+        "@rollback",
+        "@rollback lilo",
+        "@rollback grub sd-boot",
+        "@rollback grub entry=arch.conf",
+        "@rollback sd-boot snapper",
+        "@rollback sd-boot entry=../arch.conf",
+        "@rollback sd-boot 'fallback=linux lts'",
+        "@rollback grub foo=bar",
+    ];
+
+    for cmd in should_err {
+        assert!(
+            HookRollback::try_from(cmd).is_err(),
+            "expecting error for {cmd}"
+        );
+    }
+}
+
+#[test]
+fn test_fallback_entry() {
+    let entry = "title Arch Linux
+linux /vmlinuz-linux
+initrd /intel-ucode.img
+initrd /initramfs-linux.img
+options root=/dev/archvg/rootlv rw
+";
+
+    assert_eq!(
+        "# Installed by ali-rs hook @rollback
+title Arch Linux (fallback)
+linux /vmlinuz-linux
+initrd /intel-ucode.img
+initrd /initramfs-linux-fallback.img
+options root=/dev/archvg/rootlv rw
+",
+        fallback_entry(entry, None, &[Microcode::Intel]).unwrap(),
+    );
+
+    let fallback = fallback_entry(entry, Some("linux-lts"), &[]).unwrap();
+    assert!(fallback.contains("linux /vmlinuz-linux-lts\n"));
+    assert!(fallback.contains("initrd /initramfs-linux-lts.img\n"));
+
+    // Installed microcode is added before initramfs, and misordered
+    // microcode on one initrd line is moved first
+    let entry = "title Arch Linux
+linux /vmlinuz-linux
+options root=UUID=foo rw
+initrd /initramfs-linux.img /amd-ucode.img
+";
+
+    assert_eq!(
+        "# Installed by ali-rs hook @rollback
+title Arch Linux (fallback)
+linux /vmlinuz-linux
+options root=UUID=foo rw
+initrd /amd-ucode.img
+initrd /intel-ucode.img
+initrd /initramfs-linux-fallback.img
+",
+        fallback_entry(entry, None, &[Microcode::Intel]).unwrap(),
+    );
+
+    // Without initrd lines, initrds follow linux line
+    let entry = "linux /vmlinuz-linux\noptions rw\n";
+    assert_eq!(
+        "# Installed by ali-rs hook @rollback\nlinux /vmlinuz-linux\ninitrd /amd-ucode.img\noptions rw\n",
+        fallback_entry(entry, None, &[Microcode::Amd]).unwrap(),
+    );
+
+    assert!(fallback_entry("title Arch Linux\n", None, &[]).is_err());
+}
@@ -0,0 +1,725 @@
+use std::sync::LazyLock;
+
+use serde_json::json;
+
+use super::args::{
+    self,
+    Arg,
+};
+use super::constants::sssd::*;
+use super::utils::protected::{
+    self,
+    write_under,
+};
+use super::utils::{
+    file_backup,
+    output,
+};
+use super::{
+    extract_key_and_parts_shlex,
+    wrap_bad_hook_cmd,
+    ActionHook,
+    Caller,
+    Hook,
+    HookState,
+    ModeHook,
+    ParseError,
+    KEY_SSSD,
+    KEY_SSSD_PRINT,
+};
+use crate::errors::AliError;
+use crate::linux::nsswitch::{
+    self,
+    FILENAME_NSSWITCH,
+};
+use crate::linux::pam::{
+    self,
+    PamConf,
+};
+use crate::linux::systemd;
+use crate::utils::fs::path_under;
+use crate::utils::json;
+use crate::utils::secrets;
+use crate::utils::shell;
+
+const ARGS: &[Arg] = &[
+    Arg::one_of_required(&[("ldap_uri", "URL"), ("conf_file", "FILE")]),
+    Arg::key("domain", "DOMAIN"),
+    Arg::key("search_base", "DN"),
+    Arg::key("bind_dn", "DN"),
+    Arg::key("bind_password", "PASSWORD"),
+    Arg::key("bind_password_file", "FILE"),
+    Arg::key("krb5_realm", "REALM"),
+    Arg::key("krb5_server", "HOST"),
+    Arg::key_default("service", "NAME", "system-auth"),
+    Arg::flag("backup"),
+];
+
+static USAGE: LazyLock<String> = LazyLock::new(|| args::usage(ARGS));
+
+/// Example arguments, for `ali-rs hooks schema`
+pub(super) const EXAMPLE: &str = "ldap_uri=ldaps://ldap.example.com domain=example.com bind_dn=cn=reader,dc=example,dc=com bind_password_file=/root/ldap.pass";
+
+/// Keyword arguments, for shell completion
+pub(super) const KEYWORDS: &[&str] = &[
+    "ldap_uri=",
+    "conf_file=",
+    "domain=",
+    "search_base=",
+    "bind_dn=",
+    "bind_password=",
+    "bind_password_file=",
+    "krb5_realm=",
+    "krb5_server=",
+    "service=",
+    "backup",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+struct Sssd {
+    conf: SssdConf,
+    /// PAM service in `/etc/pam.d` to add pam_sss.so to
+    service: String,
+}
+
+/// Source of sssd.conf
+#[derive(Debug, Clone, PartialEq)]
+enum SssdConf {
+    /// Complete sssd.conf on the live system, read when the hook runs,
+    /// so that bind credentials do not have to live in the manifest
+    File(String),
+    Ldap(Ldap),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Ldap {
+    domain: String,
+    uri: String,
+    search_base: String,
+    bind_dn: Option<String>,
+    bind_password: Option<Secret>,
+    /// Kerberos realm and KDC, if users authenticate with Kerberos
+    /// instead of LDAP binds
+    krb5: Option<(String, Option<String>)>,
+}
+
+/// Bind password, either inline or read from file when the hook runs
+#[derive(Debug, Clone, PartialEq)]
+enum Secret {
+    Inline(String),
+    File(String),
+}
+
+struct HookSssd {
+    sssd: Sssd,
+    mode_hook: ModeHook,
+    backup: bool,
+}
+
+pub(super) fn parse(k: &str, cmd: &str) -> Result<Box<dyn Hook>, ParseError> {
+    match k {
+        KEY_SSSD | KEY_SSSD_PRINT => {
+            match HookSssd::try_from(cmd) {
+                Err(err) => Err(wrap_bad_hook_cmd(err, &USAGE)),
+                Ok(hook) => Ok(Box::new(hook)),
+            }
+        }
+
+        key => panic!("unknown key {key}"),
+    }
+}
+
+impl Hook for HookSssd {
+    fn base_key(&self) -> &'static str {
+        KEY_SSSD
+    }
+
+    /// `@sssd <ldap_uri=<URL> domain=<DOMAIN> | conf_file=<FILE>> [bind_dn=<DN>] [bind_password_file=<FILE>] [krb5_realm=<REALM>] [backup]`
+    ///
+    /// With flag `backup`, nsswitch.conf and the PAM service file are
+    /// backed up before they are written, and can be restored with
+    /// `ali-rs hooks undo`.
+    ///
+    /// Examples:
+    ///
+    /// 1. LDAP users and groups, authenticated with LDAP binds
+    ///
+    /// ```txt
+    /// @sssd ldap_uri=ldaps://ldap.example.com domain=example.com
+    /// ```
+    ///
+    /// 2. LDAP users and groups, authenticated with Kerberos
+    ///
+    /// ```txt
+    /// @sssd ldap_uri=ldap://ldap.example.com domain=example.com krb5_realm=EXAMPLE.COM
+    /// ```
+    ///
+    /// 3. Prepared sssd.conf with bind credentials
+    ///
+    /// ```txt
+    /// @sssd conf_file=/root/sssd.conf
+    /// ```
+    fn usage(&self) -> &'static str {
+        &USAGE
+    }
+
+    fn mode(&self) -> ModeHook {
+        self.mode_hook.clone()
+    }
+
+    fn should_chroot(&self) -> bool {
+        true
+    }
+
+    fn prefer_caller(&self, caller: &Caller) -> bool {
+        matches!(caller, Caller::ManifestChroot | Caller::Cli)
+    }
+
+    fn abort_if_no_mount(&self) -> bool {
+        true
+    }
+
+    fn state(&self) -> Vec<HookState> {
+        let file = |path: String, contains: &[&str]| {
+            HookState::File {
+                path,
+                contains: contains.iter().map(|s| s.to_string()).collect(),
+            }
+        };
+
+        vec![
+            file(FILENAME_CONF.to_string(), &["[sssd]"]),
+            file(FILENAME_NSSWITCH.to_string(), &[" sss"]),
+            file(
+                format!("{}/{}", pam::DIR_PAM_D, self.sssd.service),
+                &["pam_sss.so", "pam_mkhomedir.so"],
+            ),
+            HookState::Enabled {
+                unit: SERVICE.to_string(),
+                target: "multi-user.target".to_string(),
+            },
+        ]
+    }
+
+    fn run_hook(
+        &self,
+        _caller: &Caller,
+        root_location: &str,
+    ) -> Result<ActionHook, AliError> {
+        apply_sssd(
+            &self.hook_key(),
+            &self.mode_hook,
+            &self.sssd,
+            self.backup,
+            root_location,
+        )
+    }
+}
+
+impl TryFrom<&str> for HookSssd {
+    type Error = AliError;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        let (hook_key, parts) = extract_key_and_parts_shlex(s)?;
+        let mode_hook = match hook_key.as_str() {
+            KEY_SSSD => ModeHook::Normal,
+            KEY_SSSD_PRINT => ModeHook::Print,
+            key => panic!("unexpected key {key}"),
+        };
+
+        let bad = |msg: String| AliError::BadHookCmd(format!("{hook_key}: {msg}"));
+        let args = args::parse(&hook_key, &parts[1..], ARGS)?;
+        let backup = args.flag("backup");
+
+        let service = args.required("service")?.to_string();
+        let is_service = |c: char| c.is_ascii_alphanumeric() || "-_.".contains(c);
+        if service.starts_with('.') || !service.chars().all(is_service) {
+            return Err(bad(format!("bad service {service}")));
+        }
+
+        let ldap_keys = [
+            "domain",
+            "search_base",
+            "bind_dn",
+            "bind_password",
+            "bind_password_file",
+            "krb5_realm",
+            "krb5_server",
+        ];
+
+        if let Some(file) = args.get("conf_file") {
+            if let Some(key) = ldap_keys.iter().find(|k| args.get(k).is_some())
+            {
+                return Err(bad(format!(
+                    "conf_file and {key} are mutually exclusive, but found both"
+                )));
+            }
+
+            return Ok(HookSssd {
+                sssd: Sssd {
+                    conf: SssdConf::File(file.to_string()),
+                    service,
+                },
+                mode_hook,
+                backup,
+            });
+        }
+
+        let uri = args.required("ldap_uri")?.to_string();
+        let is_uri = ["ldap://", "ldaps://"]
+            .iter()
+            .any(|scheme| uri.starts_with(scheme));
+
+        if !is_uri || !is_value(&uri) {
+            return Err(bad(format!("bad ldap_uri {uri}")));
+        }
+
+        let domain = args
+            .get("domain")
+            .ok_or_else(|| bad("ldap_uri requires domain".to_string()))?;
+
+        if !is_domain(domain) {
+            return Err(bad(format!("bad domain {domain}")));
+        }
+
+        let search_base = match args.get("search_base") {
+            Some(dn) if is_value(dn) => dn.to_string(),
+            Some(dn) => return Err(bad(format!("bad search_base {dn}"))),
+            None => base_dn(domain),
+        };
+
+        let bind_dn = args.get("bind_dn").map(String::from);
+        if let Some(ref dn) = bind_dn {
+            if !is_value(dn) {
+                return Err(bad(format!("bad bind_dn {dn}")));
+            }
+        }
+
+        let bind_password =
+            match (args.get("bind_password"), args.get("bind_password_file")) {
+                (None, None) => None,
+                (Some(_), Some(_)) => {
+                    return Err(bad(
+                        "bind_password and bind_password_file are mutually exclusive, but found both"
+                            .to_string(),
+                    ));
+                }
+                (Some(password), None) if is_value(password) => {
+                    Some(Secret::Inline(password.to_string()))
+                }
+                (Some(_), None) => {
+                    return Err(bad("bad bind_password".to_string()));
+                }
+                (None, Some(file)) => Some(Secret::File(file.to_string())),
+            };
+
+        if bind_password.is_some() && bind_dn.is_none() {
+            return Err(bad("bind password requires bind_dn".to_string()));
+        }
+
+        let krb5 = match (args.get("krb5_realm"), args.get("krb5_server")) {
+            (None, None) => None,
+            (None, Some(_)) => {
+                return Err(bad("krb5_server requires krb5_realm".to_string()));
+            }
+            (Some(realm), server) => {
+                if !is_domain(realm) {
+                    return Err(bad(format!("bad krb5_realm {realm}")));
+                }
+                if let Some(server) = server {
+                    if !is_value(server) {
+                        return Err(bad(format!("bad krb5_server {server}")));
+                    }
+                }
+
+                Some((realm.to_string(), server.map(String::from)))
+            }
+        };
+
+        Ok(HookSssd {
+            sssd: Sssd {
+                conf: SssdConf::Ldap(Ldap {
+                    domain: domain.to_string(),
+                    uri,
+                    search_base,
+                    bind_dn,
+                    bind_password,
+                    krb5,
+                }),
+                service,
+            },
+            mode_hook,
+            backup,
+        })
+    }
+}
+
+/// Returns whether `s` is a single-line sssd.conf value
+fn is_value(s: &str) -> bool {
+    !s.is_empty() && !s.contains(['\n', '\r'])
+}
+
+fn is_domain(s: &str) -> bool {
+    !s.is_empty()
+        && !s.starts_with(['-', '.'])
+        && s.chars().all(|c| c.is_ascii_alphanumeric() || "-.".contains(c))
+}
+
+/// Default search base of `domain`, e.g. `dc=example,dc=com`
+fn base_dn(domain: &str) -> String {
+    domain
+        .split('.')
+        .map(|dc| format!("dc={dc}"))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Installs sssd, writes sssd.conf, adds sss to nsswitch.conf,
+/// adds pam_sss.so and pam_mkhomedir.so to PAM service,
+/// and enables sssd in the new system
+fn apply_sssd(
+    hook_key: &str,
+    mode_hook: &ModeHook,
+    sssd: &Sssd,
+    backup: bool,
+    root_location: &str,
+) -> Result<ActionHook, AliError> {
+    let cmd_install = format!("pacman -S --needed --noconfirm {PACKAGE}");
+    let s = sssd.to_string();
+
+    if matches!(mode_hook, ModeHook::Print) {
+        output::println(&format!("# {cmd_install}"));
+        output::println(&format!(
+            "# {FILENAME_CONF}\n{}",
+            sssd.encode_conf(false)?,
+        ));
+        output::println(&format!(
+            "# {FILENAME_NSSWITCH}: sss for {}",
+            NSS_DATABASES.join(", "),
+        ));
+        for stanza in pam::sss_stanzas() {
+            output::println(&stanza.to_string());
+        }
+        output::println(&format!("# enable {SERVICE}"));
+
+        return Ok(ActionHook::Sssd(s));
+    }
+
+    // Check PAM edits before changing anything
+    let filename_pam = format!("{}/{}", pam::DIR_PAM_D, sssd.service);
+    let path_pam = path_under(root_location, &filename_pam)?;
+    let mut conf_pam = PamConf::read(&path_pam)?;
+
+    if let Err(err) = conf_pam.add_sss().and_then(|_| conf_pam.check_auth()) {
+        return Err(AliError::HookError(format!(
+            "{hook_key}: refusing to write {filename_pam}: {err}"
+        )));
+    }
+
+    let conf = sssd.encode_conf(true)?;
+
+    match root_location {
+        "/" => shell::sh_c(&cmd_install)?,
+        _ => shell::arch_chroot(root_location, &cmd_install)?,
+    }
+
+    // Bind password is never readable by others, not even briefly
+    protected::write_mode_under(
+        hook_key,
+        root_location,
+        FILENAME_CONF,
+        &conf,
+        0o600,
+    )?;
+
+    let path_nss = path_under(root_location, FILENAME_NSSWITCH)?;
+    let nss = std::fs::read_to_string(&path_nss).unwrap_or_default();
+
+    let nss = nsswitch::add_source(&nss, NSS_DATABASES, "sss");
+    if file_backup::enabled(backup) {
+        file_backup::save(hook_key, root_location, &path_nss)?;
+        file_backup::save(hook_key, root_location, &path_pam)?;
+    }
+
+    write_under(hook_key, root_location, FILENAME_NSSWITCH, &nss)?;
+    write_under(
+        hook_key,
+        root_location,
+        &filename_pam,
+        &conf_pam.to_string(),
+    )?;
+
+    systemd::enable_service(root_location, SERVICE, "multi-user.target")?;
+
+    Ok(ActionHook::Sssd(s))
+}
+
+impl Sssd {
+    /// Encodes sssd.conf. Bind password is only read if `with_secrets`,
+    /// and is otherwise redacted, as is sssd.conf from `conf_file`.
+    fn encode_conf(&self, with_secrets: bool) -> Result<String, AliError> {
+        let ldap = match &self.conf {
+            SssdConf::File(_) if !with_secrets => {
+                return Ok("<redacted>".to_string());
+            }
+            SssdConf::File(path) => {
+                return std::fs::read_to_string(path).map_err(|err| {
+                    AliError::FileError(
+                        err,
+                        format!("{KEY_SSSD}: reading conf_file {path}"),
+                    )
+                });
+            }
+            SssdConf::Ldap(ldap) => ldap,
+        };
+
+        let (auth_provider, krb5) = match &ldap.krb5 {
+            None => ("ldap", String::new()),
+            Some((realm, server)) => {
+                let server = server
+                    .as_ref()
+                    .map(|s| format!("krb5_server = {s}\n"))
+                    .unwrap_or_default();
+
+                ("krb5", format!("krb5_realm = {realm}\n{server}"))
+            }
+        };
+
+        let mut bind = String::new();
+        if let Some(ref dn) = ldap.bind_dn {
+            bind.push_str(&format!("ldap_default_bind_dn = {dn}\n"));
+        }
+        if let Some(ref password) = ldap.bind_password {
+            let password = match with_secrets {
+                true => password.resolve()?,
+                false => "<redacted>".to_string(),
+            };
+
+            bind.push_str(&format!(
+                "ldap_default_authtok_type = password\nldap_default_authtok = {password}\n"
+            ));
+        }
+
+        // Plain LDAP must upgrade to TLS before sending passwords
+        let start_tls = match ldap.uri.starts_with("ldap://") {
+            true => "ldap_id_use_start_tls = true\n",
+            false => "",
+        };
+
+        Ok(format!(
+            "# Installed by ali-rs hook @sssd
+[sssd]
+services = nss, pam
+domains = {domain}
+
+[domain/{domain}]
+id_provider = ldap
+auth_provider = {auth_provider}
+chpass_provider = {auth_provider}
+ldap_uri = {uri}
+ldap_search_base = {base}
+{start_tls}ldap_tls_reqcert = demand
+{bind}{krb5}cache_credentials = true
+",
+            domain = ldap.domain,
+            uri = ldap.uri,
+            base = ldap.search_base,
+        ))
+    }
+}
+
+impl Secret {
+    fn resolve(&self) -> Result<String, AliError> {
+        let password = match self {
+            Secret::Inline(password) => secrets::resolve(password)?,
+            Secret::File(path) => {
+                std::fs::read_to_string(path)
+                    .map_err(|err| {
+                        AliError::FileError(
+                            err,
+                            format!("{KEY_SSSD}: reading bind_password_file {path}"),
+                        )
+                    })?
+                    .trim_end_matches(['\n', '\r'])
+                    .to_string()
+            }
+        };
+
+        if !is_value(&password) {
+            return Err(AliError::HookError(format!(
+                "{KEY_SSSD}: bad bind password"
+            )));
+        }
+
+        // Inline references were registered when resolved
+        if matches!(self, Secret::File(_)) {
+            secrets::register(&password);
+        }
+
+        Ok(password)
+    }
+}
+
+impl std::fmt::Display for Sssd {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Bind credentials and conf_file are omitted from reports
+        let j = match &self.conf {
+            SssdConf::File(_) => json!({ "service": self.service }),
+            SssdConf::Ldap(ldap) => {
+                json!({
+                    "service": self.service,
+                    "domain": ldap.domain,
+                    "ldap_uri": ldap.uri,
+                    "search_base": ldap.search_base,
+                    "krb5_realm": ldap.krb5.as_ref().map(|(realm, _)| realm),
+                })
+            }
+        };
+
+        write!(f, "{}", json::to_string(&j))
+    }
+}
+
+#[test]
+fn test_parse_sssd() {
+    let should_pass = vec![
+        "@sssd ldap_uri=ldaps://ldap.example.com domain=example.com",
+        "@sssd-print ldap_uri=ldap://ldap.example.com domain=example.com krb5_realm=EXAMPLE.COM",
+        "@sssd ldap_uri=ldaps://ldap.example.com domain=example.com bind_dn=cn=reader,dc=example,dc=com bind_password_file=/root/ldap.pass",
+        "@sssd conf_file=/root/sssd.conf",
+        "@sssd conf_file=/root/sssd.conf service=system-login",
+        "@sssd conf_file=/root/sssd.conf backup",
+    ];
+
+    let should_err = vec![
+        "@sssd",
+        "@sssd domain=example.com",
+        "@sssd ldap_uri=ldaps://ldap.example.com",
+        "@sssd ldap_uri=https://ldap.example.com domain=example.com",
+        "@sssd ldap_uri=ldaps://ldap.example.com domain=-example.com",
+        "@sssd ldap_uri=ldaps://ldap.example.com domain=example.com bind_password=secret",
+        "@sssd ldap_uri=ldaps://ldap.example.com domain=example.com bind_dn=cn=reader bind_password=secret bind_password_file=/root/ldap.pass",
+        "@sssd ldap_uri=ldaps://ldap.example.com domain=example.com krb5_server=kdc.example.com",
+        "@sssd ldap_uri=ldaps://ldap.example.com conf_file=/root/sssd.conf",
+        "@sssd conf_file=/root/sssd.conf domain=example.com",
+        "@sssd conf_file=/root/sssd.conf service=../shadow",
+    ];
+
+    for s in should_pass {
+        if let Err(err) = HookSssd::try_from(s) {
+            panic!("unexpected error from {s}: {err:?}");
+        }
+    }
+
+    for s in should_err {
+        assert!(HookSssd::try_from(s).is_err(), "unexpected ok result from {s}");
+    }
+
+    let hook = HookSssd::try_from(
+        "@sssd ldap_uri=ldap://ldap.example.com domain=example.com krb5_realm=EXAMPLE.COM bind_dn=cn=reader bind_password=secret",
+    )
+    .unwrap();
+
+    let redacted = hook.sssd.encode_conf(false).unwrap();
+    assert!(redacted.contains("ldap_search_base = dc=example,dc=com\n"));
+    assert!(redacted.contains("auth_provider = krb5\n"));
+    assert!(redacted.contains("ldap_id_use_start_tls = true\n"));
+    assert!(redacted.contains("ldap_default_authtok = <redacted>\n"));
+    assert!(!hook.sssd.to_string().contains("secret"));
+
+    let conf = hook.sssd.encode_conf(true).unwrap();
+    assert!(conf.contains("ldap_default_authtok = secret\n"));
+}
+
+#[test]
+fn test_apply_sssd() {
+    use std::os::unix::fs::PermissionsExt;
+    use std::sync::Arc;
+
+    use crate::utils::mock::MockRunner;
+
+    let root = std::env::temp_dir()
+        .join(format!("ali-rs-test-sssd-{}", std::process::id()));
+    let root_location = root.to_str().unwrap();
+    let service = root.join("etc/pam.d/system-auth");
+    std::fs::create_dir_all(service.parent().unwrap()).unwrap();
+    std::fs::write(&service, pam::TEST_SYSTEM_AUTH).unwrap();
+    std::fs::write(root.join("etc/nsswitch.conf"), "passwd: files\n").unwrap();
+
+    let mock = Arc::new(MockRunner::new());
+    let _guard = shell::set_runner(mock.clone());
+
+    let cmd = "@sssd ldap_uri=ldaps://ldap.example.com domain=example.com";
+    super::apply_hook(cmd, Caller::ManifestChroot, root_location).unwrap();
+    // Applying twice adds nothing, and flag backup saves files it edits
+    super::apply_hook(
+        &format!("{cmd} backup"),
+        Caller::ManifestChroot,
+        root_location,
+    )
+    .unwrap();
+
+    let backups = root.join("var/lib/ali-rs/backups/etc");
+    let names = |dir: &std::path::Path| -> Vec<String> {
+        std::fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect()
+    };
+    assert_eq!(1, names(&backups.join("pam.d")).len());
+    assert!(names(&backups)
+        .iter()
+        .any(|name| name.starts_with("nsswitch.conf.")));
+
+    let conf = PamConf::parse(&std::fs::read_to_string(&service).unwrap());
+    let auth = conf.stack("auth");
+    assert_eq!("[success=3 default=ignore]", auth[1].control);
+    assert_eq!("pam_sss.so", auth[2].module);
+    assert_eq!(8, auth.len());
+
+    let account = conf.stack("account");
+    assert_eq!("[success=2 default=ignore]", account[0].control);
+    assert_eq!("pam_sss.so", account[1].module);
+
+    let password = conf.stack("password");
+    assert_eq!("pam_sss.so", password[1].module);
+    assert!(password[1].args.is_empty());
+
+    let session: Vec<&str> =
+        conf.stack("session").iter().map(|s| s.module.as_str()).collect();
+    assert_eq!(
+        vec![
+            "pam_systemd_home.so",
+            "pam_limits.so",
+            "pam_mkhomedir.so",
+            "pam_unix.so",
+            "pam_sss.so",
+            "pam_permit.so",
+        ],
+        session,
+    );
+
+    assert_eq!(
+        "passwd: files sss\ngroup: files sss\nshadow: files sss\nnetgroup: files sss\n",
+        std::fs::read_to_string(root.join("etc/nsswitch.conf")).unwrap(),
+    );
+
+    let mode = std::fs::metadata(root.join("etc/sssd/sssd.conf"))
+        .unwrap()
+        .permissions()
+        .mode();
+    assert_eq!(0o600, mode & 0o777);
+    assert!(root
+        .join("etc/systemd/system/multi-user.target.wants/sssd.service")
+        .symlink_metadata()
+        .is_ok());
+
+    assert_eq!(
+        Some(&format!(
+            "sh -c arch-chroot {root_location} pacman -S --needed --noconfirm sssd"
+        )),
+        mock.calls().last(),
+    );
+
+    std::fs::remove_dir_all(root).unwrap();
+}
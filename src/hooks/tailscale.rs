@@ -0,0 +1,524 @@
+use std::sync::LazyLock;
+
+use serde_json::json;
+
+use super::args::{
+    self,
+    Arg,
+};
+use super::constants::tailscale::*;
+use super::utils::{
+    output,
+    protected,
+};
+use super::{
+    extract_key_and_parts_shlex,
+    wrap_bad_hook_cmd,
+    ActionHook,
+    Caller,
+    Hook,
+    HookState,
+    ModeHook,
+    ParseError,
+    KEY_TAILSCALE,
+    KEY_TAILSCALE_PRINT,
+};
+use crate::errors::AliError;
+use crate::linux::systemd;
+use crate::utils::json;
+use crate::utils::secrets;
+use crate::utils::shell;
+
+const ARGS: &[Arg] = &[
+    Arg::one_of_required(&[("authkey", "KEY"), ("authkey_file", "FILE")]),
+    Arg::key("tags", "TAG[,TAG..]"),
+    Arg::key("login_server", "URL"),
+    Arg::key("hostname", "HOSTNAME"),
+    Arg::flag("ssh"),
+    Arg::flag("accept_routes"),
+];
+
+static USAGE: LazyLock<String> = LazyLock::new(|| args::usage(ARGS));
+
+/// Example arguments, for `ali-rs hooks schema`
+pub(super) const EXAMPLE: &str =
+    "authkey_file=/root/tailscale.authkey tags=server";
+
+/// Keyword arguments, for shell completion
+pub(super) const KEYWORDS: &[&str] = &[
+    "authkey=",
+    "authkey_file=",
+    "tags=",
+    "login_server=",
+    "hostname=",
+    "ssh",
+    "accept_routes",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+struct Tailscale {
+    authkey: AuthKey,
+    /// ACL tags, with `tag:` prefix
+    tags: Vec<String>,
+    /// Coordination server, e.g. headscale
+    login_server: Option<String>,
+    hostname: Option<String>,
+    /// Enables Tailscale SSH server
+    ssh: bool,
+    accept_routes: bool,
+}
+
+/// Pre-auth key, either inline or read from file when the hook runs,
+/// so that the key does not have to live in the manifest
+#[derive(Debug, Clone, PartialEq)]
+enum AuthKey {
+    Inline(String),
+    File(String),
+}
+
+/// File to be written by tailscale, with path relative to the new root
+#[derive(Debug, Clone, PartialEq)]
+struct TailscaleFile {
+    path: &'static str,
+    content: String,
+    mode: u32,
+}
+
+struct HookTailscale {
+    tailscale: Tailscale,
+    mode_hook: ModeHook,
+}
+
+pub(super) fn parse(k: &str, cmd: &str) -> Result<Box<dyn Hook>, ParseError> {
+    match k {
+        KEY_TAILSCALE | KEY_TAILSCALE_PRINT => {
+            match HookTailscale::try_from(cmd) {
+                Err(err) => Err(wrap_bad_hook_cmd(err, &USAGE)),
+                Ok(hook) => Ok(Box::new(hook)),
+            }
+        }
+
+        key => panic!("unknown key {key}"),
+    }
+}
+
+impl Hook for HookTailscale {
+    fn base_key(&self) -> &'static str {
+        KEY_TAILSCALE
+    }
+
+    /// `@tailscale <authkey=<KEY> | authkey_file=<FILE>> [tags=<TAG[,TAG..]>]`
+    ///
+    /// Examples:
+    ///
+    /// 1. Enroll in tailnet with tag `server`
+    ///
+    /// ```txt
+    /// @tailscale authkey_file=/root/tailscale.authkey tags=server
+    /// ```
+    ///
+    /// 2. Enroll in headscale tailnet, with Tailscale SSH
+    ///
+    /// ```txt
+    /// @tailscale authkey_file=/root/headscale.authkey login_server=https://headscale.example.com ssh
+    /// ```
+    fn usage(&self) -> &'static str {
+        &USAGE
+    }
+
+    fn mode(&self) -> ModeHook {
+        self.mode_hook.clone()
+    }
+
+    fn should_chroot(&self) -> bool {
+        true
+    }
+
+    fn prefer_caller(&self, caller: &Caller) -> bool {
+        matches!(caller, Caller::ManifestChroot | Caller::Cli)
+    }
+
+    fn abort_if_no_mount(&self) -> bool {
+        true
+    }
+
+    /// Enrollment script and unit, and enabled services.
+    /// Pre-auth key is removed once enrolled.
+    fn state(&self) -> Vec<HookState> {
+        let files = [
+            (FILENAME_SCRIPT, self.tailscale.encode_script()),
+            (FILENAME_SERVICE, SERVICE.to_string()),
+        ];
+
+        let files = files.into_iter().map(|(path, content)| {
+            HookState::File {
+                path: path.to_string(),
+                contains: vec![content],
+            }
+        });
+
+        let services = self.tailscale.services().map(|unit| {
+            HookState::Enabled {
+                unit: unit.to_string(),
+                target: "multi-user.target".to_string(),
+            }
+        });
+
+        files.chain(services).collect()
+    }
+
+    fn run_hook(
+        &self,
+        _caller: &Caller,
+        root_location: &str,
+    ) -> Result<ActionHook, AliError> {
+        apply_tailscale(
+            &self.hook_key(),
+            &self.mode_hook,
+            &self.tailscale,
+            root_location,
+        )
+    }
+}
+
+impl TryFrom<&str> for HookTailscale {
+    type Error = AliError;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        let (hook_key, parts) = extract_key_and_parts_shlex(s)?;
+        let mode_hook = match hook_key.as_str() {
+            KEY_TAILSCALE => ModeHook::Normal,
+            KEY_TAILSCALE_PRINT => ModeHook::Print,
+            key => panic!("unexpected key {key}"),
+        };
+
+        let args = args::parse(&hook_key, &parts[1..], ARGS)?;
+
+        let authkey = match args.get("authkey") {
+            Some(key) if is_authkey(key) || secrets::name(key).is_some() => {
+                AuthKey::Inline(key.to_string())
+            }
+            Some(_) => {
+                return Err(AliError::BadHookCmd(format!(
+                    "{hook_key}: bad authkey"
+                )));
+            }
+            None => AuthKey::File(args.required("authkey_file")?.to_string()),
+        };
+
+        let tags = match args.get("tags") {
+            None => vec![],
+            Some(tags) => parse_tags(&hook_key, tags)?,
+        };
+
+        let login_server = args.get("login_server").map(String::from);
+        if let Some(ref url) = login_server {
+            let is_url = ["https://", "http://"]
+                .iter()
+                .any(|scheme| url.starts_with(scheme));
+
+            if !is_url || !is_quotable(url) {
+                return Err(AliError::BadHookCmd(format!(
+                    "{hook_key}: bad login_server {url}"
+                )));
+            }
+        }
+
+        let hostname = args.get("hostname").map(String::from);
+        if let Some(ref hostname) = hostname {
+            if !is_hostname(hostname) {
+                return Err(AliError::BadHookCmd(format!(
+                    "{hook_key}: bad hostname {hostname}"
+                )));
+            }
+        }
+
+        Ok(HookTailscale {
+            tailscale: Tailscale {
+                authkey,
+                tags,
+                login_server,
+                hostname,
+                ssh: args.flag("ssh"),
+                accept_routes: args.flag("accept_routes"),
+            },
+            mode_hook,
+        })
+    }
+}
+
+/// Parses comma-separated ACL tags, adding `tag:` prefix if missing
+fn parse_tags(hook_key: &str, s: &str) -> Result<Vec<String>, AliError> {
+    s.split(',')
+        .map(|tag| {
+            let name = tag.strip_prefix("tag:").unwrap_or(tag);
+            match is_hostname(name) {
+                true => Ok(format!("tag:{name}")),
+                false => {
+                    Err(AliError::BadHookCmd(format!(
+                        "{hook_key}: bad tag {tag}"
+                    )))
+                }
+            }
+        })
+        .collect()
+}
+
+fn is_authkey(key: &str) -> bool {
+    !key.is_empty() && !key.contains(char::is_whitespace)
+}
+
+fn is_hostname(s: &str) -> bool {
+    !s.is_empty()
+        && !s.starts_with('-')
+        && s.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+}
+
+/// Returns whether `s` can be single-quoted in script
+fn is_quotable(s: &str) -> bool {
+    !s.contains(['\'', '\n'])
+}
+
+/// Installs tailscale, writes pre-auth key, enrollment script and unit,
+/// and enables tailscaled and first boot enrollment in the new system
+fn apply_tailscale(
+    hook_key: &str,
+    mode_hook: &ModeHook,
+    tailscale: &Tailscale,
+    root_location: &str,
+) -> Result<ActionHook, AliError> {
+    let cmd_install = format!("pacman -S --needed --noconfirm {PACKAGE}");
+    let services = tailscale.services();
+
+    if matches!(mode_hook, ModeHook::Print) {
+        output::println(&format!("# {cmd_install}"));
+        for f in tailscale.encode_files(false)? {
+            output::println(&format!("# {}\n{}", f.path, f.content));
+        }
+        for service in services {
+            output::println(&format!("# enable {service}"));
+        }
+
+        return Ok(ActionHook::Tailscale(tailscale.to_string()));
+    }
+
+    match root_location {
+        "/" => shell::sh_c(&cmd_install)?,
+        _ => shell::arch_chroot(root_location, &cmd_install)?,
+    }
+
+    for f in tailscale.encode_files(true)? {
+        protected::write_mode_under(
+            hook_key,
+            root_location,
+            f.path,
+            &f.content,
+            f.mode,
+        )?;
+    }
+
+    for service in services {
+        systemd::enable_service(root_location, service, "multi-user.target")?;
+    }
+
+    Ok(ActionHook::Tailscale(tailscale.to_string()))
+}
+
+impl Tailscale {
+    fn services(&self) -> [&'static str; 2] {
+        [SERVICE_TAILSCALED, "ali-rs-tailscale-up.service"]
+    }
+
+    /// Returns all files to be written, with paths relative to the new root.
+    /// Pre-auth key is only read if `with_secrets`, and is otherwise redacted.
+    fn encode_files(
+        &self,
+        with_secrets: bool,
+    ) -> Result<Vec<TailscaleFile>, AliError> {
+        let authkey = match with_secrets {
+            true => self.authkey.resolve()?,
+            false => "<redacted>".to_string(),
+        };
+
+        Ok(vec![
+            TailscaleFile {
+                path: FILENAME_AUTHKEY,
+                content: format!("{authkey}\n"),
+                mode: 0o600,
+            },
+            TailscaleFile {
+                path: FILENAME_SCRIPT,
+                content: self.encode_script(),
+                mode: 0o755,
+            },
+            TailscaleFile {
+                path: FILENAME_SERVICE,
+                content: SERVICE.to_string(),
+                mode: 0o644,
+            },
+        ])
+    }
+
+    /// Encodes enrollment script, which removes pre-auth key
+    /// and saves tailnet status once enrolled
+    fn encode_script(&self) -> String {
+        let mut flags = String::new();
+        if !self.tags.is_empty() {
+            let tags = self.tags.join(",");
+            flags.push_str(&format!(" --advertise-tags={tags}"));
+        }
+        if let Some(ref url) = self.login_server {
+            flags.push_str(&format!(" --login-server='{url}'"));
+        }
+        if let Some(ref hostname) = self.hostname {
+            flags.push_str(&format!(" --hostname={hostname}"));
+        }
+        if self.ssh {
+            flags.push_str(" --ssh");
+        }
+        if self.accept_routes {
+            flags.push_str(" --accept-routes");
+        }
+
+        format!(
+            "#!/bin/sh
+# Installed by ali-rs hook @tailscale
+set -eu
+
+tailscale up --auth-key=file:{FILENAME_AUTHKEY}{flags}
+rm -f {FILENAME_AUTHKEY}
+
+mkdir -p /var/lib/ali-rs
+tailscale status --json > {FILENAME_STATUS}
+"
+        )
+    }
+}
+
+impl AuthKey {
+    fn resolve(&self) -> Result<String, AliError> {
+        let key = match self {
+            AuthKey::Inline(key) => secrets::resolve(key)?,
+            AuthKey::File(path) => {
+                std::fs::read_to_string(path)
+                    .map_err(|err| {
+                        AliError::FileError(
+                            err,
+                            format!("{KEY_TAILSCALE}: reading authkey file {path}"),
+                        )
+                    })?
+                    .trim()
+                    .to_string()
+            }
+        };
+
+        if !is_authkey(&key) {
+            return Err(AliError::HookError(format!(
+                "{KEY_TAILSCALE}: bad authkey"
+            )));
+        }
+
+        // Inline references were registered when resolved
+        if matches!(self, AuthKey::File(_)) {
+            secrets::register(&key);
+        }
+
+        Ok(key)
+    }
+}
+
+impl std::fmt::Display for Tailscale {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Pre-auth key is omitted from reports
+        let j = json!({
+            "tags": self.tags,
+            "login_server": self.login_server,
+            "hostname": self.hostname,
+            "ssh": self.ssh,
+            "accept_routes": self.accept_routes,
+        });
+
+        write!(f, "{}", json::to_string(&j))
+    }
+}
+
+#[test]
+fn test_parse_tailscale() {
+    let should_pass = vec![
+        (
+            "@tailscale authkey=tskey-auth-foo",
+            Tailscale {
+                authkey: AuthKey::Inline("tskey-auth-foo".into()),
+                tags: vec![],
+                login_server: None,
+                hostname: None,
+                ssh: false,
+                accept_routes: false,
+            },
+        ),
+        (
+            "@tailscale-print authkey_file=/root/ts.key tags=server,tag:web login_server=https://hs.example.com hostname=web-1 ssh accept_routes",
+            Tailscale {
+                authkey: AuthKey::File("/root/ts.key".into()),
+                tags: vec!["tag:server".into(), "tag:web".into()],
+                login_server: Some("https://hs.example.com".into()),
+                hostname: Some("web-1".into()),
+                ssh: true,
+                accept_routes: true,
+            },
+        ),
+    ];
+
+    for (cmd, expected) in should_pass {
+        let hook = HookTailscale::try_from(cmd).unwrap();
+        assert_eq!(expected, hook.tailscale);
+    }
+
+    let should_err = vec![
+        "@tailscale",
+        "@tailscale tags=server",
+        "@tailscale authkey=foo authkey_file=/root/ts.key",
+        "@tailscale authkey=foo tags=server,",
+        "@tailscale authkey=foo tags=tag:web_1",
+        "@tailscale authkey=foo login_server=hs.example.com",
+        "@tailscale authkey=foo 'login_server=https://hs/'\\''x'",
+        "@tailscale authkey=foo hostname=-web",
+        "@tailscale authkey=foo exit_node",
+        "@tailscale authkey=foo foo=bar",
+    ];
+
+    for cmd in should_err {
+        assert!(
+            HookTailscale::try_from(cmd).is_err(),
+            "expecting error for {cmd}"
+        );
+    }
+}
+
+#[test]
+fn test_encode_tailscale() {
+    let hook = HookTailscale::try_from(
+        "@tailscale authkey=tskey-auth-foo tags=server login_server=https://hs.example.com ssh",
+    )
+    .unwrap();
+
+    let files = hook.tailscale.encode_files(true).unwrap();
+    let paths: Vec<&str> = files.iter().map(|f| f.path).collect();
+    assert_eq!(
+        vec![FILENAME_AUTHKEY, FILENAME_SCRIPT, FILENAME_SERVICE],
+        paths,
+    );
+
+    assert_eq!("tskey-auth-foo\n", files[0].content);
+    assert!(files[1].content.contains(
+        "tailscale up --auth-key=file:/etc/ali-rs/tailscale.authkey --advertise-tags=tag:server --login-server='https://hs.example.com' --ssh\n"
+    ));
+    assert!(files[1].content.contains(
+        "tailscale status --json > /var/lib/ali-rs/tailscale-status.json"
+    ));
+
+    // Pre-auth key is redacted in print mode, and omitted from reports
+    let files = hook.tailscale.encode_files(false).unwrap();
+    assert!(!files[0].content.contains("tskey"));
+    assert!(!hook.tailscale.to_string().contains("tskey"));
+}
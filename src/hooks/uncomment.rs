@@ -1,6 +1,17 @@
+use std::sync::LazyLock;
+
 use serde_json::json;
 
-use super::utils::download;
+use super::args::{
+    self,
+    Arg,
+};
+use super::utils::{
+    download,
+    file_backup,
+    output,
+    protected,
+};
 use super::{
     wrap_bad_hook_cmd,
     ActionHook,
@@ -14,8 +25,23 @@ use super::{
     KEY_UNCOMMENT_PRINT,
 };
 use crate::errors::AliError;
+use crate::utils::fs::path_under;
+use crate::utils::json;
+
+const ARGS: &[Arg] = &[
+    Arg::positional("pattern", "PATTERN"),
+    Arg::keyword("marker", "COMMENT_MARKER", Some("#")),
+    Arg::positional("file", "FILE"),
+    Arg::flag("backup"),
+];
+
+static USAGE: LazyLock<String> = LazyLock::new(|| args::usage(ARGS));
 
-const USAGE: &str = "<PATTERN> [marker <COMMENT_MARKER=\"#\">] FILE";
+/// Example arguments, for `ali-rs hooks schema`
+pub(super) const EXAMPLE: &str = "en_US.UTF-8 /etc/locale.gen";
+
+/// Keyword arguments, for shell completion
+pub(super) const KEYWORDS: &[&str] = &["marker", "backup"];
 
 #[derive(Clone)]
 pub(super) enum Mode {
@@ -34,6 +60,8 @@ struct HookUncomment {
     mode_hook: ModeHook,
     mode: Mode,
     uc: Uncomment,
+    /// Backs up FILE before writing
+    backup: bool,
 }
 
 pub(super) fn parse(k: &str, cmd: &str) -> Result<Box<dyn Hook>, ParseError> {
@@ -45,7 +73,7 @@ pub(super) fn parse(k: &str, cmd: &str) -> Result<Box<dyn Hook>, ParseError> {
             | KEY_UNCOMMENT_ALL_PRINT
     ) {
         match HookUncomment::try_from(cmd) {
-            Err(err) => Err(wrap_bad_hook_cmd(err, USAGE)),
+            Err(err) => Err(wrap_bad_hook_cmd(err, &USAGE)),
             Ok(hook) => Ok(Box::new(hook)),
         }
     } else {
@@ -59,7 +87,7 @@ impl Hook for HookUncomment {
     }
 
     fn usage(&self) -> &'static str {
-        USAGE
+        &USAGE
     }
 
     fn mode(&self) -> ModeHook {
@@ -88,6 +116,7 @@ impl Hook for HookUncomment {
             &self.mode_hook,
             &self.mode,
             &self.uc,
+            self.backup,
             caller,
             root_location,
         )
@@ -96,11 +125,14 @@ impl Hook for HookUncomment {
 
 /// Synopsis
 /// ```txt
-/// @uncomment <PATTERN> [marker <COMMENT_MARKER="#">] FILE
+/// @uncomment <PATTERN> [marker <COMMENT_MARKER="#">] FILE [backup]
 /// ```
 /// Uncomments lines starting with PATTERN in FILE. Default comment marker is "#",
 /// although alternative marker can be provided after keyword `marker`, e.g. "//", "--", or "!".
 ///
+/// With flag `backup`, FILE is backed up before it is written,
+/// and can be restored with `ali-rs hooks undo`.
+///
 /// Examples:
 /// ```txt
 /// @uncomment PubkeyAuthentication /etc/ssh/sshd_config
@@ -128,45 +160,19 @@ impl TryFrom<&str> for HookUncomment {
             key => panic!("unexpected key {key}"),
         };
 
-        if parts.len() < 3 {
-            return Err(AliError::BadHookCmd(format!(
-                "{hook_key}: expect at least 2 arguments"
-            )));
-        }
+        let args = args::parse(&hook_key, &parts[1..], ARGS)?;
 
-        let uc = match parts.len() {
-            3 => {
-                Uncomment {
-                    marker: "#".to_string(),
-                    pattern: parts[1].to_string(),
-                    source: parts[2].to_string(),
-                }
-            }
-            5 => {
-                if parts[2] != "marker" {
-                    return Err(AliError::BadHookCmd(format!(
-                        "{hook_key}: unexpected argument {}, expecting 2nd argument to be `marker`",
-                        parts[2],
-                    )));
-                }
-
-                Uncomment {
-                    pattern: parts[1].clone(),
-                    marker: parts[3].clone(),
-                    source: parts.last().unwrap().clone(),
-                }
-            }
-            l => {
-                return Err(AliError::BadHookCmd(format!(
-                    "{hook_key}: bad cmd parts: {l}"
-                )));
-            }
+        let uc = Uncomment {
+            pattern: args.required("pattern")?.to_string(),
+            marker: args.required("marker")?.to_string(),
+            source: args.required("file")?.to_string(),
         };
 
         Ok(HookUncomment {
             mode_hook,
             mode: mode_uncomment,
             uc,
+            backup: args.flag("backup"),
         })
     }
 }
@@ -176,18 +182,16 @@ fn apply_uncomment(
     mode_hook: &ModeHook,
     mode: &Mode,
     uc: &Uncomment,
+    backup: bool,
     caller: &Caller,
     root_location: &str,
 ) -> Result<ActionHook, AliError> {
     // Outfile, and maybe infile too if uc.source is not remote URL
-    let target_file = match caller {
-        Caller::ManifestPostInstall => {
-            format!("{root_location}/{}", uc.source)
-        }
-        Caller::Cli => {
-            format!("{root_location}/{}", uc.source)
+    let (root, target_file) = match caller {
+        Caller::ManifestPostInstall | Caller::Cli => {
+            (root_location, path_under(root_location, &uc.source)?)
         }
-        _ => uc.source.clone(),
+        _ => ("/", uc.source.clone()),
     };
 
     // Get original from remote location if source is remote URL
@@ -220,10 +224,15 @@ fn apply_uncomment(
 
     match mode_hook {
         ModeHook::Print => {
-            println!("{}", uncommented);
+            output::println(&uncommented);
         }
 
         ModeHook::Normal => {
+            protected::check(hook_key, root, &target_file)?;
+            if file_backup::enabled(backup) {
+                file_backup::save(hook_key, root, &target_file)?;
+            }
+
             std::fs::write(&target_file, uncommented).map_err(|err| {
                 AliError::FileError(
                     err,
@@ -283,14 +292,15 @@ fn uncomment_text_once(
     )))
 }
 
-impl ToString for Uncomment {
-    fn to_string(&self) -> String {
-        json!({
+impl std::fmt::Display for Uncomment {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let j = json!({
             "comment_marker": self.marker,
             "pattern": self.pattern,
             "file": self.source
-        })
-        .to_string()
+        });
+
+        write!(f, "{}", json::to_string(&j))
     }
 }
 
@@ -301,6 +311,7 @@ fn test_parse_uncomment() {
         "@uncomment SomeKey /some_file",
         "@uncomment someKey marker '#' ./someFile",
         "@uncomment UseFoo marker '!!' ./someFile",
+        "@uncomment Port /etc/ssh/sshd_config backup",
     ];
 
     let should_err = vec![
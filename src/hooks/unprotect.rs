@@ -0,0 +1,182 @@
+use super::utils::protected;
+use super::{
+    wrap_bad_hook_cmd,
+    ActionHook,
+    Caller,
+    Hook,
+    HookSpec,
+    ModeHook,
+    ParseError,
+    KEY_UNPROTECT,
+};
+use crate::errors::AliError;
+use crate::hooks;
+
+const USAGE: &str = "<PATH> [PATH..] <HOOK_CMD>";
+
+/// Example arguments, for `ali-rs hooks schema`
+pub(super) const EXAMPLE: &str =
+    "/etc/shadow @replace-token HASH x /etc/shadow";
+
+/// Hook `@unprotect`, which lets the wrapped hook write protected paths
+struct HookUnprotect {
+    paths: Vec<String>,
+    inner: Box<dyn Hook>,
+}
+
+pub(super) fn parse(_k: &str, cmd: &str) -> Result<Box<dyn Hook>, ParseError> {
+    match HookUnprotect::try_from(cmd) {
+        Ok(hook) => Ok(Box::new(hook)),
+        Err(err) => Err(wrap_bad_hook_cmd(err, USAGE)),
+    }
+}
+
+impl Hook for HookUnprotect {
+    fn base_key(&self) -> &'static str {
+        KEY_UNPROTECT
+    }
+
+    fn usage(&self) -> &'static str {
+        USAGE
+    }
+
+    fn mode(&self) -> ModeHook {
+        self.inner.mode()
+    }
+
+    fn should_chroot(&self) -> bool {
+        self.inner.should_chroot()
+    }
+
+    fn prefer_caller(&self, caller: &Caller) -> bool {
+        self.inner.prefer_caller(caller)
+    }
+
+    fn abort_if_no_mount(&self) -> bool {
+        self.inner.abort_if_no_mount()
+    }
+
+    fn spec(&self) -> HookSpec {
+        HookSpec {
+            key: KEY_UNPROTECT.to_string(),
+            mode: None,
+            callers: None,
+            chroot: None,
+            abort_if_no_mount: None,
+            ..HookSpec::new(self)
+        }
+    }
+
+    fn state(&self) -> Vec<hooks::HookState> {
+        self.inner.state()
+    }
+
+    fn run_hook(
+        &self,
+        caller: &Caller,
+        root_location: &str,
+    ) -> Result<ActionHook, AliError> {
+        log::warn!(
+            "{}: {} may write protected paths {}",
+            self.base_key(),
+            self.inner.hook_key(),
+            self.paths.join(" "),
+        );
+
+        let _unprotect = protected::unprotect(&self.paths);
+        self.inner.run_hook(caller, root_location)
+    }
+}
+
+/// Synopsis
+/// ```txt
+/// @unprotect <PATH> [PATH..] <HOOK_CMD>
+/// ```
+/// Lifts protection of PATHs (and files under them)
+/// only for HOOK_CMD, see [`protected`]
+impl TryFrom<&str> for HookUnprotect {
+    type Error = AliError;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        let (hook_key, parts) = hooks::extract_key_and_parts(s)?;
+        if hook_key != KEY_UNPROTECT {
+            return Err(AliError::AliRsBug(format!(
+                "{KEY_UNPROTECT}: bad key {hook_key}",
+            )));
+        }
+
+        let Some(inner_at) = parts.iter().skip(1).position(|p| hooks::is_hook(p))
+        else {
+            return Err(AliError::BadHookCmd(format!(
+                "{hook_key}: missing inner hook",
+            )));
+        };
+
+        // Skip hook key
+        let inner_at = inner_at + 1;
+        if inner_at == 1 {
+            return Err(AliError::BadHookCmd(format!(
+                "{hook_key}: missing path",
+            )));
+        }
+
+        let paths = parts[1..inner_at].to_vec();
+        if let Some(path) = paths.iter().find(|p| !p.starts_with('/')) {
+            return Err(AliError::BadHookCmd(format!(
+                "{hook_key}: path must be absolute, got {path}"
+            )));
+        }
+
+        let inner_cmd = parts[inner_at..].join(" ");
+        let inner = hooks::parse_hook(&parts[inner_at], &inner_cmd)?;
+
+        Ok(Self { paths, inner })
+    }
+}
+
+#[test]
+fn test_unprotect() {
+    use super::KEY_UNCOMMENT;
+
+    let hook = HookUnprotect::try_from(
+        "@unprotect /etc/shadow /etc/gshadow @uncomment-print x /etc/shadow",
+    )
+    .unwrap();
+
+    assert_eq!(vec!["/etc/shadow", "/etc/gshadow"], hook.paths);
+    assert_eq!("@uncomment-print", hook.inner.hook_key());
+    assert_eq!(ModeHook::Print, hook.mode());
+
+    let should_err = vec![
+        "@unprotect",
+        "@unprotect /etc/shadow",
+        "@unprotect @uncomment x /etc/shadow",
+        "@unprotect etc/shadow @uncomment x /etc/shadow",
+        "@unprotect /etc/shadow @uncomment /etc/shadow",
+    ];
+
+    for cmd in should_err {
+        assert!(
+            HookUnprotect::try_from(cmd).is_err(),
+            "expecting error for {cmd}"
+        );
+    }
+
+    // Protection is lifted only while inner hook runs
+    let root = std::env::temp_dir().join("ali-rs-test-unprotect");
+    let _ = std::fs::remove_dir_all(&root);
+    std::fs::create_dir_all(root.join("etc")).unwrap();
+    std::fs::write(root.join("etc/shadow"), "#x\n").unwrap();
+
+    let r = root.to_str().unwrap();
+    let cmd = "@uncomment x /etc/shadow";
+    let hook = hooks::parse_hook(KEY_UNCOMMENT, cmd).unwrap();
+    assert!(hook.run_hook(&Caller::Cli, r).is_err());
+
+    let cmd = format!("@unprotect /etc/shadow {cmd}");
+    let hook = HookUnprotect::try_from(cmd.as_str()).unwrap();
+    hook.run_hook(&Caller::Cli, r).unwrap();
+    assert_eq!("x\n", std::fs::read_to_string(root.join("etc/shadow")).unwrap());
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
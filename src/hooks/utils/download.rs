@@ -1,13 +1,38 @@
+#[cfg(feature = "remote")]
 use std::io::Read;
+use std::sync::OnceLock;
+#[cfg(feature = "remote")]
+use std::time::{
+    Duration,
+    Instant,
+};
 
 use crate::errors::AliError;
+use crate::utils::checksum;
 
 const DELIMITER: &str = "://";
 
-/// Synchronous network downloader
+/// Separates mirrors of the same remote artifact, e.g.
+/// `https://a.example/foo|https://b.example/foo`.
+/// `|` is not a valid URL character, so it is safe to split on.
+const MIRROR_DELIMITER: char = '|';
+
+/// Global download rate limit in bytes per second,
+/// set once from CLI flag `--limit-rate`
+static LIMIT_RATE: OnceLock<u64> = OnceLock::new();
+
+/// Global SOCKS5 proxy URL, set once from manifest key `proxy`
+/// or CLI flag `--proxy`
+static PROXY: OnceLock<String> = OnceLock::new();
+
+/// Synchronous network downloader.
+/// Mirrors are tried in order until one succeeds
+/// (and matches the checksum, if any).
 pub(crate) struct Downloader {
     proto: Protocol,
-    url: String,
+    mirrors: Vec<String>,
+    limit_rate: Option<u64>,
+    sha256: Option<String>,
 }
 
 pub(crate) enum Protocol {
@@ -58,46 +83,173 @@ impl TryFrom<&str> for Protocol {
     }
 }
 
+/// Sets global download rate limit in bytes per second.
+/// Only the first call has effect.
+pub(crate) fn set_limit_rate(bytes_per_sec: u64) {
+    let _ = LIMIT_RATE.set(bytes_per_sec);
+}
+
+/// Sets global SOCKS5 proxy URL for HTTP downloads.
+/// Only the first call has effect.
+pub(crate) fn set_proxy(url: &str) {
+    let _ = PROXY.set(url.to_string());
+}
+
+/// Splits `|`-separated mirror list
+pub(crate) fn split_mirrors(urls: &str) -> Vec<&str> {
+    urls.split(MIRROR_DELIMITER).map(str::trim).collect()
+}
+
 impl Downloader {
-    pub(crate) fn new(url: &str, proto: Protocol) -> Self {
+    pub(crate) fn new(mirrors: Vec<String>, proto: Protocol) -> Self {
         Self {
-            url: url.to_string(),
+            mirrors,
             proto,
+            limit_rate: LIMIT_RATE.get().copied(),
+            sha256: None,
         }
     }
 
-    pub(crate) fn new_from_url(url: &str) -> Result<Self, AliError> {
-        let prefix = extract_proto_prefix(url)?;
-        let proto = Protocol::try_from(prefix)?;
+    /// Overrides global rate limit for this downloader
+    pub(crate) fn with_limit_rate(mut self, bytes_per_sec: u64) -> Self {
+        self.limit_rate = Some(bytes_per_sec);
+        self
+    }
+
+    /// Verifies downloaded bytes against hex-encoded SHA-256 `checksum`.
+    /// Mirrors with mismatched checksum are skipped.
+    pub(crate) fn with_sha256(mut self, checksum: &str) -> Self {
+        self.sha256 = Some(checksum.to_lowercase());
+        self
+    }
 
-        match proto {
-            Protocol::Http => Ok(Self::new(url, proto)),
+    /// Creates downloader from URL, or from `|`-separated mirror URLs
+    pub(crate) fn new_from_url(urls: &str) -> Result<Self, AliError> {
+        let mirrors = split_mirrors(urls);
+        for url in &mirrors {
+            let prefix = extract_proto_prefix(url)?;
+            let proto = Protocol::try_from(prefix)?;
 
-            other_proto => {
-                Err(AliError::NotImplemented(format!(
-                    "downloader protocol {other_proto}"
-                )))
+            if !matches!(proto, Protocol::Http) {
+                return Err(AliError::NotImplemented(format!(
+                    "downloader protocol {proto}"
+                )));
             }
         }
+
+        Ok(Self::new(
+            mirrors.into_iter().map(|s| s.to_string()).collect(),
+            Protocol::Http,
+        ))
     }
 
     pub(crate) fn get_string(&self) -> Result<String, AliError> {
-        match self.proto {
-            Protocol::Http => download_http_string(&self.url),
-            ref other_proto => panic!("unexpected protocol: {other_proto}"),
-        }
+        let bytes = self.get_bytes()?;
+
+        String::from_utf8(bytes).map_err(|err| {
+            AliError::HookError(format!("body is not string: {err}"))
+        })
     }
 
     pub(crate) fn get_bytes(&self) -> Result<Vec<u8>, AliError> {
-        match self.proto {
-            Protocol::Http => download_http_bytes(&self.url),
-            ref other_proto => panic!("unexpected protocol: {other_proto}"),
+        let mut errs = Vec::new();
+
+        for url in &self.mirrors {
+            let result = match self.proto {
+                Protocol::Http => download_http_bytes(url, self.limit_rate),
+                ref other_proto => panic!("unexpected protocol: {other_proto}"),
+            }
+            .and_then(|bytes| self.verify(url, bytes));
+
+            match result {
+                Ok(bytes) => return Ok(bytes),
+                Err(err) => {
+                    if self.mirrors.len() > 1 {
+                        log::warn!("mirror {url} failed: {err}");
+                    }
+
+                    errs.push(format!("{url}: {err}"));
+                }
+            }
         }
+
+        Err(AliError::HookError(format!(
+            "all mirrors failed: {}",
+            errs.join("; ")
+        )))
+    }
+
+    fn verify(&self, url: &str, bytes: Vec<u8>) -> Result<Vec<u8>, AliError> {
+        let Some(ref expected) = self.sha256 else {
+            return Ok(bytes);
+        };
+
+        checksum::verify_sha256(url, &bytes, expected)?;
+
+        Ok(bytes)
     }
 }
 
+/// Reader that throttles reads from inner reader to `bytes_per_sec`
+#[cfg(feature = "remote")]
+pub(crate) struct RateLimited<R: Read> {
+    inner: R,
+    bytes_per_sec: u64,
+    read: u64,
+    start: Instant,
+}
+
+#[cfg(feature = "remote")]
+impl<R: Read> RateLimited<R> {
+    pub(crate) fn new(inner: R, bytes_per_sec: u64) -> Self {
+        Self {
+            inner,
+            bytes_per_sec: bytes_per_sec.max(1),
+            read: 0,
+            start: Instant::now(),
+        }
+    }
+}
+
+#[cfg(feature = "remote")]
+impl<R: Read> Read for RateLimited<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        // Sleep until we are back under the limit
+        let expected = Duration::from_secs_f64(
+            self.read as f64 / self.bytes_per_sec as f64,
+        );
+        let elapsed = self.start.elapsed();
+        if expected > elapsed {
+            std::thread::sleep(expected - elapsed);
+        }
+
+        // Read at most 1/10 second worth of bytes at a time
+        let max = ((self.bytes_per_sec / 10).max(1) as usize).min(buf.len());
+        let n = self.inner.read(&mut buf[..max])?;
+        self.read += n as u64;
+
+        Ok(n)
+    }
+}
+
+#[cfg(feature = "remote")]
+fn agent() -> Result<ureq::Agent, AliError> {
+    let mut builder = ureq::AgentBuilder::new();
+
+    if let Some(url) = PROXY.get() {
+        let proxy = ureq::Proxy::new(url).map_err(|err| {
+            AliError::HookError(format!("bad proxy {url}: {err}"))
+        })?;
+
+        builder = builder.proxy(proxy);
+    }
+
+    Ok(builder.build())
+}
+
+#[cfg(feature = "remote")]
 fn http_get(url: &str) -> Result<ureq::Response, AliError> {
-    let resp = ureq::get(url).call().map_err(|err| {
+    let resp = agent()?.get(url).call().map_err(|err| {
         AliError::HookError(format!("failed to GET {url}: {err}"))
     })?;
 
@@ -109,18 +261,17 @@ fn http_get(url: &str) -> Result<ureq::Response, AliError> {
     Ok(resp)
 }
 
-fn download_http_string(url: &str) -> Result<String, AliError> {
-    let resp = http_get(url)?;
-
-    resp.into_string().map_err(|err| {
-        AliError::HookError(format!("body is not string: {err}"))
-    })
-}
-
-fn download_http_bytes(url: &str) -> Result<Vec<u8>, AliError> {
+#[cfg(feature = "remote")]
+fn download_http_bytes(
+    url: &str,
+    limit_rate: Option<u64>,
+) -> Result<Vec<u8>, AliError> {
     let resp = http_get(url)?;
 
-    let mut r = resp.into_reader();
+    let mut r: Box<dyn Read> = match limit_rate {
+        Some(rate) => Box::new(RateLimited::new(resp.into_reader(), rate)),
+        None => Box::new(resp.into_reader()),
+    };
     let mut v = Vec::new();
 
     if let Err(err) = r.read_to_end(&mut v) {
@@ -131,3 +282,65 @@ fn download_http_bytes(url: &str) -> Result<Vec<u8>, AliError> {
 
     Ok(v)
 }
+
+#[cfg(not(feature = "remote"))]
+fn download_http_bytes(
+    _url: &str,
+    _limit_rate: Option<u64>,
+) -> Result<Vec<u8>, AliError> {
+    Err(AliError::NotImplemented(
+        "HTTP downloads (ali-rs built without feature remote)".to_string(),
+    ))
+}
+
+#[cfg(feature = "remote")]
+#[test]
+fn test_rate_limited() {
+    let data = vec![0u8; 1500];
+    let start = Instant::now();
+
+    let mut r = RateLimited::new(data.as_slice(), 3000);
+    let mut v = Vec::new();
+    r.read_to_end(&mut v).unwrap();
+
+    assert_eq!(data, v);
+    assert!(start.elapsed() >= Duration::from_millis(400));
+}
+
+#[cfg(not(feature = "remote"))]
+#[test]
+fn test_download_disabled() {
+    let d = Downloader::new_from_url("https://a.example/foo").unwrap();
+    assert!(d.get_bytes().is_err());
+}
+
+#[test]
+fn test_new_from_url_mirrors() {
+    let d = Downloader::new_from_url(
+        "https://a.example/foo | https://b.example/foo",
+    )
+    .unwrap();
+
+    assert_eq!(
+        vec!["https://a.example/foo", "https://b.example/foo"],
+        d.mirrors
+    );
+
+    assert!(Downloader::new_from_url("https://a.example/foo|/tmp/foo").is_err());
+    assert!(
+        Downloader::new_from_url("https://a.example/foo|scp://b:foo").is_err()
+    );
+}
+
+#[test]
+fn test_verify_sha256() {
+    let sum =
+        "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+
+    let d = Downloader::new_from_url("https://a.example/foo")
+        .unwrap()
+        .with_sha256(&sum.to_uppercase());
+
+    assert!(d.verify("a", b"hello".to_vec()).is_ok());
+    assert!(d.verify("a", b"hello!".to_vec()).is_err());
+}
@@ -0,0 +1,194 @@
+//! Backups of files taken before file-editing hooks modify them,
+//! restored with `ali-rs hooks undo`.
+//!
+//! Backups are kept under [`defaults::BACKUPS_DIR`] of the root location,
+//! mirroring paths of the original files with suffix `.<UNIX_NANOS>`,
+//! e.g. `/var/lib/ali-rs/backups/etc/ssh/sshd_config.1681234567000000000`.
+
+use std::collections::BTreeMap;
+use std::path::{
+    Path,
+    PathBuf,
+};
+use std::sync::OnceLock;
+use std::time::{
+    SystemTime,
+    UNIX_EPOCH,
+};
+
+use crate::constants::defaults;
+use crate::errors::AliError;
+use crate::utils::fs::copy_under;
+
+/// Whether all file-editing hooks back up files,
+/// set once from CLI flag `--backup-hooks`
+static ALWAYS: OnceLock<bool> = OnceLock::new();
+
+/// Makes all file-editing hooks back up files before writing.
+/// Only the first call has effect.
+pub(crate) fn set_always(always: bool) {
+    let _ = ALWAYS.set(always);
+}
+
+/// Returns whether a hook given flag `backup` (`per_hook`)
+/// should back up files before writing
+pub(crate) fn enabled(per_hook: bool) -> bool {
+    per_hook || ALWAYS.get().copied().unwrap_or(false)
+}
+
+/// Copies `file` (a path on the live system, inside `root`) to backups
+/// directory of `root`, and returns path of the backup.
+/// Returns None if `file` does not exist yet.
+pub(crate) fn save(
+    hook_key: &str,
+    root: &str,
+    file: &str,
+) -> Result<Option<PathBuf>, AliError> {
+    let err = |err: std::io::Error, msg: String| {
+        AliError::FileError(err, format!("{hook_key}: {msg}"))
+    };
+
+    let original = match std::fs::canonicalize(file) {
+        Ok(path) => path,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(err(e, format!("resolve {file}"))),
+    };
+
+    let root = std::fs::canonicalize(root)
+        .map_err(|e| err(e, format!("resolve root location {root}")))?;
+
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or_default();
+
+    let relative = original.strip_prefix(&root).unwrap_or(&original);
+    let backup = Path::new(defaults::BACKUPS_DIR)
+        .join(relative.strip_prefix("/").unwrap_or(relative))
+        .with_added_extension(nanos.to_string());
+
+    let backup = PathBuf::from(copy_under(
+        &root.to_string_lossy(),
+        &original,
+        &backup.to_string_lossy(),
+    )?);
+
+    log::info!("{hook_key}: backed up {file} to {}", backup.display());
+
+    Ok(Some(backup))
+}
+
+/// Restores latest backup of each file under `root`, removing
+/// the restored backups. Returns restored files (relative to `root`)
+/// with their backups. If `dry_run`, only returns what would be restored.
+pub(crate) fn undo(
+    root: &str,
+    dry_run: bool,
+) -> Result<Vec<(String, PathBuf)>, AliError> {
+    let dir = backups_dir(Path::new(root));
+
+    let mut backups = Vec::new();
+    walk(&dir, &mut backups).map_err(|err| {
+        AliError::FileError(err, format!("read backups {}", dir.display()))
+    })?;
+
+    // Latest backup for each file, by path relative to root
+    let mut latest: BTreeMap<String, (u128, PathBuf)> = BTreeMap::new();
+    for backup in backups {
+        let Some((file, nanos)) = parse_backup(&dir, &backup) else {
+            log::warn!("skipping unknown backup file {}", backup.display());
+            continue;
+        };
+
+        if latest.get(&file).is_none_or(|(n, _)| nanos > *n) {
+            latest.insert(file, (nanos, backup));
+        }
+    }
+
+    let root = root.trim_end_matches('/');
+    let mut restored = Vec::new();
+    for (file, (_, backup)) in latest {
+        if !dry_run {
+            let target = copy_under(root, &backup, &file)?;
+            std::fs::remove_file(&backup).map_err(|err| {
+                let backup = backup.display();
+                AliError::FileError(
+                    err,
+                    format!("restored {target}, but failed to remove {backup}"),
+                )
+            })?;
+        }
+
+        restored.push((file, backup));
+    }
+
+    Ok(restored)
+}
+
+fn backups_dir(root: &Path) -> PathBuf {
+    root.join(defaults::BACKUPS_DIR.trim_start_matches('/'))
+}
+
+fn walk(dir: &Path, files: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            return Ok(());
+        }
+        Err(err) => return Err(err),
+    };
+
+    for entry in entries {
+        let path = entry?.path();
+        match path.is_dir() {
+            true => walk(&path, files)?,
+            false => files.push(path),
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns original path (relative to root) and timestamp of `backup`
+fn parse_backup(dir: &Path, backup: &Path) -> Option<(String, u128)> {
+    let relative = backup.strip_prefix(dir).ok()?.to_str()?;
+    let (file, nanos) = relative.rsplit_once('.')?;
+
+    Some((format!("/{file}"), nanos.parse().ok()?))
+}
+
+#[test]
+fn test_save_undo() {
+    let root = std::env::temp_dir().join("ali-rs-test-file-backup");
+    let _ = std::fs::remove_dir_all(&root);
+    std::fs::create_dir_all(root.join("etc")).unwrap();
+
+    let root_str = root.to_str().unwrap();
+    let conf = root.join("etc/app.conf");
+    let conf_str = conf.to_str().unwrap();
+
+    assert!(save("@test", root_str, conf_str).unwrap().is_none());
+
+    std::fs::write(&conf, "v1\n").unwrap();
+    let first = save("@test", root_str, conf_str).unwrap().unwrap();
+    std::fs::write(&conf, "v2\n").unwrap();
+    let second = save("@test", root_str, conf_str).unwrap().unwrap();
+    std::fs::write(&conf, "v3\n").unwrap();
+
+    assert!(first.starts_with(root.join("var/lib/ali-rs/backups/etc")));
+    assert_ne!(first, second);
+
+    // Dry run restores nothing
+    let restored = undo(root_str, true).unwrap();
+    assert_eq!(restored, vec![("/etc/app.conf".to_string(), second.clone())]);
+    assert_eq!(std::fs::read_to_string(&conf).unwrap(), "v3\n");
+
+    // Each undo restores the latest remaining backup
+    undo(root_str, false).unwrap();
+    assert_eq!(std::fs::read_to_string(&conf).unwrap(), "v2\n");
+    undo(root_str, false).unwrap();
+    assert_eq!(std::fs::read_to_string(&conf).unwrap(), "v1\n");
+    assert!(undo(root_str, false).unwrap().is_empty());
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
@@ -1,4 +1,7 @@
 pub(crate) mod download;
+pub(crate) mod file_backup;
+pub(crate) mod output;
+pub(crate) mod protected;
 
 mod replace_token;
 
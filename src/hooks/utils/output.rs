@@ -0,0 +1,304 @@
+//! Size limit of hook output. `-print` output and reported actions of
+//! a hook over the limit are truncated with a notice, and the full output
+//! is stored gzip-compressed in the run directory (that of the log file).
+
+use std::cell::RefCell;
+use std::fs::File;
+use std::io::Write;
+use std::path::{
+    Path,
+    PathBuf,
+};
+use std::sync::atomic::{
+    AtomicUsize,
+    Ordering,
+};
+use std::sync::OnceLock;
+use std::time::{
+    SystemTime,
+    UNIX_EPOCH,
+};
+
+use flate2::write::GzEncoder;
+use flate2::Compression;
+
+/// Default output limit of each hook in bytes
+pub(crate) const DEFAULT_LIMIT: usize = 256 << 10;
+
+/// Output limit in bytes and run directory, set once from
+/// CLI flags `--hook-output-limit` and `--log-file`
+static LIMIT: OnceLock<(usize, PathBuf)> = OnceLock::new();
+
+/// Sequence number of files of full output in this run
+static SEQ: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    /// Output of the hook being run
+    static CAPTURE: RefCell<Option<Capture>> = const { RefCell::new(None) };
+}
+
+/// Output of one hook, truncated at `limit` bytes
+struct Capture {
+    hook_key: String,
+    limit: usize,
+    dir: PathBuf,
+    written: usize,
+    /// Output printed so far, kept until `limit` is exceeded
+    head: String,
+    overflow: Option<Overflow>,
+}
+
+/// Destination of full output once truncated
+enum Overflow {
+    File(PathBuf, GzEncoder<File>),
+    /// Full output could not be stored
+    Discard,
+}
+
+/// Output of a hook that was truncated
+#[derive(Debug)]
+pub(crate) struct Truncated {
+    /// Size of full output in bytes
+    pub size: usize,
+    /// Path to compressed full output, if it could be stored
+    pub path: Option<PathBuf>,
+}
+
+/// Limits output of each hook to `limit` bytes, storing full output
+/// in directory `dir`. A limit of 0 disables truncation.
+/// Only the first call has effect.
+pub(crate) fn set_limit(limit: usize, dir: &Path) {
+    let _ = LIMIT.set((limit, dir.to_path_buf()));
+}
+
+fn limit() -> Option<(usize, &'static Path)> {
+    match LIMIT.get() {
+        Some((0, _)) => None,
+        Some((limit, dir)) => Some((*limit, dir.as_path())),
+        None => Some((DEFAULT_LIMIT, Path::new("."))),
+    }
+}
+
+/// Starts capturing output of hook `hook_key`, printed with [`print`]
+pub(crate) fn begin(hook_key: &str) {
+    let capture =
+        limit().map(|(limit, dir)| Capture::new(hook_key, limit, dir));
+    CAPTURE.with_borrow_mut(|c| *c = capture);
+}
+
+/// Stops capturing output, and returns what was truncated if any
+pub(crate) fn end() -> Option<Truncated> {
+    CAPTURE
+        .with_borrow_mut(Option::take)
+        .and_then(Capture::finish)
+}
+
+/// Prints `s` to stdout, truncated if the current hook
+/// has printed too much
+pub(crate) fn print(s: &str) {
+    CAPTURE.with_borrow_mut(|capture| {
+        match capture {
+            Some(capture) => capture.write(s),
+            None => print!("{s}"),
+        }
+    });
+}
+
+/// Like [`print`], followed by a newline
+pub(crate) fn println(s: &str) {
+    print(&format!("{s}\n"));
+}
+
+/// Truncates `s` if it exceeds the output limit, storing the full `s`.
+/// Returns what was truncated if any.
+pub(crate) fn truncate(hook_key: &str, s: &mut String) -> Option<Truncated> {
+    let (limit, dir) = limit()?;
+    truncate_with(Capture::new(hook_key, limit, dir), s)
+}
+
+fn truncate_with(mut capture: Capture, s: &mut String) -> Option<Truncated> {
+    if s.len() <= capture.limit {
+        return None;
+    }
+
+    capture.written = s.len();
+    capture.overflow = Some(capture.create(s));
+
+    s.truncate(floor_char_boundary(s, capture.limit));
+    s.push_str(&capture.notice());
+
+    capture.finish()
+}
+
+impl Capture {
+    fn new(hook_key: &str, limit: usize, dir: &Path) -> Self {
+        Self {
+            hook_key: hook_key.to_string(),
+            limit,
+            dir: dir.to_path_buf(),
+            written: 0,
+            head: String::new(),
+            overflow: None,
+        }
+    }
+
+    fn write(&mut self, s: &str) {
+        self.written += s.len();
+
+        if let Some(ref mut overflow) = self.overflow {
+            overflow.write(s);
+            return;
+        }
+
+        if self.written <= self.limit {
+            print!("{s}");
+            self.head.push_str(s);
+            return;
+        }
+
+        let remaining = self.limit - self.head.len();
+        print!("{}", &s[..floor_char_boundary(s, remaining)]);
+
+        let full = std::mem::take(&mut self.head) + s;
+        self.overflow = Some(self.create(&full));
+
+        println!("{}", self.notice());
+    }
+
+    /// Creates compressed file of full output, starting with `s`
+    fn create(&self, s: &str) -> Overflow {
+        let seq = SEQ.fetch_add(1, Ordering::Relaxed);
+        let secs = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+
+        let key = self.hook_key.trim_start_matches('@');
+        let name = format!("hook-output-{secs}-{seq}-{key}.gz");
+        let path = self.dir.join(name);
+
+        match File::create(&path) {
+            Ok(file) => {
+                let mut overflow = Overflow::File(
+                    path,
+                    GzEncoder::new(file, Compression::default()),
+                );
+                overflow.write(s);
+
+                overflow
+            }
+            Err(err) => {
+                log::warn!(
+                    "{}: cannot store full output to {}: {err}",
+                    self.hook_key,
+                    path.display(),
+                );
+
+                Overflow::Discard
+            }
+        }
+    }
+
+    fn notice(&self) -> String {
+        let stored = match self.overflow {
+            Some(Overflow::File(ref path, _)) => {
+                format!("full output in {}", path.display())
+            }
+            _ => "full output discarded".to_string(),
+        };
+
+        format!(
+            "\n[{}: output truncated at {} bytes, {stored}]",
+            self.hook_key, self.limit,
+        )
+    }
+
+    fn finish(self) -> Option<Truncated> {
+        let path = match self.overflow? {
+            Overflow::File(path, encoder) => {
+                match encoder.finish() {
+                    Ok(_) => Some(path),
+                    Err(err) => {
+                        log::warn!(
+                            "{}: cannot store full output to {}: {err}",
+                            self.hook_key,
+                            path.display(),
+                        );
+
+                        None
+                    }
+                }
+            }
+            Overflow::Discard => None,
+        };
+
+        Some(Truncated {
+            size: self.written,
+            path,
+        })
+    }
+}
+
+impl Overflow {
+    fn write(&mut self, s: &str) {
+        if let Self::File(_, encoder) = self {
+            if encoder.write_all(s.as_bytes()).is_err() {
+                *self = Self::Discard;
+            }
+        }
+    }
+}
+
+/// Returns largest char boundary of `s` not greater than `i`
+fn floor_char_boundary(s: &str, i: usize) -> usize {
+    (0..=i.min(s.len()))
+        .rev()
+        .find(|i| s.is_char_boundary(*i))
+        .unwrap_or(0)
+}
+
+#[test]
+fn test_output_truncate() {
+    use std::io::Read;
+
+    use flate2::read::GzDecoder;
+
+    let dir = std::env::temp_dir().join("ali-rs-test-hook-output");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let read_gz = |path: &Path| {
+        let mut s = String::new();
+        GzDecoder::new(File::open(path).unwrap())
+            .read_to_string(&mut s)
+            .unwrap();
+
+        s
+    };
+
+    // Output within limit is not stored
+    let mut capture = Capture::new("@test", 8, &dir);
+    capture.write("1234");
+    capture.write("5678");
+    assert!(capture.finish().is_none());
+
+    let mut capture = Capture::new("@test-print", 8, &dir);
+    capture.write("12345");
+    capture.write("678ไทย");
+    capture.write("more");
+    let truncated = capture.finish().unwrap();
+    assert_eq!(truncated.size, 21);
+    assert_eq!(read_gz(&truncated.path.unwrap()), "12345678ไทยmore");
+
+    let mut s = "1234".to_string();
+    assert!(truncate_with(Capture::new("@test", 8, &dir), &mut s).is_none());
+    assert_eq!(s, "1234");
+
+    let mut s = "ไทย".repeat(4);
+    let capture = Capture::new("@test", 8, &dir);
+    let truncated = truncate_with(capture, &mut s).unwrap();
+    assert!(s.starts_with("ไท\n[@test: output truncated at 8 bytes"));
+    assert_eq!(read_gz(&truncated.path.unwrap()), "ไทย".repeat(4));
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
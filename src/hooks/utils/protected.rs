@@ -0,0 +1,353 @@
+//! Protected paths that hooks may never write, as a defense against
+//! manifest typos with catastrophic targets. Hooks check every file
+//! they write with [`check`], and hook `@unprotect` lifts protection
+//! of some paths for the hook it wraps. Plugin hooks write files
+//! themselves, so protected paths are compared before and after they
+//! run with [`watch`].
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::os::unix::fs::MetadataExt;
+use std::path::{
+    Component,
+    Path,
+    PathBuf,
+};
+use std::sync::OnceLock;
+
+use crate::errors::AliError;
+
+/// Paths always protected. `/etc/shadow` is managed by ali-rs itself
+/// (e.g. manifest key `rootpasswd`), and `/run/archiso` is the live ISO.
+pub(crate) const DEFAULTS: &[&str] = &[
+    "/etc/shadow",
+    "/etc/gshadow",
+    "/boot/efi/EFI/Microsoft",
+    "/run/archiso",
+];
+
+/// Protected paths in addition to [`DEFAULTS`],
+/// set once from manifest key `protected_paths`
+static EXTRA: OnceLock<Vec<String>> = OnceLock::new();
+
+thread_local! {
+    /// Paths unprotected by `@unprotect` for the hook being run
+    static UNPROTECTED: RefCell<Vec<String>> =
+        const { RefCell::new(Vec::new()) };
+}
+
+/// Protects `paths` in addition to [`DEFAULTS`].
+/// Only the first call has effect.
+pub(crate) fn set_extra(paths: Vec<String>) {
+    let _ = EXTRA.set(paths);
+}
+
+/// Lifts protection of some paths until dropped
+pub(crate) struct Unprotect {
+    len: usize,
+}
+
+/// Unprotects `paths` until the returned guard is dropped
+pub(crate) fn unprotect(paths: &[String]) -> Unprotect {
+    UNPROTECTED.with_borrow_mut(|unprotected| {
+        let len = unprotected.len();
+        unprotected.extend(paths.iter().cloned());
+
+        Unprotect { len }
+    })
+}
+
+impl Drop for Unprotect {
+    fn drop(&mut self) {
+        UNPROTECTED.with_borrow_mut(|unprotected| {
+            unprotected.truncate(self.len);
+        });
+    }
+}
+
+/// Returns error if hook `hook_key` may not write `file`, a path on the
+/// live system. `file` is protected if either itself or its path
+/// relative to `root` is, or is under, a protected path.
+pub(crate) fn check(
+    hook_key: &str,
+    root: &str,
+    file: &str,
+) -> Result<(), AliError> {
+    crate::utils::readonly::check(&format!("write {file}"))?;
+
+    match protected_by(hook_key, root, Path::new(file)) {
+        Some(protected) => Err(refuse(hook_key, file, protected)),
+        None => Ok(()),
+    }
+}
+
+/// Returns the protected path `file` is under, if not unprotected
+fn protected_by(
+    hook_key: &str,
+    root: &str,
+    file: &Path,
+) -> Option<&'static str> {
+    let live = resolve(file);
+    let root = resolve(Path::new(root));
+    let relative = live
+        .strip_prefix(&root)
+        .map(|p| Path::new("/").join(p))
+        .unwrap_or_else(|_| live.clone());
+
+    let protected =
+        paths().find(|p| is_under(&live, p) || is_under(&relative, p))?;
+
+    let unprotected = UNPROTECTED.with_borrow(|unprotected| {
+        unprotected
+            .iter()
+            .any(|p| is_under(&live, p) || is_under(&relative, p))
+    });
+
+    if unprotected {
+        log::warn!("{hook_key}: writing protected path {}", file.display());
+        return None;
+    }
+
+    Some(protected)
+}
+
+/// All protected paths, [`DEFAULTS`] and [`EXTRA`]
+fn paths() -> impl Iterator<Item = &'static str> {
+    let extra = EXTRA.get().map(Vec::as_slice).unwrap_or_default();
+    DEFAULTS
+        .iter()
+        .copied()
+        .chain(extra.iter().map(String::as_str))
+}
+
+fn refuse(hook_key: &str, file: &str, protected: &str) -> AliError {
+    AliError::HookError(format!(
+        "{hook_key}: refusing to write {file} under protected path {protected}, \
+        wrap hook with @unprotect {protected} to override"
+    ))
+}
+
+/// Files under protected paths, both on the live system and under the
+/// root location, taken before a hook that writes files itself is run
+pub(crate) struct Watch {
+    root: String,
+    files: BTreeMap<PathBuf, Stat>,
+}
+
+/// What changes when a file is written, created, or replaced
+#[derive(PartialEq)]
+struct Stat {
+    dev: u64,
+    ino: u64,
+    len: u64,
+    mtime: (i64, i64),
+    ctime: (i64, i64),
+}
+
+/// Takes note of files under protected paths, to be compared
+/// with [`Watch::check`] after hooks ran
+pub(crate) fn watch(root: &str) -> Watch {
+    let mut files = BTreeMap::new();
+    for protected in paths() {
+        let protected = Path::new(protected);
+        let relative = protected.strip_prefix("/").unwrap_or(protected);
+
+        walk(&resolve(protected), None, &mut files);
+        walk(&resolve(&Path::new(root).join(relative)), None, &mut files);
+    }
+
+    Watch {
+        root: root.to_string(),
+        files,
+    }
+}
+
+impl Watch {
+    /// Returns error if hook `hook_key` wrote, created, or removed files
+    /// under protected paths since [`watch`]
+    pub(crate) fn check(&self, hook_key: &str) -> Result<(), AliError> {
+        let after = watch(&self.root).files;
+        let changed = self
+            .files
+            .iter()
+            .filter(|(path, stat)| after.get(*path) != Some(stat))
+            .map(|(path, _)| path)
+            .chain(after.keys().filter(|p| !self.files.contains_key(*p)));
+
+        for file in changed {
+            if let Some(protected) = protected_by(hook_key, &self.root, file)
+            {
+                let file = file.to_string_lossy();
+                return Err(refuse(hook_key, &file, protected));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Adds `path` and files under it to `files`, without following
+/// symlinks or crossing into other filesystems than `path`'s
+fn walk(path: &Path, dev: Option<u64>, files: &mut BTreeMap<PathBuf, Stat>) {
+    let Ok(meta) = std::fs::symlink_metadata(path) else {
+        return;
+    };
+
+    let stat = Stat {
+        dev: meta.dev(),
+        ino: meta.ino(),
+        len: meta.len(),
+        mtime: (meta.mtime(), meta.mtime_nsec()),
+        ctime: (meta.ctime(), meta.ctime_nsec()),
+    };
+
+    let descend = meta.is_dir() && dev.is_none_or(|dev| dev == stat.dev);
+    let dev = dev.unwrap_or(stat.dev);
+    files.insert(path.to_path_buf(), stat);
+
+    if !descend {
+        return;
+    }
+
+    for entry in std::fs::read_dir(path).into_iter().flatten().flatten() {
+        walk(&entry.path(), Some(dev), files);
+    }
+}
+
+/// Like [`crate::utils::fs::write_under`], but checks `path` first
+pub(crate) fn write_under(
+    hook_key: &str,
+    base: &str,
+    path: &str,
+    content: &str,
+) -> Result<(), AliError> {
+    write_bytes_under(hook_key, base, path, content.as_bytes())
+}
+
+/// Like [`write_under`], but for binary `content`
+pub(crate) fn write_bytes_under(
+    hook_key: &str,
+    base: &str,
+    path: &str,
+    content: &[u8],
+) -> Result<(), AliError> {
+    check(hook_key, base, &crate::utils::fs::path_under(base, path)?)?;
+    crate::utils::fs::write_bytes_under(base, path, content)
+}
+
+/// Like [`write_under`], but creates `path` with permission `mode`,
+/// set before any content is written
+pub(crate) fn write_mode_under(
+    hook_key: &str,
+    base: &str,
+    path: &str,
+    content: &str,
+    mode: u32,
+) -> Result<(), AliError> {
+    let filename = crate::utils::fs::path_under(base, path)?;
+    if let Some(parent) = Path::new(&filename).parent() {
+        crate::utils::fs::mkdir_p(&parent.to_string_lossy())?;
+    }
+
+    check(hook_key, base, &filename)?;
+    crate::utils::fs::write_mode(&filename, content.as_bytes(), mode)
+}
+
+fn is_under(path: &Path, protected: &str) -> bool {
+    path.starts_with(normalize(Path::new(protected)))
+}
+
+/// Resolves symlinks of the longest existing ancestor of `path`,
+/// so that protected paths cannot be reached via symlinks
+fn resolve(path: &Path) -> PathBuf {
+    let path = normalize(path);
+    for ancestor in path.ancestors() {
+        if let Ok(real) = std::fs::canonicalize(ancestor) {
+            return match path.strip_prefix(ancestor) {
+                Ok(rest) if !rest.as_os_str().is_empty() => real.join(rest),
+                _ => real,
+            };
+        }
+    }
+
+    path
+}
+
+/// Lexically removes `.`, `..`, and repeated separators from `path`
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            c => normalized.push(c),
+        }
+    }
+
+    normalized
+}
+
+#[test]
+fn test_check_protected() {
+    let root = std::env::temp_dir().join("ali-rs-test-protected");
+    let _ = std::fs::remove_dir_all(&root);
+    std::fs::create_dir_all(root.join("etc")).unwrap();
+    std::fs::create_dir_all(root.join("boot/efi/EFI/Microsoft")).unwrap();
+    std::os::unix::fs::symlink("/etc", root.join("link")).unwrap();
+
+    let r = root.to_str().unwrap();
+    let check_root = |file: &str| check("@test", r, &format!("{r}{file}"));
+
+    assert!(check_root("/etc/hostname").is_ok());
+    assert!(check_root("/etc/shadow-foo").is_ok());
+    assert!(check_root("/etc/shadow").is_err());
+    assert!(check_root("/etc/../etc/./shadow").is_err());
+    assert!(check_root("/boot/efi/EFI/Microsoft/Boot/bootmgfw.efi").is_err());
+    assert!(check_root("/boot/efi/EFI/arch/grubx64.efi").is_ok());
+
+    // Symlinks are resolved, and live paths are protected too
+    assert!(check_root("/link/shadow").is_err());
+    assert!(check("@test", r, "/run/archiso/x").is_err());
+
+    {
+        let _guard = unprotect(&["/etc/shadow".to_string()]);
+        assert!(check_root("/etc/shadow").is_ok());
+        assert!(check_root("/etc/gshadow").is_err());
+    }
+    assert!(check_root("/etc/shadow").is_err());
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn test_watch() {
+    let root = std::env::temp_dir()
+        .join(format!("ali-rs-test-watch-{}", std::process::id()));
+    std::fs::create_dir_all(root.join("etc")).unwrap();
+    std::fs::write(root.join("etc/shadow"), "root:x:1::::::\n").unwrap();
+
+    let r = root.to_str().unwrap();
+
+    let w = watch(r);
+    std::fs::write(root.join("etc/hostname"), "foo\n").unwrap();
+    assert!(w.check("@x-test").is_ok());
+
+    std::fs::write(root.join("etc/shadow"), "root::1::::::\n").unwrap();
+    assert!(w.check("@x-test").is_err());
+
+    let w = watch(r);
+    std::fs::write(root.join("etc/gshadow"), "root:::\n").unwrap();
+    assert!(w.check("@x-test").is_err());
+
+    let w = watch(r);
+    std::fs::remove_file(root.join("etc/shadow")).unwrap();
+    {
+        let _guard = unprotect(&["/etc/shadow".to_string()]);
+        assert!(w.check("@x-test").is_ok());
+    }
+    assert!(w.check("@x-test").is_err());
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
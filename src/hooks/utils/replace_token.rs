@@ -1,6 +1,7 @@
 use serde_json::json;
 
 use crate::errors::AliError;
+use crate::utils::json;
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub(crate) struct ReplaceToken {
@@ -25,13 +26,14 @@ impl ReplaceToken {
     }
 }
 
-impl ToString for ReplaceToken {
-    fn to_string(&self) -> String {
-        json!({
+impl std::fmt::Display for ReplaceToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let j = json!({
             "token": self.token,
             "value": self.value,
-        })
-        .to_string()
+        });
+
+        write!(f, "{}", json::to_string(&j))
     }
 }
 
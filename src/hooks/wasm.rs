@@ -0,0 +1,77 @@
+use wasmtime::{
+    Engine,
+    Linker,
+    Module,
+    Store,
+};
+use wasmtime_wasi::pipe::MemoryOutputPipe;
+use wasmtime_wasi::preview1::{
+    self,
+    WasiP1Ctx,
+};
+use wasmtime_wasi::{
+    DirPerms,
+    FilePerms,
+    I32Exit,
+    WasiCtxBuilder,
+};
+
+use crate::errors::AliError;
+
+/// Max size of ActionHook JSON printed by WASM plugins
+const STDOUT_CAPACITY: usize = 1 << 20;
+
+/// Runs WASI (preview 1) command module `module` with `args` and `envs`,
+/// with `root_location` preopened as guest `/` and no other filesystem
+/// or network access, and returns its stdout. Guest stderr is passed through.
+pub(super) fn exec(
+    key: &str,
+    module: &str,
+    args: &[String],
+    envs: &[(&str, &str)],
+    root_location: &str,
+) -> Result<Vec<u8>, AliError> {
+    let wasm_err = |err: wasmtime::Error| {
+        AliError::HookError(format!("{key}: wasm plugin {module}: {err:#}"))
+    };
+
+    let stdout = MemoryOutputPipe::new(STDOUT_CAPACITY);
+    let argv: Vec<&str> = std::iter::once(module)
+        .chain(args.iter().map(String::as_str))
+        .collect();
+
+    let wasi = WasiCtxBuilder::new()
+        .args(&argv)
+        .envs(envs)
+        .stdout(stdout.clone())
+        .inherit_stderr()
+        .preopened_dir(root_location, "/", DirPerms::all(), FilePerms::all())
+        .map_err(wasm_err)?
+        .build_p1();
+
+    let engine = Engine::default();
+    let module = Module::from_file(&engine, module).map_err(wasm_err)?;
+
+    let mut linker: Linker<WasiP1Ctx> = Linker::new(&engine);
+    preview1::add_to_linker_sync(&mut linker, |ctx| ctx).map_err(wasm_err)?;
+
+    let mut store = Store::new(&engine, wasi);
+    let start = linker
+        .module(&mut store, "", &module)
+        .and_then(|linker| linker.get_default(&mut store, ""))
+        .and_then(|start| start.typed::<(), ()>(&store))
+        .map_err(wasm_err)?;
+
+    // proc_exit(0) is reported as an error by wasmtime
+    match start.call(&mut store, ()) {
+        Ok(()) => {}
+        Err(err) => {
+            match err.downcast_ref::<I32Exit>() {
+                Some(I32Exit(0)) => {}
+                _ => return Err(wasm_err(err)),
+            }
+        }
+    }
+
+    Ok(stdout.contents().to_vec())
+}
@@ -0,0 +1,690 @@
+use std::net::IpAddr;
+use std::sync::LazyLock;
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use serde_json::json;
+
+use super::args::{
+    self,
+    Arg,
+};
+use super::constants::wireguard::*;
+use super::utils::{
+    output,
+    protected,
+};
+use super::{
+    extract_key_and_parts_shlex,
+    wrap_bad_hook_cmd,
+    ActionHook,
+    Caller,
+    Hook,
+    ModeHook,
+    ParseError,
+    KEY_WIREGUARD,
+    KEY_WIREGUARD_PRINT,
+};
+use crate::errors::AliError;
+use crate::linux::systemd;
+use crate::utils::fs::path_under;
+use crate::utils::json;
+use crate::utils::secrets;
+use crate::utils::shell;
+
+const ARGS: &[Arg] = &[
+    Arg::positional("name", "NAME"),
+    Arg::key_required("address", "CIDR[,CIDR..]"),
+    Arg::key_required("peer", "PUBLIC_KEY"),
+    Arg::key_required("endpoint", "HOST:PORT"),
+    Arg::key_required("allowed_ips", "CIDR[,CIDR..]"),
+    Arg::one_of(&[("private_key", "KEY"), ("private_key_file", "FILE")]),
+    Arg::one_of(&[("psk", "KEY"), ("psk_file", "FILE")]),
+    Arg::key("listen_port", "PORT"),
+    Arg::key("dns", "IP[,IP..]"),
+    Arg::key("keepalive", "SECONDS"),
+    Arg::key("endpoint_ip", "IP"),
+];
+
+static USAGE: LazyLock<String> = LazyLock::new(|| args::usage(ARGS));
+
+/// Example arguments, for `ali-rs hooks schema`
+pub(super) const EXAMPLE: &str = "wg0 address=10.8.0.2/32 peer=HIgo9xNzJMWLKASShiTqIybxZ0U3wGLiUeJ1PKf8ykw= endpoint=vpn.example.com:51820 allowed_ips=10.8.0.0/24";
+
+/// Keyword arguments, for shell completion
+pub(super) const KEYWORDS: &[&str] = &[
+    "address=",
+    "peer=",
+    "endpoint=",
+    "allowed_ips=",
+    "private_key=",
+    "private_key_file=",
+    "psk=",
+    "psk_file=",
+    "listen_port=",
+    "dns=",
+    "keepalive=",
+    "endpoint_ip=",
+];
+
+/// Generates private key, and prints it followed by its public key
+const SCRIPT_GENKEY: &str =
+    "umask 077 && key=$(wg genkey) && echo \"$key\" && echo \"$key\" | wg pubkey";
+
+#[derive(Debug, Clone, PartialEq)]
+struct WireGuard {
+    /// Interface name, also name of config file and wg-quick instance
+    name: String,
+    addresses: Vec<String>,
+    /// Private key of this machine, generated if `None`
+    private_key: Option<Secret>,
+    listen_port: Option<u16>,
+    dns: Vec<String>,
+    /// Public key of peer
+    peer: String,
+    psk: Option<Secret>,
+    endpoint: String,
+    allowed_ips: Vec<String>,
+    /// Persistent keepalive interval in seconds, 0 disables it
+    keepalive: u16,
+    /// Address of endpoint host, added to hosts file
+    endpoint_ip: Option<String>,
+}
+
+/// Key given inline, or read from file on the live system when
+/// the hook runs, so that the key does not have to live in the manifest
+#[derive(Debug, Clone, PartialEq)]
+enum Secret {
+    Inline(String),
+    File(String),
+}
+
+struct HookWireGuard {
+    wireguard: WireGuard,
+    mode_hook: ModeHook,
+}
+
+pub(super) fn parse(k: &str, cmd: &str) -> Result<Box<dyn Hook>, ParseError> {
+    match k {
+        KEY_WIREGUARD | KEY_WIREGUARD_PRINT => {
+            match HookWireGuard::try_from(cmd) {
+                Err(err) => Err(wrap_bad_hook_cmd(err, &USAGE)),
+                Ok(hook) => Ok(Box::new(hook)),
+            }
+        }
+
+        key => panic!("unknown key {key}"),
+    }
+}
+
+impl Hook for HookWireGuard {
+    fn base_key(&self) -> &'static str {
+        KEY_WIREGUARD
+    }
+
+    /// `@wireguard <NAME> address=<CIDR> peer=<PUBLIC_KEY> endpoint=<HOST:PORT> allowed_ips=<CIDR>`
+    ///
+    /// Examples:
+    ///
+    /// 1. Tunnel wg0 to VPN server, with generated private key
+    ///
+    /// ```txt
+    /// @wireguard wg0 address=10.8.0.2/32 peer=<PUBLIC_KEY> endpoint=vpn.example.com:51820 allowed_ips=10.8.0.0/24
+    /// ```
+    ///
+    /// 2. Same, with private key read from live system,
+    ///    and endpoint resolved via hosts file
+    ///
+    /// ```txt
+    /// @wireguard wg0 address=10.8.0.2/32 peer=<PUBLIC_KEY> endpoint=vpn.example.com:51820 allowed_ips=10.8.0.0/24 private_key_file=/root/wg0.key endpoint_ip=203.0.113.5
+    /// ```
+    fn usage(&self) -> &'static str {
+        &USAGE
+    }
+
+    fn mode(&self) -> ModeHook {
+        self.mode_hook.clone()
+    }
+
+    fn should_chroot(&self) -> bool {
+        true
+    }
+
+    fn prefer_caller(&self, caller: &Caller) -> bool {
+        matches!(caller, Caller::ManifestChroot | Caller::Cli)
+    }
+
+    fn abort_if_no_mount(&self) -> bool {
+        true
+    }
+
+    fn run_hook(
+        &self,
+        _caller: &Caller,
+        root_location: &str,
+    ) -> Result<ActionHook, AliError> {
+        apply_wireguard(
+            &self.hook_key(),
+            &self.mode_hook,
+            &self.wireguard,
+            root_location,
+        )
+    }
+}
+
+impl TryFrom<&str> for HookWireGuard {
+    type Error = AliError;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        let (hook_key, parts) = extract_key_and_parts_shlex(s)?;
+        let mode_hook = match hook_key.as_str() {
+            KEY_WIREGUARD => ModeHook::Normal,
+            KEY_WIREGUARD_PRINT => ModeHook::Print,
+            key => panic!("unexpected key {key}"),
+        };
+
+        let args = args::parse(&hook_key, &parts[1..], ARGS)?;
+
+        let name = args.required("name")?.to_string();
+
+        if !is_interface_name(&name) {
+            return Err(AliError::BadHookCmd(format!(
+                "{hook_key}: bad interface name {name}"
+            )));
+        }
+
+        let addresses = args.required("address")?;
+        let peer = args.required("peer")?;
+        let endpoint = args.required("endpoint")?;
+        let allowed_ips = args.required("allowed_ips")?;
+
+        let addresses = parse_cidrs(&hook_key, "address", addresses)?;
+        let allowed_ips = parse_cidrs(&hook_key, "allowed_ips", allowed_ips)?;
+
+        if !is_key(peer) {
+            return Err(AliError::BadHookCmd(format!(
+                "{hook_key}: bad peer public key {peer}"
+            )));
+        }
+
+        let endpoint_host = parse_endpoint(&hook_key, endpoint)?;
+
+        let private_key = parse_secret(
+            &hook_key,
+            ("private_key", args.get("private_key")),
+            ("private_key_file", args.get("private_key_file")),
+        )?;
+
+        let psk = parse_secret(
+            &hook_key,
+            ("psk", args.get("psk")),
+            ("psk_file", args.get("psk_file")),
+        )?;
+
+        let listen_port = match args.get("listen_port") {
+            None => None,
+            Some(port) => Some(parse_number(&hook_key, "listen_port", port)?),
+        };
+
+        let keepalive = match args.get("keepalive") {
+            None => DEFAULT_KEEPALIVE,
+            Some(secs) => parse_number(&hook_key, "keepalive", secs)?,
+        };
+
+        let dns = match args.get("dns") {
+            None => vec![],
+            Some(dns) => dns.split(',').map(String::from).collect(),
+        };
+
+        if let Some(ip) = dns.iter().find(|ip| ip.parse::<IpAddr>().is_err()) {
+            return Err(AliError::BadHookCmd(format!(
+                "{hook_key}: bad dns {ip}"
+            )));
+        }
+
+        let endpoint_ip = match args.get("endpoint_ip") {
+            None => None,
+            Some(ip) if ip.parse::<IpAddr>().is_err() => {
+                return Err(AliError::BadHookCmd(format!(
+                    "{hook_key}: bad endpoint_ip {ip}"
+                )));
+            }
+            Some(_) if endpoint_host.parse::<IpAddr>().is_ok() => {
+                return Err(AliError::BadHookCmd(format!(
+                    "{hook_key}: endpoint_ip requires endpoint with host name, got {endpoint}"
+                )));
+            }
+            Some(ip) => Some(ip.to_string()),
+        };
+
+        Ok(HookWireGuard {
+            wireguard: WireGuard {
+                name,
+                addresses,
+                private_key,
+                listen_port,
+                dns,
+                peer: peer.to_string(),
+                psk,
+                endpoint: endpoint.to_string(),
+                allowed_ips,
+                keepalive,
+                endpoint_ip,
+            },
+            mode_hook,
+        })
+    }
+}
+
+/// Interface names accepted by wg-quick(8)
+fn is_interface_name(name: &str) -> bool {
+    (1..=15).contains(&name.len())
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "_=+.-".contains(c))
+}
+
+/// Returns whether `s` is a base64-encoded Curve25519 key
+fn is_key(s: &str) -> bool {
+    BASE64.decode(s).is_ok_and(|key| key.len() == 32)
+}
+
+/// Parses comma-separated list of `<IP>/<PREFIX>`
+fn parse_cidrs(
+    hook_key: &str,
+    key: &str,
+    s: &str,
+) -> Result<Vec<String>, AliError> {
+    let is_cidr = |cidr: &str| {
+        let Some((ip, prefix)) = cidr.split_once('/') else {
+            return false;
+        };
+
+        let max = match ip.parse::<IpAddr>() {
+            Ok(IpAddr::V4(_)) => 32,
+            Ok(IpAddr::V6(_)) => 128,
+            Err(_) => return false,
+        };
+
+        prefix.parse::<u8>().is_ok_and(|prefix| prefix <= max)
+    };
+
+    let cidrs: Vec<String> = s.split(',').map(String::from).collect();
+    if let Some(cidr) = cidrs.iter().find(|cidr| !is_cidr(cidr)) {
+        return Err(AliError::BadHookCmd(format!(
+            "{hook_key}: bad {key} {cidr}, expecting <IP>/<PREFIX>"
+        )));
+    }
+
+    Ok(cidrs)
+}
+
+/// Parses `<HOST>:<PORT>` or `[<IPv6>]:<PORT>`, returning host
+fn parse_endpoint<'a>(
+    hook_key: &str,
+    endpoint: &'a str,
+) -> Result<&'a str, AliError> {
+    let bad_endpoint = || {
+        AliError::BadHookCmd(format!(
+            "{hook_key}: bad endpoint {endpoint}, expecting <HOST>:<PORT>"
+        ))
+    };
+
+    let (host, port) = endpoint.rsplit_once(':').ok_or_else(bad_endpoint)?;
+    port.parse::<u16>().map_err(|_| bad_endpoint())?;
+
+    let host = match host.strip_prefix('[') {
+        Some(host) => host.strip_suffix(']').ok_or_else(bad_endpoint)?,
+        None => host,
+    };
+
+    let is_bracketed = endpoint.starts_with('[');
+    if host.is_empty() || (host.contains([':', ' ']) && !is_bracketed) {
+        return Err(bad_endpoint());
+    }
+
+    Ok(host)
+}
+
+/// Parses mutually exclusive inline and file keys of secret
+fn parse_secret(
+    hook_key: &str,
+    (key_inline, inline): (&str, Option<&str>),
+    (key_file, file): (&str, Option<&str>),
+) -> Result<Option<Secret>, AliError> {
+    match (inline, file) {
+        (None, None) => Ok(None),
+        (Some(key), None) if is_key(key) || secrets::name(key).is_some() => {
+            Ok(Some(Secret::Inline(key.to_string())))
+        }
+        (Some(_), None) => {
+            Err(AliError::BadHookCmd(format!("{hook_key}: bad {key_inline}")))
+        }
+        (None, Some(file)) => Ok(Some(Secret::File(file.to_string()))),
+        (Some(_), Some(_)) => {
+            Err(AliError::BadHookCmd(format!(
+                "{hook_key}: {key_inline} and {key_file} are mutually exclusive, but found both"
+            )))
+        }
+    }
+}
+
+fn parse_number(hook_key: &str, key: &str, s: &str) -> Result<u16, AliError> {
+    s.parse().map_err(|err| {
+        AliError::BadHookCmd(format!("{hook_key}: bad {key} {s}: {err}"))
+    })
+}
+
+/// Installs wireguard-tools, writes tunnel config and hosts entry,
+/// and enables wg-quick for the tunnel in the new system
+fn apply_wireguard(
+    hook_key: &str,
+    mode_hook: &ModeHook,
+    wireguard: &WireGuard,
+    root_location: &str,
+) -> Result<ActionHook, AliError> {
+    let cmd_install = format!("pacman -S --needed --noconfirm {PACKAGE}");
+    let filename = wireguard.filename();
+    let service = wireguard.service();
+
+    if matches!(mode_hook, ModeHook::Print) {
+        let private_key = match wireguard.private_key {
+            None => "<generated>",
+            Some(_) => "<redacted>",
+        };
+        let psk = wireguard.psk.as_ref().map(|_| "<redacted>");
+
+        output::println(&format!("# {cmd_install}"));
+        output::println(&format!(
+            "# {filename}\n{}",
+            wireguard.encode_conf(private_key, psk),
+        ));
+        if let Some(entry) = wireguard.encode_hosts() {
+            output::println(&format!("# {FILENAME_HOSTS}\n{entry}"));
+        }
+        output::println(&format!("# enable {service}"));
+
+        return Ok(ActionHook::WireGuard(wireguard.report(None)));
+    }
+
+    match root_location {
+        "/" => shell::sh_c(&cmd_install)?,
+        _ => shell::arch_chroot(root_location, &cmd_install)?,
+    }
+
+    let (private_key, public_key) = match &wireguard.private_key {
+        Some(secret) => (secret.resolve("private_key")?, None),
+        None => {
+            let (private_key, public_key) = generate_keys(root_location)?;
+            log::info!(
+                "{hook_key}: generated key for {}, public key: {public_key}",
+                wireguard.name,
+            );
+
+            (private_key, Some(public_key))
+        }
+    };
+
+    let psk = match &wireguard.psk {
+        Some(secret) => Some(secret.resolve("psk")?),
+        None => None,
+    };
+
+    let conf = wireguard.encode_conf(&private_key, psk.as_deref());
+    protected::write_mode_under(
+        hook_key,
+        root_location,
+        &filename,
+        &conf,
+        0o600,
+    )?;
+
+    if let Some(entry) = wireguard.encode_hosts() {
+        let path = path_under(root_location, FILENAME_HOSTS)?;
+        let hosts = std::fs::read_to_string(&path).unwrap_or_default();
+
+        if !hosts.lines().any(|line| line == entry.trim_end()) {
+            let hosts = match hosts.is_empty() || hosts.ends_with('\n') {
+                true => hosts + &entry,
+                false => format!("{hosts}\n{entry}"),
+            };
+
+            protected::write_mode_under(
+                hook_key,
+                root_location,
+                FILENAME_HOSTS,
+                &hosts,
+                0o644,
+            )?;
+        }
+    }
+
+    systemd::enable_service(root_location, &service, "multi-user.target")?;
+
+    Ok(ActionHook::WireGuard(wireguard.report(public_key.as_deref())))
+}
+
+/// Generates key pair with wg(8) in the new system,
+/// returning private key and public key
+fn generate_keys(root_location: &str) -> Result<(String, String), AliError> {
+    let output = match root_location {
+        "/" => shell::exec_with_output("sh", &["-c", SCRIPT_GENKEY])?,
+        _ => {
+            shell::exec_with_output(
+                "arch-chroot",
+                &[root_location, "sh", "-c", SCRIPT_GENKEY],
+            )?
+        }
+    };
+
+    let output = String::from_utf8_lossy(&output);
+    let mut lines = output.lines().map(str::trim);
+
+    match (lines.next(), lines.next()) {
+        (Some(private_key), Some(public_key))
+            if is_key(private_key) && is_key(public_key) =>
+        {
+            Ok((private_key.to_string(), public_key.to_string()))
+        }
+        _ => {
+            Err(AliError::HookError(format!(
+                "{KEY_WIREGUARD}: unexpected output from wg genkey"
+            )))
+        }
+    }
+}
+
+impl WireGuard {
+    fn filename(&self) -> String {
+        format!("{DIR_WIREGUARD}/{}.conf", self.name)
+    }
+
+    fn service(&self) -> String {
+        format!("wg-quick@{}.service", self.name)
+    }
+
+    /// Encodes wg-quick(8) config with keys `private_key` and `psk`
+    fn encode_conf(&self, private_key: &str, psk: Option<&str>) -> String {
+        let mut conf = format!(
+            "# Installed by ali-rs hook @wireguard\n[Interface]\nPrivateKey = {private_key}\nAddress = {}\n",
+            self.addresses.join(", "),
+        );
+
+        if let Some(port) = self.listen_port {
+            conf.push_str(&format!("ListenPort = {port}\n"));
+        }
+        if !self.dns.is_empty() {
+            conf.push_str(&format!("DNS = {}\n", self.dns.join(", ")));
+        }
+
+        conf.push_str(&format!("\n[Peer]\nPublicKey = {}\n", self.peer));
+        if let Some(psk) = psk {
+            conf.push_str(&format!("PresharedKey = {psk}\n"));
+        }
+
+        conf.push_str(&format!(
+            "Endpoint = {}\nAllowedIPs = {}\n",
+            self.endpoint,
+            self.allowed_ips.join(", "),
+        ));
+
+        if self.keepalive != 0 {
+            conf.push_str(&format!(
+                "PersistentKeepalive = {}\n",
+                self.keepalive
+            ));
+        }
+
+        conf
+    }
+
+    /// Encodes hosts(5) entry of endpoint host, if `endpoint_ip` is given
+    fn encode_hosts(&self) -> Option<String> {
+        let ip = self.endpoint_ip.as_ref()?;
+        let (host, _) = self.endpoint.rsplit_once(':')?;
+
+        Some(format!("{ip} {host} # ali-rs @wireguard {}\n", self.name))
+    }
+
+    /// JSON report, with generated public key if any. Secrets are omitted.
+    fn report(&self, public_key: Option<&str>) -> String {
+        json::to_string(&json!({
+            "name": self.name,
+            "address": self.addresses,
+            "peer": self.peer,
+            "endpoint": self.endpoint,
+            "allowed_ips": self.allowed_ips,
+            "endpoint_ip": self.endpoint_ip,
+            "public_key": public_key,
+        }))
+    }
+}
+
+impl Secret {
+    fn resolve(&self, key: &str) -> Result<String, AliError> {
+        let secret = match self {
+            Secret::Inline(secret) => secrets::resolve(secret)?,
+            Secret::File(path) => {
+                std::fs::read_to_string(path)
+                    .map_err(|err| {
+                        AliError::FileError(
+                            err,
+                            format!("{KEY_WIREGUARD}: reading {key} file {path}"),
+                        )
+                    })?
+                    .trim()
+                    .to_string()
+            }
+        };
+
+        if !is_key(&secret) {
+            return Err(AliError::HookError(format!(
+                "{KEY_WIREGUARD}: bad {key}"
+            )));
+        }
+
+        // Inline references were registered when resolved
+        if matches!(self, Secret::File(_)) {
+            secrets::register(&secret);
+        }
+
+        Ok(secret)
+    }
+}
+
+#[test]
+fn test_parse_wireguard() {
+    const PEER: &str = "HIgo9xNzJMWLKASShiTqIybxZ0U3wGLiUeJ1PKf8ykw=";
+    const KEY: &str = "yAnz5TF+lXXJte14tji3zlMNq+hd2rYUIgJBgB3fBmk=";
+
+    let should_pass = vec![
+        (
+            format!("@wireguard wg0 address=10.8.0.2/32 peer={PEER} endpoint=vpn.example.com:51820 allowed_ips=10.8.0.0/24"),
+            WireGuard {
+                name: "wg0".into(),
+                addresses: vec!["10.8.0.2/32".into()],
+                private_key: None,
+                listen_port: None,
+                dns: vec![],
+                peer: PEER.into(),
+                psk: None,
+                endpoint: "vpn.example.com:51820".into(),
+                allowed_ips: vec!["10.8.0.0/24".into()],
+                keepalive: DEFAULT_KEEPALIVE,
+                endpoint_ip: None,
+            },
+        ),
+        (
+            format!("@wireguard-print home address=10.8.0.2/32,fd00::2/128 peer={PEER} endpoint=[2001:db8::1]:51820 allowed_ips=0.0.0.0/0,::/0 private_key={KEY} psk_file=/root/psk listen_port=51821 dns=10.8.0.1 keepalive=0"),
+            WireGuard {
+                name: "home".into(),
+                addresses: vec!["10.8.0.2/32".into(), "fd00::2/128".into()],
+                private_key: Some(Secret::Inline(KEY.into())),
+                listen_port: Some(51821),
+                dns: vec!["10.8.0.1".into()],
+                peer: PEER.into(),
+                psk: Some(Secret::File("/root/psk".into())),
+                endpoint: "[2001:db8::1]:51820".into(),
+                allowed_ips: vec!["0.0.0.0/0".into(), "::/0".into()],
+                keepalive: 0,
+                endpoint_ip: None,
+            },
+        ),
+    ];
+
+    for (cmd, expected) in should_pass {
+        let hook = HookWireGuard::try_from(cmd.as_str()).unwrap();
+        assert_eq!(expected, hook.wireguard);
+    }
+
+    let base = format!(
+        "@wireguard wg0 address=10.8.0.2/32 peer={PEER} allowed_ips=10.8.0.0/24"
+    );
+    let should_err = vec![
+        format!("@wireguard address=10.8.0.2/32 peer={PEER} endpoint=vpn:51820 allowed_ips=10.8.0.0/24"),
+        base.clone(),
+        format!("{base} endpoint=vpn.example.com"),
+        format!("{base} endpoint=vpn.example.com:port"),
+        format!("{base} endpoint=vpn:51820 address=10.8.0.3/32"),
+        format!("{base} endpoint=vpn:51820 private_key=foo"),
+        format!("{base} endpoint=vpn:51820 private_key={KEY} private_key_file=/root/key"),
+        format!("{base} endpoint=203.0.113.5:51820 endpoint_ip=203.0.113.5"),
+        format!("{base} endpoint=vpn:51820 dns=vpn"),
+        format!("{base} endpoint=vpn:51820 foo=bar"),
+        format!("{base} endpoint=vpn:51820 wg1"),
+        format!("@wireguard wg0 address=10.8.0.2 peer={PEER} endpoint=vpn:51820 allowed_ips=10.8.0.0/24"),
+        format!("@wireguard wg0 address=10.8.0.2/33 peer={PEER} endpoint=vpn:51820 allowed_ips=10.8.0.0/24"),
+        format!("@wireguard wg0 address=10.8.0.2/32 peer=foo endpoint=vpn:51820 allowed_ips=10.8.0.0/24"),
+        format!("@wireguard wg-too-long-name0 address=10.8.0.2/32 peer={PEER} endpoint=vpn:51820 allowed_ips=10.8.0.0/24"),
+    ];
+
+    for cmd in should_err {
+        assert!(
+            HookWireGuard::try_from(cmd.as_str()).is_err(),
+            "expecting error for {cmd}"
+        );
+    }
+}
+
+#[test]
+fn test_encode_wireguard() {
+    const PEER: &str = "HIgo9xNzJMWLKASShiTqIybxZ0U3wGLiUeJ1PKf8ykw=";
+
+    let hook = HookWireGuard::try_from(
+        format!("@wireguard wg0 address=10.8.0.2/32 peer={PEER} endpoint=vpn.example.com:51820 allowed_ips=10.8.0.0/24,10.9.0.0/24 dns=10.8.0.1 endpoint_ip=203.0.113.5").as_str(),
+    )
+    .unwrap();
+
+    let wireguard = hook.wireguard;
+    assert_eq!("/etc/wireguard/wg0.conf", wireguard.filename());
+    assert_eq!("wg-quick@wg0.service", wireguard.service());
+    assert_eq!(
+        format!("# Installed by ali-rs hook @wireguard\n[Interface]\nPrivateKey = <generated>\nAddress = 10.8.0.2/32\nDNS = 10.8.0.1\n\n[Peer]\nPublicKey = {PEER}\nEndpoint = vpn.example.com:51820\nAllowedIPs = 10.8.0.0/24, 10.9.0.0/24\nPersistentKeepalive = 25\n"),
+        wireguard.encode_conf("<generated>", None),
+    );
+    assert_eq!(
+        Some("203.0.113.5 vpn.example.com # ali-rs @wireguard wg0\n".into()),
+        wireguard.encode_hosts(),
+    );
+    assert!(!wireguard.report(None).contains("PrivateKey"));
+}
@@ -5,6 +5,7 @@ use crate::hooks::{
     ActionHook,
     Caller,
     Hook,
+    HookSpec,
     ModeHook,
     ParseError,
     KEY_WRAPPER_MNT,
@@ -14,6 +15,11 @@ use crate::hooks::{
 const USAGE_MNT: &str = "<MOUNTPOINT> <HOOK_CMD>";
 const USAGE_NO_MNT: &str = "<HOOK_CMD>";
 
+/// Example arguments, for `ali-rs hooks schema`
+pub(super) const EXAMPLE_MNT: &str = "/mnt @quicknet eth0";
+pub(super) const EXAMPLE_NO_MNT: &str =
+    "@uncomment PORT /mnt/etc/ssh/sshd_config";
+
 struct Wrapper {
     inner: Box<dyn Hook>,
 }
@@ -76,6 +82,16 @@ impl Hook for WrapperMnt {
         self.unwrap_inner().abort_if_no_mount()
     }
 
+    fn spec(&self) -> HookSpec {
+        HookSpec {
+            key: self.base_key().to_string(),
+            mode: None,
+            chroot: None,
+            abort_if_no_mount: None,
+            ..HookSpec::new(self)
+        }
+    }
+
     fn run_hook(
         &self,
         caller: &Caller,
@@ -137,6 +153,16 @@ impl Hook for WrapperNoMnt {
         self.unwrap_inner().abort_if_no_mount()
     }
 
+    fn spec(&self) -> HookSpec {
+        HookSpec {
+            key: self.base_key().to_string(),
+            mode: None,
+            chroot: None,
+            abort_if_no_mount: None,
+            ..HookSpec::new(self)
+        }
+    }
+
     fn run_hook(
         &self,
         caller: &Caller,
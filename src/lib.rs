@@ -0,0 +1,85 @@
+//! ali-rs as a library, for programs embedding ali-rs.
+//!
+//! Downstream crates can add compiled-in hooks by implementing
+//! [`hooks::Hook`] and registering a constructor with
+//! [`hooks::register_hook`]. Registered hooks are then parsed, validated,
+//! and reported like ali-rs hooks, including in manifest keys `chroot`
+//! and `postinstall`.
+//!
+//! Keys of ali-rs hooks are enumerated by [`hooks::HookKey`], which
+//! parses from and displays as hook key strings, e.g. `@uncomment-print`.
+//!
+//! ```
+//! use ali_rs::errors::AliError;
+//! use ali_rs::hooks::{
+//!     self,
+//!     ActionHook,
+//!     Caller,
+//!     Hook,
+//!     ModeHook,
+//!     ParseError,
+//! };
+//!
+//! struct HookHello {
+//!     mode: ModeHook,
+//! }
+//!
+//! impl Hook for HookHello {
+//!     fn base_key(&self) -> &'static str {
+//!         "@hello"
+//!     }
+//!
+//!     fn usage(&self) -> &'static str {
+//!         "takes no arguments"
+//!     }
+//!
+//!     fn mode(&self) -> ModeHook {
+//!         self.mode.clone()
+//!     }
+//!
+//!     fn should_chroot(&self) -> bool {
+//!         false
+//!     }
+//!
+//!     fn prefer_caller(&self, _caller: &Caller) -> bool {
+//!         true
+//!     }
+//!
+//!     fn abort_if_no_mount(&self) -> bool {
+//!         false
+//!     }
+//!
+//!     fn run_hook(
+//!         &self,
+//!         _caller: &Caller,
+//!         root_location: &str,
+//!     ) -> Result<ActionHook, AliError> {
+//!         Ok(ActionHook::Plugin(format!("hello {root_location}")))
+//!     }
+//! }
+//!
+//! fn parse(k: &str, _cmd: &str) -> Result<Box<dyn Hook>, ParseError> {
+//!     let mode = match k {
+//!         "@hello-print" => ModeHook::Print,
+//!         _ => ModeHook::Normal,
+//!     };
+//!
+//!     Ok(Box::new(HookHello { mode }))
+//! }
+//!
+//! hooks::register_hook(&["@hello", "@hello-print"], parse);
+//!
+//! let action = hooks::apply_hook("@hello", Caller::Cli, "/alitarget");
+//! assert!(matches!(action, Ok(ActionHook::Plugin(s)) if s == "hello /alitarget"));
+//! ```
+
+mod ali;
+pub mod cli;
+mod config;
+mod constants;
+pub mod errors;
+pub mod hooks;
+mod linux;
+pub mod run;
+mod types;
+mod utils;
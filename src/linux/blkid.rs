@@ -0,0 +1,464 @@
+//! Resolves manifest disk selectors to concrete device nodes,
+//! since raw `/dev/sdX` names are unstable across boots of the live ISO.
+//!
+//! A disk `device` may be a udev link like `/dev/disk/by-id/<ID>`,
+//! or comma-separated conditions on lsblk(8) properties, e.g.
+//! `wwn=0x5002538e40a1b2c3`, `model=Samsung SSD 980,serial=S5GX`,
+//! or `tran=nvme,size>500G`. A selector must match exactly 1 disk.
+//!
+//! Other manifest keys refer to partitions of a selected disk with
+//! `<SELECTOR>-part<N>`, following udev by-id naming.
+//!
+//! After formatting, [`DeviceMap`] maps devices to their UUIDs
+//! for generated configs.
+
+use std::collections::HashMap;
+
+use serde::Deserialize;
+
+use crate::ali::{
+    Dm,
+    Manifest,
+};
+use crate::errors::AliError;
+use crate::types::blockdev::parse_human_bytes;
+use crate::utils::{
+    shell,
+    suggest,
+};
+
+const PREFIX_UDEV_LINK: &str = "/dev/disk/";
+const PARTITION_SEPARATOR: &str = "-part";
+const KEYS: [&str; 5] = ["wwn", "serial", "model", "tran", "size"];
+
+/// Whole disk as reported by lsblk(8) from udev
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct BlockDisk {
+    pub path: String,
+    pub size: u64,
+    pub tran: Option<String>,
+    pub model: Option<String>,
+    pub serial: Option<String>,
+    pub wwn: Option<String>,
+
+    #[serde(rename = "type")]
+    pub dev_type: String,
+}
+
+#[derive(Deserialize)]
+struct Lsblk {
+    blockdevices: Vec<BlockDisk>,
+}
+
+#[derive(Debug, PartialEq)]
+enum Cond {
+    Eq(String, String),
+    SizeEq(u64),
+    SizeGt(u64),
+    SizeLt(u64),
+}
+
+/// Returns whether disk `device` is a selector rather than a device node
+pub fn is_selector(device: &str) -> bool {
+    device.starts_with(PREFIX_UDEV_LINK) || !device.starts_with('/')
+}
+
+/// Resolves disk selectors in `manifest` to device nodes,
+/// and rewrites partition references to selected disks
+pub fn resolve(manifest: &mut Manifest) -> Result<(), AliError> {
+    let Some(disks) = manifest.disks.as_mut() else {
+        return Ok(());
+    };
+
+    let mut resolved = Vec::new();
+    let mut sys_disks = None;
+
+    for disk in disks.iter_mut().filter(|d| is_selector(&d.device)) {
+        let node = match disk.device.starts_with(PREFIX_UDEV_LINK) {
+            true => {
+                std::fs::canonicalize(&disk.device)
+                    .map_err(|err| {
+                        AliError::NoSuchDevice(format!(
+                            "{}: {err}",
+                            disk.device
+                        ))
+                    })?
+                    .to_string_lossy()
+                    .to_string()
+            }
+            false => {
+                if sys_disks.is_none() {
+                    sys_disks = Some(list_disks()?);
+                }
+
+                let conds = parse_selector(&disk.device)?;
+                let sys_disks = sys_disks.as_ref().unwrap();
+
+                select(&disk.device, &conds, sys_disks)?.path.clone()
+            }
+        };
+
+        log::info!("resolved disk {} to {node}", disk.device);
+        resolved.push((disk.device.clone(), node.clone()));
+        disk.device = node;
+    }
+
+    for (selector, node) in resolved {
+        for device in devices_mut(manifest) {
+            rewrite(device, &selector, &node);
+        }
+    }
+
+    Ok(())
+}
+
+/// Lists whole disks with:
+/// ```shell
+/// lsblk --json --bytes --nodeps -o PATH,SIZE,TRAN,MODEL,SERIAL,WWN,TYPE
+/// ```
+pub fn list_disks() -> Result<Vec<BlockDisk>, AliError> {
+    let output = shell::exec_with_output(
+        "lsblk",
+        &[
+            "--json",
+            "--bytes",
+            "--nodeps",
+            "-o",
+            "PATH,SIZE,TRAN,MODEL,SERIAL,WWN,TYPE",
+        ],
+    )?;
+
+    parse_lsblk(&String::from_utf8_lossy(&output))
+}
+
+fn parse_lsblk(json: &str) -> Result<Vec<BlockDisk>, AliError> {
+    let lsblk: Lsblk = serde_json::from_str(json).map_err(|err| {
+        AliError::AliRsBug(format!("unexpected lsblk output: {err}"))
+    })?;
+
+    Ok(lsblk
+        .blockdevices
+        .into_iter()
+        .filter(|d| d.dev_type == "disk")
+        .collect())
+}
+
+fn parse_selector(selector: &str) -> Result<Vec<Cond>, AliError> {
+    selector
+        .split(',')
+        .map(|cond| {
+            let pos = cond.find(['=', '>', '<']).ok_or(AliError::BadManifest(
+                format!("bad disk selector {selector}: bad condition {cond}"),
+            ))?;
+
+            let (k, op, v) =
+                (cond[..pos].trim(), &cond[pos..=pos], cond[pos + 1..].trim());
+
+            if !KEYS.contains(&k) {
+                return Err(AliError::BadManifest(format!(
+                    "bad disk selector {selector}: unknown key {k}{}, expecting one of {}",
+                    suggest::hint(k, KEYS),
+                    KEYS.join(", ")
+                )));
+            }
+
+            match (k, op) {
+                ("size", op) => {
+                    let size = parse_human_bytes(v)?.size() as u64;
+                    Ok(match op {
+                        ">" => Cond::SizeGt(size),
+                        "<" => Cond::SizeLt(size),
+                        _ => Cond::SizeEq(size),
+                    })
+                }
+                (k, "=") => Ok(Cond::Eq(k.to_string(), v.to_string())),
+                (k, op) => {
+                    Err(AliError::BadManifest(format!(
+                        "bad disk selector {selector}: operator {op} is only valid for size, not {k}"
+                    )))
+                }
+            }
+        })
+        .collect()
+}
+
+fn matches(disk: &BlockDisk, cond: &Cond) -> bool {
+    let prop = |p: &Option<String>, v: &str| {
+        p.as_deref()
+            .is_some_and(|p| p.trim().eq_ignore_ascii_case(v))
+    };
+
+    match cond {
+        Cond::SizeEq(size) => disk.size == *size,
+        Cond::SizeGt(size) => disk.size > *size,
+        Cond::SizeLt(size) => disk.size < *size,
+        Cond::Eq(k, v) => {
+            match k.as_str() {
+                "wwn" => prop(&disk.wwn, v),
+                "serial" => prop(&disk.serial, v),
+                "model" => prop(&disk.model, v),
+                "tran" => prop(&disk.tran, v),
+                _ => false,
+            }
+        }
+    }
+}
+
+fn select<'a>(
+    selector: &str,
+    conds: &[Cond],
+    disks: &'a [BlockDisk],
+) -> Result<&'a BlockDisk, AliError> {
+    let found: Vec<&BlockDisk> = disks
+        .iter()
+        .filter(|d| conds.iter().all(|c| matches(d, c)))
+        .collect();
+
+    match found.as_slice() {
+        [disk] => Ok(disk),
+        [] => {
+            Err(AliError::NoSuchDevice(format!(
+                "no disk matches selector {selector}"
+            )))
+        }
+        found => {
+            let paths: Vec<&str> =
+                found.iter().map(|d| d.path.as_str()).collect();
+
+            Err(AliError::BadManifest(format!(
+                "disk selector {selector} is ambiguous, matching {}",
+                paths.join(", ")
+            )))
+        }
+    }
+}
+
+/// Rewrites `device` if it is `selector` or one of its partitions
+fn rewrite(device: &mut String, selector: &str, node: &str) {
+    if device == selector {
+        *device = node.to_string();
+        return;
+    }
+
+    let part_number = device
+        .strip_prefix(selector)
+        .and_then(|rest| rest.strip_prefix(PARTITION_SEPARATOR))
+        .and_then(|n| n.parse::<u8>().ok());
+
+    if let Some(n) = part_number {
+        *device = super::partition_name(node, n);
+    }
+}
+
+/// All device references in manifest, except disks
+fn devices_mut(manifest: &mut Manifest) -> Vec<&mut String> {
+    let mut devices = vec![&mut manifest.rootfs.device];
+
+    for dm in manifest.device_mappers.iter_mut().flatten() {
+        match dm {
+            Dm::Luks(luks) => devices.push(&mut luks.device),
+            Dm::Lvm(lvm) => {
+                devices.extend(lvm.pvs.iter_mut().flatten());
+                for vg in lvm.vgs.iter_mut().flatten() {
+                    devices.extend(vg.pvs.iter_mut());
+                }
+            }
+        }
+    }
+
+    devices.extend(
+        manifest
+            .filesystems
+            .iter_mut()
+            .flatten()
+            .map(|f| &mut f.device),
+    );
+    devices.extend(
+        manifest
+            .mountpoints
+            .iter_mut()
+            .flatten()
+            .map(|m| &mut m.device),
+    );
+    devices.extend(manifest.swap.iter_mut().flatten());
+
+    devices
+}
+
+/// Identifiers of a block device, from blkid(8)
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct DeviceIds {
+    pub uuid: Option<String>,
+    pub partuuid: Option<String>,
+}
+
+/// Maps devices to their identifiers after formatting, so that generated
+/// configs (fstab, crypttab, kernel command line) do not hard-code
+/// device paths, which change when enumeration changes on reboot.
+#[derive(Debug, Default)]
+pub struct DeviceMap(HashMap<String, DeviceIds>);
+
+impl DeviceMap {
+    /// Queries identifiers of `devices` with:
+    /// ```shell
+    /// blkid -o export <device>
+    /// ```
+    /// Devices without identifiers (e.g. no filesystem) are mapped
+    /// to empty identifiers.
+    pub fn query<'a, I>(devices: I) -> Self
+    where
+        I: IntoIterator<Item = &'a str>,
+    {
+        let mut map = Self::default();
+        for device in devices {
+            let ids = shell::exec_with_output_timeout(
+                "blkid",
+                &["-o", "export", device],
+                Some(shell::PROBE_TIMEOUT),
+            )
+            .map(|output| {
+                        parse_blkid_export(&String::from_utf8_lossy(&output))
+                    })
+                    .unwrap_or_default();
+
+            map.insert(device, ids);
+        }
+
+        map
+    }
+
+    pub fn insert(&mut self, device: &str, ids: DeviceIds) {
+        self.0.insert(device.to_string(), ids);
+    }
+
+    /// Stable reference to `device` for configs: `UUID=<UUID>`,
+    /// falling back to `PARTUUID=<PARTUUID>`, then device path
+    pub fn stable_ref(&self, device: &str) -> String {
+        let ids = self.0.get(device);
+        if let Some(uuid) = ids.and_then(|ids| ids.uuid.as_ref()) {
+            return format!("UUID={uuid}");
+        }
+
+        if let Some(partuuid) = ids.and_then(|ids| ids.partuuid.as_ref()) {
+            return format!("PARTUUID={partuuid}");
+        }
+
+        device.to_string()
+    }
+}
+
+fn parse_blkid_export(output: &str) -> DeviceIds {
+    let mut ids = DeviceIds::default();
+    for (k, v) in output.lines().filter_map(|line| line.split_once('=')) {
+        match k {
+            "UUID" => ids.uuid = Some(v.to_string()),
+            "PARTUUID" => ids.partuuid = Some(v.to_string()),
+            _ => continue,
+        }
+    }
+
+    ids
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LSBLK: &str = r#"{
+   "blockdevices": [
+      {"path": "/dev/sda", "size": 256060514304, "tran": "sata", "model": "Samsung SSD 860", "serial": "S3Z1NB0K111111", "wwn": "0x5002538e40a1b2c3", "type": "disk"},
+      {"path": "/dev/sdb", "size": 31914983424, "tran": "usb", "model": "Ultra Fit", "serial": "4C530001", "wwn": null, "type": "disk"},
+      {"path": "/dev/nvme0n1", "size": 1000204886016, "tran": "nvme", "model": "Samsung SSD 980 PRO 1TB", "serial": "S5GXNF0R123456", "wwn": "eui.002538b111111111", "type": "disk"},
+      {"path": "/dev/nvme1n1", "size": 256060514304, "tran": "nvme", "model": "WDC PC SN530", "serial": "21000000", "wwn": null, "type": "disk"},
+      {"path": "/dev/sr0", "size": 1073741312, "tran": "sata", "model": "QEMU DVD-ROM", "serial": null, "wwn": null, "type": "rom"}
+   ]
+}"#;
+
+    #[test]
+    fn test_select() {
+        let disks = parse_lsblk(LSBLK).unwrap();
+        assert_eq!(4, disks.len());
+
+        let tests = [
+            ("wwn=0x5002538E40A1B2C3", "/dev/sda"),
+            (
+                "model=Samsung SSD 980 PRO 1TB,serial=S5GXNF0R123456",
+                "/dev/nvme0n1",
+            ),
+            ("tran=nvme,size>500G", "/dev/nvme0n1"),
+            ("tran=usb", "/dev/sdb"),
+            ("tran=nvme,size<500G", "/dev/nvme1n1"),
+        ];
+
+        for (selector, expected) in tests {
+            let conds = parse_selector(selector).unwrap();
+            let disk = select(selector, &conds, &disks).unwrap();
+            assert_eq!(expected, disk.path, "unexpected disk for {selector}");
+        }
+
+        let should_err = ["tran=nvme", "tran=scsi", "size>2T"];
+        for selector in should_err {
+            let conds = parse_selector(selector).unwrap();
+            assert!(select(selector, &conds, &disks).is_err());
+        }
+
+        let bad_selectors = ["foo=bar", "model>foo", "size>foo", "nvme"];
+        for selector in bad_selectors {
+            assert!(parse_selector(selector).is_err(), "{selector}");
+        }
+    }
+
+    #[test]
+    fn test_device_map() {
+        let output = "DEVNAME=/dev/vda2\nUUID=0a1b2c3d-0000-4000-8000-000000000001\nTYPE=crypto_LUKS\nPARTUUID=5e6f7a8b-02\n";
+        let ids = parse_blkid_export(output);
+        assert_eq!(
+            DeviceIds {
+                uuid: Some("0a1b2c3d-0000-4000-8000-000000000001".to_string()),
+                partuuid: Some("5e6f7a8b-02".to_string()),
+            },
+            ids,
+        );
+
+        let mut map = DeviceMap::default();
+        map.insert("/dev/vda2", ids);
+        map.insert(
+            "/dev/vda3",
+            DeviceIds {
+                uuid: None,
+                partuuid: Some("5e6f7a8b-03".to_string()),
+            },
+        );
+
+        assert_eq!(
+            "UUID=0a1b2c3d-0000-4000-8000-000000000001",
+            map.stable_ref("/dev/vda2")
+        );
+        assert_eq!("PARTUUID=5e6f7a8b-03", map.stable_ref("/dev/vda3"));
+        assert_eq!("/dev/vda4", map.stable_ref("/dev/vda4"));
+    }
+
+    #[test]
+    fn test_rewrite() {
+        let selector = "/dev/disk/by-id/nvme-Samsung_SSD_980_PRO_1TB_S5GX";
+        let tests = [
+            (selector.to_string(), "/dev/nvme0n1"),
+            (format!("{selector}-part2"), "/dev/nvme0n1p2"),
+            ("/dev/sda1".to_string(), "/dev/sda1"),
+            (
+                format!("{selector}-partx"),
+                "/dev/disk/by-id/nvme-Samsung_SSD_980_PRO_1TB_S5GX-partx",
+            ),
+        ];
+
+        for (device, expected) in tests {
+            let mut device = device;
+            rewrite(&mut device, selector, "/dev/nvme0n1");
+            assert_eq!(expected, device);
+        }
+
+        assert!(is_selector(selector));
+        assert!(is_selector("tran=nvme"));
+        assert!(!is_selector("/dev/sda"));
+    }
+}
@@ -0,0 +1,150 @@
+use crate::errors::AliError;
+use crate::utils::shell::{
+    self,
+    CmdError,
+};
+
+/// Result of checking a filesystem
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FsckStatus {
+    Clean,
+    Repaired,
+    Errors,
+}
+
+impl std::fmt::Display for FsckStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Clean => write!(f, "clean"),
+            Self::Repaired => write!(f, "errors repaired"),
+            Self::Errors => write!(f, "errors found"),
+        }
+    }
+}
+
+/// Checks unmounted filesystem on `device`, repairing it if `repair`.
+/// If `fs_type` is unknown, fsck(8) detects it.
+///
+/// Btrfs is only checked read-only here, since `btrfs check --repair`
+/// is unsafe. Btrfs repairs are done with [`scrub`] after mounting.
+pub fn check(
+    device: &str,
+    fs_type: Option<&str>,
+    repair: bool,
+) -> Result<FsckStatus, AliError> {
+    let (cmd, args) = cmd_fsck(device, fs_type, repair);
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+
+    status(shell::exec(&cmd, &args), repair && fs_type != Some("btrfs"))
+}
+
+/// Executes:
+/// ```shell
+/// btrfs scrub start -B <mountpoint>
+/// ```
+pub fn scrub(mountpoint: &str) -> Result<FsckStatus, AliError> {
+    let result = shell::exec("btrfs", &["scrub", "start", "-B", mountpoint]);
+
+    match result {
+        Ok(()) => Ok(FsckStatus::Clean),
+        Err(AliError::CmdFailed {
+            error: CmdError::ErrRun { code: Some(3), .. },
+            ..
+        }) => Ok(FsckStatus::Errors),
+        Err(err) => Err(err),
+    }
+}
+
+fn cmd_fsck(
+    device: &str,
+    fs_type: Option<&str>,
+    repair: bool,
+) -> (String, Vec<String>) {
+    let device = device.to_string();
+
+    match (fs_type, repair) {
+        (Some("btrfs"), _) => {
+            (
+                "btrfs".to_string(),
+                vec!["check".into(), "--readonly".into(), device],
+            )
+        }
+        (Some("xfs"), false) => {
+            ("xfs_repair".to_string(), vec!["-n".into(), device])
+        }
+        (Some("xfs"), true) => ("xfs_repair".to_string(), vec![device]),
+        (Some(fs_type), repair) => {
+            let flag = if repair { "-y" } else { "-n" };
+            (format!("fsck.{fs_type}"), vec![flag.into(), device])
+        }
+        (None, repair) => {
+            let flag = if repair { "-y" } else { "-n" };
+            ("fsck".to_string(), vec![flag.into(), device])
+        }
+    }
+}
+
+/// Maps fsck(8) exit codes: 1 means errors were corrected
+fn status(
+    result: Result<(), AliError>,
+    repair: bool,
+) -> Result<FsckStatus, AliError> {
+    match result {
+        Ok(()) => Ok(FsckStatus::Clean),
+        Err(AliError::CmdFailed {
+            error: CmdError::ErrRun { code: Some(1), .. },
+            ..
+        }) if repair => Ok(FsckStatus::Repaired),
+        Err(AliError::CmdFailed {
+            error: CmdError::ErrRun { code: Some(_), .. },
+            ..
+        }) => Ok(FsckStatus::Errors),
+        Err(err) => Err(err),
+    }
+}
+
+#[test]
+fn test_cmd_fsck() {
+    let tests = [
+        (
+            (Some("ext4"), false),
+            ("fsck.ext4", vec!["-n", "/dev/vda1"]),
+        ),
+        ((Some("vfat"), true), ("fsck.vfat", vec!["-y", "/dev/vda1"])),
+        (
+            (Some("xfs"), false),
+            ("xfs_repair", vec!["-n", "/dev/vda1"]),
+        ),
+        ((Some("xfs"), true), ("xfs_repair", vec!["/dev/vda1"])),
+        (
+            (Some("btrfs"), true),
+            ("btrfs", vec!["check", "--readonly", "/dev/vda1"]),
+        ),
+        ((None, false), ("fsck", vec!["-n", "/dev/vda1"])),
+    ];
+
+    for ((fs_type, repair), (cmd, args)) in tests {
+        let (result_cmd, result_args) = cmd_fsck("/dev/vda1", fs_type, repair);
+        assert_eq!(cmd, result_cmd);
+        assert_eq!(args, result_args);
+    }
+}
+
+#[test]
+fn test_status() {
+    let exit = |code| {
+        Err(AliError::CmdFailed {
+            error: CmdError::ErrRun {
+                code: Some(code),
+                stdout: None,
+                stderr: None,
+            },
+            context: "test".to_string(),
+        })
+    };
+
+    assert_eq!(FsckStatus::Clean, status(Ok(()), false).unwrap());
+    assert_eq!(FsckStatus::Repaired, status(exit(1), true).unwrap());
+    assert_eq!(FsckStatus::Errors, status(exit(1), false).unwrap());
+    assert_eq!(FsckStatus::Errors, status(exit(4), true).unwrap());
+}
@@ -0,0 +1,152 @@
+//! Boot loader entries on an ESP possibly shared with other installs.
+//! Entries written by ali-rs are namespaced by entry token (as with
+//! kernel-install), and marked as such, so that ali-rs never overwrites
+//! entries of other installs.
+
+use crate::errors::AliError;
+use crate::utils::fs::path_under;
+
+/// Directory of systemd-boot loader entries, with ESP mounted on `/boot`
+pub const DIR_ENTRIES: &str = "/boot/loader/entries";
+
+/// Directory of unified kernel images
+pub const DIR_UKIS: &str = "/boot/EFI/Linux";
+
+/// First line of entries written by ali-rs
+pub const MARKER: &str = "# Installed by ali-rs";
+
+/// Entry token of new system at `root_location`, from (in order)
+/// `/etc/kernel/entry-token`, `/etc/machine-id`, or `ID` in
+/// `/etc/os-release`, like kernel-install
+pub fn entry_token(root_location: &str) -> String {
+    let read = |path: &str| {
+        path_under(root_location, path)
+            .ok()
+            .and_then(|path| std::fs::read_to_string(path).ok())
+    };
+
+    let token = |s: &str| {
+        let s = s.trim();
+        (!s.is_empty()).then(|| s.to_string())
+    };
+
+    read("/etc/kernel/entry-token")
+        .and_then(|s| token(&s))
+        .or_else(|| read("/etc/machine-id").and_then(|s| token(&s)))
+        .or_else(|| {
+            read("/etc/os-release").and_then(|s| {
+                s.lines()
+                    .find_map(|line| line.strip_prefix("ID="))
+                    .and_then(|id| token(id.trim_matches('"')))
+            })
+        })
+        .unwrap_or("ali-rs".to_string())
+}
+
+/// Name of entry file `name` namespaced by `token`, e.g.
+/// `<TOKEN>-arch-fallback.conf`, unless already namespaced
+pub fn namespaced(token: &str, name: &str) -> String {
+    match name.starts_with(&format!("{token}-")) {
+        true => name.to_string(),
+        false => format!("{token}-{name}"),
+    }
+}
+
+/// Whether entry `content` was written by ali-rs
+pub fn is_owned(content: &str) -> bool {
+    content.starts_with(MARKER)
+}
+
+/// Fails if entry `name` exists under `root_location`,
+/// but was not written by ali-rs
+pub fn check_owned(root_location: &str, name: &str) -> Result<(), AliError> {
+    let path = path_under(root_location, &format!("{DIR_ENTRIES}/{name}"))?;
+    match std::fs::read_to_string(&path) {
+        Ok(content) if !is_owned(&content) => {
+            Err(AliError::Aborted(format!(
+                "refusing to overwrite loader entry {name}, \
+                 which was not written by ali-rs"
+            )))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Loader entries and UKIs on the ESP, with whether ali-rs wrote them
+pub fn layout(root_location: &str) -> Vec<(String, bool)> {
+    let list = |dir: &str, ext: &str| {
+        let Ok(path) = path_under(root_location, dir) else {
+            return vec![];
+        };
+
+        let mut files: Vec<(String, bool)> = std::fs::read_dir(path)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .filter(|e| e.file_name().to_string_lossy().ends_with(ext))
+            .map(|e| {
+                let owned = std::fs::read_to_string(e.path())
+                    .is_ok_and(|content| is_owned(&content));
+
+                (format!("{dir}/{}", e.file_name().to_string_lossy()), owned)
+            })
+            .collect();
+
+        files.sort();
+        files
+    };
+
+    let mut files = list(DIR_ENTRIES, ".conf");
+    files.extend(list(DIR_UKIS, ".efi"));
+
+    files
+}
+
+#[test]
+fn test_loader_entries() {
+    let root = std::env::temp_dir().join("ali-rs-test-loader");
+    let _ = std::fs::remove_dir_all(&root);
+    let r = root.to_str().unwrap();
+
+    assert_eq!("ali-rs", entry_token(r));
+
+    std::fs::create_dir_all(root.join("etc/kernel")).unwrap();
+    std::fs::write(root.join("etc/os-release"), "NAME=\"Arch\"\nID=arch\n")
+        .unwrap();
+    assert_eq!("arch", entry_token(r));
+
+    std::fs::write(root.join("etc/machine-id"), "0123abcd\n").unwrap();
+    assert_eq!("0123abcd", entry_token(r));
+
+    std::fs::write(root.join("etc/kernel/entry-token"), "ws-01\n").unwrap();
+    assert_eq!("ws-01", entry_token(r));
+
+    assert_eq!("ws-01-arch.conf", namespaced("ws-01", "arch.conf"));
+    assert_eq!("ws-01-arch.conf", namespaced("ws-01", "ws-01-arch.conf"));
+
+    let entries = root.join("boot/loader/entries");
+    std::fs::create_dir_all(&entries).unwrap();
+    std::fs::write(entries.join("other.conf"), "title Other\n").unwrap();
+    std::fs::write(
+        entries.join("ws-01-arch-fallback.conf"),
+        "# Installed by ali-rs hook @rollback\ntitle Arch (fallback)\n",
+    )
+    .unwrap();
+
+    assert!(check_owned(r, "other.conf").is_err());
+    assert!(check_owned(r, "ws-01-arch-fallback.conf").is_ok());
+    assert!(check_owned(r, "missing.conf").is_ok());
+
+    assert_eq!(
+        vec![
+            ("/boot/loader/entries/other.conf".to_string(), false),
+            (
+                "/boot/loader/entries/ws-01-arch-fallback.conf".to_string(),
+                true
+            ),
+        ],
+        layout(r),
+    );
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
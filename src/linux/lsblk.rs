@@ -0,0 +1,94 @@
+use serde::Deserialize;
+
+use crate::errors::AliError;
+use crate::utils::shell;
+
+/// Identity of a whole disk, as reported by lsblk(8) from udev
+#[derive(Debug, Default, PartialEq, Deserialize)]
+pub struct DiskInfo {
+    pub model: Option<String>,
+    pub size: Option<String>,
+    pub serial: Option<String>,
+    pub wwn: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Lsblk {
+    blockdevices: Vec<DiskInfo>,
+}
+
+impl std::fmt::Display for DiskInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let unknown = "unknown".to_string();
+
+        writeln!(f, "model:  {}", self.model.as_ref().unwrap_or(&unknown))?;
+        writeln!(f, "size:   {}", self.size.as_ref().unwrap_or(&unknown))?;
+        writeln!(f, "serial: {}", self.serial.as_ref().unwrap_or(&unknown))?;
+        write!(f, "wwn:    {}", self.wwn.as_ref().unwrap_or(&unknown))
+    }
+}
+
+/// Executes:
+/// ```shell
+/// lsblk --json --nodeps -o MODEL,SIZE,SERIAL,WWN <device>
+/// ```
+pub fn disk_info(device: &str) -> Result<DiskInfo, AliError> {
+    let output = shell::exec_with_output_timeout(
+        "lsblk",
+        &["--json", "--nodeps", "-o", "MODEL,SIZE,SERIAL,WWN", device],
+        Some(shell::PROBE_TIMEOUT),
+    )?;
+
+    parse_disk_info(&String::from_utf8_lossy(&output))
+}
+
+/// Executes:
+/// ```shell
+/// lsblk -o NAME,SIZE,TYPE,FSTYPE,LABEL,MOUNTPOINTS <device>
+/// ```
+pub fn layout(device: &str) -> Result<String, AliError> {
+    let output = shell::exec_with_output_timeout(
+        "lsblk",
+        &["-o", "NAME,SIZE,TYPE,FSTYPE,LABEL,MOUNTPOINTS", device],
+        Some(shell::PROBE_TIMEOUT),
+    )?;
+
+    Ok(String::from_utf8_lossy(&output).to_string())
+}
+
+fn parse_disk_info(json: &str) -> Result<DiskInfo, AliError> {
+    let lsblk: Lsblk = serde_json::from_str(json).map_err(|err| {
+        AliError::AliRsBug(format!("unexpected lsblk output: {err}"))
+    })?;
+
+    lsblk
+        .blockdevices
+        .into_iter()
+        .next()
+        .ok_or(AliError::NoSuchDevice("lsblk found no device".to_string()))
+}
+
+#[test]
+fn test_parse_disk_info() {
+    let json = r#"{
+   "blockdevices": [
+      {
+         "model": "Samsung SSD 980 PRO 1TB",
+         "size": "931.5G",
+         "serial": "S5GXNF0R123456",
+         "wwn": "eui.002538b111111111"
+      }
+   ]
+}"#;
+
+    let info = parse_disk_info(json).unwrap();
+    assert_eq!(Some("Samsung SSD 980 PRO 1TB".to_string()), info.model);
+    assert_eq!(Some("eui.002538b111111111".to_string()), info.wwn);
+
+    let json = r#"{"blockdevices": [{"model": null, "size": "20G", "serial": null, "wwn": null}]}"#;
+    let info = parse_disk_info(json).unwrap();
+    assert_eq!(None, info.serial);
+    assert!(info.to_string().contains("serial: unknown"));
+
+    assert!(parse_disk_info(r#"{"blockdevices": []}"#).is_err());
+}
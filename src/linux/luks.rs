@@ -58,6 +58,29 @@ mod tests {
         test_utils,
     };
 
+    #[test]
+    fn test_luks_cmds() {
+        use std::sync::Arc;
+
+        use crate::utils::mock::MockRunner;
+        use crate::utils::shell;
+
+        let mock = Arc::new(MockRunner::new());
+        let _guard = shell::set_runner(mock.clone());
+
+        format("/dev/vda2", Some("pass1234")).unwrap();
+        open("/dev/vda2", None, "cryptroot").unwrap();
+        assert!(format("/dev/vda2", Some("")).is_err());
+
+        assert_eq!(
+            vec![
+                "sh -c echo 'pass1234' | cryptsetup luksFormat /dev/vda2",
+                "sh -c cryptsetup luksOpen /dev/vda2 cryptroot",
+            ],
+            mock.calls(),
+        );
+    }
+
     #[test]
     fn test_luks() {
         if !in_path("cryptsetup") {
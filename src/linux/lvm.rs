@@ -22,6 +22,14 @@ pub fn create_vg(vg: &ali::ManifestLvmVg) -> Result<(), AliError> {
     shell::exec("vgcreate", &arg)
 }
 
+/// Executes:
+/// ```shell
+/// vgchange -ay ${{ vg }}
+/// ```
+pub fn activate_vg(vg: &str) -> Result<(), AliError> {
+    shell::exec("vgchange", &["-ay", vg])
+}
+
 /// Executes:
 /// ```shell
 /// lvcreate -L ${{ lv.size }} ${{ lv.vg }} -n ${{ lv.name }}
@@ -36,5 +44,40 @@ pub fn create_lv(lv: &ali::ManifestLvmLv) -> Result<(), AliError> {
         None => ("-l", "100%FREE"),
     };
 
-    shell::exec("lvcreate", &[size_flag, size, "-n", &lv.name])
+    shell::exec("lvcreate", &[size_flag, size, &lv.vg, "-n", &lv.name])
+}
+
+#[test]
+fn test_lvm_cmds() {
+    use std::sync::Arc;
+
+    use crate::utils::mock::MockRunner;
+
+    let mock = Arc::new(MockRunner::new());
+    let _guard = shell::set_runner(mock.clone());
+
+    let vg = ali::ManifestLvmVg {
+        name: "myvg".to_string(),
+        pvs: vec!["/dev/vda1".to_string(), "/dev/vdb".to_string()],
+    };
+    let lv = ali::ManifestLvmLv {
+        name: "rootlv".to_string(),
+        vg: "myvg".to_string(),
+        size: None,
+    };
+
+    create_pv("/dev/vda1").unwrap();
+    create_vg(&vg).unwrap();
+    activate_vg("myvg").unwrap();
+    create_lv(&lv).unwrap();
+
+    assert_eq!(
+        vec![
+            "pvcreate /dev/vda1",
+            "vgcreate myvg /dev/vda1 /dev/vdb",
+            "vgchange -ay myvg",
+            "lvcreate -l 100%FREE myvg -n rootlv",
+        ],
+        mock.calls(),
+    );
 }
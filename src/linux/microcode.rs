@@ -0,0 +1,118 @@
+use crate::utils::fs::file_exists;
+
+/// CPU microcode, loaded by the kernel from the first initrd(s),
+/// so microcode images must come before the main initramfs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Microcode {
+    Amd,
+    Intel,
+}
+
+/// All microcode, in the order images are given to bootloaders
+pub const ALL: [Microcode; 2] = [Microcode::Amd, Microcode::Intel];
+
+impl Microcode {
+    /// Image installed to /boot by package `amd-ucode` or `intel-ucode`
+    pub fn image(&self) -> &'static str {
+        match self {
+            Self::Amd => "amd-ucode.img",
+            Self::Intel => "intel-ucode.img",
+        }
+    }
+}
+
+/// Microcode with images installed in `/boot` under `root`
+pub fn installed(root: &str) -> Vec<Microcode> {
+    ALL.into_iter()
+        .filter(|ucode| file_exists(format!("{root}/boot/{}", ucode.image())))
+        .collect()
+}
+
+/// Orders `initrds` for boot entries: microcode images (from `microcode`,
+/// and those already in `initrds`) come first, followed by other images
+/// in their original order. Microcode missing from `initrds` is added
+/// as `/<IMAGE>`, i.e. relative to the boot partition.
+pub fn order_initrds(
+    microcode: &[Microcode],
+    initrds: &[String],
+) -> Vec<String> {
+    let is_ucode = |initrd: &str, ucode: &Microcode| {
+        initrd.rsplit('/').next() == Some(ucode.image())
+    };
+
+    let ucode = ALL.iter().filter_map(|ucode| {
+        match initrds.iter().find(|initrd| is_ucode(initrd, ucode)) {
+            Some(initrd) => Some(initrd.clone()),
+            None => {
+                microcode
+                    .contains(ucode)
+                    .then(|| format!("/{}", ucode.image()))
+            }
+        }
+    });
+
+    let others = initrds
+        .iter()
+        .filter(|initrd| !ALL.iter().any(|ucode| is_ucode(initrd, ucode)))
+        .cloned();
+
+    ucode.chain(others).collect()
+}
+
+#[test]
+fn test_order_initrds() {
+    let initrds = |v: &[&str]| -> Vec<String> {
+        v.iter().map(|s| s.to_string()).collect()
+    };
+
+    let tests = [
+        // No microcode
+        (
+            vec![],
+            vec!["/initramfs-linux.img"],
+            vec!["/initramfs-linux.img"],
+        ),
+        // Installed microcode is added before initramfs
+        (
+            vec![Microcode::Intel],
+            vec!["/initramfs-linux.img"],
+            vec!["/intel-ucode.img", "/initramfs-linux.img"],
+        ),
+        (
+            vec![Microcode::Amd],
+            vec!["/initramfs-linux.img"],
+            vec!["/amd-ucode.img", "/initramfs-linux.img"],
+        ),
+        // Both microcode, for portable installs
+        (
+            vec![Microcode::Intel, Microcode::Amd],
+            vec!["/initramfs-linux.img"],
+            vec!["/amd-ucode.img", "/intel-ucode.img", "/initramfs-linux.img"],
+        ),
+        // Misordered microcode is moved first, keeping its path
+        (
+            vec![Microcode::Intel],
+            vec!["/arch/initramfs-linux.img", "/arch/intel-ucode.img"],
+            vec!["/arch/intel-ucode.img", "/arch/initramfs-linux.img"],
+        ),
+        // Existing microcode is kept even if not installed
+        (
+            vec![],
+            vec!["/initramfs-linux-fallback.img", "/amd-ucode.img"],
+            vec!["/amd-ucode.img", "/initramfs-linux-fallback.img"],
+        ),
+        // Dual initramfs images keep their order
+        (
+            vec![Microcode::Amd],
+            vec!["/initramfs-linux.img", "/extra.img"],
+            vec!["/amd-ucode.img", "/initramfs-linux.img", "/extra.img"],
+        ),
+    ];
+
+    for (microcode, input, expected) in tests {
+        assert_eq!(
+            initrds(&expected),
+            order_initrds(&microcode, &initrds(&input))
+        );
+    }
+}
@@ -1,9 +1,20 @@
+pub mod blkid;
 pub mod fdisk;
+pub mod fsck;
+pub mod loader;
+pub mod lsblk;
 pub mod luks;
 pub mod lvm;
+pub mod microcode;
 pub mod mkfs;
 pub mod mount;
+pub mod nsswitch;
+pub mod pam;
+pub mod rusage;
+pub mod sshd;
+pub mod systemd;
 pub mod user;
+pub mod wipe;
 
 // See linux/block/partition-generic.c
 //
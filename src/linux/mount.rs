@@ -18,6 +18,20 @@ pub fn mount(mnt: &ManifestMountpoint, base: &str) -> Result<(), AliError> {
     shell::sh_c(&cmd_mount)
 }
 
+/// Recursively unmounts everything mounted under `base`, e.g. after
+/// Ctrl-C. Uses [`std::process::Command`] directly, because
+/// [`shell`] refuses to run commands once cancelled.
+/// Failures are only logged.
+pub fn umount_all(base: &str) {
+    log::info!("unmounting {base}");
+
+    match std::process::Command::new("umount").args(["-R", base]).status() {
+        Ok(status) if status.success() => {}
+        Ok(status) => log::warn!("umount -R {base} exited with {status}"),
+        Err(err) => log::warn!("failed to run umount -R {base}: {err}"),
+    }
+}
+
 pub fn prepend_base(base: &str, mountpoint: &str) -> String {
     // e.g. base /data on manifest /foo => /data/foo
     format!("{base}{mountpoint}")
@@ -0,0 +1,73 @@
+//! Editor for `/etc/nsswitch.conf`, with lines `<database>: <source>..`
+
+pub const FILENAME_NSSWITCH: &str = "/etc/nsswitch.conf";
+
+/// Appends `source` to sources of each database in `databases`,
+/// adding lines `<database>: files <source>` for missing databases.
+/// Databases already using `source` are left untouched.
+pub fn add_source(conf: &str, databases: &[&str], source: &str) -> String {
+    let mut lines: Vec<String> = conf.lines().map(String::from).collect();
+
+    for db in databases {
+        let pos = lines.iter().position(|line| {
+            line.split_once(':').is_some_and(|(name, _)| name.trim() == *db)
+        });
+
+        match pos {
+            None => lines.push(format!("{db}: files {source}")),
+            Some(i) => {
+                let (name, sources) = lines[i].split_once(':').unwrap();
+                // Keep comments after sources
+                let (sources, comment) = match sources.split_once('#') {
+                    Some((sources, comment)) => (sources, Some(comment)),
+                    None => (sources, None),
+                };
+
+                if sources.split_whitespace().any(|s| s == source) {
+                    continue;
+                }
+
+                let mut line = format!("{name}: {} {source}", sources.trim());
+                if let Some(comment) = comment {
+                    line.push_str(&format!(" #{comment}"));
+                }
+
+                lines[i] = line;
+            }
+        }
+    }
+
+    let mut conf = lines.join("\n");
+    conf.push('\n');
+
+    conf
+}
+
+#[test]
+fn test_add_source() {
+    let conf = "# Name Service Switch configuration file.
+
+passwd: files systemd
+group: files [SUCCESS=merge] systemd
+shadow: files systemd
+gshadow: files systemd
+
+hosts: mymachines resolve [!UNAVAIL=return] files myhostname dns
+";
+
+    let expected = "# Name Service Switch configuration file.
+
+passwd: files systemd sss
+group: files [SUCCESS=merge] systemd sss
+shadow: files systemd sss
+gshadow: files systemd
+
+hosts: mymachines resolve [!UNAVAIL=return] files myhostname dns
+netgroup: files sss
+";
+
+    let databases = &["passwd", "group", "shadow", "netgroup"];
+    let result = add_source(conf, databases, "sss");
+    assert_eq!(expected, result);
+    assert_eq!(expected, add_source(&result, databases, "sss"));
+}
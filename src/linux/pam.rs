@@ -0,0 +1,435 @@
+//! Editor for PAM service files in `/etc/pam.d`, with stanzas
+//! `<type> <control> <module> [args..]`. Comments and other lines
+//! are preserved in their original order.
+//!
+//! Stanzas are inserted into a stack (all stanzas of one type) so that
+//! jumps like `[success=1 default=ignore]` of earlier stanzas still skip
+//! the same stanzas, i.e. the stack keeps its meaning.
+
+use crate::errors::AliError;
+
+pub const DIR_PAM_D: &str = "/etc/pam.d";
+
+/// Modules that can authenticate users on their own
+const AUTH_MODULES: &[&str] = &["pam_unix.so", "pam_systemd_home.so"];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Stanza {
+    /// e.g. `auth`, or `-auth` if PAM should skip missing module
+    pub kind: String,
+    /// e.g. `required`, or `[success=1 default=ignore]`
+    pub control: String,
+    pub module: String,
+    pub args: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Line {
+    Stanza(Stanza),
+    Other(String),
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PamConf {
+    lines: Vec<Line>,
+}
+
+impl Stanza {
+    pub fn new(kind: &str, control: &str, module: &str, args: &[&str]) -> Self {
+        Self {
+            kind: kind.to_string(),
+            control: control.to_string(),
+            module: module.to_string(),
+            args: args.iter().map(|arg| arg.to_string()).collect(),
+        }
+    }
+
+    /// Stanza type without `-` prefix
+    pub fn base_kind(&self) -> &str {
+        self.kind.trim_start_matches('-')
+    }
+
+    fn parse(line: &str) -> Option<Self> {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with(['#', '@']) {
+            return None;
+        }
+
+        let mut tokens = trimmed.split_whitespace();
+        let kind = tokens.next()?;
+
+        let mut control = tokens.next()?.to_string();
+        if control.starts_with('[') {
+            while !control.ends_with(']') {
+                control.push(' ');
+                control.push_str(tokens.next()?);
+            }
+        }
+
+        let module = tokens.next()?;
+
+        Some(Self {
+            kind: kind.to_string(),
+            control,
+            module: module.to_string(),
+            args: tokens.map(|arg| arg.to_string()).collect(),
+        })
+    }
+
+    /// Adds 1 to each jump of stanza at stack index `i`
+    /// that skips over stack index `k`
+    fn shift_jumps(&mut self, i: usize, k: usize) {
+        if !self.control.starts_with('[') {
+            return;
+        }
+
+        let actions: Vec<String> = self
+            .control
+            .trim_matches(['[', ']'])
+            .split_whitespace()
+            .map(|action| {
+                match action.split_once('=') {
+                    Some((value, jump)) => {
+                        match jump.parse::<usize>() {
+                            Ok(n) if i + n + 1 > k => {
+                                format!("{value}={}", n + 1)
+                            }
+                            _ => action.to_string(),
+                        }
+                    }
+                    None => action.to_string(),
+                }
+            })
+            .collect();
+
+        self.control = format!("[{}]", actions.join(" "));
+    }
+}
+
+impl std::fmt::Display for Stanza {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let line = format!(
+            "{:<10} {:<27} {:<20} {}",
+            self.kind,
+            self.control,
+            self.module,
+            self.args.join(" "),
+        );
+
+        write!(f, "{}", line.trim_end())
+    }
+}
+
+impl PamConf {
+    pub fn parse(s: &str) -> Self {
+        let lines = s
+            .lines()
+            .map(|line| {
+                match Stanza::parse(line) {
+                    Some(stanza) => Line::Stanza(stanza),
+                    None => Line::Other(line.to_string()),
+                }
+            })
+            .collect();
+
+        Self { lines }
+    }
+
+    pub fn read(path: &str) -> Result<Self, AliError> {
+        std::fs::read_to_string(path)
+            .map(|s| Self::parse(&s))
+            .map_err(|err| {
+                AliError::FileError(err, format!("failed to read file {path}"))
+            })
+    }
+
+    /// Stanzas of type `kind`, in stack order
+    pub fn stack(&self, kind: &str) -> Vec<&Stanza> {
+        self.stack_lines(kind)
+            .into_iter()
+            .filter_map(|i| {
+                match &self.lines[i] {
+                    Line::Stanza(stanza) => Some(stanza),
+                    Line::Other(_) => None,
+                }
+            })
+            .collect()
+    }
+
+    /// Stack index of first stanza of type `kind` with `module`
+    pub fn position(&self, kind: &str, module: &str) -> Option<usize> {
+        self.stack(kind).iter().position(|s| s.module == module)
+    }
+
+    pub fn find_mut(
+        &mut self,
+        kind: &str,
+        module: &str,
+    ) -> Option<&mut Stanza> {
+        self.lines.iter_mut().find_map(|line| {
+            match line {
+                Line::Stanza(s)
+                    if s.base_kind() == kind && s.module == module =>
+                {
+                    Some(s)
+                }
+                _ => None,
+            }
+        })
+    }
+
+    /// Stack index where stack of type `kind` continues
+    /// after stanza at stack index `i` succeeds
+    pub fn after_success(&self, kind: &str, i: usize) -> usize {
+        let stack = self.stack(kind);
+        let skip = stack
+            .get(i)
+            .and_then(|stanza| {
+                stanza
+                    .control
+                    .trim_matches(['[', ']'])
+                    .split_whitespace()
+                    .find_map(|action| action.strip_prefix("success="))
+                    .and_then(|jump| jump.parse::<usize>().ok())
+            })
+            .unwrap_or(0);
+
+        (i + skip + 1).min(stack.len())
+    }
+
+    /// Inserts `stanza` at stack index `k` of its type, adjusting jumps
+    /// of earlier stanzas that skip over `k`. Jumps landing exactly
+    /// on `k` land on the new stanza.
+    pub fn insert(&mut self, k: usize, stanza: Stanza) {
+        let kind = stanza.base_kind().to_string();
+        let stack = self.stack_lines(&kind);
+
+        for (i, &line) in stack.iter().enumerate().take(k) {
+            if let Line::Stanza(s) = &mut self.lines[line] {
+                s.shift_jumps(i, k);
+            }
+        }
+
+        let at = match (stack.get(k), stack.last()) {
+            (Some(&line), _) => line,
+            (None, Some(&last)) => last + 1,
+            (None, None) => self.lines.len(),
+        };
+
+        self.lines.insert(at, Line::Stanza(stanza));
+    }
+
+    /// Checks that users can still authenticate: the auth stack must have
+    /// a module authenticating users, or include another service, with no
+    /// `pam_deny.so` that always fails before it
+    pub fn check_auth(&self) -> Result<(), String> {
+        for stanza in self.stack("auth") {
+            let delegates = matches!(
+                stanza.control.as_str(),
+                "include" | "substack"
+            );
+
+            if delegates || AUTH_MODULES.contains(&stanza.module.as_str()) {
+                return Ok(());
+            }
+
+            if stanza.module == "pam_deny.so"
+                && matches!(stanza.control.as_str(), "required" | "requisite")
+            {
+                return Err(
+                    "pam_deny.so rejects all users before authentication"
+                        .to_string(),
+                );
+            }
+        }
+
+        Err("no module in auth stack can authenticate users".to_string())
+    }
+
+    /// Adds stanzas of [`sss_stanzas`] missing from service. SSSD users
+    /// are tried before pam_unix.so, except for session module
+    /// pam_sss.so which comes after.
+    pub fn add_sss(&mut self) -> Result<(), String> {
+        for mut stanza in sss_stanzas() {
+            let kind = stanza.kind.clone();
+            if self.position(&kind, &stanza.module).is_some() {
+                continue;
+            }
+
+            let unix = self
+                .position(&kind, "pam_unix.so")
+                .ok_or_else(|| format!("no pam_unix.so {kind} stanza"))?;
+
+            // Without a module prompting for new password before it,
+            // pam_sss.so has to prompt on its own
+            if kind == "password"
+                && self.stack("password")[..unix]
+                    .iter()
+                    .all(|s| s.module != "pam_pwquality.so")
+            {
+                stanza.args.clear();
+            }
+
+            let k = match (kind.as_str(), stanza.module.as_str()) {
+                ("session", "pam_sss.so") => unix + 1,
+                _ => unix,
+            };
+
+            self.insert(k, stanza);
+        }
+
+        Ok(())
+    }
+
+    /// Line indices of stanzas of type `kind`
+    fn stack_lines(&self, kind: &str) -> Vec<usize> {
+        self.lines
+            .iter()
+            .enumerate()
+            .filter_map(|(i, line)| {
+                match line {
+                    Line::Stanza(s) if s.base_kind() == kind => Some(i),
+                    _ => None,
+                }
+            })
+            .collect()
+    }
+}
+
+impl std::fmt::Display for PamConf {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for line in &self.lines {
+            match line {
+                Line::Stanza(stanza) => writeln!(f, "{stanza}")?,
+                Line::Other(line) => writeln!(f, "{line}")?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Stanzas for users from SSSD, with home directories created
+/// on first login by pam_mkhomedir.so
+pub fn sss_stanzas() -> [Stanza; 5] {
+    [
+        Stanza::new("auth", "sufficient", "pam_sss.so", &["forward_pass"]),
+        Stanza::new(
+            "account",
+            "[default=bad success=ok user_unknown=ignore authinfo_unavail=ignore]",
+            "pam_sss.so",
+            &[],
+        ),
+        Stanza::new("password", "sufficient", "pam_sss.so", &["use_authtok"]),
+        Stanza::new(
+            "session",
+            "required",
+            "pam_mkhomedir.so",
+            &["skel=/etc/skel", "umask=0077"],
+        ),
+        Stanza::new("session", "optional", "pam_sss.so", &[]),
+    ]
+}
+
+/// Sets `key = value` (or bare `key` if `value` is `None`) in config
+/// like `/etc/security/faillock.conf`, replacing the first active or
+/// commented-out line of `key`, or appending it
+pub fn set_conf_option(conf: &str, key: &str, value: Option<&str>) -> String {
+    let line = match value {
+        Some(value) => format!("{key} = {value}"),
+        None => key.to_string(),
+    };
+
+    let is_key = |l: &str| {
+        let l = l.trim_start().trim_start_matches('#').trim_start();
+        l.strip_prefix(key)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with([' ', '=']))
+    };
+
+    let mut lines: Vec<String> = conf.lines().map(String::from).collect();
+    match lines.iter().position(|l| is_key(l)) {
+        Some(i) => lines[i] = line,
+        None => lines.push(line),
+    }
+
+    let mut conf = lines.join("\n");
+    conf.push('\n');
+
+    conf
+}
+
+#[cfg(test)]
+pub(crate) const TEST_SYSTEM_AUTH: &str = "#%PAM-1.0
+
+auth       required                    pam_faillock.so      preauth
+-auth      [success=2 default=ignore]  pam_systemd_home.so
+auth       [success=1 default=bad]     pam_unix.so          try_first_pass nullok
+auth       [default=die]               pam_faillock.so      authfail
+auth       optional                    pam_permit.so
+auth       required                    pam_env.so
+auth       required                    pam_faillock.so      authsucc
+
+-account   [success=1 default=ignore]  pam_systemd_home.so
+account    required                    pam_unix.so
+account    optional                    pam_permit.so
+
+-password  [success=1 default=ignore]  pam_systemd_home.so
+password   required                    pam_unix.so          try_first_pass nullok shadow
+password   optional                    pam_permit.so
+
+-session   optional                    pam_systemd_home.so
+session    required                    pam_limits.so
+session    required                    pam_unix.so
+session    optional                    pam_permit.so
+";
+
+#[test]
+fn test_pam_conf() {
+    let mut conf = PamConf::parse(TEST_SYSTEM_AUTH);
+    assert_eq!(TEST_SYSTEM_AUTH, conf.to_string());
+    assert_eq!(7, conf.stack("auth").len());
+    assert_eq!(Some(2), conf.position("auth", "pam_unix.so"));
+    assert_eq!(4, conf.after_success("auth", 2));
+    conf.check_auth().unwrap();
+
+    // Before pam_unix: pam_systemd_home.so must skip one more stanza
+    let unix = conf.position("auth", "pam_unix.so").unwrap();
+    conf.insert(unix, Stanza::new("auth", "sufficient", "pam_u2f.so", &[]));
+    let auth = conf.stack("auth");
+    assert_eq!("[success=3 default=ignore]", auth[1].control);
+    assert_eq!("pam_u2f.so", auth[2].module);
+    assert_eq!("[success=1 default=bad]", auth[3].control);
+
+    // Where success jumps land, nothing shifts
+    let mut conf = PamConf::parse(TEST_SYSTEM_AUTH);
+    let k = conf.after_success("auth", 2);
+    conf.insert(k, Stanza::new("auth", "required", "pam_u2f.so", &["cue"]));
+    let auth = conf.stack("auth");
+    assert_eq!("[success=2 default=ignore]", auth[1].control);
+    assert_eq!("[success=1 default=bad]", auth[2].control);
+    assert_eq!("pam_u2f.so", auth[4].module);
+
+    let deny = PamConf::parse(
+        "auth required pam_deny.so\nauth required pam_unix.so\n",
+    );
+    assert!(deny.check_auth().is_err());
+    assert!(PamConf::parse("account required pam_unix.so\n")
+        .check_auth()
+        .is_err());
+    assert!(PamConf::parse("auth include system-login\n")
+        .check_auth()
+        .is_ok());
+
+    assert_eq!(
+        "# Comment\ndeny = 5\nunlock_time = 600\n",
+        set_conf_option(
+            &set_conf_option("# Comment\n# deny = 3\n", "deny", Some("5")),
+            "unlock_time",
+            Some("600"),
+        ),
+    );
+    assert_eq!(
+        "even_deny_root\n",
+        set_conf_option("# even_deny_root\n", "even_deny_root", None),
+    );
+}
@@ -0,0 +1,102 @@
+//! Resource usage accounting of ali-rs and its child processes,
+//! from getrusage(2), /proc/self/io, and cgroup v2 `io.stat` if available
+
+use nix::sys::resource::{
+    getrusage,
+    UsageWho,
+};
+use nix::sys::time::TimeValLike;
+
+use crate::types::stage::StageUsage;
+
+/// Cumulative resource usage at a point in time
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Snapshot {
+    cpu_micros: i64,
+    /// High-water mark of largest process, in KiB
+    peak_rss_kib: u64,
+    bytes_written: u64,
+}
+
+impl Snapshot {
+    pub fn now() -> Self {
+        let (mut cpu_micros, mut peak_rss_kib) = (0, 0);
+
+        // Children are only accounted once waited for,
+        // which ali-rs always does
+        for who in [UsageWho::RUSAGE_SELF, UsageWho::RUSAGE_CHILDREN] {
+            if let Ok(usage) = getrusage(who) {
+                cpu_micros += usage.user_time().num_microseconds()
+                    + usage.system_time().num_microseconds();
+                peak_rss_kib = peak_rss_kib.max(usage.max_rss() as u64);
+            }
+        }
+
+        let bytes_written = cgroup_bytes_written()
+            .unwrap_or_else(|| proc_io("write_bytes").unwrap_or_default());
+
+        Self {
+            cpu_micros,
+            peak_rss_kib,
+            bytes_written,
+        }
+    }
+
+    /// Usage of `stage` from this snapshot until now.
+    /// Peak RSS is the high-water mark at the end of the stage.
+    pub fn stage_usage(&self, stage: &str) -> StageUsage {
+        let now = Self::now();
+        let cpu_micros = (now.cpu_micros - self.cpu_micros).max(0);
+
+        StageUsage {
+            stage: stage.to_string(),
+            cpu_secs: cpu_micros as f64 / 1_000_000.0,
+            peak_rss_kib: now.peak_rss_kib,
+            bytes_written: now.bytes_written.saturating_sub(self.bytes_written),
+        }
+    }
+}
+
+/// Field `key` of /proc/self/io, which includes reaped children
+fn proc_io(key: &str) -> Option<u64> {
+    std::fs::read_to_string("/proc/self/io")
+        .ok()?
+        .lines()
+        .find_map(|l| l.strip_prefix(key)?.strip_prefix(':'))?
+        .trim()
+        .parse()
+        .ok()
+}
+
+/// Sum of `wbytes` of all devices in `io.stat` of cgroup v2 of ali-rs,
+/// which also counts writes by daemons started in the cgroup
+fn cgroup_bytes_written() -> Option<u64> {
+    let cgroups = std::fs::read_to_string("/proc/self/cgroup").ok()?;
+    let path = cgroups.lines().find_map(|l| l.strip_prefix("0::"))?;
+    let io_stat =
+        std::fs::read_to_string(format!("/sys/fs/cgroup{path}/io.stat")).ok()?;
+
+    Some(parse_io_stat(&io_stat))
+}
+
+fn parse_io_stat(io_stat: &str) -> u64 {
+    io_stat
+        .split_whitespace()
+        .filter_map(|field| field.strip_prefix("wbytes="))
+        .filter_map(|wbytes| wbytes.parse::<u64>().ok())
+        .sum()
+}
+
+#[test]
+fn test_rusage() {
+    let start = Snapshot::now();
+    let _ = std::process::Command::new("true").status();
+
+    let usage = start.stage_usage("stage-bootstrap");
+    assert_eq!("stage-bootstrap", usage.stage);
+    assert!(usage.cpu_secs >= 0.0);
+    assert!(usage.peak_rss_kib > 0);
+
+    let io_stat = "8:0 rbytes=1024 wbytes=4096 rios=1 wios=2\n259:0 rbytes=0 wbytes=512 rios=0 wios=1\n";
+    assert_eq!(4608, parse_io_stat(io_stat));
+}
@@ -0,0 +1,317 @@
+use std::io::{
+    Read,
+    Write,
+};
+use std::process::{
+    Command,
+    Stdio,
+};
+
+use nix::sys::signal::{
+    self,
+    Signal,
+};
+use nix::unistd::Pid;
+
+use super::systemd;
+use crate::errors::AliError;
+use crate::utils::{
+    logger,
+    secrets,
+    shell,
+};
+
+const SSHD_DROP_IN: &str = "/etc/ssh/sshd_config.d/10-ali-rs.conf";
+const AUTHORIZED_KEYS: &str = "/root/.ssh/authorized_keys";
+const HOST_KEY: &str = "/etc/ssh/ssh_host_ed25519_key.pub";
+const SSHD_PID: &str = "/run/sshd.pid";
+const PASSWORD_LEN: usize = 16;
+const PASSWORD_CHARS: &[u8] =
+    b"abcdefghijkmnpqrstuvwxyzABCDEFGHJKLMNPQRSTUVWXYZ23456789";
+
+/// How operators authenticate to the live installer
+#[derive(Debug, Clone, PartialEq)]
+pub enum Access {
+    /// Root password, random if not given
+    Password(Option<String>),
+
+    /// Public key, or path to public key file
+    AuthorizedKey(String),
+}
+
+/// Connection info to be shown to operators
+#[derive(Debug)]
+pub struct LiveSsh {
+    pub password: Option<String>,
+    pub fingerprint: Option<String>,
+    /// Log file of this run, for operators to follow
+    pub log_file: Option<String>,
+}
+
+/// Configures and starts sshd on the live installer environment,
+/// so that operators can attach remotely during long installs
+pub fn enable_live(access: &Access) -> Result<LiveSsh, AliError> {
+    let password = match access {
+        Access::Password(password) => {
+            let password = match password {
+                Some(password) => password.clone(),
+                None => random_password()?,
+            };
+            secrets::register(&password);
+            set_root_password(&password)?;
+
+            Some(password)
+        }
+        Access::AuthorizedKey(key) => {
+            add_authorized_key(key)?;
+            None
+        }
+    };
+
+    write_file(SSHD_DROP_IN, &sshd_drop_in(access))?;
+    match systemd::is_booted() {
+        true => shell::exec("systemctl", &["restart", "sshd.service"])?,
+        false => start_sshd()?,
+    }
+
+    Ok(LiveSsh {
+        password,
+        fingerprint: fingerprint(),
+        log_file: logger::file(),
+    })
+}
+
+/// Reads root password from `file`, or from stdin if `file` is `-`
+pub fn read_password(file: &str) -> Result<String, AliError> {
+    let mut password = String::new();
+    let result = match file {
+        "-" => std::io::stdin().read_line(&mut password).map(|_| ()),
+        file => {
+            std::fs::read_to_string(file).map(|content| password = content)
+        }
+    };
+
+    result.map_err(|err| {
+        AliError::FileError(err, format!("failed to read ssh password {file}"))
+    })?;
+
+    let password = password.trim_end_matches(['\r', '\n']);
+    if password.is_empty() {
+        return Err(AliError::BadArgs(format!("empty ssh password in {file}")));
+    }
+
+    Ok(password.to_string())
+}
+
+impl std::fmt::Display for LiveSsh {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "ssh enabled for user root")?;
+
+        if let Some(ref password) = self.password {
+            writeln!(f, "root password: {password}")?;
+        }
+
+        match self.fingerprint {
+            Some(ref fingerprint) => writeln!(f, "host key: {fingerprint}")?,
+            None => writeln!(f, "host key: unknown")?,
+        }
+
+        // The run lock keeps attached operators from starting another run
+        write!(f, "install running as PID {}", std::process::id())?;
+        match self.log_file {
+            Some(ref log_file) => write!(f, ", follow with: tail -f {log_file}"),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Starts sshd without systemd, e.g. on a busybox or dbus-free live system.
+/// Running sshd (from its PID file) is sent SIGHUP to reload config.
+fn start_sshd() -> Result<(), AliError> {
+    let pid = std::fs::read_to_string(SSHD_PID)
+        .ok()
+        .and_then(|pid| pid.trim().parse::<i32>().ok());
+
+    if let Some(pid) = pid {
+        if signal::kill(Pid::from_raw(pid), Signal::SIGHUP).is_ok() {
+            return Ok(());
+        }
+    }
+
+    // sshd re-executes itself, and so must be started with absolute path
+    let Some(sshd) = shell::which("sshd") else {
+        return Err(AliError::CmdFailed {
+            error: shell::CmdError::ErrSpawn {
+                error: std::io::ErrorKind::NotFound.into(),
+            },
+            context: "start live sshd: sshd not in path".to_string(),
+        });
+    };
+
+    // Generates missing host keys, which systemd's sshdgenkeys does
+    shell::exec("ssh-keygen", &["-A"])?;
+    shell::exec(&sshd, &[])
+}
+
+fn sshd_drop_in(access: &Access) -> String {
+    let (permit_root, password_auth) = match access {
+        Access::Password(_) => ("yes", "yes"),
+        Access::AuthorizedKey(_) => ("prohibit-password", "no"),
+    };
+
+    format!(
+        "# Installed by ali-rs --enable-ssh\nPermitRootLogin {permit_root}\nPasswordAuthentication {password_auth}\n"
+    )
+}
+
+fn set_root_password(password: &str) -> Result<(), AliError> {
+    let err = |error: std::io::Error| {
+        AliError::CmdFailed {
+            error: shell::CmdError::ErrSpawn { error },
+            context: "set live root password with chpasswd".to_string(),
+        }
+    };
+
+    // Password is written to stdin to keep it out of process list
+    let mut child = Command::new("chpasswd")
+        .stdin(Stdio::piped())
+        .spawn()
+        .map_err(err)?;
+
+    child
+        .stdin
+        .take()
+        .expect("no chpasswd stdin")
+        .write_all(format!("root:{password}\n").as_bytes())
+        .map_err(err)?;
+
+    let status = child.wait().map_err(err)?;
+    if !status.success() {
+        return Err(AliError::CmdFailed {
+            error: shell::CmdError::ErrRun {
+                code: status.code(),
+                stdout: None,
+                stderr: None,
+            },
+            context: "chpasswd exited with bad status".to_string(),
+        });
+    }
+
+    Ok(())
+}
+
+/// Appends `key` to root's authorized_keys.
+/// If `key` is a path to existing file, the file content is used.
+fn add_authorized_key(key: &str) -> Result<(), AliError> {
+    let key = match std::fs::read_to_string(key) {
+        Ok(content) => content,
+        Err(_) => key.to_string(),
+    };
+
+    let key = key.trim();
+    if !key.starts_with("ssh-") && !key.starts_with("ecdsa-") {
+        return Err(AliError::BadArgs(format!("bad ssh public key: {key}")));
+    }
+
+    let mut existing =
+        std::fs::read_to_string(AUTHORIZED_KEYS).unwrap_or_default();
+    if existing.lines().any(|line| line.trim() == key) {
+        return Ok(());
+    }
+
+    if !existing.is_empty() && !existing.ends_with('\n') {
+        existing.push('\n');
+    }
+    existing.push_str(key);
+    existing.push('\n');
+
+    write_file(AUTHORIZED_KEYS, &existing)?;
+    chmod(AUTHORIZED_KEYS, 0o600)?;
+    chmod("/root/.ssh", 0o700)
+}
+
+/// Returns SHA256 fingerprint of the live ed25519 host key
+fn fingerprint() -> Option<String> {
+    let output =
+        shell::exec_with_output("ssh-keygen", &["-l", "-f", HOST_KEY]).ok()?;
+
+    String::from_utf8(output)
+        .ok()
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+}
+
+fn random_password() -> Result<String, AliError> {
+    let mut urandom = std::fs::File::open("/dev/urandom")
+        .map_err(|err| AliError::FileError(err, "/dev/urandom".to_string()))?;
+
+    let mut password = String::new();
+    while password.len() < PASSWORD_LEN {
+        let mut buf = [0u8; PASSWORD_LEN];
+        urandom.read_exact(&mut buf).map_err(|err| {
+            AliError::FileError(err, "/dev/urandom".to_string())
+        })?;
+
+        password.push_str(&password_from_bytes(&buf));
+    }
+
+    password.truncate(PASSWORD_LEN);
+    Ok(password)
+}
+
+/// Maps random `bytes` to password characters, rejecting bytes
+/// beyond the largest multiple of alphabet size to avoid modulo bias
+fn password_from_bytes(bytes: &[u8]) -> String {
+    let limit = 256 - 256 % PASSWORD_CHARS.len();
+
+    bytes
+        .iter()
+        .filter(|b| (**b as usize) < limit)
+        .map(|b| PASSWORD_CHARS[*b as usize % PASSWORD_CHARS.len()] as char)
+        .collect()
+}
+
+fn write_file(path: &str, content: &str) -> Result<(), AliError> {
+    if let Some(parent) = std::path::Path::new(path).parent() {
+        std::fs::create_dir_all(parent).map_err(|err| {
+            AliError::FileError(err, parent.to_string_lossy().to_string())
+        })?;
+    }
+
+    std::fs::write(path, content)
+        .map_err(|err| AliError::FileError(err, path.to_string()))
+}
+
+fn chmod(path: &str, mode: u32) -> Result<(), AliError> {
+    use std::os::unix::fs::PermissionsExt;
+
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))
+        .map_err(|err| AliError::FileError(err, format!("chmod {path}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_password_from_bytes() {
+        let password = password_from_bytes(&[0, 1, 255, 56]);
+
+        // 255 is beyond the last full alphabet cycle, and is rejected
+        assert_eq!(3, password.len());
+        assert!(password.bytes().all(|b| PASSWORD_CHARS.contains(&b)));
+        assert_eq!('a', password.chars().next().unwrap());
+        assert_eq!(PASSWORD_CHARS[0] as char, password.chars().nth(2).unwrap());
+    }
+
+    #[test]
+    fn test_sshd_drop_in() {
+        let s = sshd_drop_in(&Access::Password(None));
+        assert!(s.contains("PermitRootLogin yes\n"));
+        assert!(s.contains("PasswordAuthentication yes\n"));
+
+        let s = sshd_drop_in(&Access::AuthorizedKey("ssh-ed25519 AAAA".into()));
+        assert!(s.contains("PermitRootLogin prohibit-password\n"));
+        assert!(s.contains("PasswordAuthentication no\n"));
+    }
+}
@@ -0,0 +1,396 @@
+use std::os::unix::fs::symlink;
+use std::path::Path;
+
+use crate::errors::AliError;
+use crate::utils::fs::path_under;
+use crate::utils::readonly;
+use crate::utils::shell;
+
+const DIR_UNITS: &str = "/usr/lib/systemd/system";
+const DIR_UNITS_ETC: &str = "/etc/systemd/system";
+
+/// Exists only if the running system was booted with systemd
+const RUN_SYSTEMD: &str = "/run/systemd/system";
+
+/// Returns whether the live system runs systemd as init,
+/// like `sd_booted(3)`. ali-rs never needs it: services in new systems
+/// are enabled with symlinks, and live services are started directly.
+pub fn is_booted() -> bool {
+    Path::new(RUN_SYSTEMD).is_dir()
+}
+
+/// Enables systemd `unit` under `root` by creating symlink
+/// `{root}/etc/systemd/system/{target}.wants/{unit}`, which is what
+/// `systemctl enable` does for units with `WantedBy={target}`.
+///
+/// Template instances (e.g. `foo@bar.service`) are linked to their
+/// template unit (`foo@.service`), in `/etc/systemd/system` if it exists
+/// there, or in `/usr/lib/systemd/system`. Existing links are left untouched.
+pub fn enable_service(
+    root: &str,
+    unit: &str,
+    target: &str,
+) -> Result<(), AliError> {
+    let wants = path_under(root, &format!("{DIR_UNITS_ETC}/{target}.wants"))?;
+    let link = format!("{wants}/{unit}");
+    readonly::check(&format!("enable {unit}"))?;
+
+    if std::fs::symlink_metadata(&link).is_ok() {
+        return Ok(());
+    }
+
+    std::fs::create_dir_all(&wants)
+        .map_err(|err| AliError::FileError(err, wants.clone()))?;
+
+    let template = template_unit(unit);
+    let dir = match Path::new(&format!("{root}{DIR_UNITS_ETC}/{template}"))
+        .exists()
+    {
+        true => DIR_UNITS_ETC,
+        false => DIR_UNITS,
+    };
+
+    symlink(format!("{dir}/{template}"), &link)
+        .map_err(|err| AliError::FileError(err, format!("symlink {link}")))
+}
+
+/// Masks systemd `unit` under `root` by linking
+/// `{root}/etc/systemd/system/{unit}` to `/dev/null`,
+/// which is what `systemctl mask` does
+pub fn mask(root: &str, unit: &str) -> Result<(), AliError> {
+    let dir = path_under(root, DIR_UNITS_ETC)?;
+    let link = format!("{dir}/{unit}");
+    readonly::check(&format!("mask {unit}"))?;
+
+    if std::fs::symlink_metadata(&link).is_ok() {
+        return Ok(());
+    }
+
+    std::fs::create_dir_all(&dir)
+        .map_err(|err| AliError::FileError(err, dir.clone()))?;
+
+    symlink("/dev/null", &link)
+        .map_err(|err| AliError::FileError(err, format!("symlink {link}")))
+}
+
+/// Returns whether `unit` under `root` is enabled, like
+/// `systemctl --root={root} is-enabled {unit}`: if it is linked, by its
+/// name or by one of its aliases, in a `*.wants` or `*.requires`
+/// directory in `/etc/systemd/system` or `/usr/lib/systemd/system`,
+/// or if it has an alias there. Masked units are never enabled.
+///
+/// With `target`, only links in `{target}.wants` and `{target}.requires`
+/// count.
+pub fn is_enabled(root: &str, unit: &str, target: Option<&str>) -> bool {
+    let root = root.trim_end_matches('/');
+    let masked = std::fs::read_link(format!("{root}{DIR_UNITS_ETC}/{unit}"))
+        .is_ok_and(|dst| dst == Path::new("/dev/null"));
+
+    if masked {
+        return false;
+    }
+
+    let aliases = aliases(root, unit);
+    if target.is_none() && !aliases.is_empty() {
+        return true;
+    }
+
+    let names: Vec<&str> = std::iter::once(unit)
+        .chain(aliases.iter().map(String::as_str))
+        .collect();
+
+    let is_dependency_dir = |dir: &str| {
+        match target {
+            Some(target) => {
+                dir == format!("{target}.wants")
+                    || dir == format!("{target}.requires")
+            }
+            None => dir.ends_with(".wants") || dir.ends_with(".requires"),
+        }
+    };
+
+    [DIR_UNITS_ETC, DIR_UNITS]
+        .iter()
+        .flat_map(|dir| entries(&format!("{root}{dir}")))
+        .filter(|(name, path)| is_dependency_dir(name) && path.is_dir())
+        .any(|(_, path)| {
+            names
+                .iter()
+                .any(|name| std::fs::symlink_metadata(path.join(name)).is_ok())
+        })
+}
+
+/// Returns names of symlinks in unit directories under `root`
+/// aliasing `unit`, e.g. `display-manager.service` for `gdm.service`
+fn aliases(root: &str, unit: &str) -> Vec<String> {
+    [DIR_UNITS_ETC, DIR_UNITS]
+        .iter()
+        .flat_map(|dir| entries(&format!("{root}{dir}")))
+        .filter(|(name, path)| {
+            name != unit
+                && std::fs::read_link(path)
+                    .is_ok_and(|dst| dst.file_name() == Some(unit.as_ref()))
+        })
+        .map(|(name, _)| name)
+        .collect()
+}
+
+/// Returns names and paths of entries in directory `dir`, if any
+fn entries(dir: &str) -> Vec<(String, std::path::PathBuf)> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+
+    entries
+        .flatten()
+        .map(|entry| {
+            (entry.file_name().to_string_lossy().to_string(), entry.path())
+        })
+        .collect()
+}
+
+/// Returns template unit name for template instance `unit`,
+/// or `unit` itself if it is not a template instance
+fn template_unit(unit: &str) -> String {
+    match (unit.split_once('@'), unit.rsplit_once('.')) {
+        (Some((prefix, _)), Some((_, suffix))) => {
+            format!("{prefix}@.{suffix}")
+        }
+        _ => unit.to_string(),
+    }
+}
+
+/// Escapes `path` for use as unit instance name,
+/// like `systemd-escape --path`, e.g. `/srv/my data` => `srv-my\x20data`
+pub fn escape_path(path: &str) -> String {
+    let path = path.trim_matches('/');
+    if path.is_empty() {
+        return "-".to_string();
+    }
+
+    path.split('/')
+        .filter(|component| !component.is_empty())
+        .collect::<Vec<_>>()
+        .join("/")
+        .bytes()
+        .enumerate()
+        .map(|(i, b)| {
+            match b {
+                b'/' => "-".to_string(),
+                b'.' if i == 0 => format!("\\x{b:02x}"),
+                b if b.is_ascii_alphanumeric() || b":_.".contains(&b) => {
+                    (b as char).to_string()
+                }
+                b => format!("\\x{b:02x}"),
+            }
+        })
+        .collect()
+}
+
+/// Property of transient unit, e.g. `MemoryMax`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Property {
+    U64(&'static str, u64),
+    String(&'static str, String),
+}
+
+/// Moves the running process into new transient scope `unit` with
+/// `properties`, like `systemd-run --scope` does for new processes.
+/// Children spawned afterwards are in the scope too.
+pub fn start_scope(
+    unit: &str,
+    properties: &[Property],
+) -> Result<(), AliError> {
+    let args = scope_args(unit, std::process::id(), properties);
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+
+    // Output is the object path of the start job
+    shell::exec_with_output("busctl", &args)?;
+
+    Ok(())
+}
+
+/// busctl arguments calling StartTransientUnit on systemd manager
+fn scope_args(unit: &str, pid: u32, properties: &[Property]) -> Vec<String> {
+    let mut args: Vec<String> = [
+        "call",
+        "org.freedesktop.systemd1",
+        "/org/freedesktop/systemd1",
+        "org.freedesktop.systemd1.Manager",
+        "StartTransientUnit",
+        "ssa(sv)a(sa(sv))",
+        unit,
+        "fail",
+    ]
+    .map(String::from)
+    .to_vec();
+
+    args.push((properties.len() + 1).to_string());
+    args.extend(["PIDs", "au", "1"].map(String::from));
+    args.push(pid.to_string());
+
+    for property in properties {
+        let (name, signature, value) = match property {
+            Property::U64(name, value) => (name, "t", value.to_string()),
+            Property::String(name, value) => (name, "s", value.clone()),
+        };
+
+        args.extend([name.to_string(), signature.to_string(), value]);
+    }
+
+    // No auxiliary units
+    args.push("0".to_string());
+
+    args
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_template_unit() {
+        let tests = [
+            ("iwd.service", "iwd.service"),
+            ("wpa_supplicant@wlan0.service", "wpa_supplicant@.service"),
+            ("wg-quick@wg0.service", "wg-quick@.service"),
+        ];
+
+        for (unit, expected) in tests {
+            assert_eq!(expected, template_unit(unit));
+        }
+    }
+
+    #[test]
+    fn test_scope_args() {
+        let args = scope_args(
+            "ali-rs-1.scope",
+            1,
+            &[
+                Property::U64("MemoryMax", 1024),
+                Property::String("Slice", "ali-rs.slice".into()),
+            ],
+        );
+
+        assert_eq!(
+            "call org.freedesktop.systemd1 /org/freedesktop/systemd1 \
+             org.freedesktop.systemd1.Manager StartTransientUnit \
+             ssa(sv)a(sa(sv)) ali-rs-1.scope fail 3 PIDs au 1 1 \
+             MemoryMax t 1024 Slice s ali-rs.slice 0",
+            args.join(" "),
+        );
+    }
+
+    #[test]
+    fn test_escape_path() {
+        let tests = [
+            ("/", "-"),
+            ("/home", "home"),
+            ("/srv/data/", "srv-data"),
+            ("/srv/my data", "srv-my\\x20data"),
+            ("/var/lib-foo", "var-lib\\x2dfoo"),
+            ("/.snapshots", "\\x2esnapshots"),
+        ];
+
+        for (path, expected) in tests {
+            assert_eq!(expected, escape_path(path));
+        }
+    }
+
+    #[test]
+    fn test_enable_service() {
+        let root = std::env::temp_dir().join("ali-rs-test-enable-service");
+        let root = root.to_str().unwrap();
+        let _ = std::fs::remove_dir_all(root);
+
+        enable_service(
+            root,
+            "wpa_supplicant@wlan0.service",
+            "multi-user.target",
+        )
+        .expect("failed to enable service");
+
+        // Enabling twice is a no-op
+        enable_service(
+            root,
+            "wpa_supplicant@wlan0.service",
+            "multi-user.target",
+        )
+        .expect("failed to enable service twice");
+
+        let link = format!(
+            "{root}/etc/systemd/system/multi-user.target.wants/wpa_supplicant@wlan0.service"
+        );
+        let dest = std::fs::read_link(link).unwrap();
+        assert_eq!(
+            "/usr/lib/systemd/system/wpa_supplicant@.service",
+            dest.to_str().unwrap(),
+        );
+        assert!(is_enabled(
+            root,
+            "wpa_supplicant@wlan0.service",
+            Some("multi-user.target"),
+        ));
+        assert!(!is_enabled(root, "sshd.service", None));
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn test_is_enabled() {
+        let root = std::env::temp_dir()
+            .join(format!("ali-rs-test-is-enabled-{}", std::process::id()));
+        let root = root.to_str().unwrap();
+        let etc = format!("{root}{DIR_UNITS_ETC}");
+        let usr = format!("{root}{DIR_UNITS}");
+
+        let links = [
+            // Enabled by distribution presets
+            (
+                format!("{usr}/sockets.target.wants/sshd.socket"),
+                "../sshd.socket",
+            ),
+            (
+                format!("{etc}/local-fs.target.requires/tmp.mount"),
+                "/usr/lib/systemd/system/tmp.mount",
+            ),
+            // Alias only
+            (
+                format!("{etc}/display-manager.service"),
+                "/usr/lib/systemd/system/gdm.service",
+            ),
+            // Enabled, but masked
+            (
+                format!("{etc}/multi-user.target.wants/foo.service"),
+                "/usr/lib/systemd/system/foo.service",
+            ),
+            (format!("{etc}/foo.service"), "/dev/null"),
+        ];
+
+        for (link, dst) in links {
+            let link = Path::new(&link);
+            std::fs::create_dir_all(link.parent().unwrap()).unwrap();
+            symlink(dst, link).unwrap();
+        }
+
+        let tests = [
+            ("sshd.socket", None, true),
+            ("sshd.socket", Some("sockets.target"), true),
+            ("sshd.socket", Some("multi-user.target"), false),
+            ("tmp.mount", Some("local-fs.target"), true),
+            ("gdm.service", None, true),
+            ("foo.service", None, false),
+            ("bar.service", None, false),
+        ];
+
+        for (unit, target, expected) in tests {
+            assert_eq!(
+                expected,
+                is_enabled(root, unit, target),
+                "{unit} {target:?}"
+            );
+        }
+
+        std::fs::remove_dir_all(root).unwrap();
+    }
+}
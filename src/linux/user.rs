@@ -1,6 +1,105 @@
 use nix::unistd::Uid;
 
+use crate::errors::AliError;
+use crate::utils::fs::path_under;
+
 /// Returns whether the current user is privileged
 pub fn is_root() -> bool {
     Uid::effective().is_root()
 }
+
+/// Account of a user in `/etc/passwd` of some root
+#[derive(Debug, Clone, PartialEq)]
+pub struct Account {
+    pub name: String,
+    pub uid: u32,
+    pub gid: u32,
+    pub home: String,
+    pub shell: String,
+}
+
+/// Looks up `name` in `/etc/passwd` under `root`, e.g. of the new
+/// system, whose users differ from those of the live system
+pub fn lookup(root: &str, name: &str) -> Result<Account, AliError> {
+    let passwd = read(root, "/etc/passwd")?;
+
+    passwd
+        .lines()
+        .find_map(|line| {
+            let fields: Vec<&str> = line.split(':').collect();
+            match fields[..] {
+                [user, _, uid, gid, _, home, shell] if user == name => {
+                    Some(Account {
+                        name: name.to_string(),
+                        uid: uid.parse().ok()?,
+                        gid: gid.parse().ok()?,
+                        home: home.to_string(),
+                        shell: shell.to_string(),
+                    })
+                }
+                _ => None,
+            }
+        })
+        .ok_or_else(|| {
+            AliError::BadArgs(format!("no such user {name} in {root}"))
+        })
+}
+
+/// Primary and supplementary group IDs of `account`,
+/// from `/etc/group` under `root`
+pub fn groups(root: &str, account: &Account) -> Result<Vec<u32>, AliError> {
+    let group = read(root, "/etc/group")?;
+
+    let mut gids = vec![account.gid];
+    for line in group.lines() {
+        let fields: Vec<&str> = line.split(':').collect();
+        let [_, _, gid, members] = fields[..] else {
+            continue;
+        };
+
+        let is_member = members.split(',').any(|m| m == account.name);
+        match gid.parse() {
+            Ok(gid) if is_member && !gids.contains(&gid) => gids.push(gid),
+            _ => {}
+        }
+    }
+
+    Ok(gids)
+}
+
+fn read(root: &str, path: &str) -> Result<String, AliError> {
+    let path = path_under(root, path)?;
+
+    std::fs::read_to_string(&path).map_err(|err| {
+        AliError::FileError(err, format!("failed to read {path}"))
+    })
+}
+
+#[test]
+fn test_lookup() {
+    let root = std::env::temp_dir().join("ali-rs-test-user");
+    let _ = std::fs::remove_dir_all(&root);
+    std::fs::create_dir_all(root.join("etc")).unwrap();
+    std::fs::write(
+        root.join("etc/passwd"),
+        "root:x:0:0::/root:/bin/bash\n\
+        builder:x:1000:1000::/home/builder:/bin/bash\n",
+    )
+    .unwrap();
+    std::fs::write(
+        root.join("etc/group"),
+        "root:x:0:root\nwheel:x:998:root,builder\nbuilder:x:1000:\n",
+    )
+    .unwrap();
+
+    let root = root.to_str().unwrap();
+    let builder = lookup(root, "builder").unwrap();
+    assert_eq!(1000, builder.uid);
+    assert_eq!("/home/builder", builder.home);
+    assert_eq!(vec![1000, 998], groups(root, &builder).unwrap());
+
+    assert!(lookup(root, "build").is_err());
+    assert!(lookup("/ali-rs-no-such-root", "root").is_err());
+
+    std::fs::remove_dir_all(root).unwrap();
+}
@@ -0,0 +1,248 @@
+use crate::ali::DiskWipe;
+use crate::errors::AliError;
+use crate::utils::shell;
+
+const MIB: u64 = 1024 * 1024;
+
+/// Wipes `device` as in `wipe`, before its partition table is created
+pub fn wipe(device: &str, wipe: DiskWipe) -> Result<(), AliError> {
+    if wipe == DiskWipe::None {
+        return Ok(());
+    }
+
+    wipe_signatures(device)?;
+
+    if wipe == DiskWipe::Full {
+        match discard_max(device)? {
+            0 => zero_ends(device)?,
+            _ => shell::exec("blkdiscard", &["-f", device])?,
+        }
+    }
+
+    Ok(())
+}
+
+/// Describes what [`wipe`] does to `device`, e.g. in plans
+pub fn describe(device: &str, wipe: DiskWipe) -> String {
+    match wipe {
+        DiskWipe::None => format!("keep {device} as is"),
+        DiskWipe::Signatures => {
+            format!("wipefs -a {device} and its partitions")
+        }
+        DiskWipe::Full => {
+            format!(
+                "wipefs -a {device} and its partitions, then blkdiscard \
+                {device}, or zero its first and last MiB if not supported"
+            )
+        }
+    }
+}
+
+/// Wipes signatures on existing partitions of `device` first,
+/// since they would otherwise reappear if new partitions start
+/// at the same offsets. Fails if LVM volumes or LUKS mappings
+/// still hold `device`, as wipefs cannot wipe busy devices.
+///
+/// Executes:
+/// ```shell
+/// wipefs -a <partitions..>
+/// wipefs -a <device>
+/// ```
+fn wipe_signatures(device: &str) -> Result<(), AliError> {
+    let (partitions, holders) = partitions(device)?;
+    if !holders.is_empty() {
+        return Err(AliError::Validation(format!(
+            "cannot wipe {device}, still in use by {}: deactivate its \
+            volume groups (vgchange -an <VG>) and close its LUKS mappings \
+            (cryptsetup close <NAME>) first",
+            holders.join(", "),
+        )));
+    }
+
+    if !partitions.is_empty() {
+        let mut args = vec!["-a"];
+        args.extend(partitions.iter().map(String::as_str));
+
+        shell::exec("wipefs", &args)?;
+    }
+
+    shell::exec("wipefs", &["-a", device])
+}
+
+/// Zeroes first and last MiB of `device`, where GPT headers are.
+/// The last MiB is addressed in bytes, as disk sizes are not always
+/// multiples of 1MiB.
+///
+/// Executes:
+/// ```shell
+/// dd if=/dev/zero of=<device> bs=1M count=1 conv=fsync
+/// dd if=/dev/zero of=<device> bs=1M count=1 oflag=seek_bytes seek=<SIZE - 1MiB> conv=fsync
+/// ```
+fn zero_ends(device: &str) -> Result<(), AliError> {
+    let size = lsblk_bytes(device, "SIZE")?;
+    let of = format!("of={device}");
+    let dd = |seek: &[&str]| {
+        let mut args = vec!["if=/dev/zero", &of, "bs=1M", "count=1"];
+        args.extend(seek);
+        args.push("conv=fsync");
+
+        shell::exec("dd", &args)
+    };
+
+    dd(&[])?;
+
+    let last = size.saturating_sub(MIB);
+    if last > 0 {
+        dd(&["oflag=seek_bytes", &format!("seek={last}")])?;
+    }
+
+    Ok(())
+}
+
+/// Maximum bytes discarded at once by `device`, 0 if discard
+/// is not supported (e.g. by HDDs)
+fn discard_max(device: &str) -> Result<u64, AliError> {
+    lsblk_bytes(device, "DISC-MAX")
+}
+
+/// Executes:
+/// ```shell
+/// lsblk --bytes --nodeps --noheadings -o <column> <device>
+/// ```
+fn lsblk_bytes(device: &str, column: &str) -> Result<u64, AliError> {
+    let output = shell::exec_with_output_timeout(
+        "lsblk",
+        &["--bytes", "--nodeps", "--noheadings", "-o", column, device],
+        Some(shell::PROBE_TIMEOUT),
+    )?;
+
+    let output = String::from_utf8_lossy(&output);
+    output.trim().parse().map_err(|_| {
+        AliError::AliRsBug(format!(
+            "unexpected lsblk {column} of {device}: {output}"
+        ))
+    })
+}
+
+/// Returns partitions of `device`, and its holders (LVM volumes
+/// and LUKS mappings on it or its partitions)
+///
+/// Executes:
+/// ```shell
+/// lsblk --list --noheadings --paths -o NAME,TYPE <device>
+/// ```
+fn partitions(
+    device: &str,
+) -> Result<(Vec<String>, Vec<String>), AliError> {
+    let output = shell::exec_with_output_timeout(
+        "lsblk",
+        &["--list", "--noheadings", "--paths", "-o", "NAME,TYPE", device],
+        Some(shell::PROBE_TIMEOUT),
+    )?;
+
+    Ok(parse_partitions(&String::from_utf8_lossy(&output)))
+}
+
+fn parse_partitions(lsblk: &str) -> (Vec<String>, Vec<String>) {
+    let mut partitions = Vec::new();
+    let mut holders = Vec::new();
+
+    for line in lsblk.lines() {
+        match line.split_whitespace().collect::<Vec<_>>()[..] {
+            [name, "part"] => partitions.push(name.to_string()),
+            [name, "lvm" | "crypt"] => holders.push(name.to_string()),
+            _ => {}
+        }
+    }
+
+    (partitions, holders)
+}
+
+#[test]
+fn test_wipe() {
+    use std::sync::Arc;
+
+    use crate::utils::mock::MockRunner;
+
+    let lsblk_parts = "lsblk --list --noheadings --paths -o NAME,TYPE";
+    let lsblk_size = "lsblk --bytes --nodeps --noheadings -o SIZE";
+    let lsblk_disc = "lsblk --bytes --nodeps --noheadings -o DISC-MAX";
+
+    assert_eq!(
+        (
+            vec!["/dev/vda1".to_string(), "/dev/vda2".to_string()],
+            vec![
+                "/dev/mapper/cryptlvm".to_string(),
+                "/dev/mapper/vg-root".to_string(),
+            ],
+        ),
+        parse_partitions(
+            "/dev/vda disk
+/dev/vda1 part
+/dev/vda2 part
+/dev/mapper/cryptlvm crypt
+/dev/mapper/vg-root lvm
+"
+        ),
+    );
+
+    let mock = Arc::new(
+        MockRunner::new()
+            .stdout(lsblk_parts, "/dev/vda disk\n/dev/vda1 part\n")
+            .stdout(lsblk_size, "10737942528\n")
+            .stdout(lsblk_disc, "0\n"),
+    );
+    let _guard = shell::set_runner(mock.clone());
+
+    wipe("/dev/vda", DiskWipe::None).unwrap();
+    assert!(mock.calls().is_empty());
+
+    wipe("/dev/vda", DiskWipe::Signatures).unwrap();
+    assert_eq!(
+        vec![
+            format!("{lsblk_parts} /dev/vda"),
+            "wipefs -a /dev/vda1".to_string(),
+            "wipefs -a /dev/vda".to_string(),
+        ],
+        mock.calls(),
+    );
+
+    // HDDs get their first and last MiB zeroed
+    wipe("/dev/vda", DiskWipe::Full).unwrap();
+    let calls = mock.calls();
+    assert_eq!(
+        "dd if=/dev/zero of=/dev/vda bs=1M count=1 oflag=seek_bytes \
+        seek=10736893952 conv=fsync",
+        calls.last().unwrap(),
+    );
+    assert!(!calls.iter().any(|c| c.starts_with("blkdiscard")));
+
+    // SSDs get discarded instead
+    let mock = Arc::new(
+        MockRunner::new()
+            .stdout(lsblk_parts, "/dev/nvme0n1 disk\n")
+            .stdout(lsblk_disc, "2199023255040\n"),
+    );
+    let _guard = shell::set_runner(mock.clone());
+
+    wipe("/dev/nvme0n1", DiskWipe::Full).unwrap();
+    assert_eq!(
+        vec![
+            format!("{lsblk_parts} /dev/nvme0n1"),
+            "wipefs -a /dev/nvme0n1".to_string(),
+            format!("{lsblk_disc} /dev/nvme0n1"),
+            "blkdiscard -f /dev/nvme0n1".to_string(),
+        ],
+        mock.calls(),
+    );
+
+    // Busy disks are never wiped
+    let mock = Arc::new(MockRunner::new().stdout(
+        lsblk_parts,
+        "/dev/vda disk\n/dev/vda1 part\n/dev/mapper/vg-root lvm\n",
+    ));
+    let _guard = shell::set_runner(mock.clone());
+
+    assert!(wipe("/dev/vda", DiskWipe::Signatures).is_err());
+    assert_eq!(vec![format!("{lsblk_parts} /dev/vda")], mock.calls());
+}
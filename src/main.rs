@@ -1,20 +1,21 @@
-mod ali;
-mod cli;
-mod constants;
-mod errors;
-mod hooks;
-mod linux;
-mod run;
-mod types;
-mod utils;
-
-use clap::Parser;
+use ali_rs::{
+    cli,
+    errors,
+    run,
+};
 
 fn main() -> Result<(), errors::AliError> {
-    let args = cli::Cli::parse();
+    let args = match cli::parse() {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("{}", err.to_json_string());
+            std::process::exit(err.exit_code());
+        }
+    };
 
+    // Errors were already printed by run
     if let Err(err) = run::run(args) {
-        eprintln!("{}", err.to_json_string());
+        std::process::exit(err.exit_code());
     }
 
     Ok(())
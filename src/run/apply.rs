@@ -1,26 +1,426 @@
 use std::collections::HashSet;
+use std::path::Path;
 
+use serde_json::json;
+
+use super::ManifestSource;
 use crate::ali::{
     apply,
+    portable,
+    preflight,
     validation,
     Dm,
     Manifest,
+    ManifestExtraTarget,
+    ManifestRuntime,
 };
 use crate::cli;
+use crate::constants::defaults;
 use crate::errors::AliError;
+use crate::linux::{
+    mount,
+    sshd,
+    systemd,
+};
 use crate::types::report::Report;
 use crate::types::stage;
+use crate::utils::fs::file_exists;
+use crate::utils::{
+    checksum,
+    logger,
+    progress,
+    report_sink,
+    runlock,
+    secrets,
+    shell,
+};
+
+/// Name of the manifest itself among targets of a multi-target session
+const PRIMARY: &str = "primary";
+
+/// Extra target of a multi-target session, with its manifest loaded
+struct Target {
+    name: String,
+    location: String,
+    manifest: Manifest,
+}
 
 pub(super) fn run(
-    manifest_file: &str,
+    source: &ManifestSource,
     install_location: &str,
+    has_cli_proxy: bool,
     args: cli::ArgsApply,
 ) -> Result<Report, AliError> {
     let start = std::time::Instant::now();
 
-    let mut skip_stages: HashSet<stage::Stage> =
-        HashSet::from_iter(args.skip_stages);
-    if let Some(stages) = args.stages {
+    // Operators attached with --enable-ssh must not start another run
+    let _lock = runlock::acquire(defaults::RUN_LOCK)?;
+
+    let skip_stages =
+        skip_stages(args.stages, args.skip_stages, args.from_stage)?;
+
+    // manifest is mutable because we might have to
+    // help add packages such as lvm2 and btrfs-progs
+    let mut manifest = source.load()?;
+    if args.target.is_some() {
+        manifest.target = args.target;
+    }
+
+    if let Some(ref plan_file) = args.plan {
+        // Plans only cover the primary target
+        if manifest.extra_targets.is_some() {
+            return Err(AliError::BadArgs(format!(
+                "--plan {plan_file} cannot be used with extra targets"
+            )));
+        }
+
+        super::plan::check(
+            source,
+            install_location,
+            args.target,
+            &skip_stages,
+            plan_file,
+            args.allow_destroy,
+        )?;
+    }
+
+    if let Some(ref m_runtime) = manifest.runtime {
+        confine(m_runtime)?;
+    }
+
+    if let Some(ref sinks) = manifest.reports {
+        report_sink::init(sinks);
+    }
+
+    if let Some(ref providers) = manifest.secrets {
+        secrets::init(providers);
+    }
+
+    if let Some(ref country) = args.mirror_country {
+        apply::set_mirror_country(country);
+    }
+
+    if let Some(ref paths) = manifest.protected_paths {
+        crate::hooks::set_protected_paths(paths.clone());
+    }
+
+    let mut targets = extra_targets(source, &manifest, install_location)?;
+    let apply_primary = match args.only_target.as_deref() {
+        None => true,
+        Some(PRIMARY) => {
+            targets.clear();
+            true
+        }
+        Some(name) if targets.iter().any(|t| t.name == name) => {
+            targets.retain(|t| t.name == name);
+            false
+        }
+        Some(name) => {
+            return Err(AliError::BadArgs(format!("no such target {name}")));
+        }
+    };
+
+    // Progress and reports name targets only in multi-target sessions
+    let multi_target = manifest.extra_targets.is_some();
+    if multi_target {
+        // Later targets reuse packages downloaded for earlier ones
+        apply::set_shared_cache();
+    }
+
+    if !args.no_preflight {
+        if apply_primary {
+            preflight::preflight(&manifest, &skip_stages)?;
+        }
+
+        for target in &targets {
+            preflight::preflight(&target.manifest, &skip_stages)?;
+        }
+    }
+
+    // Extra targets are validated right before they are applied,
+    // since their devices may be created by earlier targets
+    if apply_primary && !args.no_validate {
+        validation::validate(&manifest, install_location, args.overwrite)?;
+    }
+
+    if !args.yes && !skip_stages.contains(&stage::Stage::Mountpoints) {
+        if apply_primary {
+            super::confirm::confirm_disks(&manifest)?;
+        }
+
+        for target in &targets {
+            super::confirm::confirm_disks(&target.manifest)?;
+        }
+    }
+
+    let mut ssh_fingerprint = None;
+    if args.enable_ssh {
+        let access = match args.ssh_key {
+            Some(key) => sshd::Access::AuthorizedKey(key),
+            None => {
+                let password = args
+                    .ssh_password_file
+                    .map(|file| sshd::read_password(&file))
+                    .transpose()?;
+
+                sshd::Access::Password(password)
+            }
+        };
+
+        let live_ssh = sshd::enable_live(&access)?;
+        // Not logged, since it may show the root password
+        eprintln!("{live_ssh}");
+
+        ssh_fingerprint = live_ssh.fingerprint;
+    }
+
+    // Update manifest in some cases
+    update_manifest(&mut manifest);
+    for target in targets.iter_mut() {
+        update_manifest(&mut target.manifest);
+    }
+
+    // Tunnel (if any) must outlive all stages
+    // CLI proxy (if any) was set up before loading manifest
+    let _tunnel = match has_cli_proxy {
+        true => None,
+        false => super::setup_proxy(manifest.proxy.as_deref())?,
+    };
+
+    // Ctrl-C from here on cancels commands and fails the current stage,
+    // instead of leaving the disks mounted by a killed ali-rs
+    shell::handle_sigint();
+
+    // Apply manifests to their locations, one target after another
+    let mut reports = Vec::new();
+    if apply_primary {
+        if multi_target {
+            progress::target(PRIMARY);
+        }
+
+        let location = super::install_location();
+        let summary = apply_target(&manifest, &location, &skip_stages)?;
+        reports.push((
+            PRIMARY.to_string(),
+            Report {
+                location,
+                summary,
+                duration: start.elapsed(),
+                ssh_fingerprint: None,
+                targets: vec![],
+            },
+        ));
+    }
+
+    for target in targets {
+        progress::target(&target.name);
+        let target_start = std::time::Instant::now();
+
+        if !args.no_validate {
+            validation::validate(
+                &target.manifest,
+                &target.location,
+                args.overwrite,
+            )?;
+        }
+
+        let summary =
+            apply_target(&target.manifest, &target.location, &skip_stages)?;
+        reports.push((
+            target.name,
+            Report {
+                location: target.location,
+                summary,
+                duration: target_start.elapsed(),
+                ssh_fingerprint: None,
+                targets: vec![],
+            },
+        ));
+    }
+
+    let mut reports = reports.into_iter();
+    let Some((_, mut report)) = reports.next() else {
+        return Err(AliError::AliRsBug("no targets applied".to_string()));
+    };
+
+    report.duration = start.elapsed();
+    report.ssh_fingerprint = ssh_fingerprint;
+    report.targets = reports.collect();
+
+    report_sink::emit("report", json!({ "report": report.to_json() }));
+
+    Ok(report)
+}
+
+/// Applies `manifest` to `location`, emitting error (if any)
+/// to report sinks
+fn apply_target(
+    manifest: &Manifest,
+    location: &str,
+    skip_stages: &HashSet<stage::Stage>,
+) -> Result<Box<stage::StageActions>, AliError> {
+    let result = apply::apply_manifest(manifest, location, skip_stages.clone());
+    if let Err(ref err) = result {
+        log::debug!("apply failed: {err}");
+    }
+
+    // Keep log in new system if base system was installed
+    if file_exists(format!("{location}/etc")) {
+        logger::copy_to(location);
+    }
+
+    if shell::is_cancelled() {
+        mount::umount_all(location);
+    }
+
+    if let Err(ref err) = result {
+        let error = err.to_json_string();
+        report_sink::emit(
+            "error",
+            json!({
+                "error": serde_json::from_str(&error)
+                    .unwrap_or(serde_json::Value::String(error)),
+            }),
+        );
+    }
+
+    result
+}
+
+/// Loads manifests of extra targets of `manifest`,
+/// itself installed to `install_location`
+fn extra_targets(
+    source: &ManifestSource,
+    manifest: &Manifest,
+    install_location: &str,
+) -> Result<Vec<Target>, AliError> {
+    let Some(ref m_targets) = manifest.extra_targets else {
+        return Ok(vec![]);
+    };
+
+    check_targets(install_location, m_targets)?;
+
+    m_targets
+        .iter()
+        .map(|m_target| {
+            let target_manifest = source
+                .relative(&m_target.manifest, m_target.sha256.as_deref())?
+                .load()?;
+
+            if target_manifest.extra_targets.is_some() {
+                return Err(AliError::BadManifest(format!(
+                    "extra target {} has extra targets of its own",
+                    m_target.name
+                )));
+            }
+
+            check_session_keys(&m_target.name, &target_manifest)?;
+
+            Ok(Target {
+                name: m_target.name.clone(),
+                location: m_target.location.clone(),
+                manifest: target_manifest,
+            })
+        })
+        .collect()
+}
+
+/// Returns error if manifest of extra target `name` sets keys that
+/// configure the whole session, which only the primary manifest may set
+fn check_session_keys(name: &str, manifest: &Manifest) -> Result<(), AliError> {
+    let keys: Vec<&str> = [
+        ("secrets", manifest.secrets.is_some()),
+        ("reports", manifest.reports.is_some()),
+        ("protected_paths", manifest.protected_paths.is_some()),
+        ("runtime", manifest.runtime.is_some()),
+    ]
+    .into_iter()
+    .filter_map(|(key, is_set)| is_set.then_some(key))
+    .collect();
+
+    if keys.is_empty() {
+        return Ok(());
+    }
+
+    Err(AliError::BadManifest(format!(
+        "manifest of extra target {name} sets {}, which only the primary \
+        manifest may set for the whole session",
+        keys.join(", ")
+    )))
+}
+
+/// Checks that names of extra targets are unique, and that install
+/// locations are absolute and not nested in one another
+fn check_targets(
+    install_location: &str,
+    m_targets: &[ManifestExtraTarget],
+) -> Result<(), AliError> {
+    let mut names = HashSet::from([PRIMARY]);
+    let mut locations = vec![Path::new(install_location)];
+
+    for m_target in m_targets {
+        let name = m_target.name.as_str();
+        if name.is_empty() || !names.insert(name) {
+            return Err(AliError::BadManifest(format!(
+                "empty, duplicate, or reserved target name '{name}'"
+            )));
+        }
+
+        let location = Path::new(&m_target.location);
+        if !location.is_absolute() {
+            return Err(AliError::BadManifest(format!(
+                "location {} of target {name} is not absolute",
+                location.display()
+            )));
+        }
+
+        let overlapped = locations.iter().find(|other| {
+            location.starts_with(other) || other.starts_with(location)
+        });
+
+        if let Some(other) = overlapped {
+            return Err(AliError::BadManifest(format!(
+                "location {} of target {name} overlaps {}",
+                location.display(),
+                other.display()
+            )));
+        }
+
+        locations.push(location);
+
+        if let Some(ref sum) = m_target.sha256 {
+            if !checksum::is_sha256_hex(sum) {
+                return Err(AliError::BadManifest(format!(
+                    "bad sha256 checksum {sum} of target {name}"
+                )));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Stages to skip, from explicit stages (`--only`), stages to skip,
+/// and first stage to run (`--from`)
+fn skip_stages(
+    stages: Option<Vec<stage::Stage>>,
+    skip: Vec<stage::Stage>,
+    from: Option<stage::Stage>,
+) -> Result<HashSet<stage::Stage>, AliError> {
+    let mut skip_stages: HashSet<stage::Stage> = HashSet::from_iter(skip);
+
+    if let Some(from) = from {
+        if skip_stages.contains(&from) {
+            return Err(AliError::BadArgs(format!(
+                "stage {from} is ambiguous"
+            )));
+        }
+
+        let before = stage::STAGES.iter().take_while(|s| **s != from);
+        skip_stages.extend(before.cloned());
+    }
+
+    if let Some(stages) = stages {
         for explicit_stage in stages.iter() {
             if skip_stages.contains(explicit_stage) {
                 return Err(AliError::BadArgs(format!(
@@ -29,49 +429,39 @@ pub(super) fn run(
             }
         }
 
-        let mut all_stages: HashSet<stage::Stage> =
-            HashSet::from(stage::STAGES);
-        for skip in skip_stages.iter() {
-            all_stages.remove(skip);
-        }
-        skip_stages = HashSet::new();
-
         let explicit_stages: HashSet<stage::Stage> = HashSet::from_iter(stages);
-        let diff: HashSet<_> =
-            all_stages.difference(&explicit_stages).collect();
-        for d in diff {
-            skip_stages.insert(d.to_owned());
-        }
+        skip_stages = stage::STAGES
+            .iter()
+            .filter(|s| !explicit_stages.contains(s))
+            .cloned()
+            .collect();
     }
 
-    let manifest_yaml = std::fs::read_to_string(manifest_file)
-        .map_err(|err| AliError::NoSuchFile(err, manifest_file.to_string()))?;
+    if skip_stages.len() == stage::STAGES.len() {
+        return Err(AliError::BadArgs("all stages are skipped".to_string()));
+    }
 
-    // manifest is mutable because we might have to
-    // help add packages such as lvm2 and btrfs-progs
-    let mut manifest = Manifest::from_yaml(&manifest_yaml)?;
+    Ok(skip_stages)
+}
 
-    if !args.no_validate {
-        validation::validate(&manifest, install_location, args.overwrite)?;
+/// Moves this run into a transient systemd scope
+/// with limits from manifest key `runtime`
+fn confine(m_runtime: &ManifestRuntime) -> Result<(), AliError> {
+    let properties = m_runtime.properties()?;
+    if !systemd::is_booted() {
+        log::warn!("not running under systemd, ignoring manifest key runtime");
+        return Ok(());
     }
 
-    // Update manifest in some cases
-    update_manifest(&mut manifest);
-
-    // Apply manifest to location
-    let location = super::install_location();
-    let stages_applied =
-        apply::apply_manifest(&manifest, &location, skip_stages)?;
+    let unit = format!("ali-rs-{}.scope", std::process::id());
+    systemd::start_scope(&unit, &properties)?;
+    log::info!("confined to transient systemd scope {unit}");
 
-    Ok(Report {
-        location,
-        summary: stages_applied,
-        duration: start.elapsed(),
-    })
+    Ok(())
 }
 
 // Update manifest to suit the manifest
-fn update_manifest(manifest: &mut Manifest) {
+pub(super) fn update_manifest(manifest: &mut Manifest) {
     let (lvm2, btrfs, btrfs_progs) = (
         "lvm2".to_string(),
         "btrfs".to_string(),
@@ -132,4 +522,164 @@ fn update_manifest(manifest: &mut Manifest) {
         }
         _ => {}
     }
+
+    // Update manifest.pacstraps with bootloader and microcode for portable
+    if manifest.is_portable() {
+        manifest
+            .pacstraps
+            .get_or_insert_with(HashSet::new)
+            .extend(portable::PACKAGES.map(String::from));
+    }
+
+    // Update manifest.pacstraps with initramfs hooks for network root
+    if let Some(ref netroot) = manifest.netroot {
+        manifest
+            .pacstraps
+            .get_or_insert_with(HashSet::new)
+            .extend(netroot.packages().map(String::from));
+    }
+
+    // Update manifest.pacstraps with OpenSSH client for ssh client config
+    if manifest.ssh.is_some() {
+        manifest
+            .pacstraps
+            .get_or_insert_with(HashSet::new)
+            .insert("openssh".to_string());
+    }
+
+    // Update manifest.pacstraps with update-ca-trust for CA certificates
+    if manifest.ca_certs.is_some() {
+        manifest
+            .pacstraps
+            .get_or_insert_with(HashSet::new)
+            .insert("ca-certificates".to_string());
+    }
+
+    // Update manifest.pacstraps with chrony and linuxptp for time sync
+    if let Some(ref m_time) = manifest.time {
+        manifest
+            .pacstraps
+            .get_or_insert_with(HashSet::new)
+            .extend(m_time.packages().into_iter().map(String::from));
+    }
+
+    // Update manifest.pacstraps with adcli and SSSD for domain join
+    if let Some(ref m_join) = manifest.domain_join {
+        manifest
+            .pacstraps
+            .get_or_insert_with(HashSet::new)
+            .extend(m_join.packages().map(String::from));
+    }
+
+    // Update manifest.pacstraps with smartmontools and mailer for maintenance
+    if let Some(ref maintenance) = manifest.maintenance {
+        manifest
+            .pacstraps
+            .get_or_insert_with(HashSet::new)
+            .extend(maintenance.packages().into_iter().map(String::from));
+    }
+}
+
+#[test]
+fn test_skip_stages() {
+    use stage::Stage;
+
+    let skip = skip_stages(None, vec![Stage::Mountpoints], None).unwrap();
+    assert_eq!(HashSet::from([Stage::Mountpoints]), skip);
+
+    let skip = skip_stages(None, vec![], Some(Stage::Bootstrap)).unwrap();
+    assert_eq!(HashSet::from([Stage::Mountpoints]), skip);
+
+    let skip = skip_stages(
+        None,
+        vec![Stage::PostInstallUser],
+        Some(Stage::Routines),
+    )
+    .unwrap();
+    assert_eq!(
+        HashSet::from([
+            Stage::Mountpoints,
+            Stage::Bootstrap,
+            Stage::PostInstallUser
+        ]),
+        skip,
+    );
+
+    let only = Some(vec![Stage::ChrootUser]);
+    let skip = skip_stages(only, vec![], None).unwrap();
+    assert_eq!(stage::STAGES.len() - 1, skip.len());
+    assert!(!skip.contains(&Stage::ChrootUser));
+
+    let should_err = [
+        (Some(vec![Stage::ChrootUser]), vec![Stage::ChrootUser], None),
+        (None, vec![Stage::Bootstrap], Some(Stage::Bootstrap)),
+        (None, vec![Stage::PostInstallUser], Some(Stage::PostInstallUser)),
+        (Some(vec![]), vec![], None),
+    ];
+
+    for (stages, skip, from) in should_err {
+        assert!(skip_stages(stages, skip, from).is_err());
+    }
+}
+
+#[test]
+fn test_check_targets() {
+    let target = |name: &str, location: &str| {
+        ManifestExtraTarget {
+            name: name.to_string(),
+            location: location.to_string(),
+            manifest: "rescue.yaml".to_string(),
+            sha256: None,
+        }
+    };
+
+    assert!(check_targets("/mnt", &[]).is_ok());
+    assert!(check_targets(
+        "/mnt",
+        &[target("rescue", "/rescue"), target("spare", "/spare")]
+    )
+    .is_ok());
+
+    let should_err = [
+        vec![target("primary", "/rescue")],
+        vec![target("", "/rescue")],
+        vec![target("rescue", "/rescue"), target("rescue", "/spare")],
+        vec![target("rescue", "rescue")],
+        vec![target("rescue", "/mnt")],
+        vec![target("rescue", "/mnt/rescue")],
+        vec![target("rescue", "/")],
+        vec![target("rescue", "/rescue"), target("spare", "/rescue/spare")],
+        vec![ManifestExtraTarget {
+            sha256: Some("abc".to_string()),
+            ..target("rescue", "/rescue")
+        }],
+    ];
+
+    for m_targets in should_err {
+        assert!(check_targets("/mnt", &m_targets).is_err());
+    }
+
+    // Sibling prefixes are not nested
+    assert!(check_targets("/mnt", &[target("rescue", "/mnt-rescue")]).is_ok());
+}
+
+#[test]
+fn test_check_session_keys() {
+    let manifest = Manifest {
+        version: 1,
+        ..Default::default()
+    };
+    assert!(check_session_keys("rescue", &manifest).is_ok());
+
+    let manifest = Manifest {
+        protected_paths: Some(vec!["/etc/fstab".into()]),
+        secrets: Some(vec![]),
+        ..manifest
+    };
+    match check_session_keys("rescue", &manifest) {
+        Err(AliError::BadManifest(msg)) => {
+            assert!(msg.contains("sets secrets, protected_paths,"), "{msg}");
+        }
+        result => panic!("unexpected result {result:?}"),
+    }
 }
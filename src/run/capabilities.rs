@@ -0,0 +1,157 @@
+use crate::constants;
+use crate::utils::shell;
+
+/// ali-rs subsystem, and external programs it runs on the live system
+struct Subsystem {
+    name: &'static str,
+    programs: &'static [&'static str],
+}
+
+/// Programs checked before applying manifests
+const VALIDATION: Subsystem = Subsystem {
+    name: "validation",
+    programs: &constants::REQUIRED_COMMANDS,
+};
+
+/// Programs with placeholders, e.g. `mkfs.<FS>`, depend on the manifest
+const STORAGE: Subsystem = Subsystem {
+    name: "storage",
+    programs: &[
+        "sh",
+        "printf",
+        "fdisk",
+        "wipefs",
+        "blkdiscard",
+        "dd",
+        "blkid",
+        "lsblk",
+        "pvs",
+        "vgs",
+        "lvs",
+        "cryptsetup",
+        "pvcreate",
+        "vgcreate",
+        "lvcreate",
+        "mkfs.<FS>",
+        "mkswap",
+        "mount",
+    ],
+};
+
+const BOOTSTRAP: Subsystem = Subsystem {
+    name: "bootstrap",
+    programs: &["pacstrap", "reflector"],
+};
+
+const ROUTINES: Subsystem = Subsystem {
+    name: "routines",
+    programs: &["genfstab", "arch-chroot"],
+};
+
+const HOOKS: Subsystem = Subsystem {
+    name: "hooks",
+    programs: &["sh", "arch-chroot", "systemd-detect-virt"],
+};
+
+const RESCUE: Subsystem = Subsystem {
+    name: "rescue",
+    programs: &[
+        "sh",
+        "cryptsetup",
+        "vgchange",
+        "mount",
+        "swapon",
+        "fsck.<FS>",
+        "btrfs",
+        "arch-chroot",
+    ],
+};
+
+const REMOTE_ACCESS: Subsystem = Subsystem {
+    name: "remote-access",
+    programs: &["sshd", "ssh-keygen", "chpasswd"],
+};
+
+const PROXY: Subsystem = Subsystem {
+    name: "proxy",
+    programs: &["ssh"],
+};
+
+const REPORT_SINKS: Subsystem = Subsystem {
+    name: "report-sinks",
+    programs: &["sh"],
+};
+
+const ACCESSIBLE: Subsystem = Subsystem {
+    name: "accessible",
+    programs: &["espeak-ng"],
+};
+
+/// Prints enabled features, and external programs required at runtime
+/// by each subsystem, marking those not found in PATH
+pub fn run() {
+    println!("ali-rs {}", env!("CARGO_PKG_VERSION"));
+    println!("features: {}", features().join(" "));
+
+    for subsystem in subsystems() {
+        let programs: Vec<String> = subsystem
+            .programs
+            .iter()
+            .map(|program| {
+                match is_placeholder(program) || shell::in_path(program) {
+                    true => program.to_string(),
+                    false => format!("{program} (missing)"),
+                }
+            })
+            .collect();
+
+        println!("{}: {}", subsystem.name, programs.join(", "));
+    }
+}
+
+/// Cargo features ali-rs was built with
+fn features() -> Vec<&'static str> {
+    [
+        ("tui", cfg!(feature = "tui")),
+        ("qr", cfg!(feature = "qr")),
+        ("remote", cfg!(feature = "remote")),
+        ("wasm", cfg!(feature = "wasm")),
+    ]
+    .into_iter()
+    .filter_map(|(feature, enabled)| enabled.then_some(feature))
+    .collect()
+}
+
+/// Subsystems running external programs. Downloads, remote manifests,
+/// and HTTP report sinks (feature `remote`) need none.
+fn subsystems() -> Vec<Subsystem> {
+    vec![
+        VALIDATION,
+        STORAGE,
+        BOOTSTRAP,
+        ROUTINES,
+        HOOKS,
+        RESCUE,
+        REMOTE_ACCESS,
+        PROXY,
+        REPORT_SINKS,
+        ACCESSIBLE,
+    ]
+}
+
+fn is_placeholder(program: &str) -> bool {
+    program.contains('<')
+}
+
+#[test]
+fn test_capabilities() {
+    let mut names = std::collections::HashSet::new();
+    for subsystem in subsystems() {
+        assert!(names.insert(subsystem.name), "duplicate {}", subsystem.name);
+        assert!(!subsystem.programs.is_empty(), "{}", subsystem.name);
+    }
+
+    assert!(is_placeholder("mkfs.<FS>"));
+    assert!(!is_placeholder("mkfs.btrfs"));
+    assert_eq!(cfg!(feature = "remote"), features().contains(&"remote"));
+}
@@ -0,0 +1,112 @@
+use clap::CommandFactory;
+use clap_complete::Shell;
+
+use crate::cli::{
+    Cli,
+    CompletionShell,
+};
+use crate::hooks;
+
+const BIN_NAME: &str = "ali-rs";
+
+/// Completes hook strings of `ali-rs hooks` with `ali-rs hooks complete`,
+/// falling back to clap-generated function `_ali__rs`
+const DYNAMIC_BASH: &str = r#"
+_ali__rs_hooks() {
+    local cur="${COMP_WORDS[COMP_CWORD]}"
+    local quote=""
+    if [[ "${cur}" == [\"\']* ]]; then
+        quote="${cur:0:1}"
+        cur="${cur:1}"
+    fi
+
+    if [[ " ${COMP_WORDS[*]:1:COMP_CWORD-1} " == *" hooks "* && "${cur}" == @* ]]; then
+        local IFS=$'\n'
+        COMPREPLY=($(ali-rs hooks complete -- "${cur}" 2>/dev/null))
+        COMPREPLY=("${COMPREPLY[@]/#/${quote}}")
+        compopt -o nospace
+        return 0
+    fi
+
+    _ali__rs "$@"
+}
+
+complete -F _ali__rs_hooks -o bashdefault -o default ali-rs
+"#;
+
+/// Overrides clap-generated `compdef`, so the script must be sourced
+const DYNAMIC_ZSH: &str = r#"
+_ali-rs_hooks() {
+    if (( ${words[(I)hooks]} )) && [[ "${PREFIX}" == @* ]]; then
+        local -a completions
+        completions=(${(f)"$(ali-rs hooks complete -- "${PREFIX}" 2>/dev/null)"})
+        compadd -S '' -- "${completions[@]}"
+        return
+    fi
+
+    _ali-rs "$@"
+}
+
+compdef _ali-rs_hooks ali-rs
+"#;
+
+const DYNAMIC_FISH: &str = r#"
+function __ali_rs_hooks_complete
+    set -l token (commandline -ct | string trim --left --chars="\"'")
+    string match -q -- '@*' $token; or return
+    ali-rs hooks complete -- $token 2>/dev/null
+end
+
+complete -c ali-rs -n "__fish_seen_subcommand_from hooks" -f -a "(__ali_rs_hooks_complete)"
+"#;
+
+/// Prints completion script for `shell`
+pub(super) fn run(shell: CompletionShell) {
+    print!("{}", script(shell));
+}
+
+/// Prints completions of partial hook command, one per line
+pub(super) fn complete_hook(partial: &str) {
+    for completion in hooks::hook_completions(partial) {
+        println!("{completion}");
+    }
+}
+
+/// Returns clap-generated completion script with dynamic
+/// completion of hook strings appended
+fn script(shell: CompletionShell) -> String {
+    let (generator, dynamic) = match shell {
+        CompletionShell::Bash => (Shell::Bash, DYNAMIC_BASH),
+        CompletionShell::Zsh => (Shell::Zsh, DYNAMIC_ZSH),
+        CompletionShell::Fish => (Shell::Fish, DYNAMIC_FISH),
+    };
+
+    let mut buf = Vec::new();
+    clap_complete::generate(generator, &mut Cli::command(), BIN_NAME, &mut buf);
+
+    let script = String::from_utf8_lossy(&buf).to_string();
+    let script = match shell {
+        // clap_complete mangles `-` in bin name differently in bash case
+        // labels (`ali__subcmd__rs`) than in their matches (`ali__rs`)
+        CompletionShell::Bash => script.replace("ali__subcmd__rs", "ali__rs"),
+        _ => script,
+    };
+
+    script + dynamic
+}
+
+#[test]
+fn test_completions() {
+    let bash = script(CompletionShell::Bash);
+    assert!(bash.contains("_ali__rs() {"));
+    assert!(bash.ends_with(DYNAMIC_BASH));
+
+    let zsh = script(CompletionShell::Zsh);
+    assert!(zsh.starts_with("#compdef ali-rs"));
+    assert!(zsh.contains("_ali-rs() {"));
+
+    let fish = script(CompletionShell::Fish);
+    assert!(fish.contains("complete -c ali-rs"));
+    assert!(fish.contains("ali-rs hooks complete --"));
+    assert!(!bash.contains("ali__subcmd__rs"));
+}
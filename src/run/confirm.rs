@@ -0,0 +1,37 @@
+use std::io::Write;
+
+use colored::Colorize;
+
+use crate::ali::Manifest;
+use crate::errors::AliError;
+use crate::linux::lsblk;
+
+/// Prints identity and current layout of each manifest disk,
+/// and requires operator to type the device name before it is wiped
+pub(super) fn confirm_disks(manifest: &Manifest) -> Result<(), AliError> {
+    for disk in manifest.disks.iter().flatten() {
+        let device = &disk.device;
+        let info = lsblk::disk_info(device)?;
+        let layout = lsblk::layout(device)?;
+
+        eprintln!(
+            "{}\n{info}\n\n{layout}",
+            format!("ali-rs will WIPE disk {device}:").red().bold(),
+        );
+        eprint!("Type {} to confirm: ", device.bold());
+        std::io::stderr().flush().ok();
+
+        let mut answer = String::new();
+        std::io::stdin().read_line(&mut answer).map_err(|err| {
+            AliError::FileError(err, "failed to read confirmation".to_string())
+        })?;
+
+        if answer.trim() != device {
+            return Err(AliError::Aborted(format!(
+                "confirmation for {device} did not match, use --yes to skip confirmation"
+            )));
+        }
+    }
+
+    Ok(())
+}
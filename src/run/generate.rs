@@ -0,0 +1,29 @@
+use crate::ali::{
+    self,
+    generate,
+};
+use crate::cli;
+use crate::errors::AliError;
+
+pub(super) fn run(args: cli::ArgsGenerate) -> Result<(), AliError> {
+    let system = generate::inspect(&args.root)?;
+    let manifest = system.manifest()?;
+    let yaml = ali::to_yaml(&manifest)?;
+
+    log::warn!(
+        "generated manifest has no disks, device mappers, or root password, which must be added before applying"
+    );
+
+    match args.output {
+        None => print!("{yaml}"),
+        Some(output) => {
+            std::fs::write(&output, yaml).map_err(|err| {
+                AliError::FileError(err, format!("failed to write {output}"))
+            })?;
+
+            log::info!("manifest written to {output}");
+        }
+    }
+
+    Ok(())
+}
@@ -1,21 +1,113 @@
-use crate::ali::Manifest;
+use super::ManifestSource;
+use crate::ali::groups;
 use crate::errors::AliError;
+use crate::utils::json;
 use crate::{
     cli,
     hooks,
 };
 
 pub fn run(
-    manifest: &String,
+    source: &ManifestSource,
+    has_cli_proxy: bool,
     cli_args: cli::ArgsHooks,
 ) -> Result<(), AliError> {
-    let hooks = collect_hooks(manifest, &cli_args)?;
+    match cli_args.commands {
+        Some(cli::CommandsHooks::List) => list(),
+        Some(cli::CommandsHooks::Help(args)) => help(&args.key),
+        Some(cli::CommandsHooks::Run(args)) => {
+            run_hooks(source, has_cli_proxy, args)
+        }
+        Some(cli::CommandsHooks::Schema(args)) => schema(args.json),
+        Some(cli::CommandsHooks::Undo(args)) => undo(args),
+        Some(cli::CommandsHooks::Complete(args)) => {
+            super::completions::complete_hook(&args.partial);
+            Ok(())
+        }
+        None => run_hooks(source, has_cli_proxy, cli_args.run),
+    }
+}
+
+/// Prints all hook keys with one-line usage
+fn list() -> Result<(), AliError> {
+    for key in hooks::hook_keys() {
+        println!("{key}: {}", hooks::hook_usage(key)?);
+    }
+
+    println!(
+        "{}<NAME>: [ARGS..], runs plugin x-<NAME> from hooks directory",
+        hooks::KEY_PREFIX_PLUGIN,
+    );
+
+    Ok(())
+}
+
+/// Prints usage of hook `key`, followed by its manual if any
+fn help(key: &str) -> Result<(), AliError> {
+    println!("{key}: {}", hooks::hook_usage(key)?);
+
+    if let Some(manual) = hooks::hook_manual(key) {
+        println!("\n{manual}");
+    }
+
+    Ok(())
+}
+
+/// Prints versioned specs of all hooks as YAML, or JSON if `json`
+fn schema(json: bool) -> Result<(), AliError> {
+    let specs = json::versioned(&hooks::hook_specs());
+    let schema = match json {
+        true => serde_json::to_string_pretty(&specs).map_err(|e| e.to_string()),
+        false => serde_yaml::to_string(&specs).map_err(|e| e.to_string()),
+    }
+    .map_err(|err| {
+        AliError::AliRsBug(format!("failed to serialize hook schema: {err}"))
+    })?;
+
+    println!("{schema}");
+
+    Ok(())
+}
+
+/// Restores latest backups of files edited by hooks
+fn undo(cli_args: cli::ArgsHooksUndo) -> Result<(), AliError> {
+    let root = cli_args.mountpoint.unwrap_or(String::from("/"));
+    let restored = hooks::undo_backups(&root, cli_args.dry_run)?;
+
+    if restored.is_empty() {
+        println!("no backups to restore under {root}");
+    }
+
+    let verb = match cli_args.dry_run {
+        true => "would restore",
+        false => "restored",
+    };
+
+    for (file, backup) in restored {
+        println!("{verb} {file} from {}", backup.display());
+    }
+
+    Ok(())
+}
+
+fn run_hooks(
+    source: &ManifestSource,
+    has_cli_proxy: bool,
+    cli_args: cli::ArgsHooksRun,
+) -> Result<(), AliError> {
+    let (hooks, manifest_proxy) = collect_hooks(source, &cli_args)?;
     let mountpoint = extract_mountpoint(&cli_args);
 
     if cli_args.dry_run {
         return validate(hooks, mountpoint);
     }
 
+    // CLI proxy (if any) was set up before loading manifest
+    let _tunnel = match has_cli_proxy {
+        true => None,
+        false => super::setup_proxy(manifest_proxy.as_deref())?,
+    };
+
     for hook in hooks {
         hooks::apply_hook(&hook, hooks::Caller::Cli, &mountpoint)?;
     }
@@ -31,18 +123,19 @@ fn validate(hooks: Vec<String>, mountpoint: String) -> Result<(), AliError> {
     Ok(())
 }
 
+/// Collects hooks to run, and manifest key `proxy` if `--manifest` is used
+/// or if CLI hooks invoke hook groups with `@group`
 fn collect_hooks(
-    manifest_file: &String,
-    cli_args: &cli::ArgsHooks,
-) -> Result<Vec<String>, AliError> {
+    source: &ManifestSource,
+    cli_args: &cli::ArgsHooksRun,
+) -> Result<(Vec<String>, Option<String>), AliError> {
     match cli_args.use_manifest {
         true => {
-            let manifest_yaml = std::fs::read_to_string(manifest_file)
-                .map_err(|err| {
-                    AliError::FileError(err, manifest_file.to_string())
-                })?;
+            let manifest = source.load()?;
+            if let Some(paths) = manifest.protected_paths {
+                hooks::set_protected_paths(paths);
+            }
 
-            let manifest = Manifest::from_yaml(&manifest_yaml)?;
             let mut manifest_hooks = vec![];
 
             if let Some(cmds) = manifest.chroot {
@@ -61,13 +154,44 @@ fn collect_hooks(
                 }
             }
 
-            Ok(manifest_hooks)
+            Ok((manifest_hooks, manifest.proxy))
         }
 
-        false => Ok(cli_args.hooks.clone()),
+        false => {
+            let has_group = cli_args
+                .hooks
+                .iter()
+                .map(|hook| groups::group_name(hook))
+                .collect::<Result<Vec<_>, _>>()?
+                .iter()
+                .any(Option::is_some);
+
+            if !has_group {
+                return Ok((cli_args.hooks.clone(), None));
+            }
+
+            let manifest = source.load()?;
+            if let Some(paths) = manifest.protected_paths {
+                hooks::set_protected_paths(paths);
+            }
+
+            let groups = manifest.hook_groups.unwrap_or_default();
+            let mut cli_hooks = vec![];
+
+            for s in groups::expand_cmds(&cli_args.hooks, &groups)? {
+                if !hooks::is_hook(&s) {
+                    log::warn!("skipping non-hook command in hook group: {s}");
+                    continue;
+                }
+
+                cli_hooks.push(s);
+            }
+
+            Ok((cli_hooks, manifest.proxy))
+        }
     }
 }
 
-fn extract_mountpoint(cli_args: &cli::ArgsHooks) -> String {
+fn extract_mountpoint(cli_args: &cli::ArgsHooksRun) -> String {
     cli_args.mountpoint.clone().unwrap_or(String::from("/"))
 }
@@ -1,42 +1,399 @@
-pub mod apply;
-pub mod hooks;
-pub mod validate;
+mod apply;
+mod capabilities;
+mod completions;
+mod generate;
+mod hooks;
+mod plan;
+mod rescue;
+mod status;
+mod summary;
+#[cfg(feature = "tui")]
+mod tui;
+mod validate;
+mod verify;
 
-use std::env;
+mod confirm;
 
-use colored::Colorize;
+use std::collections::HashMap;
+use std::env;
+use std::path::Path;
 
+use crate::ali::{
+    self,
+    Manifest,
+    ManifestFormat,
+};
 use crate::constants::defaults;
 use crate::errors::AliError;
+use crate::run::summary::ExitSummary;
+use crate::utils::tunnel::{
+    Proxy,
+    SshTunnel,
+};
+use crate::utils::{
+    accessible,
+    aliases,
+    checksum,
+    crash,
+    logger,
+    progress,
+    qr,
+    shell,
+    watchdog,
+};
 use crate::{
     cli,
     constants,
     linux,
 };
 
+/// Runs ali-rs with `cli_args`. Errors are printed to stderr as JSON,
+/// followed by summary of where files of this run are.
 pub fn run(cli_args: cli::Cli) -> Result<(), AliError> {
+    let status_fd = cli_args.status_fd;
+    let log_file = cli_args.log_file.clone();
+
+    crash::install(&log_file, cli_args.crash_report_url.clone());
+
+    // Printed when dropped, even if run_cli panics
+    let mut summary = match cli_args.capabilities
+        || is_completion(cli_args.commands.as_ref())
+    {
+        true => None,
+        false => {
+            let apply =
+                matches!(cli_args.commands, Some(cli::Commands::Apply(_)));
+            Some(ExitSummary::new(&log_file, apply))
+        }
+    };
+
+    let result = run_cli(cli_args, summary.as_mut());
+    if let Some(fd) = status_fd {
+        status::write(fd, &result, &log_file);
+    }
+
+    // Error is printed here, so that summary is the last thing printed
+    if let Err(ref err) = result {
+        eprintln!("{}", err.to_json_string());
+    }
+
+    if let Some(ref mut summary) = summary {
+        summary.finish(&result);
+    }
+
+    result
+}
+
+fn run_cli(
+    cli_args: cli::Cli,
+    summary: Option<&mut ExitSummary>,
+) -> Result<(), AliError> {
     let new_root_location = install_location();
 
+    let source = ManifestSource {
+        format: cli_args
+            .format
+            .unwrap_or(ManifestFormat::from_path(&cli_args.manifest)),
+        file: cli_args.manifest,
+        vars: HashMap::from_iter(cli_args.vars),
+        sha256: cli_args.sha256,
+    };
+
+    match cli_args.color {
+        cli::ColorMode::Always => colored::control::set_override(true),
+        cli::ColorMode::Never => colored::control::set_override(false),
+        cli::ColorMode::Auto => {}
+    }
+
+    if cli_args.accessible {
+        accessible::enable();
+    }
+
+    shell::ensure_path();
+
+    if cli_args.capabilities {
+        capabilities::run();
+        return Ok(());
+    }
+
+    progress::set_mode(cli_args.progress);
+
+    // Shell completions run on every tab press, and must not log
+    if !is_completion(cli_args.commands.as_ref()) {
+        logger::init(cli_args.verbose, &cli_args.log_file);
+    }
+    log::debug!("ali-rs {} started", env!("CARGO_PKG_VERSION"));
+
+    watchdog::init(cli_args.watchdog, cli_args.watchdog_prompt);
+    shell::set_timeout(cli_args.cmd_timeout);
+    #[cfg(feature = "e2e")]
+    crate::utils::mock::init()?;
+    aliases::init()?;
+
+    if let Some(rate) = cli_args.limit_rate {
+        crate::hooks::set_download_limit_rate(rate);
+    }
+
+    if let Some(dirs) = cli_args.snapshot_hooks {
+        crate::hooks::set_snapshot_dirs(match dirs.is_empty() {
+            true => vec![defaults::SNAPSHOT_DIR.to_string()],
+            false => dirs,
+        });
+    }
+
+    crate::hooks::set_backup_files(cli_args.backup_hooks);
+    crate::hooks::set_output_limit(
+        cli_args.hook_output_limit,
+        &crash::run_dir(&cli_args.log_file),
+    );
+
+    // Set up CLI proxy early, so that remote manifests are fetched through it
+    let _tunnel = setup_proxy(cli_args.proxy.as_deref())?;
+
     match cli_args.commands {
         // Default is to validate
         None | Some(cli::Commands::Validate) => {
-            validate::run(&cli_args.manifest, &new_root_location)
+            validate::run(&source, &new_root_location)
         }
         // Apply manifest in full
         Some(cli::Commands::Apply(args_apply)) => {
             if !linux::user::is_root() {
-                println!("{}", "WARN: running as non-root user".yellow())
+                log::warn!("running as non-root user")
+            }
+
+            let qr = args_apply.qr;
+            let result = apply::run(
+                &source,
+                &new_root_location,
+                cli_args.proxy.is_some(),
+                args_apply,
+            );
+
+            match result {
+                Ok(_) => accessible::announce("Installation finished."),
+                Err(ref err) => {
+                    accessible::announce(&format!(
+                        "Installation failed. {}",
+                        failure_summary(err)
+                    ))
+                }
+            }
+            accessible::beep();
+
+            if qr {
+                print_qr(&match result {
+                    Ok(ref report) => report.to_short_string(),
+                    Err(ref err) => failure_summary(err),
+                });
             }
 
-            match apply::run(&cli_args.manifest, &new_root_location, args_apply)
-            {
+            if let Some(ref dir) = cli_args.report_dir {
+                let path = write_report(dir, &match result {
+                    Ok(ref report) => report.to_json_string(),
+                    Err(ref err) => err.to_json_string(),
+                });
+
+                if let (Some(path), Some(summary)) = (path, summary) {
+                    summary.add_report(path);
+                }
+            }
+
+            match result {
                 Err(err) => Err(err),
-                Ok(report) => Ok(println!("{}", report.to_json_string())),
+                Ok(report) => {
+                    println!("{}", report.to_json_string());
+                    Ok(())
+                }
             }
         }
         Some(cli::Commands::Hooks(args_hooks)) => {
-            hooks::run(&cli_args.manifest, args_hooks)
+            hooks::run(&source, cli_args.proxy.is_some(), args_hooks)
+        }
+        Some(cli::Commands::Rescue(args_rescue)) => {
+            rescue::run(&source, &new_root_location, args_rescue)
+        }
+        Some(cli::Commands::Generate(args_generate)) => {
+            generate::run(args_generate)
+        }
+        Some(cli::Commands::Completions(args_completions)) => {
+            completions::run(args_completions.shell);
+            Ok(())
+        }
+        Some(cli::Commands::Plan(args_plan)) => {
+            plan::run(source, &new_root_location, args_plan)
+        }
+        Some(cli::Commands::Verify) => {
+            verify::run(&source, &new_root_location)
+        }
+        #[cfg(feature = "tui")]
+        Some(cli::Commands::Tui(args_tui)) => {
+            let report = tui::run(
+                &new_root_location,
+                cli_args.proxy.is_some(),
+                args_tui,
+            )?;
+
+            if let Some(report) = report {
+                println!("{}", report.to_json_string());
+            }
+
+            Ok(())
+        }
+    }
+}
+
+/// Manifest file and how to load it, from global CLI flags
+pub(super) struct ManifestSource {
+    file: String,
+    format: ManifestFormat,
+    /// Variables from `--var`, overriding manifest and env variables
+    vars: HashMap<String, String>,
+    /// Pinned manifest checksum from `--sha256`
+    sha256: Option<String>,
+}
+
+impl ManifestSource {
+    /// Loads manifest from file, or downloads it if file is a remote URL,
+    /// and resolves disk selectors to device nodes on this machine.
+    /// Includes in remote manifests are resolved relative to current directory.
+    fn load(&self) -> Result<Manifest, AliError> {
+        let mut manifest = self.load_unresolved()?;
+        linux::blkid::resolve(&mut manifest)?;
+
+        Ok(manifest)
+    }
+
+    fn load_unresolved(&self) -> Result<Manifest, AliError> {
+        self.parse(&self.read()?)
+    }
+
+    /// Reads manifest text from file, or downloads it if file is a remote
+    /// URL, verifying it against pinned checksum if any
+    fn read(&self) -> Result<String, AliError> {
+        if crate::hooks::is_remote(&self.file) {
+            return crate::hooks::download_string(
+                &self.file,
+                self.sha256.as_deref(),
+            );
         }
+
+        let manifest = std::fs::read_to_string(&self.file)
+            .map_err(|err| AliError::NoSuchFile(err, self.file.clone()))?;
+
+        if let Some(ref sum) = self.sha256 {
+            checksum::verify_sha256(&self.file, manifest.as_bytes(), sum)?;
+        }
+
+        Ok(manifest)
+    }
+
+    /// Parses manifest text read from this source
+    fn parse(&self, manifest: &str) -> Result<Manifest, AliError> {
+        if crate::hooks::is_remote(&self.file) {
+            return ali::parse_format(manifest, self.format, &self.vars);
+        }
+
+        Manifest::from_str_at(manifest, &self.file, self.format, &self.vars)
+    }
+
+    /// Source of manifest `file` (e.g. of an extra target), relative to
+    /// this manifest file unless absolute or remote, with the same variables,
+    /// pinned to `sha256` if given. If this manifest is pinned, remote
+    /// manifests must be pinned too.
+    fn relative(
+        &self,
+        file: &str,
+        sha256: Option<&str>,
+    ) -> Result<ManifestSource, AliError> {
+        let is_relative =
+            !crate::hooks::is_remote(file) && !Path::new(file).is_absolute();
+
+        let file = match (is_relative, self.file.rsplit_once('/')) {
+            (true, Some((dir, _))) => format!("{dir}/{file}"),
+            _ => file.to_string(),
+        };
+
+        let unpinned = self.sha256.is_some() && sha256.is_none();
+        if unpinned && crate::hooks::is_remote(&file) {
+            return Err(AliError::BadManifest(format!(
+                "remote manifest {file} has no sha256, \
+                but {} is pinned with --sha256",
+                self.file
+            )));
+        }
+
+        Ok(ManifestSource {
+            format: ManifestFormat::from_path(&file),
+            file,
+            vars: self.vars.clone(),
+            sha256: sha256.map(String::from),
+        })
+    }
+}
+
+/// Routes hook downloads through `proxy`, opening SSH tunnel if needed.
+/// The returned tunnel is closed when dropped.
+fn setup_proxy(proxy: Option<&str>) -> Result<Option<SshTunnel>, AliError> {
+    let Some(proxy) = proxy else {
+        return Ok(None);
+    };
+
+    let (url, tunnel) = Proxy::try_from(proxy)?.open()?;
+    crate::hooks::set_download_proxy(&url);
+
+    Ok(tunnel)
+}
+
+fn is_completion(commands: Option<&cli::Commands>) -> bool {
+    matches!(
+        commands,
+        Some(cli::Commands::Completions(_))
+            | Some(cli::Commands::Hooks(cli::ArgsHooks {
+                commands: Some(cli::CommandsHooks::Complete(_)),
+                ..
+            }))
+    )
+}
+
+fn print_qr(summary: &str) {
+    match qr::render(summary) {
+        Ok(code) => eprintln!("{code}\n{summary}"),
+        Err(err) => log::warn!("{err}"),
+    }
+}
+
+/// Writes apply report (or error report) to new file
+/// `report-<UNIX_SECS>.json` in `dir`, returning its path.
+/// Failures are only logged, since the install itself is already done.
+fn write_report(dir: &str, report: &str) -> Option<String> {
+    let secs = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+
+    let path = Path::new(dir).join(format!("report-{secs}.json"));
+    let result = std::fs::create_dir_all(dir)
+        .and_then(|_| std::fs::write(&path, report));
+
+    match result {
+        Ok(()) => {
+            log::info!("report written to {}", path.display());
+            Some(path.display().to_string())
+        }
+        Err(err) => {
+            log::warn!("failed to write report {}: {err}", path.display());
+            None
+        }
+    }
+}
+
+/// Short failure summary, unwrapping top-level installation error
+fn failure_summary(err: &AliError) -> String {
+    match err {
+        AliError::InstallError { error, .. } => {
+            format!("ali-rs: installation failed: {error}")
+        }
+        err => format!("ali-rs: failed: {err}"),
     }
 }
 
@@ -44,3 +401,54 @@ fn install_location() -> String {
     env::var(constants::ENV_ALI_LOC)
         .unwrap_or(defaults::INSTALL_LOCATION.to_string())
 }
+
+#[test]
+fn test_manifest_source_relative() {
+    let source = |file: &str| {
+        ManifestSource {
+            file: file.to_string(),
+            format: ManifestFormat::Yaml,
+            vars: HashMap::from([("host".to_string(), "foo".to_string())]),
+            sha256: Some("pinned".to_string()),
+        }
+    };
+
+    let rescue =
+        source("/etc/ali/main.yaml").relative("rescue.toml", None).unwrap();
+    assert_eq!("/etc/ali/rescue.toml", rescue.file);
+    assert_eq!(ManifestFormat::Toml, rescue.format);
+    assert_eq!(Some(&"foo".to_string()), rescue.vars.get("host"));
+    assert_eq!(None, rescue.sha256);
+
+    let tests = [
+        ("main.yaml", "rescue.yaml", "rescue.yaml"),
+        ("/etc/ali/main.yaml", "/srv/rescue.yaml", "/srv/rescue.yaml"),
+        (
+            "https://example.com/ali/main.yaml",
+            "rescue.yaml",
+            "https://example.com/ali/rescue.yaml",
+        ),
+        (
+            "/etc/ali/main.yaml",
+            "https://example.com/rescue.yaml",
+            "https://example.com/rescue.yaml",
+        ),
+    ];
+
+    let sum = Some("target");
+    for (file, relative, expected) in tests {
+        let target = source(file).relative(relative, sum).unwrap();
+        assert_eq!(expected, target.file);
+        assert_eq!(sum, target.sha256.as_deref());
+    }
+
+    // Pinned manifests cannot pull unpinned remote manifests
+    let main = source("https://example.com/ali/main.yaml");
+    assert!(main.relative("rescue.yaml", None).is_err());
+    assert!(source("main.yaml")
+        .relative("https://example.com/rescue.yaml", None)
+        .is_err());
+
+    let main = ManifestSource { sha256: None, ..main };
+    assert!(main.relative("rescue.yaml", None).is_ok());
+}
@@ -0,0 +1,842 @@
+use std::cmp::Ordering;
+use std::collections::{
+    BTreeMap,
+    HashMap,
+    HashSet,
+};
+
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+use super::{
+    verify,
+    ManifestSource,
+};
+use crate::ali::apply::plan::{
+    Change,
+    Probe,
+    ProbePartition,
+    Step,
+};
+use crate::ali::{
+    apply,
+    InstallTarget,
+    Manifest,
+    ManifestFormat,
+    PartitionTable,
+};
+use crate::cli;
+use crate::errors::AliError;
+use crate::linux::{
+    blkid,
+    partition_name,
+};
+use crate::types::stage;
+use crate::utils::fs::file_exists;
+use crate::utils::{
+    checksum,
+    json,
+};
+
+/// Query keys of external program protocol
+const QUERY_MANIFEST: &str = "manifest";
+const QUERY_SHA256: &str = "sha256";
+const QUERY_VAR_PREFIX: &str = "var.";
+
+/// What `ali-rs apply` would do with the manifest
+#[derive(Debug, Serialize, Deserialize)]
+pub(super) struct Plan {
+    manifest: String,
+    manifest_sha256: String,
+    location: String,
+    hostname: Option<String>,
+    /// Disks to be wiped, as declared in manifest
+    disks: Vec<String>,
+    /// Disk selectors in `disks`, resolved to device nodes on this machine
+    #[serde(default)]
+    devices: BTreeMap<String, String>,
+    stages: Vec<String>,
+    packages: Vec<String>,
+    /// Ordered actions, as performed by `ali-rs apply`
+    steps: Vec<Step>,
+    /// Applies the exact same manifest, pinned to its checksum
+    apply_command: String,
+}
+
+pub(super) fn run(
+    mut source: ManifestSource,
+    install_location: &str,
+    args: cli::ArgsPlan,
+) -> Result<(), AliError> {
+    if !args.external_json {
+        let mut plan = plan(
+            &source,
+            install_location,
+            args.out.as_deref(),
+            args.against_current,
+        )?;
+
+        // Applying destructive plans needs --allow-destroy again
+        let destroys = count(&plan, Change::Destroy);
+        if destroys > 0 && args.allow_destroy {
+            plan.apply_command.push_str(" --allow-destroy");
+        }
+
+        match args.text {
+            true => print!("{}", text(&plan)),
+            false => println!("{}", json::to_string_pretty(&plan)),
+        }
+
+        // Destructive plans are not saved without --allow-destroy
+        if destroys > 0 && !args.allow_destroy {
+            return Err(AliError::Aborted(format!(
+                "{destroys} steps destroy data on current disks, \
+                refusing without --allow-destroy"
+            )));
+        }
+
+        if let Some(ref out) = args.out {
+            std::fs::write(out, json::to_string_pretty(&plan)).map_err(
+                |err| AliError::FileError(err, format!("writing plan {out}")),
+            )?;
+        }
+
+        return Ok(());
+    }
+
+    let result = read_query()
+        .and_then(|query| apply_query(&mut source, query))
+        .and_then(|_| plan(&source, install_location, None, false));
+
+    match result {
+        Ok(plan) => {
+            println!("{}", serde_json::to_string(&external(&plan)).unwrap());
+            Ok(())
+        }
+
+        // External program protocol expects error on stderr,
+        // and non-zero exit status
+        Err(err) => {
+            eprintln!("{err}");
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Plans manifest from `source` without validating block devices,
+/// so that plans can be made on machines other than the target.
+/// Disk selectors are resolved on this machine like `ali-rs apply` does,
+/// so that plans show which disks are wiped.
+/// If `out` is given, `apply_command` applies the plan saved to `out`.
+/// With `against_current`, disk steps are assessed against current disks.
+fn plan(
+    source: &ManifestSource,
+    install_location: &str,
+    out: Option<&str>,
+    against_current: bool,
+) -> Result<Plan, AliError> {
+    let text = source.read()?;
+    let manifest_sha256 = checksum::sha256_hex(text.as_bytes());
+
+    let mut manifest = source.parse(&text)?;
+    let disks = manifest
+        .disks
+        .iter()
+        .flatten()
+        .map(|disk| disk.device.clone())
+        .collect();
+
+    let devices = resolve_disks(&mut manifest)?;
+    super::apply::update_manifest(&mut manifest);
+
+    let mut packages: Vec<String> =
+        manifest.pacstraps.iter().flatten().cloned().collect();
+    packages.sort();
+
+    let steps = apply::plan::probed_steps(&manifest, install_location)
+        .into_iter()
+        .map(|(step, probe)| {
+            match (against_current, probe) {
+                (true, Some(probe)) => {
+                    Step {
+                        change: Some(assess(&probe)),
+                        ..step
+                    }
+                }
+                _ => step,
+            }
+        })
+        .collect();
+
+    Ok(Plan {
+        steps,
+        apply_command: apply_command(source, &manifest_sha256, out)?,
+        manifest: source.file.clone(),
+        manifest_sha256,
+        location: install_location.to_string(),
+        hostname: manifest.hostname,
+        disks,
+        devices,
+        stages: stage::STAGES.iter().map(|s| s.to_string()).collect(),
+        packages,
+    })
+}
+
+/// Resolves disk selectors in `manifest` to device nodes like
+/// `ali-rs apply` does, and returns the node of each selector
+fn resolve_disks(
+    manifest: &mut Manifest,
+) -> Result<BTreeMap<String, String>, AliError> {
+    let devices = |manifest: &Manifest| -> Vec<String> {
+        manifest
+            .disks
+            .iter()
+            .flatten()
+            .map(|disk| disk.device.clone())
+            .collect()
+    };
+
+    let declared = devices(manifest);
+    blkid::resolve(manifest)?;
+
+    Ok(declared
+        .into_iter()
+        .zip(devices(manifest))
+        .filter(|(selector, node)| selector != node)
+        .collect())
+}
+
+/// Formats `ali-rs apply` command for manifest, pinned to its checksum
+fn apply_command(
+    source: &ManifestSource,
+    sha256: &str,
+    out: Option<&str>,
+) -> Result<String, AliError> {
+    let quote = |s: &str| {
+        shlex::try_quote(s).map(|quoted| quoted.to_string()).map_err(|err| {
+            AliError::BadArgs(format!("cannot quote {s}: {err}"))
+        })
+    };
+
+    let file = quote(&source.file)?;
+    let mut cmd = format!("ali-rs -f {file} --sha256 {sha256}");
+
+    let vars: BTreeMap<_, _> = source.vars.iter().collect();
+    for (k, v) in vars {
+        cmd.push_str(&format!(" --var {}", quote(&format!("{k}={v}"))?));
+    }
+
+    cmd.push_str(" apply -y");
+    if let Some(out) = out {
+        cmd.push_str(&format!(" --plan {}", quote(out)?));
+    }
+
+    Ok(cmd)
+}
+
+/// Formats plan for humans, one numbered step per line
+fn text(plan: &Plan) -> String {
+    let mut s = format!(
+        "Plan for {} (sha256 {}) at {}:\n",
+        plan.manifest, plan.manifest_sha256, plan.location
+    );
+
+    for (selector, node) in &plan.devices {
+        s.push_str(&format!("  disk {selector} is {node}\n"));
+    }
+
+    for (i, step) in plan.steps.iter().enumerate() {
+        s.push_str(&format!("{:>4}. {step}\n", i + 1));
+    }
+
+    if plan.steps.iter().any(|step| step.change.is_some()) {
+        s.push_str(&format!(
+            "\nChanges to current disks: {} create, {} no-op, {} destroy\n",
+            count(plan, Change::Create),
+            count(plan, Change::NoOp),
+            count(plan, Change::Destroy),
+        ));
+    }
+
+    s.push_str(&format!("\nTo apply this plan:\n  {}\n", plan.apply_command));
+    s
+}
+
+fn count(plan: &Plan, change: Change) -> usize {
+    plan.steps
+        .iter()
+        .filter(|step| step.change == Some(change))
+        .count()
+}
+
+/// Assesses change of a disk step to current disks
+fn assess(probe: &Probe) -> Change {
+    match probe {
+        Probe::Table {
+            device,
+            table,
+            partitions,
+        } => {
+            let blkid = verify::blkid(device);
+            let pttype = blkid.get("PTTYPE").map(String::as_str);
+            if pttype.is_none() && !blkid.contains_key("TYPE") {
+                return Change::Create;
+            }
+
+            let same_table = matches!(
+                (table, pttype),
+                (PartitionTable::Gpt, Some("gpt"))
+                    | (PartitionTable::Mbr, Some("dos"))
+            );
+
+            // Extra partitions are lost with the new table
+            let extra = partition_name(device, partitions.len() as u8 + 1);
+            let kept = same_table
+                && partitions
+                    .iter()
+                    .all(|part| assess_partition(part) == Change::NoOp)
+                && verify::lsblk(&extra).is_none();
+
+            match kept {
+                true => Change::NoOp,
+                false => Change::Destroy,
+            }
+        }
+
+        Probe::Partition(part) => assess_partition(part),
+
+        Probe::Signature(device) => {
+            let blkid = verify::blkid(device);
+            match blkid.contains_key("TYPE") || blkid.contains_key("PTTYPE") {
+                true => Change::Destroy,
+                false => Change::Create,
+            }
+        }
+
+        Probe::Exists(path) => {
+            match file_exists(path) {
+                true => Change::NoOp,
+                false => Change::Create,
+            }
+        }
+    }
+}
+
+/// Partitions are kept if they have planned sizes, and planned types
+/// on MBR tables, like `ali-rs verify`
+fn assess_partition(part: &ProbePartition) -> Change {
+    let Some(info) = verify::lsblk(&part.device) else {
+        return Change::Create;
+    };
+
+    let same_size = part.size.as_deref().is_none_or(|size| {
+        matches!(
+            (verify::parse_size(size), info.size),
+            (Some(expected), Some(found))
+                if expected.abs_diff(found) <= verify::SIZE_TOLERANCE
+        )
+    });
+
+    let same_type = !part.mbr
+        || verify::mbr_type(&part.part_type)
+            == info.part_type.as_deref().and_then(verify::mbr_type);
+
+    match same_size && same_type {
+        true => Change::NoOp,
+        false => Change::Destroy,
+    }
+}
+
+/// Checks that `ali-rs apply` with manifest from `source` performs
+/// exactly the steps of reviewed plan saved to `plan_file` on the same
+/// disks, skipping `skip` stages. Unless `allow_destroy`, also checks
+/// that no step destroys data on current disks.
+pub(super) fn check(
+    source: &ManifestSource,
+    install_location: &str,
+    target: Option<InstallTarget>,
+    skip: &HashSet<stage::Stage>,
+    plan_file: &str,
+    allow_destroy: bool,
+) -> Result<(), AliError> {
+    let reviewed = std::fs::read_to_string(plan_file)
+        .map_err(|err| AliError::NoSuchFile(err, plan_file.to_string()))?;
+    let reviewed: Plan = serde_json::from_str(&reviewed).map_err(|err| {
+        AliError::BadArgs(format!("bad plan file {plan_file}: {err}"))
+    })?;
+
+    let text = source.read()?;
+    let sha256 = checksum::sha256_hex(text.as_bytes());
+    if sha256 != reviewed.manifest_sha256 {
+        return Err(AliError::Aborted(format!(
+            "manifest sha256 {sha256} does not match plan {plan_file} ({})",
+            reviewed.manifest_sha256,
+        )));
+    }
+
+    let mut manifest = source.parse(&text)?;
+    // Selectors may match other disks than when the plan was made
+    let devices = resolve_disks(&mut manifest)?;
+    let node = |devices: &BTreeMap<String, String>, selector: &str| {
+        devices.get(selector).cloned().unwrap_or("nothing".to_string())
+    };
+
+    let moved = devices
+        .keys()
+        .chain(reviewed.devices.keys())
+        .find(|s| devices.get(*s) != reviewed.devices.get(*s));
+
+    if let Some(selector) = moved {
+        return Err(AliError::Aborted(format!(
+            "disk {selector} is {}, plan {plan_file} has {}",
+            node(&devices, selector),
+            node(&reviewed.devices, selector),
+        )));
+    }
+
+    if target.is_some() {
+        manifest.target = target;
+    }
+    super::apply::update_manifest(&mut manifest);
+
+    let skipped = |step: &&Step| {
+        !skip.iter().any(|stage| stage.to_string() == step.stage)
+    };
+
+    // Changes to current disks are only for review
+    let reviewed: Vec<Step> = reviewed
+        .steps
+        .into_iter()
+        .map(|step| Step { change: None, ..step })
+        .collect();
+
+    let steps = apply::plan::steps(&manifest, install_location);
+    let steps: Vec<&Step> = steps.iter().filter(skipped).collect();
+    let reviewed: Vec<&Step> = reviewed.iter().filter(skipped).collect();
+
+    if let Some(diff) = diff_steps(&reviewed, &steps) {
+        return Err(AliError::Aborted(format!(
+            "apply differs from plan {plan_file}: {diff}"
+        )));
+    }
+
+    if allow_destroy {
+        return Ok(());
+    }
+
+    // Plans may have been made elsewhere, or before disks changed,
+    // so changes are assessed against current disks again
+    let destroys: Vec<Step> =
+        apply::plan::probed_steps(&manifest, install_location)
+            .into_iter()
+            .filter(|(step, probe)| {
+                skipped(&step)
+                    && probe.as_ref().is_some_and(|probe| {
+                        assess(probe) == Change::Destroy
+                    })
+            })
+            .map(|(step, _)| step)
+            .collect();
+
+    match destroys.first() {
+        None => Ok(()),
+        Some(step) => {
+            Err(AliError::Aborted(format!(
+                "{} steps destroy data on current disks, e.g. {step}, \
+                refusing without --allow-destroy",
+                destroys.len(),
+            )))
+        }
+    }
+}
+
+/// Describes the first difference between reviewed and actual steps
+fn diff_steps(reviewed: &[&Step], steps: &[&Step]) -> Option<String> {
+    let n = reviewed.iter().zip(steps).position(|(a, b)| a != b);
+    match (n, reviewed.len().cmp(&steps.len())) {
+        (Some(n), _) => {
+            Some(format!(
+                "step {} is {}, plan has {}",
+                n + 1,
+                steps[n],
+                reviewed[n]
+            ))
+        }
+        (None, Ordering::Less) => {
+            Some(format!("unplanned step {}", steps[reviewed.len()]))
+        }
+        (None, Ordering::Greater) => {
+            Some(format!("missing step {}", reviewed[steps.len()]))
+        }
+        (None, Ordering::Equal) => None,
+    }
+}
+
+/// Reads query JSON object of external program protocol from stdin
+fn read_query() -> Result<HashMap<String, String>, AliError> {
+    let query = std::io::read_to_string(std::io::stdin()).map_err(|err| {
+        AliError::FileError(err, "failed to read query from stdin".to_string())
+    })?;
+
+    if query.trim().is_empty() {
+        return Ok(HashMap::new());
+    }
+
+    serde_json::from_str(&query).map_err(|err| {
+        AliError::BadArgs(format!("bad external query: {err}"))
+    })
+}
+
+/// Overrides manifest source with query keys `manifest`, `sha256`,
+/// and manifest variables `var.<KEY>`
+fn apply_query(
+    source: &mut ManifestSource,
+    query: HashMap<String, String>,
+) -> Result<(), AliError> {
+    for (k, v) in query {
+        match k.as_str() {
+            QUERY_MANIFEST => {
+                source.format = ManifestFormat::from_path(&v);
+                source.file = v;
+            }
+
+            QUERY_SHA256 if checksum::is_sha256_hex(&v) => {
+                source.sha256 = Some(v);
+            }
+
+            QUERY_SHA256 => {
+                return Err(AliError::BadArgs(format!(
+                    "bad sha256 checksum {v}"
+                )));
+            }
+
+            _ => {
+                match k.strip_prefix(QUERY_VAR_PREFIX) {
+                    Some(var) if !var.is_empty() => {
+                        source.vars.insert(var.to_string(), v);
+                    }
+                    _ => {
+                        return Err(AliError::BadArgs(format!(
+                            "unknown external query key {k}"
+                        )));
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Flattens plan into JSON object of strings, as required by
+/// external program protocol. Full versioned plan is kept as JSON string
+/// in key `plan`.
+fn external(plan: &Plan) -> BTreeMap<&'static str, String> {
+    BTreeMap::from([
+        ("manifest", plan.manifest.clone()),
+        ("manifest_sha256", plan.manifest_sha256.clone()),
+        ("location", plan.location.clone()),
+        ("hostname", plan.hostname.clone().unwrap_or_default()),
+        ("disks", plan.disks.join(",")),
+        ("stages", plan.stages.join(",")),
+        ("packages", plan.packages.join(" ")),
+        ("apply_command", plan.apply_command.clone()),
+        ("plan", json::to_string(plan)),
+        ("version", json::VERSION.to_string()),
+    ])
+}
+
+#[test]
+fn test_plan() {
+    let mut source = ManifestSource {
+        file: "./manifest.yaml".to_string(),
+        format: ManifestFormat::Yaml,
+        vars: HashMap::new(),
+        sha256: None,
+    };
+
+    let query = HashMap::from([
+        (
+            "manifest".to_string(),
+            "./src/ali/examples/uefi-root-on-lvm.yaml".to_string(),
+        ),
+        ("var.disk".to_string(), "/dev/vda".to_string()),
+    ]);
+
+    apply_query(&mut source, query).unwrap();
+    assert_eq!("./src/ali/examples/uefi-root-on-lvm.yaml", source.file);
+    assert_eq!(Some(&"/dev/vda".to_string()), source.vars.get("disk"));
+
+    let plan = plan(&source, "/alitarget", None, false).unwrap();
+    assert_eq!(64, plan.manifest_sha256.len());
+    assert_eq!(vec!["/dev/vda"], plan.disks);
+    assert!(plan.packages.contains(&"lvm2".to_string()));
+    assert_eq!(
+        format!(
+            "ali-rs -f ./src/ali/examples/uefi-root-on-lvm.yaml --sha256 {} --var 'disk=/dev/vda' apply -y",
+            plan.manifest_sha256,
+        ),
+        plan.apply_command,
+    );
+
+    let external = external(&plan);
+    assert_eq!(Some(&"/alitarget".to_string()), external.get("location"));
+    assert!(external.contains_key("plan"));
+    assert_eq!(Some(&"1".to_string()), external.get("version"));
+
+    let should_err = [
+        ("sha256", "abc"),
+        ("vars.disk", "/dev/vda"),
+        ("var.", "/dev/vda"),
+        ("foo", "bar"),
+    ];
+
+    for (k, v) in should_err {
+        let query = HashMap::from([(k.to_string(), v.to_string())]);
+        assert!(apply_query(&mut source, query).is_err(), "{k}={v}");
+    }
+}
+
+#[test]
+fn test_check_plan() {
+    use std::sync::Arc;
+
+    use crate::utils::mock::MockRunner;
+    use crate::utils::shell;
+
+    let mut source = ManifestSource {
+        file: "./src/ali/examples/uefi-root-on-lvm-on-luks.yaml".to_string(),
+        format: ManifestFormat::Yaml,
+        vars: HashMap::new(),
+        sha256: None,
+    };
+
+    let plan_file = std::env::temp_dir()
+        .join(format!("ali-rs-test-plan-{}.json", std::process::id()));
+    let plan_file = plan_file.to_string_lossy().to_string();
+
+    let plan = plan(&source, "/alitarget", Some(&plan_file), false).unwrap();
+    assert!(plan.apply_command.ends_with(&format!("--plan {plan_file}")));
+    assert!(text(&plan)
+        .contains("   1. [stage-mountpoints] create mbr partition table"));
+    std::fs::write(&plan_file, json::to_string_pretty(&plan)).unwrap();
+
+    // Current disks are empty
+    let mock = Arc::new(MockRunner::new());
+    let guard = shell::set_runner(mock);
+
+    let no_skip = HashSet::new();
+    check(&source, "/alitarget", None, &no_skip, &plan_file, false).unwrap();
+    drop(guard);
+
+    // Steps destroying data on current disks need --allow-destroy,
+    // even if the plan was made without --against-current
+    let mock = Arc::new(
+        MockRunner::new()
+            .stdout("blkid -o export /dev/vda2", "TYPE=crypto_LUKS\n"),
+    );
+    let guard = shell::set_runner(mock);
+
+    let result = check(
+        &source,
+        "/alitarget",
+        None,
+        &no_skip,
+        &plan_file,
+        false,
+    );
+    let err = result.unwrap_err().to_string();
+    assert!(err.contains("luksFormat /dev/vda2"), "{err}");
+    check(&source, "/alitarget", None, &no_skip, &plan_file, true).unwrap();
+    drop(guard);
+
+    // Portable target adds steps not in plan
+    let result = check(
+        &source,
+        "/alitarget",
+        Some(InstallTarget::Portable),
+        &no_skip,
+        &plan_file,
+        false,
+    );
+    assert!(matches!(result, Err(AliError::Aborted(_))));
+
+    // Different install location changes mount steps,
+    // unless the stage is skipped
+    let skip_mountpoints = HashSet::from([
+        stage::Stage::Mountpoints,
+        stage::Stage::Bootstrap,
+    ]);
+    assert!(check(&source, "/mnt", None, &no_skip, &plan_file, false).is_err());
+    check(&source, "/mnt", None, &skip_mountpoints, &plan_file, false).unwrap();
+
+    // Plan for another manifest
+    source.file = "./src/ali/examples/uefi-root-on-lvm.yaml".to_string();
+    let result = check(
+        &source,
+        "/alitarget",
+        None,
+        &no_skip,
+        &plan_file,
+        false,
+    );
+    assert!(result.unwrap_err().to_string().contains("sha256"));
+
+    std::fs::remove_file(&plan_file).unwrap();
+
+    let step = |action: &str| {
+        Step {
+            stage: "stage-routines".to_string(),
+            action: action.to_string(),
+            change: None,
+        }
+    };
+    let (a, b, c) = (step("a"), step("b"), step("c"));
+    assert_eq!(None, diff_steps(&[&a, &b], &[&a, &b]));
+    assert_eq!(
+        Some("step 2 is [stage-routines] c, plan has [stage-routines] b"),
+        diff_steps(&[&a, &b], &[&a, &c]).as_deref(),
+    );
+    assert_eq!(
+        Some("unplanned step [stage-routines] b"),
+        diff_steps(&[&a], &[&a, &b]).as_deref(),
+    );
+    assert_eq!(
+        Some("missing step [stage-routines] b"),
+        diff_steps(&[&a, &b], &[&a]).as_deref(),
+    );
+}
+
+#[test]
+fn test_plan_against_current() {
+    use std::sync::Arc;
+
+    use crate::utils::mock::MockRunner;
+    use crate::utils::shell;
+
+    let source = ManifestSource {
+        file: "./src/ali/examples/uefi-root-on-lvm-on-luks.yaml".to_string(),
+        format: ManifestFormat::Yaml,
+        vars: HashMap::new(),
+        sha256: None,
+    };
+
+    // /dev/vda already has the planned partitions,
+    // and /dev/vda2 is already LUKS
+    let lsblk = "lsblk --json --bytes --nodeps -o SIZE,PARTTYPE";
+    let mock = Arc::new(
+        MockRunner::new()
+            .stdout("blkid -o export /dev/vda", "PTTYPE=dos\n")
+            .stdout("blkid -o export /dev/vda2", "TYPE=crypto_LUKS\n")
+            .stdout(
+                &format!("{lsblk} /dev/vda1"),
+                r#"{"blockdevices": [{"size": 314572800, "parttype": "0xef"}]}"#,
+            )
+            .stdout(
+                &format!("{lsblk} /dev/vda2"),
+                r#"{"blockdevices": [{"size": 9000000000, "parttype": "0x8e"}]}"#,
+            )
+            .stdout("blkid -o export /dev/vda1", ""),
+    );
+    let _guard = shell::set_runner(mock);
+
+    let plan = plan(&source, "/alitarget", None, true).unwrap();
+    let change = |action: &str| {
+        plan.steps
+            .iter()
+            .find(|step| step.action == action)
+            .unwrap_or_else(|| panic!("no step {action}"))
+            .change
+    };
+
+    assert_eq!(
+        Some(Change::NoOp),
+        change("create mbr partition table on /dev/vda")
+    );
+    assert_eq!(
+        Some(Change::NoOp),
+        change("create partition 1 on /dev/vda (300M)")
+    );
+    assert_eq!(Some(Change::Destroy), change("luksFormat /dev/vda2"));
+    assert_eq!(
+        Some(Change::Create),
+        change("mkfs.vfat -F 32 -L BOOT /dev/vda1")
+    );
+    assert_eq!(None, change("mkdir /alitarget"));
+    assert!(text(&plan).contains("7 create, 3 no-op, 1 destroy"));
+}
+
+#[test]
+fn test_plan_selectors() {
+    use std::sync::Arc;
+
+    use crate::utils::mock::MockRunner;
+    use crate::utils::shell;
+
+    let dir = std::env::temp_dir()
+        .join(format!("ali-rs-test-plan-selectors-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let file = dir.join("manifest.yaml").to_string_lossy().to_string();
+    std::fs::write(
+        &file,
+        r#"
+disks:
+  - device: tran=nvme,size>500G
+    table: gpt
+    partitions:
+      - label: root
+        type: "83"
+rootfs:
+  device: tran=nvme,size>500G-part1
+  fstype: ext4
+"#,
+    )
+    .unwrap();
+
+    let source = ManifestSource {
+        format: ManifestFormat::Yaml,
+        file,
+        vars: HashMap::new(),
+        sha256: None,
+    };
+
+    let lsblk = |nvme: &str| {
+        let lsblk = "lsblk --json --bytes --nodeps";
+        let out = format!(
+            r#"{{"blockdevices": [{{"path": "{nvme}", "size": 1000204886016, "tran": "nvme", "type": "disk"}}]}}"#
+        );
+
+        Arc::new(MockRunner::new().stdout(lsblk, &out))
+    };
+
+    let guard = shell::set_runner(lsblk("/dev/nvme0n1"));
+    let plan = plan(&source, "/alitarget", None, false).unwrap();
+    assert_eq!(vec!["tran=nvme,size>500G"], plan.disks);
+    assert_eq!(
+        Some("/dev/nvme0n1"),
+        plan.devices.get("tran=nvme,size>500G").map(String::as_str),
+    );
+    assert!(plan
+        .steps
+        .iter()
+        .any(|step| step.action == "mkfs.ext4 /dev/nvme0n1p1"));
+
+    let plan_file = dir.join("plan.json").to_string_lossy().to_string();
+    std::fs::write(&plan_file, json::to_string(&plan)).unwrap();
+
+    let no_skip = HashSet::new();
+    check(&source, "/alitarget", None, &no_skip, &plan_file, false).unwrap();
+    drop(guard);
+
+    // Selector now matches another disk
+    let _guard = shell::set_runner(lsblk("/dev/nvme1n1"));
+    let err = check(&source, "/alitarget", None, &no_skip, &plan_file, false)
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("is /dev/nvme1n1"), "{err}");
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
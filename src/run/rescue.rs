@@ -0,0 +1,175 @@
+use super::ManifestSource;
+use crate::ali::{
+    Dm,
+    FsckPolicy,
+    Manifest,
+    ManifestMountpoint,
+};
+use crate::cli;
+use crate::errors::AliError;
+use crate::linux::fsck::{
+    self,
+    FsckStatus,
+};
+use crate::linux::{
+    luks,
+    lvm,
+    mount,
+};
+use crate::utils::fs::{
+    file_exists,
+    mkdir_p,
+};
+use crate::utils::shell;
+
+/// Re-opens an installation created from manifest under `location`,
+/// without creating or formatting anything
+pub(super) fn run(
+    source: &ManifestSource,
+    location: &str,
+    args: cli::ArgsRescue,
+) -> Result<(), AliError> {
+    let manifest = source.load()?;
+
+    // Diskless root trees are plain directories
+    if manifest.netroot.is_none() {
+        open_dms(&manifest)?;
+        mount_all(&manifest, location, args.fsck)?;
+    }
+
+    log::info!("installation opened at {location}");
+
+    if args.chroot {
+        shell::exec("arch-chroot", &[location])?;
+    }
+
+    Ok(())
+}
+
+fn open_dms(manifest: &Manifest) -> Result<(), AliError> {
+    for dm in manifest.device_mappers.iter().flatten() {
+        match dm {
+            Dm::Luks(m_luks) => {
+                if file_exists(format!("/dev/mapper/{}", m_luks.name)) {
+                    continue;
+                }
+
+                luks::open(
+                    &m_luks.device,
+                    m_luks.passphrase.as_deref(),
+                    &m_luks.name,
+                )?;
+            }
+
+            Dm::Lvm(m_lvm) => {
+                for vg in m_lvm.vgs.iter().flatten() {
+                    lvm::activate_vg(&vg.name)?;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Mounts rootfs at `location`, then other mountpoints under it,
+/// parents before children. If `fsck`, each filesystem is checked
+/// before it is mounted.
+fn mount_all(
+    manifest: &Manifest,
+    location: &str,
+    fsck: bool,
+) -> Result<(), AliError> {
+    mkdir_p(location)?;
+
+    let root: ManifestMountpoint = manifest.rootfs.clone().into();
+    let mut mountpoints: Vec<&ManifestMountpoint> =
+        manifest.mountpoints.iter().flatten().collect();
+    mountpoints.sort_by_key(|m| m.dest.matches('/').count());
+
+    let mut report = Vec::new();
+    for mnt in std::iter::once(&root).chain(mountpoints) {
+        let dest = mount::prepend_base(location, &mnt.dest);
+        mkdir_p(&dest)?;
+
+        if !fsck {
+            mount::mount(mnt, location)?;
+            continue;
+        }
+
+        let fs_type = fs_type(manifest, &mnt.device);
+        let policy = manifest
+            .fsck
+            .as_ref()
+            .and_then(|policies| policies.get(&mnt.device))
+            .copied()
+            .unwrap_or(FsckPolicy::Check);
+
+        let status = match (policy, fs_type) {
+            (FsckPolicy::Skip, _) | (_, Some("swap" | "nfs" | "nfs4")) => {
+                mount::mount(mnt, location)?;
+                continue;
+            }
+            (policy, fs_type) => {
+                fsck::check(&mnt.device, fs_type, policy == FsckPolicy::Repair)?
+            }
+        };
+
+        // Keep broken filesystems read-only unless asked to repair
+        let mnt = match (policy, status) {
+            (FsckPolicy::Check, FsckStatus::Errors) => read_only(mnt),
+            _ => mnt.clone(),
+        };
+
+        mount::mount(&mnt, location)?;
+
+        // btrfs can only be repaired online, with scrub
+        let status = match (policy, fs_type) {
+            (FsckPolicy::Repair, Some("btrfs")) => fsck::scrub(&dest)?,
+            _ => status,
+        };
+
+        report.push((mnt.device, status));
+    }
+
+    for (device, status) in report {
+        match status {
+            FsckStatus::Errors => log::warn!("fsck {device}: {status}"),
+            _ => log::info!("fsck {device}: {status}"),
+        }
+    }
+
+    for swap in manifest.swap.iter().flatten() {
+        if let Err(err) = shell::exec("swapon", &[swap]) {
+            log::warn!("swapon {swap}: {err}");
+        }
+    }
+
+    Ok(())
+}
+
+/// Looks up filesystem type of `device` from `manifest`
+fn fs_type<'a>(manifest: &'a Manifest, device: &str) -> Option<&'a str> {
+    if manifest.rootfs.device == device {
+        return Some(&manifest.rootfs.fs_type);
+    }
+
+    manifest
+        .filesystems
+        .iter()
+        .flatten()
+        .find(|fs| fs.device == device)
+        .map(|fs| fs.fs_type.as_str())
+}
+
+fn read_only(mnt: &ManifestMountpoint) -> ManifestMountpoint {
+    let mnt_opts = match &mnt.mnt_opts {
+        Some(opts) => format!("{opts},ro"),
+        None => "ro".to_string(),
+    };
+
+    ManifestMountpoint {
+        mnt_opts: Some(mnt_opts),
+        ..mnt.clone()
+    }
+}
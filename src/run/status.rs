@@ -0,0 +1,114 @@
+use std::io::Write;
+
+use serde::Serialize;
+
+use crate::errors::AliError;
+use crate::utils::{
+    json,
+    report_sink,
+};
+
+/// Final status written to `--status-fd`
+#[derive(Debug, Serialize)]
+struct Status<'a> {
+    status: &'static str,
+    class: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    code: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    hint: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stage: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    /// Paths of file report sinks
+    reports: Vec<String>,
+    log: &'a str,
+}
+
+/// Writes final status of `result` as JSON line to file descriptor `fd`.
+/// Failures are logged, and never change the result.
+pub(super) fn write(fd: u32, result: &Result<(), AliError>, log_file: &str) {
+    let status = status(result, report_sink::files(), log_file);
+    let line = json::to_string(&status);
+
+    let written = std::fs::OpenOptions::new()
+        .append(true)
+        .open(format!("/dev/fd/{fd}"))
+        .and_then(|mut file| writeln!(file, "{line}"));
+
+    if let Err(err) = written {
+        log::error!("failed to write status to fd {fd}: {err}");
+    }
+}
+
+fn status<'a>(
+    result: &Result<(), AliError>,
+    reports: Vec<String>,
+    log: &'a str,
+) -> Status<'a> {
+    let err = match result {
+        Ok(()) => {
+            return Status {
+                status: "ok",
+                class: "ok",
+                code: None,
+                hint: None,
+                stage: None,
+                error: None,
+                reports,
+                log,
+            };
+        }
+        Err(err) => err,
+    };
+
+    let (stage, error) = match err {
+        AliError::InstallError {
+            error,
+            stage_failed,
+            ..
+        } => (Some(stage_failed.to_string()), error.to_string()),
+        err => (None, err.to_string()),
+    };
+
+    Status {
+        status: "error",
+        class: err.class(),
+        code: Some(err.code()),
+        hint: err.hint(),
+        stage,
+        error: Some(error),
+        reports,
+        log,
+    }
+}
+
+#[test]
+fn test_status() {
+    use crate::types::stage::Stage;
+
+    let to_json = |status: Status| json::to_string(&status);
+
+    assert_eq!(
+        r#"{"class":"ok","log":"/tmp/ali-rs.log","reports":[],"status":"ok","version":1}"#,
+        to_json(status(&Ok(()), vec![], "/tmp/ali-rs.log")),
+    );
+
+    let reports = vec!["/var/log/ali.jsonl".to_string()];
+    let err = AliError::InstallError {
+        error: Box::new(AliError::Aborted("disk busy".to_string())),
+        stage_failed: Stage::Bootstrap,
+        stages_performed: Box::default(),
+    };
+    assert_eq!(
+        r#"{"class":"aborted","code":"aborted","error":"aborted: disk busy","log":"ali.log","reports":["/var/log/ali.jsonl"],"stage":"stage-bootstrap","status":"error","version":1}"#,
+        to_json(status(&Err(err), reports, "ali.log")),
+    );
+
+    let err = AliError::BadArgs("bad stage".to_string());
+    assert_eq!(
+        r#"{"class":"usage","code":"bad-args","error":"bad cli arguments: bad stage","hint":"see `ali-rs --help`","log":"ali.log","reports":[],"status":"error","version":1}"#,
+        to_json(status(&Err(err), vec![], "ali.log")),
+    );
+}
@@ -0,0 +1,153 @@
+use crate::errors::AliError;
+use crate::utils::{
+    crash,
+    progress,
+    report_sink,
+    shell,
+};
+
+/// Scope guard around [`super::run`], printing where files of this run
+/// are to stderr when dropped. Since it is dropped on every way out
+/// (errors, panics, and Ctrl-C during apply), error sites need not
+/// print paths themselves. Only a second Ctrl-C skips it.
+pub(super) struct ExitSummary {
+    log_file: String,
+    /// Whether this run is `ali-rs apply`, which can be resumed
+    apply: bool,
+    /// Files written with `--report-dir`
+    reports: Vec<String>,
+    outcome: Option<Outcome>,
+}
+
+enum Outcome {
+    Ok,
+    /// Failed, in stage if known
+    Failed(Option<String>),
+}
+
+impl ExitSummary {
+    pub(super) fn new(log_file: &str, apply: bool) -> Self {
+        Self {
+            log_file: log_file.to_string(),
+            apply,
+            reports: vec![],
+            outcome: None,
+        }
+    }
+
+    pub(super) fn add_report(&mut self, path: String) {
+        self.reports.push(path);
+    }
+
+    /// Records result of the run. Without it, the run is reported
+    /// as crashed, since only panics skip it.
+    pub(super) fn finish(&mut self, result: &Result<(), AliError>) {
+        self.outcome = Some(match result {
+            Ok(()) => Outcome::Ok,
+            Err(AliError::InstallError { stage_failed, .. }) => {
+                Outcome::Failed(Some(stage_failed.to_string()))
+            }
+            Err(_) => Outcome::Failed(None),
+        });
+    }
+
+    /// Summary text, resuming in `stage` of `target` (if any)
+    fn text(
+        &self,
+        status: &str,
+        stage: Option<&str>,
+        target: Option<&str>,
+    ) -> String {
+        let mut lines = vec![
+            format!("ali-rs: {status}"),
+            format!(
+                "  run dir:  {}",
+                crash::run_dir(&self.log_file).display()
+            ),
+            format!("  log file: {}", self.log_file),
+        ];
+
+        let reports = self.reports.iter().cloned().chain(report_sink::files());
+        lines.extend(reports.map(|report| format!("  report:   {report}")));
+
+        if status == "ok" {
+            return lines.join("\n");
+        }
+
+        if let (true, Some(stage)) = (self.apply, stage) {
+            let only_target = target
+                .map(|target| format!("--only-target {target} "))
+                .unwrap_or_default();
+
+            lines.push(format!(
+                "  resume:   rerun with `apply {only_target}--from {stage}` \
+                 after fixing"
+            ));
+        }
+
+        lines.push(
+            "  support:  attach log file and crash-*.json files in run dir \
+             to bug reports"
+                .to_string(),
+        );
+
+        lines.join("\n")
+    }
+}
+
+impl Drop for ExitSummary {
+    fn drop(&mut self) {
+        let (status, stage) = match self.outcome.take() {
+            Some(Outcome::Ok) => ("ok", None),
+            Some(Outcome::Failed(stage)) if shell::is_cancelled() => {
+                ("cancelled", stage.or_else(progress::current_stage))
+            }
+            Some(Outcome::Failed(stage)) => ("failed", stage),
+            None => ("crashed", progress::current_stage()),
+        };
+
+        let target = progress::current_target();
+        let text = self.text(status, stage.as_deref(), target.as_deref());
+        eprintln!("{text}");
+    }
+}
+
+#[test]
+fn test_exit_summary() {
+    use crate::types::stage::Stage;
+
+    let mut summary = ExitSummary::new("/tmp/ali/ali-rs.log", true);
+    summary.add_report("/tmp/reports/report-1.json".to_string());
+
+    let text = summary.text("ok", None, None);
+    assert!(text.starts_with("ali-rs: ok\n  run dir:  /tmp/ali\n"));
+    assert!(text.contains("  log file: /tmp/ali/ali-rs.log"));
+    assert!(text.contains("  report:   /tmp/reports/report-1.json"));
+    assert!(!text.contains("resume:"));
+    assert!(!text.contains("support:"));
+
+    summary.finish(&Err(AliError::InstallError {
+        error: Box::new(AliError::Aborted("disk busy".to_string())),
+        stage_failed: Stage::Bootstrap,
+        stages_performed: Box::default(),
+    }));
+    let Some(Outcome::Failed(Some(ref stage))) = summary.outcome else {
+        panic!("failed stage not recorded");
+    };
+    assert_eq!("stage-bootstrap", stage);
+
+    let text = summary.text("failed", Some(stage), None);
+    assert!(text.contains("`apply --from stage-bootstrap`"));
+    assert!(text.contains("support:"));
+
+    // Multi-target sessions resume the failed target
+    let text = summary.text("failed", Some(stage), Some("rescue"));
+    assert!(text.contains("`apply --only-target rescue --from stage-bootstrap`"));
+
+    // Only apply can be resumed
+    let mut summary = ExitSummary::new("ali-rs.log", false);
+    summary.finish(&Ok(()));
+    let text = summary.text("failed", Some("stage-bootstrap"), None);
+    assert!(text.contains("  run dir:  ."));
+    assert!(!text.contains("resume:"));
+}
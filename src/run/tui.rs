@@ -0,0 +1,199 @@
+use std::collections::HashMap;
+use std::io::Write;
+
+use colored::Colorize;
+
+use super::ManifestSource;
+use crate::ali::preset::{
+    self,
+    Answers,
+    Layout,
+};
+use crate::ali::{
+    self,
+    ManifestFormat,
+};
+use crate::cli;
+use crate::constants::defaults;
+use crate::errors::AliError;
+use crate::linux::blkid;
+use crate::types::report::Report;
+use crate::utils::fs::file_exists;
+
+const SYS_EFI: &str = "/sys/firmware/efi";
+
+/// Walks operator through installer questions, writes generated manifest
+/// to `args.output`, and applies it if `args.apply` is set
+pub(super) fn run(
+    install_location: &str,
+    has_cli_proxy: bool,
+    args: cli::ArgsTui,
+) -> Result<Option<Report>, AliError> {
+    let answers = ask()?;
+    let manifest = preset::manifest(&answers);
+    let yaml = ali::to_yaml(&manifest)?;
+
+    std::fs::write(&args.output, &yaml).map_err(|err| {
+        AliError::FileError(err, format!("failed to write {}", args.output))
+    })?;
+
+    eprintln!("{}", format!("Manifest written to {}", args.output).green());
+
+    if !args.apply {
+        eprintln!("Review it, then run: ali-rs apply -f {}", args.output);
+        return Ok(None);
+    }
+
+    let source = ManifestSource {
+        file: args.output,
+        format: ManifestFormat::Yaml,
+        vars: HashMap::new(),
+        sha256: None,
+    };
+
+    // Disks are still confirmed by typing device names
+    let args_apply = cli::ArgsApply {
+        no_validate: false,
+        no_preflight: false,
+        target: None,
+        yes: false,
+        overwrite: false,
+        stages: None,
+        skip_stages: Vec::new(),
+        from_stage: None,
+        only_target: None,
+        dry_run: args.dry_run,
+        enable_ssh: false,
+        ssh_password_file: None,
+        ssh_key: None,
+        qr: false,
+        mirror_country: None,
+        plan: None,
+        allow_destroy: false,
+    };
+
+    super::apply::run(&source, install_location, has_cli_proxy, args_apply)
+        .map(Some)
+}
+
+fn ask() -> Result<Answers, AliError> {
+    let disks = blkid::list_disks()?;
+    if disks.is_empty() {
+        return Err(AliError::BadArgs("no disks found".to_string()));
+    }
+
+    eprintln!("{}", "Disks:".bold());
+    for (i, disk) in disks.iter().enumerate() {
+        eprintln!(
+            "  {}) {} {:.1}GiB {} {}",
+            i + 1,
+            disk.path,
+            disk.size as f64 / (1u64 << 30) as f64,
+            disk.tran.as_deref().unwrap_or_default(),
+            disk.model.as_deref().unwrap_or_default(),
+        );
+    }
+
+    let disk = loop {
+        let answer = prompt("Disk to install to (number)", Some("1"))?;
+        match answer.parse::<usize>() {
+            Ok(n) if (1..=disks.len()).contains(&n) => {
+                break disks[n - 1].path.clone()
+            }
+            _ => eprintln!("{}", "invalid disk number".yellow()),
+        }
+    };
+
+    let uefi = file_exists(SYS_EFI);
+    eprintln!(
+        "Boot mode: {}",
+        match uefi {
+            true => "UEFI",
+            false => "BIOS",
+        }
+    );
+
+    let layout = loop {
+        match prompt("Partitioning preset [simple/lvm]", Some("simple"))?
+            .as_str()
+        {
+            "simple" => break Layout::Simple,
+            "lvm" => break Layout::Lvm,
+            _ => eprintln!("{}", "invalid preset".yellow()),
+        }
+    };
+
+    let fs_type = prompt("Root filesystem", Some("ext4"))?;
+    let swap = optional(prompt("Swap size, e.g. 4G (empty for none)", None)?);
+    let hostname = prompt("Hostname", Some(defaults::HOSTNAME))?;
+    let timezone = prompt("Timezone", Some(defaults::TIMEZONE))?;
+
+    let rootpasswd =
+        optional(prompt("Root password (empty to leave unset)", None)?)
+            .map(|passwd| hash(&passwd))
+            .transpose()?;
+
+    let user = match optional(prompt("Username (empty for none)", None)?) {
+        None => None,
+        Some(name) => {
+            let passwd = prompt(&format!("Password for {name}"), None)?;
+            Some((name, hash(&passwd)?))
+        }
+    };
+
+    let packages = prompt("Extra packages (space-separated)", None)?
+        .split_whitespace()
+        .map(String::from)
+        .collect();
+
+    Ok(Answers {
+        disk,
+        uefi,
+        layout,
+        fs_type,
+        swap,
+        hostname,
+        timezone,
+        rootpasswd,
+        user,
+        packages,
+    })
+}
+
+/// Prompts on stderr and reads trimmed answer from stdin,
+/// falling back to `default` if answer is empty
+fn prompt(question: &str, default: Option<&str>) -> Result<String, AliError> {
+    match default {
+        Some(default) => eprint!("{question} [{}]: ", default.bold()),
+        None => eprint!("{question}: "),
+    }
+    std::io::stderr().flush().ok();
+
+    let mut answer = String::new();
+    let n = std::io::stdin().read_line(&mut answer).map_err(|err| {
+        AliError::FileError(err, "failed to read answer".to_string())
+    })?;
+
+    if n == 0 {
+        return Err(AliError::Aborted("stdin closed".to_string()));
+    }
+
+    let answer = answer.trim();
+    match (answer.is_empty(), default) {
+        (true, Some(default)) => Ok(default.to_string()),
+        _ => Ok(answer.to_string()),
+    }
+}
+
+fn optional(answer: String) -> Option<String> {
+    match answer.is_empty() {
+        true => None,
+        false => Some(answer),
+    }
+}
+
+fn hash(passwd: &str) -> Result<String, AliError> {
+    pwhash::bcrypt::hash(passwd).map_err(|err| {
+        AliError::AliRsBug(format!("failed to hash password: {err}"))
+    })
+}
@@ -0,0 +1,625 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use serde::Serialize;
+
+use super::ManifestSource;
+use crate::ali::apply::ca;
+use crate::ali::{
+    Manifest,
+    ManifestFs,
+    ManifestMountpoint,
+    PartitionTable,
+};
+use crate::errors::AliError;
+use crate::hooks::{
+    self,
+    HookState,
+};
+use crate::linux::{
+    self,
+    mount,
+    systemd,
+};
+use crate::utils::{
+    json,
+    shell,
+};
+
+/// Partition sizes may differ from manifest by alignment
+pub(super) const SIZE_TOLERANCE: u64 = 1 << 20;
+
+/// Outcome of checking declared state of manifest against
+/// a completed install
+#[derive(Debug, Serialize)]
+struct Verification {
+    location: String,
+    passed: usize,
+    failed: usize,
+    checks: Vec<Check>,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+struct Check {
+    check: String,
+    ok: bool,
+    /// What was expected, and what was found if different
+    detail: String,
+}
+
+/// Checks that state declared in manifest holds for the install
+/// mounted at `location`, printing a pass/fail report
+pub(super) fn run(
+    source: &ManifestSource,
+    location: &str,
+) -> Result<(), AliError> {
+    let manifest = source.load()?;
+    let verification = verify(&manifest, location)?;
+    println!("{}", json::to_string_pretty(&verification));
+
+    match verification.failed {
+        0 => Ok(()),
+        failed => {
+            Err(AliError::Validation(format!(
+                "verify: {failed} of {} checks failed",
+                failed + verification.passed
+            )))
+        }
+    }
+}
+
+fn verify(
+    manifest: &Manifest,
+    location: &str,
+) -> Result<Verification, AliError> {
+    let mut checks = Vec::new();
+
+    // Diskless installs have no local block devices
+    if manifest.netroot.is_none() {
+        checks.extend(check_partitions(manifest));
+        checks.extend(check_filesystems(manifest));
+        checks.extend(check_mounts(manifest, location));
+    }
+
+    checks.extend(check_files(manifest, location));
+    checks.extend(check_hooks(manifest, location)?);
+
+    let passed = checks.iter().filter(|c| c.ok).count();
+
+    Ok(Verification {
+        location: location.to_string(),
+        passed,
+        failed: checks.len() - passed,
+        checks,
+    })
+}
+
+impl Check {
+    fn new(check: String, expected: &str, found: Option<&str>) -> Self {
+        match found {
+            Some(found) if found == expected => {
+                Check {
+                    check,
+                    ok: true,
+                    detail: expected.to_string(),
+                }
+            }
+            found => {
+                Check {
+                    check,
+                    ok: false,
+                    detail: format!(
+                        "expected {expected}, found {}",
+                        found.unwrap_or("nothing")
+                    ),
+                }
+            }
+        }
+    }
+
+    fn bool(check: String, ok: bool, detail: &str) -> Self {
+        Check {
+            check,
+            ok,
+            detail: detail.to_string(),
+        }
+    }
+}
+
+/// Partitions exist, with declared sizes, and with declared types
+/// on MBR tables. GPT type codes are fdisk aliases that differ
+/// between fdisk versions, so they are not checked.
+fn check_partitions(manifest: &Manifest) -> Vec<Check> {
+    let mut checks = Vec::new();
+
+    for disk in manifest.disks.iter().flatten() {
+        for (i, part) in disk.partitions.iter().enumerate() {
+            let device = linux::partition_name(&disk.device, i as u8 + 1);
+            let Some(info) = lsblk(&device) else {
+                checks.push(Check::bool(
+                    format!("partition {device}"),
+                    false,
+                    "no such partition",
+                ));
+                continue;
+            };
+
+            checks.push(Check::bool(
+                format!("partition {device}"),
+                true,
+                "exists",
+            ));
+
+            if let Some(ref size) = part.size {
+                let check = format!("partition {device} size");
+                match (parse_size(size), info.size) {
+                    (Some(expected), Some(found))
+                        if expected.abs_diff(found) <= SIZE_TOLERANCE =>
+                    {
+                        checks.push(Check::bool(check, true, size));
+                    }
+                    (expected, found) => {
+                        checks.push(Check::bool(
+                            check,
+                            false,
+                            &format!(
+                                "expected {size} ({} bytes), found {} bytes",
+                                expected.unwrap_or_default(),
+                                found.unwrap_or_default(),
+                            ),
+                        ));
+                    }
+                }
+            }
+
+            if matches!(disk.table, PartitionTable::Mbr) {
+                let expected = mbr_type(&part.part_type);
+                let found = info.part_type.as_deref().and_then(mbr_type);
+                checks.push(Check::new(
+                    format!("partition {device} type"),
+                    &expected.unwrap_or_else(|| part.part_type.clone()),
+                    found.as_deref(),
+                ));
+            }
+        }
+    }
+
+    checks
+}
+
+/// Filesystems and swap have declared types, and labels if any
+fn check_filesystems(manifest: &Manifest) -> Vec<Check> {
+    let rootfs: ManifestFs = manifest.rootfs.clone().into();
+    let swaps = manifest.swap.iter().flatten().map(|device| {
+        ManifestFs {
+            device: device.clone(),
+            fs_type: "swap".to_string(),
+            fs_opts: None,
+        }
+    });
+
+    let filesystems = std::iter::once(rootfs)
+        .chain(manifest.filesystems.iter().flatten().cloned())
+        .chain(swaps);
+
+    let mut checks = Vec::new();
+    for fs in filesystems {
+        let device = &fs.device;
+        let blkid = blkid(device);
+
+        checks.push(Check::new(
+            format!("filesystem {device} type"),
+            &fs.fs_type,
+            blkid.get("TYPE").map(String::as_str),
+        ));
+
+        if let Some(label) = fs.fs_opts.as_deref().and_then(parse_label) {
+            // FAT labels are case-insensitive
+            let found = blkid.get("LABEL").map(|found| {
+                match fs.fs_type == "vfat" && found.eq_ignore_ascii_case(&label)
+                {
+                    true => label.as_str(),
+                    false => found.as_str(),
+                }
+            });
+
+            checks.push(Check::new(
+                format!("filesystem {device} label"),
+                &label,
+                found,
+            ));
+        }
+    }
+
+    checks
+}
+
+/// Rootfs and other filesystems are mounted where declared
+fn check_mounts(manifest: &Manifest, location: &str) -> Vec<Check> {
+    let root: ManifestMountpoint = manifest.rootfs.clone().into();
+
+    std::iter::once(&root)
+        .chain(manifest.mountpoints.iter().flatten())
+        .map(|mnt| {
+            let dest = match mnt.dest.as_str() {
+                "/" => location.to_string(),
+                _ => mount::prepend_base(location, &mnt.dest),
+            };
+
+            let found = findmnt(&dest);
+            let same = found
+                .as_deref()
+                .is_some_and(|found| same_device(found, &mnt.device));
+
+            let found = match same {
+                true => Some(mnt.device.as_str()),
+                false => found.as_deref(),
+            };
+
+            Check::new(format!("mount {dest}"), &mnt.device, found)
+        })
+        .collect::<Vec<_>>()
+}
+
+/// Files and services declared by manifest keys other than hooks
+fn check_files(manifest: &Manifest, location: &str) -> Vec<Check> {
+    let mut checks = Vec::new();
+
+    if let Some(ref hostname) = manifest.hostname {
+        let found = std::fs::read_to_string(format!("{location}/etc/hostname"))
+            .ok()
+            .map(|s| s.trim().to_string());
+
+        checks.push(Check::new(
+            "hostname".to_string(),
+            hostname,
+            found.as_deref(),
+        ));
+    }
+
+    if let Some(ref timezone) = manifest.timezone {
+        let link = std::fs::read_link(format!("{location}/etc/localtime")).ok();
+        let ok = link
+            .as_ref()
+            .is_some_and(|link| link.ends_with(format!("zoneinfo/{timezone}")));
+
+        checks.push(Check::bool(
+            "timezone".to_string(),
+            ok,
+            &format!("/etc/localtime links to {timezone}"),
+        ));
+    }
+
+    for file in manifest.skel.iter().flatten() {
+        let path = format!("/etc/skel/{}", file.path.trim_end_matches('/'));
+        let ok = match (&file.content, file.is_dir()) {
+            (_, true) => Path::new(&format!("{location}{path}")).is_dir(),
+            (Some(content), false) => {
+                std::fs::read_to_string(format!("{location}{path}"))
+                    .is_ok_and(|found| &found == content)
+            }
+            (None, false) => Path::new(&format!("{location}{path}")).is_file(),
+        };
+
+        checks.push(Check::bool(format!("file {path}"), ok, "skel file"));
+    }
+
+    for cert in manifest.ca_certs.iter().flatten() {
+        let path = ca::anchor(&cert.name);
+        let ok = Path::new(&format!("{location}{path}")).is_file();
+        checks.push(Check::bool(format!("file {path}"), ok, "CA certificate"));
+    }
+
+    let mut services = Vec::new();
+    if let Some(ref m_maintenance) = manifest.maintenance {
+        if m_maintenance.fstrim.unwrap_or(false) {
+            services.push(("fstrim.timer", "timers.target"));
+        }
+        if m_maintenance.smartd.is_some() {
+            services.push(("smartd.service", "multi-user.target"));
+        }
+    }
+    if manifest.domain_join.is_some() {
+        services.push(("sssd.service", "multi-user.target"));
+        services.push(("ali-rs-domain-join.service", "multi-user.target"));
+    }
+
+    for (unit, target) in services {
+        checks.push(check_enabled(location, unit, target));
+    }
+
+    if let Some(ref m_time) = manifest.time {
+        for unit in m_time.services() {
+            checks.push(check_enabled(location, &unit, "multi-user.target"));
+        }
+    }
+
+    checks
+}
+
+/// State left by hooks in manifest keys `chroot` and `postinstall`
+fn check_hooks(
+    manifest: &Manifest,
+    location: &str,
+) -> Result<Vec<Check>, AliError> {
+    let cmds = manifest
+        .chroot
+        .iter()
+        .flatten()
+        .chain(manifest.postinstall.iter().flatten())
+        .filter(|cmd| hooks::is_hook(cmd));
+
+    let mut checks = Vec::new();
+    for cmd in cmds {
+        for state in hooks::hook_state(cmd)? {
+            match state {
+                HookState::Enabled { unit, target } => {
+                    checks.push(check_enabled(location, &unit, &target));
+                }
+                HookState::File { path, contains } => {
+                    let found =
+                        std::fs::read_to_string(format!("{location}{path}"));
+
+                    let missing: Vec<&String> = match found {
+                        Ok(ref found) => {
+                            contains
+                                .iter()
+                                .filter(|s| !found.contains(s.as_str()))
+                                .collect()
+                        }
+                        Err(_) => contains.iter().collect(),
+                    };
+
+                    let detail = match (&found, missing.is_empty()) {
+                        (Err(_), _) => "no such file".to_string(),
+                        (Ok(_), true) => "expected content".to_string(),
+                        (Ok(_), false) => {
+                            format!("missing {}", json::to_string(&missing))
+                        }
+                    };
+
+                    checks.push(Check::bool(
+                        format!("file {path}"),
+                        found.is_ok() && missing.is_empty(),
+                        &detail,
+                    ));
+                }
+            }
+        }
+    }
+
+    Ok(checks)
+}
+
+fn check_enabled(location: &str, unit: &str, target: &str) -> Check {
+    Check::bool(
+        format!("service {unit}"),
+        systemd::is_enabled(location, unit, Some(target)),
+        &format!("enabled for {target}"),
+    )
+}
+
+pub(super) struct PartInfo {
+    pub(super) size: Option<u64>,
+    pub(super) part_type: Option<String>,
+}
+
+/// Executes:
+/// ```shell
+/// lsblk --json --bytes --nodeps -o SIZE,PARTTYPE <device>
+/// ```
+pub(super) fn lsblk(device: &str) -> Option<PartInfo> {
+    let output = shell::exec_with_output_timeout(
+        "lsblk",
+        &["--json", "--bytes", "--nodeps", "-o", "SIZE,PARTTYPE", device],
+        Some(shell::PROBE_TIMEOUT),
+    )
+    .ok()?;
+
+    let lsblk: serde_json::Value = serde_json::from_slice(&output).ok()?;
+    let dev = lsblk["blockdevices"].get(0)?;
+
+    // Older lsblk prints sizes as strings
+    let size = match &dev["size"] {
+        serde_json::Value::Number(n) => n.as_u64(),
+        serde_json::Value::String(s) => s.parse().ok(),
+        _ => None,
+    };
+
+    Some(PartInfo {
+        size,
+        part_type: dev["parttype"].as_str().map(String::from),
+    })
+}
+
+/// Executes:
+/// ```shell
+/// blkid -o export <device>
+/// ```
+pub(super) fn blkid(device: &str) -> HashMap<String, String> {
+    shell::exec_with_output_timeout(
+        "blkid",
+        &["-o", "export", device],
+        Some(shell::PROBE_TIMEOUT),
+    )
+    .map(|output| {
+        String::from_utf8_lossy(&output)
+            .lines()
+            .filter_map(|line| line.split_once('='))
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    })
+    .unwrap_or_default()
+}
+
+/// Source device mounted at `dest`, without btrfs subvolume suffix
+fn findmnt(dest: &str) -> Option<String> {
+    let output = shell::exec_with_output_timeout(
+        "findmnt",
+        &["-n", "-o", "SOURCE", "--mountpoint", dest],
+        Some(shell::PROBE_TIMEOUT),
+    )
+    .ok()?;
+
+    let source = String::from_utf8_lossy(&output);
+    let source = source.trim();
+    let source = source.split_once('[').map_or(source, |(dev, _)| dev);
+
+    match source.is_empty() {
+        true => None,
+        false => Some(source.to_string()),
+    }
+}
+
+/// Whether paths refer to the same device, e.g. `/dev/myvg/rootlv`
+/// and `/dev/mapper/myvg-rootlv`
+fn same_device(a: &str, b: &str) -> bool {
+    a == b
+        || matches!(
+            (std::fs::canonicalize(a), std::fs::canonicalize(b)),
+            (Ok(a), Ok(b)) if a == b
+        )
+}
+
+/// Parses fdisk size like `300M` in bytes, with binary units
+pub(super) fn parse_size(size: &str) -> Option<u64> {
+    let size = size.trim().trim_end_matches(['B', 'b']);
+    let size = size.trim_end_matches(['i', 'I']);
+    let (n, unit) = match size.char_indices().last()? {
+        (i, c) if c.is_ascii_alphabetic() => (&size[..i], Some(c)),
+        _ => (size, None),
+    };
+
+    let shift = match unit.map(|c| c.to_ascii_uppercase()) {
+        None => 0,
+        Some('K') => 10,
+        Some('M') => 20,
+        Some('G') => 30,
+        Some('T') => 40,
+        Some('P') => 50,
+        Some(_) => return None,
+    };
+
+    n.parse::<u64>().ok()?.checked_mul(1 << shift)
+}
+
+/// Normalizes MBR type like `ef` (manifest) or `0xef` (lsblk)
+pub(super) fn mbr_type(part_type: &str) -> Option<String> {
+    let hex = part_type.trim_start_matches("0x");
+    u8::from_str_radix(hex, 16).ok().map(|t| format!("{t:02x}"))
+}
+
+/// Filesystem label from mkfs options, e.g. `-L BOOT` or `-n BOOT` (vfat)
+fn parse_label(fs_opts: &str) -> Option<String> {
+    let parts = shlex::split(fs_opts)?;
+    parts
+        .iter()
+        .position(|p| matches!(p.as_str(), "-L" | "-n" | "--label"))
+        .and_then(|i| parts.get(i + 1))
+        .cloned()
+}
+
+#[test]
+fn test_parse_size() {
+    assert_eq!(Some(300 << 20), parse_size("300M"));
+    assert_eq!(Some(300 << 20), parse_size("300MiB"));
+    assert_eq!(Some(8 << 30), parse_size("8G"));
+    assert_eq!(Some(512), parse_size("512"));
+    assert_eq!(None, parse_size("8X"));
+    assert_eq!(None, parse_size("big"));
+
+    assert_eq!(Some("ef".to_string()), mbr_type("0xef"));
+    assert_eq!(Some("0b".to_string()), mbr_type("b"));
+    assert_eq!(Some("BOOT".to_string()), parse_label("-F 32 -n BOOT"));
+    assert_eq!(Some("root fs".to_string()), parse_label("-L 'root fs'"));
+    assert_eq!(None, parse_label("-F 32"));
+}
+
+#[test]
+fn test_verify() {
+    use std::sync::Arc;
+
+    use crate::utils::mock::MockRunner;
+
+    let root = std::env::temp_dir()
+        .join(format!("ali-rs-test-verify-{}", std::process::id()));
+    let location = root.to_str().unwrap();
+    std::fs::create_dir_all(root.join("etc")).unwrap();
+    std::fs::write(root.join("etc/hostname"), "arch-server\n").unwrap();
+
+    let yaml = r#"
+version: 1
+hostname: arch-server
+disks:
+  - device: /dev/vda
+    table: mbr
+    partitions:
+      - label: boot
+        size: 300M
+        type: ef
+      - label: root
+        type: "83"
+rootfs:
+  device: /dev/vda2
+  fstype: btrfs
+  fsopts: -L rootfs
+fs:
+  - device: /dev/vda1
+    fstype: vfat
+    fsopts: -F 32 -n BOOT
+mountpoints:
+  - device: /dev/vda1
+    dest: /boot
+maintenance:
+  fstrim: true
+chroot:
+  - "@tailscale authkey=tskey-auth-foo tags=server"
+"#;
+
+    let mock = Arc::new(
+        MockRunner::new()
+            .stdout(
+                "lsblk --json --bytes --nodeps -o SIZE,PARTTYPE /dev/vda1",
+                r#"{"blockdevices": [{"size": 314572800, "parttype": "0xef"}]}"#,
+            )
+            .stdout(
+                "lsblk --json --bytes --nodeps -o SIZE,PARTTYPE /dev/vda2",
+                r#"{"blockdevices": [{"size": "9000000000", "parttype": "0x8e"}]}"#,
+            )
+            .stdout("blkid -o export /dev/vda1", "TYPE=vfat\nLABEL=boot\n")
+            .stdout("blkid -o export /dev/vda2", "TYPE=btrfs\nLABEL=rootfs\n")
+            .stdout(
+                &format!("findmnt -n -o SOURCE --mountpoint {location}/boot"),
+                "/dev/vda1\n",
+            )
+            .stdout(
+                &format!("findmnt -n -o SOURCE --mountpoint {location}"),
+                "/dev/vda2[/@]\n",
+            ),
+    );
+    let _guard = shell::set_runner(mock.clone());
+
+    let manifest = crate::ali::parse(yaml).unwrap();
+    let verification = verify(&manifest, location).unwrap();
+    let failed: Vec<&str> = verification
+        .checks
+        .iter()
+        .filter(|c| !c.ok)
+        .map(|c| c.check.as_str())
+        .collect();
+
+    assert_eq!(
+        vec![
+            "partition /dev/vda2 type",
+            "service fstrim.timer",
+            "file /usr/local/bin/ali-rs-tailscale-up",
+            "file /etc/systemd/system/ali-rs-tailscale-up.service",
+            "service tailscaled.service",
+            "service ali-rs-tailscale-up.service",
+        ],
+        failed,
+    );
+    assert_eq!(verification.checks.len() - failed.len(), verification.passed);
+
+    std::fs::remove_dir_all(root).unwrap();
+}
@@ -53,6 +53,12 @@ pub enum ActionMountpoints {
     #[serde(rename = "mountFilesystems")]
     MountFilesystems,
 
+    #[serde(rename = "wipeDisk")]
+    WipeDisk {
+        device: String,
+        wipe: ali::DiskWipe,
+    },
+
     #[serde(rename = "createPartitionTable")]
     CreatePartitionTable {
         device: String,
@@ -127,6 +133,43 @@ pub enum ActionRoutine {
 
     #[serde(rename = "rootPasswd")]
     RootPasswd,
+
+    #[serde(rename = "netRoot")]
+    NetRoot,
+
+    #[serde(rename = "portable")]
+    Portable,
+
+    #[serde(rename = "crypttab")]
+    Crypttab,
+
+    #[serde(rename = "kernelCmdline")]
+    KernelCmdline,
+
+    #[serde(rename = "maintenance")]
+    Maintenance,
+
+    #[serde(rename = "sshClient")]
+    SshClient,
+
+    #[serde(rename = "skel")]
+    Skel,
+
+    /// Units enabled for time synchronization
+    #[serde(rename = "timeSync")]
+    TimeSync(Vec<String>),
+
+    /// Names of CA certificates installed as trust anchors
+    #[serde(rename = "caCertificates")]
+    CaCertificates(Vec<String>),
+
+    /// Domain join prepared, to be performed on first boot
+    /// with status written to `status` in the new system
+    #[serde(rename = "domainJoin")]
+    DomainJoin {
+        domain: String,
+        status: String,
+    },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -137,6 +180,12 @@ pub enum ActionChrootAli {
 
     #[serde(rename = "localeGen")]
     LocaleGen,
+
+    #[serde(rename = "mkinitcpio")]
+    Mkinitcpio,
+
+    #[serde(rename = "installGrub")]
+    InstallGrub,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -147,6 +196,9 @@ pub enum ActionChrootUser {
 
     #[serde(rename = "aliRsHookChrootUser")]
     Hook(hooks::ActionHook),
+
+    #[serde(rename = "sshClientUser")]
+    SshClientUser(String),
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -164,7 +216,10 @@ pub enum ActionPostInstallUser {
 // Dummy function to see JSON result
 fn test_json_stages() {
     use super::report::Report;
-    use super::stage::StageActions;
+    use super::stage::{
+        StageActions,
+        StageUsage,
+    };
     use ali::PartitionTable;
 
     let actions_mountpoints = vec![
@@ -221,13 +276,43 @@ fn test_json_stages() {
         chroot_ali: actions_chroot_ali.clone(),
         chroot_user: actions_chroot_user.clone(),
         postinstall_user: actions_postinstall_user.clone(),
+        usage: vec![StageUsage {
+            stage: "stage-bootstrap".to_string(),
+            cpu_secs: 12.5,
+            peak_rss_kib: 204800,
+            bytes_written: 1 << 30,
+        }],
     };
 
     let report = Report {
         summary: Box::new(stages),
         duration: std::time::Duration::from_secs(20),
         location: "dummy".to_string(),
+        ssh_fingerprint: None,
+        targets: vec![],
     };
 
     println!("{}", report.to_json_string());
+    assert!(report
+        .to_json_string()
+        .contains(r#""resourceUsage":[{"bytesWritten":1073741824,"cpuSecs":12.5,"peakRssKib":204800,"stage":"stage-bootstrap"}]"#));
+    assert!(!report.to_json_string().contains("targets"));
+
+    let mut report = report;
+    report.targets.push((
+        "rescue".to_string(),
+        Report {
+            summary: Box::default(),
+            duration: std::time::Duration::from_secs(5),
+            location: "/rescue".to_string(),
+            ssh_fingerprint: None,
+            targets: vec![],
+        },
+    ));
+
+    let json = report.to_json();
+    assert_eq!(5, json["targets"]["rescue"]["elaspedTime"]["secs"]);
+    assert!(report
+        .to_short_string()
+        .contains("\n  rescue: installed to /rescue in 5s"));
 }
@@ -0,0 +1,118 @@
+use crate::errors::AliError;
+
+/// Keys which may appear more than once with different values
+const REPEATABLE: [&str; 5] = [
+    "console",
+    "rd.luks.name",
+    "rd.luks.uuid",
+    "rd.luks.options",
+    "modprobe.blacklist",
+];
+
+/// Flags which cannot be given together
+const INCOMPATIBLE: [(&str, &str); 1] = [("rw", "ro")];
+
+/// Kernel command line merged from parameters contributed by
+/// different sources (e.g. rootfs, LUKS, manifest), in order
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct KernelCmdline {
+    /// Parameters with their sources
+    params: Vec<(String, String)>,
+}
+
+impl KernelCmdline {
+    /// Adds `param` (`key=value` or flag) from `source`. Duplicates are
+    /// ignored, and conflicting parameters from any source are errors.
+    pub fn push(&mut self, source: &str, param: &str) -> Result<(), AliError> {
+        if param.is_empty() || param.contains(char::is_whitespace) {
+            return Err(AliError::BadManifest(format!(
+                "bad kernel parameter {param:?} from {source}"
+            )));
+        }
+
+        if self.params.iter().any(|(_, p)| p == param) {
+            return Ok(());
+        }
+
+        let key = key(param);
+        let conflict = self.params.iter().find(|(_, p)| {
+            let k = self::key(p);
+            let same_key = k == key && !REPEATABLE.contains(&key);
+            let incompatible = INCOMPATIBLE
+                .iter()
+                .any(|&(a, b)| (a, b) == (k, key) || (b, a) == (k, key));
+
+            same_key || incompatible
+        });
+
+        if let Some((other_source, other)) = conflict {
+            return Err(AliError::BadManifest(format!(
+                "kernel parameter {param} from {source} conflicts with {other} from {other_source}"
+            )));
+        }
+
+        self.params.push((source.to_string(), param.to_string()));
+        Ok(())
+    }
+
+    /// Parameters for GRUB_CMDLINE_LINUX, which excludes `root=`, `rw`,
+    /// and `ro`, since those are generated by grub-mkconfig
+    pub fn grub_params(&self) -> String {
+        self.iter()
+            .filter(|p| !["root", "rw", "ro"].contains(&key(p)))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    fn iter(&self) -> impl Iterator<Item = &str> {
+        self.params.iter().map(|(_, p)| p.as_str())
+    }
+}
+
+fn key(param: &str) -> &str {
+    param.split_once('=').map_or(param, |(k, _)| k)
+}
+
+impl std::fmt::Display for KernelCmdline {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.iter().collect::<Vec<_>>().join(" "))
+    }
+}
+
+#[test]
+fn test_kernel_cmdline() {
+    let mut cmdline = KernelCmdline::default();
+    for (source, param) in [
+        ("luks", "cryptdevice=UUID=foo:cryptroot"),
+        ("rootfs", "root=UUID=bar"),
+        ("rootfs", "rw"),
+        ("manifest", "rw"),
+        ("manifest", "console=tty0"),
+        ("manifest", "console=ttyS0,115200"),
+        ("manifest", "quiet"),
+    ] {
+        cmdline.push(source, param).unwrap();
+    }
+
+    assert_eq!(
+        "cryptdevice=UUID=foo:cryptroot root=UUID=bar rw console=tty0 console=ttyS0,115200 quiet",
+        cmdline.to_string()
+    );
+    assert_eq!(
+        "cryptdevice=UUID=foo:cryptroot console=tty0 console=ttyS0,115200 quiet",
+        cmdline.grub_params()
+    );
+
+    for param in [
+        "root=/dev/vda2",
+        "ro",
+        "cryptdevice=/dev/vda2:root",
+        "a b",
+        "",
+    ] {
+        assert!(
+            cmdline.push("manifest", param).is_err(),
+            "expecting conflict for {param}"
+        );
+    }
+}
@@ -1,4 +1,5 @@
 pub mod action;
 pub mod blockdev;
+pub mod cmdline;
 pub mod report;
 pub mod stage;
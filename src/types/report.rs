@@ -1,33 +1,78 @@
 use serde_json::json;
 
 use super::stage::StageActions;
+use crate::utils::{
+    json,
+    secrets,
+};
 
 #[derive(Debug)]
 pub struct Report {
     pub location: String,
     pub summary: Box<StageActions>,
     pub duration: std::time::Duration,
+    /// Live installer host key, if ssh was enabled
+    pub ssh_fingerprint: Option<String>,
+    /// Reports of extra targets of a multi-target session, by name
+    pub targets: Vec<(String, Report)>,
 }
 
 impl Report {
     pub fn to_json(&self) -> serde_json::Value {
-        json!({
+        let mut value = json::versioned(&json!({
             "summary": self.summary,
             "elaspedTime": self.duration,
-        })
+        }));
+
+        if !self.targets.is_empty() {
+            let targets: serde_json::Map<_, _> = self
+                .targets
+                .iter()
+                .map(|(name, report)| (name.clone(), report.to_json()))
+                .collect();
+
+            value["targets"] = targets.into();
+        }
+        secrets::redact_json(&mut value);
+
+        value
     }
 
     pub fn to_json_string(&self) -> String {
         self.to_json().to_string()
     }
+
+    /// Short plain-text summary, e.g. for QR codes
+    pub fn to_short_string(&self) -> String {
+        let mut s = format!(
+            "ali-rs: installed to {} in {}s",
+            self.location,
+            self.duration.as_secs()
+        );
+
+        for (name, report) in &self.targets {
+            s.push_str(&format!(
+                "\n  {name}: installed to {} in {}s",
+                report.location,
+                report.duration.as_secs()
+            ));
+        }
+
+        if let Some(ref fingerprint) = self.ssh_fingerprint {
+            s.push_str(&format!("\nssh host key: {fingerprint}"));
+        }
+
+        s
+    }
 }
 
-impl ToString for Report {
-    fn to_string(&self) -> String {
-        self.to_json_string()
+impl std::fmt::Display for Report {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.to_json_string())
     }
 }
 
 pub struct ValidationReport {
+    #[allow(unused)]
     pub block_devs: super::blockdev::BlockDevPaths,
 }
@@ -10,7 +10,7 @@ use super::action::*;
 #[derive(Debug, Clone, PartialEq, Eq, Hash, ValueEnum)]
 
 pub enum Stage {
-    #[value(alias = "stage-mountpoints")]
+    #[value(alias = "stage-mountpoints", alias = "disks")]
     Mountpoints,
 
     #[value(alias = "stage-bootstrap")]
@@ -28,12 +28,14 @@ pub enum Stage {
 
     #[value(
         alias = "chroot_user",
+        alias = "chroot-hooks",
         alias = "stage-chrootuser",
         alias = "stage-chroot_user"
     )]
     ChrootUser,
 
     #[value(
+        alias = "postinstall",
         alias = "postinstalluser",
         alias = "postinstall_user",
         alias = "stage-postinstall",
@@ -79,6 +81,22 @@ pub struct StageActions {
     #[serde(rename = "stage-postinstall_user")]
     #[serde(skip_serializing_if = "Vec::is_empty")]
     pub postinstall_user: Vec<ActionPostInstallUser>,
+
+    /// Resource usage of each stage applied, in order
+    #[serde(rename = "resourceUsage", default)]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub usage: Vec<StageUsage>,
+}
+
+/// Resource usage of a stage, including its child processes
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StageUsage {
+    pub stage: String,
+    pub cpu_secs: f64,
+    /// High-water mark of largest process by the end of stage, in KiB
+    pub peak_rss_kib: u64,
+    pub bytes_written: u64,
 }
 
 impl std::fmt::Display for Stage {
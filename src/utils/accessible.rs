@@ -0,0 +1,51 @@
+use std::process::Command;
+use std::sync::OnceLock;
+
+use super::shell;
+
+const ESPEAK: &str = "espeak-ng";
+
+/// Whether accessible output mode is on, set once from CLI flag `--accessible`
+static ENABLED: OnceLock<bool> = OnceLock::new();
+
+/// Turns on accessible output mode: colors are disabled, and progress is
+/// announced as plain sentences (spoken with espeak-ng if present)
+pub fn enable() {
+    if ENABLED.set(true).is_ok() {
+        colored::control::set_override(false);
+    }
+}
+
+pub fn is_enabled() -> bool {
+    ENABLED.get().copied().unwrap_or(false)
+}
+
+/// Prints `sentence` to stderr and speaks it, only in accessible mode
+pub fn announce(sentence: &str) {
+    if !is_enabled() {
+        return;
+    }
+
+    eprintln!("{sentence}");
+    speak(sentence);
+}
+
+/// Rings terminal bell, only in accessible mode.
+/// Used on prompts and on completion.
+pub fn beep() {
+    if is_enabled() {
+        eprint!("\x07");
+    }
+}
+
+/// Speaks `sentence` in the background if espeak-ng is installed
+fn speak(sentence: &str) {
+    if !shell::in_path(ESPEAK) {
+        return;
+    }
+
+    let sentence = sentence.to_string();
+    std::thread::spawn(move || {
+        let _ = Command::new(ESPEAK).arg(sentence).status();
+    });
+}
@@ -0,0 +1,279 @@
+//! Registry of value aliases, e.g. `lvm_on_luks` for mkinitcpio
+//! boot hook preset `lvm-on-luks`, or `fat32` for filesystem type `vfat`.
+//!
+//! Built-in aliases can be extended with user aliases from YAML file
+//! [`ENV_ALIASES`] (default [`defaults::ALIASES_FILE`]), mapping each kind
+//! to canonical names and their aliases:
+//!
+//! ```yaml
+//! boot_hook:
+//!   lvm-on-luks: [fde]
+//! fstype:
+//!   vfat: [esp]
+//! ```
+
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
+
+use serde::Deserialize;
+
+use super::suggest;
+use crate::constants::defaults;
+use crate::errors::AliError;
+
+/// Env for path to user aliases file
+pub const ENV_ALIASES: &str = "ALI_ALIASES";
+
+/// Kinds of aliased values, as keyed in user aliases file
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize,
+)]
+pub enum Kind {
+    /// Preset of `@mkinitcpio boot_hook=<PRESET>`
+    #[serde(rename = "boot_hook")]
+    BootHook,
+
+    /// Manifest filesystem types, i.e. `mkfs.<FS_TYPE>` programs
+    #[serde(rename = "fstype")]
+    FsType,
+
+    /// Bootloader of `@rollback`
+    #[serde(rename = "bootloader")]
+    Bootloader,
+}
+
+impl Kind {
+    /// Whether users may alias names not built into ali-rs.
+    /// Filesystem types are open-ended, since any `mkfs.<FS_TYPE>`
+    /// in `$PATH` can be used.
+    fn is_open(&self) -> bool {
+        matches!(self, Self::FsType)
+    }
+}
+
+impl std::fmt::Display for Kind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::BootHook => write!(f, "boot_hook"),
+            Self::FsType => write!(f, "fstype"),
+            Self::Bootloader => write!(f, "bootloader"),
+        }
+    }
+}
+
+/// Built-in names and their aliases
+#[rustfmt::skip]
+const BUILTIN: &[(Kind, &str, &[&str])] = &[
+    (Kind::BootHook, "lvm", &[
+        "root-on-lvm", "root_on_lvm", "root-lvm", "root_lvm",
+        "lvm-root", "lvm_root",
+    ]),
+    (Kind::BootHook, "luks", &[
+        "root-on-luks", "root_on_luks", "root-luks", "root_luks",
+        "luks-root", "luks_root",
+    ]),
+    (Kind::BootHook, "lvm-on-luks", &[
+        "root-on-lvm-on-luks", "root_on_lvm_on_luks",
+        "lvm-on-luks-root", "lvm_on_luks_root",
+        "root-lvm-on-luks", "root_lvm_on_luks", "lvm_on_luks",
+    ]),
+    (Kind::BootHook, "luks-on-lvm", &[
+        "root-on-luks-on-lvm", "root_on_luks_on_lvm",
+        "luks-on-lvm-root", "luks_on_lvm_root",
+        "root-luks-on-lvm", "root_luks_on_lvm", "luks_on_lvm",
+    ]),
+    (Kind::FsType, "bcachefs", &[]),
+    (Kind::FsType, "btrfs", &[]),
+    (Kind::FsType, "exfat", &[]),
+    (Kind::FsType, "ext2", &[]),
+    (Kind::FsType, "ext3", &[]),
+    (Kind::FsType, "ext4", &[]),
+    (Kind::FsType, "f2fs", &[]),
+    (Kind::FsType, "fat", &[]),
+    (Kind::FsType, "jfs", &[]),
+    (Kind::FsType, "nilfs2", &[]),
+    (Kind::FsType, "vfat", &["fat16", "fat32"]),
+    (Kind::FsType, "xfs", &[]),
+    (Kind::Bootloader, "grub", &["grub2"]),
+    (Kind::Bootloader, "sd-boot", &[
+        "systemd-boot", "systemd_boot", "sdboot", "gummiboot",
+    ]),
+];
+
+/// User aliases file, as kind to canonical names to aliases
+type UserAliases = BTreeMap<Kind, BTreeMap<String, Vec<String>>>;
+
+/// Names of each kind, each mapped to its canonical name.
+/// Canonical names map to themselves.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Aliases(BTreeMap<Kind, BTreeMap<String, String>>);
+
+static ALIASES: OnceLock<Aliases> = OnceLock::new();
+
+impl Aliases {
+    pub fn builtin() -> Self {
+        let mut names: BTreeMap<Kind, BTreeMap<String, String>> =
+            BTreeMap::new();
+
+        for (kind, canonical, aliases) in BUILTIN {
+            let kind_names = names.entry(*kind).or_default();
+            for name in std::iter::once(canonical).chain(aliases.iter()) {
+                kind_names.insert(name.to_string(), canonical.to_string());
+            }
+        }
+
+        Self(names)
+    }
+
+    /// Adds user aliases. Aliases must not be taken by other names,
+    /// and must alias known names unless kind is open-ended.
+    pub fn extend(&mut self, user: UserAliases) -> Result<(), AliError> {
+        for (kind, canonicals) in user {
+            let names = self.0.entry(kind).or_default();
+            for (canonical, aliases) in canonicals {
+                let canonical = match names.get(&canonical) {
+                    Some(c) => c.clone(),
+                    None if kind.is_open() => canonical,
+                    None => {
+                        return Err(AliError::BadArgs(format!(
+                            "unknown {kind} {canonical} in aliases{}",
+                            suggest::hint(
+                                &canonical,
+                                names.keys().map(String::as_str)
+                            ),
+                        )));
+                    }
+                };
+
+                for alias in std::iter::once(&canonical).chain(&aliases) {
+                    match names.get(alias) {
+                        Some(taken) if *taken != canonical => {
+                            return Err(AliError::BadArgs(format!(
+                                "{kind} alias {alias} is already taken by {taken}"
+                            )));
+                        }
+                        _ => {
+                            names.insert(alias.clone(), canonical.clone());
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Canonical name of `value`, if known
+    pub fn resolve(&self, kind: Kind, value: &str) -> Option<&str> {
+        self.0.get(&kind)?.get(value).map(String::as_str)
+    }
+
+    /// All known names of `kind`, canonical and aliases, sorted
+    pub fn names(&self, kind: Kind) -> impl Iterator<Item = &str> {
+        self.0.get(&kind).into_iter().flat_map(|names| {
+            names.keys().map(String::as_str)
+        })
+    }
+}
+
+/// Loads user aliases from file `ALI_ALIASES`, or from default file
+/// if it exists. Built-in aliases are used if not initialized.
+pub fn init() -> Result<(), AliError> {
+    let path = match std::env::var(ENV_ALIASES) {
+        Ok(path) if !path.is_empty() => path,
+        _ if super::fs::file_exists(defaults::ALIASES_FILE) => {
+            defaults::ALIASES_FILE.to_string()
+        }
+        _ => return Ok(()),
+    };
+
+    let yaml = std::fs::read_to_string(&path).map_err(|err| {
+        AliError::FileError(err, format!("failed to read aliases {path}"))
+    })?;
+
+    let user: UserAliases = serde_yaml::from_str(&yaml).map_err(|err| {
+        AliError::BadArgs(format!("bad aliases file {path}: {err}"))
+    })?;
+
+    let mut aliases = Aliases::builtin();
+    aliases.extend(user)?;
+    if ALIASES.set(aliases).is_err() {
+        log::warn!("aliases already initialized, ignoring {path}");
+    }
+
+    Ok(())
+}
+
+fn global() -> &'static Aliases {
+    ALIASES.get_or_init(Aliases::builtin)
+}
+
+/// Canonical name of `value`, if known
+pub fn resolve(kind: Kind, value: &str) -> Option<&'static str> {
+    global().resolve(kind, value)
+}
+
+/// Canonical name of `value`, or `value` itself if unknown
+pub fn canonical(kind: Kind, value: &str) -> String {
+    resolve(kind, value).unwrap_or(value).to_string()
+}
+
+/// Suggests known names of `kind` close to `value`, like [`suggest::hint`]
+pub fn hint(kind: Kind, value: &str) -> String {
+    suggest::hint(value, global().names(kind))
+}
+
+#[test]
+fn test_aliases() {
+    let mut aliases = Aliases::builtin();
+    let tests = [
+        (Kind::BootHook, "lvm_on_luks", Some("lvm-on-luks")),
+        (Kind::BootHook, "luks", Some("luks")),
+        (Kind::BootHook, "root-luks-on-lvm", Some("luks-on-lvm")),
+        (Kind::BootHook, "fde", None),
+        (Kind::FsType, "fat32", Some("vfat")),
+        (Kind::FsType, "ext4", Some("ext4")),
+        (Kind::FsType, "lvm", None),
+        (Kind::Bootloader, "systemd-boot", Some("sd-boot")),
+    ];
+
+    for (kind, value, expected) in tests {
+        assert_eq!(expected, aliases.resolve(kind, value), "{kind} {value}");
+    }
+
+    let user: UserAliases = serde_yaml::from_str(
+        "
+boot_hook:
+  lvm_on_luks: [fde]
+fstype:
+  vfat: [esp]
+  ntfs3: [ntfs]
+",
+    )
+    .unwrap();
+
+    aliases.extend(user).unwrap();
+    assert_eq!(Some("lvm-on-luks"), aliases.resolve(Kind::BootHook, "fde"));
+    assert_eq!(Some("vfat"), aliases.resolve(Kind::FsType, "esp"));
+    assert_eq!(Some("ntfs3"), aliases.resolve(Kind::FsType, "ntfs"));
+    assert!(aliases.names(Kind::BootHook).any(|name| name == "fde"));
+
+    let should_err = [
+        // Unknown canonical name of closed kind
+        "boot_hook: { zfs: [zfs-root] }",
+        // Alias taken by another name
+        "boot_hook: { lvm: [luks] }",
+        "bootloader: { grub: [systemd-boot] }",
+        // Unknown kind
+        "kernel: { linux: [vanilla] }",
+    ];
+
+    for yaml in should_err {
+        let mut aliases = Aliases::builtin();
+        let result = serde_yaml::from_str::<UserAliases>(yaml)
+            .map_err(|err| AliError::BadArgs(err.to_string()))
+            .and_then(|user| aliases.extend(user));
+
+        assert!(result.is_err(), "{yaml}");
+    }
+}
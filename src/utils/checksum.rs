@@ -0,0 +1,47 @@
+use sha2::{
+    Digest,
+    Sha256,
+};
+
+use crate::errors::AliError;
+
+/// Returns hex-encoded SHA-256 digest of `bytes`
+pub fn sha256_hex(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+/// Returns whether `s` looks like hex-encoded SHA-256 digest
+pub fn is_sha256_hex(s: &str) -> bool {
+    s.len() == 64 && s.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Verifies `bytes` from `origin` against hex-encoded SHA-256 `expected`
+pub fn verify_sha256(
+    origin: &str,
+    bytes: &[u8],
+    expected: &str,
+) -> Result<(), AliError> {
+    let actual = sha256_hex(bytes);
+    if !actual.eq_ignore_ascii_case(expected) {
+        return Err(AliError::Validation(format!(
+            "sha256 mismatch for {origin}: expecting {expected}, got {actual}"
+        )));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_verify_sha256() {
+    let sum =
+        "2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824";
+
+    assert_eq!(sum, sha256_hex(b"hello"));
+    assert!(is_sha256_hex(sum));
+    assert!(!is_sha256_hex("abc"));
+    assert!(verify_sha256("a", b"hello", &sum.to_uppercase()).is_ok());
+    assert!(verify_sha256("a", b"hello!", sum).is_err());
+}
@@ -0,0 +1,193 @@
+//! Crash files for ali-rs panics, with opt-in submission
+//! to an HTTP(S) endpoint
+
+use std::backtrace::Backtrace;
+use std::path::{
+    Path,
+    PathBuf,
+};
+#[cfg(feature = "remote")]
+use std::time::Duration;
+use std::time::{
+    SystemTime,
+    UNIX_EPOCH,
+};
+
+use serde::Serialize;
+
+use super::{
+    fs,
+    json,
+    progress,
+    secrets,
+    shell,
+};
+use crate::errors::AliError;
+
+#[cfg(feature = "remote")]
+const HTTP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Structured crash report, written when ali-rs panics
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Crash {
+    message: String,
+    /// Source location of panic, e.g. `src/ali/mod.rs:10:5`
+    location: Option<String>,
+    stage: Option<String>,
+    /// Last external command run by ali-rs
+    last_command: Option<String>,
+    versions: Versions,
+    backtrace: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Versions {
+    ali_rs: &'static str,
+    os: &'static str,
+    arch: &'static str,
+    /// Running kernel release, from /proc/sys/kernel/osrelease
+    kernel: Option<String>,
+}
+
+/// Returns directory of `log_file`, where files of this run are kept
+pub fn run_dir(log_file: &str) -> PathBuf {
+    match Path::new(log_file).parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+        _ => PathBuf::from("."),
+    }
+}
+
+/// Installs panic hook writing crash file to the directory of `log_file`,
+/// and POSTing it to `submit_url` if the user opted in.
+/// The default panic message is still printed.
+pub fn install(log_file: &str, submit_url: Option<String>) {
+    let dir = run_dir(log_file);
+
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        default_hook(info);
+
+        let message = info
+            .payload()
+            .downcast_ref::<&str>()
+            .map(|s| s.to_string())
+            .or_else(|| info.payload().downcast_ref::<String>().cloned())
+            .unwrap_or_default();
+
+        let location = info.location().map(|l| l.to_string());
+        let crash = redacted(&crash(message, location));
+
+        match write(&dir, &crash) {
+            Ok(path) => {
+                eprintln!(
+                    "ali-rs crashed, please attach crash file {} to bug reports",
+                    path.display()
+                );
+            }
+            Err(err) => {
+                eprintln!("ali-rs crashed, and failed to write crash file: {err}");
+            }
+        }
+
+        if let Some(ref url) = submit_url {
+            if let Err(err) = submit(url, &crash) {
+                eprintln!("failed to submit crash file to {url}: {err}");
+            }
+        }
+    }));
+}
+
+fn crash(message: String, location: Option<String>) -> Crash {
+    Crash {
+        message,
+        location,
+        stage: progress::current_stage(),
+        last_command: shell::last_command(),
+        versions: Versions {
+            ali_rs: env!("CARGO_PKG_VERSION"),
+            os: std::env::consts::OS,
+            arch: std::env::consts::ARCH,
+            kernel: std::fs::read_to_string("/proc/sys/kernel/osrelease")
+                .ok()
+                .map(|s| s.trim().to_string()),
+        },
+        backtrace: Backtrace::force_capture().to_string(),
+    }
+}
+
+/// Serializes `crash` with resolved secrets redacted, e.g. secrets
+/// in panic messages or in the last command
+fn redacted(crash: &Crash) -> String {
+    let mut value = json::versioned(crash);
+    secrets::redact_json(&mut value);
+
+    json::to_string_pretty(&value)
+}
+
+/// Writes `crash` to new file `crash-<UNIX_SECS>.json` in `dir`,
+/// readable only by root
+fn write(dir: &Path, crash: &str) -> Result<PathBuf, AliError> {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+
+    let path = dir.join(format!("crash-{secs}.json"));
+    fs::mkdir_p(&dir.to_string_lossy())?;
+    fs::write_private(&path.to_string_lossy(), crash.as_bytes())?;
+
+    Ok(path)
+}
+
+#[cfg(feature = "remote")]
+fn submit(url: &str, crash: &str) -> Result<(), String> {
+    ureq::post(url)
+        .timeout(HTTP_TIMEOUT)
+        .set("Content-Type", "application/json")
+        .send_string(crash)
+        .map(|_| ())
+        .map_err(|err| err.to_string())
+}
+
+#[cfg(not(feature = "remote"))]
+fn submit(_url: &str, _crash: &str) -> Result<(), String> {
+    Err("ali-rs built without feature remote".to_string())
+}
+
+#[test]
+fn test_crash() {
+    progress::stage(&"stage-bootstrap");
+    let _ = shell::exec_with_output("true", &[]);
+
+    use std::os::unix::fs::PermissionsExt;
+
+    secrets::register("ali-rs-test-crash-secret");
+    let crash = crash(
+        "oops ali-rs-test-crash-secret".to_string(),
+        Some("src/foo.rs:1:1".to_string()),
+    );
+    // Other tests may run commands and set stages concurrently
+    assert!(crash.last_command.is_some());
+    assert!(crash.stage.is_some());
+
+    let dir = std::env::temp_dir()
+        .join(format!("ali-rs-test-crash-{}", std::process::id()));
+    let path = write(&dir, &redacted(&crash)).unwrap();
+    let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+    assert_eq!(0o600, mode & 0o777);
+
+    let written: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&path).unwrap())
+            .unwrap();
+    assert_eq!(
+        format!("oops {}", secrets::REDACTED),
+        written["message"].as_str().unwrap(),
+    );
+    assert_eq!(1, written["version"]);
+    assert_eq!(env!("CARGO_PKG_VERSION"), written["versions"]["aliRs"]);
+    assert!(written["backtrace"].is_string());
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
@@ -4,3 +4,245 @@ where
 {
     path.as_ref().exists()
 }
+
+/// Maximum number of symlinks followed by [`path_under`], like Linux
+const MAX_SYMLINKS: usize = 40;
+
+/// Returns location of `path` under `base`, resolving symlinks as if
+/// `base` were the root directory (like chroot), so that neither `..`
+/// nor absolute symlinks inside `base` lead outside of it.
+/// Errors if `..` in `path` itself climbs above `base`.
+pub fn path_under(
+    base: &str,
+    path: &str,
+) -> Result<String, crate::errors::AliError> {
+    let base = base.trim_end_matches('/');
+    let escape = |msg: String| {
+        crate::errors::AliError::FileError(
+            std::io::Error::new(std::io::ErrorKind::InvalidInput, msg),
+            format!("{path} under {base}/"),
+        )
+    };
+
+    let mut resolved: Vec<String> = Vec::new();
+    let mut rest: std::collections::VecDeque<String> =
+        path.split('/').map(String::from).collect();
+
+    // Number of components of `path` itself, after which
+    // components come from symlink targets
+    let mut from_path = rest.len();
+    let mut symlinks = 0;
+
+    while let Some(component) = rest.pop_front() {
+        let is_path = from_path > 0;
+        from_path = from_path.saturating_sub(1);
+
+        match component.as_str() {
+            "" | "." => continue,
+            ".." => {
+                // Relative symlinks stop at base, like at real root
+                if resolved.pop().is_none() && is_path {
+                    return Err(escape("path escapes base".to_string()));
+                }
+
+                continue;
+            }
+            _ => resolved.push(component),
+        }
+
+        let current = format!("{base}/{}", resolved.join("/"));
+        let Ok(target) = std::fs::read_link(&current) else {
+            continue;
+        };
+
+        symlinks += 1;
+        if symlinks > MAX_SYMLINKS {
+            return Err(escape(format!("too many symlinks at {current}")));
+        }
+
+        let target = target.to_string_lossy().to_string();
+        resolved.pop();
+        if target.starts_with('/') {
+            resolved.clear();
+        }
+
+        for component in target.split('/').rev() {
+            rest.push_front(component.to_string());
+        }
+    }
+
+    Ok(format!("{base}/{}", resolved.join("/")))
+}
+
+/// Writes `content` to `path` under `base`, creating parent directories.
+/// `path` is resolved with [`path_under`].
+pub fn write_under(
+    base: &str,
+    path: &str,
+    content: &str,
+) -> Result<(), crate::errors::AliError> {
+    write_bytes_under(base, path, content.as_bytes())
+}
+
+/// Like [`write_under`], but for binary `content`
+pub fn write_bytes_under(
+    base: &str,
+    path: &str,
+    content: &[u8],
+) -> Result<(), crate::errors::AliError> {
+    let dst = path_under(base, path)?;
+    super::readonly::check(&format!("write {dst}"))?;
+    let dir = std::path::Path::new(&dst).parent().unwrap();
+
+    std::fs::create_dir_all(dir)
+        .and_then(|_| std::fs::write(&dst, content))
+        .map_err(|err| {
+            crate::errors::AliError::FileError(
+                err,
+                format!("failed to write file {dst}"),
+            )
+        })
+}
+
+/// Copies file `src` to `path` under `base`, creating parent directories
+/// and keeping permissions of `src`. `path` is resolved with
+/// [`path_under`]. Returns the resolved destination.
+pub fn copy_under(
+    base: &str,
+    src: &std::path::Path,
+    path: &str,
+) -> Result<String, crate::errors::AliError> {
+    let dst = path_under(base, path)?;
+    super::readonly::check(&format!("write {dst}"))?;
+    let dir = std::path::Path::new(&dst).parent().unwrap();
+
+    std::fs::create_dir_all(dir)
+        .and_then(|_| std::fs::copy(src, &dst))
+        .map_err(|err| {
+            crate::errors::AliError::FileError(
+                err,
+                format!("failed to copy {} to {dst}", src.display()),
+            )
+        })?;
+
+    Ok(dst)
+}
+
+/// Writes `content` to file `path` with permission 0600, set before any
+/// content is written (also on existing files), so that secrets are never
+/// readable by others, not even briefly
+pub fn write_private(
+    path: &str,
+    content: &[u8],
+) -> Result<(), crate::errors::AliError> {
+    write_mode(path, content, 0o600)
+}
+
+/// Writes `content` to file `path` with permission `mode`, set before any
+/// content is written, like [`write_private`]
+pub fn write_mode(
+    path: &str,
+    content: &[u8],
+    mode: u32,
+) -> Result<(), crate::errors::AliError> {
+    use std::io::Write;
+    use std::os::unix::fs::{
+        OpenOptionsExt,
+        PermissionsExt,
+    };
+
+    super::readonly::check(&format!("write {path}"))?;
+
+    std::fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(mode)
+        .open(path)
+        .and_then(|mut file| {
+            // Mode only applies to new files
+            file.set_permissions(std::fs::Permissions::from_mode(mode))?;
+            file.write_all(content)
+        })
+        .map_err(|err| {
+            crate::errors::AliError::FileError(
+                err,
+                format!("failed to write file {path}"),
+            )
+        })
+}
+
+/// Creates directory `path` and its parents, like `mkdir -p`
+/// but without relying on a mkdir binary on the live system
+pub fn mkdir_p(path: &str) -> Result<(), crate::errors::AliError> {
+    super::readonly::check(&format!("create directory {path}"))?;
+
+    std::fs::create_dir_all(path).map_err(|err| {
+        crate::errors::AliError::FileError(
+            err,
+            format!("failed to create directory {path}"),
+        )
+    })
+}
+
+#[test]
+fn test_path_under() {
+    let base = std::env::temp_dir().join("ali-rs-test-path-under");
+    let _ = std::fs::remove_dir_all(&base);
+    std::fs::create_dir_all(base.join("etc/conf.d")).unwrap();
+    std::fs::create_dir_all(base.join("usr/share")).unwrap();
+
+    use std::os::unix::fs::symlink;
+    symlink("/usr/share", base.join("etc/share")).unwrap();
+    symlink("../../../../../../etc", base.join("usr/etc")).unwrap();
+    symlink("conf.d", base.join("etc/relative")).unwrap();
+    symlink("/etc/loop", base.join("etc/loop")).unwrap();
+
+    let b = base.to_str().unwrap();
+    let under = |path: &str| path_under(b, path);
+
+    assert_eq!(under("/etc/hostname").unwrap(), format!("{b}/etc/hostname"));
+    assert_eq!(under("etc//./hostname").unwrap(), format!("{b}/etc/hostname"));
+    assert_eq!(under("/etc/../etc/hosts").unwrap(), format!("{b}/etc/hosts"));
+    assert_eq!(under("/").unwrap(), format!("{b}/"));
+
+    // Symlinks are resolved inside base
+    assert_eq!(under("/etc/share/x").unwrap(), format!("{b}/usr/share/x"));
+    assert_eq!(under("/usr/etc/passwd").unwrap(), format!("{b}/etc/passwd"));
+    assert_eq!(
+        under("/etc/relative/a.conf").unwrap(),
+        format!("{b}/etc/conf.d/a.conf")
+    );
+
+    assert!(under("/../../etc/passwd").is_err());
+    assert!(under("etc/../../passwd").is_err());
+    assert!(under("/etc/loop").is_err());
+
+    // Live system
+    assert_eq!(path_under("/", "/etc/hostname").unwrap(), "/etc/hostname");
+
+    std::fs::remove_dir_all(&base).unwrap();
+}
+
+#[test]
+fn test_write_private() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let path = std::env::temp_dir().join("ali-rs-test-write-private");
+    std::fs::write(&path, "old").unwrap();
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644))
+        .unwrap();
+
+    let p = path.to_str().unwrap();
+    write_private(p, b"secret").unwrap();
+
+    let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+    assert_eq!(0o600, mode & 0o777);
+    assert_eq!("secret", std::fs::read_to_string(&path).unwrap());
+
+    write_mode(p, b"#!/bin/sh", 0o755).unwrap();
+    let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+    assert_eq!(0o755, mode & 0o777);
+
+    std::fs::remove_file(&path).unwrap();
+}
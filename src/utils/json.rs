@@ -0,0 +1,71 @@
+//! Stable serialization of JSON artifacts, e.g. reports, plans,
+//! statuses and hook actions.
+//!
+//! Artifacts are wrapped in versioned envelope `{"version": 1, ..}`,
+//! with object keys sorted, so that field order does not depend on
+//! internal struct layouts.
+
+use serde::Serialize;
+use serde_json::{
+    json,
+    Value,
+};
+
+/// Version of JSON artifacts. Bump when fields are removed or renamed.
+pub const VERSION: u32 = 1;
+
+pub const KEY_VERSION: &str = "version";
+
+/// Key for non-object values, e.g. `{"version": 1, "data": [..]}`
+pub const KEY_DATA: &str = "data";
+
+/// Serializes `value` into versioned envelope.
+/// Objects are flattened into the envelope, other values go to key `data`.
+pub fn versioned<T: Serialize + ?Sized>(value: &T) -> Value {
+    // serde_json::Map is BTreeMap without feature preserve_order,
+    // so keys are always sorted
+    let value = serde_json::to_value(value).unwrap_or_else(|err| {
+        panic!("ali-rs bug: failed to serialize JSON artifact: {err}")
+    });
+
+    let mut envelope = json!({ KEY_VERSION: VERSION });
+    let obj = envelope.as_object_mut().unwrap();
+    match value {
+        Value::Object(fields) => obj.extend(fields),
+        value => {
+            obj.insert(KEY_DATA.to_string(), value);
+        }
+    }
+
+    // Version is never overwritten by fields
+    obj.insert(KEY_VERSION.to_string(), json!(VERSION));
+
+    envelope
+}
+
+pub fn to_string<T: Serialize + ?Sized>(value: &T) -> String {
+    versioned(value).to_string()
+}
+
+pub fn to_string_pretty<T: Serialize + ?Sized>(value: &T) -> String {
+    serde_json::to_string_pretty(&versioned(value)).unwrap()
+}
+
+#[test]
+fn test_versioned() {
+    #[derive(Serialize)]
+    struct Foo {
+        zeta: u8,
+        alpha: &'static str,
+        version: &'static str,
+    }
+
+    let foo = Foo {
+        zeta: 1,
+        alpha: "a",
+        version: "bad",
+    };
+
+    assert_eq!(r#"{"alpha":"a","version":1,"zeta":1}"#, to_string(&foo));
+    assert_eq!(r#"{"data":["a","b"],"version":1}"#, to_string(&["a", "b"]));
+}
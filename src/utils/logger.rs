@@ -0,0 +1,221 @@
+use std::fs::File;
+use std::io::Write;
+use std::sync::{
+    Mutex,
+    OnceLock,
+};
+use std::time::{
+    SystemTime,
+    UNIX_EPOCH,
+};
+
+use colored::Colorize;
+use log::{
+    Level,
+    LevelFilter,
+    Log,
+    Metadata,
+    Record,
+};
+
+use super::secrets;
+
+/// Log target of lines already printed by [`super::progress`],
+/// which are only written to log file
+pub const TARGET_PROGRESS: &str = "ali_rs::progress";
+
+/// Path of log file in new system
+pub const LOG_DIR_TARGET: &str = "/var/log/ali-rs";
+
+static LOGGER: OnceLock<Logger> = OnceLock::new();
+
+/// Logs to stderr at level set by `-v`, and always at debug level
+/// to log file
+struct Logger {
+    stderr: LevelFilter,
+    file: Option<(String, Mutex<File>)>,
+}
+
+/// Sets up global logger, with stderr verbosity from number of `-v` flags.
+/// Failure to open `log_file` is reported, but is not fatal.
+pub fn init(verbosity: u8, log_file: &str) {
+    let stderr = match verbosity {
+        0 => LevelFilter::Info,
+        1 => LevelFilter::Debug,
+        _ => LevelFilter::Trace,
+    };
+
+    let file = open(log_file)
+        .map_err(|err| {
+            eprintln!(
+                "{}",
+                format!("WARN: cannot open log file {log_file}: {err}")
+                    .yellow()
+            )
+        })
+        .ok()
+        .map(|file| (log_file.to_string(), Mutex::new(file)));
+
+    let logger = LOGGER.get_or_init(|| Logger { stderr, file });
+    if log::set_logger(logger).is_ok() {
+        log::set_max_level(stderr.max(LevelFilter::Debug));
+    }
+}
+
+/// Path of log file, if it could be opened
+pub fn file() -> Option<String> {
+    LOGGER
+        .get()
+        .and_then(|l| l.file.as_ref())
+        .map(|(path, _)| path.clone())
+}
+
+/// Copies log file to `/var/log/ali-rs/` under `location`,
+/// so that failed installs can be debugged after reboot
+pub fn copy_to(location: &str) {
+    let Some((path, file)) = LOGGER.get().and_then(|l| l.file.as_ref()) else {
+        return;
+    };
+
+    let _guard = file.lock().unwrap();
+    let name = std::path::Path::new(path).file_name().unwrap_or_default();
+    let dir = format!("{location}{LOG_DIR_TARGET}");
+    let dst = format!("{dir}/{}", name.to_string_lossy());
+
+    // Log is only readable by root, like the log file itself
+    let result = std::fs::create_dir_all(&dir)
+        .and_then(|_| std::fs::read(path))
+        .map_err(|err| err.to_string())
+        .and_then(|log| {
+            super::fs::write_private(&dst, &log).map_err(|err| err.to_string())
+        });
+
+    if let Err(err) = result {
+        eprintln!(
+            "{}",
+            format!("WARN: failed to copy log file to {dst}: {err}").yellow()
+        );
+    }
+}
+
+/// Opens log file for appending, only readable by its owner
+fn open(path: &str) -> std::io::Result<File> {
+    use std::os::unix::fs::{
+        OpenOptionsExt,
+        PermissionsExt,
+    };
+
+    if let Some(dir) = std::path::Path::new(path).parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+
+    let file =
+        File::options().create(true).append(true).mode(0o600).open(path)?;
+
+    // Mode only applies to new files
+    file.set_permissions(std::fs::Permissions::from_mode(0o600))?;
+
+    Ok(file)
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.stderr || self.file.is_some()
+    }
+
+    fn log(&self, record: &Record) {
+        let args = secrets::redact(&record.args().to_string());
+        let line = format!(
+            "{} {:<5} {}: {args}",
+            timestamp(SystemTime::now()),
+            record.level(),
+            record.target(),
+        );
+
+        if let Some((_, file)) = &self.file {
+            if record.level() <= LevelFilter::Debug {
+                let _ = writeln!(file.lock().unwrap(), "{line}");
+            }
+        }
+
+        if record.level() > self.stderr || record.target() == TARGET_PROGRESS {
+            return;
+        }
+
+        // Timestamps and targets are only shown with -v
+        if self.stderr > LevelFilter::Info {
+            eprintln!("{line}");
+            return;
+        }
+
+        match record.level() {
+            Level::Error => eprintln!("{}", format!("ERROR: {args}").red()),
+            Level::Warn => eprintln!("{}", format!("WARN: {args}").yellow()),
+            _ => eprintln!("{args}"),
+        }
+    }
+
+    fn flush(&self) {
+        if let Some((_, file)) = &self.file {
+            let _ = file.lock().unwrap().flush();
+        }
+    }
+}
+
+/// Formats `t` as RFC 3339 UTC timestamp, e.g. `2023-03-05T10:20:30Z`
+fn timestamp(t: SystemTime) -> String {
+    let secs = t.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    let (days, rem) = (secs / 86400, secs % 86400);
+
+    // Civil date from days since epoch, see
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        rem / 3600,
+        (rem % 3600) / 60,
+        rem % 60
+    )
+}
+
+#[test]
+fn test_open() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let path = std::env::temp_dir().join("ali-rs-test-logger-open.log");
+    std::fs::write(&path, "old\n").unwrap();
+    std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644))
+        .unwrap();
+
+    let mut file = open(path.to_str().unwrap()).unwrap();
+    writeln!(file, "new").unwrap();
+
+    let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+    assert_eq!(0o600, mode & 0o777);
+    assert_eq!("old\nnew\n", std::fs::read_to_string(&path).unwrap());
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_timestamp() {
+    let tests = [
+        (0, "1970-01-01T00:00:00Z"),
+        (951782400, "2000-02-29T00:00:00Z"),
+        (1678011630, "2023-03-05T10:20:30Z"),
+    ];
+
+    for (secs, expected) in tests {
+        let t = UNIX_EPOCH + std::time::Duration::from_secs(secs);
+        assert_eq!(expected, timestamp(t));
+    }
+}
@@ -0,0 +1,196 @@
+//! Mock command backend, recording invocations and returning canned
+//! outputs instead of running commands. Used by unit tests, and by
+//! `ALI_RS_MOCK` to exercise ali-rs without root or real block devices.
+//! Only built for tests and with feature `e2e`, so that release builds
+//! never skip commands because of a stray env.
+
+use std::collections::BTreeMap;
+use std::sync::{
+    Arc,
+    Mutex,
+};
+
+use serde::Deserialize;
+
+use super::shell::{
+    self,
+    CmdError,
+    CommandRunner,
+};
+use crate::errors::AliError;
+
+/// Env enabling mock backend for the whole run: `1` for empty outputs,
+/// or path to JSON file of canned results keyed by command line prefix
+#[cfg(feature = "e2e")]
+pub const ENV_MOCK: &str = "ALI_RS_MOCK";
+
+/// Canned result of mocked command
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct Canned {
+    #[serde(default)]
+    pub stdout: String,
+
+    /// Exit status, 0 if omitted
+    #[serde(default)]
+    pub exit: i32,
+}
+
+#[derive(Debug, Default)]
+pub struct MockRunner {
+    /// Canned results, keyed by command line prefix
+    canned: BTreeMap<String, Canned>,
+
+    /// Command lines run, in order
+    calls: Mutex<Vec<String>>,
+}
+
+impl MockRunner {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns `stdout` for commands starting with `prefix`
+    #[allow(unused)]
+    pub fn stdout(mut self, prefix: &str, stdout: &str) -> Self {
+        self.canned.insert(prefix.to_string(), Canned {
+            stdout: stdout.to_string(),
+            exit: 0,
+        });
+
+        self
+    }
+
+    /// Fails commands starting with `prefix` with exit status `exit`
+    #[allow(unused)]
+    pub fn exit(mut self, prefix: &str, exit: i32) -> Self {
+        self.canned.insert(prefix.to_string(), Canned {
+            stdout: String::new(),
+            exit,
+        });
+
+        self
+    }
+
+    /// Command lines run so far, e.g. `pvcreate /dev/vda1`
+    #[allow(unused)]
+    pub fn calls(&self) -> Vec<String> {
+        self.calls.lock().unwrap().clone()
+    }
+
+    /// Records `cmd` and returns its canned result,
+    /// from the longest matching prefix
+    fn run(&self, cmd: &str, args: &[&str]) -> Canned {
+        let line = std::iter::once(cmd)
+            .chain(args.iter().copied())
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        log::debug!("mock: {line}");
+
+        let canned = self
+            .canned
+            .iter()
+            .filter(|(prefix, _)| line.starts_with(prefix.as_str()))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, canned)| canned.clone())
+            .unwrap_or_default();
+
+        self.calls.lock().unwrap().push(line);
+
+        canned
+    }
+}
+
+impl CommandRunner for MockRunner {
+    fn exec(&self, cmd: &str, args: &[&str]) -> Result<(), AliError> {
+        self.exec_with_output(cmd, args).map(|_| ())
+    }
+
+    fn exec_with_output(
+        &self,
+        cmd: &str,
+        args: &[&str],
+    ) -> Result<Vec<u8>, AliError> {
+        let canned = self.run(cmd, args);
+        match canned.exit {
+            0 => Ok(canned.stdout.into_bytes()),
+            code => {
+                Err(AliError::CmdFailed {
+                    error: CmdError::ErrRun {
+                        code: Some(code),
+                        stdout: Some(canned.stdout.into_bytes()),
+                        stderr: None,
+                    },
+                    context: format!(
+                        "mocked command {cmd} exited with status {code}"
+                    ),
+                })
+            }
+        }
+    }
+}
+
+/// Runs all commands with mock backend if env [`ENV_MOCK`] is set
+#[cfg(feature = "e2e")]
+pub fn init() -> Result<(), AliError> {
+    let Ok(value) = std::env::var(ENV_MOCK) else {
+        return Ok(());
+    };
+
+    let runner = match value.as_str() {
+        "" | "0" => return Ok(()),
+        "1" => MockRunner::new(),
+        path => {
+            let json = std::fs::read_to_string(path).map_err(|err| {
+                AliError::FileError(err, format!("failed to read {ENV_MOCK}"))
+            })?;
+
+            let canned = serde_json::from_str(&json).map_err(|err| {
+                AliError::BadArgs(format!("bad {ENV_MOCK} file {path}: {err}"))
+            })?;
+
+            MockRunner {
+                canned,
+                calls: Mutex::default(),
+            }
+        }
+    };
+
+    log::warn!("{ENV_MOCK} is set: commands are mocked, not run");
+    shell::set_global_runner(Arc::new(runner));
+
+    Ok(())
+}
+
+#[test]
+fn test_mock_runner() {
+    let mock = Arc::new(
+        MockRunner::new()
+            .stdout("blkid", "TYPE=ext4")
+            .stdout("blkid -o export /dev/vda2", "TYPE=btrfs")
+            .exit("false", 1),
+    );
+
+    let _guard = shell::set_runner(mock.clone());
+    assert_eq!(
+        b"TYPE=btrfs".to_vec(),
+        shell::exec_with_output("blkid", &["-o", "export", "/dev/vda2"])
+            .unwrap(),
+    );
+    assert_eq!(
+        b"TYPE=ext4".to_vec(),
+        shell::exec_with_output("blkid", &["/dev/vda1"]).unwrap(),
+    );
+    assert!(shell::exec("false", &[]).is_err());
+    shell::sh_c("rm -rf /ali-rs-mock").unwrap();
+
+    assert_eq!(
+        vec![
+            "blkid -o export /dev/vda2",
+            "blkid /dev/vda1",
+            "false",
+            "sh -c rm -rf /ali-rs-mock",
+        ],
+        mock.calls(),
+    );
+}
@@ -1,2 +1,21 @@
+pub mod accessible;
+pub mod aliases;
+pub mod checksum;
+pub mod crash;
 pub mod fs;
+pub mod json;
+pub mod logger;
+#[cfg(any(test, feature = "e2e"))]
+pub mod mock;
+pub mod progress;
+pub mod qr;
+pub mod readonly;
+pub mod report_sink;
+pub mod runlock;
+pub mod secrets;
 pub mod shell;
+pub mod shellconf;
+pub mod snapshot;
+pub mod suggest;
+pub mod tunnel;
+pub mod watchdog;
@@ -0,0 +1,216 @@
+use std::sync::{
+    mpsc,
+    Mutex,
+    OnceLock,
+};
+use std::thread::JoinHandle;
+use std::time::{
+    Duration,
+    Instant,
+};
+
+use clap::ValueEnum;
+use colored::Colorize;
+use serde_json::json;
+
+/// Interval of "still running" lines, so that long steps
+/// (e.g. mkfs or pacstrap) do not look frozen
+const HEARTBEAT: Duration = Duration::from_secs(15);
+
+/// Progress output format, set once from CLI flag `--progress`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ProgressMode {
+    /// Plain structured lines
+    Plain,
+    /// Colored lines
+    Fancy,
+    /// JSON lines, for consumption by other programs
+    Json,
+}
+
+static MODE: OnceLock<ProgressMode> = OnceLock::new();
+static START: OnceLock<Instant> = OnceLock::new();
+static STAGE: Mutex<Option<String>> = Mutex::new(None);
+static TARGET: Mutex<Option<String>> = Mutex::new(None);
+
+/// Sets progress output format, and starts the elapsed time clock
+pub fn set_mode(mode: ProgressMode) {
+    let _ = MODE.set(mode);
+    START.get_or_init(Instant::now);
+}
+
+/// Sets current stage for subsequent steps
+pub fn stage(stage: &impl std::fmt::Display) {
+    *STAGE.lock().unwrap() = Some(stage.to_string());
+}
+
+/// Current stage, if any
+pub fn current_stage() -> Option<String> {
+    STAGE.lock().unwrap().clone()
+}
+
+/// Sets current target of a multi-target session for subsequent stages
+pub fn target(name: &str) {
+    *TARGET.lock().unwrap() = Some(name.to_string());
+}
+
+/// Current target, if in a multi-target session
+pub fn current_target() -> Option<String> {
+    TARGET.lock().unwrap().clone()
+}
+
+/// Adds current target (if any) to JSON event `line`
+fn with_target(mut line: serde_json::Value) -> serde_json::Value {
+    if let (Some(target), Some(map)) = (current_target(), line.as_object_mut())
+    {
+        map.insert("target".to_string(), target.into());
+    }
+
+    line
+}
+
+/// Reports start of `step` (e.g. current device or hook), returning a guard
+/// that reports progress while the step runs, and its duration when dropped
+pub fn step(step: impl Into<String>) -> Step {
+    let step = step.into();
+    let start = Instant::now();
+    emit("start", &step, None);
+
+    let (done, rx) = mpsc::channel::<()>();
+    let heartbeat_step = step.clone();
+    let heartbeat = std::thread::spawn(move || {
+        while let Err(mpsc::RecvTimeoutError::Timeout) =
+            rx.recv_timeout(HEARTBEAT)
+        {
+            emit("running", &heartbeat_step, Some(start.elapsed()));
+        }
+    });
+
+    Step {
+        step,
+        start,
+        done: Some(done),
+        heartbeat: Some(heartbeat),
+    }
+}
+
+pub struct Step {
+    step: String,
+    start: Instant,
+    done: Option<mpsc::Sender<()>>,
+    heartbeat: Option<JoinHandle<()>>,
+}
+
+impl Drop for Step {
+    fn drop(&mut self) {
+        // Dropping sender disconnects the heartbeat loop
+        self.done.take();
+        if let Some(heartbeat) = self.heartbeat.take() {
+            let _ = heartbeat.join();
+        }
+
+        emit("done", &self.step, Some(self.start.elapsed()));
+    }
+}
+
+/// Reports `line` of output of command `cmd` on `stream`
+/// (`stdout` or `stderr`) as it arrives
+pub fn output(cmd: &str, stream: &str, line: &str) {
+    let mode = MODE.get().copied().unwrap_or(ProgressMode::Plain);
+
+    log::debug!(
+        target: super::logger::TARGET_PROGRESS,
+        "{cmd} {stream}: {line}"
+    );
+
+    match mode {
+        ProgressMode::Json => {
+            let line = json!({
+                "event": "output",
+                "stage": STAGE.lock().unwrap().clone().unwrap_or_default(),
+                "cmd": cmd,
+                "stream": stream,
+                "line": line,
+            });
+
+            eprintln!("{}", super::json::to_string(&with_target(line)));
+        }
+        ProgressMode::Fancy => eprintln!("{}", line.dimmed()),
+        ProgressMode::Plain => eprintln!("{line}"),
+    }
+}
+
+fn emit(event: &str, step: &str, step_elapsed: Option<Duration>) {
+    let elapsed = START.get_or_init(Instant::now).elapsed();
+    let stage = STAGE.lock().unwrap().clone().unwrap_or_default();
+    let mode = MODE.get().copied().unwrap_or(ProgressMode::Plain);
+
+    log::debug!(
+        target: super::logger::TARGET_PROGRESS,
+        "{event} {stage}: {step} ({})",
+        fmt_duration(step_elapsed.unwrap_or_default())
+    );
+
+    match mode {
+        ProgressMode::Json => {
+            let line = json!({
+                "event": event,
+                "stage": stage,
+                "step": step,
+                "elapsedSecs": elapsed.as_secs(),
+                "stepSecs": step_elapsed.map(|d| d.as_secs()),
+            });
+
+            eprintln!("{}", super::json::to_string(&with_target(line)));
+        }
+
+        ProgressMode::Plain | ProgressMode::Fancy => {
+            let status = match (event, step_elapsed) {
+                ("done", Some(d)) => format!(" done in {}", fmt_duration(d)),
+                ("running", Some(d)) => {
+                    format!(" still running after {}", fmt_duration(d))
+                }
+                _ => String::new(),
+            };
+
+            let stage = match (stage.is_empty(), current_target()) {
+                (true, _) => stage,
+                (false, None) => format!("{stage}: "),
+                (false, Some(target)) => format!("{target}/{stage}: "),
+            };
+
+            let line =
+                format!("[{}] {stage}{step}{status}", fmt_duration(elapsed));
+            match (mode, event) {
+                (ProgressMode::Fancy, "done") => eprintln!("{}", line.green()),
+                (ProgressMode::Fancy, _) => eprintln!("{}", line.cyan()),
+                _ => eprintln!("{line}"),
+            }
+        }
+    }
+}
+
+/// Formats duration as human-readable string, e.g. `3m20s`
+pub fn fmt_duration(d: Duration) -> String {
+    let secs = d.as_secs();
+    match (secs / 3600, (secs % 3600) / 60, secs % 60) {
+        (0, 0, s) => format!("{s}s"),
+        (0, m, s) => format!("{m}m{s}s"),
+        (h, m, s) => format!("{h}h{m}m{s}s"),
+    }
+}
+
+#[test]
+fn test_fmt_duration() {
+    let tests = [
+        (0, "0s"),
+        (59, "59s"),
+        (200, "3m20s"),
+        (3600, "1h0m0s"),
+        (3725, "1h2m5s"),
+    ];
+
+    for (secs, expected) in tests {
+        assert_eq!(expected, fmt_duration(Duration::from_secs(secs)));
+    }
+}
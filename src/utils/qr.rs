@@ -0,0 +1,67 @@
+#[cfg(feature = "qr")]
+use qrcode::render::unicode::Dense1x2;
+#[cfg(feature = "qr")]
+use qrcode::{
+    EcLevel,
+    QrCode,
+};
+
+use crate::errors::AliError;
+
+/// Longer data is truncated so that the code stays scannable on screen
+#[cfg(feature = "qr")]
+const MAX_QR_BYTES: usize = 512;
+
+/// Renders `data` as terminal QR code using Unicode half blocks
+#[cfg(feature = "qr")]
+pub fn render(data: &str) -> Result<String, AliError> {
+    let data = truncate(data, MAX_QR_BYTES);
+    let code = QrCode::with_error_correction_level(data, EcLevel::L)
+        .map_err(|err| AliError::AliRsBug(format!("qr encode: {err}")))?;
+
+    Ok(code
+        .render::<Dense1x2>()
+        .dark_color(Dense1x2::Light)
+        .light_color(Dense1x2::Dark)
+        .quiet_zone(true)
+        .build())
+}
+
+#[cfg(not(feature = "qr"))]
+pub fn render(_data: &str) -> Result<String, AliError> {
+    Err(AliError::NotImplemented(
+        "QR codes (ali-rs built without feature qr)".to_string(),
+    ))
+}
+
+/// Truncates `s` to at most `max` bytes on char boundary
+#[cfg(feature = "qr")]
+fn truncate(s: &str, max: usize) -> &str {
+    if s.len() <= max {
+        return s;
+    }
+
+    let mut end = max;
+    while !s.is_char_boundary(end) {
+        end -= 1;
+    }
+
+    &s[..end]
+}
+
+#[cfg(feature = "qr")]
+#[test]
+fn test_render() {
+    let qr = render("ali-rs: done").unwrap();
+    assert!(!qr.is_empty());
+
+    let long = "ก".repeat(MAX_QR_BYTES);
+    assert!(render(&long).is_ok());
+    assert_eq!(MAX_QR_BYTES - 2, truncate(&long, MAX_QR_BYTES).len());
+}
+
+#[cfg(not(feature = "qr"))]
+#[test]
+fn test_render_disabled() {
+    assert!(render("ali-rs: done").is_err());
+}
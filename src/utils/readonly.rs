@@ -0,0 +1,68 @@
+//! Read-only context, in which ali-rs refuses to run commands or write
+//! files. Hooks in print mode (e.g. `@uncomment-print`) are run in it,
+//! so that print hooks never touch disks, even if they forget
+//! to check their own mode.
+
+use std::cell::RefCell;
+
+use crate::errors::AliError;
+
+thread_local! {
+    /// Who entered the read-only context, e.g. hook key
+    static READ_ONLY: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// Read-only context, left when dropped
+pub struct ReadOnly {
+    previous: Option<String>,
+}
+
+/// Enters read-only context on behalf of `who`,
+/// until the returned guard is dropped
+pub fn enter(who: &str) -> ReadOnly {
+    let previous = READ_ONLY.with_borrow_mut(|r| r.replace(who.to_string()));
+
+    ReadOnly { previous }
+}
+
+impl Drop for ReadOnly {
+    fn drop(&mut self) {
+        READ_ONLY.with_borrow_mut(|r| *r = self.previous.take());
+    }
+}
+
+/// Returns error if `op` (e.g. `write /etc/hosts`) is attempted
+/// in read-only context
+pub fn check(op: &str) -> Result<(), AliError> {
+    READ_ONLY.with_borrow(|who| {
+        match who {
+            Some(who) => {
+                Err(AliError::AliRsBug(format!(
+                    "{who} is read-only, refusing to {op}"
+                )))
+            }
+            None => Ok(()),
+        }
+    })
+}
+
+#[test]
+fn test_read_only() {
+    let dir = std::env::temp_dir().join("ali-rs-test-readonly");
+    let _ = std::fs::remove_dir_all(&dir);
+    let d = dir.to_str().unwrap();
+
+    assert!(check("write foo").is_ok());
+    {
+        let _read_only = enter("@test-print");
+        assert!(check("write foo").is_err());
+        assert!(super::shell::exec("true", &[]).is_err());
+        assert!(super::fs::mkdir_p(d).is_err());
+        assert!(super::fs::write_under(d, "/foo", "foo").is_err());
+    }
+
+    assert!(check("write foo").is_ok());
+    super::fs::write_under(d, "/foo", "foo").unwrap();
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,218 @@
+use std::io::Write;
+use std::process::{
+    Command,
+    Stdio,
+};
+use std::sync::OnceLock;
+#[cfg(feature = "remote")]
+use std::time::Duration;
+
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use serde_json::json;
+
+use super::{
+    json,
+    secrets,
+};
+
+#[cfg(feature = "remote")]
+const HTTP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Destination of report events, declared in manifest key `reports`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum ReportSink {
+    /// Appends events as JSON lines to file on the live system
+    #[serde(rename = "file")]
+    File { path: String },
+
+    /// POSTs each event as JSON to HTTP(S) endpoint
+    #[serde(rename = "http")]
+    Http { url: String },
+
+    /// Runs shell command on the live system for each event,
+    /// with event JSON on its stdin
+    #[serde(rename = "command", alias = "cmd")]
+    Command {
+        #[serde(alias = "cmd")]
+        command: String,
+    },
+}
+
+static SINKS: OnceLock<Vec<ReportSink>> = OnceLock::new();
+
+/// Sets global report sinks. Only the first call has effect.
+pub fn init(sinks: &[ReportSink]) {
+    let _ = SINKS.set(sinks.to_vec());
+}
+
+/// Sends event `event` with fields `data` (a JSON object) to all sinks.
+/// Sink failures are logged, and never fail the installation.
+pub fn emit(event: &str, data: serde_json::Value) {
+    let Some(sinks) = SINKS.get().filter(|sinks| !sinks.is_empty()) else {
+        return;
+    };
+
+    let line = to_line(event, data);
+    for sink in sinks {
+        if let Err(err) = sink.send(&line) {
+            log::warn!("report sink {sink}: {err}");
+        }
+    }
+}
+
+/// Returns paths of all file sinks
+pub fn files() -> Vec<String> {
+    SINKS
+        .get()
+        .into_iter()
+        .flatten()
+        .filter_map(|sink| {
+            match sink {
+                ReportSink::File { path } => Some(path.clone()),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// Builds versioned JSON line `{"event": <event>, <data fields>..}`
+fn to_line(event: &str, data: serde_json::Value) -> String {
+    let mut line = json!({ "event": event });
+    if let (Some(line), serde_json::Value::Object(data)) =
+        (line.as_object_mut(), data)
+    {
+        line.extend(data);
+    }
+    secrets::redact_json(&mut line);
+
+    json::to_string(&line)
+}
+
+impl ReportSink {
+    fn send(&self, line: &str) -> Result<(), String> {
+        match self {
+            Self::File { path } => {
+                if let Some(dir) = std::path::Path::new(path).parent() {
+                    std::fs::create_dir_all(dir)
+                        .map_err(|err| err.to_string())?;
+                }
+
+                let mut file = std::fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .map_err(|err| err.to_string())?;
+
+                writeln!(file, "{line}").map_err(|err| err.to_string())
+            }
+
+            #[cfg(feature = "remote")]
+            Self::Http { url } => {
+                ureq::post(url)
+                    .timeout(HTTP_TIMEOUT)
+                    .set("Content-Type", "application/json")
+                    .send_string(line)
+                    .map(|_| ())
+                    .map_err(|err| err.to_string())
+            }
+
+            #[cfg(not(feature = "remote"))]
+            Self::Http { .. } => {
+                Err("ali-rs built without feature remote".to_string())
+            }
+
+            Self::Command { command } => {
+                let mut child = Command::new("sh")
+                    .args(["-c", command])
+                    .stdin(Stdio::piped())
+                    .spawn()
+                    .map_err(|err| err.to_string())?;
+
+                // Dropping stdin closes the pipe before waiting
+                if let Some(mut stdin) = child.stdin.take() {
+                    writeln!(stdin, "{line}").map_err(|err| err.to_string())?;
+                }
+
+                match child.wait().map_err(|err| err.to_string())? {
+                    status if status.success() => Ok(()),
+                    status => Err(format!("exited with {status}")),
+                }
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for ReportSink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::File { path } => write!(f, "file {path}"),
+            Self::Http { url } => write!(f, "http {url}"),
+            Self::Command { command } => write!(f, "command `{command}`"),
+        }
+    }
+}
+
+#[test]
+fn test_report_sinks() {
+    assert_eq!(
+        r#"{"event":"hook","key":"@quicknet","version":1}"#,
+        to_line("hook", json!({"key": "@quicknet"})),
+    );
+
+    let sinks: Vec<ReportSink> = serde_yaml::from_str(
+        "
+- type: file
+  path: /tmp/report.jsonl
+- type: http
+  url: https://example.com/report
+- type: cmd
+  cmd: logger -t ali-rs
+",
+    )
+    .unwrap();
+
+    assert_eq!(
+        vec![
+            ReportSink::File {
+                path: "/tmp/report.jsonl".into(),
+            },
+            ReportSink::Http {
+                url: "https://example.com/report".into(),
+            },
+            ReportSink::Command {
+                command: "logger -t ali-rs".into(),
+            },
+        ],
+        sinks,
+    );
+
+    let dir = std::env::temp_dir()
+        .join(format!("ali-rs-test-report-sink-{}", std::process::id()));
+    let file = dir.join("file/report.jsonl").to_string_lossy().to_string();
+    let cmd_out = dir.join("cmd.jsonl").to_string_lossy().to_string();
+
+    for sink in [
+        ReportSink::File { path: file.clone() },
+        ReportSink::Command {
+            command: format!("cat >> {cmd_out}"),
+        },
+    ] {
+        sink.send(r#"{"event":"foo"}"#).unwrap();
+        sink.send(r#"{"event":"bar"}"#).unwrap();
+    }
+
+    let expected = "{\"event\":\"foo\"}\n{\"event\":\"bar\"}\n";
+    assert_eq!(expected, std::fs::read_to_string(&file).unwrap());
+    assert_eq!(expected, std::fs::read_to_string(&cmd_out).unwrap());
+
+    let fail = ReportSink::Command {
+        command: "false".into(),
+    };
+    assert!(fail.send("{}").is_err());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,94 @@
+//! Lock against concurrent `ali-rs apply` runs on the same machine,
+//! e.g. by operators attached with `--enable-ssh` while an install runs.
+//! The lock file holds PID of the lock holder, and is removed when
+//! the lock is dropped. Locks of dead processes are taken over.
+
+use std::io::{
+    ErrorKind,
+    Write,
+};
+
+use crate::errors::AliError;
+
+/// Lock held until dropped
+pub struct RunLock {
+    path: String,
+}
+
+/// Acquires run lock at `path`, failing if another live process holds it
+pub fn acquire(path: &str) -> Result<RunLock, AliError> {
+    loop {
+        let created = std::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(path);
+
+        match created {
+            Ok(mut file) => {
+                file.write_all(format!("{}\n", std::process::id()).as_bytes())
+                    .map_err(|err| AliError::FileError(err, path.to_string()))?;
+
+                return Ok(RunLock {
+                    path: path.to_string(),
+                });
+            }
+
+            Err(err) if err.kind() == ErrorKind::AlreadyExists => {
+                if let Some(pid) = holder(path) {
+                    return Err(AliError::Aborted(format!(
+                        "another ali-rs apply (PID {pid}) is running, \
+                         see lock file {path}"
+                    )));
+                }
+
+                log::warn!("taking over stale run lock {path}");
+                std::fs::remove_file(path)
+                    .map_err(|err| AliError::FileError(err, path.to_string()))?;
+            }
+
+            Err(err) => {
+                return Err(AliError::FileError(
+                    err,
+                    format!("failed to create run lock {path}"),
+                ));
+            }
+        }
+    }
+}
+
+/// PID of live holder of lock at `path`, if any
+fn holder(path: &str) -> Option<u32> {
+    let pid: u32 = std::fs::read_to_string(path).ok()?.trim().parse().ok()?;
+
+    std::path::Path::new(&format!("/proc/{pid}"))
+        .exists()
+        .then_some(pid)
+}
+
+impl Drop for RunLock {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+#[test]
+fn test_run_lock() {
+    let path = std::env::temp_dir().join("ali-rs-test-run-lock");
+    let p = path.to_str().unwrap();
+    let _ = std::fs::remove_file(&path);
+
+    let lock = acquire(p).unwrap();
+    assert_eq!(
+        format!("{}\n", std::process::id()),
+        std::fs::read_to_string(&path).unwrap(),
+    );
+    assert!(acquire(p).is_err());
+
+    drop(lock);
+    assert!(!path.exists());
+
+    // Lock of dead process is taken over
+    std::fs::write(&path, format!("{}\n", u32::MAX)).unwrap();
+    let lock = acquire(p).unwrap();
+    drop(lock);
+}
@@ -0,0 +1,380 @@
+//! Secret references `secret://<NAME>` in manifests and hook commands,
+//! resolved at run time from providers in manifest key `secrets`.
+//! Hooks resolve references just before use, so plaintext never lives
+//! in manifests. Resolved values are remembered, and replaced by
+//! [`REDACTED`] in reports, `-print` output, and logs.
+
+use std::collections::HashMap;
+use std::sync::{
+    Mutex,
+    OnceLock,
+};
+
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+use super::shell;
+use crate::errors::AliError;
+
+/// Scheme of secret references
+pub const SCHEME: &str = "secret://";
+
+/// Prefix of environment variables of provider `env`
+pub const ENV_PREFIX: &str = "ALI_RS_SECRET_";
+
+/// Replacement of secrets in output
+pub const REDACTED: &str = "***";
+
+/// Source of secrets
+pub trait Provider: Send + Sync {
+    /// Returns secret `name`, or None if this provider does not have it
+    fn get(&self, name: &str) -> Result<Option<String>, AliError>;
+}
+
+/// Secrets provider, declared in manifest key `secrets`.
+/// Providers are tried in order, and the first one having
+/// the secret wins.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum ProviderConfig {
+    /// Environment variables `<PREFIX><NAME>`, with NAME uppercased and
+    /// non-alphanumeric characters replaced by `_`
+    #[serde(rename = "env")]
+    Env { prefix: Option<String> },
+
+    /// Files `<DIR>/<NAME>`, e.g. `/run/secrets`
+    #[serde(rename = "file", alias = "files")]
+    File { dir: String },
+
+    /// YAML or JSON map of secrets encrypted with sops
+    #[serde(rename = "sops")]
+    Sops { file: String },
+
+    /// YAML or JSON map of secrets encrypted with age
+    #[serde(rename = "age")]
+    Age { file: String, identity: String },
+}
+
+/// Secrets from environment variables
+pub struct Env {
+    pub prefix: String,
+}
+
+/// Secrets from one file per secret
+pub struct Files {
+    pub dir: String,
+}
+
+/// Secrets from encrypted file, decrypted on first use
+pub struct Encrypted {
+    /// Program and arguments printing decrypted file
+    cmd: Vec<String>,
+    secrets: Mutex<Option<HashMap<String, String>>>,
+}
+
+static PROVIDERS: OnceLock<Vec<Box<dyn Provider>>> = OnceLock::new();
+
+/// Secrets resolved so far, longest first
+static RESOLVED: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// Sets global secrets providers. Only the first call has effect.
+/// Without it, secrets come from environment variables with
+/// [`ENV_PREFIX`].
+pub fn init(configs: &[ProviderConfig]) {
+    let providers = configs.iter().map(ProviderConfig::provider).collect();
+    if PROVIDERS.set(providers).is_err() {
+        log::warn!("secrets providers already set");
+    }
+}
+
+fn providers() -> &'static [Box<dyn Provider>] {
+    PROVIDERS.get_or_init(|| {
+        vec![Box::new(Env {
+            prefix: ENV_PREFIX.to_string(),
+        })]
+    })
+}
+
+/// Returns secret name of reference `s`, if `s` is one
+pub fn name(s: &str) -> Option<&str> {
+    s.strip_prefix(SCHEME)
+}
+
+/// Returns whether `name` is a valid secret name
+pub fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "._-".contains(c))
+}
+
+/// Returns secret referenced by `s`, or `s` itself
+/// if it is not a secret reference
+pub fn resolve(s: &str) -> Result<String, AliError> {
+    resolve_with(providers(), s)
+}
+
+fn resolve_with(
+    providers: &[Box<dyn Provider>],
+    s: &str,
+) -> Result<String, AliError> {
+    let Some(name) = name(s) else {
+        return Ok(s.to_string());
+    };
+
+    if !is_valid_name(name) {
+        return Err(AliError::BadManifest(format!("bad secret name {name}")));
+    }
+
+    for provider in providers {
+        if let Some(secret) = provider.get(name)? {
+            register(&secret);
+            return Ok(secret);
+        }
+    }
+
+    Err(AliError::BadManifest(format!(
+        "secret {name} not found in any secrets provider"
+    )))
+}
+
+/// Remembers `secret` read from sources other than secret references,
+/// e.g. password files of hooks, so that it is redacted from output
+pub fn register(secret: &str) {
+    if secret.is_empty() {
+        return;
+    }
+
+    let mut resolved = RESOLVED.lock().unwrap();
+    if !resolved.iter().any(|s| s == secret) {
+        resolved.push(secret.to_string());
+        // Longer secrets first, in case one contains another
+        resolved.sort_by_key(|s| std::cmp::Reverse(s.len()));
+    }
+}
+
+/// Returns `s` with all resolved secrets replaced by [`REDACTED`]
+pub fn redact(s: &str) -> String {
+    let resolved = RESOLVED.lock().unwrap();
+
+    resolved
+        .iter()
+        .fold(s.to_string(), |s, secret| s.replace(secret, REDACTED))
+}
+
+/// Like [`redact`], but for all strings in JSON `value`
+pub fn redact_json(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::String(s) => *s = redact(s),
+        serde_json::Value::Array(values) => {
+            values.iter_mut().for_each(redact_json);
+        }
+        serde_json::Value::Object(map) => {
+            map.values_mut().for_each(redact_json);
+        }
+        _ => {}
+    }
+}
+
+impl ProviderConfig {
+    fn provider(&self) -> Box<dyn Provider> {
+        match self {
+            Self::Env { prefix } => {
+                Box::new(Env {
+                    prefix: prefix.as_deref().unwrap_or(ENV_PREFIX).to_string(),
+                })
+            }
+            Self::File { dir } => Box::new(Files { dir: dir.clone() }),
+            Self::Sops { file } => {
+                Box::new(Encrypted::new(&["sops", "--decrypt", file]))
+            }
+            Self::Age { file, identity } => {
+                Box::new(Encrypted::new(&[
+                    "age",
+                    "--decrypt",
+                    "--identity",
+                    identity,
+                    file,
+                ]))
+            }
+        }
+    }
+
+    /// Returns program needed on the live system, if any
+    pub fn program(&self) -> Option<&'static str> {
+        match self {
+            Self::Env { .. } | Self::File { .. } => None,
+            Self::Sops { .. } => Some("sops"),
+            Self::Age { .. } => Some("age"),
+        }
+    }
+
+    /// Returns files needed on the live system
+    pub fn files(&self) -> Vec<&str> {
+        match self {
+            Self::Env { .. } => vec![],
+            Self::File { dir } => vec![dir],
+            Self::Sops { file } => vec![file],
+            Self::Age { file, identity } => vec![file, identity],
+        }
+    }
+}
+
+impl Provider for Env {
+    fn get(&self, name: &str) -> Result<Option<String>, AliError> {
+        let var: String = name
+            .chars()
+            .map(|c| {
+                match c.is_ascii_alphanumeric() {
+                    true => c.to_ascii_uppercase(),
+                    false => '_',
+                }
+            })
+            .collect();
+
+        Ok(std::env::var(format!("{}{var}", self.prefix)).ok())
+    }
+}
+
+impl Provider for Files {
+    fn get(&self, name: &str) -> Result<Option<String>, AliError> {
+        let path = format!("{}/{name}", self.dir);
+        match std::fs::read_to_string(&path) {
+            Ok(secret) => Ok(Some(secret.trim_end_matches('\n').to_string())),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(err) => {
+                Err(AliError::FileError(
+                    err,
+                    format!("read secret file {path}"),
+                ))
+            }
+        }
+    }
+}
+
+impl Encrypted {
+    fn new(cmd: &[&str]) -> Self {
+        Self {
+            cmd: cmd.iter().map(|s| s.to_string()).collect(),
+            secrets: Mutex::new(None),
+        }
+    }
+
+    fn decrypt(&self) -> Result<HashMap<String, String>, AliError> {
+        let args: Vec<&str> =
+            self.cmd[1..].iter().map(|s| s.as_str()).collect();
+        let output = shell::exec_with_output(&self.cmd[0], &args)?;
+
+        let map: HashMap<String, serde_yaml::Value> =
+            serde_yaml::from_slice(&output).map_err(|err| {
+                AliError::BadManifest(format!(
+                    "decrypted secrets from {} is not a map: {err}",
+                    self.cmd[0],
+                ))
+            })?;
+
+        Ok(map
+            .into_iter()
+            .filter_map(|(name, value)| {
+                match value {
+                    serde_yaml::Value::String(s) => Some((name, s)),
+                    serde_yaml::Value::Number(n) => Some((name, n.to_string())),
+                    serde_yaml::Value::Bool(b) => Some((name, b.to_string())),
+                    _ => None,
+                }
+            })
+            .collect())
+    }
+}
+
+impl Provider for Encrypted {
+    fn get(&self, name: &str) -> Result<Option<String>, AliError> {
+        let mut secrets = self.secrets.lock().unwrap();
+        if secrets.is_none() {
+            *secrets = Some(self.decrypt()?);
+        }
+
+        Ok(secrets.as_ref().and_then(|s| s.get(name).cloned()))
+    }
+}
+
+#[test]
+fn test_resolve_secrets() {
+    use std::sync::Arc;
+
+    use super::mock::MockRunner;
+
+    let dir = std::env::temp_dir().join("ali-rs-test-secrets");
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("wifi-psk"), "from-file\n").unwrap();
+
+    let mock = Arc::new(
+        MockRunner::new()
+            .stdout("sops --decrypt", "wifi-psk: from-sops\npin: 90210377\n"),
+    );
+    let _guard = shell::set_runner(mock.clone());
+
+    std::env::set_var("ALI_RS_TEST_SECRET_ROOT_HASH", "from-env");
+
+    let providers: Vec<Box<dyn Provider>> = [
+        ProviderConfig::Env {
+            prefix: Some("ALI_RS_TEST_SECRET_".to_string()),
+        },
+        ProviderConfig::File {
+            dir: dir.to_string_lossy().to_string(),
+        },
+        ProviderConfig::Sops {
+            file: "secrets.yaml".to_string(),
+        },
+    ]
+    .iter()
+    .map(ProviderConfig::provider)
+    .collect();
+
+    let tests = [
+        ("plain", "plain"),
+        ("secret://root.hash", "from-env"),
+        ("secret://wifi-psk", "from-file"),
+        ("secret://pin", "90210377"),
+    ];
+
+    for (s, expected) in tests {
+        assert_eq!(resolve_with(&providers, s).unwrap(), expected);
+    }
+
+    assert!(resolve_with(&providers, "secret://missing").is_err());
+    assert!(resolve_with(&providers, "secret://../etc/shadow").is_err());
+
+    // Encrypted file is only decrypted once
+    assert_eq!(mock.calls(), vec!["sops --decrypt secrets.yaml"]);
+
+    std::env::remove_var("ALI_RS_TEST_SECRET_ROOT_HASH");
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_redact_secrets() {
+    use serde_json::json;
+
+    register("hunter2-test-redact");
+    register("x-hunter2-test-redact-x");
+    register("");
+
+    assert_eq!(
+        redact("psk=x-hunter2-test-redact-x, pw=hunter2-test-redact"),
+        "psk=***, pw=***",
+    );
+
+    let mut value = json!({
+        "summary": ["@quicknet psk=hunter2-test-redact", 22],
+        "ok": true,
+    });
+    redact_json(&mut value);
+    assert_eq!(value, json!({
+        "summary": ["@quicknet psk=***", 22],
+        "ok": true,
+    }));
+}
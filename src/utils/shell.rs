@@ -1,13 +1,78 @@
+use std::io::Read;
+use std::path::Path;
 use std::process::{
+    Child,
     Command,
+    ExitStatus,
     Stdio,
 };
+use std::sync::atomic::{
+    AtomicBool,
+    AtomicU64,
+    Ordering,
+};
+#[cfg(feature = "e2e")]
+use std::sync::OnceLock;
+use std::sync::{
+    Arc,
+    Mutex,
+};
+use std::thread::JoinHandle;
+use std::time::{
+    Duration,
+    Instant,
+};
 use std::{
     env,
     fs,
 };
 
+use nix::sys::signal::{
+    self,
+    SaFlags,
+    SigAction,
+    SigHandler,
+    SigSet,
+    Signal,
+};
+
+use super::fs::path_under;
+use super::progress::{
+    self,
+    fmt_duration,
+};
+use super::watchdog;
 use crate::errors::AliError;
+use crate::linux::user;
+
+/// Timeout for read-only probes like lsblk and blkid
+pub const PROBE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Time given to commands to exit after SIGINT or SIGTERM,
+/// before they are killed with SIGKILL
+const GRACE: Duration = Duration::from_secs(5);
+
+/// Longest interval between polls of running commands
+const POLL_MAX: Duration = Duration::from_millis(100);
+
+static LAST_COMMAND: Mutex<Option<String>> = Mutex::new(None);
+
+/// Backend replacing all commands, see [`set_global_runner`]
+#[cfg(feature = "e2e")]
+static GLOBAL_RUNNER: OnceLock<Arc<dyn CommandRunner>> = OnceLock::new();
+
+#[cfg(test)]
+thread_local! {
+    /// Backend replacing commands of current test thread
+    static TEST_RUNNER: std::cell::RefCell<Option<Arc<dyn CommandRunner>>> =
+        const { std::cell::RefCell::new(None) };
+}
+
+/// Default timeout of commands in seconds, 0 for none
+static TIMEOUT_SECS: AtomicU64 = AtomicU64::new(0);
+
+/// Set on SIGINT, see [`handle_sigint`]
+static CANCELLED: AtomicBool = AtomicBool::new(false);
 
 pub enum CmdError {
     /// Command spawned, but returned non-0 exit code
@@ -21,57 +86,240 @@ pub enum CmdError {
     ErrSpawn { error: std::io::Error },
 }
 
+/// Backend running commands in place of spawning processes,
+/// e.g. [`MockRunner`](super::mock::MockRunner)
+pub trait CommandRunner: Send + Sync {
+    /// Runs command, like [`exec`]
+    fn exec(&self, cmd: &str, args: &[&str]) -> Result<(), AliError>;
+
+    /// Runs command and returns its stdout, like [`exec_with_output`]
+    fn exec_with_output(
+        &self,
+        cmd: &str,
+        args: &[&str],
+    ) -> Result<Vec<u8>, AliError>;
+}
+
+/// Runs all commands with `runner` instead of spawning them.
+/// Can only be set once.
+#[cfg(feature = "e2e")]
+pub fn set_global_runner(runner: Arc<dyn CommandRunner>) {
+    if GLOBAL_RUNNER.set(runner).is_err() {
+        log::warn!("command runner already set");
+    }
+}
+
+/// Runs commands of current test thread with `runner`,
+/// until the returned guard is dropped
+#[cfg(test)]
+pub fn set_runner(runner: Arc<dyn CommandRunner>) -> RunnerGuard {
+    let previous = TEST_RUNNER.with(|r| r.replace(Some(runner)));
+
+    RunnerGuard { previous }
+}
+
+#[cfg(test)]
+pub struct RunnerGuard {
+    previous: Option<Arc<dyn CommandRunner>>,
+}
+
+#[cfg(test)]
+impl Drop for RunnerGuard {
+    fn drop(&mut self) {
+        TEST_RUNNER.with(|r| r.replace(self.previous.take()));
+    }
+}
+
+/// Backend replacing commands, if any
+fn runner() -> Option<Arc<dyn CommandRunner>> {
+    #[cfg(test)]
+    if let Some(runner) = TEST_RUNNER.with(|r| r.borrow().clone()) {
+        return Some(runner);
+    }
+
+    global_runner()
+}
+
+#[cfg(feature = "e2e")]
+fn global_runner() -> Option<Arc<dyn CommandRunner>> {
+    GLOBAL_RUNNER.get().cloned()
+}
+
+/// Commands are never mocked outside of tests and feature `e2e`
+#[cfg(not(feature = "e2e"))]
+fn global_runner() -> Option<Arc<dyn CommandRunner>> {
+    None
+}
+
+/// Sets default timeout of commands in seconds, 0 for no timeout
+pub fn set_timeout(secs: u64) {
+    TIMEOUT_SECS.store(secs, Ordering::Relaxed);
+}
+
+fn default_timeout() -> Option<Duration> {
+    match TIMEOUT_SECS.load(Ordering::Relaxed) {
+        0 => None,
+        secs => Some(Duration::from_secs(secs)),
+    }
+}
+
+/// Whether commands were cancelled with SIGINT. If so, in-flight commands
+/// are interrupted, and later commands fail without being spawned.
+pub fn is_cancelled() -> bool {
+    CANCELLED.load(Ordering::SeqCst)
+}
+
+/// Handles SIGINT by cancelling commands, so that ali-rs can fail
+/// the current stage and clean up. A second SIGINT exits immediately.
+pub fn handle_sigint() {
+    extern "C" fn on_sigint(_: nix::libc::c_int) {
+        if CANCELLED.swap(true, Ordering::SeqCst) {
+            // Only async-signal-safe calls are allowed here
+            unsafe { nix::libc::_exit(130) };
+        }
+    }
+
+    let action = SigAction::new(
+        SigHandler::Handler(on_sigint),
+        SaFlags::SA_RESTART,
+        SigSet::empty(),
+    );
+
+    if let Err(err) = unsafe { signal::sigaction(Signal::SIGINT, &action) } {
+        log::warn!("failed to install SIGINT handler: {err}");
+    }
+}
+
 /// Executes command `cmd` with arguments `args`.
 /// Output is discarded (printed to console) and not used.
 /// Throw an error if `cmd` fails to spawn or exit code != 0
 pub fn exec(cmd: &str, args: &[&str]) -> Result<(), AliError> {
-    match Command::new(cmd).args(args).spawn() {
-        Ok(mut result) => {
-            match result.wait() {
-                // Spawned but may still fail
-                Ok(r) => {
-                    match r.code() {
-                        Some(0) => Ok(()),
-                        Some(code) => {
-                            Err(AliError::CmdFailed {
-                                error: CmdError::ErrRun {
-                                    code: Some(code),
-                                    stdout: None,
-                                    stderr: None,
-                                },
-                                context: format!(
-                            "command {cmd} exited with non-zero status {code}"
-                        ),
-                            })
-                        }
-                        None => {
-                            Err(AliError::CmdFailed {
-                                error: CmdError::ErrRun {
-                                    code: None,
-                                    stdout: None,
-                                    stderr: None,
-                                },
-                                context: format!(
-                                    "command {cmd} terminated by signal"
-                                ),
-                            })
-                        }
-                    }
-                }
-                Err(error) => {
-                    Err(AliError::CmdFailed {
-                        error: CmdError::ErrSpawn { error },
-                        context: format!("command ${cmd} failed to run"),
-                    })
-                }
-            }
+    log::debug!("exec: {cmd} {}", args.join(" "));
+    record(format!("{cmd} {}", args.join(" ")));
+    check_allowed(cmd)?;
+
+    if let Some(runner) = runner() {
+        return runner.exec(cmd, args);
+    }
+
+    let mut child = Command::new(cmd).args(args).spawn().map_err(|error| {
+        AliError::CmdFailed {
+            error: CmdError::ErrSpawn { error },
+            context: format!("command {cmd} failed to spawn"),
         }
+    })?;
 
-        // Failed to spawn
-        Err(error) => {
-            Err(AliError::CmdFailed {
+    let status = wait(&mut child, cmd, default_timeout())?;
+
+    exit_result(cmd, status)
+}
+
+/// Executes command `cmd` with arguments `args` as `user` of the system
+/// at `root`, from its home directory. The user and its supplementary
+/// groups are looked up in `root`, and privileges are dropped with
+/// setpriv(1) of that system. Unlike [`exec`], the root environment
+/// is not inherited: the command only sees HOME, USER, LOGNAME, SHELL
+/// and PATH of `user`, and `envs`.
+///
+/// Executes:
+/// ```shell
+/// arch-chroot <root> setpriv --reuid=<UID> --regid=<GID> --groups=<GIDS> \
+///     -- env -C <HOME> <cmd> <args..>
+/// ```
+#[allow(unused)]
+pub fn exec_as(
+    root: &str,
+    user: &str,
+    envs: &[(&str, &str)],
+    cmd: &str,
+    args: &[&str],
+) -> Result<(), AliError> {
+    let account = user::lookup(root, user)?;
+    let groups: Vec<String> = user::groups(root, &account)?
+        .iter()
+        .map(u32::to_string)
+        .collect();
+
+    // e.g. nobody has home /nonexistent
+    let home = match path_under(root, &account.home) {
+        Ok(home) if Path::new(&home).is_dir() => account.home.as_str(),
+        _ => "/",
+    };
+
+    let shell = match account.shell.is_empty() {
+        true => "/bin/sh",
+        false => account.shell.as_str(),
+    };
+
+    let (reuid, regid, groups) = (
+        format!("--reuid={}", account.uid),
+        format!("--regid={}", account.gid),
+        format!("--groups={}", groups.join(",")),
+    );
+
+    let mut argv = match root {
+        "/" => vec!["setpriv"],
+        _ => vec!["arch-chroot", root, "setpriv"],
+    };
+    argv.extend([&reuid, &regid, &groups, "--", "env", "-C", home, cmd]);
+    argv.extend(args);
+
+    let (program, argv) = (argv[0], &argv[1..]);
+    log::debug!("exec as {user}: {program} {}", argv.join(" "));
+    record(format!("{program} {}", argv.join(" ")));
+    check_allowed(program)?;
+
+    if let Some(runner) = runner() {
+        return runner.exec(program, argv);
+    }
+
+    let mut child = Command::new(program)
+        .args(argv)
+        .env_clear()
+        .envs([
+            ("HOME", account.home.as_str()),
+            ("USER", user),
+            ("LOGNAME", user),
+            ("SHELL", shell),
+            ("PATH", DEFAULT_PATH),
+        ])
+        .envs(envs.iter().copied())
+        .spawn()
+        .map_err(|error| {
+            AliError::CmdFailed {
                 error: CmdError::ErrSpawn { error },
-                context: format!("command {cmd} failed to spawn"),
+                context: format!("command {cmd} failed to spawn as {user}"),
+            }
+        })?;
+
+    let status = wait(&mut child, cmd, default_timeout())?;
+
+    exit_result(cmd, status)
+}
+
+fn exit_result(cmd: &str, status: ExitStatus) -> Result<(), AliError> {
+    match status.code() {
+        Some(0) => Ok(()),
+        Some(code) => {
+            Err(AliError::CmdFailed {
+                error: CmdError::ErrRun {
+                    code: Some(code),
+                    stdout: None,
+                    stderr: None,
+                },
+                context: format!(
+                    "command {cmd} exited with non-zero status {code}"
+                ),
+            })
+        }
+        None => {
+            Err(AliError::CmdFailed {
+                error: CmdError::ErrRun {
+                    code: None,
+                    stdout: None,
+                    stderr: None,
+                },
+                context: format!("command {cmd} terminated by signal"),
             })
         }
     }
@@ -82,24 +330,78 @@ pub fn exec(cmd: &str, args: &[&str]) -> Result<(), AliError> {
 /// or stderr output as lossy UTF-8 strings.
 ///
 /// Throws an error if command fails to spawn
-#[allow(unused)]
 pub fn exec_with_output(cmd: &str, args: &[&str]) -> Result<Vec<u8>, AliError> {
-    let output = Command::new(cmd).args(args).output().map_err(|err| {
-        AliError::CmdFailed {
-            error: CmdError::ErrSpawn { error: err },
-            context: format!("command {cmd} failed to spawn"),
-        }
-    })?;
+    output(cmd, args, &[], default_timeout())
+}
+
+/// Like [`exec_with_output`], but with `timeout` instead of
+/// the default timeout
+pub fn exec_with_output_timeout(
+    cmd: &str,
+    args: &[&str],
+    timeout: Option<Duration>,
+) -> Result<Vec<u8>, AliError> {
+    output(cmd, args, &[], timeout)
+}
+
+/// Like [`exec_with_output`], but with extra environment `envs`
+pub fn exec_with_output_envs(
+    cmd: &str,
+    args: &[&str],
+    envs: &[(&str, &str)],
+) -> Result<Vec<u8>, AliError> {
+    output(cmd, args, envs, default_timeout())
+}
 
-    if !output.status.success() {
-        let stdout = Some(output.stdout);
-        let stderr = Some(output.stderr);
+fn output(
+    cmd: &str,
+    args: &[&str],
+    envs: &[(&str, &str)],
+    timeout: Option<Duration>,
+) -> Result<Vec<u8>, AliError> {
+    log::debug!("exec: {cmd} {}", args.join(" "));
+    record(format!("{cmd} {}", args.join(" ")));
+    check_allowed(cmd)?;
+
+    if let Some(runner) = runner() {
+        return runner.exec_with_output(cmd, args);
+    }
+
+    let mut child = Command::new(cmd)
+        .args(args)
+        .envs(envs.iter().copied())
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| {
+            AliError::CmdFailed {
+                error: CmdError::ErrSpawn { error: err },
+                context: format!("command {cmd} failed to spawn"),
+            }
+        })?;
+
+    // Drain pipes while waiting, so that child never blocks on full pipes
+    let stdout = read_all(child.stdout.take());
+    let stderr = read_all(child.stderr.take());
+
+    // On errors, readers are left detached instead of joined,
+    // in case orphaned grandchildren still hold the pipes
+    let status = wait(&mut child, cmd, timeout)?;
+    let stdout = stdout.join().unwrap_or_default();
+    let stderr = stderr.join().unwrap_or_default();
+
+    if !status.success() {
+        log::debug!(
+            "{cmd} exited with {status}: {}",
+            String::from_utf8_lossy(&stderr).trim()
+        );
 
         return Err(AliError::CmdFailed {
             error: CmdError::ErrRun {
-                code: output.status.code(),
-                stdout,
-                stderr,
+                code: status.code(),
+                stdout: Some(stdout),
+                stderr: Some(stderr),
             },
             context: format!(
                 "command {cmd} {} exited with non-zero status",
@@ -108,7 +410,54 @@ pub fn exec_with_output(cmd: &str, args: &[&str]) -> Result<Vec<u8>, AliError> {
         });
     }
 
-    Ok(output.stdout)
+    Ok(stdout)
+}
+
+/// Executes command `cmd` with arguments `args`, streaming its stdout
+/// and stderr line by line to the log file and progress output while
+/// it runs. Output is also captured: stdout is returned, and both
+/// are kept in errors.
+pub fn exec_stream(cmd: &str, args: &[&str]) -> Result<Vec<u8>, AliError> {
+    log::debug!("exec: {cmd} {}", args.join(" "));
+    record(format!("{cmd} {}", args.join(" ")));
+    check_allowed(cmd)?;
+
+    if let Some(runner) = runner() {
+        return runner.exec_with_output(cmd, args);
+    }
+
+    let mut child = Command::new(cmd)
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|error| {
+            AliError::CmdFailed {
+                error: CmdError::ErrSpawn { error },
+                context: format!("command {cmd} failed to spawn"),
+            }
+        })?;
+
+    let stdout = stream_lines(child.stdout.take(), cmd, "stdout");
+    let stderr = stream_lines(child.stderr.take(), cmd, "stderr");
+
+    let status = wait(&mut child, cmd, default_timeout())?;
+    let stdout = stdout.join().unwrap_or_default();
+    let stderr = stderr.join().unwrap_or_default();
+
+    if !status.success() {
+        return Err(AliError::CmdFailed {
+            error: CmdError::ErrRun {
+                code: status.code(),
+                stdout: Some(stdout),
+                stderr: Some(stderr),
+            },
+            context: format!("command {cmd} exited with {status}"),
+        });
+    }
+
+    Ok(stdout)
 }
 
 /// Pipe stdout of `producer_cmd` to stdin of `consumer_cmd`,
@@ -122,7 +471,23 @@ pub fn pipe(
     producer_cmd: (&str, &[&str]),
     consumer_cmd: (&str, &[&str]),
 ) -> Result<(), AliError> {
-    let producer = Command::new(producer_cmd.0)
+    let cmd = format!(
+        "{} {} | {} {}",
+        producer_cmd.0,
+        producer_cmd.1.join(" "),
+        consumer_cmd.0,
+        consumer_cmd.1.join(" ")
+    );
+    log::debug!("exec: {cmd}");
+    record(cmd);
+    check_allowed(consumer_cmd.0)?;
+
+    if let Some(runner) = runner() {
+        runner.exec_with_output(producer_cmd.0, producer_cmd.1)?;
+        return runner.exec(consumer_cmd.0, consumer_cmd.1);
+    }
+
+    let mut producer = Command::new(producer_cmd.0)
         .args(producer_cmd.1)
         .stdout(Stdio::piped())
         .spawn()
@@ -135,9 +500,9 @@ pub fn pipe(
         });
 
     // Ignore fdisk stderr - it will be inherited from ali-rs
-    let consumer = Command::new(consumer_cmd.0)
+    let mut consumer = Command::new(consumer_cmd.0)
         .args(consumer_cmd.1)
-        .stdin(producer.stdout.unwrap())
+        .stdin(producer.stdout.take().unwrap())
         .spawn()
         .unwrap_or_else(|_| {
             panic!(
@@ -147,45 +512,186 @@ pub fn pipe(
             )
         });
 
-    match consumer.wait_with_output() {
-        Ok(result) => {
-            match result.status.success() {
-                false => {
-                    Err(AliError::CmdFailed {
-                        error: CmdError::ErrRun {
-                            code: result.status.code(),
-                            stdout: None,
-                            stderr: Some(result.stderr),
-                        },
-                        context: format!(
-                            "consumer {} command exited with bad status: {}",
-                            consumer_cmd.0,
-                            result
-                                .status
-                                .code()
-                                .expect("failed to get exit code"),
-                        ),
-                    })
-                }
-                _ => Ok(()),
+    let result = wait(&mut consumer, consumer_cmd.0, default_timeout());
+    if result.is_err() {
+        watchdog::kill_tree(producer.id(), Signal::SIGKILL);
+    }
+
+    // Reap producer once consumer is done reading from it
+    let _ = producer.wait();
+
+    let status = result?;
+    match status.success() {
+        false => {
+            Err(AliError::CmdFailed {
+                error: CmdError::ErrRun {
+                    code: status.code(),
+                    stdout: None,
+                    stderr: None,
+                },
+                context: format!(
+                    "consumer {} command exited with bad status: {status}",
+                    consumer_cmd.0,
+                ),
+            })
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Waits for `child` running `cmd`. If `timeout` elapses or commands
+/// are cancelled, the process tree of `child` is stopped, and an error
+/// is returned.
+fn wait(
+    child: &mut Child,
+    cmd: &str,
+    timeout: Option<Duration>,
+) -> Result<ExitStatus, AliError> {
+    let _watch = watchdog::watch(child.id(), cmd);
+    let start = Instant::now();
+
+    // Poll often at first, so that short commands return quickly
+    let mut poll = Duration::from_millis(1);
+    loop {
+        match child.try_wait() {
+            Ok(Some(status)) => return Ok(status),
+            Ok(None) => {}
+            Err(error) => {
+                return Err(AliError::CmdFailed {
+                    error: CmdError::ErrSpawn { error },
+                    context: format!("command {cmd} failed to run"),
+                });
             }
         }
-        Err(error) => {
-            Err(AliError::CmdFailed {
+
+        if is_cancelled() {
+            stop(child, Signal::SIGINT);
+
+            return Err(AliError::Aborted(format!(
+                "command {cmd} interrupted"
+            )));
+        }
+
+        if let Some(timeout) = timeout.filter(|t| start.elapsed() >= *t) {
+            stop(child, Signal::SIGTERM);
+
+            return Err(AliError::CmdFailed {
                 error: CmdError::ErrRun {
                     code: None,
                     stdout: None,
                     stderr: None,
                 },
                 context: format!(
-                    "consumer {} command failed to run: {error}",
-                    consumer_cmd.0
+                    "command {cmd} timed out after {}",
+                    fmt_duration(timeout)
                 ),
-            })
+            });
         }
+
+        std::thread::sleep(poll);
+        poll = (poll * 2).min(POLL_MAX);
     }
 }
 
+/// Sends `sig` to process tree of `child`, and kills the tree
+/// if `child` is still running after [`GRACE`]
+fn stop(child: &mut Child, sig: Signal) {
+    log::warn!("sending {sig} to pid {}", child.id());
+    watchdog::kill_tree(child.id(), sig);
+
+    let start = Instant::now();
+    while start.elapsed() < GRACE {
+        if let Ok(Some(_)) = child.try_wait() {
+            return;
+        }
+
+        std::thread::sleep(POLL_MAX);
+    }
+
+    log::warn!("killing pid {} after {}", child.id(), fmt_duration(GRACE));
+    watchdog::kill_tree(child.id(), Signal::SIGKILL);
+    let _ = child.wait();
+}
+
+/// Returns error if `cmd` may not run, i.e. if cancelled
+/// or in [read-only context](super::readonly)
+fn check_allowed(cmd: &str) -> Result<(), AliError> {
+    super::readonly::check(&format!("run {cmd}"))?;
+
+    match is_cancelled() {
+        true => {
+            Err(AliError::Aborted(format!(
+                "command {cmd} not run: cancelled"
+            )))
+        }
+        false => Ok(()),
+    }
+}
+
+/// Reads `pipe` of command `cmd` to the end in a new thread,
+/// passing each line to [`progress::output`] as it arrives
+fn stream_lines<R: Read + Send + 'static>(
+    pipe: Option<R>,
+    cmd: &str,
+    stream: &'static str,
+) -> JoinHandle<Vec<u8>> {
+    let cmd = cmd.to_string();
+    std::thread::spawn(move || {
+        let mut captured = Vec::new();
+        let Some(mut pipe) = pipe else {
+            return captured;
+        };
+
+        let mut buf = [0u8; 4096];
+        let mut start = 0;
+        while let Ok(n @ 1..) = pipe.read(&mut buf) {
+            captured.extend_from_slice(&buf[..n]);
+
+            // Progress bars redraw lines with carriage returns
+            for line in complete_lines(&captured, &mut start) {
+                progress::output(&cmd, stream, &line);
+            }
+        }
+
+        let rest = String::from_utf8_lossy(&captured[start..]);
+        if !rest.trim().is_empty() {
+            progress::output(&cmd, stream, &rest);
+        }
+
+        captured
+    })
+}
+
+/// Non-empty lines in `buf` ending after `start`, terminated by newlines
+/// or carriage returns. `start` is advanced past the last terminator.
+fn complete_lines(buf: &[u8], start: &mut usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    while let Some(i) =
+        buf[*start..].iter().position(|b| matches!(b, b'\n' | b'\r'))
+    {
+        let line = String::from_utf8_lossy(&buf[*start..*start + i]);
+        if !line.trim().is_empty() {
+            lines.push(line.to_string());
+        }
+
+        *start += i + 1;
+    }
+
+    lines
+}
+
+/// Reads `pipe` to the end in a new thread
+fn read_all<R: Read + Send + 'static>(pipe: Option<R>) -> JoinHandle<Vec<u8>> {
+    std::thread::spawn(move || {
+        let mut buf = Vec::new();
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut buf);
+        }
+
+        buf
+    })
+}
+
 // Executes cmd_str with `sh -c`:
 /// ```shell
 /// sh -c {cmd_str}
@@ -205,17 +711,45 @@ pub fn arch_chroot(location: &str, cmd: &str) -> Result<(), AliError> {
     sh_c(&format!("arch-chroot {location} {cmd}"))
 }
 
+/// Records `cmd` as the last command run, for crash files
+fn record(cmd: String) {
+    if let Ok(mut last) = LAST_COMMAND.lock() {
+        *last = Some(cmd.trim_end().to_string());
+    }
+}
+
+/// Last command run by ali-rs, if any. Never blocks, so that it is safe
+/// to call from panic hooks.
+pub fn last_command() -> Option<String> {
+    LAST_COMMAND.try_lock().ok()?.clone()
+}
+
+/// Search path used when PATH is unset, e.g. in a bare busybox initramfs
+const DEFAULT_PATH: &str = "/usr/local/sbin:/usr/local/bin:/usr/sbin:/usr/bin:/sbin:/bin";
+
+/// Sets PATH to [`DEFAULT_PATH`] if unset, so that child processes
+/// find programs in sbin directories even with a minimal environment
+pub fn ensure_path() {
+    if env::var_os("PATH").is_none() {
+        env::set_var("PATH", DEFAULT_PATH);
+    }
+}
+
 pub fn in_path(program: &str) -> bool {
-    if let Ok(path) = env::var("PATH") {
-        for p in path.split(':') {
-            let p_str = format!("{}/{}", p, program);
-            if fs::metadata(p_str).is_ok() {
-                return true;
-            }
+    which(program).is_some()
+}
+
+/// Returns absolute path of `program` found in PATH
+pub fn which(program: &str) -> Option<String> {
+    let path = env::var("PATH").unwrap_or(DEFAULT_PATH.to_string());
+    for p in path.split(':').filter(|p| !p.is_empty()) {
+        let p_str = format!("{}/{}", p, program);
+        if fs::metadata(&p_str).is_ok() {
+            return Some(p_str);
         }
     }
 
-    false
+    None
 }
 
 impl std::fmt::Debug for CmdError {
@@ -260,6 +794,93 @@ impl std::fmt::Display for CmdError {
     }
 }
 
+#[test]
+fn test_timeout() {
+    let start = Instant::now();
+    let result = exec_with_output_timeout(
+        "sh",
+        &["-c", "sleep 30"],
+        Some(Duration::from_millis(200)),
+    );
+
+    match result {
+        Err(AliError::CmdFailed { context, .. }) => {
+            assert!(context.contains("timed out"), "{context}");
+        }
+        result => panic!("unexpected result {result:?}"),
+    }
+
+    // sh and sleep both got SIGTERM, so neither waits out the grace period
+    assert!(start.elapsed() < GRACE);
+
+    let timeout = Some(Duration::from_secs(10));
+    let output = exec_with_output_timeout("echo", &["foo"], timeout).unwrap();
+    assert_eq!(b"foo\n".to_vec(), output);
+}
+
+#[test]
+fn test_exec_stream() {
+    let output = exec_stream("sh", &["-c", "echo foo; echo bar >&2"]).unwrap();
+    assert_eq!(b"foo\n".to_vec(), output);
+
+    match exec_stream("sh", &["-c", "echo bar >&2; exit 3"]) {
+        Err(AliError::CmdFailed {
+            error: CmdError::ErrRun { code, stderr, .. },
+            ..
+        }) => {
+            assert_eq!(Some(3), code);
+            assert_eq!(Some(b"bar\n".to_vec()), stderr);
+        }
+        result => panic!("unexpected result {result:?}"),
+    }
+
+    let mut start = 0;
+    let buf = b"\nfoo\r10%\r20%\nbar";
+    assert_eq!(vec!["foo", "10%", "20%"], complete_lines(buf, &mut start));
+    assert_eq!(b"bar", &buf[start..]);
+}
+
+#[test]
+fn test_exec_as() {
+    use super::mock::MockRunner;
+
+    let root = env::temp_dir().join("ali-rs-test-exec-as");
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(root.join("etc")).unwrap();
+    fs::create_dir_all(root.join("home/builder")).unwrap();
+    fs::write(
+        root.join("etc/passwd"),
+        "builder:x:1000:1000::/home/builder:/bin/bash
+\
+        nobody:x:65534:65534::/nonexistent:/usr/bin/nologin
+",
+    )
+    .unwrap();
+    fs::write(root.join("etc/group"), "wheel:x:998:builder
+").unwrap();
+
+    let mock = Arc::new(MockRunner::new());
+    let _guard = set_runner(mock.clone());
+
+    let root = root.to_str().unwrap();
+    exec_as(root, "builder", &[("FOO", "bar")], "makepkg", &["-s"]).unwrap();
+    exec_as(root, "nobody", &[], "true", &[]).unwrap();
+    assert!(exec_as(root, "ali-rs-no-such-user", &[], "true", &[]).is_err());
+
+    let calls = mock.calls();
+    assert_eq!(
+        format!(
+            "arch-chroot {root} setpriv --reuid=1000 --regid=1000 \
+            --groups=1000,998 -- env -C /home/builder makepkg -s"
+        ),
+        calls[0],
+    );
+    assert!(calls[1].ends_with("--groups=65534 -- env -C / true"));
+    assert_eq!(2, calls.len());
+
+    fs::remove_dir_all(root).unwrap();
+}
+
 #[ignore]
 #[test]
 fn test_shell_fns() {
@@ -0,0 +1,343 @@
+//! Editor for shell-style config files, e.g. `/etc/mkinitcpio.conf`
+//! or `/etc/default/grub`, with assignments `NAME=(..)` and `NAME="..."`.
+//! Only assignments are edited, other lines and comments are preserved
+//! in their original order.
+
+use std::ops::Range;
+
+use super::fs::write_under;
+use crate::errors::AliError;
+
+/// Characters that must be quoted in shell words
+const SPECIAL_CHARS: &[char] = &[
+    '"', '\'', '\\', '$', '`', '(', ')', '#', ';', '&', '|', '<', '>',
+];
+
+/// Right-hand side of shell assignment
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    /// `NAME=(a b c)`
+    Array(Vec<String>),
+    /// `NAME="abc"`, `NAME='abc'` or `NAME=abc`
+    String(String),
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ShellConf {
+    lines: Vec<String>,
+}
+
+impl ShellConf {
+    pub fn parse(s: &str) -> Self {
+        Self {
+            lines: s.lines().map(|l| l.to_string()).collect(),
+        }
+    }
+
+    pub fn read(path: &str) -> Result<Self, AliError> {
+        std::fs::read_to_string(path)
+            .map(|s| Self::parse(&s))
+            .map_err(|err| {
+                AliError::FileError(err, format!("failed to read file {path}"))
+            })
+    }
+
+    /// Writes config to `path` under `base`, creating parent directories
+    pub fn write_under(&self, base: &str, path: &str) -> Result<(), AliError> {
+        write_under(base, path, &self.to_string())
+    }
+
+    /// Value of `name`. If assigned more than once, the last assignment
+    /// wins, like in shell.
+    pub fn get(&self, name: &str) -> Option<Value> {
+        let range = self.assignments(name).pop()?;
+        let assignment = self.lines[range].join("\n");
+        let (_, rhs) = assignment.trim_start().split_once('=')?;
+
+        Some(parse_value(rhs))
+    }
+
+    /// Replaces the first assignment of `name` with `value`,
+    /// removing other assignments. If `name` is not assigned,
+    /// the assignment is appended.
+    pub fn set(&mut self, name: &str, value: Value) {
+        let line = assignment(name, &value);
+        let mut ranges = self.assignments(name);
+
+        let Some(first) = ranges.first().cloned() else {
+            self.lines.push(line);
+            return;
+        };
+
+        // Remove from the back, so that earlier ranges stay valid
+        while let Some(range) = ranges.pop() {
+            self.lines.drain(range);
+        }
+
+        self.lines.insert(first.start, line);
+    }
+
+    pub fn set_array(&mut self, name: &str, elems: &[String]) {
+        self.set(name, Value::Array(elems.to_vec()));
+    }
+
+    pub fn set_string(&mut self, name: &str, s: &str) {
+        self.set(name, Value::String(s.to_string()));
+    }
+
+    /// Line ranges of active assignments of `name`.
+    /// Arrays and quoted strings spanning multiple lines are one range.
+    fn assignments(&self, name: &str) -> Vec<Range<usize>> {
+        let prefix = format!("{name}=");
+        let mut ranges = Vec::new();
+
+        let mut i = 0;
+        while i < self.lines.len() {
+            let Some(rhs) = self.lines[i].trim_start().strip_prefix(&prefix)
+            else {
+                i += 1;
+                continue;
+            };
+
+            let closing = match rhs.chars().next() {
+                Some('(') if !rhs.contains(')') => Some(')'),
+                Some(q @ ('"' | '\'')) if rhs.matches(q).count() == 1 => {
+                    Some(q)
+                }
+                _ => None,
+            };
+
+            let start = i;
+            if let Some(closing) = closing {
+                while i + 1 < self.lines.len() {
+                    i += 1;
+                    if self.lines[i].contains(closing) {
+                        break;
+                    }
+                }
+            }
+
+            i += 1;
+            ranges.push(start..i);
+        }
+
+        ranges
+    }
+}
+
+impl std::fmt::Display for ShellConf {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for line in &self.lines {
+            writeln!(f, "{line}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Formats shell assignment, e.g. `HOOKS=(base udev)`
+pub fn assignment(name: &str, value: &Value) -> String {
+    match value {
+        Value::Array(elems) => {
+            let elems: Vec<String> =
+                elems.iter().map(|elem| quote_elem(elem)).collect();
+
+            format!("{name}=({})", elems.join(" "))
+        }
+        Value::String(s) => format!("{name}={}", quote(s)),
+    }
+}
+
+fn parse_value(rhs: &str) -> Value {
+    if let Some(inner) = rhs.strip_prefix('(') {
+        let inner = inner.rsplit_once(')').map_or(inner, |(inner, _)| inner);
+
+        // Handles quoting, and comments following elements
+        // in multi-line arrays
+        let elems = shlex::split(inner).unwrap_or_else(|| {
+            inner.split_whitespace().map(|elem| elem.to_string()).collect()
+        });
+
+        return Value::Array(elems);
+    }
+
+    match rhs.chars().next() {
+        Some(q @ ('"' | '\'')) => {
+            let inner = &rhs[1..];
+            let inner = &inner[..find_closing(inner, q).unwrap_or(inner.len())];
+
+            match q {
+                '"' => Value::String(unescape(inner)),
+                _ => Value::String(inner.to_string()),
+            }
+        }
+        _ => {
+            Value::String(
+                rhs.split_whitespace().next().unwrap_or_default().to_string(),
+            )
+        }
+    }
+}
+
+/// Byte index of first unescaped quote `q` in `s`
+fn find_closing(s: &str, q: char) -> Option<usize> {
+    let mut escaped = false;
+    for (i, c) in s.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if q == '"' => escaped = true,
+            c if c == q => return Some(i),
+            _ => {}
+        }
+    }
+
+    None
+}
+
+/// Double-quotes `s`, escaping characters special inside double quotes
+fn quote(s: &str) -> String {
+    let mut quoted = String::from('"');
+    for c in s.chars() {
+        if matches!(c, '"' | '\\' | '$' | '`') {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push('"');
+
+    quoted
+}
+
+/// Quotes array element only if needed
+fn quote_elem(elem: &str) -> String {
+    let special = |c: char| c.is_whitespace() || SPECIAL_CHARS.contains(&c);
+
+    match elem.is_empty() || elem.contains(special) {
+        true => quote(elem),
+        false => elem.to_string(),
+    }
+}
+
+/// Removes backslashes escaping characters in double-quoted `s`
+fn unescape(s: &str) -> String {
+    let mut unescaped = String::new();
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => unescaped.extend(chars.next()),
+            c => unescaped.push(c),
+        }
+    }
+
+    unescaped
+}
+
+#[test]
+fn test_shellconf() {
+    let conf = r#"# vim:set ft=sh
+MODULES=()
+
+# BINARIES
+BINARIES=()
+
+# HOOKS
+HOOKS=(base udev autodetect microcode modconf kms keyboard keymap consolefont block filesystems fsck)
+#HOOKS=(base udev)
+"#;
+
+    let mut conf = ShellConf::parse(conf);
+    assert_eq!(Some(Value::Array(vec![])), conf.get("MODULES"));
+    assert_eq!(None, conf.get("FILES"));
+
+    conf.set(
+        "HOOKS",
+        Value::Array(vec!["base".into(), "udev".into(), "lvm2".into()]),
+    );
+    conf.set_array("BINARIES", &["btrfs".to_string()]);
+    assert_eq!(
+        conf.to_string(),
+        r#"# vim:set ft=sh
+MODULES=()
+
+# BINARIES
+BINARIES=(btrfs)
+
+# HOOKS
+HOOKS=(base udev lvm2)
+#HOOKS=(base udev)
+"#,
+    );
+
+    let mut multi_line = ShellConf::parse(
+        "HOOKS=(\n  base # comment\n  udev\n)\nCOMPRESSION=\"zstd\"\n",
+    );
+    assert_eq!(
+        Some(Value::Array(vec!["base".into(), "udev".into()])),
+        multi_line.get("HOOKS"),
+    );
+    assert_eq!(
+        Some(Value::String("zstd".into())),
+        multi_line.get("COMPRESSION"),
+    );
+
+    multi_line.set_array("HOOKS", &["base".to_string()]);
+    assert_eq!(
+        multi_line.to_string(),
+        "HOOKS=(base)\nCOMPRESSION=\"zstd\"\n",
+    );
+
+    let mut conf = ShellConf::parse("MODULES=()\n");
+    conf.set_array("BINARIES", &["btrfs".to_string()]);
+    assert_eq!("MODULES=()\nBINARIES=(btrfs)\n", conf.to_string());
+}
+
+#[test]
+fn test_shellconf_string() {
+    let default_grub = r#"GRUB_DEFAULT=0
+GRUB_CMDLINE_LINUX_DEFAULT="loglevel=3 quiet"
+GRUB_CMDLINE_LINUX=""
+GRUB_DISABLE_SUBMENU=y
+GRUB_THEME='/boot/grub/themes/foo bar/theme.txt'
+GRUB_CMDLINE_LINUX="foo"
+"#;
+
+    let mut conf = ShellConf::parse(default_grub);
+    let tests = [
+        ("GRUB_DEFAULT", "0"),
+        ("GRUB_CMDLINE_LINUX_DEFAULT", "loglevel=3 quiet"),
+        ("GRUB_CMDLINE_LINUX", "foo"),
+        ("GRUB_DISABLE_SUBMENU", "y"),
+        ("GRUB_THEME", "/boot/grub/themes/foo bar/theme.txt"),
+    ];
+
+    for (name, expected) in tests {
+        assert_eq!(
+            Some(Value::String(expected.to_string())),
+            conf.get(name),
+            "{name}",
+        );
+    }
+
+    conf.set_string("GRUB_CMDLINE_LINUX", r#"acpi="off" $foo"#);
+    assert_eq!(
+        Some(Value::String(r#"acpi="off" $foo"#.to_string())),
+        conf.get("GRUB_CMDLINE_LINUX"),
+    );
+    assert_eq!(
+        conf.to_string(),
+        r#"GRUB_DEFAULT=0
+GRUB_CMDLINE_LINUX_DEFAULT="loglevel=3 quiet"
+GRUB_CMDLINE_LINUX="acpi=\"off\" \$foo"
+GRUB_DISABLE_SUBMENU=y
+GRUB_THEME='/boot/grub/themes/foo bar/theme.txt'
+"#,
+    );
+
+    assert_eq!(
+        r#"FILES=(/etc/foo "/etc/foo bar")"#,
+        assignment(
+            "FILES",
+            &Value::Array(vec!["/etc/foo".into(), "/etc/foo bar".into()]),
+        ),
+    );
+}
@@ -0,0 +1,221 @@
+//! Metadata snapshots of directory trees, used to report
+//! which files each hook touched with `--snapshot-hooks`
+
+use std::collections::BTreeMap;
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+
+use serde::Serialize;
+
+use super::checksum;
+
+/// Files larger than this are compared by size and mtime only
+const MAX_HASH_SIZE: u64 = 16 << 20;
+
+/// Mask of file type bits in `st_mode`
+const S_IFMT: u32 = 0o170000;
+
+#[derive(Debug, Clone, PartialEq)]
+struct Entry {
+    size: u64,
+    mode: u32,
+    mtime: (i64, i64),
+    /// SHA-256 of regular files, or target of symlinks
+    content: Option<String>,
+}
+
+/// Metadata of all files under some directories of a root
+#[derive(Debug, Default)]
+pub struct Snapshot {
+    /// Entries by path relative to root, e.g. `/etc/hostname`
+    entries: BTreeMap<String, Entry>,
+}
+
+/// Files changed between two snapshots
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct Diff {
+    pub created: Vec<String>,
+    /// Content, size, or type changed
+    pub modified: Vec<String>,
+    /// Only mtime or mode changed
+    pub touched: Vec<String>,
+    pub deleted: Vec<String>,
+}
+
+impl Snapshot {
+    /// Snapshots files under `dirs` (e.g. `/etc`) of `root`.
+    /// Unreadable files and missing directories are skipped.
+    pub fn take(root: &str, dirs: &[String]) -> Self {
+        let root = root.trim_end_matches('/');
+        let mut snapshot = Self::default();
+        for dir in dirs {
+            snapshot.walk(root, &format!("{root}{dir}"));
+        }
+
+        snapshot
+    }
+
+    fn walk(&mut self, root: &str, path: &str) {
+        let Ok(meta) = std::fs::symlink_metadata(path) else {
+            return;
+        };
+
+        let content = match meta.file_type() {
+            t if t.is_dir() => {
+                let Ok(children) = std::fs::read_dir(path) else {
+                    return;
+                };
+
+                for child in children.flatten() {
+                    self.walk(root, &child.path().to_string_lossy());
+                }
+
+                None
+            }
+            t if t.is_symlink() => {
+                std::fs::read_link(path)
+                    .ok()
+                    .map(|target| target.to_string_lossy().to_string())
+            }
+            _ if meta.len() > MAX_HASH_SIZE => None,
+            _ => std::fs::read(path).ok().map(|b| checksum::sha256_hex(&b)),
+        };
+
+        // Directory sizes and mtimes change with their entries,
+        // which are already reported on their own
+        let (size, mtime) = match meta.is_dir() {
+            true => (0, (0, 0)),
+            false => (meta.len(), (meta.mtime(), meta.mtime_nsec())),
+        };
+
+        let relative = path.strip_prefix(root).unwrap_or(path);
+        self.entries.insert(relative.to_string(), Entry {
+            size,
+            mode: meta.mode(),
+            mtime,
+            content,
+        });
+    }
+
+    /// Returns files changed from `self` to `after`
+    pub fn diff(&self, after: &Snapshot) -> Diff {
+        let mut diff = Diff::default();
+
+        for (path, entry) in &after.entries {
+            let Some(before) = self.entries.get(path) else {
+                diff.created.push(path.clone());
+                continue;
+            };
+
+            let file_type = |e: &Entry| e.mode & S_IFMT;
+            if before.content != entry.content
+                || before.size != entry.size
+                || file_type(before) != file_type(entry)
+            {
+                diff.modified.push(path.clone());
+            } else if before != entry {
+                diff.touched.push(path.clone());
+            }
+        }
+
+        for path in self.entries.keys() {
+            if !after.entries.contains_key(path) {
+                diff.deleted.push(path.clone());
+            }
+        }
+
+        diff
+    }
+}
+
+impl Diff {
+    pub fn is_empty(&self) -> bool {
+        self.created.is_empty()
+            && self.modified.is_empty()
+            && self.touched.is_empty()
+            && self.deleted.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.created.len()
+            + self.modified.len()
+            + self.touched.len()
+            + self.deleted.len()
+    }
+}
+
+/// One line per file, prefixed with `+` (created), `~` (modified),
+/// `*` (touched), or `-` (deleted)
+impl std::fmt::Display for Diff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let lines = [
+            ('+', &self.created),
+            ('~', &self.modified),
+            ('*', &self.touched),
+            ('-', &self.deleted),
+        ];
+
+        let mut first = true;
+        for (prefix, paths) in lines {
+            for path in paths {
+                if !first {
+                    writeln!(f)?;
+                }
+                first = false;
+
+                write!(f, "{prefix} {path}")?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns whether `dir` is acceptable for [`Snapshot::take`]
+pub fn is_valid_dir(dir: &str) -> bool {
+    Path::new(dir).is_absolute() && !dir.split('/').any(|c| c == "..")
+}
+
+#[test]
+fn test_snapshot_diff() {
+    use std::fs::Permissions;
+    use std::os::unix::fs::PermissionsExt;
+
+    let root = std::env::temp_dir().join("ali-rs-test-snapshot");
+    let _ = std::fs::remove_dir_all(&root);
+    let etc = root.join("etc");
+    std::fs::create_dir_all(etc.join("conf.d")).unwrap();
+    std::fs::write(etc.join("hostname"), "foo\n").unwrap();
+    std::fs::write(etc.join("conf.d/a.conf"), "a=1\n").unwrap();
+    std::fs::write(etc.join("old.conf"), "old\n").unwrap();
+    std::fs::write(etc.join("mode.conf"), "mode\n").unwrap();
+    std::fs::write(root.join("outside"), "ignored\n").unwrap();
+
+    let root_str = root.to_str().unwrap();
+    let dirs = vec!["/etc".to_string()];
+    let before = Snapshot::take(root_str, &dirs);
+    assert!(before.diff(&Snapshot::take(root_str, &dirs)).is_empty());
+
+    std::fs::write(etc.join("hostname"), "bar\n").unwrap();
+    std::fs::write(etc.join("conf.d/b.conf"), "b=1\n").unwrap();
+    std::fs::remove_file(etc.join("old.conf")).unwrap();
+    std::fs::set_permissions(
+        etc.join("mode.conf"),
+        Permissions::from_mode(0o600),
+    )
+    .unwrap();
+    std::fs::write(root.join("outside"), "still ignored\n").unwrap();
+
+    let diff = before.diff(&Snapshot::take(root_str, &dirs));
+    assert_eq!(diff.created, vec!["/etc/conf.d/b.conf"]);
+    assert_eq!(diff.modified, vec!["/etc/hostname"]);
+    assert_eq!(diff.touched, vec!["/etc/mode.conf"]);
+    assert_eq!(diff.deleted, vec!["/etc/old.conf"]);
+    assert!(diff.to_string().starts_with("+ /etc/conf.d/b.conf\n"));
+
+    assert!(is_valid_dir("/etc"));
+    assert!(!is_valid_dir("etc"));
+    assert!(!is_valid_dir("/etc/../root"));
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
@@ -0,0 +1,119 @@
+/// Max number of suggestions returned by [`closest`]
+const MAX_SUGGESTIONS: usize = 3;
+
+/// Levenshtein edit distance between `a` and `b`, in chars
+pub fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+
+    // Distances from prefix of `a` to every prefix of `b`
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diag = row[0];
+        row[0] = i + 1;
+
+        for (j, cb) in b.iter().enumerate() {
+            let substitute = diag + usize::from(ca != *cb);
+            diag = row[j + 1];
+            row[j + 1] = substitute.min(row[j] + 1).min(diag + 1);
+        }
+    }
+
+    row[b.len()]
+}
+
+/// Candidates closest to `input`, if close enough to be a likely typo.
+/// Ties keep the order of `candidates`.
+pub fn closest<'a>(
+    input: &str,
+    candidates: impl IntoIterator<Item = &'a str>,
+) -> Vec<&'a str> {
+    // Allow about one typo per 3 chars
+    let max_distance = (input.chars().count() / 3).max(1);
+
+    let matches: Vec<(usize, &str)> = candidates
+        .into_iter()
+        .map(|candidate| (levenshtein(input, candidate), candidate))
+        .filter(|(distance, _)| *distance <= max_distance)
+        .collect();
+
+    let Some(best) = matches.iter().map(|(distance, _)| *distance).min()
+    else {
+        return Vec::new();
+    };
+
+    matches
+        .into_iter()
+        .filter(|(distance, _)| *distance == best)
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, candidate)| candidate)
+        .collect()
+}
+
+/// Formats suggestions as ` (did you mean <A> or <B>?)`,
+/// or an empty string if there's none
+pub fn did_you_mean(suggestions: &[&str]) -> String {
+    match suggestions {
+        [] => String::new(),
+        [only] => format!(" (did you mean {only}?)"),
+        [init @ .., last] => {
+            format!(" (did you mean {} or {last}?)", init.join(", "))
+        }
+    }
+}
+
+/// Formats suggestions of candidates closest to `input`, like
+/// [`did_you_mean`], for appending to errors about unknown values
+pub fn hint<'a>(
+    input: &str,
+    candidates: impl IntoIterator<Item = &'a str>,
+) -> String {
+    did_you_mean(&closest(input, candidates))
+}
+
+#[test]
+fn test_levenshtein() {
+    let tests = [
+        ("", "", 0),
+        ("abc", "", 3),
+        ("", "abc", 3),
+        ("@uncomment", "@uncomment", 0),
+        ("@uncoment", "@uncomment", 1),
+        ("@quickent", "@quicknet", 2),
+        ("kitten", "sitting", 3),
+    ];
+
+    for (a, b, expected) in tests {
+        assert_eq!(expected, levenshtein(a, b), "{a} -> {b}");
+        assert_eq!(expected, levenshtein(b, a), "{b} -> {a}");
+    }
+}
+
+#[test]
+fn test_closest() {
+    let keys = [
+        "@uncomment",
+        "@uncomment-all",
+        "@uncomment-print",
+        "@quicknet",
+        "@mnt",
+    ];
+
+    assert_eq!(vec!["@uncomment"], closest("@uncoment", keys));
+    assert_eq!(vec!["@quicknet"], closest("@quickent", keys));
+    assert_eq!(vec!["@mnt"], closest("@mt", keys));
+    assert!(closest("@foo", keys).is_empty());
+
+    // Only the closest candidates are suggested
+    assert_eq!(vec!["@uncomment-print"], closest("@uncoment-print", keys));
+    assert_eq!(vec!["@a", "@c"], closest("@b", ["@a", "@bcd", "@c"]));
+
+    assert_eq!("", did_you_mean(&[]));
+    assert_eq!(" (did you mean @mnt?)", did_you_mean(&["@mnt"]));
+    assert_eq!(
+        " (did you mean @a, @b or @c?)",
+        did_you_mean(&["@a", "@b", "@c"])
+    );
+
+    assert_eq!(" (did you mean btrfs?)", hint("btfs", ["ext4", "btrfs"]));
+    assert_eq!("", hint("zfs", ["ext4", "btrfs"]));
+}
@@ -0,0 +1,222 @@
+use std::net::{
+    TcpListener,
+    TcpStream,
+};
+use std::process::{
+    Child,
+    Command,
+    Stdio,
+};
+use std::time::{
+    Duration,
+    Instant,
+};
+
+use crate::errors::AliError;
+
+const PREFIX_SOCKS5: &str = "socks5://";
+const PREFIX_SSH: &str = "ssh://";
+
+/// How long we wait for `ssh -D` to start listening
+const TUNNEL_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Proxy used to reach remote sources, declared in manifest key `proxy`
+/// or with CLI flag `--proxy`
+#[derive(Debug, Clone, PartialEq)]
+pub enum Proxy {
+    /// Existing SOCKS5 proxy, e.g. `socks5://127.0.0.1:1080`
+    Socks5(String),
+
+    /// Ephemeral SOCKS5 proxy via `ssh -D` to destination,
+    /// e.g. `ssh://user@bastion` or `ssh://bastion:2222`
+    Ssh {
+        destination: String,
+        port: Option<u16>,
+    },
+}
+
+/// Running `ssh -D` child process, killed on drop
+pub struct SshTunnel {
+    child: Child,
+    local_port: u16,
+}
+
+impl TryFrom<&str> for Proxy {
+    type Error = AliError;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        if let Some(addr) = s.strip_prefix(PREFIX_SOCKS5) {
+            if addr.is_empty() {
+                return Err(AliError::BadManifest(format!(
+                    "proxy {s}: missing address"
+                )));
+            }
+
+            return Ok(Self::Socks5(s.to_string()));
+        }
+
+        if let Some(dest) = s.strip_prefix(PREFIX_SSH) {
+            // Split optional port, e.g. user@bastion:2222
+            let (destination, port) = match dest.rsplit_once(':') {
+                Some((host, port)) => {
+                    let port = port.parse::<u16>().map_err(|err| {
+                        AliError::BadManifest(format!(
+                            "proxy {s}: bad ssh port {port}: {err}"
+                        ))
+                    })?;
+
+                    (host, Some(port))
+                }
+                None => (dest, None),
+            };
+
+            if destination.is_empty() || destination.ends_with('@') {
+                return Err(AliError::BadManifest(format!(
+                    "proxy {s}: missing ssh host"
+                )));
+            }
+
+            return Ok(Self::Ssh {
+                destination: destination.to_string(),
+                port,
+            });
+        }
+
+        Err(AliError::BadManifest(format!(
+            "proxy {s}: expecting prefix {PREFIX_SOCKS5} or {PREFIX_SSH}"
+        )))
+    }
+}
+
+impl Proxy {
+    /// Opens the proxy, returning the SOCKS5 URL to use and
+    /// the SSH tunnel guard (if any), which must be kept alive
+    /// for as long as the proxy is in use
+    pub fn open(&self) -> Result<(String, Option<SshTunnel>), AliError> {
+        match self {
+            Self::Socks5(url) => Ok((url.clone(), None)),
+            Self::Ssh { destination, port } => {
+                let tunnel = SshTunnel::open(destination, *port)?;
+                Ok((tunnel.socks5_url(), Some(tunnel)))
+            }
+        }
+    }
+}
+
+impl SshTunnel {
+    /// Spawns `ssh -N -D <LOCAL_PORT> <DESTINATION>` and waits
+    /// until the local SOCKS5 port accepts connections
+    pub fn open(
+        destination: &str,
+        port: Option<u16>,
+    ) -> Result<Self, AliError> {
+        let local_port = free_port()?;
+
+        let mut cmd = Command::new("ssh");
+        cmd.args([
+            "-N",
+            "-o",
+            "ExitOnForwardFailure=yes",
+            "-D",
+            &format!("127.0.0.1:{local_port}"),
+        ]);
+
+        if let Some(port) = port {
+            cmd.args(["-p", &port.to_string()]);
+        }
+
+        let child = cmd.arg(destination).stdin(Stdio::null()).spawn().map_err(
+            |err| AliError::FileError(err, "failed to spawn ssh".to_string()),
+        )?;
+
+        let mut tunnel = Self { child, local_port };
+        tunnel.wait_ready(destination)?;
+
+        Ok(tunnel)
+    }
+
+    pub fn socks5_url(&self) -> String {
+        format!("{PREFIX_SOCKS5}127.0.0.1:{}", self.local_port)
+    }
+
+    fn wait_ready(&mut self, destination: &str) -> Result<(), AliError> {
+        let start = Instant::now();
+
+        while start.elapsed() < TUNNEL_TIMEOUT {
+            if let Ok(Some(status)) = self.child.try_wait() {
+                return Err(AliError::BadArgs(format!(
+                    "ssh tunnel to {destination} exited early: {status}"
+                )));
+            }
+
+            if TcpStream::connect(("127.0.0.1", self.local_port)).is_ok() {
+                return Ok(());
+            }
+
+            std::thread::sleep(Duration::from_millis(200));
+        }
+
+        Err(AliError::BadArgs(format!(
+            "ssh tunnel to {destination} not ready after {}s",
+            TUNNEL_TIMEOUT.as_secs()
+        )))
+    }
+}
+
+impl Drop for SshTunnel {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// Asks the kernel for an unused local TCP port
+fn free_port() -> Result<u16, AliError> {
+    TcpListener::bind(("127.0.0.1", 0))
+        .and_then(|listener| listener.local_addr())
+        .map(|addr| addr.port())
+        .map_err(|err| {
+            AliError::FileError(err, "failed to find free port".to_string())
+        })
+}
+
+#[test]
+fn test_parse_proxy() {
+    let should_pass = [
+        (
+            "socks5://127.0.0.1:1080",
+            Proxy::Socks5("socks5://127.0.0.1:1080".into()),
+        ),
+        (
+            "ssh://bastion",
+            Proxy::Ssh {
+                destination: "bastion".into(),
+                port: None,
+            },
+        ),
+        (
+            "ssh://admin@bastion:2222",
+            Proxy::Ssh {
+                destination: "admin@bastion".into(),
+                port: Some(2222),
+            },
+        ),
+    ];
+
+    for (s, expected) in should_pass {
+        assert_eq!(expected, Proxy::try_from(s).unwrap());
+    }
+
+    let should_err = [
+        "127.0.0.1:1080",
+        "http://proxy:3128",
+        "socks5://",
+        "ssh://",
+        "ssh://admin@",
+        "ssh://bastion:port",
+    ];
+
+    for s in should_err {
+        assert!(Proxy::try_from(s).is_err(), "expecting error for {s}");
+    }
+}
@@ -0,0 +1,269 @@
+//! Watchdog for hung external commands. Unlike a timeout, it never
+//! flags slow commands that are still making progress: a command is
+//! idle only if its whole process tree did no I/O and used no CPU time.
+
+use std::io::{
+    IsTerminal,
+    Write,
+};
+use std::sync::atomic::{
+    AtomicBool,
+    AtomicU64,
+    Ordering,
+};
+use std::sync::mpsc;
+use std::thread::JoinHandle;
+use std::time::{
+    Duration,
+    Instant,
+};
+
+use nix::sys::signal::{
+    self,
+    Signal,
+};
+use nix::unistd::Pid;
+
+use super::progress::fmt_duration;
+
+/// Default idle period before warning
+pub const DEFAULT_IDLE_SECS: u64 = 600;
+
+/// Interval of progress polling
+const POLL: Duration = Duration::from_secs(5);
+
+static IDLE_SECS: AtomicU64 = AtomicU64::new(DEFAULT_IDLE_SECS);
+static PROMPT: AtomicBool = AtomicBool::new(false);
+
+/// Sets idle period in seconds (0 disables the watchdog), and whether
+/// to ask interactive users to kill idle commands
+pub fn init(idle_secs: u64, prompt: bool) {
+    IDLE_SECS.store(idle_secs, Ordering::Relaxed);
+    PROMPT.store(prompt, Ordering::Relaxed);
+}
+
+/// Watches process `pid` running `cmd`, returning a guard
+/// that stops watching when dropped
+pub fn watch(pid: u32, cmd: &str) -> Watch {
+    let idle = match IDLE_SECS.load(Ordering::Relaxed) {
+        0 => return Watch::default(),
+        secs => Duration::from_secs(secs),
+    };
+
+    let cmd = cmd.to_string();
+    let (done, rx) = mpsc::channel::<()>();
+    let watcher = std::thread::spawn(move || {
+        let mut last = progress(pid);
+        let mut last_change = Instant::now();
+
+        while let Err(mpsc::RecvTimeoutError::Timeout) =
+            rx.recv_timeout(POLL.min(idle))
+        {
+            let current = progress(pid);
+            if current != last {
+                last = current;
+                last_change = Instant::now();
+                continue;
+            }
+
+            if last_change.elapsed() < idle {
+                continue;
+            }
+
+            log::warn!(
+                "watchdog: command `{cmd}` made no progress for {}\n{}",
+                fmt_duration(last_change.elapsed()),
+                status(pid),
+            );
+
+            if should_kill(pid) {
+                return;
+            }
+
+            // Warn again only after another idle period
+            last_change = Instant::now();
+        }
+    });
+
+    Watch {
+        done: Some(done),
+        watcher: Some(watcher),
+    }
+}
+
+#[derive(Default)]
+pub struct Watch {
+    done: Option<mpsc::Sender<()>>,
+    watcher: Option<JoinHandle<()>>,
+}
+
+impl Drop for Watch {
+    fn drop(&mut self) {
+        // Dropping sender disconnects the watch loop
+        self.done.take();
+        if let Some(watcher) = self.watcher.take() {
+            let _ = watcher.join();
+        }
+    }
+}
+
+/// Asks interactive user whether to kill process tree of `pid`,
+/// killing it if so
+fn should_kill(pid: u32) -> bool {
+    if !PROMPT.load(Ordering::Relaxed) || !std::io::stdin().is_terminal() {
+        return false;
+    }
+
+    eprint!("watchdog: [k]ill command, or [c]ontinue waiting? ");
+    std::io::stderr().flush().ok();
+
+    let mut answer = String::new();
+    if std::io::stdin().read_line(&mut answer).is_err() {
+        return false;
+    }
+
+    if !matches!(answer.trim(), "k" | "kill") {
+        return false;
+    }
+
+    kill_tree(pid, Signal::SIGKILL);
+
+    true
+}
+
+/// Sends `sig` to process `pid` and all its descendants
+pub fn kill_tree(pid: u32, sig: Signal) {
+    // Signal children first, so that they are not re-parented and left behind
+    for p in tree(pid).into_iter().rev() {
+        if let Err(err) = signal::kill(Pid::from_raw(p as i32), sig) {
+            log::debug!("failed to send {sig} to pid {p}: {err}");
+        }
+    }
+}
+
+/// Progress counters of process tree of `pid`:
+/// bytes read and written, and CPU ticks used
+fn progress(pid: u32) -> (u64, u64) {
+    tree(pid)
+        .into_iter()
+        .map(|p| (io_bytes(p), cpu_ticks(p)))
+        .fold((0, 0), |(io, cpu), (p_io, p_cpu)| (io + p_io, cpu + p_cpu))
+}
+
+/// `pid` and all its descendants, parents before children
+fn tree(pid: u32) -> Vec<u32> {
+    let parents: Vec<(u32, u32)> = std::fs::read_dir("/proc")
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|entry| entry.file_name().to_str()?.parse::<u32>().ok())
+        .filter_map(|p| Some((p, stat_fields(p)?.get(1)?.parse().ok()?)))
+        .collect();
+
+    let mut tree = vec![pid];
+    let mut i = 0;
+    while i < tree.len() {
+        let parent = tree[i];
+        tree.extend(
+            parents
+                .iter()
+                .filter(|(_, ppid)| *ppid == parent)
+                .map(|(p, _)| *p),
+        );
+        i += 1;
+    }
+
+    tree
+}
+
+/// Fields of /proc/<pid>/stat after command name, starting with state
+fn stat_fields(pid: u32) -> Option<Vec<String>> {
+    let stat = std::fs::read_to_string(format!("/proc/{pid}/stat")).ok()?;
+
+    // Command name may contain spaces and parentheses
+    let (_, fields) = stat.rsplit_once(')')?;
+
+    Some(fields.split_whitespace().map(|f| f.to_string()).collect())
+}
+
+/// User and system CPU ticks (fields 14 and 15 of /proc/<pid>/stat)
+fn cpu_ticks(pid: u32) -> u64 {
+    let Some(fields) = stat_fields(pid) else {
+        return 0;
+    };
+
+    [11, 12]
+        .iter()
+        .filter_map(|&i| fields.get(i)?.parse::<u64>().ok())
+        .sum()
+}
+
+/// Bytes read and written, including terminal and pipe I/O
+fn io_bytes(pid: u32) -> u64 {
+    std::fs::read_to_string(format!("/proc/{pid}/io"))
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|l| {
+            let (key, value) = l.split_once(':')?;
+            match key {
+                "rchar" | "wchar" => value.trim().parse::<u64>().ok(),
+                _ => None,
+            }
+        })
+        .sum()
+}
+
+/// Name, state, and kernel wait channel of each process in tree of `pid`
+fn status(pid: u32) -> String {
+    tree(pid)
+        .into_iter()
+        .map(|p| {
+            let read = |f: &str| {
+                std::fs::read_to_string(format!("/proc/{p}/{f}"))
+                    .unwrap_or_default()
+            };
+
+            let status = read("status");
+            let field = |key: &str| {
+                status
+                    .lines()
+                    .find_map(|l| l.strip_prefix(key))
+                    .map(|v| v.trim().to_string())
+                    .unwrap_or_default()
+            };
+
+            format!(
+                "  pid {p} ({}): state {}, wchan {}",
+                field("Name:"),
+                field("State:"),
+                read("wchan"),
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[test]
+fn test_watchdog() {
+    let mut child = std::process::Command::new("sh")
+        .args(["-c", "sleep 5"])
+        .spawn()
+        .unwrap();
+
+    let pid = child.id();
+
+    // sh may exec sleep directly, or fork it
+    let tree = tree(pid);
+    assert_eq!(pid, tree[0]);
+
+    let status = status(pid);
+    assert!(status.contains(&format!("pid {pid} (")), "{status}");
+
+    child.kill().unwrap();
+    child.wait().unwrap();
+
+    let this = std::process::id();
+    let before = progress(this);
+    let _ = std::fs::read_to_string("/proc/self/status");
+    assert_ne!(before, progress(this));
+}